@@ -1,5 +1,6 @@
-from collections.abc import Iterable, Mapping, Sequence
-from typing import ClassVar, Literal, Self, TypeAlias, final
+import os
+from collections.abc import Callable, Iterable, Mapping, Sequence
+from typing import Any, ClassVar, Literal, Self, TextIO, TypeAlias, final
 
 import numpy as np
 import numpy.typing as npt
@@ -18,8 +19,31 @@ class Channel:
         offset: npt.ArrayLike | None = ...,
         iir: npt.ArrayLike | None = ...,
         fir: npt.ArrayLike | None = ...,
+        exp_corrections: Sequence[tuple[float, float]] | None = ...,
+        compression_table: Sequence[tuple[float, float]] | None = ...,
+        compression_poly: Sequence[float] | None = ...,
         filter_offset: bool = ...,
         is_real: bool = ...,
+        padding: Literal["zero", "hold", "ramp"] | Padding | None = ...,
+        ramp_length: int = ...,
+        wrap_phase: bool = ...,
+        compensate_group_delay: bool = ...,
+        phase_from_first_use: bool = ...,
+        blanking: Sequence[tuple[float, float]] | None = ...,
+        overflow: Literal["error", "truncate", "wrap"] | Overflow | None = ...,
+        differential: str | None = ...,
+        skew: float = ...,
+        trigger_latency: float = ...,
+        metadata: dict[str, object] | None = ...,
+    ) -> Self: ...
+    @classmethod
+    def for_awg(
+        cls,
+        model: str,
+        length: int,
+        *,
+        base_freq: float = ...,
+        **kwargs: object,
     ) -> Self: ...
     @property
     def base_freq(self) -> float: ...
@@ -40,9 +64,92 @@ class Channel:
     @property
     def fir(self) -> np.ndarray | None: ...
     @property
+    def exp_corrections(self) -> list[tuple[float, float]]: ...
+    @property
+    def compression_table(self) -> list[tuple[float, float]] | None: ...
+    @property
+    def compression_poly(self) -> list[float] | None: ...
+    @property
     def filter_offset(self) -> bool: ...
     @property
     def is_real(self) -> bool: ...
+    @property
+    def padding(self) -> Padding: ...
+    @property
+    def ramp_length(self) -> int: ...
+    @property
+    def wrap_phase(self) -> bool: ...
+    @property
+    def compensate_group_delay(self) -> bool: ...
+    @property
+    def group_delay(self) -> float | None: ...
+    @property
+    def phase_from_first_use(self) -> bool: ...
+    @property
+    def blanking(self) -> list[tuple[float, float]]: ...
+    @property
+    def overflow(self) -> Overflow: ...
+    @property
+    def differential(self) -> str | None: ...
+    @property
+    def skew(self) -> float: ...
+    @property
+    def trigger_latency(self) -> float: ...
+    @property
+    def metadata(self) -> dict[str, object] | None: ...
+
+@final
+class DigitalChannel:
+    def __new__(
+        cls,
+        sample_rate: float,
+        length: int,
+        *,
+        delay: float = ...,
+        align_level: int = ...,
+    ) -> Self: ...
+    @property
+    def sample_rate(self) -> float: ...
+    @property
+    def length(self) -> int: ...
+    @property
+    def delay(self) -> float: ...
+    @property
+    def align_level(self) -> int: ...
+
+@final
+class AwgPreset:
+    def __new__(
+        cls,
+        sample_rate: float,
+        *,
+        align_level: int = ...,
+        granularity: int = ...,
+    ) -> Self: ...
+    @property
+    def sample_rate(self) -> float: ...
+    @property
+    def align_level(self) -> int: ...
+    @property
+    def granularity(self) -> int: ...
+
+def register_awg(model: str, preset: AwgPreset) -> None: ...
+def awg_presets() -> dict[str, AwgPreset]: ...
+@final
+class Padding:
+    Zero: ClassVar[Padding]
+    Hold: ClassVar[Padding]
+    Ramp: ClassVar[Padding]
+    @staticmethod
+    def convert(obj: Literal["zero", "hold", "ramp"] | Padding) -> Padding: ...
+
+@final
+class Overflow:
+    Error: ClassVar[Overflow]
+    Truncate: ClassVar[Overflow]
+    Wrap: ClassVar[Overflow]
+    @staticmethod
+    def convert(obj: Literal["error", "truncate", "wrap"] | Overflow) -> Overflow: ...
 
 @final
 class Alignment:
@@ -53,12 +160,72 @@ class Alignment:
     @staticmethod
     def convert(obj: Literal["end", "start", "center", "stretch"] | Alignment) -> Alignment: ...
 
-class Shape: ...
+class Shape:
+    def __mul__(self, other: Shape) -> ShapeProduct: ...
+    def __add__(self, other: Shape) -> ShapeSum: ...
 
 @final
 class Hann(Shape):
     def __new__(cls) -> Self: ...
 
+@final
+class Gaussian(Shape):
+    def __new__(cls, truncation: float = ...) -> Self: ...
+    @property
+    def truncation(self) -> float: ...
+
+@final
+class ShapeProduct(Shape):
+    def __new__(cls, a: Shape, b: Shape) -> Self: ...
+    @property
+    def a(self) -> Shape: ...
+    @property
+    def b(self) -> Shape: ...
+
+@final
+class ShapeSum(Shape):
+    def __new__(cls, a: Shape, b: Shape) -> Self: ...
+    @property
+    def a(self) -> Shape: ...
+    @property
+    def b(self) -> Shape: ...
+
+@final
+class CustomShape(Shape):
+    def __new__(
+        cls,
+        function: Callable[[float], float] | Callable[[npt.NDArray[np.float64]], npt.ArrayLike],
+        *,
+        vectorized: bool = ...,
+        cache: bool = ...,
+    ) -> Self: ...
+    @property
+    def function(
+        self,
+    ) -> Callable[[float], float] | Callable[[npt.NDArray[np.float64]], npt.ArrayLike]: ...
+    @property
+    def vectorized(self) -> bool: ...
+    @property
+    def cache(self) -> bool: ...
+
+@final
+class ErfSquare(Shape):
+    def __new__(cls, truncation: float = ...) -> Self: ...
+    @property
+    def truncation(self) -> float: ...
+
+@final
+class CosineSum(Shape):
+    def __new__(cls, a0: float, a1: float, a2: float = ..., a3: float = ...) -> Self: ...
+    @staticmethod
+    def blackman() -> CosineSum: ...
+    @staticmethod
+    def hamming() -> CosineSum: ...
+    @staticmethod
+    def nuttall() -> CosineSum: ...
+    @property
+    def coefficients(self) -> tuple[float, float, float, float]: ...
+
 @final
 class Interp(Shape):
     def __new__(cls, knots: Iterable[float], controls: Iterable[float], degree: float) -> Self: ...
@@ -69,6 +236,38 @@ class Interp(Shape):
     @property
     def degree(self) -> float: ...
 
+@final
+class Spline(Shape):
+    def __new__(cls, x: Iterable[float], y: Iterable[float]) -> Self: ...
+    @property
+    def x(self) -> Sequence[float]: ...
+    @property
+    def y(self) -> Sequence[float]: ...
+
+@final
+class SampledShape(Shape):
+    def __new__(
+        cls,
+        samples: Iterable[float],
+        sample_rate: float,
+        *,
+        interpolation: Literal["linear", "cubic"] = ...,
+    ) -> Self: ...
+    @property
+    def samples(self) -> Sequence[float]: ...
+    @property
+    def sample_rate(self) -> float: ...
+    @property
+    def interpolation(self) -> Literal["linear", "cubic"]: ...
+    @property
+    def duration(self) -> float: ...
+
+@final
+class Param:
+    def __new__(cls, name: str) -> Self: ...
+    @property
+    def name(self) -> str: ...
+
 class Element:
     @property
     def margin(self) -> tuple[float, float]: ...
@@ -77,11 +276,38 @@ class Element:
     @property
     def phantom(self) -> bool: ...
     @property
+    def phantom_ignored(self) -> bool: ...
+    @property
     def duration(self) -> float | None: ...
     @property
+    def duration_of(self) -> tuple[str, float] | None: ...
+    @property
     def max_duration(self) -> float: ...
     @property
     def min_duration(self) -> float: ...
+    @property
+    def label(self) -> str | None: ...
+    @property
+    def params(self) -> dict[str, str]: ...
+    def param_names(self) -> list[str]: ...
+    def arrange(self, time: float = ..., duration: float | None = ...) -> None: ...
+    @property
+    def child_time_ranges(self) -> Sequence[tuple[float, float]] | None: ...
+    def stats(self) -> ElementStats: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(text: str) -> Element: ...
+
+@final
+class ElementStats:
+    @property
+    def node_count(self) -> int: ...
+    @property
+    def depth(self) -> int: ...
+    @property
+    def variant_counts(self) -> dict[str, int]: ...
+    @property
+    def pulse_count(self) -> int: ...
 
 @final
 class Play(Element):
@@ -89,20 +315,28 @@ class Play(Element):
         cls,
         channel_id: str,
         shape_id: str | None,
-        amplitude: float,
-        width: float,
+        amplitude: float | Param,
+        width: float | Param,
         *,
-        plateau: float = ...,
-        drag_coef: float = ...,
-        frequency: float = ...,
-        phase: float = ...,
+        plateau: float | Param = ...,
+        total: float | None = ...,
+        drag_coef: float | Param = ...,
+        frequency: float | Param = ...,
+        chirp_rate: float | Param = ...,
+        phase: float | Param = ...,
         flexible: bool = ...,
+        align_level: int | None = ...,
+        fall_shape_id: str | None = ...,
+        fall_width: float | None = ...,
+        centered: bool = ...,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_id(self) -> str: ...
@@ -119,23 +353,35 @@ class Play(Element):
     @property
     def frequency(self) -> float: ...
     @property
+    def chirp_rate(self) -> float: ...
+    @property
     def phase(self) -> float: ...
     @property
     def flexible(self) -> bool: ...
+    @property
+    def align_level(self) -> int | None: ...
+    @property
+    def fall_shape_id(self) -> str | None: ...
+    @property
+    def fall_width(self) -> float | None: ...
+    @property
+    def centered(self) -> bool: ...
 
 @final
 class ShiftPhase(Element):
     def __new__(
         cls,
         channel_id: str,
-        phase: float,
+        phase: float | Param,
         *,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_id(self) -> str: ...
@@ -147,14 +393,16 @@ class SetPhase(Element):
     def __new__(
         cls,
         channel_id: str,
-        phase: float,
+        phase: float | Param,
         *,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_id(self) -> str: ...
@@ -166,14 +414,16 @@ class ShiftFreq(Element):
     def __new__(
         cls,
         channel_id: str,
-        frequency: float,
+        frequency: float | Param,
         *,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_id(self) -> str: ...
@@ -185,20 +435,43 @@ class SetFreq(Element):
     def __new__(
         cls,
         channel_id: str,
-        frequency: float,
+        frequency: float | Param,
         *,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_id(self) -> str: ...
     @property
     def frequency(self) -> float: ...
 
+@final
+class PhaseRamp(Element):
+    def __new__(
+        cls,
+        channel_id: str,
+        rate: float | Param,
+        *,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Self: ...
+    @property
+    def channel_id(self) -> str: ...
+    @property
+    def rate(self) -> float: ...
+
 @final
 class SwapPhase(Element):
     def __new__(
@@ -208,10 +481,12 @@ class SwapPhase(Element):
         *,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_id1(self) -> str: ...
@@ -225,28 +500,73 @@ class Barrier(Element):
         *channel_ids: str,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def channel_ids(self) -> Sequence[str]: ...
 
+@final
+class Delay(Element):
+    def __new__(
+        cls,
+        delay: float | Param,
+        *channel_ids: str,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Self: ...
+    @property
+    def delay(self) -> float: ...
+    @property
+    def channel_ids(self) -> Sequence[str]: ...
+
+@final
+class Marker(Element):
+    def __new__(
+        cls,
+        channel_id: str,
+        width: float | Param = ...,
+        *,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Self: ...
+    @property
+    def channel_id(self) -> str: ...
+    @property
+    def width(self) -> float: ...
+
 @final
 class Repeat(Element):
     def __new__(
         cls,
         child: Element,
         count: int,
-        spacing: float = ...,
+        spacing: float | Param = ...,
         *,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     @property
     def child(self) -> Element: ...
@@ -255,6 +575,45 @@ class Repeat(Element):
     @property
     def spacing(self) -> float: ...
 
+@final
+class Reverse(Element):
+    def __new__(
+        cls,
+        child: Element,
+        *,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Self: ...
+    @property
+    def child(self) -> Element: ...
+
+@final
+class Scale(Element):
+    def __new__(
+        cls,
+        child: Element,
+        factor: float,
+        *,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Self: ...
+    @property
+    def child(self) -> Element: ...
+    @property
+    def factor(self) -> float: ...
+
 @final
 class Direction:
     Forward: ClassVar[Direction]
@@ -268,17 +627,53 @@ class Stack(Element):
         cls,
         *children: Element,
         direction: Literal["forward", "backward"] | Direction = ...,
+        auto_barrier: bool = ...,
+        spacing: float = ...,
+        align_children: bool = ...,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
+    @classmethod
+    def from_plays(
+        cls,
+        channel_ids: Sequence[str],
+        shape_ids: Sequence[str | None],
+        amplitudes: npt.ArrayLike,
+        widths: npt.ArrayLike,
+        *,
+        plateaus: npt.ArrayLike | None = ...,
+        drag_coefs: npt.ArrayLike | None = ...,
+        frequencies: npt.ArrayLike | None = ...,
+        phases: npt.ArrayLike | None = ...,
+        direction: Literal["forward", "backward"] | Direction = ...,
+        auto_barrier: bool = ...,
+        spacing: float = ...,
+        align_children: bool = ...,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Stack: ...
     def with_children(self, *children: Element) -> Stack: ...
     @property
     def direction(self) -> Direction: ...
     @property
+    def auto_barrier(self) -> bool: ...
+    @property
+    def spacing(self) -> float: ...
+    @property
+    def align_children(self) -> bool: ...
+    @property
     def children(self) -> Sequence[Element]: ...
 
 _AbsoluteEntryLike: TypeAlias = Element | tuple[float, Element] | AbsoluteEntry
@@ -300,20 +695,56 @@ class Absolute(Element):
         *children: _AbsoluteEntryLike,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     def with_children(self, *children: _AbsoluteEntryLike) -> Absolute: ...
+    @staticmethod
+    def from_arrays(
+        times: npt.ArrayLike,
+        elements: Sequence[Element],
+        *,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Absolute: ...
     @property
     def children(self) -> Sequence[AbsoluteEntry]: ...
 
+@final
+class Overlay(Element):
+    def __new__(
+        cls,
+        *children: Element,
+        margin: float | tuple[float, float] | None = ...,
+        alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
+        duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
+        max_duration: float = ...,
+        min_duration: float = ...,
+        label: str | None = ...,
+    ) -> Self: ...
+    def with_children(self, *children: Element) -> Overlay: ...
+    @property
+    def children(self) -> Sequence[Element]: ...
+
 @final
 class GridLengthUnit:
     Seconds: ClassVar[GridLengthUnit]
     Auto: ClassVar[GridLengthUnit]
     Star: ClassVar[GridLengthUnit]
+    @staticmethod
+    def convert(obj: Literal["seconds", "auto", "star"] | GridLengthUnit) -> GridLengthUnit: ...
 
 @final
 class GridLength:
@@ -335,12 +766,23 @@ _GridEntryLike: TypeAlias = Element | tuple[Element, int] | tuple[Element, int,
 
 @final
 class GridEntry:
-    def __new__(cls, element: Element, column: int = ..., span: int = ...) -> Self: ...
+    def __new__(
+        cls,
+        element: Element,
+        column: int = ...,
+        span: int = ...,
+        row: int = ...,
+        row_span: int = ...,
+    ) -> Self: ...
     @property
     def column(self) -> int: ...
     @property
     def span(self) -> int: ...
     @property
+    def row(self) -> int: ...
+    @property
+    def row_span(self) -> int: ...
+    @property
     def element(self) -> Element: ...
     @staticmethod
     def convert(obj: _GridEntryLike) -> GridEntry: ...
@@ -351,12 +793,15 @@ class Grid(Element):
         cls,
         *children: _GridEntryLike,
         columns: Sequence[str | float | GridLength] = ...,
+        rows: Sequence[Sequence[str]] = ...,
         margin: float | tuple[float, float] | None = ...,
         alignment: Literal["end", "start", "center", "stretch"] | Alignment | None = ...,
-        phantom: bool = ...,
+        phantom: bool | Literal["layout", "ignore"] = ...,
         duration: float | None = ...,
+        duration_of: str | tuple[str, float] | None = ...,
         max_duration: float = ...,
         min_duration: float = ...,
+        label: str | None = ...,
     ) -> Self: ...
     def with_children(
         self,
@@ -366,14 +811,546 @@ class Grid(Element):
     def children(self) -> Sequence[GridEntry]: ...
     @property
     def columns(self) -> Sequence[GridLength]: ...
+    @property
+    def rows(self) -> Sequence[Sequence[str]]: ...
+
+class BosingError(RuntimeError):
+    code: str
+    channel: str | None
+    label: str | None
+    time: float | None
+    details: dict[str, object]
+
+class ScheduleError(BosingError): ...
+class SamplingError(BosingError): ...
+class ClippingError(BosingError): ...
+
+def bind(schedule: Element, params: Mapping[str, float]) -> Element: ...
+
+def patch(
+    schedule: Element,
+    patches: Mapping[str, Mapping[str, float]],
+) -> Element: ...
+
+_CrosstalkMatrixLike: TypeAlias = npt.ArrayLike | Sequence[tuple[float, npt.ArrayLike]]
+_CrosstalkLike: TypeAlias = (
+    tuple[_CrosstalkMatrixLike, Sequence[str]]
+    | tuple[_CrosstalkMatrixLike, Sequence[str], npt.ArrayLike]
+)
 
 def generate_waveforms(
     channels: Mapping[str, Channel],
     shapes: Mapping[str, Shape],
-    schedule: Element,
+    schedule: Element | Iterable[_AbsoluteEntryLike],
     *,
     time_tolerance: float = ...,
     amp_tolerance: float = ...,
     allow_oversize: bool = ...,
-    crosstalk: tuple[npt.ArrayLike, Sequence[str]] | None = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+    envelope_precision: Literal["f64", "f32"] = ...,
+    clip_limit: float | None = ...,
+    digital_channels: Mapping[str, DigitalChannel] | None = ...,
 ) -> dict[str, np.ndarray]: ...
+@final
+class Clipping:
+    @property
+    def channel(self) -> str: ...
+    @property
+    def start(self) -> int: ...
+    @property
+    def end(self) -> int: ...
+    @property
+    def peak(self) -> float: ...
+    @property
+    def labels(self) -> list[str]: ...
+
+def generate_waveforms_into(
+    buffers: Mapping[str, np.ndarray],
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+    envelope_precision: Literal["f64", "f32"] = ...,
+) -> None: ...
+def generate_waveform_chunks(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    chunk_size: int,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+    envelope_precision: Literal["f64", "f32"] = ...,
+) -> WaveformChunks: ...
+@final
+class WaveformChunks:
+    def __iter__(self) -> Self: ...
+    def __next__(self) -> tuple[int, dict[str, np.ndarray]]: ...
+
+def generate_sparse_waveforms(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    chunk_size: int = ...,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+    envelope_precision: Literal["f64", "f32"] = ...,
+) -> dict[str, list[tuple[int, np.ndarray]]]: ...
+
+def generate_sweep(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    amp_scales: Mapping[str, npt.ArrayLike] | None = ...,
+    phase_shifts: Mapping[str, npt.ArrayLike] | None = ...,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+    envelope_precision: Literal["f64", "f32"] = ...,
+) -> dict[str, np.ndarray]: ...
+def generate_waveforms_batch(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    param_sets: Sequence[Mapping[str, float]],
+    *,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+    envelope_precision: Literal["f64", "f32"] = ...,
+) -> dict[str, np.ndarray]: ...
+@final
+class Regenerator:
+    def __new__(
+        cls,
+        channels: Mapping[str, Channel],
+        shapes: Mapping[str, Shape],
+        schedule: Element | Iterable[_AbsoluteEntryLike],
+        *,
+        time_tolerance: float = ...,
+        amp_tolerance: float = ...,
+        allow_oversize: bool = ...,
+        optimize: bool | Sequence[str] | None = ...,
+        scales: Mapping[str, float] | None = ...,
+        mute_channels: Sequence[str] | None = ...,
+        aliases: Mapping[str, str] | None = ...,
+        envelope_precision: Literal["f64", "f32"] = ...,
+    ) -> Self: ...
+    def update(
+        self,
+        label: str,
+        *,
+        amp_scale: float | None = ...,
+        phase_shift: float | None = ...,
+    ) -> None: ...
+    def generate(self) -> dict[str, np.ndarray]: ...
+
+def iq_imbalance_sweep(
+    channel: Channel,
+    frequencies: Sequence[float],
+    gains: Sequence[float],
+    phases: Sequence[float],
+    *,
+    amplitude: float = ...,
+    time_tolerance: float = ...,
+) -> tuple[np.ndarray, np.ndarray]: ...
+def compute_amp_scales(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    labels: Sequence[str],
+    *,
+    target: float = ...,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+) -> dict[str, float]: ...
+
+@final
+class PulseStats:
+    @property
+    def pulses(self) -> int: ...
+    @property
+    def envelopes(self) -> list[tuple[str | None, float, float, int]]: ...
+
+def pulse_stats(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+) -> dict[str, PulseStats]: ...
+
+@final
+class ClippedPulse:
+    @property
+    def path(self) -> str: ...
+    @property
+    def label(self) -> str | None: ...
+    @property
+    def channel(self) -> str: ...
+    @property
+    def start(self) -> float: ...
+    @property
+    def end(self) -> float: ...
+    @property
+    def before(self) -> float: ...
+    @property
+    def after(self) -> float: ...
+
+def find_clipped_pulses(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+) -> list[ClippedPulse]: ...
+
+@final
+class Difference:
+    @property
+    def path(self) -> str: ...
+    @property
+    def field(self) -> str: ...
+    @property
+    def old(self) -> Any: ...
+    @property
+    def new(self) -> Any: ...
+
+def diff(a: Element, b: Element) -> list[Difference]: ...
+def explain(
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    channels: Sequence[str] | None = ...,
+    *,
+    file: TextIO | None = ...,
+) -> None: ...
+
+@final
+class ElementTiming:
+    @property
+    def label(self) -> str: ...
+    @property
+    def channel(self) -> str: ...
+    @property
+    def schedule_time(self) -> float: ...
+    @property
+    def hardware_time(self) -> float: ...
+    @property
+    def duration(self) -> float: ...
+
+def element_timings(
+    channels: Mapping[str, Channel],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    labels: Sequence[str] | None = ...,
+) -> list[ElementTiming]: ...
+
+@final
+class FragmentInstance:
+    @property
+    def name(self) -> str: ...
+    @property
+    def version(self) -> int: ...
+    @property
+    def arguments(self) -> dict[str, Any]: ...
+    @property
+    def paths(self) -> list[str]: ...
+
+@final
+class FragmentRegistry:
+    def __new__(cls) -> Self: ...
+    def register(
+        self, name: str, factory: Callable[..., Element], *, version: int = ...
+    ) -> None: ...
+    def instantiate(
+        self,
+        name: str,
+        arguments: Mapping[str, Any] | None = ...,
+        *,
+        version: int | None = ...,
+    ) -> Element: ...
+    def names(self) -> list[str]: ...
+    def versions(self, name: str) -> list[int]: ...
+    def instances(self, schedule: Element) -> list[FragmentInstance]: ...
+    def to_json(self, schedule: Element) -> str: ...
+    @staticmethod
+    def from_json(text: str) -> tuple[Element, list[FragmentInstance]]: ...
+
+@final
+class FrameProgram:
+    @property
+    def envelopes(self) -> list[tuple[str | None, float, float]]: ...
+    @property
+    def envelope_samples(self) -> list[npt.NDArray[np.float64]]: ...
+    @property
+    def events(self) -> list[tuple[object, ...]]: ...
+
+def compile_frames(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+) -> dict[str, FrameProgram]: ...
+
+@final
+class PulseTimeline:
+    @property
+    def envelopes(self) -> list[tuple[str | None, float, float]]: ...
+    @property
+    def envelope_samples(self) -> list[npt.NDArray[np.float64]] | None: ...
+    @property
+    def pulses(self) -> list[tuple[float, int, complex, complex, float, float]]: ...
+
+def compile_timeline(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    envelope_rate: float | None = ...,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+) -> dict[str, PulseTimeline]: ...
+
+@final
+class ScheduledPulse:
+    @property
+    def channel(self) -> str: ...
+    @property
+    def time(self) -> float: ...
+    @property
+    def shape_id(self) -> str | None: ...
+    @property
+    def width(self) -> float: ...
+    @property
+    def fall_shape_id(self) -> str | None: ...
+    @property
+    def fall_width(self) -> float | None: ...
+    @property
+    def plateau(self) -> float: ...
+    @property
+    def amplitude(self) -> float: ...
+    @property
+    def drag_coef(self) -> float: ...
+    @property
+    def freq(self) -> float: ...
+    @property
+    def chirp_rate(self) -> float: ...
+    @property
+    def phase(self) -> float: ...
+
+def generate_pulse_table(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+) -> list[ScheduledPulse]: ...
+
+@final
+class WaveformStats:
+    @property
+    def peak(self) -> float: ...
+    @property
+    def rms(self) -> float: ...
+    @property
+    def papr(self) -> float: ...
+    @property
+    def histogram(self) -> npt.NDArray[np.uint64]: ...
+    @property
+    def bin_edges(self) -> npt.NDArray[np.float64]: ...
+
+def waveform_stats(
+    waveforms: Mapping[str, npt.NDArray[np.float64]],
+    *,
+    bins: int = ...,
+    range: tuple[float, float] | None = ...,
+) -> dict[str, WaveformStats]: ...
+def measure_schedule(
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+) -> tuple[float, dict[str, tuple[float, float]]]: ...
+def integration_weights(
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    label: str,
+    sample_rate: float,
+    *,
+    length: int | None = ...,
+    align_level: int = ...,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+) -> tuple[float, npt.NDArray[np.complex128]]: ...
+def load_experiment(
+    path: str | os.PathLike[str],
+    *,
+    format: Literal["yaml", "toml"] | None = ...,
+) -> tuple[dict[str, Channel], dict[str, Shape], Element]: ...
+@final
+class ResultStore:
+    def __new__(cls, path: str | os.PathLike[str]) -> Self: ...
+    @property
+    def path(self) -> str: ...
+    def save(
+        self,
+        waveforms: Mapping[str, npt.NDArray[np.float64]],
+        *,
+        name: str | None = ...,
+        schedule: Element | None = ...,
+        channels: Mapping[str, Channel] | None = ...,
+        metadata: object = ...,
+    ) -> str: ...
+    def names(self) -> list[str]: ...
+
+@final
+class Server:
+    def __new__(
+        cls,
+        address: str,
+        *,
+        chunk_size: int = ...,
+        time_tolerance: float = ...,
+        amp_tolerance: float = ...,
+        allow_oversize: bool = ...,
+    ) -> Self: ...
+    @property
+    def address(self) -> str: ...
+    def serve(self, max_requests: int | None = ...) -> None: ...
+
+def export_html(
+    path: str | os.PathLike[str],
+    channels: Mapping[str, Channel],
+    shapes: Mapping[str, Shape],
+    schedule: Element | Iterable[_AbsoluteEntryLike],
+    *,
+    title: str | None = ...,
+    max_points: int = ...,
+    time_tolerance: float = ...,
+    amp_tolerance: float = ...,
+    allow_oversize: bool = ...,
+    crosstalk: _CrosstalkLike | None = ...,
+    optimize: bool | Sequence[str] | None = ...,
+    amp_scales: Mapping[str, float] | None = ...,
+    scales: Mapping[str, float] | None = ...,
+    mute_channels: Sequence[str] | None = ...,
+    aliases: Mapping[str, str] | None = ...,
+) -> None: ...
+def set_envelope_cache(
+    path: str | os.PathLike[str] | None,
+    *,
+    max_size: int = ...,
+) -> None: ...
+def clear_envelope_cache() -> None: ...
+def set_layout_check(enabled: bool) -> None: ...
+def set_phase_unit(unit: Literal["cycles", "radians"]) -> None: ...
+def get_phase_unit() -> Literal["cycles", "radians"]: ...
+def set_logging(
+    file: str | os.PathLike[str] | None = ...,
+    *,
+    python: bool = ...,
+    level: Literal["error", "warn", "info", "debug", "trace"] = ...,
+) -> None: ...
+def build_info() -> dict[str, object]: ...
+def envelope_area(
+    shape: Shape | None,
+    width: float,
+    sample_rate: float,
+    *,
+    plateau: float = ...,
+    drag_coef: float = ...,
+) -> complex: ...
+def envelope_fwhm(
+    shape: Shape | None,
+    width: float,
+    sample_rate: float,
+    *,
+    plateau: float = ...,
+) -> float: ...
+def shift_waveform(
+    waveform: npt.ArrayLike,
+    shift: float,
+    *,
+    half_width: int = ...,
+) -> np.ndarray: ...
+def quantize_waveforms(
+    waveforms: Mapping[str, npt.ArrayLike],
+    full_scale: float | Mapping[str, float],
+    *,
+    dither: bool = ...,
+    seed: int = ...,
+    allow_clip: bool = ...,
+) -> dict[str, tuple[np.ndarray, float]]: ...
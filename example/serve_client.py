@@ -0,0 +1,33 @@
+"""Request waveforms from a running ``bosing.Server``.
+
+Start the server first, e.g. ``python -c "import bosing; bosing.Server('127.0.0.1:8000').serve()"``.
+"""
+
+import json
+import urllib.request
+
+import numpy as np
+
+experiment = {
+    "channels": {"xy": {"base_freq": 30e6, "sample_rate": 2e9, "length": 1000}},
+    "shapes": {"hann": {"type": "hann"}},
+    "schedule": {
+        "type": "stack",
+        "duration": 500e-9,
+        "children": [
+            {"type": "play", "channel_id": "xy", "shape_id": "hann", "amplitude": 0.3, "width": 100e-9},
+        ],
+    },
+}
+request = urllib.request.Request(  # noqa: S310
+    "http://127.0.0.1:8000/generate",
+    data=json.dumps(experiment).encode(),
+    method="POST",
+)
+chunks = {}
+with urllib.request.urlopen(request) as response:  # noqa: S310
+    for line in response:
+        chunk = json.loads(line)
+        chunks.setdefault(chunk["channel"], []).append(np.array(chunk["data"]))
+waveforms = {name: np.concatenate(c, axis=1) for name, c in chunks.items()}
+print(waveforms["xy"].shape)
@@ -1,29 +1,164 @@
-use std::iter;
+use std::{
+    iter,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+use itertools::{Either, Itertools};
 use thiserror::Error;
+use tracing::info_span;
 
 use crate::{
     pulse::{Envelope, PulseList, PulseListBuilder, PushArgs},
-    quant::{Amplitude, ChannelId, Frequency, Phase, ShapeId, Time},
+    quant::{Amplitude, ChannelId, ChirpRate, Frequency, Label, Phase, ShapeId, Time},
     schedule::{
-        Arrange as _, Arranged, ElementRef, ElementVariant, Measure, Play, SetFreq, SetPhase,
-        ShiftFreq, ShiftPhase, SwapPhase, TimeRange,
+        measure_parallel, Arrange as _, Arranged, ElementRef, ElementVariant, Grid, GridConflict,
+        Marker, Measure, PhaseRamp, Play, Reverse, Scale, SetFreq, SetPhase, ShiftFreq, ShiftPhase,
+        SwapPhase, TimeRange,
     },
     shape::Shape,
 };
 
 #[derive(Debug, Clone)]
-pub(crate) struct Executor {
+pub struct Executor {
     channels: HashMap<ChannelId, Channel>,
     shapes: HashMap<ShapeId, Shape>,
+    amp_scales: HashMap<Label, f64>,
+    channel_scales: HashMap<ChannelId, f64>,
+    phase_shifts: HashMap<Label, Phase>,
+    muted: HashSet<ChannelId>,
+    solo: Option<Label>,
+    phase_from_first_use: HashSet<ChannelId>,
     amp_tolerance: Amplitude,
     time_tolerance: Time,
     allow_oversize: bool,
+    record_frames: bool,
+    pulse_table: Option<Vec<ScheduledPulse>>,
+    pulse_records: Option<Vec<PulseRecord>>,
+}
+
+/// Frame change or pulse on a channel, see [`Executor::record_frames`].
+///
+/// Phases are in cycles and times are relative to the start of the schedule,
+/// without the channel delay.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameEvent {
+    ShiftPhase {
+        time: Time,
+        phase: Phase,
+    },
+    SetPhase {
+        time: Time,
+        phase: Phase,
+    },
+    ShiftFreq {
+        time: Time,
+        freq: Frequency,
+    },
+    SetFreq {
+        time: Time,
+        freq: Frequency,
+    },
+    /// Phase offset increasing at `rate` from `time` to `time + duration`.
+    PhaseRamp {
+        time: Time,
+        duration: Time,
+        rate: Frequency,
+    },
+    /// Phase swap with the `other` channel.
+    SwapPhase {
+        time: Time,
+        other: ChannelId,
+    },
+    /// Pulse with the envelope at index `envelope` of
+    /// [`FrameProgram::envelopes`]. `freq` and `phase` are relative to the
+    /// frame of the channel, see [`Play::with_chirp_rate`] for `chirp_rate`.
+    Pulse {
+        time: Time,
+        envelope: usize,
+        amplitude: Amplitude,
+        drag_coef: f64,
+        freq: Frequency,
+        chirp_rate: ChirpRate,
+        phase: Phase,
+    },
+}
+
+impl FrameEvent {
+    pub fn time(&self) -> Time {
+        match self {
+            FrameEvent::ShiftPhase { time, .. }
+            | FrameEvent::SetPhase { time, .. }
+            | FrameEvent::ShiftFreq { time, .. }
+            | FrameEvent::SetFreq { time, .. }
+            | FrameEvent::PhaseRamp { time, .. }
+            | FrameEvent::SwapPhase { time, .. }
+            | FrameEvent::Pulse { time, .. } => *time,
+        }
+    }
+}
+
+/// Frame events of a channel with the envelopes referenced by the pulses.
+#[derive(Debug, Clone, Default)]
+pub struct FrameProgram {
+    pub envelopes: Vec<Envelope>,
+    /// Events sorted by time. Events at the same time are in execution order.
+    pub events: Vec<FrameEvent>,
+}
+
+/// Pulse scheduled by a [`Play`] element, see [`Executor::record_pulse_table`].
+///
+/// The parameters are those of the element after layout, with the durations
+/// scaled by the enclosing [`Scale`] elements. The carrier of the pulse is
+/// `exp(2j * pi * (freq * (t - time) + phase))`, i.e. `phase` includes the
+/// frame of the channel at `time`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledPulse {
+    pub channel: ChannelId,
+    /// Start of the pulse relative to the start of the schedule, without the
+    /// channel delay.
+    pub time: Time,
+    pub shape_id: Option<ShapeId>,
+    pub width: Time,
+    pub fall_shape_id: Option<ShapeId>,
+    pub fall_width: Option<Time>,
+    pub plateau: Time,
+    /// Amplitude including the label and channel scales.
+    pub amplitude: Amplitude,
+    pub drag_coef: f64,
+    /// Total frequency of the carrier at the center of the pulse.
+    pub freq: Frequency,
+    pub chirp_rate: ChirpRate,
+    pub phase: Phase,
+}
+
+/// Pulse as pushed to the [`PulseList`] of its channel, see
+/// [`Executor::record_pulses`].
+#[derive(Debug, Clone)]
+pub struct PulseRecord {
+    pub channel: ChannelId,
+    pub label: Option<Label>,
+    pub args: PushArgs,
+}
+
+/// [`Play`] element whose pulse doesn't fit in the output window of its
+/// channel, see [`Executor::validate`].
+#[derive(Debug, Clone)]
+pub struct ClippedPulse {
+    /// Path to the element in the same format as [`Error::LayoutDrift`].
+    pub path: String,
+    pub label: Option<Label>,
+    pub channel: ChannelId,
+    /// Time range of the pulse relative to the start of the schedule.
+    pub time_range: TimeRange,
+    /// Time by which the pulse starts before the window.
+    pub before: Time,
+    /// Time by which the pulse ends after the window.
+    pub after: Time,
 }
 
 #[derive(Error, Debug)]
-pub(crate) enum Error {
+pub enum Error {
     #[error("Channel not found: {0:?}")]
     ChannelNotFound(Vec<ChannelId>),
     #[error("Shape not found: {0:?}")]
@@ -32,16 +167,60 @@ pub(crate) enum Error {
     NegativePlateau(Time),
     #[error("Not enough duration: required {required:?}, available {available:?}")]
     NotEnoughDuration { required: Time, available: Time },
+    #[error("Layout drift at {path}: arranged in [{start:?}, {end:?}] but the container range is [{container_start:?}, {container_end:?}]")]
+    LayoutDrift {
+        path: String,
+        label: Option<Label>,
+        start: Time,
+        end: Time,
+        container_start: Time,
+        container_end: Time,
+    },
+    #[error("Over-constrained grid: child {child} requires {required:?} but its columns provide {available:?}")]
+    OverconstrainedGrid {
+        label: Option<Label>,
+        child: usize,
+        required: Time,
+        available: Time,
+    },
+}
+
+impl Error {
+    /// Machine-readable identifier of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ChannelNotFound(_) => "channel_not_found",
+            Error::ShapeNotFound(_) => "shape_not_found",
+            Error::NegativePlateau(_) => "negative_plateau",
+            Error::NotEnoughDuration { .. } => "not_enough_duration",
+            Error::LayoutDrift { .. } => "layout_drift",
+            Error::OverconstrainedGrid { .. } => "overconstrained_grid",
+        }
+    }
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+static CHECK_LAYOUT: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
 #[derive(Debug, Clone)]
 struct Channel {
     base_freq: Frequency,
     delta_freq: Frequency,
     phase: Phase,
+    phase_offset: Phase,
+    wrap_phase: bool,
+    /// Executed [`PhaseRamp`]s, whose full phase is already added to `phase`.
+    ramps: Vec<Ramp>,
     pulses: PulseListBuilder,
+    frames: FrameProgram,
+    envelope_ids: HashMap<Envelope, usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    time_range: TimeRange,
+    rate: Frequency,
 }
 
 struct AddPulseArgs {
@@ -52,64 +231,275 @@ struct AddPulseArgs {
     amplitude: Amplitude,
     drag_coef: f64,
     freq: Frequency,
+    chirp_rate: ChirpRate,
     phase: Phase,
+    align_level: Option<i32>,
 }
 
 #[derive(Debug)]
-enum IterVariant<S, A, G, R> {
+enum IterVariant<S, A, O, G, R, C, V> {
     Stack(S),
     Absolute(A),
+    Overlay(O),
     Grid(G),
     Repeat(R),
+    Scale(C),
+    Reverse(V),
+}
+
+/// Affine map from the time axis of an element to the time axis of the root,
+/// which is not identity inside [`Scale`] and [`Reverse`] elements. The factor
+/// is negative inside an odd number of [`Reverse`] elements.
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    offset: Time,
+    factor: f64,
 }
 
 impl Executor {
-    pub(crate) fn new(
-        amp_tolerance: Amplitude,
-        time_tolerance: Time,
-        allow_oversize: bool,
-    ) -> Self {
+    pub fn new(amp_tolerance: Amplitude, time_tolerance: Time, allow_oversize: bool) -> Self {
         Self {
             channels: HashMap::new(),
             shapes: HashMap::new(),
+            amp_scales: HashMap::new(),
+            channel_scales: HashMap::new(),
+            phase_shifts: HashMap::new(),
+            muted: HashSet::new(),
+            solo: None,
+            phase_from_first_use: HashSet::new(),
             amp_tolerance,
             time_tolerance,
             allow_oversize,
+            record_frames: false,
+            pulse_table: None,
+            pulse_records: None,
         }
     }
 
-    pub(crate) fn add_channel(&mut self, name: ChannelId, base_freq: Frequency) {
+    /// Add a channel. If `wrap_phase` is set, the accumulated phase of the
+    /// channel is wrapped to `[0, 1)` after each frame change to avoid
+    /// precision loss in long schedules.
+    pub fn add_channel(&mut self, name: ChannelId, base_freq: Frequency, wrap_phase: bool) {
         self.channels.insert(
             name,
-            Channel::new(base_freq, self.amp_tolerance, self.time_tolerance),
+            Channel::new(
+                base_freq,
+                wrap_phase,
+                self.amp_tolerance,
+                self.time_tolerance,
+            ),
         );
     }
 
-    pub(crate) fn add_shape(&mut self, name: ShapeId, shape: Shape) {
+    pub fn add_shape(&mut self, name: ShapeId, shape: Shape) {
         self.shapes.insert(name, shape);
     }
 
-    pub(crate) fn into_result(self) -> HashMap<ChannelId, PulseList> {
+    /// Scale the amplitude of all [`Play`] elements with the given label.
+    pub fn add_amp_scale(&mut self, label: Label, scale: f64) {
+        self.amp_scales.insert(label, scale);
+    }
+
+    /// Scale the amplitude of all [`Play`] elements on the given channel. The
+    /// factor multiplies with the label scale factors.
+    pub fn add_channel_scale(&mut self, name: ChannelId, scale: f64) {
+        self.channel_scales.insert(name, scale);
+    }
+
+    /// Add a phase to all [`Play`] elements with the given label.
+    pub fn add_phase_shift(&mut self, label: Label, phase: Phase) {
+        self.phase_shifts.insert(label, phase);
+    }
+
+    /// Skip all [`Play`] elements on the channel as if they were phantom.
+    pub fn mute_channel(&mut self, name: ChannelId) {
+        self.muted.insert(name);
+    }
+
+    /// Skip all [`Play`] elements without the given label as if they were
+    /// phantom. Frame changes are still applied.
+    pub fn solo_label(&mut self, label: Label) {
+        self.solo = Some(label);
+    }
+
+    /// Accumulate the phase of the base frequency of the channel from the
+    /// start of its first pulse instead of from `t = 0`, like an NCO which is
+    /// reset on trigger.
+    pub fn phase_from_first_use(&mut self, name: ChannelId) {
+        self.phase_from_first_use.insert(name);
+    }
+
+    /// Record the frame changes and pulses as [`FrameEvent`]s for hardware
+    /// which updates the frames natively, instead of baking the frames into
+    /// the pulse phases. The events are returned by
+    /// [`into_frame_programs`](Self::into_frame_programs).
+    pub fn record_frames(&mut self) {
+        self.record_frames = true;
+    }
+
+    /// Record the parameters of every executed pulse before merging, for
+    /// hardware with native pulse queues. The pulses are returned by
+    /// [`into_pulse_table`](Self::into_pulse_table).
+    pub fn record_pulse_table(&mut self) {
+        self.pulse_table.get_or_insert_with(Vec::new);
+    }
+
+    /// Recorded pulses sorted by time. Pulses at the same time are in
+    /// execution order.
+    pub fn into_pulse_table(self) -> Vec<ScheduledPulse> {
+        let mut pulses = self.pulse_table.unwrap_or_default();
+        pulses.sort_by_key(|p| p.time);
+        pulses
+    }
+
+    /// Record the pulses pushed to the pulse lists with the labels of their
+    /// elements, so that the lists can be rebuilt with different amplitudes
+    /// and phases without executing the schedule again. The pulses are
+    /// returned by [`into_pulse_records`](Self::into_pulse_records).
+    pub fn record_pulses(&mut self) {
+        self.pulse_records.get_or_insert_with(Vec::new);
+    }
+
+    /// Recorded pulses in execution order.
+    pub fn into_pulse_records(self) -> Vec<PulseRecord> {
+        self.pulse_records.unwrap_or_default()
+    }
+
+    pub fn into_frame_programs(self) -> HashMap<ChannelId, FrameProgram> {
+        self.channels
+            .into_iter()
+            .map(|(n, c)| {
+                let mut frames = c.frames;
+                // Stable sort keeps the execution order of simultaneous
+                // events.
+                frames.events.sort_by_key(|e| e.time());
+                (n, frames)
+            })
+            .collect()
+    }
+
+    pub fn into_result(self) -> HashMap<ChannelId, PulseList> {
         self.channels
             .into_iter()
-            .map(|(n, b)| (n, b.pulses.build()))
+            .map(|(n, b)| {
+                let _span = info_span!("build", channel = %n).entered();
+                (n, b.pulses.build())
+            })
             .collect()
     }
 
-    pub(crate) fn execute(&mut self, root: &ElementRef) -> Result<()> {
+    /// Finds the pulses which don't fit in the output windows of their
+    /// channels, which are given relative to the start of the schedule.
+    ///
+    /// Unlike the sampling errors, the report points to every offending
+    /// element of the tree. Muted and soloed-out pulses and channels without a
+    /// window are skipped, and overflows within the time tolerance are
+    /// ignored.
+    pub fn validate(
+        &self,
+        root: &ElementRef,
+        windows: &HashMap<ChannelId, TimeRange>,
+    ) -> Vec<ClippedPulse> {
         let time_range = TimeRange {
             start: Time::ZERO,
             span: root.measure(),
         };
-        for Arranged { item, time_range } in arrange_tree(root, time_range) {
+        let mut path = vec![root.variant.name().to_string()];
+        let mut report = Vec::new();
+        self.validate_node(
+            (
+                Arranged {
+                    item: root,
+                    time_range,
+                },
+                Transform::IDENTITY,
+            ),
+            &mut path,
+            windows,
+            &mut report,
+        );
+        report
+    }
+
+    fn validate_node(
+        &self,
+        node: (Arranged<&ElementRef>, Transform),
+        path: &mut Vec<String>,
+        windows: &HashMap<ChannelId, TimeRange>,
+        report: &mut Vec<ClippedPulse>,
+    ) {
+        let (Arranged { item, time_range }, transform) = node;
+        if item.common.phantom() {
+            return;
+        }
+        if let ElementVariant::Play(play) = &item.variant {
+            let label = item.common.label();
+            let id = play.channel_id();
+            if self.muted.contains(id) || self.solo.is_some() && self.solo.as_ref() != label {
+                return;
+            }
+            let Some(window) = windows.get(id) else {
+                return;
+            };
+            let pulse = play_span(play, item.inner_time_range(time_range), transform);
+            let before = window.start - pulse.start;
+            let after = (pulse.start + pulse.span) - (window.start + window.span);
+            if before > self.time_tolerance || after > self.time_tolerance {
+                report.push(ClippedPulse {
+                    path: path.join(" > "),
+                    label: label.cloned(),
+                    channel: id.clone(),
+                    time_range: pulse,
+                    before: before.max(Time::ZERO),
+                    after: after.max(Time::ZERO),
+                });
+            }
+            return;
+        }
+        let Some(children) = arrange_children(node) else {
+            return;
+        };
+        for (i, child) in children.enumerate() {
+            path.push(path_segment(i, child.0.item));
+            self.validate_node(child, path, windows, report);
+            path.pop();
+        }
+    }
+
+    pub fn execute(&mut self, root: &ElementRef) -> Result<()> {
+        let _span = info_span!("execute").entered();
+        if !self.allow_oversize && CHECK_LAYOUT.load(Ordering::Relaxed) {
+            let _span = info_span!("check_layout").entered();
+            check_layout(root, self.time_tolerance)?;
+        }
+        if !self.phase_from_first_use.is_empty() {
+            self.set_phase_origins(root);
+        }
+        let span = info_span!("measure").in_scope(|| measure_parallel(root));
+        let time_range = TimeRange {
+            start: Time::ZERO,
+            span,
+        };
+        // Arranging is lazy, so the span also covers executing the elements.
+        let _span = info_span!("arrange").entered();
+        for (Arranged { item, time_range }, transform) in arrange_tree(root, time_range) {
             let time_range = item.inner_time_range(time_range);
             if !self.allow_oversize {
+                // A squeezed grid is reported with the child which doesn't fit.
+                if let ElementVariant::Grid(grid) = &item.variant {
+                    check_grid(item, grid, time_range.span, self.time_tolerance)?;
+                }
                 let required = item.variant.measure();
                 check_duration(required, time_range.span, self.time_tolerance)?;
             }
+            let time_range = transform.apply(time_range);
             match &item.variant {
-                ElementVariant::Play(variant) => self.execute_play(variant, time_range),
-                ElementVariant::ShiftPhase(variant) => self.execute_shift_phase(variant),
+                ElementVariant::Play(variant) => {
+                    self.execute_play(variant, item.common.label(), time_range, transform)
+                }
+                ElementVariant::ShiftPhase(variant) => {
+                    self.execute_shift_phase(variant, time_range.start)
+                }
                 ElementVariant::SetPhase(variant) => {
                     self.execute_set_phase(variant, time_range.start)
                 }
@@ -119,6 +509,7 @@ impl Executor {
                 ElementVariant::SetFreq(variant) => {
                     self.execute_set_freq(variant, time_range.start)
                 }
+                ElementVariant::PhaseRamp(variant) => self.execute_phase_ramp(variant, time_range),
                 ElementVariant::SwapPhase(variant) => {
                     self.execute_swap_phase(variant, time_range.start)
                 }
@@ -128,71 +519,217 @@ impl Executor {
         Ok(())
     }
 
-    fn execute_play(&mut self, variant: &Play, time_range: TimeRange) -> Result<()> {
+    /// Muted and soloed-out pulses still count as the first use, so that the
+    /// phase of the remaining pulses does not change.
+    fn set_phase_origins(&mut self, root: &ElementRef) {
+        let mut origins: HashMap<&ChannelId, Time> = HashMap::new();
+        for (_, play, time_range) in play_spans(root) {
+            let id = play.channel_id();
+            if self.phase_from_first_use.contains(id) {
+                origins
+                    .entry(id)
+                    .and_modify(|t| *t = (*t).min(time_range.start))
+                    .or_insert(time_range.start);
+            }
+        }
+        for (id, origin) in origins {
+            if let Some(channel) = self.channels.get_mut(id) {
+                channel.set_phase_origin(origin);
+            }
+        }
+    }
+
+    fn execute_play(
+        &mut self,
+        variant: &Play,
+        label: Option<&Label>,
+        time_range: TimeRange,
+        transform: Transform,
+    ) -> Result<()> {
+        if self.muted.contains(variant.channel_id()) {
+            return Ok(());
+        }
+        if self.solo.is_some() && self.solo.as_ref() != label {
+            return Ok(());
+        }
         let shape = match variant.shape_id() {
-            Some(id) => Some(
-                self.shapes
-                    .get(id)
-                    .ok_or(Error::ShapeNotFound(id.clone()))?
-                    .clone(),
-            ),
+            Some(id) => Some(self.get_shape(id)?),
             None => None,
         };
-        let width = variant.width();
+        let shape = match shape {
+            Some(rise) if variant.is_asymmetric() => {
+                let fall = match variant.fall_shape_id() {
+                    Some(id) => self.get_shape(id)?,
+                    None => rise.clone(),
+                };
+                let rise_width = variant.width().value();
+                let fall_width = variant.fall_width().unwrap_or(variant.width()).value();
+                let total = rise_width + fall_width;
+                if total > 0.0 {
+                    let shape = Shape::new_asymmetric(rise, fall, rise_width / total)
+                        .expect("Rise fraction should be in [0, 1]");
+                    Some(shape)
+                } else {
+                    Some(rise)
+                }
+            }
+            shape => shape,
+        };
+        let width = variant.edge_width() * transform.scale();
         let plateau = if variant.flexible() {
             time_range.span - width
         } else {
-            variant.plateau()
+            variant.plateau() * transform.scale()
         };
         if plateau < Time::ZERO {
             return Err(Error::NegativePlateau(plateau));
         }
-        let amplitude = variant.amplitude();
+        let scale = label
+            .and_then(|l| self.amp_scales.get(l))
+            .copied()
+            .unwrap_or(1.0)
+            * self
+                .channel_scales
+                .get(variant.channel_id())
+                .copied()
+                .unwrap_or(1.0);
+        let amplitude = variant.amplitude() * scale;
         let drag_coef = variant.drag_coef();
         let freq = variant.frequency();
-        let phase = variant.phase();
-        let channel = self.get_mut_channel(variant.channel_id())?;
-        channel.add_pulse(AddPulseArgs {
+        let chirp_rate = variant.chirp_rate();
+        let phase = label
+            .and_then(|l| self.phase_shifts.get(l))
+            .map_or(variant.phase(), |&p| variant.phase() + p);
+        let align_level = variant.align_level();
+        let time = pulse_start(variant, time_range, width + plateau, transform.reversed());
+        let record_frames = self.record_frames;
+        let channel = self.channels.get_mut(variant.channel_id());
+        let channel = channel.ok_or(Error::ChannelNotFound(vec![variant.channel_id().clone()]))?;
+        if let Some(pulse_table) = &mut self.pulse_table {
+            pulse_table.push(ScheduledPulse {
+                channel: variant.channel_id().clone(),
+                time,
+                shape_id: variant.shape_id().cloned(),
+                width: variant.width() * transform.scale(),
+                fall_shape_id: variant.fall_shape_id().cloned(),
+                fall_width: variant.fall_width().map(|w| w * transform.scale()),
+                plateau,
+                amplitude,
+                drag_coef,
+                freq: channel.total_freq() + freq,
+                chirp_rate,
+                phase: (channel.phase_at(time) + phase + channel.total_freq() * time).wrapped(),
+            });
+        }
+        if record_frames {
+            let envelope = channel.envelope_id(Envelope::new(shape, width, plateau));
+            channel.frames.events.push(FrameEvent::Pulse {
+                time,
+                envelope,
+                amplitude,
+                drag_coef,
+                freq,
+                chirp_rate,
+                phase,
+            });
+            return Ok(());
+        }
+        let args = channel.push_args(AddPulseArgs {
             shape,
-            time: time_range.start,
+            time,
             width,
             plateau,
             amplitude,
             drag_coef,
             freq,
+            chirp_rate,
             phase,
+            align_level,
         });
+        if let Some(pulse_records) = &mut self.pulse_records {
+            pulse_records.push(PulseRecord {
+                channel: variant.channel_id().clone(),
+                label: label.cloned(),
+                args: args.clone(),
+            });
+        }
+        channel.pulses.push(args);
         Ok(())
     }
 
-    fn execute_shift_phase(&mut self, variant: &ShiftPhase) -> Result<()> {
+    fn execute_shift_phase(&mut self, variant: &ShiftPhase, time: Time) -> Result<()> {
         let delta_phase = variant.phase();
+        let record_frames = self.record_frames;
         let channel = self.get_mut_channel(variant.channel_id())?;
+        if record_frames {
+            let phase = delta_phase;
+            channel
+                .frames
+                .events
+                .push(FrameEvent::ShiftPhase { time, phase });
+        }
         channel.shift_phase(delta_phase);
         Ok(())
     }
 
     fn execute_set_phase(&mut self, variant: &SetPhase, time: Time) -> Result<()> {
         let phase = variant.phase();
+        let record_frames = self.record_frames;
         let channel = self.get_mut_channel(variant.channel_id())?;
+        if record_frames {
+            channel
+                .frames
+                .events
+                .push(FrameEvent::SetPhase { time, phase });
+        }
         channel.set_phase(phase, time);
         Ok(())
     }
 
     fn execute_shift_freq(&mut self, variant: &ShiftFreq, time: Time) -> Result<()> {
         let delta_freq = variant.frequency();
+        let record_frames = self.record_frames;
         let channel = self.get_mut_channel(variant.channel_id())?;
+        if record_frames {
+            let freq = delta_freq;
+            channel
+                .frames
+                .events
+                .push(FrameEvent::ShiftFreq { time, freq });
+        }
         channel.shift_freq(delta_freq, time);
         Ok(())
     }
 
     fn execute_set_freq(&mut self, variant: &SetFreq, time: Time) -> Result<()> {
         let freq = variant.frequency();
+        let record_frames = self.record_frames;
         let channel = self.get_mut_channel(variant.channel_id())?;
+        if record_frames {
+            channel
+                .frames
+                .events
+                .push(FrameEvent::SetFreq { time, freq });
+        }
         channel.set_freq(freq, time);
         Ok(())
     }
 
+    fn execute_phase_ramp(&mut self, variant: &PhaseRamp, time_range: TimeRange) -> Result<()> {
+        let rate = variant.rate();
+        let record_frames = self.record_frames;
+        let channel = self.get_mut_channel(variant.channel_id())?;
+        if record_frames {
+            channel.frames.events.push(FrameEvent::PhaseRamp {
+                time: time_range.start,
+                duration: time_range.span,
+                rate,
+            });
+        }
+        channel.ramp_phase(rate, time_range);
+        Ok(())
+    }
+
     fn execute_swap_phase(&mut self, variant: &SwapPhase, time: Time) -> Result<()> {
         let ch1 = variant.channel_id1();
         let ch2 = variant.channel_id2();
@@ -203,10 +740,27 @@ impl Executor {
             .channels
             .get_many_mut([ch1, ch2])
             .ok_or(Error::ChannelNotFound(vec![ch1.clone(), ch2.clone()]))?;
+        if self.record_frames {
+            channel.frames.events.push(FrameEvent::SwapPhase {
+                time,
+                other: ch2.clone(),
+            });
+            other.frames.events.push(FrameEvent::SwapPhase {
+                time,
+                other: ch1.clone(),
+            });
+        }
         channel.swap_phase(other, time);
         Ok(())
     }
 
+    fn get_shape(&self, id: &ShapeId) -> Result<Shape> {
+        self.shapes
+            .get(id)
+            .cloned()
+            .ok_or(Error::ShapeNotFound(id.clone()))
+    }
+
     fn get_mut_channel(&mut self, id: &ChannelId) -> Result<&mut Channel> {
         self.channels
             .get_mut(id)
@@ -215,19 +769,39 @@ impl Executor {
 }
 
 impl Channel {
-    fn new(base_freq: Frequency, amp_tolerance: Amplitude, time_tolerance: Time) -> Self {
+    fn new(
+        base_freq: Frequency,
+        wrap_phase: bool,
+        amp_tolerance: Amplitude,
+        time_tolerance: Time,
+    ) -> Self {
         Self {
             base_freq,
             delta_freq: Frequency::ZERO,
             phase: Phase::ZERO,
+            phase_offset: Phase::ZERO,
+            wrap_phase,
+            ramps: Vec::new(),
             pulses: PulseListBuilder::new(amp_tolerance, time_tolerance),
+            frames: FrameProgram::default(),
+            envelope_ids: HashMap::new(),
         }
     }
 
+    /// Index of the envelope in the envelope table of the frame program.
+    fn envelope_id(&mut self, envelope: Envelope) -> usize {
+        let envelopes = &mut self.frames.envelopes;
+        *self.envelope_ids.entry(envelope).or_insert_with_key(|e| {
+            envelopes.push(e.clone());
+            envelopes.len() - 1
+        })
+    }
+
     fn shift_freq(&mut self, delta_freq: Frequency, time: Time) {
         let delta_phase = -delta_freq * time;
         self.delta_freq += delta_freq;
         self.phase += delta_phase;
+        self.wrap_phase();
     }
 
     fn set_freq(&mut self, freq: Frequency, time: Time) {
@@ -235,14 +809,53 @@ impl Channel {
         let delta_phase = -delta_freq * time;
         self.delta_freq = freq;
         self.phase += delta_phase;
+        self.wrap_phase();
     }
 
     fn shift_phase(&mut self, delta_phase: Phase) {
         self.phase += delta_phase;
+        self.wrap_phase();
     }
 
     fn set_phase(&mut self, phase: Phase, time: Time) {
-        self.phase = phase - self.delta_freq * time;
+        self.phase = phase - self.delta_freq * time + self.phase_offset + self.pending_phase(time);
+        self.wrap_phase();
+    }
+
+    fn ramp_phase(&mut self, rate: Frequency, time_range: TimeRange) {
+        self.phase += rate * time_range.span;
+        self.ramps.push(Ramp { time_range, rate });
+        self.wrap_phase();
+    }
+
+    /// Phase of the ramps which is not yet reached at `time`.
+    fn pending_phase(&self, time: Time) -> Phase {
+        self.ramps
+            .iter()
+            .map(|r| {
+                let end = r.time_range.start + r.time_range.span;
+                r.rate * (end - time).max(Time::ZERO).min(r.time_range.span)
+            })
+            .fold(Phase::ZERO, |acc, p| acc + p)
+    }
+
+    /// Phase offset of the channel at `time` including the ramps.
+    fn phase_at(&self, time: Time) -> Phase {
+        self.phase - self.pending_phase(time)
+    }
+
+    /// Moves the zero phase of the base frequency from `t = 0` to `origin`.
+    fn set_phase_origin(&mut self, origin: Time) {
+        let offset = -self.base_freq * origin;
+        self.phase += offset - self.phase_offset;
+        self.phase_offset = offset;
+        self.wrap_phase();
+    }
+
+    fn wrap_phase(&mut self) {
+        if self.wrap_phase {
+            self.phase = self.phase.wrapped();
+        }
     }
 
     fn total_freq(&self) -> Frequency {
@@ -251,14 +864,17 @@ impl Channel {
 
     fn swap_phase(&mut self, other: &mut Self, time: Time) {
         let delta_freq = self.total_freq() - other.total_freq();
-        let phase1 = self.phase;
-        let phase2 = other.phase;
-        self.phase = phase2 - delta_freq * time;
-        other.phase = phase1 + delta_freq * time;
+        let phase1 = self.phase_at(time);
+        let phase2 = other.phase_at(time);
+        self.phase = phase2 - delta_freq * time + self.pending_phase(time);
+        other.phase = phase1 + delta_freq * time + other.pending_phase(time);
+        self.wrap_phase();
+        other.wrap_phase();
     }
 
-    fn add_pulse(
-        &mut self,
+    /// Arguments to push the pulse to the pulse list with the current frame.
+    fn push_args(
+        &self,
         AddPulseArgs {
             shape,
             time,
@@ -267,30 +883,37 @@ impl Channel {
             amplitude,
             drag_coef,
             freq,
+            chirp_rate,
             phase,
+            align_level,
         }: AddPulseArgs,
-    ) {
+    ) -> PushArgs {
         let envelope = Envelope::new(shape, width, plateau);
         let global_freq = self.total_freq();
         let local_freq = freq;
-        self.pulses.push(PushArgs {
+        PushArgs {
             envelope,
             global_freq,
             local_freq,
+            chirp_rate,
             time,
             amplitude,
             drag_coef,
-            phase,
-        })
+            phase: self.phase_at(time) + phase,
+            align_level,
+        }
     }
 }
 
-impl<S, A, G, R, T> Iterator for IterVariant<S, A, G, R>
+impl<S, A, O, G, R, C, V, T> Iterator for IterVariant<S, A, O, G, R, C, V>
 where
     S: Iterator<Item = T>,
     A: Iterator<Item = T>,
+    O: Iterator<Item = T>,
     G: Iterator<Item = T>,
     R: Iterator<Item = T>,
+    C: Iterator<Item = T>,
+    V: Iterator<Item = T>,
 {
     type Item = T;
 
@@ -298,10 +921,539 @@ where
         match self {
             IterVariant::Stack(s) => s.next(),
             IterVariant::Absolute(a) => a.next(),
+            IterVariant::Overlay(o) => o.next(),
             IterVariant::Grid(g) => g.next(),
             IterVariant::Repeat(r) => r.next(),
+            IterVariant::Scale(c) => c.next(),
+            IterVariant::Reverse(v) => v.next(),
+        }
+    }
+}
+
+impl Transform {
+    const IDENTITY: Self = Self {
+        offset: Time::ZERO,
+        factor: 1.0,
+    };
+
+    fn apply(&self, time_range: TimeRange) -> TimeRange {
+        let start = if self.reversed() {
+            time_range.start + time_range.span
+        } else {
+            time_range.start
+        };
+        TimeRange {
+            start: self.offset + start * self.factor,
+            span: time_range.span * self.scale(),
+        }
+    }
+
+    /// Factor of the durations.
+    fn scale(&self) -> f64 {
+        self.factor.abs()
+    }
+
+    fn reversed(&self) -> bool {
+        self.factor < 0.0
+    }
+
+    /// Transform of the child of `scale` arranged in `time_range`.
+    fn then_scale(&self, scale: &Scale, time_range: TimeRange) -> Self {
+        Self {
+            offset: self.offset + scale.to_parent(time_range, Time::ZERO) * self.factor,
+            factor: self.factor * scale.factor(),
         }
     }
+
+    /// Transform of the child of `reverse` arranged in `time_range`.
+    fn then_reverse(&self, reverse: &Reverse, time_range: TimeRange) -> Self {
+        Self {
+            offset: self.offset + reverse.to_parent(time_range, Time::ZERO) * self.factor,
+            factor: -self.factor,
+        }
+    }
+}
+
+/// Computes the busy span of each channel in the schedule.
+///
+/// The busy span of a channel starts at the beginning of its first pulse and
+/// ends at the end of its last pulse. Channels without pulses are omitted.
+pub fn channel_spans(root: &ElementRef) -> HashMap<ChannelId, TimeRange> {
+    let mut spans: HashMap<ChannelId, (Time, Time)> = HashMap::new();
+    for (_, play, time_range) in play_spans(root) {
+        let start = time_range.start;
+        let end = start + time_range.span;
+        spans
+            .entry(play.channel_id().clone())
+            .and_modify(|(s, e)| {
+                *s = (*s).min(start);
+                *e = (*e).max(end);
+            })
+            .or_insert((start, end));
+    }
+    spans
+        .into_iter()
+        .map(|(n, (start, end))| {
+            (
+                n,
+                TimeRange {
+                    start,
+                    span: end - start,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Arranges the tree in `time_range` and yields the time ranges of the children
+/// of each non-phantom container in the time axis of the root. Containers are
+/// visited in pre-order, and an element placed multiple times is yielded once
+/// per placement.
+pub fn child_time_ranges(
+    root: &ElementRef,
+    time_range: TimeRange,
+) -> impl Iterator<Item = (&ElementRef, Vec<TimeRange>)> {
+    arrange_tree(root, time_range).filter_map(|node| {
+        let item = node.0.item;
+        let children = arrange_children(node)?;
+        let ranges = children
+            .map(|(child, transform)| transform.apply(child.time_range))
+            .collect();
+        Some((item, ranges))
+    })
+}
+
+/// Iterates over non-phantom [`Barrier`] children of [`Grid`] elements which
+/// have no channel ids, with their inner time ranges.
+///
+/// Such a barrier spans its columns on all channels of the grid and is shown
+/// as a synchronization line.
+///
+/// [`Barrier`]: crate::schedule::Barrier
+/// [`Grid`]: crate::schedule::Grid
+pub fn grid_barriers(root: &ElementRef) -> impl Iterator<Item = (&ElementRef, TimeRange)> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    arrange_tree(root, time_range)
+        .filter(|(Arranged { item, .. }, _)| matches!(item.variant, ElementVariant::Grid(_)))
+        .filter_map(arrange_children)
+        .flatten()
+        .filter_map(|(Arranged { item, time_range }, transform)| {
+            let ElementVariant::Barrier(barrier) = &item.variant else {
+                return None;
+            };
+            if item.common.phantom() || !barrier.channel_ids().is_empty() {
+                return None;
+            }
+            Some((item, transform.apply(item.inner_time_range(time_range))))
+        })
+}
+
+/// Iterates over non-phantom [`Play`] elements with the time range occupied by
+/// the pulse, which may be shorter than the time range of the element.
+pub fn play_spans(root: &ElementRef) -> impl Iterator<Item = (&ElementRef, &Play, TimeRange)> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    arrange_tree(root, time_range).filter_map(|(Arranged { item, time_range }, transform)| {
+        let ElementVariant::Play(play) = &item.variant else {
+            return None;
+        };
+        Some((
+            item,
+            play,
+            play_span(play, item.inner_time_range(time_range), transform),
+        ))
+    })
+}
+
+/// Iterates over non-phantom elements with a label and their inner time
+/// ranges, one item for each placement of the element.
+pub fn labeled_spans(root: &ElementRef) -> impl Iterator<Item = (&ElementRef, TimeRange)> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    arrange_tree(root, time_range).filter_map(|(Arranged { item, time_range }, transform)| {
+        item.common.label()?;
+        Some((item, transform.apply(item.inner_time_range(time_range))))
+    })
+}
+
+/// Iterates over non-phantom [`Marker`] elements with the time range during
+/// which the marker is high, i.e. the inner time range of the element.
+pub fn marker_spans(root: &ElementRef) -> impl Iterator<Item = (&ElementRef, &Marker, TimeRange)> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    arrange_tree(root, time_range).filter_map(|(Arranged { item, time_range }, transform)| {
+        let ElementVariant::Marker(marker) = &item.variant else {
+            return None;
+        };
+        Some((
+            item,
+            marker,
+            transform.apply(item.inner_time_range(time_range)),
+        ))
+    })
+}
+
+/// [`Play`] element found by [`locate_pulse`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PulseSource {
+    /// Path to the element in the same format as [`Error::LayoutDrift`].
+    pub path: String,
+    pub label: Option<Label>,
+    pub channel: ChannelId,
+}
+
+/// Finds the non-phantom [`Play`] element whose pulse starts at `time` on
+/// `channel`, e.g. to report a pulse which failed to be sampled.
+///
+/// Pulses on other channels are considered if none matches on `channel`,
+/// because crosstalk mixes the pulses of several channels.
+pub fn locate_pulse(
+    root: &ElementRef,
+    channel: &ChannelId,
+    time: Time,
+    time_tolerance: Time,
+) -> Option<PulseSource> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    let mut path = vec![root.variant.name().to_string()];
+    let mut found = vec![];
+    locate_pulse_node(
+        (
+            Arranged {
+                item: root,
+                time_range,
+            },
+            Transform::IDENTITY,
+        ),
+        &mut path,
+        time,
+        time_tolerance,
+        &mut found,
+    );
+    let index = found.iter().position(|s| &s.channel == channel);
+    found.into_iter().nth(index.unwrap_or(0))
+}
+
+fn locate_pulse_node(
+    node: (Arranged<&ElementRef>, Transform),
+    path: &mut Vec<String>,
+    time: Time,
+    time_tolerance: Time,
+    found: &mut Vec<PulseSource>,
+) {
+    let (Arranged { item, time_range }, transform) = node;
+    if item.common.phantom() {
+        return;
+    }
+    if let ElementVariant::Play(play) = &item.variant {
+        let pulse = play_span(play, item.inner_time_range(time_range), transform);
+        if (pulse.start - time).value().abs() <= time_tolerance.value() {
+            found.push(PulseSource {
+                path: path.join(" > "),
+                label: item.common.label().cloned(),
+                channel: play.channel_id().clone(),
+            });
+        }
+        return;
+    }
+    let Some(children) = arrange_children(node) else {
+        return;
+    };
+    for (i, child) in children.enumerate() {
+        path.push(path_segment(i, child.0.item));
+        locate_pulse_node(child, path, time, time_tolerance, found);
+        path.pop();
+    }
+}
+
+/// Time range of the pulse of `play` arranged in the inner `time_range`.
+fn play_span(play: &Play, time_range: TimeRange, transform: Transform) -> TimeRange {
+    let time_range = transform.apply(time_range);
+    let span = if play.flexible() {
+        time_range.span
+    } else {
+        (play.edge_width() + play.plateau()) * transform.scale()
+    };
+    TimeRange {
+        start: pulse_start(play, time_range, span, transform.reversed()),
+        span,
+    }
+}
+
+/// Collects the channels played on by [`Play`] elements of each label.
+pub fn label_channels(root: &ElementRef) -> HashMap<Label, HashSet<ChannelId>> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    let mut result: HashMap<Label, HashSet<ChannelId>> = HashMap::new();
+    for (Arranged { item, .. }, _) in arrange_tree(root, time_range) {
+        if let (ElementVariant::Play(play), Some(label)) = (&item.variant, item.common.label()) {
+            result
+                .entry(label.clone())
+                .or_default()
+                .insert(play.channel_id().clone());
+        }
+    }
+    result
+}
+
+/// Describes the timing of the schedule as indented lines like
+/// `Play 'x90' 10 ns .. 40 ns (30 ns) [xy]`, one line for each non-phantom
+/// element and each placement of it.
+///
+/// If `channels` is given, only elements on these channels are described.
+/// Elements without channels, like barriers on all channels, are always
+/// described.
+pub fn explain(root: &ElementRef, channels: Option<&[ChannelId]>) -> String {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    let root = (
+        Arranged {
+            item: root,
+            time_range,
+        },
+        Transform::IDENTITY,
+    );
+    let mut out = String::new();
+    explain_node(root, 0, channels, &mut out);
+    out
+}
+
+fn explain_node(
+    node: (Arranged<&ElementRef>, Transform),
+    depth: usize,
+    filter: Option<&[ChannelId]>,
+    out: &mut String,
+) {
+    let (Arranged { item, time_range }, transform) = node;
+    let channels = item.channels();
+    let shown = match filter {
+        Some(filter) => channels.is_empty() || channels.iter().any(|c| filter.contains(c)),
+        None => true,
+    };
+    if item.common.phantom() || !shown {
+        return;
+    }
+    let TimeRange { start, span } = transform.apply(item.inner_time_range(time_range));
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(item.variant.name());
+    if let Some(label) = item.common.label() {
+        out.push_str(&format!(" '{}'", label));
+    }
+    let channels = if channels.is_empty() {
+        "all".to_string()
+    } else {
+        channels.iter().map(ToString::to_string).sorted().join(", ")
+    };
+    out.push_str(&format!(
+        " {} .. {} ({}) [{}]\n",
+        format_time(start),
+        format_time(start + span),
+        format_time(span),
+        channels
+    ));
+    let Some(children) = arrange_children(node) else {
+        return;
+    };
+    let mut children: Vec<_> = children.collect();
+    if transform.reversed() {
+        children.reverse();
+    }
+    for child in children {
+        explain_node(child, depth + 1, filter, out);
+    }
+}
+
+/// Formats the time with an SI prefix, e.g. `1.5 us`.
+fn format_time(time: Time) -> String {
+    let seconds = time.value();
+    let (factor, unit) = match seconds.abs() {
+        t if t == 0.0 || t >= 1.0 => (1.0, "s"),
+        t if t >= 1e-3 => (1e3, "ms"),
+        t if t >= 1e-6 => (1e6, "us"),
+        t if t >= 1e-9 => (1e9, "ns"),
+        _ => (1e12, "ps"),
+    };
+    let value = (seconds * factor * 1e3).round() / 1e3;
+    format!("{} {}", value, unit)
+}
+
+/// Enables or disables [`check_layout`] before every execution. It is enabled
+/// by default in debug builds.
+pub fn set_check_layout(enabled: bool) {
+    CHECK_LAYOUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Verifies that the arrangement agrees with the measured durations.
+///
+/// Every container should arrange its children inside its own time range, and
+/// the children arranged in the measured duration of the container should span
+/// exactly that duration. Children of a [`Grid`] may leave free time because of
+/// fixed columns and cell alignment, so only the former is checked for grids,
+/// and children of an [`Absolute`](crate::schedule::Absolute) may start later
+/// than the container. Oversized containers are skipped. The error contains the
+/// path to the offending child or container.
+pub fn check_layout(root: &ElementRef, time_tolerance: Time) -> Result<()> {
+    let time_range = TimeRange {
+        start: Time::ZERO,
+        span: root.measure(),
+    };
+    let mut path = vec![root.variant.name().to_string()];
+    check_layout_node(root, time_range, &mut path, time_tolerance)
+}
+
+fn check_layout_node(
+    item: &ElementRef,
+    time_range: TimeRange,
+    path: &mut Vec<String>,
+    time_tolerance: Time,
+) -> Result<()> {
+    let Some(children) = arrange_children((Arranged { item, time_range }, Transform::IDENTITY))
+    else {
+        return Ok(());
+    };
+    let inner = item.inner_time_range(time_range);
+    if item.variant.measure() > inner.span + time_tolerance {
+        // Reported as `NotEnoughDuration` or `OverconstrainedGrid` during
+        // execution.
+        return Ok(());
+    }
+    if let Some((children, expected, check_start)) = measured_arrangement(&item.variant) {
+        check_extent(
+            &children,
+            expected,
+            check_start,
+            path,
+            item.common.label(),
+            time_tolerance,
+        )?;
+    }
+    // Children of a scale element are arranged in the unscaled time axis.
+    let span = match &item.variant {
+        ElementVariant::Scale(s) => inner.span / s.factor(),
+        _ => inner.span,
+    };
+    let container_start = inner.start;
+    let container_end = inner.start + span;
+    for (i, (child, _)) in children.enumerate() {
+        let child_item = child.item;
+        path.push(path_segment(i, child_item));
+        let start = child.time_range.start;
+        let end = start + child.time_range.span;
+        if start < container_start - time_tolerance || end > container_end + time_tolerance {
+            return Err(Error::LayoutDrift {
+                path: path.join(" > "),
+                label: child_item.common.label().cloned(),
+                start,
+                end,
+                container_start,
+                container_end,
+            });
+        }
+        if !child_item.common.phantom() {
+            check_layout_node(child_item, child.time_range, path, time_tolerance)?;
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+/// Time ranges of the children of a container arranged in its measured
+/// duration, the range which they should span and whether they should start at
+/// the start of the range.
+fn measured_arrangement(variant: &ElementVariant) -> Option<(Vec<TimeRange>, TimeRange, bool)> {
+    fn spans<'a>(children: impl Iterator<Item = Arranged<&'a ElementRef>>) -> Vec<TimeRange> {
+        children.map(|a| a.time_range).collect()
+    }
+    let measured = TimeRange {
+        start: Time::ZERO,
+        span: variant.measure(),
+    };
+    Some(match variant {
+        ElementVariant::Repeat(r) => (spans(r.arrange(measured)), measured, true),
+        ElementVariant::Stack(s) => (spans(s.arrange(measured)), measured, true),
+        ElementVariant::Absolute(a) => (spans(a.arrange(measured)), measured, false),
+        ElementVariant::Overlay(o) => (spans(o.arrange(measured)), measured, true),
+        ElementVariant::Scale(c) => {
+            let unscaled = TimeRange {
+                start: Time::ZERO,
+                span: measured.span / c.factor(),
+            };
+            (spans(c.arrange(measured)), unscaled, true)
+        }
+        ElementVariant::Reverse(v) => (spans(v.arrange(measured)), measured, true),
+        _ => return None,
+    })
+}
+
+/// Checks that the union of the `children` ranges ends at the end of
+/// `expected`, and also starts at its start if `check_start` is set. The
+/// error reports the union at `path`.
+fn check_extent(
+    children: &[TimeRange],
+    expected: TimeRange,
+    check_start: bool,
+    path: &[String],
+    label: Option<&Label>,
+    time_tolerance: Time,
+) -> Result<()> {
+    let Some(start) = children.iter().map(|r| r.start).min() else {
+        return Ok(());
+    };
+    let end = children
+        .iter()
+        .map(|r| r.start + r.span)
+        .max()
+        .expect("Children should not be empty");
+    let container_end = expected.start + expected.span;
+    let drifted = |a: Time, b: Time| (a - b).value().abs() > time_tolerance.value();
+    if (check_start && drifted(start, expected.start)) || drifted(end, container_end) {
+        return Err(Error::LayoutDrift {
+            path: path.join(" > "),
+            label: label.cloned(),
+            start,
+            end,
+            container_start: expected.start,
+            container_end,
+        });
+    }
+    Ok(())
+}
+
+/// Segment of the path to the `i`-th child `item` of a container.
+pub fn path_segment(i: usize, item: &ElementRef) -> String {
+    let mut segment = format!("{}: {}", i, item.variant.name());
+    if let Some(label) = item.common.label() {
+        segment.push_str(&format!(" '{}'", label));
+    }
+    segment
+}
+
+/// Start of the pulse of `play` with length `length` in the allotted
+/// `time_range`. Inside [`Reverse`] elements the pulse is moved to the end of
+/// the mirrored time range.
+fn pulse_start(play: &Play, time_range: TimeRange, length: Time, reversed: bool) -> Time {
+    if play.centered() {
+        time_range.start + (time_range.span - length) / 2.0
+    } else if reversed {
+        time_range.start + time_range.span - length
+    } else {
+        time_range.start
+    }
 }
 
 fn check_duration(required: Time, available: Time, time_tolerance: Time) -> Result<()> {
@@ -314,34 +1466,74 @@ fn check_duration(required: Time, available: Time, time_tolerance: Time) -> Resu
     Ok(())
 }
 
+fn check_grid(item: &ElementRef, grid: &Grid, span: Time, time_tolerance: Time) -> Result<()> {
+    if let Some(GridConflict {
+        child,
+        required,
+        available,
+    }) = grid.find_conflict(span, time_tolerance)
+    {
+        return Err(Error::OverconstrainedGrid {
+            label: item.common.label().cloned(),
+            child,
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
 fn arrange_tree(
     root: &ElementRef,
     time_range: TimeRange,
-) -> impl Iterator<Item = Arranged<&ElementRef>> {
+) -> impl Iterator<Item = (Arranged<&ElementRef>, Transform)> {
     pre_order_iter(
-        Arranged {
-            item: root,
-            time_range,
+        (
+            Arranged {
+                item: root,
+                time_range,
+            },
+            Transform::IDENTITY,
+        ),
+        |node| {
+            let children = arrange_children(node)?;
+            // Children of mirrored containers are visited back to front, so
+            // that the frame changes are applied in the order of time.
+            if node.1.reversed() {
+                let children: Vec<_> = children.collect();
+                Some(Either::Right(children.into_iter().rev()))
+            } else {
+                Some(Either::Left(children))
+            }
         },
-        arrange_children,
     )
-    .filter(|Arranged { item, .. }| !item.common.phantom())
+    .filter(|(Arranged { item, .. }, _)| !item.common.phantom())
 }
 
 fn arrange_children(
-    Arranged { item, time_range }: Arranged<&ElementRef>,
-) -> Option<impl Iterator<Item = Arranged<&ElementRef>>> {
+    (Arranged { item, time_range }, transform): (Arranged<&ElementRef>, Transform),
+) -> Option<impl Iterator<Item = (Arranged<&ElementRef>, Transform)>> {
     if item.common.phantom() {
         return None;
     }
     let time_range = item.inner_time_range(time_range);
-    match &item.variant {
-        ElementVariant::Repeat(r) => Some(IterVariant::Repeat(r.arrange(time_range))),
-        ElementVariant::Stack(s) => Some(IterVariant::Stack(s.arrange(time_range))),
-        ElementVariant::Absolute(a) => Some(IterVariant::Absolute(a.arrange(time_range))),
-        ElementVariant::Grid(g) => Some(IterVariant::Grid(g.arrange(time_range))),
-        _ => None,
-    }
+    let (children, transform) = match &item.variant {
+        ElementVariant::Repeat(r) => (IterVariant::Repeat(r.arrange(time_range)), transform),
+        ElementVariant::Stack(s) => (IterVariant::Stack(s.arrange(time_range)), transform),
+        ElementVariant::Absolute(a) => (IterVariant::Absolute(a.arrange(time_range)), transform),
+        ElementVariant::Overlay(o) => (IterVariant::Overlay(o.arrange(time_range)), transform),
+        ElementVariant::Grid(g) => (IterVariant::Grid(g.arrange(time_range)), transform),
+        ElementVariant::Scale(c) => (
+            IterVariant::Scale(c.arrange(time_range)),
+            transform.then_scale(c, time_range),
+        ),
+        ElementVariant::Reverse(v) => (
+            IterVariant::Reverse(v.arrange(time_range)),
+            transform.then_reverse(v, time_range),
+        ),
+        _ => return None,
+    };
+    Some(children.map(move |a| (a, transform)))
 }
 
 fn pre_order_iter<T, F, I>(root: T, mut children: F) -> impl Iterator<Item = T>
@@ -368,6 +1560,744 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        quant::{Amplitude, ChannelId, ChirpRate, Frequency, Label, Phase, ShapeId, Time},
+        schedule::{
+            test_util::play, Absolute, AbsoluteEntry, Barrier, Element, ElementCommonBuilder,
+            ElementRef, Grid, GridEntry, Marker, Measure as _, PhaseRamp, Play, Reverse, Scale,
+            SetFreq, ShiftPhase, Stack, TimeRange,
+        },
+        Direction, GridLength,
+    };
+
+    use super::{Channel, Executor, FrameEvent};
+
+    #[test]
+    fn record_frames() {
+        let a = ChannelId::new("a");
+        let shift = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            ShiftPhase::new(a.clone(), Phase::new(0.25).unwrap()).unwrap(),
+        ));
+        let set_freq = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            SetFreq::new(a.clone(), Frequency::new(1e6).unwrap()).unwrap(),
+        ));
+        let stack = Stack::new()
+            .with_direction(Direction::Forward)
+            .with_children(vec![
+                play("a", 1.0, None),
+                shift,
+                set_freq,
+                play("a", 1.0, None),
+            ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(a.clone(), Frequency::new(1e8).unwrap(), false);
+        executor.record_frames();
+
+        executor.execute(&root).unwrap();
+
+        let programs = executor.into_frame_programs();
+        let program = &programs[&a];
+        assert_eq!(program.envelopes.len(), 1);
+        let pulse = |time: f64| FrameEvent::Pulse {
+            time: Time::new(time).unwrap(),
+            envelope: 0,
+            amplitude: Amplitude::new(1.0).unwrap(),
+            drag_coef: 0.0,
+            freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            phase: Phase::ZERO,
+        };
+        let time = Time::new(10.0).unwrap();
+        assert_eq!(
+            program.events,
+            [
+                pulse(0.0),
+                FrameEvent::ShiftPhase {
+                    time,
+                    phase: Phase::new(0.25).unwrap()
+                },
+                FrameEvent::SetFreq {
+                    time,
+                    freq: Frequency::new(1e6).unwrap()
+                },
+                pulse(10.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn reversed_frames() {
+        let a = ChannelId::new("a");
+        let shift = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            ShiftPhase::new(a.clone(), Phase::new(0.25).unwrap()).unwrap(),
+        ));
+        let long_play = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .duration(Some(Time::new(15.0).unwrap()))
+                .build()
+                .unwrap(),
+            play("a", 1.0, None).variant.clone(),
+        ));
+        let stack = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new()
+                .with_direction(Direction::Forward)
+                .with_children(vec![play("a", 1.0, None), shift, long_play]),
+        ));
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(1.0).unwrap(), Time::ZERO))
+                .build()
+                .unwrap(),
+            Reverse::new(stack),
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(a.clone(), Frequency::ZERO, false);
+        executor.record_frames();
+
+        executor.execute(&root).unwrap();
+
+        let programs = executor.into_frame_programs();
+        let pulse = |time: f64| FrameEvent::Pulse {
+            time: Time::new(time).unwrap(),
+            envelope: 0,
+            amplitude: Amplitude::new(1.0).unwrap(),
+            drag_coef: 0.0,
+            freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            phase: Phase::ZERO,
+        };
+        // The shift now precedes the first pulse, and the pulse of the longer
+        // element moves to the end of the element.
+        assert_eq!(
+            programs[&a].events,
+            [
+                pulse(6.0),
+                FrameEvent::ShiftPhase {
+                    time: Time::new(16.0).unwrap(),
+                    phase: Phase::new(0.25).unwrap()
+                },
+                pulse(16.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn pulse_table() {
+        let a = ChannelId::new("a");
+        let shift = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            ShiftPhase::new(a.clone(), Phase::new(0.25).unwrap()).unwrap(),
+        ));
+        let scale = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Scale::new(play("a", 1.0, None), 0.5).unwrap(),
+        ));
+        let stack = Stack::new()
+            .with_direction(Direction::Forward)
+            .with_children(vec![play("a", 1.0, None), shift, scale]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(a.clone(), Frequency::new(0.1).unwrap(), true);
+        executor.add_channel_scale(a.clone(), 2.0);
+        executor.record_pulse_table();
+
+        executor.execute(&root).unwrap();
+
+        let table = executor.into_pulse_table();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0].time, Time::ZERO);
+        assert_eq!(table[0].phase, Phase::ZERO);
+        assert_eq!(table[1].time, Time::new(10.0).unwrap());
+        assert_eq!(table[1].width, Time::new(5.0).unwrap());
+        assert_eq!(table[1].amplitude, Amplitude::new(2.0).unwrap());
+        assert_eq!(table[1].freq, Frequency::new(0.1).unwrap());
+        // Frame shift plus one cycle of the carrier at t = 10.
+        float_cmp::assert_approx_eq!(f64, table[1].phase.value(), 0.25, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn phase_ramp() {
+        let a = ChannelId::new("a");
+        let ramp = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .duration(Some(Time::new(20.0).unwrap()))
+                .build()
+                .unwrap(),
+            PhaseRamp::new(a.clone(), Frequency::new(0.01).unwrap()).unwrap(),
+        ));
+        let entry = |time: f64, element: ElementRef| {
+            AbsoluteEntry::new(element)
+                .with_time(Time::new(time).unwrap())
+                .unwrap()
+        };
+        let absolute = Absolute::new().with_children(vec![
+            entry(0.0, ramp),
+            entry(5.0, play("a", 1.0, None)),
+            entry(25.0, play("a", 1.0, None)),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            absolute,
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(a.clone(), Frequency::ZERO, true);
+        executor.record_pulse_table();
+
+        executor.execute(&root).unwrap();
+
+        let table = executor.into_pulse_table();
+        // A quarter through the ramp and after its end.
+        float_cmp::assert_approx_eq!(f64, table[0].phase.value(), 0.05, epsilon = 1e-12);
+        float_cmp::assert_approx_eq!(f64, table[1].phase.value(), 0.2, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn label_channels() {
+        let stack = Stack::new().with_children(vec![
+            play("a", 1.0, Some("x")),
+            play("b", 1.0, Some("x")),
+            play("c", 1.0, Some("y")),
+            play("d", 1.0, None),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+
+        let result = super::label_channels(&root);
+
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            result[&Label::new("x")],
+            [ChannelId::new("a"), ChannelId::new("b")]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            result[&Label::new("y")],
+            [ChannelId::new("c")].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn explain_timing() {
+        let barrier = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Barrier::new(vec![]),
+        ));
+        let stack = Stack::new().with_children(vec![
+            play("a", 1.0, Some("x")),
+            barrier,
+            play("b", 1.0, None),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+
+        assert_eq!(
+            super::explain(&root, None),
+            "Stack 0 s .. 20 s (20 s) [a, b]\n\
+             \x20 Play 'x' 0 s .. 10 s (10 s) [a]\n\
+             \x20 Barrier 10 s .. 10 s (0 s) [all]\n\
+             \x20 Play 10 s .. 20 s (10 s) [b]\n"
+        );
+        let filtered = super::explain(&root, Some(&[ChannelId::new("b")]));
+        assert_eq!(filtered.lines().count(), 3);
+        assert_eq!(super::format_time(Time::new(1.5e-6).unwrap()), "1.5 us");
+    }
+
+    #[test]
+    fn marker_spans() {
+        let marker = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(1.0).unwrap(), Time::ZERO))
+                .build()
+                .unwrap(),
+            Marker::new(ChannelId::new("m"), Time::new(4.0).unwrap()).unwrap(),
+        ));
+        let barrier = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Barrier::new(vec![]),
+        ));
+        let stack = Stack::new()
+            .with_direction(Direction::Forward)
+            .with_children(vec![play("a", 1.0, None), barrier, marker]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+
+        let spans: Vec<_> = super::marker_spans(&root)
+            .map(|(_, marker, span)| {
+                (
+                    marker.channel_id().to_string(),
+                    span.start.value(),
+                    span.span.value(),
+                )
+            })
+            .collect();
+
+        assert_eq!(spans, [("m".to_string(), 11.0, 4.0)]);
+    }
+
+    #[test]
+    fn labeled_spans() {
+        let barrier = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Barrier::new(vec![]),
+        ));
+        let stack = Stack::new().with_children(vec![
+            play("a", 1.0, Some("x")),
+            barrier,
+            play("b", 1.0, None),
+            play("b", 1.0, Some("y")),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+
+        let spans: Vec<_> = super::labeled_spans(&root)
+            .map(|(item, span)| {
+                let label = item.common.label().unwrap().to_string();
+                (label, span.start.value(), span.span.value())
+            })
+            .collect();
+
+        assert_eq!(
+            spans,
+            [("x".to_string(), 0.0, 10.0), ("y".to_string(), 20.0, 10.0)]
+        );
+    }
+
+    #[test]
+    fn grid_barriers() {
+        let barrier = |channels: Vec<ChannelId>| {
+            Arc::new(Element::new(
+                ElementCommonBuilder::new()
+                    .duration(Some(Time::new(4.0).unwrap()))
+                    .build()
+                    .unwrap(),
+                Barrier::new(channels),
+            ))
+        };
+        let grid = Grid::new()
+            .with_columns(vec![GridLength::auto(); 3])
+            .with_children(vec![
+                GridEntry::new(play("a", 1.0, None)),
+                GridEntry::new(barrier(vec![])).with_column(1),
+                GridEntry::new(barrier(vec![ChannelId::new("a")])).with_column(1),
+                GridEntry::new(play("b", 1.0, None)).with_column(2),
+            ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![
+                play("a", 1.0, None),
+                Arc::new(Element::new(
+                    ElementCommonBuilder::new().build().unwrap(),
+                    grid,
+                )),
+            ]),
+        ));
+
+        let result: Vec<_> = super::grid_barriers(&root).map(|(_, r)| r).collect();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].start, Time::new(20.0).unwrap());
+        assert_eq!(result[0].span, Time::new(4.0).unwrap());
+    }
+
+    #[test]
+    fn scaled_spans() {
+        let inner = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(1.0).unwrap(), Time::ZERO))
+                .build()
+                .unwrap(),
+            Stack::new().with_children(vec![play("a", 1.0, None), play("b", 1.0, None)]),
+        ));
+        let scale = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Scale::new(inner, 2.0).unwrap(),
+        ));
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![play("a", 1.0, None), scale]),
+        ));
+
+        let spans = super::channel_spans(&root);
+
+        assert_eq!(root.measure(), Time::new(32.0).unwrap());
+        assert_eq!(spans[&ChannelId::new("a")].start, Time::ZERO);
+        assert_eq!(spans[&ChannelId::new("a")].span, Time::new(32.0).unwrap());
+        assert_eq!(spans[&ChannelId::new("b")].start, Time::new(12.0).unwrap());
+        assert_eq!(spans[&ChannelId::new("b")].span, Time::new(20.0).unwrap());
+    }
+
+    #[test]
+    fn check_layout_nested() {
+        let inner = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(1.0).unwrap(), Time::new(2.0).unwrap()))
+                .build()
+                .unwrap(),
+            Stack::new().with_children(vec![play("a", 1.0, None), play("b", 1.0, Some("x"))]),
+        ));
+        let scale = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Scale::new(inner, 0.5).unwrap(),
+        ));
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![play("a", 1.0, None), scale]),
+        ));
+
+        assert!(super::check_layout(&root, Time::ZERO).is_ok());
+    }
+
+    #[test]
+    fn check_extent_underfilled() {
+        let range = |start, span| TimeRange {
+            start: Time::new(start).unwrap(),
+            span: Time::new(span).unwrap(),
+        };
+        let path = ["Stack".to_string()];
+        let label = Label::new("x");
+        let children = [range(0.0, 4.0), range(4.0, 4.0)];
+
+        let result = super::check_extent(
+            &children,
+            range(0.0, 10.0),
+            true,
+            &path,
+            Some(&label),
+            Time::new(1e-3).unwrap(),
+        );
+
+        let Err(super::Error::LayoutDrift {
+            path,
+            label,
+            start,
+            end,
+            container_end,
+            ..
+        }) = result
+        else {
+            panic!("Should be a layout drift, got {:?}", result);
+        };
+        assert_eq!(path, "Stack");
+        assert_eq!(label, Some(Label::new("x")));
+        assert_eq!(start, Time::ZERO);
+        assert_eq!(end, Time::new(8.0).unwrap());
+        assert_eq!(container_end, Time::new(10.0).unwrap());
+        // Late start is allowed in absolute containers only.
+        let path = ["Absolute".to_string()];
+        let children = [range(2.0, 8.0)];
+        let tolerance = Time::new(1e-3).unwrap();
+        let expected = range(0.0, 10.0);
+        assert!(super::check_extent(&children, expected, false, &path, None, tolerance).is_ok());
+        assert!(super::check_extent(&children, expected, true, &path, None, tolerance).is_err());
+    }
+
+    #[test]
+    fn validate_windows() {
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![
+                play("a", 1.0, Some("x")),
+                play("b", 1.0, Some("y")),
+                play("c", 1.0, None),
+            ]),
+        ));
+        let window = |start, span| TimeRange {
+            start: Time::new(start).unwrap(),
+            span: Time::new(span).unwrap(),
+        };
+        let windows = [
+            (ChannelId::new("a"), window(0.0, 8.0)),
+            (ChannelId::new("b"), window(0.0, 10.0)),
+        ]
+        .into_iter()
+        .collect();
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+
+        let report = executor.validate(&root, &windows);
+        executor.mute_channel(ChannelId::new("a"));
+        let muted = executor.validate(&root, &windows);
+
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].path, "Stack > 0: Play 'x'");
+        assert_eq!(report[0].label, Some(Label::new("x")));
+        assert_eq!(report[0].channel, ChannelId::new("a"));
+        assert_eq!(report[0].time_range.start, Time::ZERO);
+        assert_eq!(report[0].before, Time::ZERO);
+        assert_eq!(report[0].after, Time::new(2.0).unwrap());
+        assert!(muted.is_empty());
+    }
+
+    #[test]
+    fn locate_pulse_by_time() {
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new()
+                .with_direction(Direction::Forward)
+                .with_children(vec![
+                    play("a", 1.0, Some("x")),
+                    play("b", 1.0, Some("y")),
+                    play("a", 1.0, None),
+                ]),
+        ));
+        let locate = |channel: &str, time: f64| {
+            super::locate_pulse(
+                &root,
+                &ChannelId::new(channel),
+                Time::new(time).unwrap(),
+                Time::new(1e-9).unwrap(),
+            )
+        };
+
+        let found = locate("b", 0.0).unwrap();
+        // Only crosstalk from other channels can put a pulse on "c".
+        let crosstalk = locate("c", 0.0).unwrap();
+
+        assert_eq!(found.path, "Stack > 1: Play 'y'");
+        assert_eq!(found.label, Some(Label::new("y")));
+        assert_eq!(crosstalk.path, "Stack > 0: Play 'x'");
+        assert_eq!(crosstalk.channel, ChannelId::new("a"));
+        assert_eq!(locate("a", 10.0).unwrap().path, "Stack > 2: Play");
+        assert!(locate("a", 5.0).is_none());
+    }
+
+    #[test]
+    fn asymmetric_play_span() {
+        let play = Play::new(
+            ChannelId::new("a"),
+            Some(ShapeId::new("s")),
+            Amplitude::new(1.0).unwrap(),
+            Time::new(4.0).unwrap(),
+        )
+        .unwrap()
+        .with_plateau(Time::new(3.0).unwrap())
+        .unwrap()
+        .with_fall(None, Some(Time::new(2.0).unwrap()))
+        .unwrap();
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![Arc::new(Element::new(
+                ElementCommonBuilder::new().build().unwrap(),
+                play,
+            ))]),
+        ));
+
+        let spans = super::channel_spans(&root);
+
+        assert_eq!(root.measure(), Time::new(6.0).unwrap());
+        assert_eq!(spans[&ChannelId::new("a")].span, Time::new(6.0).unwrap());
+    }
+
+    #[test]
+    fn child_time_ranges() {
+        let inner = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![play("b", 1.0, None)]),
+        ));
+        let scale = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Scale::new(inner.clone(), 2.0).unwrap(),
+        ));
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![play("a", 1.0, None), scale.clone()]),
+        ));
+        let time_range = TimeRange {
+            start: Time::new(5.0).unwrap(),
+            span: root.measure(),
+        };
+
+        let ranges: Vec<_> = super::child_time_ranges(&root, time_range)
+            .map(|(item, ranges)| {
+                let ranges: Vec<_> = ranges
+                    .iter()
+                    .map(|r| (r.start.value(), r.span.value()))
+                    .collect();
+                (Arc::as_ptr(item), ranges)
+            })
+            .collect();
+
+        assert_eq!(
+            ranges,
+            vec![
+                (Arc::as_ptr(&root), vec![(15.0, 10.0), (5.0, 20.0)]),
+                (Arc::as_ptr(&scale), vec![(5.0, 20.0)]),
+                (Arc::as_ptr(&inner), vec![(5.0, 20.0)]),
+            ]
+        );
+    }
+
+    fn centered_play(flexible: bool) -> ElementRef {
+        let play = Play::new(
+            ChannelId::new("a"),
+            None,
+            Amplitude::new(1.0).unwrap(),
+            Time::new(10.0).unwrap(),
+        )
+        .unwrap()
+        .with_flexible(flexible)
+        .with_centered(true);
+        Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(2.0).unwrap(), Time::new(4.0).unwrap()))
+                .duration(Some(Time::new(30.0).unwrap()))
+                .build()
+                .unwrap(),
+            play,
+        ))
+    }
+
+    #[test]
+    fn centered_play_with_margin() {
+        let root = centered_play(false);
+
+        let spans: Vec<_> = super::play_spans(&root).map(|(_, _, r)| r).collect();
+
+        assert_eq!(root.measure(), Time::new(36.0).unwrap());
+        assert_eq!(spans[0].start, Time::new(12.0).unwrap());
+        assert_eq!(spans[0].span, Time::new(10.0).unwrap());
+    }
+
+    #[test]
+    fn centered_flexible_play() {
+        let root = centered_play(true);
+
+        let spans: Vec<_> = super::play_spans(&root).map(|(_, _, r)| r).collect();
+
+        assert_eq!(spans[0].start, Time::new(2.0).unwrap());
+        assert_eq!(spans[0].span, Time::new(30.0).unwrap());
+    }
+
+    #[test]
+    fn solo_label() {
+        let stack = Stack::new().with_children(vec![
+            play("a", 1.0, Some("x")),
+            play("a", 1.0, Some("y")),
+            play("a", 1.0, None),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+        let mut executor = super::Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(ChannelId::new("a"), Frequency::ZERO, true);
+        executor.solo_label(Label::new("x"));
+
+        executor.execute(&root).unwrap();
+
+        let result = executor.into_result();
+        assert_eq!(result[&ChannelId::new("a")].len(), 1);
+    }
+
+    #[test]
+    fn channel_scale() {
+        let stack = Stack::new().with_children(vec![
+            play("a", 1.0, Some("x")),
+            play("b", 1.0, Some("x")),
+            play("c", 1.0, None),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+        let mut executor = super::Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        for n in ["a", "b", "c"] {
+            executor.add_channel(ChannelId::new(n), Frequency::ZERO, true);
+        }
+        executor.add_amp_scale(Label::new("x"), 0.5);
+        executor.add_channel_scale(ChannelId::new("a"), 3.0);
+        executor.add_channel_scale(ChannelId::new("c"), 0.25);
+        executor.record_frames();
+
+        executor.execute(&root).unwrap();
+
+        let programs = executor.into_frame_programs();
+        let amplitude = |n: &str| match programs[&ChannelId::new(n)].events[..] {
+            [FrameEvent::Pulse { amplitude, .. }] => amplitude.value(),
+            _ => panic!("Expected a single pulse"),
+        };
+        assert_eq!(amplitude("a"), 1.5);
+        assert_eq!(amplitude("b"), 0.5);
+        assert_eq!(amplitude("c"), 0.25);
+    }
+
+    #[test]
+    fn channel_spans() {
+        let stack = Stack::new().with_children(vec![
+            play("a", 1.0, None),
+            play("b", 1.0, None),
+            play("a", 1.0, None),
+        ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            stack,
+        ));
+
+        let spans = super::channel_spans(&root);
+
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[&ChannelId::new("a")].start, Time::ZERO);
+        assert_eq!(spans[&ChannelId::new("a")].span, Time::new(20.0).unwrap());
+        assert_eq!(spans[&ChannelId::new("b")].start, Time::new(10.0).unwrap());
+        assert_eq!(spans[&ChannelId::new("b")].span, Time::new(10.0).unwrap());
+    }
+
+    /// Distance between the channel phase and `expected` modulo 1.
+    fn phase_error(channel: &Channel, expected: f64) -> f64 {
+        let diff = (channel.phase.value() - expected).rem_euclid(1.0);
+        diff.min(1.0 - diff)
+    }
+
+    fn long_sequence(wrap_phase: bool) -> Channel {
+        let mut channel = Channel::new(Frequency::ZERO, wrap_phase, Amplitude::ZERO, Time::ZERO);
+        // One hour into the schedule with a 100.1 MHz detuning.
+        let time = Time::new(3600.0).unwrap();
+        channel.set_freq(Frequency::new(100.1e6).unwrap(), time);
+        for _ in 0..1000 {
+            channel.shift_phase(Phase::new(0.1).unwrap());
+        }
+        channel
+    }
+
+    #[test]
+    fn wrap_phase_precision() {
+        // -100.1e6 * 3600 + 1000 * 0.1 is an integer.
+        let wrapped = long_sequence(true);
+        let unwrapped = long_sequence(false);
+
+        assert!((0.0..1.0).contains(&wrapped.phase.value()));
+        assert!(phase_error(&wrapped, 0.0) < 1e-9);
+        assert!(phase_error(&unwrapped, 0.0) > 1e-6);
+    }
+
+    #[test]
+    fn phase_wrapped() {
+        assert_eq!(Phase::new(2.25).unwrap().wrapped().value(), 0.25);
+        assert_eq!(Phase::new(-0.25).unwrap().wrapped().value(), 0.75);
+        assert_eq!(Phase::new(-1e-20).unwrap().wrapped().value(), 0.0);
+    }
+
     #[test]
     fn pre_order() {
         let node_children = vec![
@@ -0,0 +1,428 @@
+//! Deterministic generator of random schedules for property tests.
+//!
+//! Enabled by the `fuzz` feature, so that downstream crates can test their
+//! own schedule transformations against the same invariants as this crate.
+//! The generated trees only depend on the seed and the settings of
+//! [`ScheduleFuzzer`], not on the platform or an external random number
+//! generator.
+//!
+//! ```
+//! use bosing::{
+//!     fuzz::{check_invariants, ScheduleFuzzer},
+//!     prelude::*,
+//! };
+//!
+//! let channels = vec![ChannelId::new("xy"), ChannelId::new("z")];
+//! for seed in 0..100 {
+//!     let root = ScheduleFuzzer::new(seed, channels.clone()).generate();
+//!     check_invariants(&root, Time::new(1e-9)?)?;
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use ndarray::Array2;
+use thiserror::Error;
+
+use crate::{
+    executor::{self, path_segment, Executor},
+    pulse::{self, Sampler},
+    quant::{Amplitude, ChannelId, Frequency, Phase, ShapeId, Time},
+    schedule::{
+        Absolute, AbsoluteEntry, Barrier, Delay, Element, ElementCommon, ElementCommonBuilder,
+        ElementRef, ElementVariant, Grid, GridEntry, Measure as _, Overlay, PhaseRamp, Play,
+        Repeat, Reverse, Scale, SetFreq, SetPhase, ShiftFreq, ShiftPhase, Stack, SwapPhase,
+    },
+    shape::Shape,
+    Alignment, Direction, GridLength, GridLengthUnit, Overflow,
+};
+
+/// Sample rate used by [`check_invariants`]. All generated times are
+/// multiples of its sample interval.
+const SAMPLE_RATE: f64 = 4.0;
+
+/// Generator of random valid schedule trees.
+///
+/// Times are small multiples of 0.25, so the schedules are cheap to sample at
+/// unit scale. Explicit durations are never shorter than the measured
+/// content, so the trees can be executed without `allow_oversize`.
+#[derive(Debug, Clone)]
+pub struct ScheduleFuzzer {
+    rng: SplitMix64,
+    channels: Vec<ChannelId>,
+    max_depth: usize,
+    max_children: usize,
+}
+
+#[derive(Debug, Error)]
+pub enum Violation {
+    #[error("Element {path} measures {measure:?}, less than its total margin {margin:?}")]
+    MeasureBelowMargin {
+        path: String,
+        measure: Time,
+        margin: Time,
+    },
+    #[error("Layout check failed: {0}")]
+    Layout(executor::Error),
+    #[error("Execution failed: {0}")]
+    Execute(executor::Error),
+    #[error(transparent)]
+    Sampling(#[from] pulse::Error),
+    #[error("Sample {index} of channel '{channel}' is not finite")]
+    NonFiniteSample { channel: ChannelId, index: usize },
+}
+
+impl ScheduleFuzzer {
+    /// Creates a generator playing on `channels`, which should not be empty.
+    pub fn new(seed: u64, channels: Vec<ChannelId>) -> Self {
+        assert!(!channels.is_empty(), "At least one channel is required");
+        Self {
+            rng: SplitMix64(seed),
+            channels,
+            max_depth: 4,
+            max_children: 3,
+        }
+    }
+
+    /// Maximum number of nested containers. Defaults to 4.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Maximum number of children of a container. Defaults to 3.
+    pub fn with_max_children(mut self, max_children: usize) -> Self {
+        self.max_children = max_children.max(1);
+        self
+    }
+
+    pub fn generate(&mut self) -> ElementRef {
+        self.element(self.max_depth)
+    }
+
+    fn element(&mut self, depth: usize) -> ElementRef {
+        let variant = if depth == 0 || self.rng.chance(0.3) {
+            self.leaf()
+        } else {
+            self.container(depth - 1)
+        };
+        let common = self.common(&variant);
+        Arc::new(Element::new(common, variant))
+    }
+
+    fn common(&mut self, variant: &ElementVariant) -> ElementCommon {
+        let alignment = match self.rng.below(4) {
+            0 => Alignment::End,
+            1 => Alignment::Start,
+            2 => Alignment::Center,
+            _ => Alignment::Stretch,
+        };
+        let mut builder = ElementCommonBuilder::new();
+        builder
+            .margin((self.time(1.0), self.time(1.0)))
+            .alignment(alignment)
+            .phantom(self.rng.chance(0.1));
+        if self.rng.chance(0.2) {
+            builder.duration(Some(variant.measure() + self.time(2.0)));
+        }
+        builder
+            .build()
+            .expect("Generated parameters should be valid")
+    }
+
+    fn leaf(&mut self) -> ElementVariant {
+        let channel = self.channel();
+        match self.rng.below(8) {
+            0..=2 => self.play(channel).into(),
+            3 => ShiftPhase::new(channel, self.phase()).unwrap().into(),
+            4 => SetPhase::new(channel, self.phase()).unwrap().into(),
+            5 => ShiftFreq::new(channel, self.frequency()).unwrap().into(),
+            6 if self.rng.chance(0.5) => SetFreq::new(channel, self.frequency()).unwrap().into(),
+            6 => PhaseRamp::new(channel, self.frequency()).unwrap().into(),
+            _ if self.rng.chance(0.5) => SwapPhase::new(channel, self.channel()).into(),
+            _ => {
+                let channels = if self.rng.chance(0.5) {
+                    vec![]
+                } else {
+                    vec![channel]
+                };
+                if self.rng.chance(0.5) {
+                    Barrier::new(channels).into()
+                } else {
+                    Delay::new(channels, self.time(4.0)).unwrap().into()
+                }
+            }
+        }
+    }
+
+    fn play(&mut self, channel: ChannelId) -> Play {
+        let shape_id = self.rng.chance(0.5).then(|| ShapeId::new("hann"));
+        let amplitude = Amplitude::new(self.rng.below(9) as f64 * 0.25 - 1.0).unwrap();
+        Play::new(channel, shape_id, amplitude, self.time(4.0))
+            .unwrap()
+            .with_plateau(self.time(4.0))
+            .unwrap()
+            .with_drag_coef(self.rng.below(3) as f64 * 0.5)
+            .unwrap()
+            .with_frequency(self.frequency())
+            .unwrap()
+            .with_phase(self.phase())
+            .unwrap()
+            .with_flexible(self.rng.chance(0.2))
+            .with_centered(self.rng.chance(0.2))
+    }
+
+    fn container(&mut self, depth: usize) -> ElementVariant {
+        let kind = self.rng.below(7);
+        let count = match kind {
+            3..=5 => 1,
+            _ => 1 + self.rng.below(self.max_children as u64) as usize,
+        };
+        let mut children = Vec::with_capacity(count);
+        for _ in 0..count {
+            children.push(self.element(depth));
+        }
+        match kind {
+            0 => {
+                let direction = if self.rng.chance(0.5) {
+                    Direction::Backward
+                } else {
+                    Direction::Forward
+                };
+                Stack::new()
+                    .with_direction(direction)
+                    .with_children(children)
+                    .into()
+            }
+            1 => {
+                let mut entries = Vec::with_capacity(count);
+                for child in children {
+                    entries.push(AbsoluteEntry::new(child).with_time(self.time(8.0)).unwrap());
+                }
+                Absolute::new().with_children(entries).into()
+            }
+            2 => {
+                // Fixed columns narrower than a child would let the child
+                // spill out of the grid.
+                let widest = children
+                    .iter()
+                    .map(|c| c.measure())
+                    .max()
+                    .unwrap_or(Time::ZERO);
+                let n_columns = 1 + self.rng.below(3) as usize;
+                let mut columns = Vec::with_capacity(n_columns);
+                for _ in 0..n_columns {
+                    let column = match self.rng.below(3) {
+                        0 => GridLength {
+                            value: 0.0,
+                            unit: GridLengthUnit::Auto,
+                        },
+                        1 => GridLength {
+                            value: 1.0 + self.rng.below(3) as f64,
+                            unit: GridLengthUnit::Star,
+                        },
+                        _ => GridLength {
+                            value: (widest + self.time(4.0)).value(),
+                            unit: GridLengthUnit::Seconds,
+                        },
+                    };
+                    columns.push(column);
+                }
+                let mut entries = Vec::with_capacity(count);
+                for child in children {
+                    let column = self.rng.below(n_columns as u64) as usize;
+                    let span = 1 + self.rng.below((n_columns - column) as u64) as usize;
+                    let entry = GridEntry::new(child)
+                        .with_column(column)
+                        .with_span(span)
+                        .unwrap();
+                    entries.push(entry);
+                }
+                Grid::new()
+                    .with_columns(columns)
+                    .with_children(entries)
+                    .into()
+            }
+            3 => {
+                let count = 1 + self.rng.below(3) as usize;
+                let spacing = self.time(1.0);
+                Repeat::new(children.remove(0), count)
+                    .with_spacing(spacing)
+                    .unwrap()
+                    .into()
+            }
+            4 => {
+                let factor = [0.5, 1.0, 2.0][self.rng.below(3) as usize];
+                Scale::new(children.remove(0), factor).unwrap().into()
+            }
+            5 => Reverse::new(children.remove(0)).into(),
+            _ => Overlay::new().with_children(children).into(),
+        }
+    }
+
+    fn channel(&mut self) -> ChannelId {
+        let i = self.rng.below(self.channels.len() as u64) as usize;
+        self.channels[i].clone()
+    }
+
+    /// Multiple of 0.25 in `[0, max]`.
+    fn time(&mut self, max: f64) -> Time {
+        let steps = (max * SAMPLE_RATE) as u64;
+        Time::new(self.rng.below(steps + 1) as f64 / SAMPLE_RATE).unwrap()
+    }
+
+    fn phase(&mut self) -> Phase {
+        Phase::new(self.rng.below(8) as f64 * 0.125).unwrap()
+    }
+
+    fn frequency(&mut self) -> Frequency {
+        Frequency::new(self.rng.below(5) as f64 * 0.05).unwrap()
+    }
+}
+
+/// Shapes referenced by the [`Play`] elements of the generated schedules.
+pub fn shapes() -> Vec<(ShapeId, Shape)> {
+    vec![(ShapeId::new("hann"), Shape::new_hann())]
+}
+
+/// Checks the invariants which should hold for every valid schedule:
+///
+/// - Every element measures at least its total margin.
+/// - Every container arranges its children inside its own time range, and
+///   the children span its measured duration, see [`executor::check_layout`].
+/// - The schedule can be executed with the [`shapes`] and sampled into
+///   waveforms covering its duration, and all samples are finite.
+pub fn check_invariants(root: &ElementRef, time_tolerance: Time) -> Result<(), Violation> {
+    let mut path = vec![root.variant.name().to_string()];
+    check_margins(root, &mut path, time_tolerance)?;
+    executor::check_layout(root, time_tolerance).map_err(Violation::Layout)?;
+    let mut executor = Executor::new(Amplitude::ZERO, time_tolerance, false);
+    for n in root.channels() {
+        executor.add_channel(n.clone(), Frequency::new(0.1).unwrap(), true);
+    }
+    for (n, s) in shapes() {
+        executor.add_shape(n, s);
+    }
+    executor.execute(root).map_err(Violation::Execute)?;
+    let sample_rate = Frequency::new(SAMPLE_RATE).unwrap();
+    // Extra samples in case the envelope is longer than the pulse due to
+    // rounding.
+    let length = (root.measure().value() * SAMPLE_RATE).ceil() as usize + 2;
+    let mut waveforms: HashMap<_, _> = root
+        .channels()
+        .iter()
+        .map(|n| (n.clone(), Array2::zeros((2, length))))
+        .collect();
+    let mut sampler = Sampler::new(executor.into_result());
+    for (n, w) in &mut waveforms {
+        sampler.add_channel(
+            n.clone(),
+            w.view_mut(),
+            sample_rate,
+            Time::ZERO,
+            -10,
+            Overflow::Error,
+        );
+    }
+    sampler.sample(time_tolerance)?;
+    for (n, w) in waveforms {
+        let index = w
+            .columns()
+            .into_iter()
+            .position(|c| c.iter().any(|x| !x.is_finite()));
+        if let Some(index) = index {
+            return Err(Violation::NonFiniteSample { channel: n, index });
+        }
+    }
+    Ok(())
+}
+
+fn check_margins(
+    element: &ElementRef,
+    path: &mut Vec<String>,
+    time_tolerance: Time,
+) -> Result<(), Violation> {
+    let measure = element.measure();
+    let (start, end) = element.common.margin();
+    let margin = start + end;
+    if measure + time_tolerance < margin {
+        return Err(Violation::MeasureBelowMargin {
+            path: path.join(" > "),
+            measure,
+            margin,
+        });
+    }
+    let children: Vec<&ElementRef> = match &element.variant {
+        ElementVariant::Repeat(r) => vec![r.child()],
+        ElementVariant::Scale(s) => vec![s.child()],
+        ElementVariant::Reverse(v) => vec![v.child()],
+        ElementVariant::Stack(s) => s.children().iter().collect(),
+        ElementVariant::Absolute(a) => a.children().iter().map(|e| e.element()).collect(),
+        ElementVariant::Overlay(o) => o.children().iter().collect(),
+        ElementVariant::Grid(g) => g.children().iter().map(|e| e.element()).collect(),
+        _ => vec![],
+    };
+    for (i, child) in children.into_iter().enumerate() {
+        path.push(path_segment(i, child));
+        check_margins(child, path, time_tolerance)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+/// SplitMix64 generator, which is small and stable across versions.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform integer in `[0, n)`. The modulo bias is negligible for the
+    /// small `n` used here.
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64) < p * (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schedule::to_json;
+
+    use super::*;
+
+    fn channels() -> Vec<ChannelId> {
+        vec![ChannelId::new("a"), ChannelId::new("b")]
+    }
+
+    #[test]
+    fn deterministic() {
+        let first = ScheduleFuzzer::new(42, channels()).generate();
+        let second = ScheduleFuzzer::new(42, channels()).generate();
+        let other = ScheduleFuzzer::new(43, channels()).generate();
+
+        assert_eq!(to_json(&first).unwrap(), to_json(&second).unwrap());
+        assert_ne!(to_json(&first).unwrap(), to_json(&other).unwrap());
+    }
+
+    #[test]
+    fn invariants_hold() {
+        for seed in 0..200 {
+            let root = ScheduleFuzzer::new(seed, channels()).generate();
+            if let Err(e) = check_invariants(&root, Time::new(1e-9).unwrap()) {
+                panic!("seed {}: {}", seed, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,902 @@
+//! Waveform generation into caller-provided buffers.
+//!
+//! The buffers are borrowed as [`ArrayViewMut2`], so the waveforms can be
+//! written directly into preallocated memory, e.g. numpy arrays or DMA-ready
+//! memory pools, without an intermediate copy.
+
+use std::ops::Range;
+
+use hashbrown::HashMap;
+use ndarray::{
+    s, Array2, ArrayView1, ArrayView2, ArrayViewMut1, ArrayViewMut2, ArrayViewMut3, Axis,
+};
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    executor::marker_spans,
+    pulse::{
+        self, apply_blanking_inplace, apply_fir_inplace, apply_hold_inplace, apply_iir_inplace,
+        apply_iq_inplace, apply_offset_inplace, apply_predistortion_inplace, apply_ramp_inplace,
+        ChunkSampler, Crosstalk, EnvelopePrecision, OverflowStats, Predistortion, PulseList,
+        Sampler,
+    },
+    quant::{AlignedIndex, ChannelId, Frequency, Time},
+    schedule::ElementRef,
+    Overflow, Padding,
+};
+
+/// Sampling and post-processing parameters of a channel.
+#[derive(Debug, Clone)]
+pub struct ChannelConfig<'a> {
+    pub sample_rate: Frequency,
+    pub delay: Time,
+    pub align_level: i32,
+    pub padding: Padding,
+    pub ramp_length: usize,
+    /// Handling of pulses which extend past the end of the buffer.
+    pub overflow: Overflow,
+    pub iq_matrix: Option<ArrayView2<'a, f64>>,
+    pub offset: Option<ArrayView1<'a, f64>>,
+    pub iir: Option<ArrayView2<'a, f64>>,
+    pub fir: Option<ArrayView1<'a, f64>>,
+    /// Applied after the filters, so the offset is only predistorted if
+    /// `filter_offset` is set.
+    pub predistortion: Option<&'a Predistortion>,
+    pub filter_offset: bool,
+    /// Time ranges relative to the first sample which are forced to zero
+    /// after all other post-processing.
+    pub blanking: Vec<(Time, Time)>,
+    pub envelope_precision: EnvelopePrecision,
+    /// Second output of a differential pair, see [`Differential`].
+    pub differential: Option<Differential>,
+}
+
+/// Negative output of a differential pair.
+///
+/// The pulses of the channel are sampled a second time into the buffer of
+/// `negative` with the opposite sign and the delay increased by `skew`. Both
+/// outputs share the post-processing of the channel, so the offset is not
+/// inverted.
+#[derive(Debug, Clone)]
+pub struct Differential {
+    pub negative: ChannelId,
+    pub skew: Time,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("No buffer is provided for channel '{0}'")]
+    BufferNotFound(ChannelId),
+    #[error("Buffer of channel '{channel}' should have 1 or 2 rows, got shape {shape:?}")]
+    InvalidBufferShape {
+        channel: ChannelId,
+        shape: Vec<usize>,
+    },
+    #[error(
+        "IQ sweep of channel '{channel}' needs a buffer of shape ({points}, 2, n), got {shape:?}"
+    )]
+    InvalidSweepShape {
+        channel: ChannelId,
+        points: usize,
+        shape: Vec<usize>,
+    },
+    #[error("Output '{0}' is used by more than one channel")]
+    DuplicateOutput(ChannelId),
+    #[error("Marker channel '{0}' is not defined")]
+    MarkerChannelNotFound(ChannelId),
+    #[error("Channel '{0}' can't be sampled in chunks because its padding or filters depend on the whole waveform")]
+    ChunkUnsupported(ChannelId),
+    #[error(transparent)]
+    Sampling(#[from] pulse::Error),
+}
+
+/// Samples the pulse lists into `buffers` and applies the post-processing of
+/// each channel.
+///
+/// Each buffer has shape `(n, length)`, where `n` is 2 for complex channels and
+/// 1 for real channels. Channels with a [`Differential`] output need a second
+/// buffer of the same shape. Previous contents of the buffers are overwritten.
+/// Buffers of channels not in `channels` are left untouched.
+///
+/// Returns the statistics of the channels with pulses truncated or wrapped
+/// according to [`ChannelConfig::overflow`].
+pub fn sample_into(
+    pulse_lists: HashMap<ChannelId, PulseList>,
+    channels: &HashMap<ChannelId, ChannelConfig>,
+    buffers: &mut HashMap<ChannelId, ArrayViewMut2<f64>>,
+    crosstalk: Option<&Crosstalk>,
+    time_tolerance: Time,
+) -> Result<HashMap<ChannelId, OverflowStats>, Error> {
+    let outputs = outputs(channels)?;
+    for &n in outputs.keys() {
+        let buffer = buffers
+            .get(n)
+            .ok_or_else(|| Error::BufferNotFound(n.clone()))?;
+        if !matches!(buffer.shape(), [1 | 2, _]) {
+            return Err(Error::InvalidBufferShape {
+                channel: n.clone(),
+                shape: buffer.shape().to_vec(),
+            });
+        }
+    }
+    let mut sampler = Sampler::new(pulse_lists);
+    for (n, w) in buffers.iter_mut() {
+        let Some(o) = outputs.get(n) else {
+            continue;
+        };
+        let c = o.config;
+        w.fill(0.0);
+        sampler.add_channel(
+            n.clone(),
+            w.view_mut(),
+            c.sample_rate,
+            o.delay,
+            c.align_level,
+            c.overflow,
+        );
+        sampler.set_envelope_precision(n, c.envelope_precision);
+        sampler.set_source(n.clone(), o.source.clone());
+    }
+    if let Some(crosstalk) = crosstalk {
+        sampler.set_crosstalk(crosstalk.view());
+    }
+    let stats = sampler.sample(time_tolerance)?;
+    buffers.par_iter_mut().for_each(|(n, w)| {
+        if let Some(o) = outputs.get(n) {
+            if o.inverted {
+                w.map_inplace(|x| *x = -*x);
+            }
+            post_process(w, o.config);
+        }
+    });
+    Ok(stats)
+}
+
+/// Buffer written by a channel, which is either the channel itself or the
+/// negative output of a differential pair.
+struct Output<'c, 'a> {
+    source: &'c ChannelId,
+    config: &'c ChannelConfig<'a>,
+    delay: Time,
+    inverted: bool,
+}
+
+/// Outputs of the channels by buffer name.
+fn outputs<'c, 'a>(
+    channels: &'c HashMap<ChannelId, ChannelConfig<'a>>,
+) -> Result<HashMap<&'c ChannelId, Output<'c, 'a>>, Error> {
+    let mut outputs: HashMap<_, _> = channels
+        .iter()
+        .map(|(n, c)| {
+            let output = Output {
+                source: n,
+                config: c,
+                delay: c.delay,
+                inverted: false,
+            };
+            (n, output)
+        })
+        .collect();
+    for (n, c) in channels {
+        let Some(differential) = &c.differential else {
+            continue;
+        };
+        let output = Output {
+            source: n,
+            config: c,
+            delay: c.delay + differential.skew,
+            inverted: true,
+        };
+        if outputs.insert(&differential.negative, output).is_some() {
+            return Err(Error::DuplicateOutput(differential.negative.clone()));
+        }
+    }
+    Ok(outputs)
+}
+
+/// IQ matrix which scales the Q component by `gain` and rotates it by `phase`
+/// in cycles towards the I component, i.e. `I' = I` and
+/// `Q' = gain * (sin(2 pi phase) I + cos(2 pi phase) Q)`.
+pub fn iq_imbalance_matrix(gain: f64, phase: f64) -> Array2<f64> {
+    let (sin, cos) = (std::f64::consts::TAU * phase).sin_cos();
+    Array2::from_shape_vec((2, 2), vec![1.0, 0.0, gain * sin, gain * cos])
+        .expect("Shape should match")
+}
+
+/// Samples the pulses of a complex channel once and writes a copy
+/// post-processed with each of `iq_matrices` into `buffer`.
+///
+/// `buffer` has shape `(iq_matrices.len(), 2, length)`. The IQ matrix of
+/// `config` is replaced by the swept ones, the other post-processing steps are
+/// the same as in [`sample_into`]. The [`Differential`] output is not
+/// sampled.
+pub fn sample_iq_sweep<'a>(
+    pulse_lists: HashMap<ChannelId, PulseList>,
+    channel: &ChannelId,
+    config: &ChannelConfig<'a>,
+    iq_matrices: &'a [Array2<f64>],
+    mut buffer: ArrayViewMut3<f64>,
+    time_tolerance: Time,
+) -> Result<HashMap<ChannelId, OverflowStats>, Error> {
+    let [points, 2, length] = *buffer.shape() else {
+        return Err(Error::InvalidSweepShape {
+            channel: channel.clone(),
+            points: iq_matrices.len(),
+            shape: buffer.shape().to_vec(),
+        });
+    };
+    if points != iq_matrices.len() {
+        return Err(Error::InvalidSweepShape {
+            channel: channel.clone(),
+            points: iq_matrices.len(),
+            shape: buffer.shape().to_vec(),
+        });
+    }
+    let raw_config = ChannelConfig {
+        differential: None,
+        padding: Padding::Zero,
+        iq_matrix: None,
+        offset: None,
+        iir: None,
+        fir: None,
+        predistortion: None,
+        blanking: vec![],
+        ..config.clone()
+    };
+    let mut raw = Array2::zeros((2, length));
+    let channels = [(channel.clone(), raw_config)].into_iter().collect();
+    let mut buffers = [(channel.clone(), raw.view_mut())].into_iter().collect();
+    let stats = sample_into(pulse_lists, &channels, &mut buffers, None, time_tolerance)?;
+    drop(buffers);
+    buffer
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .zip(iq_matrices)
+        .for_each(|(mut w, iq_matrix)| {
+            w.assign(&raw);
+            let config = ChannelConfig {
+                iq_matrix: Some(iq_matrix.view()),
+                ..config.clone()
+            };
+            post_process(&mut w, &config);
+        });
+    Ok(stats)
+}
+
+/// Checks that the post-processing of the channels only depends on the
+/// current sample, which is required by [`sample_chunk_into`].
+pub fn check_chunked(channels: &HashMap<ChannelId, ChannelConfig>) -> Result<(), Error> {
+    for (n, c) in channels {
+        if c.padding != Padding::Zero || c.iir.is_some() || c.fir.is_some() {
+            return Err(Error::ChunkUnsupported(n.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Samples the chunk starting at sample `start` of each channel into
+/// `buffers` and applies the post-processing of each channel.
+///
+/// The buffers have the same layout as in [`sample_into`], but may be shorter
+/// than the channels. Channels of `channels` without a buffer are skipped, so
+/// channels which are already finished can be left out. The statistics of
+/// truncated or wrapped pulses are only returned for the first chunk.
+///
+/// The [`Differential`] outputs should be added to `sampler` with their delay
+/// and [`ChunkSampler::set_source`].
+pub fn sample_chunk_into(
+    sampler: &ChunkSampler,
+    channels: &HashMap<ChannelId, ChannelConfig>,
+    buffers: &mut HashMap<ChannelId, ArrayViewMut2<f64>>,
+    start: usize,
+    time_tolerance: Time,
+) -> Result<HashMap<ChannelId, OverflowStats>, Error> {
+    check_chunked(channels)?;
+    let outputs = outputs(channels)?;
+    for (n, w) in buffers.iter_mut() {
+        if !outputs.contains_key(n) {
+            continue;
+        }
+        if !matches!(w.shape(), [1 | 2, _]) {
+            return Err(Error::InvalidBufferShape {
+                channel: n.clone(),
+                shape: w.shape().to_vec(),
+            });
+        }
+        w.fill(0.0);
+    }
+    let stats = sampler.sample_chunk(start, buffers, time_tolerance)?;
+    buffers.par_iter_mut().for_each(|(n, w)| {
+        if let Some(o) = outputs.get(n) {
+            if o.inverted {
+                w.map_inplace(|x| *x = -*x);
+            }
+            post_process_chunk(w, o.config, start);
+        }
+    });
+    Ok(stats)
+}
+
+/// Post-processing steps of [`post_process`] which are allowed by
+/// [`check_chunked`].
+fn post_process_chunk(w: &mut ArrayViewMut2<f64>, c: &ChannelConfig, start: usize) {
+    if let Some(iq_matrix) = c.iq_matrix {
+        apply_iq_inplace(w, iq_matrix);
+    }
+    if let Some(offset) = c.offset.filter(|_| c.filter_offset) {
+        apply_offset_inplace(w, offset);
+    }
+    if let Some(predistortion) = c.predistortion {
+        apply_predistortion_inplace(w, predistortion);
+    }
+    if let Some(offset) = c.offset.filter(|_| !c.filter_offset) {
+        apply_offset_inplace(w, offset);
+    }
+    apply_blanking_inplace(w, &c.blanking, c.sample_rate, start);
+}
+
+fn post_process(w: &mut ArrayViewMut2<f64>, c: &ChannelConfig) {
+    match c.padding {
+        Padding::Zero => {}
+        Padding::Hold => apply_hold_inplace(w),
+        Padding::Ramp => apply_ramp_inplace(w, c.ramp_length),
+    }
+    if let Some(iq_matrix) = c.iq_matrix {
+        apply_iq_inplace(w, iq_matrix);
+    }
+    if c.filter_offset {
+        if let Some(offset) = c.offset {
+            apply_offset_inplace(w, offset);
+        }
+        if let Some(iir) = c.iir {
+            apply_iir_inplace(w, iir);
+        }
+        if let Some(fir) = c.fir {
+            apply_fir_inplace(w, fir);
+        }
+        if let Some(predistortion) = c.predistortion {
+            apply_predistortion_inplace(w, predistortion);
+        }
+    } else {
+        if let Some(iir) = c.iir {
+            apply_iir_inplace(w, iir);
+        }
+        if let Some(fir) = c.fir {
+            apply_fir_inplace(w, fir);
+        }
+        if let Some(predistortion) = c.predistortion {
+            apply_predistortion_inplace(w, predistortion);
+        }
+        if let Some(offset) = c.offset {
+            apply_offset_inplace(w, offset);
+        }
+    }
+    apply_blanking_inplace(w, &c.blanking, c.sample_rate, 0);
+}
+
+/// Sampling parameters of a digital channel, see [`sample_markers`].
+#[derive(Debug, Clone, Copy)]
+pub struct MarkerConfig {
+    pub sample_rate: Frequency,
+    pub delay: Time,
+    pub align_level: i32,
+}
+
+/// Sets the samples of the digital channels to one while a non-phantom
+/// [`Marker`](crate::schedule::Marker) is high. The edges are aligned in the
+/// same way as the start of pulses on analog channels, and the parts outside
+/// of the buffers are dropped.
+///
+/// Other samples of `buffers` are not changed, so the buffers should be
+/// zeroed before.
+pub fn sample_markers(
+    root: &ElementRef,
+    channels: &HashMap<ChannelId, MarkerConfig>,
+    buffers: &mut HashMap<ChannelId, ArrayViewMut1<u8>>,
+) -> Result<(), Error> {
+    for (_, marker, span) in marker_spans(root) {
+        let channel = marker.channel_id();
+        let c = channels
+            .get(channel)
+            .ok_or_else(|| Error::MarkerChannelNotFound(channel.clone()))?;
+        let buffer = buffers
+            .get_mut(channel)
+            .ok_or_else(|| Error::BufferNotFound(channel.clone()))?;
+        let length = buffer.len();
+        let to_index = |t: Time| {
+            let i = AlignedIndex::new(t + c.delay, c.sample_rate, c.align_level)
+                .expect("Marker time should be finite");
+            (i.value().max(0.0).ceil() as usize).min(length)
+        };
+        let start = to_index(span.start);
+        let end = to_index(span.start + span.span);
+        if start < end {
+            buffer.slice_mut(s![start..end]).fill(1);
+        }
+    }
+    Ok(())
+}
+
+/// Waveform stored as the runs of columns with a non-zero sample in any row.
+///
+/// The chunks of [`sample_chunk_into`] are pushed in order and runs touching
+/// the end of the previous chunk are extended, so the runs are the same as
+/// if the whole waveform was scanned at once.
+#[derive(Debug, Clone, Default)]
+pub struct SparseWaveform {
+    runs: Vec<(usize, Array2<f64>)>,
+    end: usize,
+}
+
+impl SparseWaveform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the chunk starting at sample `start`, which should be the end
+    /// of the previous chunk.
+    pub fn push_chunk(&mut self, start: usize, chunk: ArrayView2<f64>) {
+        assert_eq!(start, self.end, "Chunks should be contiguous");
+        for range in nonzero_runs(chunk) {
+            let columns = chunk.slice(s![.., range.clone()]);
+            match self.runs.last_mut() {
+                Some((run_start, run)) if range.start == 0 && *run_start + run.ncols() == start => {
+                    run.append(Axis(1), columns)
+                        .expect("Chunks should have the same number of rows");
+                }
+                _ => self.runs.push((start + range.start, columns.to_owned())),
+            }
+        }
+        self.end = start + chunk.ncols();
+    }
+
+    /// Start index and samples of each run.
+    pub fn into_runs(self) -> Vec<(usize, Array2<f64>)> {
+        self.runs
+    }
+}
+
+/// Ranges of the columns with a non-zero sample in any row.
+fn nonzero_runs(waveform: ArrayView2<f64>) -> Vec<Range<usize>> {
+    column_runs(waveform, |x| x != 0.0)
+}
+
+/// Ranges of the columns with a sample matching `predicate` in any row.
+fn column_runs(waveform: ArrayView2<f64>, predicate: impl Fn(f64) -> bool) -> Vec<Range<usize>> {
+    let mut runs = vec![];
+    let mut run_start = None;
+    for (i, column) in waveform.axis_iter(Axis(1)).enumerate() {
+        let matched = column.iter().any(|&x| predicate(x));
+        match (matched, run_start) {
+            (true, None) => run_start = Some(i),
+            (false, Some(start)) => {
+                runs.push(start..i);
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = run_start {
+        runs.push(start..waveform.ncols());
+    }
+    runs
+}
+
+/// Run of samples beyond the limit of [`find_clipping`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClippedRange {
+    pub range: Range<usize>,
+    /// Largest absolute value of the samples in the range.
+    pub peak: f64,
+}
+
+/// Finds the runs of columns with a sample whose absolute value exceeds
+/// `limit` in any row, e.g. to check the waveforms before uploading them to
+/// an instrument which would clip them silently.
+pub fn find_clipping(waveform: ArrayView2<f64>, limit: f64) -> Vec<ClippedRange> {
+    column_runs(waveform, |x| x.abs() > limit)
+        .into_iter()
+        .map(|range| {
+            let peak = waveform
+                .slice(s![.., range.clone()])
+                .fold(0.0, |peak: f64, x| peak.max(x.abs()));
+            ClippedRange { range, peak }
+        })
+        .collect()
+}
+
+/// Waveform quantized to signed 16-bit DAC codes, see [`quantize`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantized {
+    pub codes: Array2<i16>,
+    /// Value of one code, i.e. the full scale divided by `i16::MAX`.
+    pub scale: f64,
+    /// Number of samples beyond the full scale, which are clipped.
+    pub clipped: usize,
+}
+
+/// Quantizes the waveform to signed 16-bit codes with `full_scale` mapped to
+/// `i16::MAX`. The codes are symmetric, so `i16::MIN` is not used.
+///
+/// With `dither` set to a seed, triangular dither of one code peak is added
+/// before rounding, which decorrelates the quantization error from the signal
+/// at the cost of a slightly higher noise floor.
+///
+/// # Panics
+///
+/// Panics if `full_scale` is not positive and finite.
+pub fn quantize(waveform: ArrayView2<f64>, full_scale: f64, dither: Option<u64>) -> Quantized {
+    assert!(full_scale.is_finite() && full_scale > 0.0);
+    let max = f64::from(i16::MAX);
+    let scale = full_scale / max;
+    let mut rng = dither.map(SplitMix64);
+    let mut clipped = 0;
+    let codes = waveform.map(|&x| {
+        let mut code = x / scale;
+        if code.abs() > max {
+            clipped += 1;
+        }
+        if let Some(rng) = &mut rng {
+            code += rng.unit() - rng.unit();
+        }
+        code.round().clamp(-max, max) as i16
+    });
+    Quantized {
+        codes,
+        scale,
+        clipped,
+    }
+}
+
+/// Small deterministic generator for the dither, so that quantized waveforms
+/// are reproducible from the seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform number in `[0, 1)`.
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ndarray::{array, Array2};
+
+    use super::*;
+    use crate::{
+        executor::Executor,
+        quant::Amplitude,
+        schedule::{Element, ElementCommonBuilder, Marker, Play, Stack},
+        Direction,
+    };
+
+    fn pulse_lists() -> HashMap<ChannelId, PulseList> {
+        let play = Play::new(
+            ChannelId::new("a"),
+            None,
+            Amplitude::new(0.5).unwrap(),
+            Time::new(2.0).unwrap(),
+        )
+        .unwrap();
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            play,
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(ChannelId::new("a"), Frequency::ZERO, true);
+        executor.execute(&root).unwrap();
+        executor.into_result()
+    }
+
+    fn config<'a>() -> ChannelConfig<'a> {
+        ChannelConfig {
+            sample_rate: Frequency::new(1.0).unwrap(),
+            delay: Time::ZERO,
+            align_level: 0,
+            padding: Padding::Zero,
+            ramp_length: 0,
+            overflow: Overflow::Error,
+            iq_matrix: None,
+            offset: None,
+            iir: None,
+            fir: None,
+            predistortion: None,
+            filter_offset: false,
+            blanking: vec![],
+            envelope_precision: EnvelopePrecision::F64,
+            differential: None,
+        }
+    }
+
+    #[test]
+    fn sample_into_buffer() {
+        let offset = array![0.25];
+        let channels = [(
+            ChannelId::new("a"),
+            ChannelConfig {
+                offset: Some(offset.view()),
+                ..config()
+            },
+        )]
+        .into_iter()
+        .collect();
+        let mut memory = Array2::from_elem((1, 4), 9.0);
+        let mut buffers = [(ChannelId::new("a"), memory.view_mut())]
+            .into_iter()
+            .collect();
+
+        sample_into(pulse_lists(), &channels, &mut buffers, None, Time::ZERO).unwrap();
+
+        drop(buffers);
+        assert_eq!(memory, array![[0.75, 0.75, 0.25, 0.25]]);
+    }
+
+    #[test]
+    fn differential_output() {
+        let offset = array![0.25];
+        let channels = [(
+            ChannelId::new("a"),
+            ChannelConfig {
+                offset: Some(offset.view()),
+                differential: Some(Differential {
+                    negative: ChannelId::new("a_n"),
+                    skew: Time::new(1.0).unwrap(),
+                }),
+                ..config()
+            },
+        )]
+        .into_iter()
+        .collect();
+        let mut positive = Array2::zeros((1, 4));
+        let mut negative = Array2::zeros((1, 4));
+        let mut buffers = [
+            (ChannelId::new("a"), positive.view_mut()),
+            (ChannelId::new("a_n"), negative.view_mut()),
+        ]
+        .into_iter()
+        .collect();
+
+        sample_into(pulse_lists(), &channels, &mut buffers, None, Time::ZERO).unwrap();
+
+        drop(buffers);
+        assert_eq!(positive, array![[0.75, 0.75, 0.25, 0.25]]);
+        assert_eq!(negative, array![[0.25, -0.25, -0.25, 0.25]]);
+    }
+
+    #[test]
+    fn differential_output_conflict() {
+        let channels = [
+            (ChannelId::new("a"), config()),
+            (
+                ChannelId::new("b"),
+                ChannelConfig {
+                    differential: Some(Differential {
+                        negative: ChannelId::new("a"),
+                        skew: Time::ZERO,
+                    }),
+                    ..config()
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut buffers = HashMap::new();
+
+        let result = sample_into(pulse_lists(), &channels, &mut buffers, None, Time::ZERO);
+
+        assert!(matches!(result, Err(Error::DuplicateOutput(n)) if n == ChannelId::new("a")));
+    }
+
+    #[test]
+    fn blanking_after_offset() {
+        let offset = array![0.25];
+        let channels = [(
+            ChannelId::new("a"),
+            ChannelConfig {
+                offset: Some(offset.view()),
+                blanking: vec![(Time::new(1.0).unwrap(), Time::new(3.0).unwrap())],
+                ..config()
+            },
+        )]
+        .into_iter()
+        .collect();
+        let mut memory = Array2::zeros((1, 4));
+        let mut buffers = [(ChannelId::new("a"), memory.view_mut())]
+            .into_iter()
+            .collect();
+
+        sample_into(pulse_lists(), &channels, &mut buffers, None, Time::ZERO).unwrap();
+
+        drop(buffers);
+        assert_eq!(memory, array![[0.75, 0.0, 0.0, 0.25]]);
+    }
+
+    #[test]
+    fn phase_from_first_use() {
+        let play = Play::new(
+            ChannelId::new("a"),
+            None,
+            Amplitude::new(0.5).unwrap(),
+            Time::new(1.0).unwrap(),
+        )
+        .unwrap();
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(2.0).unwrap(), Time::ZERO))
+                .build()
+                .unwrap(),
+            play,
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(ChannelId::new("a"), Frequency::new(0.25).unwrap(), true);
+        executor.phase_from_first_use(ChannelId::new("a"));
+        executor.execute(&root).unwrap();
+        let channels = [(ChannelId::new("a"), config())].into_iter().collect();
+        let mut memory = Array2::zeros((2, 4));
+        let mut buffers = [(ChannelId::new("a"), memory.view_mut())]
+            .into_iter()
+            .collect();
+
+        sample_into(
+            executor.into_result(),
+            &channels,
+            &mut buffers,
+            None,
+            Time::ZERO,
+        )
+        .unwrap();
+
+        drop(buffers);
+        // Without the option the carrier would start with phase pi at t = 2.
+        float_cmp::assert_approx_eq!(f64, memory[[0, 2]], 0.5, epsilon = 1e-12);
+        float_cmp::assert_approx_eq!(f64, memory[[1, 2]], 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn iq_sweep() {
+        let offset = array![0.1, 0.0];
+        let config = ChannelConfig {
+            offset: Some(offset.view()),
+            ..config()
+        };
+        let matrices = [
+            iq_imbalance_matrix(1.0, 0.0),
+            iq_imbalance_matrix(2.0, 0.25),
+        ];
+        let mut memory = ndarray::Array3::zeros((2, 2, 4));
+
+        sample_iq_sweep(
+            pulse_lists(),
+            &ChannelId::new("a"),
+            &config,
+            &matrices,
+            memory.view_mut(),
+            Time::ZERO,
+        )
+        .unwrap();
+
+        let expected = array![
+            [[0.6, 0.6, 0.1, 0.1], [0.0, 0.0, 0.0, 0.0]],
+            [[0.6, 0.6, 0.1, 0.1], [1.0, 1.0, 0.0, 0.0]],
+        ];
+        for (x, y) in memory.iter().zip(&expected) {
+            float_cmp::assert_approx_eq!(f64, *x, *y, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn missing_buffer() {
+        let channels = [(ChannelId::new("a"), config())].into_iter().collect();
+        let mut buffers = HashMap::new();
+
+        let result = sample_into(pulse_lists(), &channels, &mut buffers, None, Time::ZERO);
+
+        assert!(matches!(result, Err(Error::BufferNotFound(_))));
+    }
+
+    #[test]
+    fn sparse_runs_across_chunks() {
+        let waveform = array![
+            [0.0, 1.0, 0.0, 0.0, 2.0, 3.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0, 0.0, 4.0, 5.0],
+        ];
+        let mut sparse = SparseWaveform::new();
+
+        sparse.push_chunk(0, waveform.slice(s![.., ..5]));
+        sparse.push_chunk(5, waveform.slice(s![.., 5..]));
+
+        assert_eq!(nonzero_runs(waveform.view()), [1..2, 4..7]);
+        let runs = sparse.into_runs();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0], (1, array![[1.0], [0.0]]));
+        assert_eq!(runs[1], (4, array![[2.0, 3.0, 0.0], [0.0, 4.0, 5.0]]));
+    }
+
+    #[test]
+    fn markers() {
+        let marker = |channel: &str, margin: f64| {
+            let common = ElementCommonBuilder::new()
+                .margin((Time::new(margin).unwrap(), Time::ZERO))
+                .build()
+                .unwrap();
+            let marker = Marker::new(ChannelId::new(channel), Time::new(3.0).unwrap()).unwrap();
+            Arc::new(Element::new(common, marker)) as ElementRef
+        };
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new()
+                .with_direction(Direction::Forward)
+                .with_children(vec![marker("m", 1.0), marker("n", 6.0)]),
+        ));
+        let config = MarkerConfig {
+            sample_rate: Frequency::new(1.0).unwrap(),
+            delay: Time::new(0.5).unwrap(),
+            align_level: 0,
+        };
+        let channels = [(ChannelId::new("m"), config), (ChannelId::new("n"), config)]
+            .into_iter()
+            .collect();
+        let mut m = ndarray::Array1::zeros(6);
+        let mut n = ndarray::Array1::zeros(6);
+        let mut buffers = [
+            (ChannelId::new("m"), m.view_mut()),
+            (ChannelId::new("n"), n.view_mut()),
+        ]
+        .into_iter()
+        .collect();
+
+        sample_markers(&root, &channels, &mut buffers).unwrap();
+
+        drop(buffers);
+        assert_eq!(m, array![0, 0, 1, 1, 1, 0]);
+        assert_eq!(n, array![0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn clipped_ranges() {
+        let waveform = array![[0.5, 1.2, 0.9, 0.0, -1.5], [0.0, 0.3, 1.1, 0.0, 0.0]];
+
+        let clipped = find_clipping(waveform.view(), 1.0);
+
+        assert_eq!(
+            clipped,
+            [
+                ClippedRange {
+                    range: 1..3,
+                    peak: 1.2
+                },
+                ClippedRange {
+                    range: 4..5,
+                    peak: 1.5
+                },
+            ]
+        );
+        assert!(find_clipping(waveform.view(), 1.5).is_empty());
+    }
+
+    #[test]
+    fn quantize_codes() {
+        let waveform = array![[0.0, 0.5, -1.0, 1.5, 1.0 / 32767.0]];
+
+        let quantized = quantize(waveform.view(), 1.0, None);
+
+        assert_eq!(quantized.codes, array![[0, 16384, -32767, 32767, 1]]);
+        assert_eq!(quantized.clipped, 1);
+        let dithered = quantize(waveform.view(), 1.0, Some(1));
+        assert_eq!(dithered, quantize(waveform.view(), 1.0, Some(1)));
+        let diff = &dithered.codes.mapv(i32::from) - &quantized.codes.mapv(i32::from);
+        assert!(diff.iter().all(|d| d.abs() <= 1));
+    }
+}
@@ -0,0 +1,172 @@
+//! Layout engine of the schedules for user-defined leaf types.
+//!
+//! The layout containers [`Stack`], [`Grid`], [`Absolute`] and [`Overlay`]
+//! are generic over their children, which only need to implement [`Measure`]
+//! and [`LayoutItem`]. The schedule elements of bosing are one instantiation
+//! of them, other time-domain scheduling problems like laser pulse sequences
+//! or acquisition windows can reuse the same solver with their own tree type.
+//!
+//! A container measures its children once and caches the result, so the tree
+//! should be built bottom-up and not changed afterwards. [`Arrange::arrange`]
+//! places the children of a container in the time range given to it, and the
+//! tree is laid out by walking it from the root with the measured duration.
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use bosing::layout::*;
+//!
+//! enum Node {
+//!     Pulse(&'static str, [ChannelId; 1], Time),
+//!     Stack(Stack<Arc<Node>>),
+//! }
+//!
+//! impl Measure for Node {
+//!     fn measure(&self) -> Time {
+//!         match self {
+//!             Node::Pulse(_, _, duration) => *duration,
+//!             Node::Stack(s) => s.measure(),
+//!         }
+//!     }
+//!
+//!     fn channels(&self) -> &[ChannelId] {
+//!         match self {
+//!             Node::Pulse(_, channels, _) => channels,
+//!             Node::Stack(s) => s.channels(),
+//!         }
+//!     }
+//! }
+//!
+//! impl LayoutItem for Node {}
+//!
+//! fn walk(node: &Node, time_range: TimeRange, out: &mut Vec<(&'static str, Time)>) {
+//!     match node {
+//!         Node::Pulse(name, _, _) => out.push((*name, time_range.start)),
+//!         Node::Stack(s) => {
+//!             for Arranged { item, time_range } in s.arrange(time_range) {
+//!                 walk(item, time_range, out);
+//!             }
+//!         }
+//!     }
+//! }
+//!
+//! let laser = ChannelId::new("laser");
+//! let pulse = |name, t| Arc::new(Node::Pulse(name, [laser.clone()], Time::new(t).unwrap()));
+//! let root = Node::Stack(
+//!     Stack::new()
+//!         .with_direction(Direction::Forward)
+//!         .with_children(vec![pulse("cool", 10.0), pulse("probe", 2.0)]),
+//! );
+//! let mut out = vec![];
+//! walk(&root, TimeRange { start: Time::ZERO, span: root.measure() }, &mut out);
+//! assert_eq!(out[1], ("probe", Time::new(10.0)?));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub use crate::{
+    quant::{ChannelId, Time},
+    schedule::{
+        Absolute, AbsoluteEntry, Arrange, Arranged, Grid, GridConflict, GridEntry, LayoutItem,
+        Measure, Overlay, Stack, TimeRange,
+    },
+    Alignment, Direction, GridLength, GridLengthUnit,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Window {
+        channels: Vec<ChannelId>,
+        duration: Time,
+        alignment: Alignment,
+    }
+
+    impl Measure for Window {
+        fn measure(&self) -> Time {
+            self.duration
+        }
+
+        fn channels(&self) -> &[ChannelId] {
+            &self.channels
+        }
+    }
+
+    impl LayoutItem for Window {
+        fn alignment(&self) -> Alignment {
+            self.alignment
+        }
+    }
+
+    fn window(channel: &str, duration: f64, alignment: Alignment) -> Window {
+        Window {
+            channels: vec![ChannelId::new(channel)],
+            duration: Time::new(duration).unwrap(),
+            alignment,
+        }
+    }
+
+    fn starts<'a, C: 'a>(arranged: impl Iterator<Item = Arranged<&'a C>>) -> Vec<f64> {
+        arranged.map(|a| a.time_range.start.value()).collect()
+    }
+
+    #[test]
+    fn custom_leaves() {
+        let time_range = |span: f64| TimeRange {
+            start: Time::ZERO,
+            span: Time::new(span).unwrap(),
+        };
+        let stack = Stack::new().with_children(vec![
+            window("a", 10.0, Alignment::End),
+            window("b", 5.0, Alignment::End),
+            window("a", 20.0, Alignment::End),
+        ]);
+        let columns = ["*", "auto"].map(|c| c.parse().unwrap()).to_vec();
+        let grid = Grid::new().with_columns(columns).with_children(vec![
+            GridEntry::new(window("a", 10.0, Alignment::Center)),
+            GridEntry::new(window("b", 30.0, Alignment::Start)).with_column(1),
+        ]);
+        let absolute = Absolute::new().with_children(vec![AbsoluteEntry::new(window(
+            "a",
+            10.0,
+            Alignment::End,
+        ))
+        .with_time(Time::new(5.0).unwrap())
+        .unwrap()]);
+
+        assert_eq!(stack.measure(), Time::new(30.0).unwrap());
+        assert_eq!(starts(stack.arrange(time_range(30.0))), [0.0, 25.0, 10.0]);
+        assert_eq!(grid.measure(), Time::new(40.0).unwrap());
+        assert_eq!(starts(grid.arrange(time_range(60.0))), [10.0, 30.0]);
+        assert_eq!(absolute.measure(), Time::new(15.0).unwrap());
+        assert_eq!(starts(absolute.arrange(time_range(15.0))), [5.0]);
+    }
+
+    #[test]
+    fn stack_alignment_and_spacing() {
+        let both = Window {
+            channels: vec![ChannelId::new("a"), ChannelId::new("b")],
+            duration: Time::new(10.0).unwrap(),
+            alignment: Alignment::Start,
+        };
+        let stack = Stack::new()
+            .with_direction(Direction::Forward)
+            .with_spacing(Time::new(5.0).unwrap())
+            .with_align_children(true)
+            .with_children(vec![
+                window("a", 10.0, Alignment::Start),
+                window("b", 30.0, Alignment::Start),
+                window("a", 10.0, Alignment::Center),
+                both,
+            ]);
+        let span = stack.measure();
+
+        assert_eq!(span, Time::new(45.0).unwrap());
+        let time_range = TimeRange {
+            start: Time::ZERO,
+            span,
+        };
+        assert_eq!(starts(stack.arrange(time_range)), [0.0, 0.0, 17.5, 35.0]);
+    }
+}
@@ -0,0 +1,993 @@
+//! Schedule optimization passes.
+//!
+//! Every pass rewrites the element tree into a tree with the same layout and
+//! the same waveform output, but which is cheaper to execute. Subtrees that are
+//! not touched by a pass are shared with the input tree.
+use std::{convert::Infallible, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, bail, Result};
+use float_cmp::approx_eq;
+use hashbrown::{HashMap, HashSet};
+
+use crate::{
+    quant::{Amplitude, ChannelId, ChirpRate, Frequency, Label, Phase, Time},
+    schedule::{
+        Absolute, AbsoluteEntry, Barrier, Delay, Element, ElementCommon, ElementRef,
+        ElementVariant, Grid, GridEntry, Marker, Measure, Overlay, ParamRef, PhaseRamp, Play,
+        Repeat, Reverse, Scale, SetFreq, SetPhase, ShiftFreq, ShiftPhase, Stack, SwapPhase,
+    },
+    Alignment, Direction,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Pass {
+    /// Merge adjacent [`ShiftPhase`] children of a [`Stack`] targeting the same
+    /// channel.
+    MergeShiftPhase,
+    /// Replace zero-amplitude [`Play`] elements with [`Barrier`] elements of
+    /// the same duration.
+    RemoveZeroAmplitude,
+    /// Replace a [`Stack`] with a single child by the child itself.
+    CollapseStack,
+    /// Replace a [`Stack`] of identical children by a [`Repeat`].
+    FoldRepeat,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Optimizer {
+    passes: Vec<Pass>,
+    amp_tolerance: Amplitude,
+}
+
+struct Rewriter<F> {
+    f: F,
+    memo: HashMap<*const Element, ElementRef>,
+}
+
+impl Pass {
+    pub(crate) const ALL: [Pass; 4] = [
+        Pass::MergeShiftPhase,
+        Pass::RemoveZeroAmplitude,
+        Pass::CollapseStack,
+        Pass::FoldRepeat,
+    ];
+}
+
+impl FromStr for Pass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge_shift_phase" => Ok(Pass::MergeShiftPhase),
+            "remove_zero_amplitude" => Ok(Pass::RemoveZeroAmplitude),
+            "collapse_stack" => Ok(Pass::CollapseStack),
+            "fold_repeat" => Ok(Pass::FoldRepeat),
+            _ => Err(anyhow!("Invalid optimization pass: {}", s)),
+        }
+    }
+}
+
+impl Optimizer {
+    pub(crate) fn new(passes: Vec<Pass>, amp_tolerance: Amplitude) -> Self {
+        Self {
+            passes,
+            amp_tolerance,
+        }
+    }
+
+    /// Run all passes in order.
+    pub(crate) fn optimize(&self, root: &ElementRef) -> ElementRef {
+        self.passes
+            .iter()
+            .fold(root.clone(), |root, &pass| self.run_pass(pass, &root))
+    }
+
+    fn run_pass(&self, pass: Pass, root: &ElementRef) -> ElementRef {
+        match pass {
+            Pass::MergeShiftPhase => Rewriter::new(merge_shift_phase).rewrite(root),
+            Pass::RemoveZeroAmplitude => {
+                Rewriter::new(|e: &ElementRef| remove_zero_amplitude(e, self.amp_tolerance))
+                    .rewrite(root)
+            }
+            Pass::CollapseStack => Rewriter::new(collapse_stack).rewrite(root),
+            Pass::FoldRepeat => Rewriter::new(fold_repeat).rewrite(root),
+        }
+    }
+}
+
+impl<F> Rewriter<F>
+where
+    F: FnMut(&ElementRef) -> Option<ElementRef>,
+{
+    fn new(f: F) -> Self {
+        Self {
+            f,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Rewrite the tree bottom-up.
+    ///
+    /// Results are memoized by node address so that shared subtrees stay
+    /// shared after rewriting.
+    fn rewrite(&mut self, element: &ElementRef) -> ElementRef {
+        let key = Arc::as_ptr(element);
+        if let Some(result) = self.memo.get(&key) {
+            return result.clone();
+        }
+        let element_with_children = self.rewrite_children(element);
+        let result = (self.f)(&element_with_children).unwrap_or(element_with_children);
+        self.memo.insert(key, result.clone());
+        result
+    }
+
+    fn rewrite_children(&mut self, element: &ElementRef) -> ElementRef {
+        let result: Result<_, Infallible> = try_map_children(element, |e| Ok(self.rewrite(e)));
+        result.unwrap_or_else(|e| match e {})
+    }
+}
+
+/// Rebuilds the element with each child replaced by `f(child)`. The element
+/// itself is returned if no child changed.
+fn try_map_children<E>(
+    element: &ElementRef,
+    mut f: impl FnMut(&ElementRef) -> Result<ElementRef, E>,
+) -> Result<ElementRef, E> {
+    let variant: Option<ElementVariant> = match &element.variant {
+        ElementVariant::Repeat(r) => {
+            let child = f(r.child())?;
+            (!Arc::ptr_eq(&child, r.child())).then(|| {
+                Repeat::new(child, r.count())
+                    .with_spacing(r.spacing())
+                    .expect("Should be a valid spacing")
+                    .into()
+            })
+        }
+        ElementVariant::Scale(c) => {
+            let child = f(c.child())?;
+            (!Arc::ptr_eq(&child, c.child())).then(|| {
+                Scale::new(child, c.factor())
+                    .expect("Should be a valid factor")
+                    .into()
+            })
+        }
+        ElementVariant::Reverse(v) => {
+            let child = f(v.child())?;
+            (!Arc::ptr_eq(&child, v.child())).then(|| Reverse::new(child).into())
+        }
+        ElementVariant::Stack(s) => {
+            let children = s
+                .children()
+                .iter()
+                .map(&mut f)
+                .collect::<Result<Vec<_>, _>>()?;
+            any_changed(s.children().iter(), &children).then(|| rebuild_stack(s, children).into())
+        }
+        ElementVariant::Absolute(a) => {
+            let children = a
+                .children()
+                .iter()
+                .map(|e| f(e.element()))
+                .collect::<Result<Vec<_>, _>>()?;
+            any_changed(a.children().iter().map(|e| e.element()), &children).then(|| {
+                let entries = a
+                    .children()
+                    .iter()
+                    .zip(children)
+                    .map(|(entry, element)| {
+                        AbsoluteEntry::new(element)
+                            .with_time(entry.time())
+                            .expect("Should be a valid time")
+                    })
+                    .collect();
+                Absolute::new().with_children(entries).into()
+            })
+        }
+        ElementVariant::Overlay(o) => {
+            let children = o
+                .children()
+                .iter()
+                .map(&mut f)
+                .collect::<Result<Vec<_>, _>>()?;
+            any_changed(o.children().iter(), &children)
+                .then(|| Overlay::new().with_children(children).into())
+        }
+        ElementVariant::Grid(g) => {
+            let children = g
+                .children()
+                .iter()
+                .map(|e| f(e.element()))
+                .collect::<Result<Vec<_>, _>>()?;
+            any_changed(g.children().iter().map(|e| e.element()), &children).then(|| {
+                let entries = g
+                    .children()
+                    .iter()
+                    .zip(children)
+                    .map(|(entry, element)| {
+                        GridEntry::new(element)
+                            .with_column(entry.column())
+                            .with_span(entry.span())
+                            .and_then(|e| e.with_row(entry.row()).with_row_span(entry.row_span()))
+                            .expect("Should be a valid span")
+                    })
+                    .collect();
+                Grid::new()
+                    .with_columns(g.columns().to_vec())
+                    .with_rows(g.rows().to_vec())
+                    .with_children(entries)
+                    .into()
+            })
+        }
+        _ => None,
+    };
+    Ok(match variant {
+        Some(variant) => Arc::new(Element::new(element.common.clone(), variant)),
+        None => element.clone(),
+    })
+}
+
+fn any_changed<'a>(old: impl Iterator<Item = &'a ElementRef>, new: &[ElementRef]) -> bool {
+    old.zip(new).any(|(o, n)| !Arc::ptr_eq(o, n))
+}
+
+fn rebuild_stack(stack: &Stack, children: Vec<ElementRef>) -> Stack {
+    Stack::new()
+        .with_direction(stack.direction())
+        .with_auto_barrier(stack.auto_barrier())
+        .with_spacing(stack.spacing())
+        .with_align_children(stack.align_children())
+        .with_children(children)
+}
+
+/// Whether the element takes exactly the duration of its content and is
+/// executed.
+fn is_layout_neutral(common: &ElementCommon) -> bool {
+    !common.phantom()
+        && common.margin() == (Time::ZERO, Time::ZERO)
+        && common.duration().is_none()
+        && common.min_duration() == Time::ZERO
+        && common.max_duration() == Time::INFINITY
+        && common.duration_ref().is_none()
+}
+
+fn merge_shift_phase(element: &ElementRef) -> Option<ElementRef> {
+    fn as_shift_phase(element: &Element) -> Option<&ShiftPhase> {
+        if !is_layout_neutral(&element.common) {
+            return None;
+        }
+        (&element.variant).try_into().ok()
+    }
+
+    let stack: &Stack = (&element.variant).try_into().ok()?;
+    let mut children: Vec<ElementRef> = Vec::with_capacity(stack.children().len());
+    let mut changed = false;
+    for child in stack.children() {
+        let merged = match (
+            children.last().and_then(|e| as_shift_phase(e)),
+            as_shift_phase(child),
+        ) {
+            (Some(prev), Some(next)) if prev.channel_id() == next.channel_id() => {
+                ShiftPhase::new(next.channel_id().clone(), prev.phase() + next.phase()).ok()
+            }
+            _ => None,
+        };
+        match merged {
+            Some(merged) => {
+                let last = children.last_mut().expect("Should have a previous child");
+                *last = Arc::new(Element::new(last.common.clone(), merged));
+                changed = true;
+            }
+            None => children.push(child.clone()),
+        }
+    }
+    changed.then(|| {
+        Arc::new(Element::new(
+            element.common.clone(),
+            rebuild_stack(stack, children),
+        ))
+    })
+}
+
+fn remove_zero_amplitude(element: &ElementRef, amp_tolerance: Amplitude) -> Option<ElementRef> {
+    let play: &Play = (&element.variant).try_into().ok()?;
+    if !approx_eq!(
+        f64,
+        play.amplitude().value(),
+        0.0,
+        epsilon = amp_tolerance.value()
+    ) {
+        return None;
+    }
+    // The barrier measures zero, so it needs the duration of the play as its
+    // minimum. The maximum is kept for plays which fill the time given by the
+    // parent, e.g. flexible or stretched ones.
+    let common = element
+        .common
+        .to_builder()
+        .min_duration(element.inner_duration())
+        .build()
+        .ok()?;
+    let barrier = Barrier::new(vec![play.channel_id().clone()]);
+    Some(Arc::new(Element::new(common, barrier)))
+}
+
+fn collapse_stack(element: &ElementRef) -> Option<ElementRef> {
+    let stack: &Stack = (&element.variant).try_into().ok()?;
+    let [child] = stack.children() else {
+        return None;
+    };
+    let alignment = element.common.alignment();
+    if !is_layout_neutral(&element.common)
+        || alignment == Alignment::Stretch
+        || child.common.alignment() != alignment
+    {
+        return None;
+    }
+    Some(child.clone())
+}
+
+fn fold_repeat(element: &ElementRef) -> Option<ElementRef> {
+    let stack: &Stack = (&element.variant).try_into().ok()?;
+    let (first, rest) = stack.children().split_first()?;
+    if rest.is_empty() || !rest.iter().all(|c| Arc::ptr_eq(c, first)) {
+        return None;
+    }
+    // Backward stacks align children to the end of the available span, while
+    // repeat always starts from the beginning.
+    if stack.direction() == Direction::Backward
+        && !(is_layout_neutral(&element.common) && element.common.alignment() != Alignment::Stretch)
+    {
+        return None;
+    }
+    let repeat = Repeat::new(first.clone(), stack.children().len());
+    Some(Arc::new(Element::new(element.common.clone(), repeat)))
+}
+
+/// Follows alias chains so that every alias maps directly to a name which is
+/// not an alias itself.
+pub(crate) fn canonicalize_aliases(
+    aliases: &HashMap<ChannelId, ChannelId>,
+) -> Result<HashMap<ChannelId, ChannelId>> {
+    aliases
+        .keys()
+        .map(|alias| {
+            let mut target = alias;
+            for _ in 0..=aliases.len() {
+                match aliases.get(target) {
+                    Some(next) => target = next,
+                    None => return Ok((alias.clone(), target.clone())),
+                }
+            }
+            Err(anyhow!("Cyclic channel alias: {}", alias))
+        })
+        .collect()
+}
+
+/// Replaces aliased channel ids in the tree by their targets.
+///
+/// Unlike the optimization passes this changes the channels of the schedule,
+/// so it should run before layout. `aliases` should be canonical, see
+/// [`canonicalize_aliases`].
+pub(crate) fn resolve_aliases(
+    root: &ElementRef,
+    aliases: &HashMap<ChannelId, ChannelId>,
+) -> ElementRef {
+    if aliases.is_empty() {
+        return root.clone();
+    }
+    Rewriter::new(|e: &ElementRef| rename_channels(e, aliases)).rewrite(root)
+}
+
+fn rename_channels(
+    element: &ElementRef,
+    aliases: &HashMap<ChannelId, ChannelId>,
+) -> Option<ElementRef> {
+    if !element.channels().iter().any(|c| aliases.contains_key(c)) {
+        return None;
+    }
+    let rename = |id: &ChannelId| aliases.get(id).unwrap_or(id).clone();
+    let rename_all = |ids: &[ChannelId]| {
+        let mut channel_ids = Vec::with_capacity(ids.len());
+        for id in ids.iter().map(rename) {
+            if !channel_ids.contains(&id) {
+                channel_ids.push(id);
+            }
+        }
+        channel_ids
+    };
+    let variant: ElementVariant = match &element.variant {
+        ElementVariant::Play(v) => v.clone().with_channel_id(rename(v.channel_id())).into(),
+        ElementVariant::ShiftPhase(v) => ShiftPhase::new(rename(v.channel_id()), v.phase())
+            .expect("Should be a valid phase")
+            .into(),
+        ElementVariant::SetPhase(v) => SetPhase::new(rename(v.channel_id()), v.phase())
+            .expect("Should be a valid phase")
+            .into(),
+        ElementVariant::ShiftFreq(v) => ShiftFreq::new(rename(v.channel_id()), v.frequency())
+            .expect("Should be a valid frequency")
+            .into(),
+        ElementVariant::SetFreq(v) => SetFreq::new(rename(v.channel_id()), v.frequency())
+            .expect("Should be a valid frequency")
+            .into(),
+        ElementVariant::PhaseRamp(v) => PhaseRamp::new(rename(v.channel_id()), v.rate())
+            .expect("Should be a valid rate")
+            .into(),
+        ElementVariant::SwapPhase(v) => {
+            SwapPhase::new(rename(v.channel_id1()), rename(v.channel_id2())).into()
+        }
+        ElementVariant::Barrier(v) => Barrier::new(rename_all(v.channel_ids())).into(),
+        ElementVariant::Delay(v) => Delay::new(rename_all(v.channel_ids()), v.delay())
+            .expect("Should be a valid delay")
+            .into(),
+        ElementVariant::Marker(v) => Marker::new(rename(v.channel_id()), v.width())
+            .expect("Should be a valid width")
+            .into(),
+        ElementVariant::Grid(v) if v.rows().iter().flatten().any(|c| aliases.contains_key(c)) => {
+            let rows = v.rows().iter().map(|r| rename_all(r)).collect();
+            v.clone().with_rows(rows).into()
+        }
+        // Containers are rebuilt from their rewritten children.
+        _ => return None,
+    };
+    Some(Arc::new(Element::new(element.common.clone(), variant)))
+}
+
+/// Replaces the duration references in a tree by fixed durations.
+///
+/// A reference resolves to its factor times the measured duration, including
+/// margins, of the first element with the label in depth-first order. The
+/// referenced element can't contain the referencing element, neither directly
+/// nor through other references. Like [`resolve_aliases`], this should run
+/// before layout.
+pub(crate) struct DurationResolver {
+    targets: HashMap<Label, ElementRef>,
+    has_refs: bool,
+    memo: HashMap<*const Element, ElementRef>,
+    visiting: HashSet<*const Element>,
+}
+
+impl DurationResolver {
+    pub(crate) fn new(root: &ElementRef) -> Self {
+        let mut resolver = Self {
+            targets: HashMap::new(),
+            has_refs: false,
+            memo: HashMap::new(),
+            visiting: HashSet::new(),
+        };
+        resolver.collect_targets(root, &mut HashSet::new());
+        resolver
+    }
+
+    pub(crate) fn resolve(&mut self, root: &ElementRef) -> Result<ElementRef> {
+        if !self.has_refs {
+            return Ok(root.clone());
+        }
+        self.resolve_element(root)
+    }
+
+    /// Address of the resolved counterpart of an element of the input tree.
+    pub(crate) fn resolved_ptr(&self, element: *const Element) -> *const Element {
+        self.memo.get(&element).map_or(element, Arc::as_ptr)
+    }
+
+    fn collect_targets(&mut self, element: &ElementRef, visited: &mut HashSet<*const Element>) {
+        if !visited.insert(Arc::as_ptr(element)) {
+            return;
+        }
+        if let Some(label) = element.common.label() {
+            self.targets
+                .entry(label.clone())
+                .or_insert_with(|| element.clone());
+        }
+        self.has_refs |= element.common.duration_ref().is_some();
+        let children: Vec<&ElementRef> = match &element.variant {
+            ElementVariant::Repeat(r) => vec![r.child()],
+            ElementVariant::Scale(s) => vec![s.child()],
+            ElementVariant::Reverse(v) => vec![v.child()],
+            ElementVariant::Stack(s) => s.children().iter().collect(),
+            ElementVariant::Absolute(a) => a.children().iter().map(|e| e.element()).collect(),
+            ElementVariant::Overlay(o) => o.children().iter().collect(),
+            ElementVariant::Grid(g) => g.children().iter().map(|e| e.element()).collect(),
+            _ => vec![],
+        };
+        for child in children {
+            self.collect_targets(child, visited);
+        }
+    }
+
+    fn resolve_element(&mut self, element: &ElementRef) -> Result<ElementRef> {
+        let key = Arc::as_ptr(element);
+        if let Some(result) = self.memo.get(&key) {
+            return Ok(result.clone());
+        }
+        self.visiting.insert(key);
+        let mut result = try_map_children(element, |e| self.resolve_element(e))?;
+        if let Some(duration_ref) = element.common.duration_ref() {
+            let label = duration_ref.label();
+            let Some(target) = self.targets.get(label).cloned() else {
+                bail!("Label of duration reference not found: {}", label);
+            };
+            if self.visiting.contains(&Arc::as_ptr(&target)) {
+                bail!("Cyclic duration reference: {}", label);
+            }
+            let duration = self.resolve_element(&target)?.measure() * duration_ref.factor();
+            let common = element
+                .common
+                .to_builder()
+                .duration(Some(duration))
+                .duration_ref(None)
+                .build()?;
+            result = Arc::new(Element::new(common, result.variant.clone()));
+        }
+        self.visiting.remove(&key);
+        self.memo.insert(key, result.clone());
+        Ok(result)
+    }
+}
+
+/// Replaces the duration references in the tree, see [`DurationResolver`].
+pub(crate) fn resolve_duration_refs(root: &ElementRef) -> Result<ElementRef> {
+    DurationResolver::new(root).resolve(root)
+}
+
+/// Fills in the fields bound to parameters, see [`ParamRef`].
+///
+/// Every parameter in the tree should have a value, and the tree can be bound
+/// once per set of values, e.g. for every point of a scan. Subtrees without
+/// parameters are shared with the input tree.
+pub(crate) fn bind_params(root: &ElementRef, values: &HashMap<String, f64>) -> Result<ElementRef> {
+    let mut memo = HashMap::new();
+    bind_element(root, values, &mut memo)
+}
+
+fn bind_element(
+    element: &ElementRef,
+    values: &HashMap<String, f64>,
+    memo: &mut HashMap<*const Element, ElementRef>,
+) -> Result<ElementRef> {
+    let key = Arc::as_ptr(element);
+    if let Some(result) = memo.get(&key) {
+        return Ok(result.clone());
+    }
+    let mut result = try_map_children(element, |e| bind_element(e, values, memo))?;
+    if !element.common.params().is_empty() {
+        let mut variant = result.variant.clone();
+        for param in element.common.params() {
+            let Some(&value) = values.get(param.name()) else {
+                bail!("Parameter '{}' is not bound", param.name());
+            };
+            variant = set_field(variant, param.field(), value)?;
+        }
+        let common = element.common.to_builder().params(vec![]).build()?;
+        result = Arc::new(Element::new(common, variant));
+    }
+    memo.insert(key, result.clone());
+    Ok(result)
+}
+
+/// Names of the parameters in the tree, sorted.
+pub(crate) fn param_names(root: &ElementRef) -> Vec<String> {
+    let mut names: Vec<_> = collect_params(root)
+        .into_iter()
+        .map(ParamRef::name)
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names.into_iter().map(String::from).collect()
+}
+
+/// Names of the parameters bound to a phase field.
+pub(crate) fn phase_param_names(root: &ElementRef) -> HashSet<String> {
+    collect_params(root)
+        .into_iter()
+        .filter(|p| p.field() == "phase")
+        .map(|p| p.name().to_string())
+        .collect()
+}
+
+fn collect_params(root: &ElementRef) -> Vec<&ParamRef> {
+    fn collect<'a>(
+        element: &'a ElementRef,
+        visited: &mut HashSet<*const Element>,
+        params: &mut Vec<&'a ParamRef>,
+    ) {
+        if !visited.insert(Arc::as_ptr(element)) {
+            return;
+        }
+        params.extend(element.common.params());
+        let children: Vec<&ElementRef> = match &element.variant {
+            ElementVariant::Repeat(r) => vec![r.child()],
+            ElementVariant::Scale(s) => vec![s.child()],
+            ElementVariant::Reverse(v) => vec![v.child()],
+            ElementVariant::Stack(s) => s.children().iter().collect(),
+            ElementVariant::Absolute(a) => a.children().iter().map(|e| e.element()).collect(),
+            ElementVariant::Overlay(o) => o.children().iter().collect(),
+            ElementVariant::Grid(g) => g.children().iter().map(|e| e.element()).collect(),
+            _ => vec![],
+        };
+        for child in children {
+            collect(child, visited, params);
+        }
+    }
+    let mut params = vec![];
+    collect(root, &mut HashSet::new(), &mut params);
+    params
+}
+
+/// Replaces a numeric field of the element, e.g. the `amplitude` of a
+/// [`Play`]. Fields of containers other than the `spacing` of a [`Repeat`]
+/// can't be replaced. Phases are in cycles.
+pub(crate) fn set_field(
+    variant: ElementVariant,
+    field: &str,
+    value: f64,
+) -> Result<ElementVariant> {
+    let variant = match (variant, field) {
+        (ElementVariant::Play(v), "amplitude") => v.with_amplitude(Amplitude::new(value)?)?.into(),
+        (ElementVariant::Play(v), "width") => v.with_width(Time::new(value)?)?.into(),
+        (ElementVariant::Play(v), "plateau") => v.with_plateau(Time::new(value)?)?.into(),
+        (ElementVariant::Play(v), "drag_coef") => v.with_drag_coef(value)?.into(),
+        (ElementVariant::Play(v), "frequency") => v.with_frequency(Frequency::new(value)?)?.into(),
+        (ElementVariant::Play(v), "chirp_rate") => {
+            v.with_chirp_rate(ChirpRate::new(value)?)?.into()
+        }
+        (ElementVariant::Play(v), "phase") => v.with_phase(Phase::new(value)?)?.into(),
+        (ElementVariant::ShiftPhase(v), "phase") => {
+            ShiftPhase::new(v.channel_id().clone(), Phase::new(value)?)?.into()
+        }
+        (ElementVariant::SetPhase(v), "phase") => {
+            SetPhase::new(v.channel_id().clone(), Phase::new(value)?)?.into()
+        }
+        (ElementVariant::ShiftFreq(v), "frequency") => {
+            ShiftFreq::new(v.channel_id().clone(), Frequency::new(value)?)?.into()
+        }
+        (ElementVariant::SetFreq(v), "frequency") => {
+            SetFreq::new(v.channel_id().clone(), Frequency::new(value)?)?.into()
+        }
+        (ElementVariant::PhaseRamp(v), "rate") => {
+            PhaseRamp::new(v.channel_id().clone(), Frequency::new(value)?)?.into()
+        }
+        (ElementVariant::Delay(v), "delay") => {
+            Delay::new(v.channel_ids().to_vec(), Time::new(value)?)?.into()
+        }
+        (ElementVariant::Marker(v), "width") => {
+            Marker::new(v.channel_id().clone(), Time::new(value)?)?.into()
+        }
+        (ElementVariant::Repeat(v), "spacing") => Repeat::new(v.child().clone(), v.count())
+            .with_spacing(Time::new(value)?)?
+            .into(),
+        (variant, field) => bail!("{} has no parameter '{}'", variant.name(), field),
+    };
+    Ok(variant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        quant::Phase,
+        schedule::{
+            test_util::{play, play_with},
+            Arrange as _, DurationRef, ElementCommonBuilder, TimeRange,
+        },
+    };
+
+    fn common() -> ElementCommon {
+        ElementCommonBuilder::new().build().unwrap()
+    }
+
+    fn element(variant: impl Into<ElementVariant>) -> ElementRef {
+        Arc::new(Element::new(common(), variant))
+    }
+
+    fn shift_phase(channel: &str, phase: f64) -> ElementRef {
+        element(ShiftPhase::new(ChannelId::new(channel), Phase::new(phase).unwrap()).unwrap())
+    }
+
+    fn stack(children: Vec<ElementRef>) -> ElementRef {
+        element(Stack::new().with_children(children))
+    }
+
+    fn optimize(pass: Pass, root: &ElementRef) -> ElementRef {
+        Optimizer::new(vec![pass], Amplitude::new(1e-6).unwrap()).optimize(root)
+    }
+
+    #[test]
+    fn merge_shift_phase() {
+        let root = stack(vec![
+            shift_phase("a", 0.1),
+            shift_phase("a", 0.2),
+            shift_phase("b", 0.3),
+            play("a", 1.0, None),
+            shift_phase("a", 0.4),
+        ]);
+
+        let result = optimize(Pass::MergeShiftPhase, &root);
+
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        let phases: Vec<_> = stack
+            .children()
+            .iter()
+            .filter_map(|c| <&ShiftPhase>::try_from(&c.variant).ok())
+            .map(|s| (s.channel_id().to_string(), s.phase().value()))
+            .collect();
+        assert_eq!(stack.children().len(), 4);
+        assert_eq!(phases.len(), 3);
+        assert_eq!(phases[0].0, "a");
+        float_cmp::assert_approx_eq!(f64, phases[0].1, 0.3);
+        assert_eq!(phases[1], ("b".to_string(), 0.3));
+        assert_eq!(phases[2], ("a".to_string(), 0.4));
+    }
+
+    #[test]
+    fn remove_zero_amplitude_keeps_duration() {
+        let root = stack(vec![play("a", 0.0, None), play("a", 1.0, None)]);
+
+        let result = optimize(Pass::RemoveZeroAmplitude, &root);
+
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        let removed = &stack.children()[0];
+        assert!(<&Barrier>::try_from(&removed.variant).is_ok());
+        assert_eq!(removed.measure(), Time::new(10.0).unwrap());
+        assert_eq!(removed.channels(), &[ChannelId::new("a")]);
+        assert!(Arc::ptr_eq(
+            &stack.children()[1],
+            &<&Stack>::try_from(&root.variant).unwrap().children()[1]
+        ));
+        assert_eq!(result.measure(), root.measure());
+    }
+
+    #[test]
+    fn remove_zero_amplitude_keeps_flexible_span() {
+        let common = ElementCommonBuilder::new()
+            .alignment(Alignment::Stretch)
+            .build()
+            .unwrap();
+        let play = play_with("a", 0.0, common);
+        let flexible: &Play = (&play.variant).try_into().unwrap();
+        let flexible = Arc::new(Element::new(
+            play.common.clone(),
+            flexible.clone().with_flexible(true),
+        ));
+        let root = element(Grid::new().with_children(vec![GridEntry::new(flexible)]));
+        let spans = |root: &ElementRef| {
+            let grid: &Grid = (&root.variant).try_into().unwrap();
+            let time_range = TimeRange {
+                start: Time::ZERO,
+                span: Time::new(50.0).unwrap(),
+            };
+            grid.arrange(time_range)
+                .map(|a| {
+                    let inner = a.item.inner_time_range(a.time_range);
+                    (inner.start, inner.span)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let result = optimize(Pass::RemoveZeroAmplitude, &root);
+
+        let grid: &Grid = (&result.variant).try_into().unwrap();
+        assert!(<&Barrier>::try_from(&grid.children()[0].element().variant).is_ok());
+        assert_eq!(result.measure(), root.measure());
+        assert_eq!(spans(&result), spans(&root));
+        assert_eq!(spans(&result)[0].1, Time::new(50.0).unwrap());
+    }
+
+    #[test]
+    fn collapse_stack() {
+        let inner = play("a", 1.0, None);
+        let root = stack(vec![stack(vec![inner.clone()])]);
+
+        let result = optimize(Pass::CollapseStack, &root);
+
+        assert!(Arc::ptr_eq(&result, &inner));
+    }
+
+    #[test]
+    fn collapse_stack_respects_constraints() {
+        let inner = play("a", 1.0, None);
+        let common = ElementCommonBuilder::new()
+            .duration(Some(Time::new(100.0).unwrap()))
+            .build()
+            .unwrap();
+        let root = Arc::new(Element::new(
+            common,
+            Stack::new().with_children(vec![inner]),
+        ));
+
+        let result = optimize(Pass::CollapseStack, &root);
+
+        assert!(Arc::ptr_eq(&result, &root));
+    }
+
+    #[test]
+    fn fold_repeat() {
+        let child = play("a", 1.0, None);
+        let root = stack(vec![child.clone(), child.clone(), child.clone()]);
+
+        let result = optimize(Pass::FoldRepeat, &root);
+
+        let repeat: &Repeat = (&result.variant).try_into().unwrap();
+        assert_eq!(repeat.count(), 3);
+        assert!(Arc::ptr_eq(repeat.child(), &child));
+        assert_eq!(result.measure(), root.measure());
+    }
+
+    #[test]
+    fn fold_repeat_requires_identical_children() {
+        let root = stack(vec![play("a", 1.0, None), play("a", 1.0, None)]);
+
+        let result = optimize(Pass::FoldRepeat, &root);
+
+        assert!(Arc::ptr_eq(&result, &root));
+    }
+
+    #[test]
+    fn shared_subtrees_stay_shared() {
+        let shared = stack(vec![shift_phase("a", 0.1), shift_phase("a", 0.2)]);
+        let root = stack(vec![shared.clone(), shared.clone()]);
+
+        let result = optimize(Pass::MergeShiftPhase, &root);
+
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        assert!(!Arc::ptr_eq(&stack.children()[0], &shared));
+        assert!(Arc::ptr_eq(&stack.children()[0], &stack.children()[1]));
+    }
+
+    #[test]
+    fn parse_pass() {
+        assert_eq!(
+            "merge_shift_phase".parse::<Pass>().unwrap(),
+            Pass::MergeShiftPhase
+        );
+        assert_eq!("fold_repeat".parse::<Pass>().unwrap(), Pass::FoldRepeat);
+        assert!("unknown".parse::<Pass>().is_err());
+    }
+
+    #[test]
+    fn resolve_channel_aliases() {
+        let aliases = [
+            (ChannelId::new("q0"), ChannelId::new("q0.drive")),
+            (ChannelId::new("q0.drive"), ChannelId::new("awg1")),
+        ]
+        .into_iter()
+        .collect();
+        let aliases = canonicalize_aliases(&aliases).unwrap();
+        let untouched = play("b", 1.0, None);
+        let barrier = element(Barrier::new(vec![
+            ChannelId::new("q0"),
+            ChannelId::new("awg1"),
+        ]));
+        let root = stack(vec![
+            play("q0", 1.0, None),
+            shift_phase("q0.drive", 0.1),
+            barrier,
+            untouched.clone(),
+        ]);
+
+        let result = resolve_aliases(&root, &aliases);
+
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        let children = stack.children();
+        let play: &Play = (&children[0].variant).try_into().unwrap();
+        assert_eq!(play.channel_id(), &ChannelId::new("awg1"));
+        assert_eq!(children[1].channels(), &[ChannelId::new("awg1")]);
+        assert_eq!(children[2].channels(), &[ChannelId::new("awg1")]);
+        assert!(Arc::ptr_eq(&children[3], &untouched));
+        let mut channels = result.channels().to_vec();
+        channels.sort_by_key(|c| c.to_string());
+        assert_eq!(channels, [ChannelId::new("awg1"), ChannelId::new("b")]);
+    }
+
+    #[test]
+    fn cyclic_aliases() {
+        let aliases = [
+            (ChannelId::new("a"), ChannelId::new("b")),
+            (ChannelId::new("b"), ChannelId::new("a")),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(canonicalize_aliases(&aliases).is_err());
+    }
+
+    fn with_common(
+        element: &ElementRef,
+        label: Option<&str>,
+        duration_of: Option<(&str, f64)>,
+    ) -> ElementRef {
+        let common = ElementCommonBuilder::new()
+            .label(label.map(Label::new))
+            .duration_ref(duration_of.map(|(l, f)| DurationRef::new(Label::new(l), f)))
+            .build()
+            .unwrap();
+        Arc::new(Element::new(common, element.variant.clone()))
+    }
+
+    #[test]
+    fn duration_refs() {
+        let gates = with_common(
+            &stack(vec![play("a", 1.0, None), play("a", 1.0, None)]),
+            Some("gates"),
+            None,
+        );
+        let idle = with_common(&element(Barrier::new(vec![])), None, Some(("gates", 1.5)));
+        let untouched = play("b", 1.0, None);
+        let root = stack(vec![idle, gates, untouched.clone()]);
+
+        let result = resolve_duration_refs(&root).unwrap();
+
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        let children = stack.children();
+        assert_eq!(
+            children[0].common.duration(),
+            Some(Time::new(30.0).unwrap())
+        );
+        assert!(children[0].common.duration_ref().is_none());
+        assert!(Arc::ptr_eq(&children[2], &untouched));
+        assert_eq!(result.measure(), Time::new(50.0).unwrap());
+    }
+
+    #[test]
+    fn cyclic_duration_refs() {
+        let idle = with_common(&element(Barrier::new(vec![])), None, Some(("outer", 1.0)));
+        let root = with_common(
+            &stack(vec![play("a", 1.0, None), idle]),
+            Some("outer"),
+            None,
+        );
+
+        assert!(resolve_duration_refs(&root).is_err());
+    }
+
+    #[test]
+    fn missing_duration_ref() {
+        let idle = with_common(&element(Barrier::new(vec![])), None, Some(("gates", 1.0)));
+
+        assert!(resolve_duration_refs(&idle).is_err());
+    }
+
+    fn with_params(element: &ElementRef, params: &[(&str, &str)]) -> ElementRef {
+        let params = params
+            .iter()
+            .map(|&(field, name)| ParamRef::new(field.into(), name.into()))
+            .collect();
+        let common = element.common.to_builder().params(params).build().unwrap();
+        Arc::new(Element::new(common, element.variant.clone()))
+    }
+
+    #[test]
+    fn bind_shared_params() {
+        let x = with_params(
+            &play("a", 0.0, None),
+            &[("amplitude", "amp"), ("phase", "phi")],
+        );
+        let untouched = play("b", 1.0, None);
+        let root = stack(vec![x.clone(), untouched.clone(), x]);
+        let values = HashMap::from([("amp".to_string(), 0.5), ("phi".to_string(), 0.25)]);
+
+        let result = bind_params(&root, &values).unwrap();
+
+        assert_eq!(param_names(&root), ["amp", "phi"]);
+        assert_eq!(phase_param_names(&root), HashSet::from(["phi".to_string()]));
+        assert!(param_names(&result).is_empty());
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        let children = stack.children();
+        let play: &Play = (&children[0].variant).try_into().unwrap();
+        assert_eq!(play.amplitude(), Amplitude::new(0.5).unwrap());
+        assert_eq!(play.phase(), Phase::new(0.25).unwrap());
+        assert!(Arc::ptr_eq(&children[0], &children[2]));
+        assert!(Arc::ptr_eq(&children[1], &untouched));
+    }
+
+    #[test]
+    fn bind_errors() {
+        let x = with_params(&play("a", 0.0, None), &[("amplitude", "amp")]);
+        let values = HashMap::from([("other".to_string(), 0.5)]);
+        assert!(bind_params(&x, &values).is_err());
+
+        let s = with_params(&stack(vec![]), &[("spacing", "t")]);
+        let values = HashMap::from([("t".to_string(), 1.0)]);
+        assert!(bind_params(&s, &values).is_err());
+    }
+}
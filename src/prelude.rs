@@ -0,0 +1,160 @@
+//! Rust API for building schedules and generating waveforms without Python.
+//!
+//! The Python bindings are thin wrappers around these types. A Rust
+//! application builds the schedule tree with the element constructors and
+//! [`ElementCommonBuilder`], runs the [`Executor`] to collect the pulses of
+//! each channel, and samples them into ndarray buffers with [`sample_into`].
+//! Depend on bosing with `default-features = false` to leave out the Python
+//! bindings.
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use bosing::prelude::*;
+//! use hashbrown::HashMap;
+//! use ndarray::Array2;
+//!
+//! let xy = ChannelId::new("xy");
+//! let hann = ShapeId::new("hann");
+//! let play = Play::new(
+//!     xy.clone(),
+//!     Some(hann.clone()),
+//!     Amplitude::new(0.3)?,
+//!     Time::new(100e-9)?,
+//! )?;
+//! let root = Arc::new(Element::new(ElementCommonBuilder::new().build()?, play));
+//!
+//! let mut executor = Executor::new(Amplitude::new(1e-6)?, Time::new(1e-12)?, false);
+//! executor.add_channel(xy.clone(), Frequency::new(30e6)?, true);
+//! executor.add_shape(hann, Shape::new_hann());
+//! executor.execute(&root)?;
+//!
+//! let config = ChannelConfig {
+//!     sample_rate: Frequency::new(2e9)?,
+//!     delay: Time::ZERO,
+//!     align_level: -10,
+//!     padding: Padding::Zero,
+//!     ramp_length: 0,
+//!     overflow: Overflow::Error,
+//!     iq_matrix: None,
+//!     offset: None,
+//!     iir: None,
+//!     fir: None,
+//!     predistortion: None,
+//!     filter_offset: false,
+//!     blanking: vec![],
+//!     envelope_precision: EnvelopePrecision::F64,
+//!     differential: None,
+//! };
+//! let channels = HashMap::from([(xy.clone(), config)]);
+//! let mut waveform = Array2::zeros((2, 1000));
+//! let mut buffers = HashMap::from([(xy, waveform.view_mut())]);
+//! sample_into(
+//!     executor.into_result(),
+//!     &channels,
+//!     &mut buffers,
+//!     None,
+//!     Time::new(1e-12)?,
+//! )?;
+//! drop(buffers);
+//! assert!(waveform.iter().any(|&x| x != 0.0));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Long waveforms can be sampled chunk by chunk with [`ChunkSampler`] and
+//! [`sample_chunk_into`].
+
+pub use crate::{
+    executor::{
+        explain, locate_pulse, ClippedPulse, Error as ExecutorError, Executor, FrameEvent,
+        FrameProgram, PulseRecord, PulseSource, ScheduledPulse,
+    },
+    generate::{
+        check_chunked, find_clipping, iq_imbalance_matrix, quantize, sample_chunk_into,
+        sample_into, sample_iq_sweep, sample_markers, ChannelConfig, ClippedRange, Differential,
+        Error as GenerateError, MarkerConfig, Quantized, SparseWaveform,
+    },
+    pulse::{
+        exp_correction_sos, shift_waveform, ChunkSampler, Crosstalk, EnvelopePrecision,
+        Error as SamplingError, OverflowStats, Predistortion, PulseList, Sampler, TimelinePulse,
+    },
+    quant::{
+        Amplitude, ChannelId, ChirpRate, Error as QuantError, Frequency, Label, Phase, ShapeId,
+        Time,
+    },
+    regenerate::Regenerator,
+    schedule::{
+        diff, from_json, from_json_with_fragments, to_json, to_json_with_fragments, Absolute,
+        AbsoluteEntry, Barrier, Delay, Difference, Element, ElementCommon, ElementCommonBuilder,
+        ElementRef, ElementVariant, Fragment, FragmentError, FragmentInstance, FragmentRegistry,
+        Grid, GridConflict, GridEntry, Marker, Overlay, ParamRef, PhaseRamp, Play, Repeat, Reverse,
+        Scale, SetFreq, SetPhase, ShiftFreq, ShiftPhase, Stack, SwapPhase,
+    },
+    shape::{Interpolation, SampleFn, Shape},
+    store::{Record, ResultStore},
+    Alignment, Direction, GridLength, GridLengthUnit, Overflow, Padding,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hashbrown::HashMap;
+    use ndarray::Array2;
+
+    use super::*;
+
+    #[test]
+    fn generate_without_python() {
+        let xy = ChannelId::new("xy");
+        let play = Play::new(
+            xy.clone(),
+            None,
+            Amplitude::new(0.5).unwrap(),
+            Time::new(2.0).unwrap(),
+        )
+        .unwrap();
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![Arc::new(Element::new(
+                ElementCommonBuilder::new().build().unwrap(),
+                play,
+            ))]),
+        ));
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        executor.add_channel(xy.clone(), Frequency::ZERO, false);
+        executor.execute(&root).unwrap();
+        let config = ChannelConfig {
+            sample_rate: Frequency::new(1.0).unwrap(),
+            delay: Time::ZERO,
+            align_level: 0,
+            padding: Padding::Zero,
+            ramp_length: 0,
+            overflow: Overflow::Error,
+            iq_matrix: None,
+            offset: None,
+            iir: None,
+            fir: None,
+            predistortion: None,
+            filter_offset: false,
+            blanking: vec![],
+            envelope_precision: EnvelopePrecision::F64,
+            differential: None,
+        };
+        let channels = HashMap::from([(xy.clone(), config)]);
+        let mut waveform = Array2::zeros((1, 3));
+        let mut buffers = HashMap::from([(xy, waveform.view_mut())]);
+
+        sample_into(
+            executor.into_result(),
+            &channels,
+            &mut buffers,
+            None,
+            Time::ZERO,
+        )
+        .unwrap();
+
+        drop(buffers);
+        assert_eq!(waveform, ndarray::array![[0.5, 0.5, 0.0]]);
+    }
+}
@@ -0,0 +1,299 @@
+//! Standalone HTML preview of a schedule and its waveforms.
+//!
+//! The preview is a single HTML file with an embedded Vega-Lite specification,
+//! so no display server or Python plotting library is needed to produce it.
+//! The Vega libraries are not embedded but loaded from [`CDN`] when the file
+//! is opened, so viewing it needs network access.
+
+use ndarray::ArrayView1;
+use serde_json::{json, Value};
+
+/// Time range occupied by a pulse in the schedule layout.
+#[derive(Debug, Clone)]
+pub(crate) struct Block {
+    pub(crate) channel: String,
+    pub(crate) label: Option<String>,
+    pub(crate) start: f64,
+    pub(crate) end: f64,
+}
+
+/// Synchronization line across all channels, e.g. a barrier in a grid.
+#[derive(Debug, Clone)]
+pub(crate) struct SyncLine {
+    pub(crate) label: Option<String>,
+    pub(crate) time: f64,
+}
+
+/// Decimated samples of one waveform component.
+#[derive(Debug, Clone)]
+pub(crate) struct Trace {
+    pub(crate) name: String,
+    pub(crate) points: Vec<(f64, f64)>,
+}
+
+const WIDTH: usize = 900;
+
+/// Base URL of the Vega libraries.
+const CDN: &str = "https://cdn.jsdelivr.net/npm";
+
+impl Trace {
+    /// Decimates the samples to at most `max_points` points, keeping the
+    /// minimum and maximum of each bucket so that peaks remain visible.
+    pub(crate) fn new(
+        name: String,
+        values: ArrayView1<f64>,
+        dt: f64,
+        t0: f64,
+        max_points: usize,
+    ) -> Self {
+        let points = decimate(values, max_points)
+            .into_iter()
+            .map(|(i, v)| (t0 + i as f64 * dt, v))
+            .collect();
+        Self { name, points }
+    }
+}
+
+fn decimate(values: ArrayView1<f64>, max_points: usize) -> Vec<(usize, f64)> {
+    let n = values.len();
+    if n <= max_points {
+        return values.iter().copied().enumerate().collect();
+    }
+    let n_buckets = (max_points / 2).max(1);
+    let mut result = Vec::with_capacity(2 * n_buckets);
+    for b in 0..n_buckets {
+        let start = b * n / n_buckets;
+        let end = (b + 1) * n / n_buckets;
+        let bucket = values
+            .iter()
+            .copied()
+            .enumerate()
+            .skip(start)
+            .take(end - start);
+        let (min, max) = bucket.fold(
+            ((start, f64::INFINITY), (start, f64::NEG_INFINITY)),
+            |(min, max), (i, v)| {
+                (
+                    if v < min.1 { (i, v) } else { min },
+                    if v > max.1 { (i, v) } else { max },
+                )
+            },
+        );
+        if min.0 <= max.0 {
+            result.push(min);
+            result.push(max);
+        } else {
+            result.push(max);
+            result.push(min);
+        }
+    }
+    result.dedup_by_key(|(i, _)| *i);
+    result
+}
+
+/// Renders the preview as a standalone HTML document.
+pub(crate) fn render(
+    title: &str,
+    blocks: &[Block],
+    sync_lines: &[SyncLine],
+    traces: &[Trace],
+) -> String {
+    let spec = script_json(&spec(title, blocks, sync_lines, traces));
+    let title = escape_html(title);
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<script src="{CDN}/vega@5"></script>
+<script src="{CDN}/vega-lite@5"></script>
+<script src="{CDN}/vega-embed@6"></script>
+</head>
+<body>
+<div id="view"></div>
+<script type="application/json" id="spec">{spec}</script>
+<script>
+vegaEmbed("#view", JSON.parse(document.getElementById("spec").textContent));
+</script>
+</body>
+</html>
+"##
+    )
+}
+
+fn spec(title: &str, blocks: &[Block], sync_lines: &[SyncLine], traces: &[Trace]) -> Value {
+    let layout: Vec<_> = blocks
+        .iter()
+        .map(|b| json!({"channel": b.channel, "label": b.label, "start": b.start, "end": b.end}))
+        .collect();
+    let sync: Vec<_> = sync_lines
+        .iter()
+        .map(|l| json!({"label": l.label, "t": l.time}))
+        .collect();
+    let samples: Vec<_> = traces
+        .iter()
+        .flat_map(|trace| {
+            trace
+                .points
+                .iter()
+                .map(|&(t, v)| json!({"trace": trace.name, "t": t, "v": v}))
+        })
+        .collect();
+    let zoom = |name: &str| {
+        json!({
+            "name": name,
+            "select": {"type": "interval", "encodings": ["x"]},
+            "bind": "scales",
+        })
+    };
+    json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "title": title,
+        "resolve": {"scale": {"x": "shared"}},
+        "vconcat": [
+            {
+                "width": WIDTH,
+                "layer": [
+                    {
+                        "data": {"values": layout},
+                        "mark": "bar",
+                        "params": [zoom("zoom_layout")],
+                        "encoding": {
+                            "x": {"field": "start", "type": "quantitative", "title": "Time (s)"},
+                            "x2": {"field": "end"},
+                            "y": {"field": "channel", "type": "nominal", "title": "Channel"},
+                            "color": {"field": "label", "type": "nominal", "title": "Label"},
+                            "tooltip": [
+                                {"field": "channel"},
+                                {"field": "label"},
+                                {"field": "start"},
+                                {"field": "end"},
+                            ],
+                        },
+                    },
+                    {
+                        "data": {"values": sync},
+                        "mark": {"type": "rule", "strokeDash": [4, 4]},
+                        "encoding": {
+                            "x": {"field": "t", "type": "quantitative"},
+                            "tooltip": [{"field": "label"}, {"field": "t"}],
+                        },
+                    },
+                ],
+            },
+            {
+                "width": WIDTH,
+                "height": 300,
+                "data": {"values": samples},
+                "mark": "line",
+                "params": [zoom("zoom_waveform")],
+                "encoding": {
+                    "x": {"field": "t", "type": "quantitative", "title": "Time (s)"},
+                    "y": {"field": "v", "type": "quantitative", "title": "Amplitude"},
+                    "color": {"field": "trace", "type": "nominal", "title": "Waveform"},
+                },
+            },
+        ],
+    })
+}
+
+/// Serializes the value as JSON which is also safe to embed in a `<script>`
+/// element. The escaped characters can only appear in strings, where the
+/// escapes decode to the same text.
+fn script_json(value: &Value) -> String {
+    value
+        .to_string()
+        .replace('<', r"\u003c")
+        .replace('>', r"\u003e")
+        .replace('&', r"\u0026")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::Array1;
+
+    use super::*;
+
+    #[test]
+    fn decimate_keeps_extrema() {
+        let mut values = Array1::zeros(1000);
+        values[123] = 1.0;
+        values[789] = -2.0;
+
+        let result = decimate(values.view(), 20);
+
+        assert!(result.len() <= 20);
+        assert!(result.contains(&(123, 1.0)));
+        assert!(result.contains(&(789, -2.0)));
+        assert!(result.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn decimate_short() {
+        let values = Array1::from_vec(vec![1.0, 2.0, 3.0]);
+
+        let result = decimate(values.view(), 20);
+
+        assert_eq!(result, vec![(0, 1.0), (1, 2.0), (2, 3.0)]);
+    }
+
+    #[test]
+    fn script_json_escape() {
+        let value = json!({"a": r#"a"b\c"#, "b": "</script>&"});
+
+        let result = script_json(&value);
+
+        assert_eq!(result, r#"{"a":"a\"b\\c","b":"\u003c/script\u003e\u0026"}"#);
+        assert_eq!(serde_json::from_str::<Value>(&result).unwrap(), value);
+    }
+
+    #[test]
+    fn render_embeds_data() {
+        let blocks = [Block {
+            channel: "xy".into(),
+            label: Some("drive".into()),
+            start: 0.0,
+            end: 1e-7,
+        }];
+        let traces = [Trace {
+            name: "xy.I".into(),
+            points: vec![(0.0, 0.5)],
+        }];
+
+        let sync_lines = [SyncLine {
+            label: None,
+            time: 5e-8,
+        }];
+
+        let html = render("<preview>", &blocks, &sync_lines, &traces);
+
+        assert!(html.contains("<title>&lt;preview&gt;</title>"));
+        let (_, spec) = html
+            .split_once(r#"<script type="application/json" id="spec">"#)
+            .unwrap();
+        let (spec, _) = spec.split_once("</script>").unwrap();
+        let spec: Value = serde_json::from_str(spec).unwrap();
+        assert_eq!(spec["title"], "<preview>");
+        let layer = &spec["vconcat"][0]["layer"];
+        assert_eq!(
+            layer[0]["data"]["values"],
+            json!([{"channel": "xy", "label": "drive", "start": 0.0, "end": 1e-7}])
+        );
+        assert_eq!(
+            layer[1]["data"]["values"],
+            json!([{"label": null, "t": 5e-8}])
+        );
+        assert_eq!(
+            spec["vconcat"][1]["data"]["values"],
+            json!([{"trace": "xy.I", "t": 0.0, "v": 0.5}])
+        );
+    }
+}
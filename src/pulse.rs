@@ -1,38 +1,149 @@
+pub mod disk_cache;
 mod fir;
 mod iir;
 
 use std::{
-    ops::{Add, Mul},
+    iter,
+    ops::{Add, Range},
+    rc::Rc,
     sync::Arc,
 };
 
-use anyhow::{bail, Context, Result};
 use cached::proc_macro::cached;
 use float_cmp::approx_eq;
 use hashbrown::HashMap;
 use itertools::{izip, Itertools};
-use ndarray::{azip, s, ArrayView1, ArrayView2, ArrayViewMut2, Axis};
-use numpy::Complex64;
+use ndarray::{azip, s, Array2, ArrayView1, ArrayView2, ArrayView3, ArrayViewMut2, Axis};
+use num::complex::Complex64;
+use pulp::Arch;
 use rayon::prelude::*;
+use thiserror::Error;
+use tracing::{info_span, Span};
 
 use crate::{
-    quant::{AlignedIndex, Amplitude, ChannelId, Frequency, Phase, Time},
+    quant::{AlignedIndex, Amplitude, ChannelId, ChirpRate, Frequency, Phase, Time},
     shape::Shape,
+    Overflow,
 };
 
+#[derive(Debug, Error)]
+#[error("Failed to sample channel '{channel}': {kind}")]
+pub struct Error {
+    pub channel: ChannelId,
+    pub kind: ErrorKind,
+    /// Start of the failed pulse relative to the start of the schedule,
+    /// without the channel delay.
+    pub pulse_time: Time,
+}
+
+/// Kind of a sampling [`Error`]. Times include the channel delay, and `index`
+/// is the sample of the channel at `time`, fractional if it is negative.
+#[derive(Debug, Error)]
+pub enum ErrorKind {
+    #[error("The start time of a pulse is negative, try adjusting channel delay or schedule. start time: {time}, start index: {index}")]
+    NegativeStartTime { time: f64, index: f64 },
+    #[error("The start index of a pulse is out of bounds, try adjusting channel delay, length or schedule. start index: {index}, start time: {time}, channel length: {length}")]
+    StartIndexOutOfBounds {
+        index: usize,
+        time: f64,
+        length: usize,
+    },
+    #[error("The pulse end time is out of bounds, try adjusting channel delay, length or schedule. end time: {time}, end index: {index}, channel length: {length}")]
+    EndTimeOutOfBounds {
+        time: f64,
+        index: usize,
+        length: usize,
+    },
+    #[error("Failed to sample the shape of a pulse at time {time}: {error}")]
+    ShapeFailed { time: f64, error: anyhow::Error },
+}
+
+impl ErrorKind {
+    /// Machine-readable identifier of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::NegativeStartTime { .. } => "negative_start_time",
+            ErrorKind::StartIndexOutOfBounds { .. } => "start_index_out_of_bounds",
+            ErrorKind::EndTimeOutOfBounds { .. } => "end_time_out_of_bounds",
+            ErrorKind::ShapeFailed { .. } => "shape_failed",
+        }
+    }
+
+    /// Time at which the error occurred.
+    pub fn time(&self) -> f64 {
+        match self {
+            ErrorKind::NegativeStartTime { time, .. }
+            | ErrorKind::StartIndexOutOfBounds { time, .. }
+            | ErrorKind::EndTimeOutOfBounds { time, .. }
+            | ErrorKind::ShapeFailed { time, .. } => *time,
+        }
+    }
+}
+
+/// Error of a single pulse before the channel is known.
+#[derive(Debug)]
+struct PulseError {
+    time: Time,
+    kind: ErrorKind,
+}
+
+type Result<T, E = ErrorKind> = std::result::Result<T, E>;
+
+/// Pulses of a channel which extend past its end and are truncated or
+/// wrapped according to [`Overflow`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OverflowStats {
+    /// Number of affected pulses.
+    pub pulses: usize,
+    /// Number of samples after the end of the channel.
+    pub samples: usize,
+}
+
+/// Precision of the sampled envelopes kept in the envelope cache.
+///
+/// The carrier and the mixing into the waveform are always computed in `f64`.
+/// With [`EnvelopePrecision::F32`] each envelope sample is rounded to `f32`
+/// with a relative error of at most `2^-24` (about `6e-8`), so a pulse deviates
+/// from the `f64` result by at most `6e-8` times its amplitude plus its DRAG
+/// amplitude. This is far below the resolution of a 16-bit DAC and halves the
+/// memory of the cache, which matters for long [`Shape`]s like interpolated
+/// ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum EnvelopePrecision {
+    #[default]
+    F64,
+    F32,
+}
+
+/// Cached envelope samples in one of the [`EnvelopePrecision`]s.
+#[derive(Debug, Clone)]
+enum EnvelopeSamples {
+    F64(Arc<Vec<f64>>),
+    F32(Arc<Vec<f32>>),
+}
+
+impl EnvelopeSamples {
+    fn len(&self) -> usize {
+        match self {
+            Self::F64(samples) => samples.len(),
+            Self::F32(samples) => samples.len(),
+        }
+    }
+}
+
 /// A pulse envelope
 ///
 /// If `shape` is `None`, constructor will set `plateau` to `width + plateau`
 /// and `width` to `0`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Envelope {
+pub struct Envelope {
     shape: Option<Shape>,
     width: Time,
     plateau: Time,
 }
 
 impl Envelope {
-    pub(crate) fn new(mut shape: Option<Shape>, mut width: Time, mut plateau: Time) -> Self {
+    pub fn new(mut shape: Option<Shape>, mut width: Time, mut plateau: Time) -> Self {
         if shape.is_none() {
             plateau += width;
             width = Time::ZERO;
@@ -46,6 +157,18 @@ impl Envelope {
             plateau,
         }
     }
+
+    pub fn shape(&self) -> Option<&Shape> {
+        self.shape.as_ref()
+    }
+
+    pub fn width(&self) -> Time {
+        self.width
+    }
+
+    pub fn plateau(&self) -> Time {
+        self.plateau
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -53,6 +176,10 @@ struct ListBin {
     envelope: Envelope,
     global_freq: Frequency,
     local_freq: Frequency,
+    chirp_rate: ChirpRate,
+    // Alignment level override, the coarser of this and the channel level is
+    // used.
+    align_level: Option<i32>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -74,148 +201,568 @@ impl Add for PulseAmplitude {
     }
 }
 
-impl Mul<f64> for PulseAmplitude {
-    type Output = Self;
+impl PulseAmplitude {
+    fn scaled(self, amp_factor: f64, drag_factor: f64) -> Self {
+        Self {
+            amp: self.amp * amp_factor,
+            drag: self.drag * drag_factor,
+        }
+    }
+}
+
+/// Pulses of the same bin stored as separate arrays of times and amplitudes.
+///
+/// Pushing a pulse earlier than the last one starts a new sorted run. The runs
+/// are merged with a k-way merge in [`Pulses::build`], which avoids sorting
+/// the whole list when the pulses are mostly in order.
+#[derive(Debug, Clone, Default)]
+struct Pulses {
+    times: Vec<Time>,
+    amplitudes: Vec<PulseAmplitude>,
+    // Start indices of the sorted runs except the first one.
+    run_starts: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PulseList {
+    items: HashMap<ListBin, Pulses>,
+}
+
+impl Pulses {
+    fn len(&self) -> usize {
+        self.times.len()
+    }
+
+    fn push(&mut self, time: Time, amplitude: PulseAmplitude) {
+        if self.times.last().is_some_and(|&last| time < last) {
+            self.run_starts.push(self.times.len());
+        }
+        self.times.push(time);
+        self.amplitudes.push(amplitude);
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Time, PulseAmplitude)> + '_ {
+        iter::zip(self.times.iter().copied(), self.amplitudes.iter().copied())
+    }
 
-    fn mul(self, rhs: f64) -> Self {
+    /// Sorts the pulses by time and merges pulses within `time_tolerance`.
+    fn build(self, time_tolerance: Time) -> Self {
+        let Self {
+            times,
+            amplitudes,
+            run_starts,
+        } = self;
+        let bounds = iter::once(0)
+            .chain(run_starts)
+            .chain(iter::once(times.len()));
+        let merged = bounds
+            .tuple_windows()
+            .map(|(start, end)| start..end)
+            .kmerge_by(|&a, &b| times[a] < times[b])
+            .map(|i| (times[i], amplitudes[i]))
+            .coalesce(|a, b| {
+                if approx_eq!(
+                    f64,
+                    a.0.value(),
+                    b.0.value(),
+                    epsilon = time_tolerance.value()
+                ) {
+                    Ok((a.0, a.1 + b.1))
+                } else {
+                    Err((a, b))
+                }
+            });
+        let (times, amplitudes) = merged.unzip();
         Self {
-            amp: self.amp * rhs,
-            drag: self.drag * rhs,
+            times,
+            amplitudes,
+            run_starts: vec![],
+        }
+    }
+}
+
+impl PulseList {
+    /// Number of pulses after merging pulses at the same time.
+    pub fn len(&self) -> usize {
+        self.items.values().map(Pulses::len).sum()
+    }
+
+    /// Whether the list contains no pulses.
+    pub fn is_empty(&self) -> bool {
+        self.items.values().all(|p| p.len() == 0)
+    }
+
+    /// Number of pulses using each envelope.
+    pub fn envelope_counts(&self) -> HashMap<&Envelope, usize> {
+        let mut counts = HashMap::new();
+        for (bin, pulses) in &self.items {
+            *counts.entry(&bin.envelope).or_default() += pulses.len();
         }
+        counts
+    }
+
+    /// Pulses sorted by start time, independent of any sample rate.
+    ///
+    /// The value of a pulse at time `t` is
+    /// `(amplitude * e(t - time) + drag * e'(t - time)) * exp(2j * pi * phi)`
+    /// with `phi = freq * (t - time) + chirp_rate * (t - center)^2 / 2`,
+    /// where `e` is the envelope and `center` the middle of the pulse. Unlike
+    /// in waveforms the start times are not aligned to the sample grid.
+    pub fn timeline(&self) -> Vec<TimelinePulse<'_>> {
+        let mut pulses: Vec<_> = self
+            .items
+            .iter()
+            .flat_map(|(bin, pulses)| {
+                pulses
+                    .iter()
+                    .map(move |(time, PulseAmplitude { amp, drag })| {
+                        // Carrier phase of the global frequency at the start.
+                        let phaser = (bin.global_freq * time).phaser();
+                        TimelinePulse {
+                            time,
+                            envelope: &bin.envelope,
+                            amplitude: amp * phaser,
+                            drag: drag * phaser,
+                            freq: bin.global_freq + bin.local_freq,
+                            chirp_rate: bin.chirp_rate,
+                        }
+                    })
+            })
+            .collect();
+        pulses.sort_by_key(|p| p.time);
+        pulses
+    }
+
+    fn iter(
+        &self,
+    ) -> impl Iterator<Item = (ListBin, impl Iterator<Item = (Time, PulseAmplitude)> + '_)> + '_
+    {
+        self.items
+            .iter()
+            .map(|(bin, pulses)| (bin.clone(), pulses.iter()))
     }
 }
 
+/// Pulse returned by [`PulseList::timeline`].
 #[derive(Debug, Clone)]
-pub(crate) struct PulseList {
-    items: HashMap<ListBin, Vec<(Time, PulseAmplitude)>>,
+pub struct TimelinePulse<'a> {
+    pub time: Time,
+    pub envelope: &'a Envelope,
+    /// Complex amplitude including the phase of the carrier at `time`.
+    pub amplitude: Complex64,
+    /// Complex amplitude of the derivative of the envelope.
+    pub drag: Complex64,
+    pub freq: Frequency,
+    pub chirp_rate: ChirpRate,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Crosstalk<'a> {
-    matrix: ArrayView2<'a, f64>,
+pub struct Crosstalk<'a> {
+    mixing: Mixing<'a>,
     names: Vec<ChannelId>,
 }
 
+#[derive(Debug, Clone)]
+enum Mixing<'a> {
+    /// Matrices sorted by the start time from which they apply.
+    Matrix {
+        segments: Vec<(Time, ArrayView2<'a, f64>)>,
+        drag_matrix: Option<ArrayView2<'a, f64>>,
+    },
+    /// FIR kernels indexed by `[target, source, tap]`.
+    Kernels(ArrayView3<'a, f64>),
+}
+
 impl<'a> Crosstalk<'a> {
-    pub(crate) fn new(matrix: ArrayView2<'a, f64>, names: Vec<ChannelId>) -> Self {
-        Self { matrix, names }
+    pub fn new(matrix: ArrayView2<'a, f64>, names: Vec<ChannelId>) -> Self {
+        Self::piecewise(vec![(Time::ZERO, matrix)], names)
+    }
+
+    /// Crosstalk changing during the schedule, e.g. after a pump is turned
+    /// on. Each matrix applies to the pulses starting at or after its start
+    /// time until the start time of the next one, and the first matrix also
+    /// applies to the pulses before its start time.
+    pub fn piecewise(segments: Vec<(Time, ArrayView2<'a, f64>)>, names: Vec<ChannelId>) -> Self {
+        assert!(!segments.is_empty(), "At least one matrix is needed");
+        assert!(
+            segments.windows(2).all(|w| w[0].0 < w[1].0),
+            "Start times should be strictly increasing"
+        );
+        Self {
+            mixing: Mixing::Matrix {
+                segments,
+                drag_matrix: None,
+            },
+            names,
+        }
+    }
+
+    /// Frequency dependent crosstalk, where the pulses of each source channel
+    /// are convolved with the FIR kernel `kernels[[target, source, ..]]`
+    /// instead of being scaled by a matrix entry. The DRAG component is
+    /// convolved with the same kernel.
+    ///
+    /// The pulses of the sources are sampled separately for the convolution,
+    /// so this is slower than a matrix.
+    pub fn from_kernels(kernels: ArrayView3<'a, f64>, names: Vec<ChannelId>) -> Self {
+        assert!(
+            kernels.shape()[2] > 0,
+            "Kernels should have at least one tap"
+        );
+        Self {
+            mixing: Mixing::Kernels(kernels),
+            names,
+        }
+    }
+
+    /// Mixes the DRAG component of the pulses with a different matrix. By
+    /// default the DRAG component is mixed with the same matrix as the
+    /// amplitude. Has no effect on crosstalk [`from_kernels`](Self::from_kernels).
+    pub fn with_drag_matrix(mut self, drag_matrix: ArrayView2<'a, f64>) -> Self {
+        if let Mixing::Matrix {
+            drag_matrix: drag, ..
+        } = &mut self.mixing
+        {
+            *drag = Some(drag_matrix);
+        }
+        self
+    }
+
+    /// Reborrows the matrices with a shorter lifetime.
+    pub fn view(&self) -> Crosstalk<'_> {
+        let mixing = match &self.mixing {
+            Mixing::Matrix {
+                segments,
+                drag_matrix,
+            } => Mixing::Matrix {
+                segments: segments.iter().map(|(t, m)| (*t, m.view())).collect(),
+                drag_matrix: drag_matrix.as_ref().map(|m| m.view()),
+            },
+            Mixing::Kernels(kernels) => Mixing::Kernels(kernels.view()),
+        };
+        Crosstalk {
+            mixing,
+            names: self.names.clone(),
+        }
     }
 }
 
 #[derive(Debug)]
-pub(crate) struct Sampler<'a> {
+pub struct Sampler<'a> {
     channels: HashMap<ChannelId, Channel<'a>>,
+    sources: HashMap<ChannelId, ChannelId>,
     pulse_lists: HashMap<ChannelId, PulseList>,
     crosstalk: Option<Crosstalk<'a>>,
 }
 
 impl<'a> Sampler<'a> {
-    pub(crate) fn new(pulse_lists: HashMap<ChannelId, PulseList>) -> Self {
+    pub fn new(pulse_lists: HashMap<ChannelId, PulseList>) -> Self {
         Self {
             channels: HashMap::new(),
+            sources: HashMap::new(),
             pulse_lists,
             crosstalk: None,
         }
     }
 
-    pub(crate) fn add_channel(
+    pub fn add_channel(
         &mut self,
         name: ChannelId,
         waveform: ArrayViewMut2<'a, f64>,
         sample_rate: Frequency,
         delay: Time,
         align_level: i32,
+        overflow: Overflow,
     ) {
         self.channels.insert(
             name.clone(),
             Channel {
                 waveform,
-                sample_rate,
-                align_level,
-                delay,
+                params: ChannelParams {
+                    sample_rate,
+                    align_level,
+                    delay,
+                    overflow,
+                    precision: EnvelopePrecision::F64,
+                },
             },
         );
     }
 
-    pub(crate) fn set_crosstalk(&mut self, crosstalk: ArrayView2<'a, f64>, names: Vec<ChannelId>) {
-        self.crosstalk = Some(Crosstalk::new(crosstalk, names));
+    /// Sets the envelope precision of a channel added before. Defaults to
+    /// [`EnvelopePrecision::F64`].
+    pub fn set_envelope_precision(&mut self, name: &ChannelId, precision: EnvelopePrecision) {
+        if let Some(c) = self.channels.get_mut(name) {
+            c.params.precision = precision;
+        }
+    }
+
+    /// Samples the pulses of `source` into the channel `name` instead of its
+    /// own pulses, e.g. for the second output of a differential pair. The
+    /// crosstalk row of `source` is used as well.
+    pub fn set_source(&mut self, name: ChannelId, source: ChannelId) {
+        self.sources.insert(name, source);
+    }
+
+    pub fn set_crosstalk(&mut self, crosstalk: Crosstalk<'a>) {
+        self.crosstalk = Some(crosstalk);
+    }
+
+    /// Samples all channels and returns the statistics of the channels with
+    /// truncated or wrapped pulses.
+    pub fn sample(self, time_tolerance: Time) -> Result<HashMap<ChannelId, OverflowStats>, Error> {
+        // The channels are sampled on rayon threads which don't inherit the
+        // current span.
+        let parent = Span::current();
+        let Self {
+            channels,
+            sources: channel_sources,
+            pulse_lists,
+            crosstalk,
+        } = self;
+        let sources = Sources::new(&pulse_lists, crosstalk.as_ref());
+        let stats: Vec<_> = channels
+            .into_par_iter()
+            .map(|(n, c)| {
+                let _span = info_span!(parent: &parent, "sample", channel = %n).entered();
+                let window = Window {
+                    start: 0,
+                    length: c.waveform.shape()[1],
+                };
+                let source = channel_sources.get(&n).unwrap_or(&n);
+                let stats =
+                    sources.sample(&n, source, c.waveform, window, c.params, time_tolerance)?;
+                Ok((n, stats))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(stats.into_iter().filter(|(_, s)| s.pulses > 0).collect())
+    }
+}
+
+/// Samples long waveforms chunk by chunk, so that the full waveforms never
+/// have to be in memory at once.
+///
+/// The samples of a chunk are the same as the corresponding samples written
+/// by [`Sampler`] into buffers of the full length. Pulses are still checked
+/// against the full channel length, so errors are reported by every chunk,
+/// while truncated or wrapped pulses are only reported by the chunk starting
+/// at sample 0.
+#[derive(Debug)]
+pub struct ChunkSampler<'a> {
+    channels: HashMap<ChannelId, (usize, ChannelParams)>,
+    sources: HashMap<ChannelId, ChannelId>,
+    pulse_lists: &'a HashMap<ChannelId, PulseList>,
+    crosstalk: Option<Crosstalk<'a>>,
+}
+
+impl<'a> ChunkSampler<'a> {
+    pub fn new(pulse_lists: &'a HashMap<ChannelId, PulseList>) -> Self {
+        Self {
+            channels: HashMap::new(),
+            sources: HashMap::new(),
+            pulse_lists,
+            crosstalk: None,
+        }
+    }
+
+    /// Adds a channel with `length` samples in total.
+    pub fn add_channel(
+        &mut self,
+        name: ChannelId,
+        length: usize,
+        sample_rate: Frequency,
+        delay: Time,
+        align_level: i32,
+        overflow: Overflow,
+    ) {
+        let params = ChannelParams {
+            sample_rate,
+            align_level,
+            delay,
+            overflow,
+            precision: EnvelopePrecision::F64,
+        };
+        self.channels.insert(name, (length, params));
+    }
+
+    /// Sets the envelope precision of a channel added before. Defaults to
+    /// [`EnvelopePrecision::F64`].
+    pub fn set_envelope_precision(&mut self, name: &ChannelId, precision: EnvelopePrecision) {
+        if let Some((_, params)) = self.channels.get_mut(name) {
+            params.precision = precision;
+        }
+    }
+
+    /// Same as [`Sampler::set_source`].
+    pub fn set_source(&mut self, name: ChannelId, source: ChannelId) {
+        self.sources.insert(name, source);
+    }
+
+    pub fn set_crosstalk(&mut self, crosstalk: Crosstalk<'a>) {
+        self.crosstalk = Some(crosstalk);
     }
 
-    pub(crate) fn sample(self, time_tolerance: Time) -> Result<()> {
-        if let Some(crosstalk) = self.crosstalk {
+    /// Adds the samples `start..start + n` of each channel to its buffer of
+    /// shape `(_, n)`. Buffers of unknown channels are left untouched.
+    ///
+    /// Every call iterates over all pulses of the channel, so the chunks
+    /// should be large compared to the number of pulses. Returns the same
+    /// statistics as [`Sampler::sample`] if `start` is 0, and an empty map
+    /// otherwise.
+    pub fn sample_chunk(
+        &self,
+        start: usize,
+        buffers: &mut HashMap<ChannelId, ArrayViewMut2<f64>>,
+        time_tolerance: Time,
+    ) -> Result<HashMap<ChannelId, OverflowStats>, Error> {
+        let parent = Span::current();
+        let sources = Sources::new(self.pulse_lists, self.crosstalk.as_ref());
+        let stats: Vec<_> = buffers
+            .par_iter_mut()
+            .filter_map(|(n, w)| {
+                let &(length, params) = self.channels.get(n)?;
+                let _span =
+                    info_span!(parent: &parent, "sample_chunk", channel = %n, start).entered();
+                let window = Window { start, length };
+                let source = self.sources.get(n).unwrap_or(n);
+                let stats = sources.sample(n, source, w.view_mut(), window, params, time_tolerance);
+                Some(stats.map(|s| (n.clone(), s)))
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(stats.into_iter().filter(|(_, s)| s.pulses > 0).collect())
+    }
+}
+
+/// Pulse lists and crosstalk shared by the channels.
+struct Sources<'s, 'c> {
+    pulse_lists: &'s HashMap<ChannelId, PulseList>,
+    crosstalk: Option<(&'s Crosstalk<'c>, HashMap<&'s ChannelId, usize>)>,
+}
+
+impl<'s, 'c> Sources<'s, 'c> {
+    fn new(
+        pulse_lists: &'s HashMap<ChannelId, PulseList>,
+        crosstalk: Option<&'s Crosstalk<'c>>,
+    ) -> Self {
+        let crosstalk = crosstalk.map(|crosstalk| {
             let ct_lookup = crosstalk
                 .names
                 .iter()
                 .enumerate()
                 .map(|(i, name)| (name, i))
-                .collect::<HashMap<_, _>>();
-            self.channels.into_par_iter().try_for_each(|(n, c)| {
-                let row_index = ct_lookup.get(&n).copied();
-                if let Some(row_index) = row_index {
-                    let row = crosstalk.matrix.slice(s![row_index, ..]);
-                    let lists = row
-                        .iter()
-                        .copied()
-                        .zip(&crosstalk.names)
-                        .map(|(multiplier, in_name)| (multiplier, &self.pulse_lists[in_name]));
-                    merge_and_sample(
-                        lists,
-                        c.waveform,
-                        c.sample_rate,
-                        c.delay,
-                        c.align_level,
-                        time_tolerance,
-                    )
-                    .with_context(|| format!("Failed to sample channel '{}'", n))
-                } else {
-                    let list = self.pulse_lists[&n]
-                        .items
-                        .iter()
-                        .map(|(bin, items)| (bin.clone(), items.iter().copied()));
-                    sample_pulse_list(list, c.waveform, c.sample_rate, c.delay, c.align_level)
-                        .with_context(|| format!("Failed to sample channel '{}'", n))
+                .collect();
+            (crosstalk, ct_lookup)
+        });
+        Self {
+            pulse_lists,
+            crosstalk,
+        }
+    }
+
+    /// Samples the pulses of `source` into the channel `n`.
+    fn sample(
+        &self,
+        n: &ChannelId,
+        source: &ChannelId,
+        waveform: ArrayViewMut2<f64>,
+        window: Window,
+        c: ChannelParams,
+        time_tolerance: Time,
+    ) -> Result<OverflowStats, Error> {
+        let row_index = self
+            .crosstalk
+            .as_ref()
+            .and_then(|(crosstalk, ct_lookup)| Some((*crosstalk, *ct_lookup.get(source)?)));
+        let result = if let Some((crosstalk, row_index)) = row_index {
+            let sources = crosstalk.names.iter().map(|n| &self.pulse_lists[n]);
+            match &crosstalk.mixing {
+                Mixing::Matrix {
+                    segments,
+                    drag_matrix,
+                } => {
+                    let lists = sources.enumerate().map(|(j, list)| {
+                        let multipliers = segments
+                            .iter()
+                            .map(|(t, m)| {
+                                let drag = drag_matrix.as_ref().unwrap_or(m);
+                                (*t, m[(row_index, j)], drag[(row_index, j)])
+                            })
+                            .collect();
+                        (multipliers, list)
+                    });
+                    merge_and_sample(lists, waveform, window, c, time_tolerance)
                 }
-            })
+                Mixing::Kernels(kernels) => {
+                    let kernels = kernels.slice(s![row_index, .., ..]);
+                    convolve_and_sample(kernels, sources, waveform, window, c)
+                }
+            }
         } else {
-            self.channels.into_par_iter().try_for_each(|(n, c)| {
-                let list = self.pulse_lists[&n]
-                    .items
-                    .iter()
-                    .map(|(bin, items)| (bin.clone(), items.iter().copied()));
-                sample_pulse_list(list, c.waveform, c.sample_rate, c.delay, c.align_level)
-                    .with_context(|| format!("Failed to sample channel '{}'", n))
-            })
+            let list = self.pulse_lists[source].iter();
+            sample_pulse_list(list, waveform, window, c)
+        };
+        let stats = result.map_err(|PulseError { time, kind }| Error {
+            channel: n.clone(),
+            kind,
+            pulse_time: time,
+        })?;
+        if stats.pulses > 0 {
+            tracing::warn!(
+                pulses = stats.pulses,
+                samples = stats.samples,
+                overflow = ?c.overflow,
+                "Pulses extend past the end of the channel"
+            );
         }
+        Ok(stats)
     }
 }
 
 #[derive(Debug)]
 struct Channel<'a> {
     waveform: ArrayViewMut2<'a, f64>,
+    params: ChannelParams,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelParams {
     sample_rate: Frequency,
     align_level: i32,
     delay: Time,
+    overflow: Overflow,
+    precision: EnvelopePrecision,
+}
+
+/// Position of a waveform buffer in a channel with `length` samples, whose
+/// first sample is the sample `start` of the channel.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: usize,
+    length: usize,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct PulseListBuilder {
-    items: HashMap<ListBin, Vec<(Time, PulseAmplitude)>>,
+pub struct PulseListBuilder {
+    items: HashMap<ListBin, Pulses>,
     amp_tolerance: Amplitude,
     time_tolerance: Time,
 }
 
-pub(crate) struct PushArgs {
-    pub(crate) envelope: Envelope,
-    pub(crate) global_freq: Frequency,
-    pub(crate) local_freq: Frequency,
-    pub(crate) time: Time,
-    pub(crate) amplitude: Amplitude,
-    pub(crate) drag_coef: f64,
-    pub(crate) phase: Phase,
+#[derive(Debug, Clone)]
+pub struct PushArgs {
+    pub envelope: Envelope,
+    pub global_freq: Frequency,
+    pub local_freq: Frequency,
+    pub chirp_rate: ChirpRate,
+    pub time: Time,
+    pub amplitude: Amplitude,
+    pub drag_coef: f64,
+    pub phase: Phase,
+    pub align_level: Option<i32>,
 }
 
 impl PulseListBuilder {
-    pub(crate) fn new(amp_tolerance: Amplitude, time_tolerance: Time) -> Self {
+    pub fn new(amp_tolerance: Amplitude, time_tolerance: Time) -> Self {
         Self {
             items: HashMap::new(),
             amp_tolerance,
@@ -223,16 +770,18 @@ impl PulseListBuilder {
         }
     }
 
-    pub(crate) fn push(
+    pub fn push(
         &mut self,
         PushArgs {
             envelope,
             global_freq,
             local_freq,
+            chirp_rate,
             time,
             amplitude,
             drag_coef,
             phase,
+            align_level,
         }: PushArgs,
     ) {
         if approx_eq!(
@@ -247,135 +796,325 @@ impl PulseListBuilder {
             envelope,
             global_freq,
             local_freq,
+            chirp_rate,
+            align_level,
         };
         let amp = amplitude.value() * phase.phaser();
         let drag = amp * Complex64::i() * drag_coef;
         let amplitude = PulseAmplitude { amp, drag };
-        self.items.entry(bin).or_default().push((time, amplitude));
+        self.items.entry(bin).or_default().push(time, amplitude);
     }
 
-    pub(crate) fn build(mut self) -> PulseList {
-        for pulses in self.items.values_mut() {
-            pulses.sort_unstable_by_key(|(time, _)| *time);
-            let mut i = 0;
-            for j in 1..pulses.len() {
-                if approx_eq!(
-                    f64,
-                    pulses[i].0.value(),
-                    pulses[j].0.value(),
-                    epsilon = self.time_tolerance.value()
-                ) {
-                    pulses[i].1 = pulses[i].1 + pulses[j].1;
-                } else {
-                    i += 1;
-                    pulses[i] = pulses[j];
-                }
-            }
-            pulses.truncate(i + 1);
+    pub fn build(self) -> PulseList {
+        let time_tolerance = self.time_tolerance;
+        let items = self
+            .items
+            .into_iter()
+            .map(|(bin, pulses)| (bin, pulses.build(time_tolerance)))
+            .collect();
+        PulseList { items }
+    }
+}
+
+/// Phase of the carrier at the first sample of a segment, and its first and
+/// second differences between samples.
+#[derive(Debug, Clone, Copy)]
+struct Carrier {
+    phase: Phase,
+    dphase: Phase,
+    ddphase: Phase,
+}
+
+impl Carrier {
+    /// Carrier whose frequency increases by `chirp_rate` per time, starting at
+    /// the sample at `tau` relative to the center of the pulse.
+    ///
+    /// `phase` and `dphase` are those without the chirp, which adds
+    /// `chirp_rate * tau^2 / 2`.
+    fn new(phase: Phase, dphase: Phase, chirp_rate: ChirpRate, tau: Time, dt: Time) -> Self {
+        let freq = chirp_rate * tau;
+        Self {
+            phase: phase + freq * tau / 2.0,
+            dphase: dphase + (freq + chirp_rate * dt / 2.0) * dt,
+            ddphase: chirp_rate * dt * dt,
+        }
+    }
+
+    /// Carrier starting `n` samples later.
+    fn advance(self, n: usize) -> Self {
+        let n = n as f64;
+        Self {
+            phase: self.phase + self.dphase * n + self.ddphase * (n * (n - 1.0) / 2.0),
+            dphase: self.dphase + self.ddphase * n,
+            ddphase: self.ddphase,
         }
-        PulseList { items: self.items }
     }
 }
 
-fn mix_add_envelope(
+/// Mixes `envelope[skip..]` into the waveform.
+///
+/// The samples inside the plateau of a flat-top envelope are exactly one with
+/// zero slope, so they are mixed by [`mix_add_plateau`] without multiplying
+/// the envelope and the DRAG component.
+fn mix_add_envelope<T: Copy + Into<f64>>(
+    waveform: ArrayViewMut2<f64>,
+    envelope: &[T],
+    skip: usize,
+    amplitude: Complex64,
+    drag_amp: Complex64,
+    carrier: Carrier,
+) {
+    let end = skip + waveform.ncols();
+    let plateau = plateau_interior(envelope);
+    let plateau = plateau.start.max(skip)..plateau.end.min(end);
+    if plateau.is_empty() {
+        mix_add_shaped(waveform, envelope, skip, amplitude, drag_amp, carrier);
+        return;
+    }
+    let (head, rest) = waveform.split_at(Axis(1), plateau.start - skip);
+    let (body, tail) = rest.split_at(Axis(1), plateau.len());
+    mix_add_shaped(head, envelope, skip, amplitude, drag_amp, carrier);
+    mix_add_plateau(body, amplitude, carrier.advance(plateau.start - skip));
+    mix_add_shaped(
+        tail,
+        envelope,
+        plateau.end,
+        amplitude,
+        drag_amp,
+        carrier.advance(plateau.end - skip),
+    );
+}
+
+/// Samples of the first run of ones in the envelope without its two ends,
+/// where the envelope is one and its central difference is zero.
+fn plateau_interior<T: Copy + Into<f64>>(envelope: &[T]) -> Range<usize> {
+    let is_one = |x: &T| (*x).into() == 1.0;
+    let Some(start) = envelope.iter().position(is_one) else {
+        return 0..0;
+    };
+    let len = envelope[start..].iter().take_while(|x| is_one(x)).count();
+    if len < 3 {
+        return 0..0;
+    }
+    start + 1..start + len - 1
+}
+
+/// Mixes `envelope[skip..]` into the waveform sample by sample.
+fn mix_add_shaped<T: Copy + Into<f64>>(
     mut waveform: ArrayViewMut2<f64>,
-    envelope: &[f64],
+    envelope: &[T],
+    skip: usize,
     amplitude: Complex64,
     drag_amp: Complex64,
-    phase0: Phase,
-    dphase: Phase,
+    carrier: Carrier,
 ) {
-    let mut carrier = phase0.phaser();
-    let dcarrier = dphase.phaser();
-    let slope_iter = (0..envelope.len()).map(|i| {
-        let left = if i > 0 { envelope[i - 1] } else { 0.0 };
+    Arch::new().dispatch(
+        #[inline(always)]
+        || {
+            let mut dcarrier = carrier.dphase.phaser();
+            let ddcarrier = carrier.ddphase.phaser();
+            let mut carrier = carrier.phase.phaser();
+            let slope_iter = envelope_slope(envelope, skip);
+            let columns = waveform.columns_mut();
+            let env_iter = envelope[skip..].iter().map(|&x| x.into());
+            for (mut y, env, slope) in izip!(columns, env_iter, slope_iter) {
+                let w = carrier * (amplitude * env + drag_amp * slope);
+                y[0] += w.re;
+                if let Some(y1) = y.get_mut(1) {
+                    *y1 += w.im;
+                }
+                carrier *= dcarrier;
+                dcarrier *= ddcarrier;
+            }
+        },
+    )
+}
+
+/// Central difference of the envelope from index `skip`, assuming zeros
+/// outside.
+#[inline(always)]
+fn envelope_slope<T: Copy + Into<f64>>(
+    envelope: &[T],
+    skip: usize,
+) -> impl Iterator<Item = f64> + '_ {
+    (skip..envelope.len()).map(|i| {
+        let left = if i > 0 { envelope[i - 1].into() } else { 0.0 };
         let right = if i < envelope.len() - 1 {
-            envelope[i + 1]
+            envelope[i + 1].into()
         } else {
             0.0
         };
         (right - left) / 2.0
-    });
-    for (mut y, env, slope) in izip!(waveform.columns_mut(), envelope.iter().copied(), slope_iter) {
-        let w = carrier * (amplitude * env + drag_amp * slope);
-        y[0] += w.re;
-        if let Some(y1) = y.get_mut(1) {
-            *y1 += w.im;
-        }
-        carrier *= dcarrier;
-    }
+    })
 }
 
-fn mix_add_plateau(
-    mut waveform: ArrayViewMut2<f64>,
-    amplitude: Complex64,
-    phase: Phase,
-    dphase: Phase,
-) {
-    let mut carrier = phase.phaser() * amplitude;
-    let dcarrier = dphase.phaser();
-    for mut y in waveform.columns_mut() {
-        y[0] += carrier.re;
-        if let Some(y1) = y.get_mut(1) {
-            *y1 += carrier.im;
+/// Mixes a constant envelope into the waveform.
+///
+/// Without a frequency or chirp the carrier is constant, e.g. for flux
+/// pulses, and the value is added to the rows without a per-sample multiply.
+fn mix_add_plateau(mut waveform: ArrayViewMut2<f64>, amplitude: Complex64, carrier: Carrier) {
+    if carrier.dphase == Phase::ZERO && carrier.ddphase == Phase::ZERO {
+        let w = carrier.phase.phaser() * amplitude;
+        for (mut row, value) in waveform.rows_mut().into_iter().zip([w.re, w.im]) {
+            row += value;
         }
-        carrier *= dcarrier;
+        return;
     }
+    Arch::new().dispatch(
+        #[inline(always)]
+        || {
+            let mut dcarrier = carrier.dphase.phaser();
+            let ddcarrier = carrier.ddphase.phaser();
+            let mut carrier = carrier.phase.phaser() * amplitude;
+            for mut y in waveform.columns_mut() {
+                y[0] += carrier.re;
+                if let Some(y1) = y.get_mut(1) {
+                    *y1 += carrier.im;
+                }
+                carrier *= dcarrier;
+                dcarrier *= ddcarrier;
+            }
+        },
+    )
 }
 
-#[cached(size = 1024)]
+/// Samples the envelope with the cache, unless the shape opts out of it.
 fn get_envelope(
     shape: Shape,
     width: Time,
     plateau: Time,
     index_offset: AlignedIndex,
     sample_rate: Frequency,
-) -> Arc<Vec<f64>> {
+) -> anyhow::Result<Arc<Vec<f64>>> {
+    if shape.cacheable() {
+        cached_envelope(shape, width, plateau, index_offset, sample_rate)
+    } else {
+        cached_envelope_no_cache(shape, width, plateau, index_offset, sample_rate)
+    }
+}
+
+/// Same as [`get_envelope`] but rounded to `f32`, which is cached separately.
+fn get_envelope_f32(
+    shape: Shape,
+    width: Time,
+    plateau: Time,
+    index_offset: AlignedIndex,
+    sample_rate: Frequency,
+) -> anyhow::Result<Arc<Vec<f32>>> {
+    if shape.cacheable() {
+        cached_envelope_f32(shape, width, plateau, index_offset, sample_rate)
+    } else {
+        cached_envelope_f32_no_cache(shape, width, plateau, index_offset, sample_rate)
+    }
+}
+
+#[cached(size = 1024, result = true)]
+fn cached_envelope(
+    shape: Shape,
+    width: Time,
+    plateau: Time,
+    index_offset: AlignedIndex,
+    sample_rate: Frequency,
+) -> anyhow::Result<Arc<Vec<f64>>> {
+    let envelope = load_or_sample_envelope(&shape, width, plateau, index_offset, sample_rate)?;
+    Ok(Arc::new(envelope))
+}
+
+#[cached(size = 1024, result = true)]
+fn cached_envelope_f32(
+    shape: Shape,
+    width: Time,
+    plateau: Time,
+    index_offset: AlignedIndex,
+    sample_rate: Frequency,
+) -> anyhow::Result<Arc<Vec<f32>>> {
+    let envelope = load_or_sample_envelope(&shape, width, plateau, index_offset, sample_rate)?;
+    Ok(Arc::new(envelope.into_iter().map(|x| x as f32).collect()))
+}
+
+/// Samples the envelope, or loads it from the disk cache if the shape has a
+/// fingerprint.
+fn load_or_sample_envelope(
+    shape: &Shape,
+    width: Time,
+    plateau: Time,
+    index_offset: AlignedIndex,
+    sample_rate: Frequency,
+) -> anyhow::Result<Vec<f64>> {
+    let key = shape
+        .fingerprint()
+        .map(|f| disk_cache::Key::new(f, width, plateau, index_offset, sample_rate));
+    if let Some(envelope) = key.and_then(disk_cache::load) {
+        return Ok(envelope);
+    }
+    let envelope = sample_envelope(shape, width, plateau, index_offset, sample_rate)?;
+    if let Some(key) = key {
+        disk_cache::store(key, &envelope);
+    }
+    Ok(envelope)
+}
+
+fn sample_envelope(
+    shape: &Shape,
+    width: Time,
+    plateau: Time,
+    index_offset: AlignedIndex,
+    sample_rate: Frequency,
+) -> anyhow::Result<Vec<f64>> {
     let width = width.value();
     let plateau = plateau.value();
     let index_offset = index_offset.value();
     let sample_rate = sample_rate.value();
     let dt = 1.0 / sample_rate;
     let t_offset = index_offset * dt;
-    let t1 = width / 2.0 - t_offset;
-    let t2 = width / 2.0 + plateau - t_offset;
+    let peak = shape.peak();
+    let t1 = width * (0.5 + peak) - t_offset;
+    let t2 = t1 + plateau;
     let t3 = width + plateau - t_offset;
     let length = (t3 * sample_rate).ceil() as usize;
     let plateau_start_index = (t1 * sample_rate).ceil() as usize;
     let plateau_end_index = (t2 * sample_rate).ceil() as usize;
     let mut envelope = vec![0.0; length];
-    let x0 = -t1 / width;
+    let x0 = peak - t1 / width;
     let dx = dt / width;
     if plateau == 0.0 {
-        shape.sample_array(x0, dx, &mut envelope);
+        shape.try_sample_array(x0, dx, &mut envelope)?;
     } else {
-        shape.sample_array(x0, dx, &mut envelope[..plateau_start_index]);
+        shape.try_sample_array(x0, dx, &mut envelope[..plateau_start_index])?;
         envelope[plateau_start_index..plateau_end_index].fill(1.0);
-        let x2 = (plateau_end_index as f64 * dt - t2) / width;
-        shape.sample_array(x2, dx, &mut envelope[plateau_end_index..]);
+        let x2 = peak + (plateau_end_index as f64 * dt - t2) / width;
+        shape.try_sample_array(x2, dx, &mut envelope[plateau_end_index..])?;
     }
-    Arc::new(envelope)
+    Ok(envelope)
 }
 
+/// Merges the pulse lists scaled by the multipliers of the amplitude and the
+/// DRAG component, which are sorted by the start time from which they apply.
 fn merge_and_sample<'a>(
-    lists: impl IntoIterator<Item = (f64, &'a PulseList)>,
+    lists: impl IntoIterator<Item = (Vec<(Time, f64, f64)>, &'a PulseList)>,
     waveform: ArrayViewMut2<f64>,
-    sample_rate: Frequency,
-    delay: Time,
-    align_level: i32,
+    window: Window,
+    params: ChannelParams,
     time_tolerance: Time,
-) -> Result<()> {
+) -> Result<OverflowStats, PulseError> {
     let mut merged: HashMap<ListBin, Vec<_>> = HashMap::new();
-    for (multiplier, list) in lists {
-        if multiplier == 0.0 {
+    for (multipliers, list) in lists {
+        if multipliers.iter().all(|&(_, m, d)| m == 0.0 && d == 0.0) {
             continue;
         }
-        for (bin, items) in &list.items {
-            merged.entry(bin.clone()).or_default().push(
-                items
-                    .iter()
-                    .map(move |&(time, amp)| (time, amp * multiplier)),
-            )
+        let multipliers = Rc::new(multipliers);
+        for (bin, items) in list.iter() {
+            let multipliers = multipliers.clone();
+            merged
+                .entry(bin)
+                .or_default()
+                .push(items.map(move |(time, amp)| {
+                    let i = multipliers
+                        .partition_point(|&(t, _, _)| t <= time)
+                        .saturating_sub(1);
+                    let (_, m, d) = multipliers[i];
+                    (time, amp.scaled(m, d))
+                }))
         }
     }
     let merged = merged.into_iter().map(|(bin, items)| {
@@ -398,31 +1137,87 @@ fn merge_and_sample<'a>(
                 }),
         )
     });
-    sample_pulse_list(merged, waveform, sample_rate, delay, align_level)
+    sample_pulse_list(merged, waveform, window, params)
+}
+
+/// Samples the pulse lists separately and adds them to the waveform convolved
+/// with the rows of `kernels`.
+///
+/// The samples before the window which the kernels reach back to are sampled
+/// as well, so that chunks are the same as the full waveform.
+fn convolve_and_sample<'a>(
+    kernels: ArrayView2<f64>,
+    lists: impl IntoIterator<Item = &'a PulseList>,
+    mut waveform: ArrayViewMut2<f64>,
+    window: Window,
+    params: ChannelParams,
+) -> Result<OverflowStats, PulseError> {
+    let history = (kernels.ncols() - 1).min(window.start);
+    let extended = Window {
+        start: window.start - history,
+        length: window.length,
+    };
+    let (rows, cols) = waveform.dim();
+    let mut buffer = Array2::zeros((rows, history + cols));
+    let mut stats = OverflowStats::default();
+    for (kernel, list) in kernels.rows().into_iter().zip(lists) {
+        if kernel.iter().all(|&x| x == 0.0) {
+            continue;
+        }
+        buffer.fill(0.0);
+        let list_stats = sample_pulse_list(list.iter(), buffer.view_mut(), extended, params)?;
+        // Only the chunk at the start reports the statistics.
+        if window.start == 0 {
+            stats.pulses += list_stats.pulses;
+            stats.samples += list_stats.samples;
+        }
+        apply_fir_inplace(&mut buffer.view_mut(), kernel);
+        waveform += &buffer.slice(s![.., history..]);
+    }
+    Ok(stats)
 }
 
+/// Adds the pulses to the part of the channel covered by `waveform`.
+///
+/// With [`Overflow::Wrap`], the samples after the end of the channel are
+/// added to the beginning of the channel, so a pulse may cover several
+/// segments of the channel.
 fn sample_pulse_list<PL, L>(
     list: PL,
     mut waveform: ArrayViewMut2<f64>,
-    sample_rate: Frequency,
-    delay: Time,
-    align_level: i32,
-) -> Result<()>
+    window: Window,
+    params: ChannelParams,
+) -> Result<OverflowStats, PulseError>
 where
     PL: IntoIterator<Item = (ListBin, L)>,
     L: IntoIterator<Item = (Time, PulseAmplitude)>,
 {
+    let ChannelParams {
+        sample_rate,
+        align_level,
+        delay,
+        overflow,
+        precision,
+    } = params;
+    let mut stats = OverflowStats::default();
     for (bin, items) in list {
         let ListBin {
             envelope,
             global_freq,
             local_freq,
+            chirp_rate,
+            align_level: bin_align_level,
         } = bin;
+        let align_level = bin_align_level.map_or(align_level, |l| l.max(align_level));
         for (time, PulseAmplitude { amp, drag }) in items {
+            let fail = |kind| PulseError { time, kind };
             let t_start = time + delay;
             let i_frac_start = AlignedIndex::new(t_start, sample_rate, align_level).unwrap();
             if i_frac_start.value() < 0.0 {
-                bail!("The start time of a pulse is negative, try adjusting channel delay or schedule. start time: {}", t_start.value());
+                return Err(fail(ErrorKind::NegativeStartTime {
+                    time: t_start.value(),
+                    index: i_frac_start.value(),
+                }));
             }
             let i_start = i_frac_start.ceil_to_usize().unwrap();
             let index_offset = i_frac_start.index_offset().unwrap();
@@ -431,38 +1226,106 @@ where
             let phase0 = global_freq * (i_start as f64 * dt - delay)
                 + local_freq * index_offset.value() * dt;
             let dphase = total_freq * dt;
-            if i_start >= waveform.shape()[1] {
-                bail!("The start index of a pulse is out of bounds, try adjusting channel delay, length or schedule. start index: {}, start time: {}", i_start, t_start.value());
+            if i_start >= window.length && overflow == Overflow::Error {
+                return Err(fail(ErrorKind::StartIndexOutOfBounds {
+                    index: i_start,
+                    time: t_start.value(),
+                    length: window.length,
+                }));
             }
-            let mut waveform = waveform.slice_mut(s![.., i_start..]);
-            if let Some(shape) = &envelope.shape {
-                let envelope = get_envelope(
-                    shape.clone(),
-                    envelope.width,
-                    envelope.plateau,
-                    index_offset,
-                    sample_rate,
-                );
-                let drag = drag * sample_rate.value();
-                if waveform.shape()[1] < envelope.len() {
-                    bail!("The pulse end time is out of bounds, try adjusting channel delay, length or schedule. end time: {}", t_start.value() + envelope.len() as f64 * dt.value());
+            let envelope_samples = envelope
+                .shape
+                .clone()
+                .map(|shape| {
+                    let (width, plateau) = (envelope.width, envelope.plateau);
+                    anyhow::Ok(match precision {
+                        EnvelopePrecision::F64 => EnvelopeSamples::F64(get_envelope(
+                            shape,
+                            width,
+                            plateau,
+                            index_offset,
+                            sample_rate,
+                        )?),
+                        EnvelopePrecision::F32 => EnvelopeSamples::F32(get_envelope_f32(
+                            shape,
+                            width,
+                            plateau,
+                            index_offset,
+                            sample_rate,
+                        )?),
+                    })
+                })
+                .transpose()
+                .map_err(|error| {
+                    fail(ErrorKind::ShapeFailed {
+                        time: t_start.value(),
+                        error,
+                    })
+                })?;
+            let n_samples = match &envelope_samples {
+                Some(samples) => samples.len(),
+                None => (envelope.plateau.value() * sample_rate.value()).ceil() as usize,
+            };
+            let i_end = i_start + n_samples;
+            if i_end > window.length {
+                if overflow == Overflow::Error {
+                    let time = match &envelope_samples {
+                        Some(samples) => t_start.value() + samples.len() as f64 * dt.value(),
+                        None => t_start.value() + envelope.plateau.value(),
+                    };
+                    return Err(fail(ErrorKind::EndTimeOutOfBounds {
+                        time,
+                        index: i_end,
+                        length: window.length,
+                    }));
                 }
-                mix_add_envelope(waveform, &envelope, amp, drag, phase0, dphase);
-            } else {
-                let plateau = envelope.plateau;
-                let i_plateau = (plateau.value() * sample_rate.value()).ceil() as usize;
-                if waveform.shape()[1] < i_plateau {
-                    bail!("The pulse end time is out of bounds, try adjusting channel delay, length or schedule. end time: {}", t_start.value() + plateau.value());
+                if window.start == 0 {
+                    stats.pulses += 1;
+                    stats.samples += i_end - i_start.max(window.length);
+                }
+            }
+            if n_samples == 0 {
+                continue;
+            }
+            // Segments of the pulse in each period of the channel.
+            let periods = match overflow {
+                Overflow::Wrap => i_start / window.length..=(i_end - 1) / window.length,
+                _ => 0..=0,
+            };
+            let chunk_end = window.start + waveform.shape()[1];
+            for period in periods {
+                let offset = period * window.length;
+                let first = i_start.max(offset + window.start);
+                let last = i_end.min(offset + chunk_end.min(window.length));
+                if first >= last {
+                    continue;
+                }
+                let skip = first - i_start;
+                let tau = dt * (index_offset.value() + skip as f64)
+                    - (envelope.width + envelope.plateau) / 2.0;
+                let carrier =
+                    Carrier::new(phase0 + dphase * skip as f64, dphase, chirp_rate, tau, dt);
+                let waveform = waveform.slice_mut(s![
+                    ..,
+                    first - offset - window.start..last - offset - window.start
+                ]);
+                let drag = drag * sample_rate.value();
+                match &envelope_samples {
+                    Some(EnvelopeSamples::F64(samples)) => {
+                        mix_add_envelope(waveform, samples, skip, amp, drag, carrier)
+                    }
+                    Some(EnvelopeSamples::F32(samples)) => {
+                        mix_add_envelope(waveform, samples, skip, amp, drag, carrier)
+                    }
+                    None => mix_add_plateau(waveform, amp, carrier),
                 }
-                let waveform = waveform.slice_mut(s![.., ..i_plateau]);
-                mix_add_plateau(waveform, amp, phase0, dphase);
             }
         }
     }
-    Ok(())
+    Ok(stats)
 }
 
-pub(crate) fn apply_iq_inplace(waveform: &mut ArrayViewMut2<f64>, iq_matrix: ArrayView2<f64>) {
+pub fn apply_iq_inplace(waveform: &mut ArrayViewMut2<f64>, iq_matrix: ArrayView2<f64>) {
     assert!(matches!(waveform.shape(), [2, _]));
     assert!(matches!(iq_matrix.shape(), [2, 2]));
     for mut col in waveform.columns_mut() {
@@ -475,15 +1338,1256 @@ pub(crate) fn apply_iq_inplace(waveform: &mut ArrayViewMut2<f64>, iq_matrix: Arr
     }
 }
 
-pub(crate) fn apply_offset_inplace(waveform: &mut ArrayViewMut2<f64>, offset: ArrayView1<f64>) {
+pub fn apply_offset_inplace(waveform: &mut ArrayViewMut2<f64>, offset: ArrayView1<f64>) {
     assert!(waveform.shape()[0] == offset.len());
     azip!((mut row in waveform.axis_iter_mut(Axis(0)), &offset in &offset) row += offset);
 }
 
-pub(crate) fn apply_iir_inplace(waveform: &mut ArrayViewMut2<f64>, sos: ArrayView2<f64>) {
-    self::iir::iir_filter_inplace(waveform.view_mut(), sos).unwrap()
+pub fn apply_iir_inplace(waveform: &mut ArrayViewMut2<f64>, sos: ArrayView2<f64>) {
+    Arch::new().dispatch(
+        #[inline(always)]
+        || self::iir::iir_filter_inplace(waveform.view_mut(), sos).unwrap(),
+    )
 }
 
-pub(crate) fn apply_fir_inplace(waveform: &mut ArrayViewMut2<f64>, taps: ArrayView1<f64>) {
+pub fn apply_fir_inplace(waveform: &mut ArrayViewMut2<f64>, taps: ArrayView1<f64>) {
     self::fir::fir_filter_inplace(waveform.view_mut(), taps)
 }
+
+/// Instruction set used by the mixing, IIR filtering and direct FIR
+/// convolution kernels, which is detected at runtime so that a single build
+/// runs well on different CPUs.
+///
+/// The crosstalk kernels are compiled for the baseline target only, and the
+/// FFT convolution of long FIR filters relies on the detection of `rustfft`.
+/// AVX-512 needs the nightly feature of `pulp` and is not used.
+pub fn simd_level() -> &'static str {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if pulp::x86::V3::try_new().is_some() {
+        return "avx2";
+    }
+    #[cfg(target_arch = "aarch64")]
+    if pulp::aarch64::Neon::try_new().is_some() {
+        return "neon";
+    }
+    "scalar"
+}
+
+/// SIMD extensions of the CPU relevant to the dispatched kernels.
+pub fn cpu_features() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut features = vec![];
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    for (name, detected) in [
+        ("avx2", is_x86_feature_detected!("avx2")),
+        ("fma", is_x86_feature_detected!("fma")),
+        ("avx512f", is_x86_feature_detected!("avx512f")),
+    ] {
+        if detected {
+            features.push(name);
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        features.push("neon");
+    }
+    features
+}
+
+/// Group delay in samples of the cascaded IIR and FIR filters at `freq`, which
+/// is normalized to the sample rate.
+///
+/// Returns `None` if the group delay is undefined because the response of a
+/// filter vanishes at `freq`.
+pub fn group_delay(
+    sos: Option<ArrayView2<f64>>,
+    taps: Option<ArrayView1<f64>>,
+    freq: f64,
+) -> Option<f64> {
+    let mut delay = 0.0;
+    if let Some(sos) = sos {
+        for section in sos.rows() {
+            let (b, a) = (section.slice(s![..3]), section.slice(s![3..]));
+            delay += polynomial_group_delay(b, freq)? - polynomial_group_delay(a, freq)?;
+        }
+    }
+    if let Some(taps) = taps {
+        delay += polynomial_group_delay(taps, freq)?;
+    }
+    Some(delay)
+}
+
+/// IIR sections in the format of [`apply_iir_inplace`] which correct the
+/// distortion of a line whose step response is
+/// `1 + sum(amplitude * exp(-t / tau))` for the `(amplitude, tau)` pairs.
+///
+/// Each term is inverted by a first-order section, matched to the sampled
+/// step response so that a single term is corrected exactly. The sections are
+/// cascaded, which is a good approximation for several terms with small
+/// amplitudes. All sections have unit DC gain.
+pub fn exp_correction_sos(
+    corrections: &[(f64, Time)],
+    sample_rate: Frequency,
+) -> anyhow::Result<Array2<f64>> {
+    let mut sos = Array2::zeros((corrections.len(), 6));
+    for (mut section, &(amplitude, tau)) in sos.rows_mut().into_iter().zip(corrections) {
+        if !(amplitude.is_finite() && tau.value() > 0.0) {
+            anyhow::bail!(
+                "Invalid exponential correction ({}, {}), amplitude should be finite and tau positive",
+                amplitude,
+                tau.value()
+            );
+        }
+        // Sampled distortion: ((1 + A) - (r + A) z^-1) / (1 - r z^-1).
+        let r = (-1.0 / (tau.value() * sample_rate.value())).exp();
+        let gain = 1.0 + amplitude;
+        let pole = (r + amplitude) / gain;
+        if gain <= 0.0 || pole.abs() >= 1.0 {
+            anyhow::bail!(
+                "Exponential correction ({}, {}) is unstable",
+                amplitude,
+                tau.value()
+            );
+        }
+        section.assign(&ndarray::arr1(&[
+            1.0 / gain,
+            -r / gain,
+            0.0,
+            1.0,
+            -pole,
+            0.0,
+        ]));
+    }
+    Ok(sos)
+}
+
+/// Number of points of [`Predistortion::from_polynomial`].
+const POLYNOMIAL_POINTS: usize = 1025;
+
+/// Predistortion compensating the compression of an amplifier.
+///
+/// The amplifier is modeled by its output amplitude as a monotonic function
+/// of the input amplitude, and the predistortion maps the wanted output
+/// amplitude to the input amplitude by linear interpolation of the inverse.
+/// Outputs beyond the saturation of the model are clipped to the largest
+/// input. The model is memoryless, so complex IQ samples keep their phase
+/// and real samples keep their sign.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predistortion {
+    input: Vec<f64>,
+    output: Vec<f64>,
+}
+
+impl Predistortion {
+    /// Creates the predistortion of a table of `(input, output)` amplitudes.
+    /// The point `(0, 0)` is added if it's not in the table.
+    pub fn from_table(points: &[(f64, f64)]) -> anyhow::Result<Self> {
+        let (mut input, mut output): (Vec<_>, Vec<_>) =
+            iter::once((0.0, 0.0)).chain(points.iter().copied()).unzip();
+        if points.first().is_some_and(|&p| p == (0.0, 0.0)) {
+            input.remove(0);
+            output.remove(0);
+        }
+        if input.len() < 2 || input.iter().chain(&output).any(|x| !x.is_finite()) {
+            anyhow::bail!("Compression table should have finite points besides (0, 0)");
+        }
+        if !(input.windows(2).all(|w| w[0] < w[1]) && output.windows(2).all(|w| w[0] < w[1])) {
+            anyhow::bail!(
+                "Compression table should be strictly increasing in both input and output, starting from (0, 0)"
+            );
+        }
+        Ok(Self { input, output })
+    }
+
+    /// Creates the predistortion of the output amplitude
+    /// `c[0] * x + c[1] * x^2 + ...` for inputs `x` between 0 and 1, the full
+    /// scale of the waveform.
+    pub fn from_polynomial(coefficients: &[f64]) -> anyhow::Result<Self> {
+        let input: Vec<_> = (0..POLYNOMIAL_POINTS)
+            .map(|i| i as f64 / (POLYNOMIAL_POINTS - 1) as f64)
+            .collect();
+        let output: Vec<_> = input
+            .iter()
+            .map(|&x| coefficients.iter().rev().fold(0.0, |y, &c| (y + c) * x))
+            .collect();
+        if !output.windows(2).all(|w| w[0] < w[1]) {
+            anyhow::bail!("Compression polynomial should be strictly increasing between 0 and 1");
+        }
+        Ok(Self { input, output })
+    }
+
+    /// Input amplitude giving the output amplitude.
+    pub fn correct(&self, amplitude: f64) -> f64 {
+        let n = self.output.len();
+        if amplitude >= self.output[n - 1] {
+            return self.input[n - 1];
+        }
+        let i = self.output.partition_point(|&y| y <= amplitude).max(1);
+        let (x0, x1) = (self.input[i - 1], self.input[i]);
+        let (y0, y1) = (self.output[i - 1], self.output[i]);
+        x0 + (x1 - x0) * (amplitude - y0) / (y1 - y0)
+    }
+}
+
+/// Applies the predistortion to the amplitude of the IQ samples, or to the
+/// absolute value of real samples.
+pub fn apply_predistortion_inplace(
+    waveform: &mut ArrayViewMut2<f64>,
+    predistortion: &Predistortion,
+) {
+    match waveform.shape() {
+        [1, _] => waveform.map_inplace(|x| *x = predistortion.correct(x.abs()).copysign(*x)),
+        [2, _] => {
+            for mut col in waveform.columns_mut() {
+                let amplitude = col[0].hypot(col[1]);
+                if amplitude > 0.0 {
+                    col *= predistortion.correct(amplitude) / amplitude;
+                }
+            }
+        }
+        shape => panic!("Waveform should have 1 or 2 rows, got {:?}", shape),
+    }
+}
+
+/// Group delay of `sum(c[n] * z^-n)` evaluated at `z = exp(2j * pi * freq)`.
+fn polynomial_group_delay(coefficients: ArrayView1<f64>, freq: f64) -> Option<f64> {
+    let (response, weighted) = coefficients.iter().enumerate().fold(
+        (Complex64::new(0.0, 0.0), Complex64::new(0.0, 0.0)),
+        |(response, weighted), (n, &c)| {
+            let term =
+                c * Complex64::from_polar(1.0, -2.0 * std::f64::consts::PI * freq * n as f64);
+            (response + term, weighted + n as f64 * term)
+        },
+    );
+    let l1_norm: f64 = coefficients.iter().map(|c| c.abs()).sum();
+    if response.norm() <= 1e-12 * l1_norm {
+        return None;
+    }
+    Some((weighted / response).re)
+}
+
+/// Shifts the waveform later by `shift` samples, which may be fractional and
+/// negative.
+///
+/// The fractional part is interpolated with a Blackman windowed sinc kernel
+/// of `half_width` taps on each side, so the waveform should be band limited
+/// well below the Nyquist frequency. Samples beyond the ends of the waveform
+/// hold the first and the last sample, which keeps constant offsets and held
+/// padding intact at the edges. Integer shifts only move the samples.
+///
+/// # Panics
+///
+/// Panics if `shift` is not finite or `half_width` is zero.
+pub fn shift_waveform(waveform: ArrayView2<f64>, shift: f64, half_width: usize) -> Array2<f64> {
+    assert!(shift.is_finite());
+    assert!(half_width > 0);
+    let length = waveform.ncols();
+    let mut result = Array2::zeros(waveform.raw_dim());
+    if length == 0 {
+        return result;
+    }
+    let integer = shift.floor();
+    let fraction = shift - integer;
+    // The kernel weights the samples `i - integer - 1 + d` for `d` starting
+    // from `first`, which are `1 - fraction - d` before the position `i - shift`.
+    let (kernel, first) = if fraction == 0.0 {
+        (vec![1.0], 1)
+    } else {
+        let n = half_width as isize;
+        let kernel: Vec<_> = (1 - n..=n)
+            .map(|d| windowed_sinc(1.0 - fraction - d as f64, half_width as f64))
+            .collect();
+        let sum: f64 = kernel.iter().sum();
+        (kernel.into_iter().map(|k| k / sum).collect(), 1 - n)
+    };
+    let integer = integer as isize;
+    for (src, mut dst) in waveform.rows().into_iter().zip(result.rows_mut()) {
+        for (i, y) in dst.iter_mut().enumerate() {
+            let base = i as isize - integer - 1 + first;
+            *y = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, &h)| {
+                    let j = (base + k as isize).clamp(0, length as isize - 1);
+                    h * src[j as usize]
+                })
+                .sum();
+        }
+    }
+    result
+}
+
+fn windowed_sinc(t: f64, half_width: f64) -> f64 {
+    if t.abs() >= half_width {
+        return 0.0;
+    }
+    let x = std::f64::consts::PI * t;
+    let sinc = if t == 0.0 { 1.0 } else { x.sin() / x };
+    let phase = x / half_width;
+    sinc * (0.42 + 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos())
+}
+
+/// Samples the envelope the same way as pulses in waveforms, starting exactly
+/// at a sample point. Fails only if sampling a custom shape fails.
+pub fn sample_envelope_at(
+    envelope: &Envelope,
+    sample_rate: Frequency,
+) -> anyhow::Result<Arc<Vec<f64>>> {
+    let index_offset = AlignedIndex::new(Time::ZERO, sample_rate, 0).unwrap();
+    match &envelope.shape {
+        Some(shape) => get_envelope(
+            shape.clone(),
+            envelope.width,
+            envelope.plateau,
+            index_offset,
+            sample_rate,
+        ),
+        None => {
+            let length = (envelope.plateau.value() * sample_rate.value()).ceil() as usize;
+            Ok(Arc::new(vec![1.0; length]))
+        }
+    }
+}
+
+/// Area of the sampled envelope with unit amplitude.
+///
+/// The imaginary part is the contribution of the DRAG correction, which is
+/// ignored for rectangular pulses like in waveforms.
+pub fn envelope_area(
+    envelope: &Envelope,
+    drag_coef: f64,
+    sample_rate: Frequency,
+) -> anyhow::Result<Complex64> {
+    let samples = sample_envelope_at(envelope, sample_rate)?;
+    let dt = sample_rate.dt().value();
+    let area = samples.iter().sum::<f64>() * dt;
+    let drag_area = if envelope.shape.is_some() {
+        // Drag amplitude is multiplied by sample rate when sampling.
+        envelope_slope(&samples, 0).sum::<f64>() * drag_coef
+    } else {
+        0.0
+    };
+    Ok(Complex64::new(area, drag_area))
+}
+
+/// Full width at half maximum of the sampled envelope.
+///
+/// Half maximum crossings are linearly interpolated between samples.
+pub fn envelope_fwhm(envelope: &Envelope, sample_rate: Frequency) -> anyhow::Result<f64> {
+    let samples = sample_envelope_at(envelope, sample_rate)?;
+    let max = samples.iter().copied().fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Ok(0.0);
+    }
+    let half = max / 2.0;
+    // Pad with zeros such that crossings always exist.
+    let padded: Vec<_> = iter::once(0.0)
+        .chain(samples.iter().copied())
+        .chain(iter::once(0.0))
+        .collect();
+    let first = padded.iter().position(|&x| x >= half).unwrap();
+    let last = padded.iter().rposition(|&x| x >= half).unwrap();
+    let left =
+        (first - 1) as f64 + (half - padded[first - 1]) / (padded[first] - padded[first - 1]);
+    let right = last as f64 + (padded[last] - half) / (padded[last] - padded[last + 1]);
+    Ok((right - left) * sample_rate.dt().value())
+}
+
+/// Hold the value of the last non-zero sample until the end of the waveform.
+pub fn apply_hold_inplace(waveform: &mut ArrayViewMut2<f64>) {
+    let Some(last) = last_nonzero_index(waveform.view()) else {
+        return;
+    };
+    let value = waveform.column(last).to_owned();
+    for mut col in waveform.slice_mut(s![.., last + 1..]).columns_mut() {
+        col.assign(&value);
+    }
+}
+
+/// Linearly ramp the value of the last non-zero sample to zero in `length`
+/// samples.
+pub fn apply_ramp_inplace(waveform: &mut ArrayViewMut2<f64>, length: usize) {
+    let Some(last) = last_nonzero_index(waveform.view()) else {
+        return;
+    };
+    let value = waveform.column(last).to_owned();
+    for (i, mut col) in waveform
+        .slice_mut(s![.., last + 1..])
+        .columns_mut()
+        .into_iter()
+        .take(length)
+        .enumerate()
+    {
+        let ratio = 1.0 - (i + 1) as f64 / length as f64;
+        col.assign(&(&value * ratio));
+    }
+}
+
+/// Force the samples in the time ranges `(start, end)` to zero. The times are
+/// relative to the first sample of the channel, and partially covered samples
+/// are also zeroed. `start` is the index of the first sample of `waveform` in
+/// the channel.
+pub fn apply_blanking_inplace(
+    waveform: &mut ArrayViewMut2<f64>,
+    ranges: &[(Time, Time)],
+    sample_rate: Frequency,
+    start: usize,
+) {
+    let length = waveform.shape()[1];
+    let to_index = |x: f64| ((x - start as f64).max(0.0) as usize).min(length);
+    for &(start, end) in ranges {
+        let start = to_index((start.value() * sample_rate.value()).floor());
+        let end = to_index((end.value() * sample_rate.value()).ceil());
+        if start < end {
+            waveform.slice_mut(s![.., start..end]).fill(0.0);
+        }
+    }
+}
+
+fn last_nonzero_index(waveform: ArrayView2<f64>) -> Option<usize> {
+    (0..waveform.shape()[1])
+        .rev()
+        .find(|&i| waveform.column(i).iter().any(|&x| x != 0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::TAU;
+
+    use ndarray::{array, Array2, Array3};
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn fir_group_delay() {
+        let taps = array![1.0, 2.0, 1.0];
+
+        for freq in [0.0, 0.1, 0.3] {
+            let delay = group_delay(None, Some(taps.view()), freq).unwrap();
+            assert!((delay - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn exp_correction_restores_step() {
+        let (amplitude, tau) = (-0.1, Time::new(20.0).unwrap());
+        let sample_rate = Frequency::new(1.0).unwrap();
+        let r = (-1.0 / 20.0f64).exp();
+        let distortion = array![[1.0 + amplitude, -(r + amplitude), 0.0, 1.0, -r, 0.0]];
+        let mut step = Array2::ones((1, 100));
+        apply_iir_inplace(&mut step.view_mut(), distortion.view());
+        assert!((step[[0, 10]] - (1.0 + amplitude * r.powi(10))).abs() < 1e-12);
+
+        let sos = exp_correction_sos(&[(amplitude, tau)], sample_rate).unwrap();
+        apply_iir_inplace(&mut step.view_mut(), sos.view());
+
+        assert!(step.iter().all(|&x| (x - 1.0).abs() < 1e-12));
+        assert!(exp_correction_sos(&[(-1.5, tau)], sample_rate).is_err());
+        assert!(exp_correction_sos(&[(0.1, Time::ZERO)], sample_rate).is_err());
+    }
+
+    #[test]
+    fn predistortion_inverts_compression() {
+        let coefficients = [1.0, 0.0, -0.2];
+        let compress = |x: f64| x - 0.2 * x.powi(3);
+        let predistortion = Predistortion::from_polynomial(&coefficients).unwrap();
+        let mut waveform = array![[0.3, -0.5, 0.0, 0.6], [0.4, 0.2, -0.7, 0.0]];
+        let wanted = waveform.clone();
+        apply_predistortion_inplace(&mut waveform.view_mut(), &predistortion);
+
+        for (col, wanted) in waveform.columns().into_iter().zip(wanted.columns()) {
+            let amplitude = col[0].hypot(col[1]);
+            let scale = compress(amplitude) / amplitude;
+            assert!((col[0] * scale - wanted[0]).abs() < 1e-6);
+            assert!((col[1] * scale - wanted[1]).abs() < 1e-6);
+        }
+        let table = Predistortion::from_table(&[(0.5, 0.4), (1.0, 0.6)]).unwrap();
+        let mut real = array![[0.2, -0.5, 0.9]];
+        apply_predistortion_inplace(&mut real.view_mut(), &table);
+        assert_eq!(real, array![[0.25, -0.75, 1.0]]);
+        assert!(Predistortion::from_polynomial(&[1.0, 0.0, -1.0]).is_err());
+        assert!(Predistortion::from_table(&[(0.5, 0.4), (1.0, 0.4)]).is_err());
+    }
+
+    #[test]
+    fn shift_waveform_samples() {
+        let waveform = array![[0.0, 1.0, 2.0, 3.0]];
+        assert_eq!(
+            shift_waveform(waveform.view(), 1.0, 8),
+            array![[0.0, 0.0, 1.0, 2.0]]
+        );
+        assert_eq!(
+            shift_waveform(waveform.view(), -2.0, 8),
+            array![[2.0, 3.0, 3.0, 3.0]]
+        );
+        let constant = Array2::from_elem((2, 50), 0.3);
+        let shifted = shift_waveform(constant.view(), 2.5, 8);
+        assert!(shifted.iter().all(|&x| (x - 0.3).abs() < 1e-12));
+
+        let freq = 0.05;
+        let sine = Array2::from_shape_fn((1, 200), |(_, i)| (TAU * freq * i as f64).sin());
+        let shifted = shift_waveform(sine.view(), 0.3, 16);
+        for i in 20..180 {
+            let expected = (TAU * freq * (i as f64 - 0.3)).sin();
+            assert!((shifted[(0, i)] - expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn iir_group_delay() {
+        let p = 0.5;
+        let sos = array![
+            [1.0 - p, 0.0, 0.0, 1.0, -p, 0.0],
+            [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+        ];
+
+        let delay = group_delay(Some(sos.view()), None, 0.0).unwrap();
+
+        assert!((delay - p / (1.0 - p)).abs() < 1e-12);
+        let highpass = array![[1.0, -1.0, 0.0, 1.0, 0.0, 0.0]];
+        assert_eq!(group_delay(Some(highpass.view()), None, 0.0), None);
+    }
+
+    #[test_case(None, 2; "channel level")]
+    #[test_case(Some(-10), 2; "finer than channel")]
+    #[test_case(Some(2), 4; "coarser than channel")]
+    fn pulse_align_level(align_level: Option<i32>, expected_start: usize) {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: Envelope::new(None, Time::new(1.0).unwrap(), Time::ZERO),
+            global_freq: Frequency::ZERO,
+            local_freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            time: Time::new(1.5).unwrap(),
+            amplitude: Amplitude::new(1.0).unwrap(),
+            drag_coef: 0.0,
+            phase: Phase::ZERO,
+            align_level,
+        });
+        let mut waveform: Array2<f64> = Array2::zeros((2, 8));
+        let sample_rate = Frequency::new(1.0).unwrap();
+
+        sample_pulse_list(
+            builder.build().iter(),
+            waveform.view_mut(),
+            Window {
+                start: 0,
+                length: 8,
+            },
+            ChannelParams {
+                sample_rate,
+                align_level: -10,
+                delay: Time::ZERO,
+                overflow: Overflow::Error,
+                precision: EnvelopePrecision::F64,
+            },
+        )
+        .unwrap();
+
+        let start = waveform.row(0).iter().position(|&x| x != 0.0);
+        assert_eq!(start, Some(expected_start));
+    }
+
+    #[test]
+    fn dc_plateau() {
+        let carrier = |dphase| Carrier {
+            phase: Phase::new(0.125).unwrap(),
+            dphase,
+            ddphase: Phase::ZERO,
+        };
+        let amplitude = Complex64::new(0.5, 0.0);
+        let mut fast: Array2<f64> = Array2::ones((2, 4));
+        let mut slow: Array2<f64> = Array2::ones((2, 4));
+
+        mix_add_plateau(fast.view_mut(), amplitude, carrier(Phase::ZERO));
+        // A full turn per sample takes the general path with the same result.
+        mix_add_plateau(
+            slow.view_mut(),
+            amplitude,
+            carrier(Phase::new(1.0).unwrap()),
+        );
+
+        let expected = 1.0 + 0.5 * std::f64::consts::FRAC_1_SQRT_2;
+        assert!(fast.iter().all(|&x| (x - expected).abs() < 1e-12));
+        assert!(izip!(&fast, &slow).all(|(a, b)| (a - b).abs() < 1e-12));
+    }
+
+    #[test]
+    fn flat_top_plateau() {
+        let envelope = [0.2, 0.6, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.6, 0.2];
+        let carrier = Carrier {
+            phase: Phase::new(0.125).unwrap(),
+            dphase: Phase::new(0.1).unwrap(),
+            ddphase: Phase::new(0.01).unwrap(),
+        };
+        let amplitude = Complex64::new(0.5, 0.25);
+        let drag_amp = Complex64::new(0.0, 0.3);
+
+        assert_eq!(plateau_interior(&envelope), 3..7);
+        for (skip, len) in [(0, 10), (1, 4), (4, 6), (5, 1), (8, 2)] {
+            let mut fast: Array2<f64> = Array2::ones((2, len));
+            let mut slow: Array2<f64> = Array2::ones((2, len));
+
+            mix_add_envelope(
+                fast.view_mut(),
+                &envelope,
+                skip,
+                amplitude,
+                drag_amp,
+                carrier,
+            );
+            mix_add_shaped(
+                slow.view_mut(),
+                &envelope,
+                skip,
+                amplitude,
+                drag_amp,
+                carrier,
+            );
+
+            assert!(izip!(&fast, &slow).all(|(a, b)| (a - b).abs() < 1e-12));
+        }
+    }
+
+    #[test]
+    fn pulse_list_stats() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::new(1e-9).unwrap());
+        let mut push = |width: f64, time: f64| {
+            builder.push(PushArgs {
+                envelope: Envelope::new(None, Time::new(width).unwrap(), Time::ZERO),
+                global_freq: Frequency::ZERO,
+                local_freq: Frequency::ZERO,
+                chirp_rate: ChirpRate::ZERO,
+                time: Time::new(time).unwrap(),
+                amplitude: Amplitude::new(1.0).unwrap(),
+                drag_coef: 0.0,
+                phase: Phase::ZERO,
+                align_level: None,
+            })
+        };
+        push(1.0, 0.0);
+        push(1.0, 0.0);
+        push(1.0, 2.0);
+        push(2.0, 0.0);
+
+        let list = builder.build();
+
+        assert_eq!(list.len(), 3);
+        let counts = list.envelope_counts();
+        assert_eq!(counts.len(), 2);
+        let short = Envelope::new(None, Time::new(1.0).unwrap(), Time::ZERO);
+        assert_eq!(counts[&short], 2);
+    }
+
+    #[test]
+    fn timeline_matches_waveform() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: Envelope::new(None, Time::new(4.0).unwrap(), Time::ZERO),
+            global_freq: Frequency::new(0.1).unwrap(),
+            local_freq: Frequency::new(0.05).unwrap(),
+            chirp_rate: ChirpRate::ZERO,
+            time: Time::new(2.0).unwrap(),
+            amplitude: Amplitude::new(0.5).unwrap(),
+            drag_coef: 0.0,
+            phase: Phase::new(0.25).unwrap(),
+            align_level: None,
+        });
+        let list = builder.build();
+        let mut waveform: Array2<f64> = Array2::zeros((2, 8));
+
+        sample_pulse_list(
+            list.iter(),
+            waveform.view_mut(),
+            Window {
+                start: 0,
+                length: 8,
+            },
+            ChannelParams {
+                sample_rate: Frequency::new(1.0).unwrap(),
+                align_level: -10,
+                delay: Time::ZERO,
+                overflow: Overflow::Error,
+                precision: EnvelopePrecision::F64,
+            },
+        )
+        .unwrap();
+
+        let timeline = list.timeline();
+        assert_eq!(timeline.len(), 1);
+        let pulse = &timeline[0];
+        assert_eq!(pulse.time, Time::new(2.0).unwrap());
+        assert_eq!(pulse.envelope.plateau(), Time::new(4.0).unwrap());
+        for i in 2..6 {
+            let t = Time::new(i as f64).unwrap();
+            let value = pulse.amplitude * (pulse.freq * (t - pulse.time)).phaser();
+            float_cmp::assert_approx_eq!(f64, value.re, waveform[[0, i]], epsilon = 1e-12);
+            float_cmp::assert_approx_eq!(f64, value.im, waveform[[1, i]], epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn chirped_pulse() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: Envelope::new(None, Time::new(6.0).unwrap(), Time::ZERO),
+            global_freq: Frequency::new(0.1).unwrap(),
+            local_freq: Frequency::new(0.05).unwrap(),
+            chirp_rate: ChirpRate::new(0.02).unwrap(),
+            time: Time::new(1.25).unwrap(),
+            amplitude: Amplitude::new(0.5).unwrap(),
+            drag_coef: 0.0,
+            phase: Phase::new(0.25).unwrap(),
+            align_level: None,
+        });
+        let list = builder.build();
+        let params = ChannelParams {
+            sample_rate: Frequency::new(1.0).unwrap(),
+            align_level: -10,
+            delay: Time::ZERO,
+            overflow: Overflow::Error,
+            precision: EnvelopePrecision::F64,
+        };
+        let mut waveform: Array2<f64> = Array2::zeros((2, 10));
+        let window = |start| Window { start, length: 10 };
+        sample_pulse_list(list.iter(), waveform.view_mut(), window(0), params).unwrap();
+        // Sampling in chunks splits the pulse in the middle.
+        let mut chunked: Array2<f64> = Array2::zeros((2, 10));
+        let (first, second) = chunked.view_mut().split_at(Axis(1), 5);
+        sample_pulse_list(list.iter(), first, window(0), params).unwrap();
+        sample_pulse_list(list.iter(), second, window(5), params).unwrap();
+
+        let pulse = &list.timeline()[0];
+        let center = pulse.time + Time::new(3.0).unwrap();
+        for i in 0..10 {
+            let t = Time::new(i as f64).unwrap();
+            let expected = if (2..8).contains(&i) {
+                let chirp = pulse.chirp_rate * (t - center) * (t - center) / 2.0;
+                pulse.amplitude * (pulse.freq * (t - pulse.time) + chirp).phaser()
+            } else {
+                Complex64::ZERO
+            };
+            float_cmp::assert_approx_eq!(f64, expected.re, waveform[[0, i]], epsilon = 1e-12);
+            float_cmp::assert_approx_eq!(f64, expected.im, waveform[[1, i]], epsilon = 1e-12);
+        }
+        for (a, b) in waveform.iter().zip(&chunked) {
+            float_cmp::assert_approx_eq!(f64, *a, *b, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn build_merges_unsorted_runs() {
+        let amplitude = |v: f64| PulseAmplitude {
+            amp: Complex64::new(v, 0.0),
+            drag: Complex64::ZERO,
+        };
+        let mut pulses = Pulses::default();
+        for (t, v) in [(0.0, 1.0), (2.0, 2.0), (1.0, 3.0), (3.0, 4.0), (2.0, 5.0)] {
+            pulses.push(Time::new(t).unwrap(), amplitude(v));
+        }
+
+        let pulses = pulses.build(Time::new(1e-9).unwrap());
+
+        let result: Vec<_> = pulses.iter().map(|(t, a)| (t.value(), a.amp.re)).collect();
+        assert_eq!(result, vec![(0.0, 1.0), (1.0, 3.0), (2.0, 7.0), (3.0, 4.0)]);
+        assert!(pulses.run_starts.is_empty());
+    }
+
+    #[test]
+    fn drag_crosstalk_matrix() {
+        let sample = |drag_coef: f64, drag_matrix: Option<Array2<f64>>| {
+            let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::new(1e-9).unwrap());
+            builder.push(PushArgs {
+                envelope: Envelope::new(
+                    Some(Shape::new_hann()),
+                    Time::new(8.0).unwrap(),
+                    Time::ZERO,
+                ),
+                global_freq: Frequency::ZERO,
+                local_freq: Frequency::ZERO,
+                chirp_rate: ChirpRate::ZERO,
+                time: Time::ZERO,
+                amplitude: Amplitude::new(1.0).unwrap(),
+                drag_coef,
+                phase: Phase::ZERO,
+                align_level: None,
+            });
+            let pulse_lists = [
+                (ChannelId::new("a"), builder.build()),
+                (
+                    ChannelId::new("b"),
+                    PulseListBuilder::new(Amplitude::ZERO, Time::ZERO).build(),
+                ),
+            ]
+            .into_iter()
+            .collect();
+            let matrix = array![[1.0, 0.0], [0.5, 1.0]];
+            let mut waveform = Array2::zeros((2, 8));
+            let mut sampler = Sampler::new(pulse_lists);
+            sampler.add_channel(
+                ChannelId::new("b"),
+                waveform.view_mut(),
+                Frequency::new(1.0).unwrap(),
+                Time::ZERO,
+                0,
+                Overflow::Error,
+            );
+            let names = vec![ChannelId::new("a"), ChannelId::new("b")];
+            let crosstalk = Crosstalk::new(matrix.view(), names);
+            sampler.set_crosstalk(match &drag_matrix {
+                Some(m) => crosstalk.with_drag_matrix(m.view()),
+                None => crosstalk,
+            });
+            sampler.sample(Time::ZERO).unwrap();
+            waveform
+        };
+
+        let with_drag = sample(1.0, None);
+        let drag_excluded = sample(1.0, Some(Array2::eye(2)));
+        let without_drag = sample(0.0, None);
+
+        assert_ne!(with_drag, without_drag);
+        assert_eq!(drag_excluded, without_drag);
+    }
+
+    #[test_case(Overflow::Truncate, [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]; "truncate")]
+    #[test_case(Overflow::Wrap, [1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]; "wrap")]
+    fn overflow_policy(overflow: Overflow, expected: [f64; 8]) {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: Envelope::new(None, Time::ZERO, Time::new(6.0).unwrap()),
+            global_freq: Frequency::ZERO,
+            local_freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            time: Time::new(4.0).unwrap(),
+            amplitude: Amplitude::new(1.0).unwrap(),
+            drag_coef: 0.0,
+            phase: Phase::ZERO,
+            align_level: None,
+        });
+        let pulse_lists: HashMap<_, _> = [(ChannelId::new("a"), builder.build())]
+            .into_iter()
+            .collect();
+        let sample_rate = Frequency::new(1.0).unwrap();
+        let sample = |overflow| {
+            let mut waveform = Array2::zeros((2, 8));
+            let mut sampler = Sampler::new(pulse_lists.clone());
+            sampler.add_channel(
+                ChannelId::new("a"),
+                waveform.view_mut(),
+                sample_rate,
+                Time::ZERO,
+                0,
+                overflow,
+            );
+            sampler.sample(Time::ZERO).map(|stats| (waveform, stats))
+        };
+
+        let (waveform, stats) = sample(overflow).unwrap();
+        let error = sample(Overflow::Error).unwrap_err();
+
+        assert_eq!(waveform.row(0).to_vec(), expected);
+        assert_eq!(
+            stats[&ChannelId::new("a")],
+            OverflowStats {
+                pulses: 1,
+                samples: 2
+            }
+        );
+        assert!(matches!(
+            error.kind,
+            ErrorKind::EndTimeOutOfBounds {
+                index: 10,
+                length: 8,
+                ..
+            }
+        ));
+        assert_eq!(error.pulse_time, Time::new(4.0).unwrap());
+
+        let mut chunk_sampler = ChunkSampler::new(&pulse_lists);
+        chunk_sampler.add_channel(ChannelId::new("a"), 8, sample_rate, Time::ZERO, 0, overflow);
+        for start in (0..8).step_by(3) {
+            let mut chunk = Array2::zeros((2, 3.min(8 - start)));
+            let mut buffers = [(ChannelId::new("a"), chunk.view_mut())]
+                .into_iter()
+                .collect();
+            let stats = chunk_sampler
+                .sample_chunk(start, &mut buffers, Time::ZERO)
+                .unwrap();
+            drop(buffers);
+            assert_eq!(stats.is_empty(), start != 0);
+            assert_eq!(
+                chunk,
+                waveform.slice(s![.., start..start + chunk.shape()[1]])
+            );
+        }
+    }
+
+    #[test]
+    fn f32_envelope_within_bound() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: Envelope::new(
+                Some(Shape::new_hann()),
+                Time::new(100.0).unwrap(),
+                Time::new(10.0).unwrap(),
+            ),
+            global_freq: Frequency::new(0.1).unwrap(),
+            local_freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            time: Time::new(0.3).unwrap(),
+            amplitude: Amplitude::new(0.8).unwrap(),
+            drag_coef: 0.3,
+            phase: Phase::ZERO,
+            align_level: None,
+        });
+        let pulse_lists: HashMap<_, _> = [(ChannelId::new("a"), builder.build())]
+            .into_iter()
+            .collect();
+        let sample = |precision| {
+            let mut waveform = Array2::zeros((2, 128));
+            let mut sampler = Sampler::new(pulse_lists.clone());
+            sampler.add_channel(
+                ChannelId::new("a"),
+                waveform.view_mut(),
+                Frequency::new(1.0).unwrap(),
+                Time::ZERO,
+                -10,
+                Overflow::Error,
+            );
+            sampler.set_envelope_precision(&ChannelId::new("a"), precision);
+            sampler.sample(Time::ZERO).unwrap();
+            waveform
+        };
+
+        let reference = sample(EnvelopePrecision::F64);
+        let single = sample(EnvelopePrecision::F32);
+
+        let max_error = (&single - &reference)
+            .iter()
+            .fold(0.0f64, |acc, x| acc.max(x.abs()));
+        assert!(max_error > 0.0);
+        assert!(max_error <= 2f64.powi(-24) * (0.8 + 0.3));
+    }
+
+    #[test]
+    fn custom_shape_cache_and_errors() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let custom = |cacheable, calls: Arc<AtomicUsize>| {
+            let function = move |_, _, array: &mut [f64]| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                array.fill(1.0);
+                Ok(())
+            };
+            Shape::new_custom(Arc::new(function), cacheable)
+        };
+        let sample_rate = Frequency::new(1.0).unwrap();
+        let envelope = |shape| Envelope::new(Some(shape), Time::new(8.0).unwrap(), Time::ZERO);
+        for cacheable in [true, false] {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let envelope = envelope(custom(cacheable, calls.clone()));
+            for _ in 0..2 {
+                let samples = sample_envelope_at(&envelope, sample_rate).unwrap();
+                assert_eq!(*samples, [1.0; 8]);
+            }
+            assert_eq!(calls.load(Ordering::Relaxed), if cacheable { 1 } else { 2 });
+        }
+
+        let failing = Shape::new_custom(Arc::new(|_, _, _| anyhow::bail!("boom")), true);
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: envelope(failing),
+            global_freq: Frequency::ZERO,
+            local_freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            time: Time::new(2.0).unwrap(),
+            amplitude: Amplitude::new(0.5).unwrap(),
+            drag_coef: 0.0,
+            phase: Phase::ZERO,
+            align_level: None,
+        });
+        let pulse_lists = [(ChannelId::new("a"), builder.build())].into();
+        let mut waveform = Array2::zeros((1, 16));
+        let mut sampler = Sampler::new(pulse_lists);
+        sampler.add_channel(
+            ChannelId::new("a"),
+            waveform.view_mut(),
+            sample_rate,
+            Time::ZERO,
+            0,
+            Overflow::Error,
+        );
+        let err = sampler.sample(Time::ZERO).unwrap_err();
+        assert_eq!(err.kind.code(), "shape_failed");
+        assert_eq!(err.kind.time(), 2.0);
+    }
+
+    #[test]
+    fn chunks_match_full_waveform() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        for (i, plateau) in [0.0, 3.0, 0.0].into_iter().enumerate() {
+            builder.push(PushArgs {
+                envelope: Envelope::new(
+                    Some(Shape::new_hann()),
+                    Time::new(6.0).unwrap(),
+                    Time::new(plateau).unwrap(),
+                ),
+                global_freq: Frequency::new(0.1).unwrap(),
+                local_freq: Frequency::new(0.03).unwrap(),
+                chirp_rate: ChirpRate::ZERO,
+                time: Time::new(1.3 + 9.0 * i as f64).unwrap(),
+                amplitude: Amplitude::new(0.5).unwrap(),
+                drag_coef: 0.2,
+                phase: Phase::new(0.1).unwrap(),
+                align_level: None,
+            });
+        }
+        let pulse_lists: HashMap<_, _> = [(ChannelId::new("a"), builder.build())]
+            .into_iter()
+            .collect();
+        let sample_rate = Frequency::new(1.0).unwrap();
+        let delay = Time::new(0.5).unwrap();
+        let mut full = Array2::zeros((2, 32));
+        let mut sampler = Sampler::new(pulse_lists.clone());
+        sampler.add_channel(
+            ChannelId::new("a"),
+            full.view_mut(),
+            sample_rate,
+            delay,
+            -10,
+            Overflow::Error,
+        );
+        sampler.sample(Time::ZERO).unwrap();
+
+        let mut chunk_sampler = ChunkSampler::new(&pulse_lists);
+        chunk_sampler.add_channel(
+            ChannelId::new("a"),
+            32,
+            sample_rate,
+            delay,
+            -10,
+            Overflow::Error,
+        );
+        for start in (0..32).step_by(5) {
+            let mut chunk = Array2::zeros((2, 5.min(32 - start)));
+            let mut buffers = [(ChannelId::new("a"), chunk.view_mut())]
+                .into_iter()
+                .collect();
+            chunk_sampler
+                .sample_chunk(start, &mut buffers, Time::ZERO)
+                .unwrap();
+            drop(buffers);
+            let expected = full.slice(s![.., start..start + chunk.shape()[1]]);
+            for (x, y) in chunk.iter().zip(expected) {
+                float_cmp::assert_approx_eq!(f64, *x, *y, epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn piecewise_crosstalk() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        for time in [0.0, 10.0] {
+            builder.push(PushArgs {
+                envelope: Envelope::new(
+                    Some(Shape::new_hann()),
+                    Time::new(6.0).unwrap(),
+                    Time::ZERO,
+                ),
+                global_freq: Frequency::new(0.1).unwrap(),
+                local_freq: Frequency::ZERO,
+                chirp_rate: ChirpRate::ZERO,
+                time: Time::new(time).unwrap(),
+                amplitude: Amplitude::new(0.5).unwrap(),
+                drag_coef: 0.0,
+                phase: Phase::ZERO,
+                align_level: None,
+            });
+        }
+        let (a, b) = (ChannelId::new("a"), ChannelId::new("b"));
+        let pulse_lists = [
+            (a.clone(), builder.build()),
+            (
+                b.clone(),
+                PulseListBuilder::new(Amplitude::ZERO, Time::ZERO).build(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let before = array![[1.0, 0.0], [0.5, 1.0]];
+        let after = array![[1.0, 0.0], [0.2, 1.0]];
+        let mut full_a = Array2::zeros((2, 20));
+        let mut full_b = Array2::zeros((2, 20));
+        let mut sampler = Sampler::new(pulse_lists);
+        for (n, w) in [(&a, full_a.view_mut()), (&b, full_b.view_mut())] {
+            let sample_rate = Frequency::new(1.0).unwrap();
+            sampler.add_channel(n.clone(), w, sample_rate, Time::ZERO, 0, Overflow::Error);
+        }
+        let segments = vec![
+            (Time::ZERO, before.view()),
+            (Time::new(8.0).unwrap(), after.view()),
+        ];
+        sampler.set_crosstalk(Crosstalk::piecewise(segments, vec![a, b]));
+        sampler.sample(Time::ZERO).unwrap();
+
+        let mut expected = full_a.clone();
+        expected.slice_mut(s![.., ..8]).mapv_inplace(|x| x * 0.5);
+        expected.slice_mut(s![.., 8..]).mapv_inplace(|x| x * 0.2);
+        assert!(full_a.slice(s![.., 10..]).iter().any(|&x| x != 0.0));
+        for (x, y) in full_b.iter().zip(&expected) {
+            float_cmp::assert_approx_eq!(f64, *x, *y, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn kernel_crosstalk() {
+        let mut builder = PulseListBuilder::new(Amplitude::ZERO, Time::ZERO);
+        builder.push(PushArgs {
+            envelope: Envelope::new(
+                Some(Shape::new_hann()),
+                Time::new(6.0).unwrap(),
+                Time::new(2.0).unwrap(),
+            ),
+            global_freq: Frequency::new(0.1).unwrap(),
+            local_freq: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
+            time: Time::new(3.0).unwrap(),
+            amplitude: Amplitude::new(0.5).unwrap(),
+            drag_coef: 0.0,
+            phase: Phase::ZERO,
+            align_level: None,
+        });
+        let (a, b) = (ChannelId::new("a"), ChannelId::new("b"));
+        let pulse_lists: HashMap<_, _> = [
+            (a.clone(), builder.build()),
+            (
+                b.clone(),
+                PulseListBuilder::new(Amplitude::ZERO, Time::ZERO).build(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let sample_rate = Frequency::new(1.0).unwrap();
+        let mut kernels = Array3::zeros((2, 2, 4));
+        kernels[[0, 0, 0]] = 1.0;
+        kernels[[1, 1, 0]] = 1.0;
+        kernels
+            .slice_mut(s![1, 0, ..])
+            .assign(&array![0.0, 0.5, 0.2, 0.1]);
+        let names = vec![a.clone(), b.clone()];
+        let mut full_a = Array2::zeros((2, 24));
+        let mut full_b = Array2::zeros((2, 24));
+        let mut sampler = Sampler::new(pulse_lists.clone());
+        for (n, w) in [(&a, full_a.view_mut()), (&b, full_b.view_mut())] {
+            sampler.add_channel(n.clone(), w, sample_rate, Time::ZERO, 0, Overflow::Error);
+        }
+        sampler.set_crosstalk(Crosstalk::from_kernels(kernels.view(), names.clone()));
+        sampler.sample(Time::ZERO).unwrap();
+
+        let mut expected = full_a.clone();
+        apply_fir_inplace(&mut expected.view_mut(), kernels.slice(s![1, 0, ..]));
+        assert!(full_a.iter().any(|&x| x != 0.0));
+        for (x, y) in full_b.iter().zip(&expected) {
+            float_cmp::assert_approx_eq!(f64, *x, *y, epsilon = 1e-12);
+        }
+        let mut chunk_sampler = ChunkSampler::new(&pulse_lists);
+        chunk_sampler.add_channel(b.clone(), 24, sample_rate, Time::ZERO, 0, Overflow::Error);
+        chunk_sampler.set_crosstalk(Crosstalk::from_kernels(kernels.view(), names));
+        for start in (0..24).step_by(5) {
+            let mut chunk = Array2::zeros((2, 5.min(24 - start)));
+            let mut buffers = [(b.clone(), chunk.view_mut())].into_iter().collect();
+            chunk_sampler
+                .sample_chunk(start, &mut buffers, Time::ZERO)
+                .unwrap();
+            drop(buffers);
+            let expected = full_b.slice(s![.., start..start + chunk.shape()[1]]);
+            for (x, y) in chunk.iter().zip(expected) {
+                float_cmp::assert_approx_eq!(f64, *x, *y, epsilon = 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn rect_envelope_area_and_fwhm() {
+        let envelope = Envelope::new(None, Time::new(4.0).unwrap(), Time::ZERO);
+        let sample_rate = Frequency::new(1.0).unwrap();
+
+        let area = envelope_area(&envelope, 1.0, sample_rate).unwrap();
+        let fwhm = envelope_fwhm(&envelope, sample_rate).unwrap();
+
+        assert_eq!(area, Complex64::new(4.0, 0.0));
+        assert_eq!(fwhm, 4.0);
+    }
+
+    #[test]
+    fn hann_envelope_area_and_fwhm() {
+        let envelope = Envelope::new(
+            Some(Shape::new_hann()),
+            Time::new(100.0).unwrap(),
+            Time::new(10.0).unwrap(),
+        );
+        let sample_rate = Frequency::new(1.0).unwrap();
+
+        let area = envelope_area(&envelope, 0.0, sample_rate).unwrap();
+        let fwhm = envelope_fwhm(&envelope, sample_rate).unwrap();
+
+        assert!((area.re - 60.0).abs() < 1e-6);
+        assert_eq!(area.im, 0.0);
+        assert!((fwhm - 60.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn hold_last_sample() {
+        let mut waveform: Array2<f64> =
+            array![[0.0, 1.0, 2.0, 0.0, 0.0], [0.0, 0.5, 1.0, 0.0, 0.0]];
+
+        apply_hold_inplace(&mut waveform.view_mut());
+
+        assert_eq!(
+            waveform,
+            array![[0.0, 1.0, 2.0, 2.0, 2.0], [0.0, 0.5, 1.0, 1.0, 1.0]]
+        );
+    }
+
+    #[test]
+    fn ramp_to_zero() {
+        let mut waveform: Array2<f64> = array![[4.0, 0.0, 0.0, 0.0, 0.0, 0.0]];
+
+        apply_ramp_inplace(&mut waveform.view_mut(), 4);
+
+        assert_eq!(waveform, array![[4.0, 3.0, 2.0, 1.0, 0.0, 0.0]]);
+    }
+
+    #[test]
+    fn blanking_partial_samples() {
+        let mut waveform: Array2<f64> = Array2::ones((2, 8));
+        let ranges = [
+            (Time::new(1.5).unwrap(), Time::new(3.0).unwrap()),
+            (Time::new(-1.0).unwrap(), Time::new(0.5).unwrap()),
+            (Time::new(7.0).unwrap(), Time::new(100.0).unwrap()),
+        ];
+
+        apply_blanking_inplace(
+            &mut waveform.view_mut(),
+            &ranges,
+            Frequency::new(1.0).unwrap(),
+            0,
+        );
+
+        let expected = array![0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0];
+        assert_eq!(waveform.row(0), expected);
+        assert_eq!(waveform.row(1), expected);
+    }
+
+    #[test]
+    fn padding_all_zero() {
+        let mut waveform: Array2<f64> = Array2::zeros((2, 4));
+
+        apply_hold_inplace(&mut waveform.view_mut());
+        apply_ramp_inplace(&mut waveform.view_mut(), 2);
+
+        assert_eq!(waveform, Array2::<f64>::zeros((2, 4)));
+    }
+}
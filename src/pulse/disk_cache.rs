@@ -0,0 +1,297 @@
+//! Persistent envelope cache shared between processes.
+//!
+//! Each envelope is stored in a separate file. The file starts with a header
+//! containing the format version, the crate version and the full key, so that
+//! stale or colliding entries are treated as misses.
+
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+use crate::quant::{AlignedIndex, Frequency, Time};
+
+const MAGIC: &[u8; 8] = b"BSENV001";
+const EXTENSION: &str = "env";
+const HEADER_LEN: usize = MAGIC.len() + 8 + Key::LEN;
+
+static GLOBAL: RwLock<Option<DiskCache>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Key {
+    fingerprint: u64,
+    width: f64,
+    plateau: f64,
+    index_offset: f64,
+    sample_rate: f64,
+}
+
+#[derive(Debug)]
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+    size: Mutex<u64>,
+}
+
+impl Key {
+    const LEN: usize = 5 * 8;
+
+    pub(crate) fn new(
+        fingerprint: u64,
+        width: Time,
+        plateau: Time,
+        index_offset: AlignedIndex,
+        sample_rate: Frequency,
+    ) -> Self {
+        Self {
+            fingerprint,
+            width: width.value(),
+            plateau: plateau.value(),
+            index_offset: index_offset.value(),
+            sample_rate: sample_rate.value(),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        let values = [
+            self.fingerprint,
+            self.width.to_bits(),
+            self.plateau.to_bits(),
+            self.index_offset.to_bits(),
+            self.sample_rate.to_bits(),
+        ];
+        for (chunk, v) in bytes.chunks_exact_mut(8).zip(values) {
+            chunk.copy_from_slice(&v.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+impl DiskCache {
+    pub(crate) fn new(dir: impl Into<PathBuf>, max_size: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let size = entries(&dir)?.iter().map(|e| e.len).sum();
+        Ok(Self {
+            dir,
+            max_size,
+            size: Mutex::new(size),
+        })
+    }
+
+    pub(crate) fn load(&self, key: Key) -> Option<Vec<f64>> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        if bytes.len() < HEADER_LEN || bytes[..HEADER_LEN] != header(key) {
+            return None;
+        }
+        let data = &bytes[HEADER_LEN..];
+        if data.len() % 8 != 0 {
+            return None;
+        }
+        let envelope = data
+            .chunks_exact(8)
+            .map(|c| f64::from_le_bytes(c.try_into().expect("Chunk should have 8 bytes")))
+            .collect();
+        Some(envelope)
+    }
+
+    pub(crate) fn store(&self, key: Key, envelope: &[f64]) -> io::Result<()> {
+        let len = (HEADER_LEN + envelope.len() * 8) as u64;
+        if len > self.max_size {
+            return Ok(());
+        }
+        let mut bytes = Vec::with_capacity(len as usize);
+        bytes.extend_from_slice(&header(key));
+        for v in envelope {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        // Write to a temporary file first so that other processes never read
+        // a partially written entry.
+        let path = self.path(key);
+        let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp_path, &bytes)?;
+        fs::rename(&tmp_path, &path)?;
+        let mut size = self.size.lock().expect("Lock should not be poisoned");
+        *size += len;
+        if *size > self.max_size {
+            *size = self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Removes all entries in the cache directory.
+    pub(crate) fn clear(&self) -> io::Result<()> {
+        let mut size = self.size.lock().expect("Lock should not be poisoned");
+        for entry in entries(&self.dir)? {
+            remove_entry(&entry.path)?;
+        }
+        *size = 0;
+        Ok(())
+    }
+
+    /// Removes the oldest entries until the total size fits in the limit.
+    /// Returns the remaining total size.
+    fn evict(&self) -> io::Result<u64> {
+        let mut entries = entries(&self.dir)?;
+        entries.sort_by_key(|e| e.modified);
+        let mut size: u64 = entries.iter().map(|e| e.len).sum();
+        for entry in entries {
+            if size <= self.max_size {
+                break;
+            }
+            remove_entry(&entry.path)?;
+            size -= entry.len;
+        }
+        Ok(size)
+    }
+
+    fn path(&self, key: Key) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        key.to_bytes().hash(&mut hasher);
+        self.dir
+            .join(format!("{:016x}", hasher.finish()))
+            .with_extension(EXTENSION)
+    }
+}
+
+/// Sets the cache used by [`load`] and [`store`]. `None` disables the cache.
+pub(crate) fn set_global(cache: Option<DiskCache>) {
+    *GLOBAL.write().expect("Lock should not be poisoned") = cache;
+}
+
+pub(crate) fn clear_global() -> io::Result<()> {
+    match &*GLOBAL.read().expect("Lock should not be poisoned") {
+        Some(cache) => cache.clear(),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn load(key: Key) -> Option<Vec<f64>> {
+    GLOBAL
+        .read()
+        .expect("Lock should not be poisoned")
+        .as_ref()?
+        .load(key)
+}
+
+/// Stores the envelope if the cache is enabled. The cache is best effort, so
+/// IO errors are ignored.
+pub(crate) fn store(key: Key, envelope: &[f64]) {
+    if let Some(cache) = &*GLOBAL.read().expect("Lock should not be poisoned") {
+        let _ = cache.store(key, envelope);
+    }
+}
+
+fn header(key: Key) -> [u8; HEADER_LEN] {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    let mut header = [0; HEADER_LEN];
+    let (magic, rest) = header.split_at_mut(MAGIC.len());
+    let (version, rest) = rest.split_at_mut(8);
+    magic.copy_from_slice(MAGIC);
+    version.copy_from_slice(&hasher.finish().to_le_bytes());
+    rest.copy_from_slice(&key.to_bytes());
+    header
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    len: u64,
+    modified: std::time::SystemTime,
+}
+
+fn entries(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension() != Some(EXTENSION.as_ref()) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        result.push(Entry {
+            path,
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        });
+    }
+    Ok(result)
+}
+
+fn remove_entry(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        // Another process may have removed it.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        r => r,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bosing-disk-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn key(fingerprint: u64) -> Key {
+        Key::new(
+            fingerprint,
+            Time::new(10.0).unwrap(),
+            Time::ZERO,
+            AlignedIndex::new(Time::ZERO, Frequency::new(1.0).unwrap(), 0).unwrap(),
+            Frequency::new(1.0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = temp_dir("store");
+        let cache = DiskCache::new(&dir, 1 << 20).unwrap();
+        let envelope = vec![0.0, 0.5, 1.0, 0.5];
+
+        cache.store(key(1), &envelope).unwrap();
+
+        assert_eq!(cache.load(key(1)), Some(envelope));
+        assert_eq!(cache.load(key(2)), None);
+        let reopened = DiskCache::new(&dir, 1 << 20).unwrap();
+        assert_eq!(reopened.load(key(1)), Some(vec![0.0, 0.5, 1.0, 0.5]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn evict_over_limit() {
+        let dir = temp_dir("evict");
+        let entry_size = (HEADER_LEN + 8 * 8) as u64;
+        let cache = DiskCache::new(&dir, 2 * entry_size).unwrap();
+
+        for i in 0..3 {
+            cache.store(key(i), &[1.0; 8]).unwrap();
+        }
+
+        assert_eq!(entries(&dir).unwrap().len(), 2);
+        assert!(*cache.size.lock().unwrap() <= 2 * entry_size);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clear() {
+        let dir = temp_dir("clear");
+        let cache = DiskCache::new(&dir, 1 << 20).unwrap();
+        cache.store(key(1), &[1.0]).unwrap();
+
+        cache.clear().unwrap();
+
+        assert_eq!(cache.load(key(1)), None);
+        assert!(entries(&dir).unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
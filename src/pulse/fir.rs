@@ -1,5 +1,11 @@
 use ndarray::{ArrayView1, ArrayViewMut2, Axis};
+use num::complex::Complex64;
 use pulp::{Arch, Simd, WithSimd};
+use rustfft::FftPlanner;
+
+/// Filters with at least this many taps use the FFT convolution, which costs
+/// O(log M) instead of O(M) per sample.
+const FFT_MIN_TAPS: usize = 128;
 
 struct ApplyFirInplace<'a, 'b> {
     waveform: ArrayViewMut2<'a, f64>,
@@ -13,7 +19,7 @@ impl<'a, 'b> WithSimd for ApplyFirInplace<'a, 'b> {
     fn with_simd<S: Simd>(mut self, simd: S) -> Self::Output {
         let lanes = std::mem::size_of::<S::f64s>() / std::mem::size_of::<f64>();
         let buffer_len = align_ceil(self.taps.len(), lanes);
-        assert!(buffer_len % lanes == 0);
+        assert!(buffer_len.is_multiple_of(lanes));
         let taps_buffer = {
             let mut buffer = vec![0.0; buffer_len * 2];
             for (&t, b) in self.taps.iter().zip(buffer[..buffer_len].iter_mut().rev()) {
@@ -46,10 +52,74 @@ impl<'a, 'b> WithSimd for ApplyFirInplace<'a, 'b> {
 }
 
 pub(crate) fn fir_filter_inplace(waveform: ArrayViewMut2<f64>, taps: ArrayView1<f64>) {
+    if taps.len() >= FFT_MIN_TAPS {
+        fft_filter_inplace(waveform, taps);
+        return;
+    }
     let arch = Arch::new();
     arch.dispatch(ApplyFirInplace { waveform, taps });
 }
 
+/// Overlap-save convolution with zero initial state, equivalent to the direct
+/// form up to rounding.
+///
+/// The taps are real, so two rows are filtered at once as the real and the
+/// imaginary part of a complex signal.
+fn fft_filter_inplace(mut waveform: ArrayViewMut2<f64>, taps: ArrayView1<f64>) {
+    let n_taps = taps.len();
+    let fft_len = (4 * n_taps).next_power_of_two();
+    // Each block yields the outputs which don't wrap around.
+    let step = fft_len - (n_taps - 1);
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(fft_len);
+    let ifft = planner.plan_fft_inverse(fft_len);
+    let mut response = vec![Complex64::default(); fft_len];
+    for (r, &t) in response.iter_mut().zip(taps) {
+        *r = Complex64::new(t / fft_len as f64, 0.0);
+    }
+    fft.process(&mut response);
+
+    let mut block = vec![Complex64::default(); fft_len];
+    let mut scratch = vec![Complex64::default(); fft.get_inplace_scratch_len()];
+    let mut rows = waveform.axis_iter_mut(Axis(0));
+    while let Some(mut re) = rows.next() {
+        let mut im = rows.next();
+        let signal: Vec<_> = match &im {
+            Some(im) => re
+                .iter()
+                .zip(im)
+                .map(|(&x, &y)| Complex64::new(x, y))
+                .collect(),
+            None => re.iter().map(|&x| Complex64::new(x, 0.0)).collect(),
+        };
+        for start in (0..signal.len()).step_by(step) {
+            // Input of the block starts `n_taps - 1` samples before the output.
+            let input_start = start as isize - (n_taps - 1) as isize;
+            for (i, b) in block.iter_mut().enumerate() {
+                let j = input_start + i as isize;
+                *b = usize::try_from(j)
+                    .ok()
+                    .and_then(|j| signal.get(j))
+                    .copied()
+                    .unwrap_or_default();
+            }
+            fft.process_with_scratch(&mut block, &mut scratch);
+            for (b, &r) in block.iter_mut().zip(&response) {
+                *b *= r;
+            }
+            ifft.process_with_scratch(&mut block, &mut scratch);
+            let output = &block[n_taps - 1..];
+            let end = (start + step).min(signal.len());
+            for (i, y) in (start..end).zip(output) {
+                re[i] = y.re;
+                if let Some(im) = &mut im {
+                    im[i] = y.im;
+                }
+            }
+        }
+    }
+}
+
 #[inline]
 fn align_ceil(x: usize, n: usize) -> usize {
     let r = x % n;
@@ -62,7 +132,7 @@ fn align_ceil(x: usize, n: usize) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use ndarray::{array, stack, Array2};
+    use ndarray::{array, stack, Array1, Array2};
 
     use super::*;
 
@@ -79,4 +149,24 @@ mod tests {
 
         assert_eq!(signal, expected);
     }
+
+    #[test]
+    fn fft_matches_direct_form() {
+        let n_taps = FFT_MIN_TAPS + 50;
+        let taps = Array1::from_shape_fn(n_taps, |i| (-(i as f64) / 30.0).exp() * 0.05);
+        let signal = Array2::from_shape_fn((3, 1500), |(r, i)| ((i * (r + 2)) as f64 * 0.37).sin());
+        let mut direct = signal.clone();
+        Arch::new().dispatch(ApplyFirInplace {
+            waveform: direct.view_mut(),
+            taps: taps.view(),
+        });
+
+        let mut fft = signal.clone();
+        fir_filter_inplace(fft.view_mut(), taps.view());
+
+        let error = (&fft - &direct)
+            .mapv(f64::abs)
+            .fold(0.0, |a: f64, &b| a.max(b));
+        assert!(error < 1e-12, "error = {error}");
+    }
 }
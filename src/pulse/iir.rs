@@ -66,6 +66,7 @@ impl<T> Biquad<T>
 where
     T: Add<Output = T> + Mul<Output = T> + Sub<Output = T> + Copy,
 {
+    #[inline(always)]
     fn run(&mut self, x: T) -> T {
         let y = self.coefficients.b0 * x + self.s1;
         self.s1 = self.coefficients.b1 * x - self.coefficients.a1 * y + self.s2;
@@ -86,6 +87,7 @@ impl<T> Iir<T>
 where
     T: Add<Output = T> + Mul<Output = T> + Sub<Output = T> + Copy,
 {
+    #[inline(always)]
     fn run(&mut self, x: T) -> T {
         let mut y = x;
         for biquad in &mut self.biquads {
@@ -94,6 +96,7 @@ where
         y
     }
 
+    #[inline(always)]
     fn filter_inplace(&mut self, x: &mut [T]) {
         for x in x.iter_mut() {
             *x = self.run(*x);
@@ -116,6 +119,7 @@ impl<T, const N: usize> IirPipeline<T, N>
 where
     T: Add<Output = T> + Mul<Output = T> + Sub<Output = T> + Copy + Default,
 {
+    #[inline(always)]
     fn run(&mut self, x: T) -> T {
         let res = self.y[N - 1];
         for i in (0..N).rev() {
@@ -128,6 +132,7 @@ where
         res
     }
 
+    #[inline(always)]
     fn filter_inplace(&mut self, signal: &mut [T]) {
         for i in 0..signal.len() + N {
             let x = if i < signal.len() {
@@ -212,6 +217,8 @@ where
     }
 }
 
+/// Inlined so that the caller can compile it for the detected instruction set.
+#[inline(always)]
 pub(crate) fn iir_filter_inplace<T>(signal: ArrayViewMut2<T>, sos: ArrayView2<T>) -> Result<()>
 where
     T: Add<Output = T> + Mul<Output = T> + Sub<Output = T> + Copy + Default,
@@ -226,6 +233,7 @@ where
     }
 }
 
+#[inline(always)]
 fn specialized_filter<T, const N: usize>(
     mut signal: ArrayViewMut2<T>,
     sos: ArrayView2<T>,
@@ -243,6 +251,7 @@ where
     Ok(())
 }
 
+#[inline(always)]
 fn fallback_filter<T>(mut signal: ArrayViewMut2<T>, sos: ArrayView2<T>) -> Result<()>
 where
     T: Add<Output = T> + Mul<Output = T> + Sub<Output = T> + Copy + Default,
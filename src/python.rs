@@ -0,0 +1,9600 @@
+//! Python bindings of bosing.
+//!
+//! Although Element struct may contains [`Py<Element>`] as children, it is not
+//! possible to create cyclic references because we don't allow mutate the
+//! children after creation.
+
+mod experiment;
+mod logging;
+#[cfg(feature = "serve")]
+mod serve;
+
+use std::{
+    borrow::Borrow,
+    fmt::Debug,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use hashbrown::{HashMap, HashSet};
+use ndarray::{Array2, Array3, ArrayView1, ArrayView2, ArrayViewMut2, Axis, Ix2, Ix3};
+use numpy::{
+    prelude::*, AllowTypeChange, Complex64, PyArray1, PyArray2, PyArray3, PyArrayLike1,
+    PyArrayLike2, PyArrayLikeDyn, PyReadonlyArray1, PyReadonlyArray2, PyReadonlyArrayDyn,
+};
+use pyo3::{
+    create_exception,
+    exceptions::{
+        PyImportError, PyKeyError, PyRuntimeError, PyRuntimeWarning, PyTypeError, PyValueError,
+    },
+    prelude::*,
+    sync::GILOnceCell,
+    types::{DerefToPyAny, PyComplex, PyDict, PyTuple, PyType},
+    PyTypeInfo,
+};
+use rayon::prelude::*;
+
+use crate::{
+    executor::{self, Executor, FrameEvent},
+    generate,
+    optimize::{
+        bind_params, canonicalize_aliases, param_names, phase_param_names, resolve_aliases,
+        resolve_duration_refs, DurationResolver, Optimizer, Pass,
+    },
+    preview,
+    pulse::{self, disk_cache, Envelope, EnvelopePrecision, PulseList, Sampler},
+    quant::{self, Amplitude, ChannelId, ChirpRate, Frequency, Label, Phase, ShapeId, Time},
+    regenerate,
+    schedule::{self, ElementCommonBuilder, ElementRef, Measure as _},
+    shape, stats, store, Alignment, Direction, GridLength, GridLengthUnit, Overflow, Padding,
+};
+
+create_exception!(
+    bosing,
+    BosingError,
+    PyRuntimeError,
+    "Base class of errors raised during waveform generation.
+
+Attributes:
+    code (str): Machine-readable identifier of the error kind.
+    channel (str | None): Channel related to the error, if any.
+    label (str | None): Label of the element related to the error, if known.
+    time (float | None): Time related to the error, if any.
+    details (dict[str, object]): Additional kind-specific information."
+);
+create_exception!(
+    bosing,
+    ScheduleError,
+    BosingError,
+    "Error raised when the schedule cannot be executed."
+);
+create_exception!(
+    bosing,
+    ClippingError,
+    BosingError,
+    "Error raised when the waveforms exceed the clipping limit.
+
+``details['clippings']`` is the list of :class:`Clipping` sorted by channel and
+sample index and ``details['limit']`` the limit. `channel` and `time` are
+those of the first clipping."
+);
+create_exception!(
+    bosing,
+    SamplingError,
+    BosingError,
+    "Error raised when the pulses cannot be sampled to the waveforms.
+
+If the pulse comes from a :class:`Play` element, `label` is its label and
+``details['path']`` the path to it. ``details['pulse_time']`` is the start of
+the pulse without the channel delay. Out of bounds pulses have the sample
+``details['index']`` of `time` and the channel length ``details['length']``."
+);
+
+/// Creates an exception of type `T` with the structured fields of
+/// :class:`BosingError` attached.
+fn new_bosing_error<T: PyTypeInfo>(
+    py: Python,
+    message: String,
+    code: &str,
+    channel: Option<&ChannelId>,
+    label: Option<&Label>,
+    time: Option<f64>,
+    details: Bound<PyDict>,
+) -> PyErr {
+    let err = PyErr::new::<T, _>(message);
+    let value = err.value_bound(py);
+    let result = (|| {
+        value.setattr("code", code)?;
+        value.setattr("channel", channel.into_py(py))?;
+        value.setattr("label", label.into_py(py))?;
+        value.setattr("time", time)?;
+        value.setattr("details", details)
+    })();
+    match result {
+        Ok(()) => err,
+        Err(e) => e,
+    }
+}
+
+fn schedule_error(py: Python, err: executor::Error) -> PyErr {
+    tracing::error!(code = err.code(), "{}", err);
+    let details = PyDict::new_bound(py);
+    let mut channel = None;
+    let mut time = None;
+    let result = match &err {
+        executor::Error::ChannelNotFound(ids) => {
+            if let [id] = ids.as_slice() {
+                channel = Some(id);
+            }
+            details.set_item("channels", ids.clone().into_py(py))
+        }
+        executor::Error::ShapeNotFound(id) => details.set_item("shape", id.into_py(py)),
+        executor::Error::NegativePlateau(plateau) => {
+            time = Some(plateau.value());
+            Ok(())
+        }
+        executor::Error::NotEnoughDuration {
+            required,
+            available,
+        } => details
+            .set_item("required", required.value())
+            .and_then(|()| details.set_item("available", available.value())),
+        executor::Error::LayoutDrift {
+            path,
+            label,
+            start,
+            end,
+            container_start,
+            container_end,
+        } => {
+            time = Some(start.value());
+            details
+                .set_item("path", path)
+                .and_then(|()| details.set_item("label", label.clone().map(|l| l.to_string())))
+                .and_then(|()| details.set_item("end", end.value()))
+                .and_then(|()| details.set_item("container_start", container_start.value()))
+                .and_then(|()| details.set_item("container_end", container_end.value()))
+        }
+        executor::Error::OverconstrainedGrid {
+            label,
+            child,
+            required,
+            available,
+        } => details
+            .set_item("label", label.clone().map(|l| l.to_string()))
+            .and_then(|()| details.set_item("child", child))
+            .and_then(|()| details.set_item("required", required.value()))
+            .and_then(|()| details.set_item("available", available.value())),
+    };
+    if let Err(e) = result {
+        return e;
+    }
+    new_bosing_error::<ScheduleError>(
+        py,
+        err.to_string(),
+        err.code(),
+        channel,
+        None,
+        time,
+        details,
+    )
+}
+
+/// Converts the error with the [`Play`] element of the failed pulse looked up
+/// in `schedule`.
+fn sampling_error(
+    py: Python,
+    err: pulse::Error,
+    schedule: &ElementRef,
+    time_tolerance: Time,
+) -> PyErr {
+    tracing::error!(code = err.kind.code(), channel = %err.channel, "{}", err);
+    let source = executor::locate_pulse(schedule, &err.channel, err.pulse_time, time_tolerance);
+    let details = PyDict::new_bound(py);
+    let result = (|| {
+        details.set_item("pulse_time", err.pulse_time.value())?;
+        if let Some(source) = &source {
+            details.set_item("path", &source.path)?;
+        }
+        match err.kind {
+            pulse::ErrorKind::NegativeStartTime { index, .. } => details.set_item("index", index),
+            pulse::ErrorKind::StartIndexOutOfBounds { index, length, .. }
+            | pulse::ErrorKind::EndTimeOutOfBounds { index, length, .. } => details
+                .set_item("index", index)
+                .and_then(|()| details.set_item("length", length)),
+            pulse::ErrorKind::ShapeFailed { .. } => Ok(()),
+        }
+    })();
+    if let Err(e) = result {
+        return e;
+    }
+    let py_err = new_bosing_error::<SamplingError>(
+        py,
+        err.to_string(),
+        err.kind.code(),
+        Some(&err.channel),
+        source.and_then(|s| s.label).as_ref(),
+        Some(err.kind.time()),
+        details,
+    );
+    // Keep the traceback of the function of a custom shape.
+    if let pulse::ErrorKind::ShapeFailed { error, .. } = &err.kind {
+        if let Some(cause) = error.downcast_ref::<PyErr>() {
+            py_err.set_cause(py, Some(cause.clone_ref(py)));
+        }
+    }
+    py_err
+}
+
+/// Channel configuration.
+///
+/// `align_level` is the time axis alignment granularity. With sampling interval
+/// :math:`\Delta t` and `align_level` :math:`n`, start of pulse is aligned to
+/// the nearest multiple of :math:`2^n \Delta t`.
+///
+/// Each channel can be either real or complex. If the channel is complex, the
+/// filter will be applied to both I and Q components. If the channel is real,
+/// `iq_matrix` will be ignored.
+///
+/// `padding` controls the waveform after the last non-zero sample, which is
+/// useful for DC-coupled lines where an abrupt return to zero is not desired.
+/// Padding is applied before the IQ matrix, filters and offset.
+///
+/// `exp_corrections` compensates the exponential overshoot or undershoot of
+/// flux lines whose step response is :math:`1 + \sum_i A_i e^{-t/\tau_i}`.
+/// Each ``(A, tau)`` pair is compiled into a first-order IIR section which
+/// inverts the term, and the sections are applied after `iir`. A single term
+/// is corrected exactly, and several terms are corrected approximately, which
+/// is accurate for small amplitudes.
+///
+/// The IIR and FIR filters delay the pulses by their group delay, which is
+/// evaluated at `base_freq` and available as :attr:`Channel.group_delay`. If
+/// `compensate_group_delay` is ``True``, the group delay is subtracted from
+/// `delay` so that filtered pulses stay aligned with other channels.
+///
+/// If `differential` is set, the channel drives a differential pair. The
+/// waveform under the channel name is the positive output and the waveform
+/// named `differential` is the negative output, which has the opposite sign
+/// and is delayed by `skew` relative to the positive output. Both outputs
+/// share the padding, IQ matrix, filters, offset and blanking of the channel,
+/// so the offset is not inverted.
+///
+/// `metadata` is not used by bosing. It is saved with the channel by
+/// :meth:`ResultStore.save` and sent along with the waveforms by
+/// :class:`Server`, so that routing information like the slot and port of
+/// the instrument travels with the waveforms.
+///
+/// .. caution::
+///
+///     Crosstalk matrix will not be applied to offset.
+///
+/// Args:
+///     base_freq (float): Base frequency of the channel.
+///     sample_rate (float): Sample rate of the channel.
+///     length (int): Length of the waveform.
+///     delay (float): Delay of the channel. Defaults to 0.0.
+///     align_level (int): Time axis alignment granularity. Defaults to -10.
+///     iq_matrix (array_like[2, 2] | None): IQ matrix of the channel. Defaults
+///         to ``None``.
+///     offset (Sequence[float] | None): Offsets of the channel. The length of the
+///         sequence should be 2 if the channel is complex, or 1 if the channel is
+///         real. Defaults to ``None``.
+///     iir (array_like[N, 6] | None): IIR filter of the channel. The format of
+///         the array is ``[[b0, b1, b2, a0, a1, a2], ...]``, which is the same
+///         as `sos` parameter of :func:`scipy.signal.sosfilt`. Defaults to ``None``.
+///     fir (array_like[M] | None): FIR filter of the channel. Defaults to None.
+///     exp_corrections (Sequence[tuple[float, float]] | None): Exponential
+///         distortions ``(amplitude, tau)`` of the line to correct. Defaults
+///         to ``None``.
+///     compression_table (Sequence[tuple[float, float]] | None): Output
+///         amplitude of the amplifier as a function of the input amplitude,
+///         given as increasing ``(input, output)`` points. The waveform is
+///         predistorted after the filters to compensate the compression.
+///         Defaults to ``None``.
+///     compression_poly (Sequence[float] | None): Output amplitude of the
+///         amplifier as a polynomial ``c[0] * x + c[1] * x**2 + ...`` of the
+///         input amplitude ``x`` between 0 and 1, used like
+///         `compression_table`. Defaults to ``None``.
+///     filter_offset (bool): Whether to apply filter to the offset. Defaults to
+///         ``False``.
+///     is_real (bool): Whether the channel is real. Defaults to ``False``.
+///     padding (str | Padding): Padding after the last pulse. The value can be
+///         :class:`Padding` or one of 'zero', 'hold', 'ramp'. Defaults to
+///         :attr:`Padding.Zero`.
+///     ramp_length (int): Number of samples to ramp to zero when `padding` is
+///         :attr:`Padding.Ramp`. Defaults to 0.
+///     wrap_phase (bool): Whether to wrap the accumulated frame phase to
+///         :math:`[0, 1)` after each frame change. Disabling it reproduces the
+///         unbounded accumulation, which loses precision in long schedules.
+///         Defaults to ``True``.
+///     compensate_group_delay (bool): Whether to compensate the group delay of
+///         the filters. Defaults to ``False``.
+///     phase_from_first_use (bool): Whether the phase of `base_freq`
+///         accumulates from the start of the first pulse on the channel instead
+///         of from time 0, matching instruments which reset the NCO on
+///         trigger. Defaults to ``False``.
+///     blanking (Sequence[tuple[float, float]] | None): Time ranges
+///         ``(start, end)`` in schedule time during which the output is forced
+///         to zero, e.g. while a readout is in progress. The ranges are shifted
+///         by `delay` but not by the group delay compensation, and are applied
+///         after all other post-processing, including the offset. Samples
+///         partially covered by a range are zeroed. Defaults to ``None``.
+///     overflow (str | Overflow): Handling of pulses which extend past the end
+///         of the waveform. The value can be :class:`Overflow` or one of
+///         'error', 'truncate', 'wrap'. Defaults to :attr:`Overflow.Error`.
+///     differential (str | None): Name of the negative output of a
+///         differential pair. Defaults to ``None``.
+///     skew (float): Additional delay of the negative output. Defaults to
+///         0.0.
+///     trigger_latency (float): Latency of the instrument from the trigger to
+///         the output, which only affects the hardware times reported by
+///         :func:`element_timings`. Defaults to 0.0.
+///     metadata (dict[str, object] | None): User metadata, which should be
+///         serializable to JSON. Defaults to ``None``.
+/// Raises:
+///     ValueError: If `compensate_group_delay` is ``True`` and the group delay
+///         is undefined because the filter response vanishes at `base_freq`,
+///         or if a blanking range has ``start > end``, or if `metadata` is not
+///         serializable to JSON, or if an exponential correction has
+///         ``tau <= 0`` or an amplitude too negative to be inverted stably,
+///         or if both compression models are given or the compression model
+///         is not strictly increasing.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Channel {
+    base_freq: Frequency,
+    sample_rate: Frequency,
+    length: usize,
+    delay: Time,
+    align_level: i32,
+    iq_matrix: Option<Py<PyArray2<f64>>>,
+    offset: Option<Py<PyArray1<f64>>>,
+    iir: Option<Py<PyArray2<f64>>>,
+    fir: Option<Py<PyArray1<f64>>>,
+    exp_corrections: Vec<(f64, Time)>,
+    compression_table: Option<Vec<(f64, f64)>>,
+    compression_poly: Option<Vec<f64>>,
+    filter_offset: bool,
+    is_real: bool,
+    padding: Padding,
+    ramp_length: usize,
+    wrap_phase: bool,
+    compensate_group_delay: bool,
+    group_delay: Option<Time>,
+    phase_from_first_use: bool,
+    blanking: Vec<(Time, Time)>,
+    overflow: Overflow,
+    differential: Option<ChannelId>,
+    skew: Time,
+    trigger_latency: Time,
+    metadata: Option<Py<PyDict>>,
+}
+
+#[pymethods]
+impl Channel {
+    #[new]
+    #[pyo3(signature = (
+        base_freq,
+        sample_rate,
+        length,
+        *,
+        delay=Time::ZERO,
+        align_level=-10,
+        iq_matrix=None,
+        offset=None,
+        iir=None,
+        fir=None,
+        exp_corrections=None,
+        compression_table=None,
+        compression_poly=None,
+        filter_offset=false,
+        is_real=false,
+        padding=None,
+        ramp_length=0,
+        wrap_phase=true,
+        compensate_group_delay=false,
+        phase_from_first_use=false,
+        blanking=None,
+        overflow=None,
+        differential=None,
+        skew=Time::ZERO,
+        trigger_latency=Time::ZERO,
+        metadata=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        base_freq: Frequency,
+        sample_rate: Frequency,
+        length: usize,
+        delay: Time,
+        align_level: i32,
+        mut iq_matrix: Option<PyArrayLike2<f64, AllowTypeChange>>,
+        offset: Option<PyArrayLike1<f64, AllowTypeChange>>,
+        iir: Option<PyArrayLike2<f64, AllowTypeChange>>,
+        fir: Option<PyArrayLike1<f64, AllowTypeChange>>,
+        exp_corrections: Option<Vec<(f64, Time)>>,
+        compression_table: Option<Vec<(f64, f64)>>,
+        compression_poly: Option<Vec<f64>>,
+        filter_offset: bool,
+        is_real: bool,
+        padding: Option<&Bound<PyAny>>,
+        ramp_length: usize,
+        wrap_phase: bool,
+        compensate_group_delay: bool,
+        phase_from_first_use: bool,
+        blanking: Option<Vec<(Time, Time)>>,
+        overflow: Option<&Bound<PyAny>>,
+        differential: Option<ChannelId>,
+        skew: Time,
+        trigger_latency: Time,
+        metadata: Option<&Bound<PyDict>>,
+    ) -> PyResult<Self> {
+        let exp_corrections = exp_corrections.unwrap_or_default();
+        let sos = corrected_sos(
+            iir.as_ref().map(|x| x.as_array()),
+            &exp_corrections,
+            sample_rate,
+        )?;
+        let group_delay = pulse::group_delay(
+            sos.as_ref()
+                .map(|x| x.view())
+                .or_else(|| iir.as_ref().map(|x| x.as_array())),
+            fir.as_ref().map(|x| x.as_array()),
+            base_freq.value() / sample_rate.value(),
+        )
+        .map(|d| Time::new(d * sample_rate.dt().value()))
+        .transpose()?;
+        predistortion(compression_table.as_deref(), compression_poly.as_deref())?;
+        if compensate_group_delay && group_delay.is_none() {
+            return Err(PyValueError::new_err(
+                "Group delay of the filters is undefined at base_freq",
+            ));
+        }
+        if is_real {
+            iq_matrix = None;
+        }
+        let iq_matrix = if let Some(iq_matrix) = iq_matrix {
+            if iq_matrix.shape() != [2, 2] {
+                return Err(PyValueError::new_err("iq_matrix should be a 2x2 matrix"));
+            }
+            let kwargs = PyDict::new_bound(py);
+            kwargs.set_item("write", false)?;
+            iq_matrix.getattr("setflags")?.call((), Some(&kwargs))?;
+            Some(Bound::clone(&iq_matrix).unbind())
+        } else {
+            None
+        };
+        let offset = if let Some(offset) = offset {
+            if !matches!((offset.len(), is_real), (1, true) | (2, false)) {
+                return Err(PyValueError::new_err(
+                    "offset length does not match is_real",
+                ));
+            }
+            let kwargs = PyDict::new_bound(py);
+            kwargs.set_item("write", false)?;
+            offset.getattr("setflags")?.call((), Some(&kwargs))?;
+            Some(Bound::clone(&offset).unbind())
+        } else {
+            None
+        };
+        let iir = if let Some(iir) = iir {
+            if !matches!(iir.shape(), [_, 6]) {
+                return Err(PyValueError::new_err("iir should be a Nx6 matrix"));
+            }
+            let kwargs = PyDict::new_bound(py);
+            kwargs.set_item("write", false)?;
+            iir.getattr("setflags")?.call((), Some(&kwargs))?;
+            Some(Bound::clone(&iir).unbind())
+        } else {
+            None
+        };
+        let fir = if let Some(fir) = fir {
+            let kwargs = PyDict::new_bound(py);
+            kwargs.set_item("write", false)?;
+            fir.getattr("setflags")?.call((), Some(&kwargs))?;
+            Some(Bound::clone(&fir).unbind())
+        } else {
+            None
+        };
+        let padding = match padding {
+            Some(obj) => extract_padding(obj)?,
+            None => Padding::Zero,
+        };
+        let overflow = match overflow {
+            Some(obj) => extract_overflow(obj)?,
+            None => Overflow::Error,
+        };
+        let blanking = blanking.unwrap_or_default();
+        if blanking.iter().any(|(start, end)| start > end) {
+            return Err(PyValueError::new_err(
+                "blanking range start should not be greater than end",
+            ));
+        }
+        let metadata = if let Some(metadata) = metadata {
+            json_object(metadata).map_err(|e| {
+                PyValueError::new_err(format!("metadata is not serializable to JSON: {}", e))
+            })?;
+            Some(metadata.copy()?.unbind())
+        } else {
+            None
+        };
+        Ok(Channel {
+            base_freq,
+            sample_rate,
+            length,
+            delay,
+            align_level,
+            iq_matrix,
+            offset,
+            iir,
+            fir,
+            exp_corrections,
+            compression_table,
+            compression_poly,
+            filter_offset,
+            is_real,
+            padding,
+            ramp_length,
+            wrap_phase,
+            compensate_group_delay,
+            group_delay,
+            phase_from_first_use,
+            blanking,
+            overflow,
+            differential,
+            skew,
+            trigger_latency,
+            metadata,
+        })
+    }
+
+    /// Create a channel with the presets of an AWG model.
+    ///
+    /// `sample_rate` and `align_level` are taken from the preset registered
+    /// for `model` and `length` is rounded up to a multiple of the waveform
+    /// granularity of the instrument. The model name is case-insensitive.
+    /// Built-in models are listed by :func:`awg_presets` and more can be added
+    /// with :func:`register_awg`.
+    ///
+    /// Args:
+    ///     model (str): AWG model, e.g. ``"HDAWG"``.
+    ///     length (int): Minimum length of the waveform.
+    ///     base_freq (float): Base frequency of the channel. Defaults to 0.0.
+    ///     **kwargs: Other arguments of :class:`Channel`. `align_level`
+    ///         overrides the preset.
+    /// Returns:
+    ///     Channel: New channel.
+    /// Raises:
+    ///     ValueError: If `model` is unknown.
+    /// Example:
+    ///     .. code-block:: python
+    ///
+    ///         channel = Channel.for_awg("HDAWG", 1000, base_freq=100e6)
+    ///         assert channel.length == 1008
+    #[classmethod]
+    #[pyo3(signature = (model, length, *, base_freq=Frequency::ZERO, **kwargs))]
+    fn for_awg<'py>(
+        cls: &Bound<'py, PyType>,
+        model: &str,
+        length: usize,
+        base_freq: Frequency,
+        kwargs: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = cls.py();
+        let preset = awg_preset(model)?;
+        let kwargs = match kwargs {
+            Some(kwargs) => kwargs.copy()?,
+            None => PyDict::new_bound(py),
+        };
+        if !kwargs.contains("align_level")? {
+            kwargs.set_item("align_level", preset.align_level)?;
+        }
+        let length = length.div_ceil(preset.granularity) * preset.granularity;
+        cls.call((base_freq, preset.sample_rate, length), Some(&kwargs))
+    }
+}
+
+/// Digital channel configuration.
+///
+/// The waveform of a digital channel is a ``uint8`` array which is 1 while a
+/// :class:`Marker` on the channel is high and 0 elsewhere. The edges of the
+/// marker are aligned in the same way as the start of pulses, see
+/// :class:`Channel`.
+///
+/// Args:
+///     sample_rate (float): Sample rate of the channel.
+///     length (int): Length of the waveform.
+///     delay (float): Delay of the channel. Defaults to 0.0.
+///     align_level (int): Time axis alignment granularity. Defaults to -10.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone, Copy)]
+struct DigitalChannel {
+    sample_rate: Frequency,
+    length: usize,
+    delay: Time,
+    align_level: i32,
+}
+
+#[pymethods]
+impl DigitalChannel {
+    #[new]
+    #[pyo3(signature = (sample_rate, length, *, delay=Time::ZERO, align_level=-10))]
+    fn new(sample_rate: Frequency, length: usize, delay: Time, align_level: i32) -> Self {
+        Self {
+            sample_rate,
+            length,
+            delay,
+            align_level,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DigitalChannel({}, {}, delay={}, align_level={})",
+            self.sample_rate.value(),
+            self.length,
+            self.delay.value(),
+            self.align_level
+        )
+    }
+}
+
+impl DigitalChannel {
+    fn config(&self) -> generate::MarkerConfig {
+        generate::MarkerConfig {
+            sample_rate: self.sample_rate,
+            delay: self.delay,
+            align_level: self.align_level,
+        }
+    }
+}
+
+/// Sample rate and alignment presets of an AWG model.
+///
+/// Args:
+///     sample_rate (float): Sample rate of the instrument.
+///     align_level (int): Time axis alignment granularity, see
+///         :class:`Channel`. Defaults to -10.
+///     granularity (int): Waveform lengths should be a multiple of this
+///         number of samples. Defaults to 1.
+/// Raises:
+///     ValueError: If `granularity` is 0.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone, Copy)]
+struct AwgPreset {
+    sample_rate: Frequency,
+    align_level: i32,
+    granularity: usize,
+}
+
+#[pymethods]
+impl AwgPreset {
+    #[new]
+    #[pyo3(signature = (sample_rate, *, align_level=-10, granularity=1))]
+    fn new(sample_rate: Frequency, align_level: i32, granularity: usize) -> PyResult<Self> {
+        if granularity == 0 {
+            return Err(PyValueError::new_err("granularity should be at least 1."));
+        }
+        Ok(Self {
+            sample_rate,
+            align_level,
+            granularity,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "AwgPreset({}, align_level={}, granularity={})",
+            self.sample_rate.value(),
+            self.align_level,
+            self.granularity
+        )
+    }
+}
+
+/// Built-in presets as `(model, sample rate, waveform granularity)`.
+const BUILTIN_AWG_PRESETS: [(&str, f64, usize); 5] = [
+    ("HDAWG", 2.4e9, 16),
+    ("M3202A", 1e9, 10),
+    ("M8190A", 12e9, 64),
+    ("OPX+", 1e9, 4),
+    ("SHFSG", 2e9, 16),
+];
+
+/// Registered presets with upper-case model names as keys.
+fn awg_presets_registry() -> &'static Mutex<HashMap<String, AwgPreset>> {
+    static PRESETS: OnceLock<Mutex<HashMap<String, AwgPreset>>> = OnceLock::new();
+    PRESETS.get_or_init(|| {
+        let presets = BUILTIN_AWG_PRESETS
+            .iter()
+            .map(|&(model, sample_rate, granularity)| {
+                let preset = AwgPreset {
+                    sample_rate: Frequency::new(sample_rate).expect("Should be a valid frequency"),
+                    align_level: -10,
+                    granularity,
+                };
+                (model.to_owned(), preset)
+            })
+            .collect();
+        Mutex::new(presets)
+    })
+}
+
+fn awg_preset(model: &str) -> PyResult<AwgPreset> {
+    let presets = awg_presets_registry()
+        .lock()
+        .expect("Lock should not be poisoned");
+    presets
+        .get(&model.to_ascii_uppercase())
+        .copied()
+        .ok_or_else(|| {
+            let mut known: Vec<_> = presets.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            PyValueError::new_err(format!(
+                "Unknown AWG model '{}', expected one of {:?}",
+                model, known
+            ))
+        })
+}
+
+/// Read-only borrows of the array parameters of a [`Channel`].
+struct ChannelArrays<'py> {
+    iq_matrix: Option<PyReadonlyArray2<'py, f64>>,
+    offset: Option<PyReadonlyArray1<'py, f64>>,
+    iir: Option<PyReadonlyArray2<'py, f64>>,
+    fir: Option<PyReadonlyArray1<'py, f64>>,
+    /// `iir` followed by the exponential corrections, if there are any.
+    corrected_iir: Option<Array2<f64>>,
+    predistortion: Option<pulse::Predistortion>,
+}
+
+/// Appends the sections of the exponential corrections to `iir`, or returns
+/// `None` if there are no corrections.
+fn corrected_sos(
+    iir: Option<ArrayView2<f64>>,
+    corrections: &[(f64, Time)],
+    sample_rate: Frequency,
+) -> PyResult<Option<Array2<f64>>> {
+    if corrections.is_empty() {
+        return Ok(None);
+    }
+    let sos = pulse::exp_correction_sos(corrections, sample_rate)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let Some(iir) = iir else {
+        return Ok(Some(sos));
+    };
+    ndarray::concatenate(Axis(0), &[iir.view(), sos.view()])
+        .map(Some)
+        .map_err(|_| PyValueError::new_err("iir should be a Nx6 matrix"))
+}
+
+/// Predistortion of the compression model given by one of a table or a
+/// polynomial.
+fn predistortion(
+    table: Option<&[(f64, f64)]>,
+    poly: Option<&[f64]>,
+) -> PyResult<Option<pulse::Predistortion>> {
+    let predistortion = match (table, poly) {
+        (None, None) => return Ok(None),
+        (Some(table), None) => pulse::Predistortion::from_table(table),
+        (None, Some(poly)) => pulse::Predistortion::from_polynomial(poly),
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "compression_table and compression_poly can't be used together",
+            ))
+        }
+    };
+    predistortion
+        .map(Some)
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+impl Channel {
+    fn readonly_arrays<'py>(&self, py: Python<'py>) -> ChannelArrays<'py> {
+        let iir = self.iir.as_ref().map(|x| x.bind(py).readonly());
+        let corrected_iir = corrected_sos(
+            iir.as_ref().map(|x| x.as_array()),
+            &self.exp_corrections,
+            self.sample_rate,
+        )
+        .expect("Corrections should be validated");
+        let predistortion = predistortion(
+            self.compression_table.as_deref(),
+            self.compression_poly.as_deref(),
+        )
+        .expect("Compression should be validated");
+        ChannelArrays {
+            iq_matrix: self.iq_matrix.as_ref().map(|x| x.bind(py).readonly()),
+            offset: self.offset.as_ref().map(|x| x.bind(py).readonly()),
+            iir,
+            fir: self.fir.as_ref().map(|x| x.bind(py).readonly()),
+            corrected_iir,
+            predistortion,
+        }
+    }
+
+    fn config<'a>(&self, arrays: &'a ChannelArrays) -> generate::ChannelConfig<'a> {
+        generate::ChannelConfig {
+            sample_rate: self.sample_rate,
+            delay: self.total_delay(),
+            align_level: self.align_level,
+            padding: self.padding,
+            ramp_length: self.ramp_length,
+            overflow: self.overflow,
+            iq_matrix: arrays.iq_matrix.as_ref().map(|x| x.as_array()),
+            offset: arrays.offset.as_ref().map(|x| x.as_array()),
+            iir: arrays
+                .corrected_iir
+                .as_ref()
+                .map(|x| x.view())
+                .or_else(|| arrays.iir.as_ref().map(|x| x.as_array())),
+            fir: arrays.fir.as_ref().map(|x| x.as_array()),
+            predistortion: arrays.predistortion.as_ref(),
+            filter_offset: self.filter_offset,
+            blanking: self
+                .blanking
+                .iter()
+                .map(|&(start, end)| (start + self.delay, end + self.delay))
+                .collect(),
+            envelope_precision: EnvelopePrecision::F64,
+            differential: self
+                .differential
+                .clone()
+                .map(|negative| generate::Differential {
+                    negative,
+                    skew: self.skew,
+                }),
+        }
+    }
+
+    /// Configuration recorded by [`ResultStore`].
+    fn to_json_value(&self, py: Python) -> PyResult<serde_json::Value> {
+        let arrays = self.readonly_arrays(py);
+        let matrix = |x: Option<PyReadonlyArray2<f64>>| {
+            x.map(|x| {
+                x.as_array()
+                    .outer_iter()
+                    .map(|row| row.to_vec())
+                    .collect::<Vec<_>>()
+            })
+        };
+        Ok(serde_json::json!({
+            "base_freq": self.base_freq.value(),
+            "sample_rate": self.sample_rate.value(),
+            "length": self.length,
+            "delay": self.delay.value(),
+            "align_level": self.align_level,
+            "iq_matrix": matrix(arrays.iq_matrix),
+            "offset": arrays.offset.map(|x| x.as_array().to_vec()),
+            "iir": matrix(arrays.iir),
+            "fir": arrays.fir.map(|x| x.as_array().to_vec()),
+            "exp_corrections": self
+                .exp_corrections
+                .iter()
+                .map(|&(amplitude, tau)| (amplitude, tau.value()))
+                .collect::<Vec<_>>(),
+            "compression_table": self.compression_table,
+            "compression_poly": self.compression_poly,
+            "filter_offset": self.filter_offset,
+            "is_real": self.is_real,
+            "padding": format!("{:?}", self.padding).to_lowercase(),
+            "ramp_length": self.ramp_length,
+            "wrap_phase": self.wrap_phase,
+            "compensate_group_delay": self.compensate_group_delay,
+            "group_delay": self.group_delay.map(|t| t.value()),
+            "phase_from_first_use": self.phase_from_first_use,
+            "blanking": self
+                .blanking
+                .iter()
+                .map(|(start, end)| (start.value(), end.value()))
+                .collect::<Vec<_>>(),
+            "overflow": format!("{:?}", self.overflow).to_lowercase(),
+            "differential": self.differential,
+            "skew": self.skew.value(),
+            "trigger_latency": self.trigger_latency.value(),
+            "metadata": self.metadata_json(py)?,
+        }))
+    }
+
+    fn metadata_json(
+        &self,
+        py: Python,
+    ) -> PyResult<Option<serde_json::Map<String, serde_json::Value>>> {
+        self.metadata
+            .as_ref()
+            .map(|m| json_object(m.bind(py)))
+            .transpose()
+    }
+
+    /// Names of the waveforms written by the channel `name`.
+    fn outputs(&self, name: &ChannelId) -> impl Iterator<Item = ChannelId> {
+        std::iter::once(name.clone()).chain(self.differential.clone())
+    }
+
+    /// Delay applied when sampling, including the group delay compensation.
+    fn total_delay(&self) -> Time {
+        match (self.compensate_group_delay, self.group_delay) {
+            (true, Some(group_delay)) => self.delay - group_delay,
+            _ => self.delay,
+        }
+    }
+}
+
+/// Arguments of ``__reduce__`` of the enums, see [`reduce_enum`].
+type EnumReduce<'py> = (Bound<'py, PyAny>, (Bound<'py, PyType>, String));
+
+/// Pickles a variant of an enum as ``getattr(cls, name)``, so that unpickling
+/// returns the same variant.
+fn reduce_enum<'py>(slf: &Bound<'py, PyAny>, name: String) -> PyResult<EnumReduce<'py>> {
+    let getattr = slf.py().import_bound("builtins")?.getattr("getattr")?;
+    Ok((getattr, (slf.get_type(), name)))
+}
+
+#[pymethods]
+impl Padding {
+    /// Convert the value to Padding.
+    ///
+    /// The value can be one of the following:
+    ///
+    /// - :class:`Padding`
+    /// - "zero"
+    /// - "hold"
+    /// - "ramp"
+    ///
+    /// Args:
+    ///     obj (str | Padding): The value to convert.
+    /// Returns:
+    ///     Padding: The converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted to Padding.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(s) = obj.extract() {
+            let padding = match s {
+                "zero" => Some(Padding::Zero),
+                "hold" => Some(Padding::Hold),
+                "ramp" => Some(Padding::Ramp),
+                _ => None,
+            };
+            if let Some(padding) = padding {
+                return Py::new(obj.py(), padding);
+            }
+        }
+        let msg = concat!(
+            "Failed to convert the value to Padding. ",
+            "Must be Padding or one of 'zero', 'hold', 'ramp'"
+        );
+        Err(PyValueError::new_err(msg))
+    }
+
+    fn __str__(&self) -> &'static str {
+        match self {
+            Padding::Zero => "zero",
+            Padding::Hold => "hold",
+            Padding::Ramp => "ramp",
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        *self as u64
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<EnumReduce<'py>> {
+        reduce_enum(slf.as_any(), format!("{:?}", slf.get()))
+    }
+}
+
+fn extract_padding(obj: &Bound<PyAny>) -> PyResult<Padding> {
+    Padding::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+#[pymethods]
+impl Overflow {
+    /// Convert the value to Overflow.
+    ///
+    /// The value can be one of the following:
+    ///
+    /// - :class:`Overflow`
+    /// - "error"
+    /// - "truncate"
+    /// - "wrap"
+    ///
+    /// Args:
+    ///     obj (str | Overflow): The value to convert.
+    /// Returns:
+    ///     Overflow: The converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted to Overflow.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(s) = obj.extract() {
+            let overflow = match s {
+                "error" => Some(Overflow::Error),
+                "truncate" => Some(Overflow::Truncate),
+                "wrap" => Some(Overflow::Wrap),
+                _ => None,
+            };
+            if let Some(overflow) = overflow {
+                return Py::new(obj.py(), overflow);
+            }
+        }
+        let msg = concat!(
+            "Failed to convert the value to Overflow. ",
+            "Must be Overflow or one of 'error', 'truncate', 'wrap'"
+        );
+        Err(PyValueError::new_err(msg))
+    }
+
+    fn __str__(&self) -> &'static str {
+        match self {
+            Overflow::Error => "error",
+            Overflow::Truncate => "truncate",
+            Overflow::Wrap => "wrap",
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        *self as u64
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<EnumReduce<'py>> {
+        reduce_enum(slf.as_any(), format!("{:?}", slf.get()))
+    }
+}
+
+fn extract_overflow(obj: &Bound<PyAny>) -> PyResult<Overflow> {
+    Overflow::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+#[pymethods]
+impl Alignment {
+    /// Convert the value to Alignment.
+    ///
+    /// The value can be one of the following:
+    ///
+    /// - :class:`Alignment`
+    /// - "end"
+    /// - "start"
+    /// - "center"
+    /// - "stretch"
+    ///
+    /// Args:
+    ///     obj (str | Alignment): The value to convert.
+    /// Returns:
+    ///     Alignment: The converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted to Alignment.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(s) = obj.extract() {
+            let alignment = match s {
+                "end" => Some(Alignment::End),
+                "start" => Some(Alignment::Start),
+                "center" => Some(Alignment::Center),
+                "stretch" => Some(Alignment::Stretch),
+                _ => None,
+            };
+            if let Some(alignment) = alignment {
+                return Py::new(obj.py(), alignment);
+            }
+        }
+        let msg = concat!(
+            "Failed to convert the value to Alignment. ",
+            "Must be Alignment or one of 'end', 'start', 'center', 'stretch'"
+        );
+        Err(PyValueError::new_err(msg))
+    }
+
+    fn __str__(&self) -> &'static str {
+        match self {
+            Alignment::End => "end",
+            Alignment::Start => "start",
+            Alignment::Center => "center",
+            Alignment::Stretch => "stretch",
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        *self as u64
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<EnumReduce<'py>> {
+        reduce_enum(slf.as_any(), format!("{:?}", slf.get()))
+    }
+}
+
+fn extract_alignment(obj: &Bound<PyAny>) -> PyResult<Alignment> {
+    Alignment::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+/// Base class for shapes.
+///
+/// Shapes are used to define the envelope of a pulse. Internally, the shape is
+/// represented as a function :math:`f(t)` defined on the interval :math:`t \in
+/// [-0.5, 0.5]`. The shape should be normalized such that :math:`f(\pm 0.5) = 0`
+/// and :math:`f(0) = 1`.
+///
+/// Following shapes are supported:
+///
+/// - :class:`Hann`: Hann window.
+/// - :class:`Gaussian`: Truncated Gaussian.
+/// - :class:`CosineSum`: Generalized cosine window, e.g. Blackman.
+/// - :class:`ErfSquare`: Flat-top with error function edges.
+/// - :class:`Interp`: Interpolated shape.
+/// - :class:`Spline`: Cubic spline through control points.
+/// - :class:`SampledShape`: Pre-sampled envelope.
+/// - :class:`ShapeProduct`: Product of two shapes, also ``a * b``.
+/// - :class:`ShapeSum`: Sum of two shapes, also ``a + b``.
+/// - :class:`CustomShape`: Shape defined by a Python function.
+#[pyclass(subclass, frozen)]
+#[derive(Debug, Clone)]
+struct Shape;
+
+#[pymethods]
+impl Shape {
+    fn __mul__(slf: &Bound<Self>, other: &Bound<PyAny>) -> PyResult<PyObject> {
+        let py = slf.py();
+        let Ok(other) = other.downcast::<Shape>() else {
+            return Ok(py.NotImplemented());
+        };
+        let product = ShapeProduct::new(slf.clone().unbind(), other.clone().unbind());
+        Ok(Py::new(py, product)?.into_py(py))
+    }
+
+    fn __add__(slf: &Bound<Self>, other: &Bound<PyAny>) -> PyResult<PyObject> {
+        let py = slf.py();
+        let Ok(other) = other.downcast::<Shape>() else {
+            return Ok(py.NotImplemented());
+        };
+        let sum = ShapeSum::new(slf.clone().unbind(), other.clone().unbind());
+        Ok(Py::new(py, sum)?.into_py(py))
+    }
+}
+
+impl Shape {
+    fn get_rust_shape(slf: &Bound<Shape>) -> PyResult<shape::Shape> {
+        let py = slf.py();
+        if slf.downcast::<Hann>().is_ok() {
+            return Ok(shape::Shape::new_hann());
+        }
+        if let Ok(custom) = slf.downcast::<CustomShape>() {
+            return Ok(custom.get().shape.clone());
+        }
+        if let Ok(product) = slf.downcast::<ShapeProduct>() {
+            let product = product.get();
+            return Ok(shape::Shape::new_product(
+                Shape::get_rust_shape(product.a.bind(py))?,
+                Shape::get_rust_shape(product.b.bind(py))?,
+            ));
+        }
+        if let Ok(sum) = slf.downcast::<ShapeSum>() {
+            let sum = sum.get();
+            return Ok(shape::Shape::new_sum(
+                Shape::get_rust_shape(sum.a.bind(py))?,
+                Shape::get_rust_shape(sum.b.bind(py))?,
+            ));
+        }
+        if let Ok(gaussian) = slf.downcast::<Gaussian>() {
+            return Ok(shape::Shape::new_gaussian(gaussian.get().truncation)?);
+        }
+        if let Ok(erf_square) = slf.downcast::<ErfSquare>() {
+            return Ok(shape::Shape::new_erf_square(erf_square.get().truncation)?);
+        }
+        if let Ok(cosine_sum) = slf.downcast::<CosineSum>() {
+            return Ok(shape::Shape::new_cosine_sum(cosine_sum.get().coefficients)?);
+        }
+        if let Ok(interp) = slf.downcast::<Interp>() {
+            let interp = interp.get();
+            return Ok(shape::Shape::new_interp(
+                interp.knots.clone(),
+                interp.controls.clone(),
+                interp.degree,
+            )?);
+        }
+        if let Ok(sampled) = slf.downcast::<SampledShape>() {
+            return sampled.get().to_rust_shape();
+        }
+        if let Ok(spline) = slf.downcast::<Spline>() {
+            let spline = spline.get();
+            return Ok(shape::Shape::new_spline(
+                spline.x.clone(),
+                spline.y.clone(),
+            )?);
+        }
+        Err(PyTypeError::new_err("Invalid shape type."))
+    }
+}
+
+/// A Hann shape.
+#[pyclass(extends=Shape, frozen)]
+#[derive(Debug, Clone)]
+struct Hann;
+
+#[pymethods]
+impl Hann {
+    #[new]
+    fn new() -> (Self, Shape) {
+        (Self, Shape)
+    }
+}
+
+/// A Gaussian shape.
+///
+/// The Gaussian is truncated at `truncation` standard deviations on both
+/// sides of the center, i.e. the pulse width is ``2 * truncation * sigma``.
+/// The value at the truncation point is subtracted and the shape rescaled, so
+/// that :math:`f(\pm 0.5) = 0` and :math:`f(0) = 1`.
+///
+/// Args:
+///     truncation (float): Half width of the pulse in units of the standard
+///         deviation. Defaults to 2.0.
+/// Raises:
+///     ValueError: If `truncation` is not positive.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Gaussian, Play
+///         shapes = {"gauss": Gaussian(truncation=3.0)}
+///         play = Play("xy", "gauss", 0.5, 60e-9)
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Gaussian {
+    truncation: f64,
+}
+
+#[pymethods]
+impl Gaussian {
+    #[new]
+    #[pyo3(signature = (truncation=2.0))]
+    fn new(truncation: f64) -> PyResult<(Self, Shape)> {
+        shape::Shape::new_gaussian(truncation).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok((Self { truncation }, Shape))
+    }
+}
+
+/// Elementwise product of two shapes.
+///
+/// This is useful for windowed modulations, e.g. a :class:`Hann` window times
+/// a slow :class:`Spline`. The plateau of a flat-top pulse is inserted at the
+/// peak of `a`. ``a * b`` is a shorthand.
+///
+/// Args:
+///     a (Shape): First factor.
+///     b (Shape): Second factor.
+/// Example:
+///     .. code-block:: python
+///
+///         import numpy as np
+///         from bosing import Hann, Spline
+///         x = np.linspace(-0.5, 0.5, 51)
+///         chirp = Spline(x, np.cos(2 * np.pi * x**2))
+///         shapes = {"windowed": Hann() * chirp}
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug)]
+struct ShapeProduct {
+    a: Py<Shape>,
+    b: Py<Shape>,
+}
+
+#[pymethods]
+impl ShapeProduct {
+    #[new]
+    fn new(a: Py<Shape>, b: Py<Shape>) -> (Self, Shape) {
+        (Self { a, b }, Shape)
+    }
+}
+
+/// Elementwise sum of two shapes.
+///
+/// The plateau of a flat-top pulse is inserted at the peak of `a`. The sum is
+/// not rescaled, so it is only normalized if the values of the two shapes at
+/// the peak add up to 1. ``a + b`` is a shorthand.
+///
+/// Args:
+///     a (Shape): First term.
+///     b (Shape): Second term.
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug)]
+struct ShapeSum {
+    a: Py<Shape>,
+    b: Py<Shape>,
+}
+
+#[pymethods]
+impl ShapeSum {
+    #[new]
+    fn new(a: Py<Shape>, b: Py<Shape>) -> (Self, Shape) {
+        (Self { a, b }, Shape)
+    }
+}
+
+/// A shape defined by a Python function.
+///
+/// The function is called with the positions in :math:`[-0.5, 0.5]` when the
+/// envelopes are sampled. Without `vectorized`, it is called with one float at
+/// a time and returns a float. With `vectorized`, it is called once per
+/// envelope with a 1-D array of positions and returns an array of the same
+/// length, which is much faster.
+///
+/// Sampling runs on worker threads without the GIL, and the GIL is acquired
+/// for each call of the function, so custom shapes don't benefit from parallel
+/// sampling. An exception raised by the function is the ``__cause__`` of the
+/// :class:`SamplingError` of :func:`generate_waveforms`. The sampled envelopes
+/// are cached like those of built-in shapes, which assumes that the function
+/// always returns the same values. Set `cache` to ``False`` if the function
+/// depends on external state. Custom shapes are never stored in the
+/// persistent envelope cache.
+///
+/// Args:
+///     function (Callable[[float], float] | Callable[[numpy.ndarray], ArrayLike]):
+///         Value of the shape at the given position.
+///     vectorized (bool): Whether `function` takes an array. Defaults to
+///         ``False``.
+///     cache (bool): Whether to cache the sampled envelopes. Defaults to
+///         ``True``.
+/// Raises:
+///     TypeError: If `function` is not callable.
+/// Example:
+///     .. code-block:: python
+///
+///         import numpy as np
+///         from bosing import CustomShape
+///         sine = CustomShape(lambda x: np.cos(np.pi * x), vectorized=True)
+#[pyclass(extends=Shape, frozen)]
+#[derive(Debug)]
+struct CustomShape {
+    #[pyo3(get)]
+    function: PyObject,
+    #[pyo3(get)]
+    vectorized: bool,
+    #[pyo3(get)]
+    cache: bool,
+    /// Created once, because custom shapes are only equal to themselves.
+    shape: shape::Shape,
+}
+
+#[pymethods]
+impl CustomShape {
+    #[new]
+    #[pyo3(signature = (function, *, vectorized=false, cache=true))]
+    fn new(function: &Bound<PyAny>, vectorized: bool, cache: bool) -> PyResult<(Self, Shape)> {
+        if !function.is_callable() {
+            return Err(PyTypeError::new_err("The shape function must be callable."));
+        }
+        let sample = custom_sample_fn(function.clone().unbind(), vectorized);
+        let custom = Self {
+            function: function.clone().unbind(),
+            vectorized,
+            cache,
+            shape: shape::Shape::new_custom(sample, cache),
+        };
+        Ok((custom, Shape))
+    }
+}
+
+fn custom_sample_fn(function: PyObject, vectorized: bool) -> Arc<shape::SampleFn> {
+    Arc::new(move |x0, dx, array: &mut [f64]| {
+        // Called from worker threads, which don't hold the GIL.
+        Python::with_gil(|py| -> PyResult<()> {
+            let function = function.bind(py);
+            if !vectorized {
+                for (i, y) in array.iter_mut().enumerate() {
+                    *y = function.call1((x0 + i as f64 * dx,))?.extract()?;
+                }
+                return Ok(());
+            }
+            let x = PyArray1::from_iter_bound(py, (0..array.len()).map(|i| x0 + i as f64 * dx));
+            let y = function.call1((x,))?;
+            let y: Vec<f64> = match y.extract::<PyReadonlyArray1<f64>>() {
+                Ok(y) => y.as_array().to_vec(),
+                Err(_) => y.extract()?,
+            };
+            if y.len() != array.len() {
+                return Err(PyValueError::new_err(format!(
+                    "The shape function returned {} values for {} positions.",
+                    y.len(),
+                    array.len()
+                )));
+            }
+            array.copy_from_slice(&y);
+            Ok(())
+        })?;
+        Ok(())
+    })
+}
+
+/// A flat-top shape with error function edges.
+///
+/// The edges are those of a rectangle smoothed by a Gaussian filter, which has
+/// a much narrower spectrum than :class:`Hann` edges and suits long flux
+/// pulses. The `width` of :class:`Play` is the total length of both edges, and
+/// the flat part is given by `plateau`. Each edge is centered in its half of
+/// `width` and truncated at `truncation` standard deviations of the filter.
+/// The edges are rescaled, so that :math:`f(\pm 0.5) = 0` and :math:`f(0) = 1`.
+///
+/// Args:
+///     truncation (float): Half length of each edge in units of the standard
+///         deviation of the filter. Defaults to 2.0.
+/// Raises:
+///     ValueError: If `truncation` is not positive.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import ErfSquare, Play
+///         shapes = {"erf": ErfSquare(truncation=3.0)}
+///         play = Play("z", "erf", 0.2, 20e-9, plateau=1e-6)
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct ErfSquare {
+    truncation: f64,
+}
+
+#[pymethods]
+impl ErfSquare {
+    #[new]
+    #[pyo3(signature = (truncation=2.0))]
+    fn new(truncation: f64) -> PyResult<(Self, Shape)> {
+        shape::Shape::new_erf_square(truncation)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok((Self { truncation }, Shape))
+    }
+}
+
+/// A sum of cosines.
+///
+/// The shape is
+///
+/// .. math::
+///
+///     f(t) = a_0 + a_1 \cos(2 \pi t) + a_2 \cos(4 \pi t) + a_3 \cos(6 \pi t)
+///
+/// on :math:`t \in [-0.5, 0.5]`, which covers the common generalized cosine
+/// windows. :meth:`blackman`, :meth:`hamming` and :meth:`nuttall` create the
+/// named windows.
+///
+/// .. caution::
+///
+///     The coefficients are not rescaled. The shape is normalized only if
+///     :math:`a_0 + a_1 + a_2 + a_3 = 1`. The Hamming window doesn't vanish at
+///     :math:`t = \pm 0.5`.
+///
+/// Args:
+///     a0 (float): Constant term.
+///     a1 (float): Coefficient of :math:`\cos(2 \pi t)`.
+///     a2 (float): Coefficient of :math:`\cos(4 \pi t)`. Defaults to 0.
+///     a3 (float): Coefficient of :math:`\cos(6 \pi t)`. Defaults to 0.
+/// Raises:
+///     ValueError: If a coefficient is not finite.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import CosineSum
+///         shapes = {
+///             "blackman": CosineSum.blackman(),
+///             "hann": CosineSum(0.5, 0.5),
+///         }
+#[pyclass(extends=Shape, frozen)]
+#[derive(Debug, Clone)]
+struct CosineSum {
+    coefficients: [f64; 4],
+}
+
+#[pymethods]
+impl CosineSum {
+    #[new]
+    #[pyo3(signature = (a0, a1, a2=0.0, a3=0.0))]
+    fn new(a0: f64, a1: f64, a2: f64, a3: f64) -> PyResult<(Self, Shape)> {
+        let coefficients = [a0, a1, a2, a3];
+        shape::Shape::new_cosine_sum(coefficients)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok((Self { coefficients }, Shape))
+    }
+
+    /// Blackman window with coefficients (0.42, 0.5, 0.08).
+    ///
+    /// Returns:
+    ///     CosineSum: The shape.
+    #[staticmethod]
+    fn blackman(py: Python<'_>) -> PyResult<Py<Self>> {
+        Py::new(py, Self::new(0.42, 0.5, 0.08, 0.0)?)
+    }
+
+    /// Hamming window with coefficients (25/46, 21/46).
+    ///
+    /// Returns:
+    ///     CosineSum: The shape.
+    #[staticmethod]
+    fn hamming(py: Python<'_>) -> PyResult<Py<Self>> {
+        Py::new(py, Self::new(25.0 / 46.0, 21.0 / 46.0, 0.0, 0.0)?)
+    }
+
+    /// Nuttall window with coefficients (0.355768, 0.487396, 0.144232,
+    /// 0.012604).
+    ///
+    /// Returns:
+    ///     CosineSum: The shape.
+    #[staticmethod]
+    fn nuttall(py: Python<'_>) -> PyResult<Py<Self>> {
+        Py::new(py, Self::new(0.355768, 0.487396, 0.144232, 0.012604)?)
+    }
+
+    /// Coefficients (a0, a1, a2, a3).
+    #[getter]
+    fn coefficients(&self) -> (f64, f64, f64, f64) {
+        let [a0, a1, a2, a3] = self.coefficients;
+        (a0, a1, a2, a3)
+    }
+}
+
+/// An interpolated shape.
+///
+/// The interpolated shape use a B-spline. :func:`scipy.interpolate.make_interp_spline`
+/// can be used to calculate the parameters.
+///
+/// .. caution::
+///
+///     It's user's responsibility to ensure the b-spline parameters are valid and
+///     the shape is normalized such that :math:`f(\pm 0.5) = 0` and :math:`f(0) = 1`.
+///
+/// Args:
+///     knots (Sequence[float]): Knots of the B-spline.
+///     controls (Sequence[float]): Control points of the B-spline.
+///     degree (int): Degree of the B-spline.
+/// Example:
+///     .. code-block:: python
+///
+///         import numpy as np
+///         from scipy.interpolate import make_interp_spline
+///         from bosing import Interp
+///         x = np.linspace(0, np.pi, 10)
+///         y = np.sin(x)
+///         x = (x - x[0]) / (x[-1] - x[0]) - 0.5 # Normalize x to [-0.5, 0.5]
+///         spline = make_interp_spline(x, y, k=3)
+///         interp = Interp(spline.t, spline.c, spline.k)
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Interp {
+    knots: Vec<f64>,
+    controls: Vec<f64>,
+    degree: usize,
+}
+
+#[pymethods]
+impl Interp {
+    #[new]
+    fn new(knots: Vec<f64>, controls: Vec<f64>, degree: usize) -> PyResult<(Self, Shape)> {
+        Ok((
+            Self {
+                knots,
+                controls,
+                degree,
+            },
+            Shape,
+        ))
+    }
+}
+
+/// A cubic spline shape through control points.
+///
+/// The shape is a cubic Hermite spline with Catmull-Rom tangents, which passes
+/// through all the points and is zero outside ``[x[0], x[-1]]``. Unlike
+/// :class:`Interp`, calibrated envelopes can be used without fitting a
+/// B-spline first.
+///
+/// .. caution::
+///
+///     It's user's responsibility to ensure the shape is normalized such that
+///     :math:`f(\pm 0.5) = 0` and :math:`f(0) = 1`.
+///
+/// Args:
+///     x (Sequence[float]): Positions of the points in strictly increasing
+///         order, usually in the range :math:`[-0.5, 0.5]`.
+///     y (Sequence[float]): Values at the points.
+/// Raises:
+///     ValueError: If there are less than 2 points, the lengths differ, or
+///         `x` is not strictly increasing.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Spline
+///         spline = Spline([-0.5, -0.2, 0.0, 0.2, 0.5], [0.0, 0.6, 1.0, 0.6, 0.0])
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Spline {
+    x: Vec<f64>,
+    y: Vec<f64>,
+}
+
+#[pymethods]
+impl Spline {
+    #[new]
+    fn new(x: Vec<f64>, y: Vec<f64>) -> PyResult<(Self, Shape)> {
+        shape::Shape::new_spline(x.clone(), y.clone())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok((Self { x, y }, Shape))
+    }
+}
+
+/// A shape from pre-sampled values, e.g. a measured envelope.
+///
+/// The samples are taken at `sample_rate` and resampled to the rate of the
+/// channel when the envelope is generated. The envelope is reproduced when
+/// the `width` of :class:`Play` is :attr:`duration`, other widths stretch it.
+/// Sample ``k`` is at the start of the ``k``-th interval and the shape returns
+/// to zero at the end of the last interval. Between the samples the values
+/// are interpolated linearly or with a cubic Hermite spline with Catmull-Rom
+/// tangents.
+///
+/// Args:
+///     samples (Sequence[float]): Values of the envelope.
+///     sample_rate (float): Sample rate of `samples`.
+///     interpolation (str): ``"linear"`` or ``"cubic"``. Defaults to
+///         ``"linear"``.
+/// Raises:
+///     ValueError: If `samples` is empty or not finite, `sample_rate` is not
+///         positive, or `interpolation` is invalid.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Play, SampledShape
+///         shape = SampledShape(measured, 2e9, interpolation="cubic")
+///         play = Play("xy", "measured", 0.5, shape.duration)
+#[pyclass(extends=Shape, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct SampledShape {
+    samples: Vec<f64>,
+    sample_rate: f64,
+    interpolation: String,
+}
+
+#[pymethods]
+impl SampledShape {
+    #[new]
+    #[pyo3(signature = (samples, sample_rate, *, interpolation="linear"))]
+    fn new(samples: Vec<f64>, sample_rate: f64, interpolation: &str) -> PyResult<(Self, Shape)> {
+        if !(sample_rate.is_finite() && sample_rate > 0.0) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid sample rate {}",
+                sample_rate
+            )));
+        }
+        let shape = Self {
+            samples,
+            sample_rate,
+            interpolation: interpolation.to_string(),
+        };
+        shape.to_rust_shape()?;
+        Ok((shape, Shape))
+    }
+
+    /// Duration of the samples, ``len(samples) / sample_rate``.
+    #[getter]
+    fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate
+    }
+}
+
+impl SampledShape {
+    fn to_rust_shape(&self) -> PyResult<shape::Shape> {
+        let interpolation = match self.interpolation.as_str() {
+            "linear" => shape::Interpolation::Linear,
+            "cubic" => shape::Interpolation::Cubic,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid interpolation '{}', expected 'linear' or 'cubic'.",
+                    self.interpolation
+                )))
+            }
+        };
+        shape::Shape::new_sampled(self.samples.clone(), interpolation)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
+
+fn extract_margin(obj: &Bound<PyAny>) -> PyResult<(Time, Time)> {
+    if let Ok(v) = obj.extract() {
+        let t = Time::new(v)?;
+        return Ok((t, t));
+    }
+    if let Ok((v1, v2)) = obj.extract() {
+        let t1 = Time::new(v1)?;
+        let t2 = Time::new(v2)?;
+        return Ok((t1, t2));
+    }
+    let msg = "Failed to convert the value to (float, float).";
+    Err(PyValueError::new_err(msg))
+}
+
+/// Returns whether the element is phantom and whether it is ignored by the
+/// layout.
+fn extract_phantom(obj: &Bound<PyAny>) -> PyResult<(bool, bool)> {
+    if let Ok(phantom) = obj.extract() {
+        return Ok((phantom, false));
+    }
+    if let Ok(mode) = obj.extract::<&str>() {
+        match mode {
+            "layout" => return Ok((true, false)),
+            "ignore" => return Ok((true, true)),
+            _ => (),
+        }
+    }
+    let msg = "Failed to convert the value to bool, 'layout' or 'ignore'.";
+    Err(PyValueError::new_err(msg))
+}
+
+fn extract_duration_of(obj: &Bound<PyAny>) -> PyResult<schedule::DurationRef> {
+    if let Ok(label) = obj.extract() {
+        return Ok(schedule::DurationRef::new(label, 1.0));
+    }
+    if let Ok((label, factor)) = obj.extract() {
+        return Ok(schedule::DurationRef::new(label, factor));
+    }
+    let msg = "Failed to convert the value to str or (str, float).";
+    Err(PyValueError::new_err(msg))
+}
+
+/// Placeholder of a numeric element parameter.
+///
+/// A :class:`Param` can be passed instead of the value of the numeric
+/// parameters of :class:`Play`, :class:`ShiftPhase`, :class:`SetPhase`,
+/// :class:`ShiftFreq`, :class:`SetFreq`, :class:`PhaseRamp`, :class:`Delay`,
+/// :class:`Marker` and the `spacing` of :class:`Repeat`. The schedule is built once and
+/// :func:`bind` fills in the values, e.g. for every point of a scan. Until
+/// then the parameter reads as zero.
+///
+/// .. code-block:: python
+///
+///     template = Play("xy", "hann", Param("amp"), 100e-9)
+///     schedules = [bind(template, {"amp": a}) for a in amplitudes]
+///
+/// Args:
+///     name (str): Name of the parameter.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Param {
+    name: String,
+}
+
+#[pymethods]
+impl Param {
+    #[new]
+    fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Param({:?})", self.name)
+    }
+}
+
+/// Value of a numeric element parameter or a :class:`Param`.
+#[derive(Debug, Clone)]
+enum ParamArg<T> {
+    Value(T),
+    Param(String),
+}
+
+impl<'py, T: FromPyObject<'py>> FromPyObject<'py> for ParamArg<T> {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(param) = ob.downcast::<Param>() {
+            return Ok(Self::Param(param.get().name.clone()));
+        }
+        ob.extract().map(Self::Value)
+    }
+}
+
+impl<T> ParamArg<T> {
+    /// Returns the value, or `placeholder` after recording the parameter.
+    fn value_or(self, field: &str, placeholder: T, params: &mut Vec<schedule::ParamRef>) -> T {
+        match self {
+            Self::Value(value) => value,
+            Self::Param(name) => {
+                params.push(schedule::ParamRef::new(field.to_string(), name));
+                placeholder
+            }
+        }
+    }
+}
+
+/// Binds the fields of the element to the parameters.
+fn with_params(element: Element, params: Vec<schedule::ParamRef>) -> PyResult<Element> {
+    if params.is_empty() {
+        return Ok(element);
+    }
+    let common = element.0.common.to_builder().params(params).build()?;
+    let variant = element.0.variant.clone();
+    Ok(Element::new(Arc::new(schedule::Element::new(
+        common, variant,
+    ))))
+}
+
+/// Base class for schedule elements.
+///
+/// A schedule element is a node in the tree structure of a schedule similar to
+/// HTML elements. The design is inspired by `XAML in WPF / WinUI
+/// <https://learn.microsoft.com/en-us/windows/apps/design/layout/layouts-with-xaml>`_
+///
+/// Every element has the following properties:
+///
+/// - :attr:`margin`
+///     The margin of an element is a tuple of two floats representing the
+///     margin before and after the element. If :attr:`margin` is set to a
+///     single float, both sides use the same value.
+///
+///     Similar to margins in XAML, margins don't collapse. For example, if two
+///     elements have a margin of 10 and 20, the space between the two elements
+///     is 30, not 20.
+///
+/// - :attr:`alignment`
+///     The alignment of the element. This property takes effect when the
+///     element is a child of a :class:`Grid` element, or of a :class:`Stack`
+///     element with `align_children`.
+///
+/// - :attr:`phantom`
+///     Whether the element is a phantom element. Phantom elements are measured
+///     and arranged in the layout but do not add to the waveforms. With
+///     ``phantom="ignore"`` the element is also left out of the layout like a
+///     placeholder: it takes no time and doesn't occupy its channels, see
+///     :attr:`phantom_ignored`.
+///
+/// - :attr:`duration`, :attr:`max_duration`, and :attr:`min_duration`
+///     Constraints on the duration of the element. When :attr:`duration`,
+///     :attr:`max_duration`, and :attr:`min_duration` are conflicting, the
+///     priority is as follows:
+///
+///     1. :attr:`min_duration`
+///     2. :attr:`max_duration`
+///     3. :attr:`duration`
+///
+///     When :attr:`duration` is not set, the duration is calculated such that
+///     the element occupies the minimum duration.
+///
+/// There are two types of elements:
+///
+/// - Instruction elements:
+///     Elements that instruct the waveform generator to perform certain
+///     operations, such as playing a pulse or setting the phase of a channel.
+///
+///     - :class:`Play`: Play a pulse on a channel.
+///     - :class:`ShiftPhase`: Shift the phase of a channel.
+///     - :class:`SetPhase`: Set the phase of a channel.
+///     - :class:`ShiftFreq`: Shift the frequency of a channel.
+///     - :class:`SetFreq`: Set the frequency of a channel.
+///     - :class:`PhaseRamp`: Ramp the phase of a channel over time.
+///     - :class:`SwapPhase`: Swap the phase of two channels.
+///
+///     The timing information required by the waveform generator is calculated
+///     by the layout system.
+///
+/// - Layout elements:
+///     Elements that control the layout of child elements.
+///
+///     - :class:`Grid`: Grid layout.
+///     - :class:`Stack`: Stack layout.
+///     - :class:`Absolute`: Absolute layout.
+///     - :class:`Overlay`: Overlay layout.
+///     - :class:`Repeat`: Repeat element.
+///     - :class:`Scale`: Time scaling element.
+///     - :class:`Reverse`: Time reversal element.
+///     - :class:`Barrier`: Barrier element.
+///     - :class:`Delay`: Delay element.
+///     - :class:`Marker`: Digital marker element.
+///
+/// Args:
+///     margin (float | tuple[float, float]): Margin of the element. Defaults to
+///         0.
+///     alignment (str | Alignment): Alignment of the element. The value can
+///         be :class:`Alignment` or one of 'end', 'start', 'center', 'stretch'.
+///         Defaults to :attr:`Alignment.End`.
+///     phantom (bool | str): Whether the element is a phantom element and
+///         should not add to waveforms. ``True`` or ``'layout'`` keeps the
+///         element in the layout, ``'ignore'`` measures it as zero. Defaults
+///         to ``False``.
+///     duration (float): Duration of the element. Defaults to ``None``.
+///     duration_of (str | tuple[str, float] | None): Declare the duration as a
+///         multiple of the measured duration of another element, given as
+///         ``label`` or ``(label, factor)``. The first element with the label
+///         in depth-first order is used, and its margins are included. The
+///         reference is resolved before layout and can't be combined with
+///         `duration`. Defaults to ``None``.
+///     max_duration (float): Maximum duration of the element. Defaults to
+///         ``inf``.
+///     min_duration (float): Minimum duration of the element. Defaults to 0.
+///     label (str | None): Label of the element. Labels are used to refer to
+///         elements, for example in :func:`compute_amp_scales`. Defaults to
+///         ``None``.
+#[pyclass(subclass, frozen)]
+#[derive(Debug, Clone)]
+struct Element(ElementRef, ArrangeCache);
+
+/// Child time ranges recorded by :meth:`Element.arrange`.
+#[derive(Debug, Default)]
+struct ArrangeCache(Mutex<Option<Vec<TimeSpan>>>);
+
+impl Clone for ArrangeCache {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.get()))
+    }
+}
+
+impl ArrangeCache {
+    fn get(&self) -> Option<Vec<TimeSpan>> {
+        self.0.lock().expect("Lock should not be poisoned").clone()
+    }
+
+    fn set(&self, value: Option<Vec<TimeSpan>>) {
+        *self.0.lock().expect("Lock should not be poisoned") = value;
+    }
+}
+
+impl Element {
+    fn new(element: ElementRef) -> Self {
+        Self(element, ArrangeCache::default())
+    }
+}
+
+#[pymethods]
+impl Element {
+    /// Arrange the element and record the time ranges of children.
+    ///
+    /// After arranging, :attr:`child_time_ranges` of this element and all
+    /// descendant containers are available. If an element is placed multiple
+    /// times, e.g. the child of a :class:`Repeat`, the first placement is
+    /// recorded. Calling this method again overwrites previous records.
+    ///
+    /// Args:
+    ///     time (float): Start time of the element. Defaults to 0.
+    ///     duration (float | None): Duration available to the element. Defaults
+    ///         to the measured duration.
+    #[pyo3(signature = (time=Time::ZERO, duration=None))]
+    fn arrange(slf: &Bound<Self>, time: Time, duration: Option<Time>) -> PyResult<()> {
+        let mut resolver = DurationResolver::new(&slf.get().0);
+        let root = &resolver
+            .resolve(&slf.get().0)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let time_range = schedule::TimeRange {
+            start: time,
+            span: duration.unwrap_or_else(|| root.measure()),
+        };
+        let mut objects = HashMap::new();
+        collect_py_elements(slf, &mut objects)?;
+        for obj in objects.values() {
+            obj.get().1.set(None);
+        }
+        // Containers above duration references are rebuilt by the resolver.
+        let objects: HashMap<_, _> = objects
+            .into_iter()
+            .map(|(k, v)| (resolver.resolved_ptr(k), v))
+            .collect();
+        for (item, ranges) in executor::child_time_ranges(root, time_range) {
+            let Some(obj) = objects.get(&Arc::as_ptr(item)) else {
+                continue;
+            };
+            let cache = &obj.get().1;
+            if cache.get().is_none() {
+                let spans = ranges.iter().map(|r| (r.start, r.start + r.span));
+                cache.set(Some(spans.collect()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Time ranges ``(start, end)`` of the children recorded by the last call
+    /// of :meth:`arrange`, including margins. ``None`` if the element is not
+    /// arranged or is not a container.
+    #[getter]
+    fn child_time_ranges(&self) -> Option<Vec<TimeSpan>> {
+        self.1.get()
+    }
+
+    /// Estimate the size and complexity of the schedule tree.
+    ///
+    /// Useful to warn before generating pathological schedules or to track
+    /// the complexity across versions of an experiment. Shared subtrees are
+    /// counted every time they appear, but computed only once.
+    ///
+    /// Returns:
+    ///     ElementStats: Statistics of the tree rooted at this element.
+    fn stats(&self) -> ElementStats {
+        let stats = schedule::ScheduleStats::new(&self.0);
+        ElementStats {
+            node_count: stats.node_count,
+            depth: stats.depth,
+            variant_counts: stats
+                .variant_counts
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            pulse_count: stats.pulse_count,
+        }
+    }
+
+    /// Serialize the schedule tree to JSON.
+    ///
+    /// The format is the same as the schedule of :func:`load_experiment`.
+    /// Shared subtrees are written at every occurrence.
+    ///
+    /// Returns:
+    ///     str: JSON document of the schedule.
+    fn to_json(&self) -> PyResult<String> {
+        schedule::to_json(&self.0).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Deserialize a schedule tree from JSON.
+    ///
+    /// Args:
+    ///     text (str): JSON document created by :meth:`to_json`.
+    /// Returns:
+    ///     Element: Root element of the schedule.
+    /// Raises:
+    ///     ValueError: If the document is invalid.
+    #[staticmethod]
+    fn from_json(py: Python, text: &str) -> PyResult<Py<Element>> {
+        let root = py
+            .allow_threads(|| schedule::from_json(text))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        to_py_element(py, &root, &mut HashMap::new())
+    }
+
+    #[getter]
+    fn margin(&self) -> (Time, Time) {
+        self.0.common.margin()
+    }
+
+    #[getter]
+    fn alignment(&self) -> Alignment {
+        self.0.common.alignment()
+    }
+
+    #[getter]
+    fn phantom(&self) -> bool {
+        self.0.common.phantom()
+    }
+
+    #[getter]
+    fn phantom_ignored(&self) -> bool {
+        self.0.common.phantom_ignored()
+    }
+
+    #[getter]
+    fn duration(&self) -> Option<Time> {
+        self.0.common.duration()
+    }
+
+    #[getter]
+    fn duration_of(&self) -> Option<(Label, f64)> {
+        self.0
+            .common
+            .duration_ref()
+            .map(|r| (r.label().clone(), r.factor()))
+    }
+
+    #[getter]
+    fn max_duration(&self) -> Time {
+        self.0.common.max_duration()
+    }
+
+    #[getter]
+    fn min_duration(&self) -> Time {
+        self.0.common.min_duration()
+    }
+
+    #[getter]
+    fn label(&self) -> Option<&Label> {
+        self.0.common.label()
+    }
+
+    /// Names of the :class:`Param` placeholders of this element by the bound
+    /// fields, e.g. ``{"amplitude": "amp"}``.
+    #[getter]
+    fn params(&self) -> HashMap<&str, &str> {
+        self.0
+            .common
+            .params()
+            .iter()
+            .map(|p| (p.field(), p.name()))
+            .collect()
+    }
+
+    /// Names of the :class:`Param` placeholders in the tree rooted at this
+    /// element.
+    ///
+    /// Returns:
+    ///     list[str]: Sorted parameter names which :func:`bind` needs.
+    fn param_names(&self) -> Vec<String> {
+        param_names(&self.0)
+    }
+}
+
+trait ElementSubclass: Sized + DerefToPyAny
+where
+    for<'a> &'a Self::Variant: TryFrom<&'a schedule::ElementVariant>,
+    for<'a> <&'a Self::Variant as TryFrom<&'a schedule::ElementVariant>>::Error: Debug,
+{
+    type Variant: Into<schedule::ElementVariant>;
+
+    fn variant<'a>(slf: &'a Bound<Self>) -> &'a Self::Variant {
+        slf.downcast::<Element>()
+            .expect("Self should be a subclass of Element")
+            .get()
+            .0
+            .variant
+            .borrow()
+            .try_into()
+            .expect("Element should have a valid variant")
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_element(
+        variant: Self::Variant,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<Element> {
+        let mut builder = ElementCommonBuilder::new();
+        if let Some(obj) = margin {
+            builder.margin(extract_margin(obj)?);
+        }
+        if let Some(obj) = alignment {
+            builder.alignment(extract_alignment(obj)?);
+        }
+        if let Some(obj) = phantom {
+            let (phantom, ignored) = extract_phantom(obj)?;
+            builder.phantom(phantom).phantom_ignored(ignored);
+        }
+        builder
+            .duration(duration)
+            .max_duration(max_duration)
+            .min_duration(min_duration)
+            .label(label);
+        if let Some(obj) = duration_of {
+            if duration.is_some() {
+                return Err(PyValueError::new_err(
+                    "duration and duration_of are mutually exclusive",
+                ));
+            }
+            builder.duration_ref(Some(extract_duration_of(obj)?));
+        }
+        let common = builder.build()?;
+        Ok(Element::new(Arc::new(schedule::Element::new(
+            common, variant,
+        ))))
+    }
+}
+
+/// A pulse play element.
+///
+/// Given the pulse envelope :math:`E(t)`, channel total frequency :math:`f_c`,
+/// and channel phase :math:`\phi_c`, the the final pulse :math:`P(t)` starts at
+/// :math:`t_0` with sideband will be
+///
+/// .. math::
+///
+///     E_d(t) = \left( 1 + i \alpha \frac{d}{dt} \right) E(t)
+///
+///     P(t) = E_d(t) \exp \big[ i 2 \pi (f_c t + f_p (t-t_0) + \phi_c + \phi_p) \big]
+///
+/// where :math:`\alpha` is the `drag_coef` parameter, :math:`f_p` is the
+/// `frequency` parameter, and :math:`\phi_p` is the `phase` parameter. The
+/// derivative is calculated using the central difference method. An exceptional
+/// case is when the pulse is a rectangular pulse. In this case, the drag
+/// coefficient is ignored.
+///
+/// With a non-zero `chirp_rate` :math:`\beta`, the frequency of the pulse
+/// sweeps linearly as :math:`f_p + \beta (t - t_m)`, where :math:`t_m` is the
+/// middle of the pulse, i.e. the phase gains an extra term
+/// :math:`\beta (t - t_m)^2 / 2`. The phase at the middle of the pulse is not
+/// changed by the chirp.
+///
+/// If `flexible` is set to ``True``, the `plateau` parameter is ignored and the
+/// actual plateau length is determined by the duration of the element.
+///
+/// The rising edge is the first half of the shape with `width`, and the
+/// falling edge is the second half. `fall_shape_id` and `fall_width` replace the
+/// shape and width of the falling edge, so the total length of the pulse is
+/// ``(width + fall_width) / 2 + plateau``. Asymmetric edges are not supported
+/// for rectangular pulses.
+///
+/// .. caution::
+///
+///     The unit of phase is number of cycles, not radians. For example, a phase
+///     of :math:`0.5` means a phase shift of :math:`\pi` radians. Use
+///     :func:`set_phase_unit` to give and get phases in radians instead.
+///
+/// Args:
+///     channel_id (str): Target channel ID.
+///     shape_id (str | None): Shape ID of the pulse. If ``None``, the pulse is
+///         a rectangular pulse.
+///     amplitude (float): Amplitude of the pulse.
+///     width (float): Width of the pulse.
+///     plateau (float): Plateau length of the pulse. Defaults to 0.
+///     total (float | None): Total length of the pulse including the edges,
+///         which sets the plateau to ``total - (width + fall_width) / 2``.
+///         Can't be given together with `plateau`. Defaults to ``None``.
+///     drag_coef (float): Drag coefficient of the pulse. If the pulse is a
+///         rectangular pulse, the drag coefficient is ignored. Defaults to 0.
+///     frequency (float): Additional frequency of the pulse on top of channel
+///         base frequency and frequency shift. Defaults to 0.
+///     chirp_rate (float): Rate of the linear frequency sweep in Hz/s. Defaults
+///         to 0.
+///     phase (float): Additional phase of the pulse in **cycles**, or radians
+///         as set by :func:`set_phase_unit`. Defaults to 0.
+///     flexible (bool): Whether the pulse has flexible plateau length. Defaults
+///         to ``False``.
+///     align_level (int | None): Time axis alignment granularity of this pulse.
+///         The coarser of this and the channel `align_level` is used, so it can
+///         only make the alignment stricter. Defaults to ``None``.
+///     fall_shape_id (str | None): Shape ID of the falling edge. If ``None``,
+///         the falling edge uses `shape_id`. Defaults to ``None``.
+///     fall_width (float | None): Width of the falling edge shape. If ``None``,
+///         the falling edge uses `width`. Defaults to ``None``.
+///     centered (bool): Whether to place the pulse at the center of the
+///         element instead of the start when the element is longer than the
+///         pulse, e.g. because of `duration`. Only affects the layout. Defaults
+///         to ``False``.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct Play;
+
+impl ElementSubclass for Play {
+    type Variant = schedule::Play;
+}
+
+#[pymethods]
+impl Play {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        shape_id,
+        amplitude,
+        width,
+        *,
+        plateau=None,
+        total=None,
+        drag_coef=ParamArg::Value(0.0),
+        frequency=ParamArg::Value(Frequency::ZERO),
+        chirp_rate=ParamArg::Value(ChirpRate::ZERO),
+        phase=ParamArg::Value(Phase::ZERO),
+        flexible=false,
+        align_level=None,
+        fall_shape_id=None,
+        fall_width=None,
+        centered=false,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        shape_id: Option<ShapeId>,
+        amplitude: ParamArg<Amplitude>,
+        width: ParamArg<Time>,
+        plateau: Option<ParamArg<Time>>,
+        total: Option<Time>,
+        drag_coef: ParamArg<f64>,
+        frequency: ParamArg<Frequency>,
+        chirp_rate: ParamArg<ChirpRate>,
+        phase: ParamArg<Phase>,
+        flexible: bool,
+        align_level: Option<i32>,
+        fall_shape_id: Option<ShapeId>,
+        fall_width: Option<Time>,
+        centered: bool,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        if total.is_some() {
+            if plateau.is_some() {
+                return Err(PyValueError::new_err(
+                    "plateau and total can't be given together.",
+                ));
+            }
+            if matches!(width, ParamArg::Param(_)) {
+                return Err(PyValueError::new_err(
+                    "total can't be given with a parameter width.",
+                ));
+            }
+        }
+        let mut params = vec![];
+        let amplitude = amplitude.value_or("amplitude", Amplitude::ZERO, &mut params);
+        let width = width.value_or("width", Time::ZERO, &mut params);
+        let plateau = plateau.unwrap_or(ParamArg::Value(Time::ZERO)).value_or(
+            "plateau",
+            Time::ZERO,
+            &mut params,
+        );
+        let drag_coef = drag_coef.value_or("drag_coef", 0.0, &mut params);
+        let frequency = frequency.value_or("frequency", Frequency::ZERO, &mut params);
+        let chirp_rate = chirp_rate.value_or("chirp_rate", ChirpRate::ZERO, &mut params);
+        let phase = phase.value_or("phase", Phase::ZERO, &mut params);
+        let variant = schedule::Play::new(channel_id, shape_id, amplitude, width)?
+            .with_plateau(plateau)?
+            .with_drag_coef(drag_coef)?
+            .with_frequency(frequency)?
+            .with_chirp_rate(chirp_rate)?
+            .with_phase(phase)?
+            .with_flexible(flexible)
+            .with_align_level(align_level)
+            .with_fall(fall_shape_id, fall_width)?
+            .with_centered(centered);
+        let variant = match total {
+            Some(total) => variant.with_total(total)?,
+            None => variant,
+        };
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id()
+    }
+
+    #[getter]
+    fn shape_id<'a>(slf: &'a Bound<Self>) -> Option<&'a ShapeId> {
+        Self::variant(slf).shape_id()
+    }
+
+    #[getter]
+    fn amplitude(slf: &Bound<Self>) -> Amplitude {
+        Self::variant(slf).amplitude()
+    }
+
+    #[getter]
+    fn width(slf: &Bound<Self>) -> Time {
+        Self::variant(slf).width()
+    }
+
+    #[getter]
+    fn plateau(slf: &Bound<Self>) -> Time {
+        Self::variant(slf).plateau()
+    }
+
+    #[getter]
+    fn drag_coef(slf: &Bound<Self>) -> f64 {
+        Self::variant(slf).drag_coef()
+    }
+
+    #[getter]
+    fn frequency(slf: &Bound<Self>) -> Frequency {
+        Self::variant(slf).frequency()
+    }
+
+    #[getter]
+    fn chirp_rate(slf: &Bound<Self>) -> ChirpRate {
+        Self::variant(slf).chirp_rate()
+    }
+
+    #[getter]
+    fn phase(slf: &Bound<Self>) -> Phase {
+        Self::variant(slf).phase()
+    }
+
+    #[getter]
+    fn flexible(slf: &Bound<Self>) -> bool {
+        Self::variant(slf).flexible()
+    }
+
+    #[getter]
+    fn align_level(slf: &Bound<Self>) -> Option<i32> {
+        Self::variant(slf).align_level()
+    }
+
+    #[getter]
+    fn fall_shape_id<'a>(slf: &'a Bound<Self>) -> Option<&'a ShapeId> {
+        Self::variant(slf).fall_shape_id()
+    }
+
+    #[getter]
+    fn fall_width(slf: &Bound<Self>) -> Option<Time> {
+        Self::variant(slf).fall_width()
+    }
+
+    #[getter]
+    fn centered(slf: &Bound<Self>) -> bool {
+        Self::variant(slf).centered()
+    }
+}
+
+/// A phase shift element.
+///
+/// Phase shift will be added to the channel phase offset :math:`\phi_c` and is
+/// time-independent.
+///
+/// .. caution::
+///
+///     The unit of phase is number of cycles, not radians. For example, a phase
+///     of :math:`0.5` means a phase shift of :math:`\pi` radians. Use
+///     :func:`set_phase_unit` to give and get phases in radians instead.
+///
+/// Args:
+///     channel_id (str): Target channel ID.
+///     phase (float): Phase shift in **cycles**, or radians as set by
+///         :func:`set_phase_unit`.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct ShiftPhase;
+
+impl ElementSubclass for ShiftPhase {
+    type Variant = schedule::ShiftPhase;
+}
+
+#[pymethods]
+impl ShiftPhase {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        phase,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        phase: ParamArg<Phase>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let phase = phase.value_or("phase", Phase::ZERO, &mut params);
+        let variant = schedule::ShiftPhase::new(channel_id, phase)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id()
+    }
+
+    #[getter]
+    fn phase(slf: &Bound<Self>) -> Phase {
+        Self::variant(slf).phase()
+    }
+}
+
+/// A phase set element.
+///
+/// Waveform generator treats the base frequency :math:`f_0` and the channel
+/// frequency shift :math:`\Delta f` differently. :math:`f_0` is never changed
+/// during the execution of the schedule, while :math:`\Delta f` can be changed
+/// by :class:`ShiftFreq` and :class:`SetFreq`. :class:`SetPhase` only considers
+/// :math:`\Delta f` part of the frequency. The channel phase offset
+/// :math:`\phi_c` will be adjusted such that
+///
+/// .. math:: \Delta f t + \phi_c = \phi
+///
+/// at the scheduled time point, where :math:`\phi` is the `phase` parameter.
+///
+/// .. caution::
+///
+///     The unit of phase is number of cycles, not radians. For example, a phase
+///     of :math:`0.5` means a phase shift of :math:`\pi` radians. Use
+///     :func:`set_phase_unit` to give and get phases in radians instead.
+///
+/// Args:
+///     channel_id (str): Target channel ID.
+///     phase (float): Target phase value in **cycles**, or radians as set by
+///         :func:`set_phase_unit`.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct SetPhase;
+
+impl ElementSubclass for SetPhase {
+    type Variant = schedule::SetPhase;
+}
+
+#[pymethods]
+impl SetPhase {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        phase,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        phase: ParamArg<Phase>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let phase = phase.value_or("phase", Phase::ZERO, &mut params);
+        let variant = schedule::SetPhase::new(channel_id, phase)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id()
+    }
+
+    #[getter]
+    fn phase(slf: &Bound<Self>) -> Phase {
+        Self::variant(slf).phase()
+    }
+}
+
+/// A frequency shift element.
+///
+/// Frequency shift will be added to the channel frequency shift :math:`\Delta
+/// f` and the channel phase offset :math:`\phi_c` will be adjusted such that
+/// the phase is continuous at the scheduled time point.
+///
+/// Args:
+///     channel_id (str): Target channel ID.
+///     frequency (float): Delta frequency.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct ShiftFreq;
+
+impl ElementSubclass for ShiftFreq {
+    type Variant = schedule::ShiftFreq;
+}
+
+#[pymethods]
+impl ShiftFreq {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        frequency,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        frequency: ParamArg<Frequency>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let frequency = frequency.value_or("frequency", Frequency::ZERO, &mut params);
+        let variant = schedule::ShiftFreq::new(channel_id, frequency)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id()
+    }
+
+    #[getter]
+    fn frequency(slf: &Bound<Self>) -> Frequency {
+        Self::variant(slf).frequency()
+    }
+}
+
+/// A frequency set element.
+///
+/// The channel frequency shift :math:`\Delta f` will be set to the provided
+/// `frequency` parameter and the channel phase offset :math:`\phi_c` will be
+/// adjusted such that the phase is continuous at the scheduled time point.
+/// The channel base frequency :math:`f_0` will not be changed.
+///
+/// Args:
+///     channel_id (str): Target channel ID.
+///     frequency (float): Target frequency.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct SetFreq;
+
+impl ElementSubclass for SetFreq {
+    type Variant = schedule::SetFreq;
+}
+
+#[pymethods]
+impl SetFreq {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        frequency,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        frequency: ParamArg<Frequency>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let frequency = frequency.value_or("frequency", Frequency::ZERO, &mut params);
+        let variant = schedule::SetFreq::new(channel_id, frequency)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id()
+    }
+
+    #[getter]
+    fn frequency(slf: &Bound<Self>) -> Frequency {
+        Self::variant(slf).frequency()
+    }
+}
+
+/// A phase ramp element.
+///
+/// The channel phase offset :math:`\phi_c` increases continuously at `rate`
+/// over the time span the element is scheduled in, i.e. by `rate` times the
+/// span in total. Unlike :class:`ShiftFreq`, the channel frequency shift
+/// :math:`\Delta f` is not changed, which is useful to emulate a detuning
+/// during idle periods. Pulses starting during the span get the phase reached
+/// at their start time.
+///
+/// The element is measured as zero like the other instruction elements, so
+/// the span is usually given by `duration`.
+///
+/// Args:
+///     channel_id (str): Target channel ID.
+///     rate (float): Rate of the phase in **cycles** per second.
+/// Example:
+///     .. code-block:: python
+///
+///         idle = PhaseRamp("xy", 1e6, duration=200e-9)
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct PhaseRamp;
+
+impl ElementSubclass for PhaseRamp {
+    type Variant = schedule::PhaseRamp;
+}
+
+#[pymethods]
+impl PhaseRamp {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        rate,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        rate: ParamArg<Frequency>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let rate = rate.value_or("rate", Frequency::ZERO, &mut params);
+        let variant = schedule::PhaseRamp::new(channel_id, rate)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id()
+    }
+
+    #[getter]
+    fn rate(slf: &Bound<Self>) -> Frequency {
+        Self::variant(slf).rate()
+    }
+}
+
+/// A phase swap element.
+///
+/// Different from :class:`SetPhase` and :class:`SetFreq`, both the channel
+/// base frequency :math:`f_0` and the channel frequency shift :math:`\Delta f`
+/// will be considered. At the scheduled time point, the phase to be swapped
+/// is calculated as
+///
+/// .. math:: \phi(t) = (f_0 + \Delta f) t + \phi_c
+///
+/// Args:
+///     channel_id1 (str): Target channel ID 1.
+///     channel_id2 (str): Target channel ID 2.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct SwapPhase;
+
+impl ElementSubclass for SwapPhase {
+    type Variant = schedule::SwapPhase;
+}
+
+#[pymethods]
+impl SwapPhase {
+    #[new]
+    #[pyo3(signature = (
+        channel_id1,
+        channel_id2,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id1: ChannelId,
+        channel_id2: ChannelId,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let variant = schedule::SwapPhase::new(channel_id1, channel_id2);
+        Ok((
+            Self,
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    #[getter]
+    fn channel_id1<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id1()
+    }
+
+    #[getter]
+    fn channel_id2<'a>(slf: &'a Bound<Self>) -> &'a ChannelId {
+        Self::variant(slf).channel_id2()
+    }
+}
+
+/// A barrier element.
+///
+/// A barrier element is a no-op element. Useful for aligning elements on
+/// different channels and adding space between elements in a :class:`Stack`
+/// layout.
+///
+/// If no channel IDs are provided, the layout system will arrange the barrier
+/// element as if it occupies all channels in its parent. Such a barrier in a
+/// :class:`Grid` marks a synchronization point spanning its columns, which is
+/// drawn as vertical lines by :func:`export_html`.
+///
+/// Args:
+///     *channel_ids (str): Channel IDs. Defaults to empty.
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct Barrier;
+
+impl ElementSubclass for Barrier {
+    type Variant = schedule::Barrier;
+}
+
+#[pymethods]
+impl Barrier {
+    #[new]
+    #[pyo3(signature = (
+        *channel_ids,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_ids: Vec<ChannelId>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let variant = schedule::Barrier::new(channel_ids);
+        Ok((
+            Self,
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    #[getter]
+    fn channel_ids(slf: &Bound<Self>) -> Vec<ChannelId> {
+        Self::variant(slf).channel_ids().to_vec()
+    }
+}
+
+/// A delay element.
+///
+/// A delay element occupies the channels for `delay` seconds without emitting
+/// any pulse. It is arranged in :class:`Stack` and :class:`Grid` layouts like
+/// a :class:`Play` of the same length, which is clearer than a zero-amplitude
+/// play and doesn't add to the pulse lists.
+///
+/// If no channel IDs are provided, the layout system will arrange the delay
+/// element as if it occupies all channels in its parent.
+///
+/// Args:
+///     delay (float): Idle time in seconds.
+///     *channel_ids (str): Channel IDs. Defaults to empty.
+/// Example:
+///     .. code-block:: python
+///
+///         schedule = Stack(x90, Delay(100e-9, "xy"), x90, direction="forward")
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct Delay;
+
+impl ElementSubclass for Delay {
+    type Variant = schedule::Delay;
+}
+
+#[pymethods]
+impl Delay {
+    #[new]
+    #[pyo3(signature = (
+        delay,
+        *channel_ids,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        delay: ParamArg<Time>,
+        channel_ids: Vec<ChannelId>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let delay = delay.value_or("delay", Time::ZERO, &mut params);
+        let variant = schedule::Delay::new(channel_ids, delay)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn delay(slf: &Bound<Self>) -> Time {
+        Self::variant(slf).delay()
+    }
+
+    #[getter]
+    fn channel_ids(slf: &Bound<Self>) -> Vec<ChannelId> {
+        Self::variant(slf).channel_ids().to_vec()
+    }
+}
+
+/// A digital marker element.
+///
+/// The marker of a :class:`DigitalChannel` is high over the time range the
+/// element is arranged in, e.g. to trigger a scope or gate an acquisition
+/// together with the analog pulses. The element is measured as `width`, so a
+/// marker with ``alignment="stretch"`` covers the whole range given by its
+/// parent, like the duration of a readout pulse in a :class:`Grid` cell.
+///
+/// Args:
+///     channel_id (str): Name of the digital channel.
+///     width (float): Time the marker is high. Defaults to 0.
+/// Example:
+///     .. code-block:: python
+///
+///         readout = Overlay(
+///             Play("ro", "rect", 0.2, 0, plateau=1e-6),
+///             Marker("adc_gate", alignment="stretch"),
+///         )
+#[pyclass(extends=Element, frozen)]
+#[derive(Debug, Clone)]
+struct Marker;
+
+impl ElementSubclass for Marker {
+    type Variant = schedule::Marker;
+}
+
+#[pymethods]
+impl Marker {
+    #[new]
+    #[pyo3(signature = (
+        channel_id,
+        width=ParamArg::Value(Time::ZERO),
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        channel_id: ChannelId,
+        width: ParamArg<Time>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let width = width.value_or("width", Time::ZERO, &mut params);
+        let variant = schedule::Marker::new(channel_id, width)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn channel_id(slf: &Bound<Self>) -> ChannelId {
+        Self::variant(slf).channel_id().clone()
+    }
+
+    #[getter]
+    fn width(slf: &Bound<Self>) -> Time {
+        Self::variant(slf).width()
+    }
+}
+
+/// A repeat element.
+///
+/// Repeat the child element multiple times with a spacing between repetitions.
+///
+/// Args:
+///     child (Element): Child element to repeat.
+///     count (int): Number of repetitions.
+///     spacing (float): Spacing between repetitions. Defaults to 0.
+#[pyclass(extends=Element, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Repeat {
+    child: Py<Element>,
+}
+
+impl ElementSubclass for Repeat {
+    type Variant = schedule::Repeat;
+}
+
+#[pymethods]
+impl Repeat {
+    #[new]
+    #[pyo3(signature = (
+        child,
+        count,
+        spacing=ParamArg::Value(Time::ZERO),
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        child: Py<Element>,
+        count: usize,
+        spacing: ParamArg<Time>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let mut params = vec![];
+        let spacing = spacing.value_or("spacing", Time::ZERO, &mut params);
+        let rust_child = child.get().0.clone();
+        let variant = schedule::Repeat::new(rust_child, count).with_spacing(spacing)?;
+        let element = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Ok((Self { child }, with_params(element, params)?))
+    }
+
+    #[getter]
+    fn count(slf: &Bound<Self>) -> usize {
+        Self::variant(slf).count()
+    }
+
+    #[getter]
+    fn spacing(slf: &Bound<Self>) -> Time {
+        Self::variant(slf).spacing()
+    }
+}
+
+/// A time scaling element.
+///
+/// All durations in the child subtree, including widths, plateaus, margins,
+/// spacings and duration constraints, are multiplied by `factor`. Amplitudes
+/// and frequencies are unchanged. This is useful to stretch a block without
+/// rebuilding the children.
+///
+/// Args:
+///     child (Element): Child element to scale.
+///     factor (float): Time scaling factor. Must be positive.
+#[pyclass(extends=Element, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Scale {
+    child: Py<Element>,
+}
+
+impl ElementSubclass for Scale {
+    type Variant = schedule::Scale;
+}
+
+#[pymethods]
+impl Scale {
+    #[new]
+    #[pyo3(signature = (
+        child,
+        factor,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        child: Py<Element>,
+        factor: f64,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let rust_child = child.get().0.clone();
+        let variant = schedule::Scale::new(rust_child, factor)?;
+        Ok((
+            Self { child },
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    #[getter]
+    fn factor(slf: &Bound<Self>) -> f64 {
+        Self::variant(slf).factor()
+    }
+}
+
+/// A time reversal element.
+///
+/// The child subtree is played back to front in time, e.g. a sequence of
+/// pulses is played in the opposite order with mirrored spacings. Frame
+/// changes in the child are applied in the reversed order. The envelopes of
+/// the pulses are not reversed, and a pulse shorter than its element is moved
+/// to the other end of the element unless it is centered.
+///
+/// Args:
+///     child (Element): Child element to reverse.
+#[pyclass(extends=Element, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Reverse {
+    child: Py<Element>,
+}
+
+impl ElementSubclass for Reverse {
+    type Variant = schedule::Reverse;
+}
+
+#[pymethods]
+impl Reverse {
+    #[new]
+    #[pyo3(signature = (
+        child,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        child: Py<Element>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let rust_child = child.get().0.clone();
+        let variant = schedule::Reverse::new(rust_child);
+        Ok((
+            Self { child },
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+}
+
+#[pymethods]
+impl Direction {
+    /// Convert the value to Direction.
+    ///
+    /// The value can be:
+    ///
+    /// - :class:`Direction`
+    /// - str: 'backward' or 'forward'
+    ///
+    /// Args:
+    ///     obj (str | Direction): Value to convert.
+    /// Returns:
+    ///     Direction: Converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(s) = obj.extract() {
+            let direction = match s {
+                "backward" => Some(Direction::Backward),
+                "forward" => Some(Direction::Forward),
+                _ => None,
+            };
+            if let Some(direction) = direction {
+                return Py::new(obj.py(), direction);
+            }
+        }
+        let msg = concat!(
+            "Failed to convert the value to Direction. ",
+            "Must be Direction or one of 'backward', 'forward'"
+        );
+        Err(PyValueError::new_err(msg))
+    }
+
+    fn __str__(&self) -> &'static str {
+        match self {
+            Direction::Backward => "backward",
+            Direction::Forward => "forward",
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        *self as u64
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<EnumReduce<'py>> {
+        reduce_enum(slf.as_any(), format!("{:?}", slf.get()))
+    }
+}
+
+fn extract_direction(obj: &Bound<PyAny>) -> PyResult<Direction> {
+    Direction::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+/// A stack layout element.
+///
+/// Each child element occupies some channels and has a duration. Stack layout
+/// will put children as close as possible without changing the order of
+/// children with common channels. Two layout orders are available:
+/// :attr:`Direction.Backward` and :attr:`Direction.Forward`. The default order
+/// is :attr:`Direction.Backward`.
+///
+/// With `auto_barrier`, all channels of the stack are synchronized between
+/// consecutive children as if a :class:`Barrier` without channels were
+/// inserted, so the children are played one after another like the moments of
+/// a circuit.
+///
+/// `spacing` keeps a gap between consecutive children with a duration on the
+/// same channel, instead of separating them with margins or delays.
+///
+/// The children are packed in the layout order regardless of their
+/// :attr:`Element.alignment`. With `align_children`, each child is placed in
+/// the time left free on its channels by the neighbouring children according
+/// to its alignment, e.g. a short pulse can be centered against a long pulse
+/// on another channel. The default alignment 'end' of the children moves them
+/// towards the end of a forward stack, so the option is off by default.
+///
+/// Args:
+///     *children (Element): Child elements.
+///     direction (str | Direction): Layout order. Defaults to 'backward'.
+///     auto_barrier (bool): Synchronize all channels between children.
+///         Defaults to ``False``.
+///     spacing (float): Gap between consecutive children on the same
+///         channel. Defaults to 0.
+///     align_children (bool): Place the children according to their
+///         alignment. Defaults to ``False``.
+#[pyclass(extends=Element, frozen)]
+struct Stack {
+    /// Python objects of the children. Stacks created by
+    /// :meth:`Stack.from_plays` create them on first access.
+    children: GILOnceCell<Vec<Py<Element>>>,
+}
+
+impl ElementSubclass for Stack {
+    type Variant = schedule::Stack;
+}
+
+#[pymethods]
+impl Stack {
+    #[new]
+    #[pyo3(signature = (
+        *children,
+        direction=None,
+        auto_barrier=false,
+        spacing=Time::ZERO,
+        align_children=false,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        children: Vec<Py<Element>>,
+        direction: Option<&Bound<PyAny>>,
+        auto_barrier: bool,
+        spacing: Time,
+        align_children: bool,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let rust_children = children.iter().map(|x| x.get().0.clone()).collect();
+        let variant = schedule::Stack::new()
+            .with_auto_barrier(auto_barrier)
+            .with_spacing(spacing)
+            .with_align_children(align_children)
+            .with_children(rust_children);
+        let variant = if let Some(obj) = direction {
+            variant.with_direction(extract_direction(obj)?)
+        } else {
+            variant
+        };
+        Ok((
+            Self::with_py_children(py, children),
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    /// Create a stack of play elements from arrays of parameters.
+    ///
+    /// This is equivalent to
+    ///
+    /// .. code-block:: python
+    ///
+    ///     Stack(*(
+    ///         Play(c, s, a, w, plateau=p, drag_coef=d, frequency=f, phase=ph)
+    ///         for c, s, a, w, p, d, f, ph in zip(
+    ///             channel_ids, shape_ids, amplitudes, widths,
+    ///             plateaus, drag_coefs, frequencies, phases,
+    ///         )
+    ///     ), **kwargs)
+    ///
+    /// but the play elements are created in bulk without the per-element
+    /// overhead of Python calls, which is useful for schedules with a large
+    /// number of pulses. The Python objects of the children are only created
+    /// when :attr:`children` is accessed.
+    ///
+    /// All sequences must have the same length.
+    ///
+    /// Args:
+    ///     channel_ids (Sequence[str]): Target channel IDs.
+    ///     shape_ids (Sequence[str | None]): Shape IDs of the pulses.
+    ///     amplitudes (array_like): Amplitudes of the pulses.
+    ///     widths (array_like): Widths of the pulses.
+    ///     plateaus (array_like | None): Plateau lengths of the pulses.
+    ///         Defaults to 0.
+    ///     drag_coefs (array_like | None): Drag coefficients of the pulses.
+    ///         Defaults to 0.
+    ///     frequencies (array_like | None): Additional frequencies of the
+    ///         pulses. Defaults to 0.
+    ///     phases (array_like | None): Additional phases of the pulses in
+    ///         **cycles**. Defaults to 0.
+    ///     direction (str | Direction): Layout order. Defaults to 'backward'.
+    ///     auto_barrier (bool): Synchronize all channels between children.
+    ///         Defaults to ``False``.
+    ///     spacing (float): Gap between consecutive pulses on the same
+    ///         channel. Defaults to 0.
+    ///     align_children (bool): Place the pulses according to their
+    ///         alignment. Defaults to ``False``.
+    /// Returns:
+    ///     Stack: New stack layout.
+    /// Raises:
+    ///     ValueError: If the lengths do not match or a parameter is invalid.
+    #[classmethod]
+    #[pyo3(signature = (
+        channel_ids,
+        shape_ids,
+        amplitudes,
+        widths,
+        *,
+        plateaus=None,
+        drag_coefs=None,
+        frequencies=None,
+        phases=None,
+        direction=None,
+        auto_barrier=false,
+        spacing=Time::ZERO,
+        align_children=false,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_plays(
+        cls: &Bound<PyType>,
+        channel_ids: Vec<ChannelId>,
+        shape_ids: Vec<Option<ShapeId>>,
+        amplitudes: PyArrayLike1<f64, AllowTypeChange>,
+        widths: PyArrayLike1<f64, AllowTypeChange>,
+        plateaus: Option<PyArrayLike1<f64, AllowTypeChange>>,
+        drag_coefs: Option<PyArrayLike1<f64, AllowTypeChange>>,
+        frequencies: Option<PyArrayLike1<f64, AllowTypeChange>>,
+        phases: Option<PyArrayLike1<f64, AllowTypeChange>>,
+        direction: Option<&Bound<PyAny>>,
+        auto_barrier: bool,
+        spacing: Time,
+        align_children: bool,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<Py<Self>> {
+        let py = cls.py();
+        let n = channel_ids.len();
+        let amplitudes = amplitudes.as_array();
+        let widths = widths.as_array();
+        let plateaus = plateaus.as_ref().map(|x| x.as_array());
+        let drag_coefs = drag_coefs.as_ref().map(|x| x.as_array());
+        let frequencies = frequencies.as_ref().map(|x| x.as_array());
+        let phases = phases.as_ref().map(|x| x.as_array());
+        let lengths = [
+            ("shape_ids", Some(shape_ids.len())),
+            ("amplitudes", Some(amplitudes.len())),
+            ("widths", Some(widths.len())),
+            ("plateaus", plateaus.as_ref().map(|x| x.len())),
+            ("drag_coefs", drag_coefs.as_ref().map(|x| x.len())),
+            ("frequencies", frequencies.as_ref().map(|x| x.len())),
+            ("phases", phases.as_ref().map(|x| x.len())),
+        ];
+        for (name, len) in lengths {
+            if let Some(len) = len.filter(|&len| len != n) {
+                return Err(PyValueError::new_err(format!(
+                    "Length of {} ({}) does not match length of channel_ids ({})",
+                    name, len, n
+                )));
+            }
+        }
+        let children = py
+            .allow_threads(|| {
+                let value_at =
+                    |x: &Option<ArrayView1<f64>>, i: usize| x.as_ref().map_or(0.0, |x| x[i]);
+                (0..n)
+                    .map(|i| {
+                        let variant = schedule::Play::new(
+                            channel_ids[i].clone(),
+                            shape_ids[i].clone(),
+                            Amplitude::new(amplitudes[i])?,
+                            Time::new(widths[i])?,
+                        )?
+                        .with_plateau(Time::new(value_at(&plateaus, i))?)?
+                        .with_drag_coef(value_at(&drag_coefs, i))?
+                        .with_frequency(Frequency::new(value_at(&frequencies, i))?)?
+                        .with_phase(Phase::from_py_value(value_at(&phases, i))?)?;
+                        let common = ElementCommonBuilder::new().build()?;
+                        Ok(Arc::new(schedule::Element::new(common, variant)))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+            })
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let variant = schedule::Stack::new()
+            .with_auto_barrier(auto_barrier)
+            .with_spacing(spacing)
+            .with_align_children(align_children)
+            .with_children(children);
+        let variant = if let Some(obj) = direction {
+            variant.with_direction(extract_direction(obj)?)
+        } else {
+            variant
+        };
+        Py::new(
+            py,
+            (
+                Self {
+                    children: GILOnceCell::new(),
+                },
+                Self::build_element(
+                    variant,
+                    margin,
+                    alignment,
+                    phantom,
+                    duration,
+                    duration_of,
+                    max_duration,
+                    min_duration,
+                    label,
+                )?,
+            ),
+        )
+    }
+
+    /// Create a new stack layout with different children.
+    ///
+    /// Using this method may be more readable than specifying children in the
+    /// constructor.
+    ///
+    /// .. code-block:: python
+    ///
+    ///     stack = Stack(direction='forward').with_children(
+    ///         element1,
+    ///         element2,
+    ///     )
+    ///
+    /// Args:
+    ///     *children (Element): New child elements.
+    /// Returns:
+    ///     Stack: New stack layout.
+    #[pyo3(signature=(*children))]
+    fn with_children(slf: &Bound<Self>, children: Vec<Py<Element>>) -> PyResult<Py<Self>> {
+        let py = slf.py();
+        let rust_children = children.iter().map(|x| x.get().0.clone()).collect();
+        let rust_base = &slf.downcast::<Element>()?.get().0;
+        let common = rust_base.common.clone();
+        let variant = Self::variant(slf).clone().with_children(rust_children);
+        Py::new(
+            py,
+            (
+                Self::with_py_children(py, children),
+                Element::new(Arc::new(schedule::Element::new(common, variant))),
+            ),
+        )
+    }
+
+    #[getter]
+    fn direction(slf: &Bound<Self>) -> Direction {
+        Self::variant(slf).direction()
+    }
+
+    #[getter]
+    fn auto_barrier(slf: &Bound<Self>) -> bool {
+        Self::variant(slf).auto_barrier()
+    }
+
+    #[getter]
+    fn spacing(slf: &Bound<Self>) -> Time {
+        Self::variant(slf).spacing()
+    }
+
+    #[getter]
+    fn align_children(slf: &Bound<Self>) -> bool {
+        Self::variant(slf).align_children()
+    }
+
+    #[getter]
+    fn children(slf: &Bound<Self>) -> PyResult<Vec<Py<Element>>> {
+        let py = slf.py();
+        let children = slf.get().children.get_or_try_init(py, || {
+            // Only stacks created by `from_plays` reach here.
+            Self::variant(slf)
+                .children()
+                .iter()
+                .map(|child| {
+                    let play = Py::new(py, (Play, Element::new(child.clone())))?;
+                    Ok(play.into_bound(py).into_any().downcast_into()?.unbind())
+                })
+                .collect::<PyResult<Vec<_>>>()
+        })?;
+        Ok(children.iter().map(|c| c.clone_ref(py)).collect())
+    }
+}
+
+impl Stack {
+    fn with_py_children(py: Python, children: Vec<Py<Element>>) -> Self {
+        let cell = GILOnceCell::new();
+        cell.set(py, children)
+            .expect("Cell should be empty after creation");
+        Self { children: cell }
+    }
+}
+
+/// A child element with an absolute time in a absolute layout.
+///
+/// The time of each child element is relative to the start of the absolute
+/// layout.
+///
+/// Args:
+///     time (float): Time relative to the start of the parent element.
+///     element (Element): Child element.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct AbsoluteEntry {
+    time: Time,
+    element: Py<Element>,
+}
+
+#[pymethods]
+impl AbsoluteEntry {
+    #[new]
+    fn new(time: Time, element: Py<Element>) -> PyResult<Self> {
+        if !time.value().is_finite() {
+            return Err(PyValueError::new_err("Time must be finite"));
+        }
+        Ok(AbsoluteEntry { time, element })
+    }
+
+    /// Convert the value to AbsoluteEntry.
+    ///
+    /// the value can be:
+    ///
+    /// - AbsoluteEntry
+    /// - Element
+    /// - tuple[float, Element]: Time and element.
+    ///
+    /// Args:
+    ///     obj (AbsoluteEntry | Element | tuple[float, Element]): Value to convert.
+    /// Returns:
+    ///     AbsoluteEntry: Converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        let py = obj.py();
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(element) = obj.extract() {
+            return Py::new(py, AbsoluteEntry::new(Time::ZERO, element)?);
+        }
+        if let Ok((time, element)) = obj.extract() {
+            return Py::new(py, AbsoluteEntry::new(time, element)?);
+        }
+        Err(PyValueError::new_err(
+            "Failed to convert the value to AbsoluteEntry",
+        ))
+    }
+}
+
+fn extract_absolute_entry(obj: &Bound<PyAny>) -> PyResult<AbsoluteEntry> {
+    AbsoluteEntry::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+/// An absolute layout element.
+///
+/// The child elements are arranged in absolute time. The time of each child
+/// element is relative to the start of the absolute schedule. The duration of
+/// the absolute schedule is the maximum end time of the child elements.
+///
+/// The `children` argument can be:
+///
+/// - AbsoluteEntry
+/// - Element
+/// - tuple[float, Element]: Time and element.
+///
+/// Args:
+///     *children (AbsoluteEntry | Element | tuple[float, Element]): Child elements.
+/// Example:
+///     .. code-block:: python
+///
+///         absolute = Absolute(
+///             element1,
+///             (1.0, element2),
+///             AbsoluteEntry(2.0, element3),
+///         )
+#[pyclass(extends=Element, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Absolute {
+    children: Vec<AbsoluteEntry>,
+}
+
+impl ElementSubclass for Absolute {
+    type Variant = schedule::Absolute;
+}
+
+#[pymethods]
+impl Absolute {
+    #[new]
+    #[pyo3(signature = (
+        *children,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        children: Vec<Py<PyAny>>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let children: Vec<AbsoluteEntry> = children
+            .into_iter()
+            .map(|x| extract_absolute_entry(&x.into_bound(py)))
+            .collect::<PyResult<_>>()?;
+        let rust_children = children
+            .iter()
+            .map(|x| {
+                let element = x.element.get().0.clone();
+                Ok(schedule::AbsoluteEntry::new(element).with_time(x.time)?)
+            })
+            .collect::<PyResult<_>>()?;
+        let variant = schedule::Absolute::new().with_children(rust_children);
+        Ok((
+            Self { children },
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    /// Create a new absolute schedule with different children.
+    ///
+    /// Using this method may be more readable than specifying children in the
+    /// constructor.
+    ///
+    /// .. code-block:: python
+    ///
+    ///     absolute = Absolute(duration=50e-6).with_children(
+    ///         element1,
+    ///         (100e-9, element2),
+    ///     )
+    ///
+    /// Args:
+    ///     *children (AbsoluteEntry | Element | tuple[float, Element]): New
+    ///         child elements.
+    /// Returns:
+    ///     Absolute: New absolute schedule.
+    #[pyo3(signature=(*children))]
+    fn with_children(slf: &Bound<Self>, children: Vec<Py<PyAny>>) -> PyResult<Py<Self>> {
+        let py = slf.py();
+        let children: Vec<_> = children
+            .into_iter()
+            .map(|x| extract_absolute_entry(&x.into_bound(py)))
+            .collect::<PyResult<_>>()?;
+        let rust_children = children
+            .iter()
+            .map(|x| {
+                let element = x.element.get().0.clone();
+                Ok(schedule::AbsoluteEntry::new(element).with_time(x.time)?)
+            })
+            .collect::<PyResult<_>>()?;
+        let rust_base = &slf.downcast::<Element>()?.get().0;
+        let common = rust_base.common.clone();
+        let variant = Self::variant(slf).clone().with_children(rust_children);
+        Py::new(
+            py,
+            (
+                Self { children },
+                Element::new(Arc::new(schedule::Element::new(common, variant))),
+            ),
+        )
+    }
+
+    /// Create a new absolute schedule from arrays of times and elements.
+    ///
+    /// This is faster than the constructor for tens of thousands of children
+    /// because the times are read from the array at once.
+    ///
+    /// .. code-block:: python
+    ///
+    ///     times = np.arange(10000) * 100e-9
+    ///     absolute = Absolute.from_arrays(times, [pulse] * 10000)
+    ///
+    /// Args:
+    ///     times (array_like): Times of the children, in non-decreasing order.
+    ///     elements (Sequence[Element]): Child elements.
+    ///     **kwargs: Same as the constructor.
+    /// Returns:
+    ///     Absolute: New absolute schedule.
+    /// Raises:
+    ///     ValueError: If the lengths differ, or the times are not finite or
+    ///         not sorted.
+    #[staticmethod]
+    #[pyo3(signature = (
+        times,
+        elements,
+        *,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_arrays(
+        py: Python,
+        times: PyArrayLike1<f64, AllowTypeChange>,
+        elements: Vec<Py<Element>>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<Py<Self>> {
+        let times = times.as_array();
+        if times.len() != elements.len() {
+            return Err(PyValueError::new_err(format!(
+                "Got {} times but {} elements.",
+                times.len(),
+                elements.len()
+            )));
+        }
+        let mut children = Vec::with_capacity(elements.len());
+        let mut rust_children = Vec::with_capacity(elements.len());
+        let mut last = f64::NEG_INFINITY;
+        for (i, (&time, element)) in times.iter().zip(elements).enumerate() {
+            if !time.is_finite() {
+                return Err(PyValueError::new_err(format!(
+                    "Time at index {} is not finite.",
+                    i
+                )));
+            }
+            if time < last {
+                return Err(PyValueError::new_err(format!(
+                    "Times should be sorted, but time at index {} is less than the previous one.",
+                    i
+                )));
+            }
+            last = time;
+            let time = Time::new(time)?;
+            let rust_element = element.get().0.clone();
+            rust_children.push(schedule::AbsoluteEntry::new(rust_element).with_time(time)?);
+            children.push(AbsoluteEntry { time, element });
+        }
+        let variant = schedule::Absolute::new().with_children(rust_children);
+        let base = Self::build_element(
+            variant,
+            margin,
+            alignment,
+            phantom,
+            duration,
+            duration_of,
+            max_duration,
+            min_duration,
+            label,
+        )?;
+        Py::new(py, (Self { children }, base))
+    }
+}
+
+/// An overlay layout element.
+///
+/// All child elements start at the start of the overlay, and the duration of
+/// the overlay is the maximum duration of the child elements. Children with
+/// alignment 'stretch' fill the whole overlay, e.g. when the overlay itself is
+/// stretched in a :class:`Grid`.
+///
+/// Args:
+///     *children (Element): Child elements.
+/// Example:
+///     .. code-block:: python
+///
+///         overlay = Overlay(
+///             Play("xy0", "hann", 0.5, 40e-9),
+///             Play("xy1", "hann", 0.5, 40e-9),
+///             Play("m0", "rect", 0.3, 2e-6),
+///         )
+#[pyclass(extends=Element, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Overlay {
+    children: Vec<Py<Element>>,
+}
+
+impl ElementSubclass for Overlay {
+    type Variant = schedule::Overlay;
+}
+
+#[pymethods]
+impl Overlay {
+    #[new]
+    #[pyo3(signature = (
+        *children,
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        children: Vec<Py<Element>>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let rust_children = children.iter().map(|x| x.get().0.clone()).collect();
+        let variant = schedule::Overlay::new().with_children(rust_children);
+        Ok((
+            Self { children },
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    /// Create a new overlay layout with different children.
+    ///
+    /// Args:
+    ///     *children (Element): New child elements.
+    /// Returns:
+    ///     Overlay: New overlay layout.
+    #[pyo3(signature=(*children))]
+    fn with_children(slf: &Bound<Self>, children: Vec<Py<Element>>) -> PyResult<Py<Self>> {
+        let py = slf.py();
+        let rust_children = children.iter().map(|x| x.get().0.clone()).collect();
+        let rust_base = &slf.downcast::<Element>()?.get().0;
+        let common = rust_base.common.clone();
+        let variant = Self::variant(slf).clone().with_children(rust_children);
+        Py::new(
+            py,
+            (
+                Self { children },
+                Element::new(Arc::new(schedule::Element::new(common, variant))),
+            ),
+        )
+    }
+}
+
+#[pymethods]
+impl GridLengthUnit {
+    /// Convert the value to GridLengthUnit.
+    ///
+    /// The value can be one of the following:
+    ///
+    /// - :class:`GridLengthUnit`
+    /// - "seconds"
+    /// - "auto"
+    /// - "star"
+    ///
+    /// Args:
+    ///     obj (str | GridLengthUnit): The value to convert.
+    /// Returns:
+    ///     GridLengthUnit: The converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted to GridLengthUnit.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(s) = obj.extract() {
+            let unit = match s {
+                "seconds" => Some(GridLengthUnit::Seconds),
+                "auto" => Some(GridLengthUnit::Auto),
+                "star" => Some(GridLengthUnit::Star),
+                _ => None,
+            };
+            if let Some(unit) = unit {
+                return Py::new(obj.py(), unit);
+            }
+        }
+        let msg = concat!(
+            "Failed to convert the value to GridLengthUnit. ",
+            "Must be GridLengthUnit or one of 'seconds', 'auto', 'star'"
+        );
+        Err(PyValueError::new_err(msg))
+    }
+
+    fn __str__(&self) -> &'static str {
+        match self {
+            GridLengthUnit::Seconds => "seconds",
+            GridLengthUnit::Auto => "auto",
+            GridLengthUnit::Star => "star",
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        *self as u64
+    }
+
+    fn __reduce__<'py>(slf: &Bound<'py, Self>) -> PyResult<EnumReduce<'py>> {
+        reduce_enum(slf.as_any(), format!("{:?}", slf.get()))
+    }
+}
+
+#[pymethods]
+impl GridLength {
+    /// Create an automatic grid length.
+    ///
+    /// Returns:
+    ///     GridLength: Automatic grid length.
+    #[staticmethod]
+    #[pyo3(name = "auto")]
+    fn py_auto() -> Self {
+        GridLength::auto()
+    }
+
+    /// Create a ratio based grid length.
+    ///
+    /// Args:
+    ///     value (float): Ratio of the remaining duration.
+    /// Returns:
+    ///     GridLength: Ratio based grid length.
+    #[staticmethod]
+    #[pyo3(name = "star")]
+    fn py_star(value: f64) -> PyResult<Self> {
+        GridLength::star(value).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Create a fixed grid length.
+    ///
+    /// Args:
+    ///     value (float): Fixed length in seconds.
+    /// Returns:
+    ///     GridLength: Fixed grid length.
+    #[staticmethod]
+    #[pyo3(name = "fixed")]
+    fn py_fixed(value: f64) -> PyResult<Self> {
+        GridLength::fixed(value).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Convert the value to GridLength.
+    ///
+    /// The value can be:
+    ///
+    /// - GridLength
+    /// - float: Fixed length in seconds.
+    /// - 'auto': Auto length.
+    /// - 'x*': x stars.
+    /// - 'x': Fixed length in seconds.
+    /// - '*': 1 star.
+    ///
+    /// Args:
+    ///     obj (GridLength | float | str): Value to convert.
+    /// Returns:
+    ///     GridLength: Converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        let py = obj.py();
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(v) = obj.extract() {
+            return Py::new(py, GridLength::py_fixed(v)?);
+        }
+        if let Ok(s) = obj.extract() {
+            return Py::new(py, GridLength::from_str(s)?);
+        }
+        Err(PyValueError::new_err(
+            "Failed to convert the value to GridLength.",
+        ))
+    }
+}
+
+fn extract_grid_length(obj: &Bound<PyAny>) -> PyResult<GridLength> {
+    GridLength::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+/// A child element in a grid layout.
+///
+/// Args:
+///     element (Element): Child element.
+///     column (int): Column index.
+///     span (int): Column span.
+///     row (int): Row index.
+///     row_span (int): Row span.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct GridEntry {
+    element: Py<Element>,
+    column: usize,
+    span: usize,
+    row: usize,
+    row_span: usize,
+}
+
+#[pymethods]
+impl GridEntry {
+    #[new]
+    #[pyo3(signature = (element, column=0, span=1, row=0, row_span=1))]
+    fn new(
+        element: Py<Element>,
+        column: usize,
+        span: usize,
+        row: usize,
+        row_span: usize,
+    ) -> PyResult<Self> {
+        if span == 0 {
+            return Err(PyValueError::new_err("The span must be greater than 0."));
+        }
+        if row_span == 0 {
+            return Err(PyValueError::new_err(
+                "The row span must be greater than 0.",
+            ));
+        }
+        Ok(GridEntry {
+            element,
+            column,
+            span,
+            row,
+            row_span,
+        })
+    }
+
+    /// Convert the value to GridEntry.
+    ///
+    /// The value can be:
+    ///
+    /// - GridEntry
+    /// - Element
+    /// - tuple[Element, int]: Element and column.
+    /// - tuple[Element, int, int]: Element, column, and span.
+    ///
+    /// Args:
+    ///     obj (GridEntry | Element | tuple[Element, int] | tuple[Element, int, int]): Value to convert.
+    /// Returns:
+    ///     GridEntry: Converted value.
+    /// Raises:
+    ///     ValueError: If the value cannot be converted.
+    #[staticmethod]
+    fn convert(obj: &Bound<PyAny>) -> PyResult<Py<Self>> {
+        let py = obj.py();
+        if let Ok(slf) = obj.extract() {
+            return Ok(slf);
+        }
+        if let Ok(element) = obj.extract() {
+            return Py::new(py, GridEntry::new(element, 0, 1, 0, 1)?);
+        }
+        if let Ok((element, column)) = obj.extract() {
+            return Py::new(py, GridEntry::new(element, column, 1, 0, 1)?);
+        }
+        if let Ok((element, column, span)) = obj.extract() {
+            return Py::new(py, GridEntry::new(element, column, span, 0, 1)?);
+        }
+        Err(PyValueError::new_err(
+            "Failed to convert the value to GridEntry.",
+        ))
+    }
+}
+
+fn extract_grid_entry(obj: &Bound<PyAny>) -> PyResult<GridEntry> {
+    GridEntry::convert(obj).and_then(|x| x.extract(obj.py()))
+}
+
+fn to_rust_grid_entry(entry: &GridEntry) -> schedule::GridEntry {
+    schedule::GridEntry::new(entry.element.get().0.clone())
+        .with_column(entry.column)
+        .with_span(entry.span)
+        .and_then(|e| e.with_row(entry.row).with_row_span(entry.row_span))
+        .expect("Should be checked in GridEntry::new")
+}
+
+fn check_grid_rows(grid: &schedule::Grid) -> PyResult<()> {
+    if let Some((child, channel)) = grid.find_row_violation() {
+        return Err(PyValueError::new_err(format!(
+            "Child {} uses channel {:?} outside of its rows.",
+            child, channel
+        )));
+    }
+    Ok(())
+}
+
+/// A grid layout element.
+///
+/// A grid layout has multiple columns and each child element occupies some
+/// columns. The width of each column can be specified by :class:`GridLength`,
+/// which can be:
+///
+/// - Fixed length in seconds.
+/// - Auto length:
+///     The width is determined by the child element.
+///
+/// - Star length:
+///     The width id determined by remaining duration. For example, if there
+///     are two columns with 1* and 2* and the remaining duration is 300 ns,
+///     the width of the columns will be 100 ns and 200 ns.
+///
+/// Columns length can be specified with a simplified syntax:
+///
+/// - 'auto': Auto length.
+/// - 'x*': x stars.
+/// - 'x': Fixed length in seconds.
+/// - '*': 1 star.
+///
+/// If no columns are provided, the grid layout will have one column with '*'.
+///
+/// Auto and star columns grow to fit the children including their
+/// `min_duration`, and a star column gives up its share of the extra time
+/// before it squeezes a child of another star column. A child spanning only
+/// fixed columns which are shorter than the child, or a child which doesn't
+/// fit before the end of a grid with less time than it needs, e.g. because of
+/// `max_duration`, makes the schedule functions raise :class:`ScheduleError`
+/// with code ``"overconstrained_grid"`` unless `allow_oversize` is ``True``.
+///
+/// The columns are shared by the rows of the grid, so children in different
+/// rows are aligned to the same columns. Each row is a lane of channels, e.g.
+/// the channels of a qubit, and a child spanning multiple rows like a
+/// two-qubit gate can use the channels of all of them. The channels of the
+/// rows are occupied by the grid even if no child uses them. A row without
+/// channels doesn't restrict its children. If no rows are provided, the grid
+/// has one row without channels.
+///
+/// Children can be provided as:
+///
+/// - GridEntry
+/// - Element: The column index is 0 and the span is 1.
+/// - tuple[Element, int]: Element and column. The span is 1.
+/// - tuple[Element, int, int]: Element, column, and span.
+///
+/// Args:
+///     *children (GridEntry | Element | tuple[Element, int] | tuple[Element, int, int]): Child elements.
+///     columns (Iterable[GridLength | float | str]): Column lengths. Defaults to ['*'].
+///     rows (Iterable[Iterable[str]]): Channels of the rows. Defaults to one
+///         row without channels.
+/// Raises:
+///     ValueError: If a child uses a channel outside of its rows.
+/// Example:
+///     .. code-block:: python
+///
+///         grid = Grid(
+///             GridEntry(element1, 0, 1),
+///             (element2, 1),
+///             (element3, 2, 2),
+///             element4,
+///             columns=['auto', '1*', '2'],
+///         )
+///
+///         cz = Grid(
+///             GridEntry(x_q0, 0, row=0),
+///             GridEntry(x_q1, 0, row=1),
+///             GridEntry(cz_q0_q1, 1, row=0, row_span=2),
+///             columns=['auto', 'auto'],
+///             rows=[['q0'], ['q1', 'c01']],
+///         )
+#[pyclass(extends=Element, get_all, frozen)]
+#[derive(Debug, Clone)]
+struct Grid {
+    children: Vec<GridEntry>,
+}
+
+impl ElementSubclass for Grid {
+    type Variant = schedule::Grid;
+}
+
+#[pymethods]
+impl Grid {
+    #[new]
+    #[pyo3(signature = (
+        *children,
+        columns=vec![],
+        rows=vec![],
+        margin=None,
+        alignment=None,
+        phantom=None,
+        duration=None,
+        duration_of=None,
+        max_duration=Time::INFINITY,
+        min_duration=Time::ZERO,
+        label=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        children: Vec<Py<PyAny>>,
+        columns: Vec<Py<PyAny>>,
+        rows: Vec<Vec<ChannelId>>,
+        margin: Option<&Bound<PyAny>>,
+        alignment: Option<&Bound<PyAny>>,
+        phantom: Option<&Bound<PyAny>>,
+        duration: Option<Time>,
+        duration_of: Option<&Bound<PyAny>>,
+        max_duration: Time,
+        min_duration: Time,
+        label: Option<Label>,
+    ) -> PyResult<(Self, Element)> {
+        let children: Vec<_> = children
+            .into_iter()
+            .map(|x| extract_grid_entry(&x.into_bound(py)))
+            .collect::<PyResult<_>>()?;
+        let columns: Vec<_> = columns
+            .into_iter()
+            .map(|x| extract_grid_length(&x.into_bound(py)))
+            .collect::<PyResult<_>>()?;
+        let rust_children = children.iter().map(to_rust_grid_entry).collect();
+        let variant = schedule::Grid::new()
+            .with_children(rust_children)
+            .with_columns(columns)
+            .with_rows(rows);
+        check_grid_rows(&variant)?;
+        Ok((
+            Self { children },
+            Self::build_element(
+                variant,
+                margin,
+                alignment,
+                phantom,
+                duration,
+                duration_of,
+                max_duration,
+                min_duration,
+                label,
+            )?,
+        ))
+    }
+
+    /// Create a new grid schedule with different children.
+    ///
+    /// Using this method may be more readable than specifying children in the
+    /// constructor.
+    ///
+    /// .. code-block:: python
+    ///
+    ///     grid = Grid(columns=['auto', '*', 'auto']).with_children(
+    ///         element1,
+    ///         (element2, 2),
+    ///         (element3, 0, 3),
+    ///     )
+    ///
+    /// Args:
+    ///     *children (GridEntry | Element | tuple[Element, int] | tuple[Element, int, int]): New child elements.
+    /// Returns:
+    ///     Grid: New grid schedule.
+    #[pyo3(signature=(*children))]
+    fn with_children(slf: &Bound<Self>, children: Vec<Py<PyAny>>) -> PyResult<Py<Self>> {
+        let py = slf.py();
+        let children: Vec<_> = children
+            .into_iter()
+            .map(|x| extract_grid_entry(&x.into_bound(py)))
+            .collect::<PyResult<_>>()?;
+        let rust_children = children.iter().map(to_rust_grid_entry).collect();
+        let rust_base = &slf.downcast::<Element>()?.get().0;
+        let common = rust_base.common.clone();
+        let variant = Self::variant(slf).clone().with_children(rust_children);
+        check_grid_rows(&variant)?;
+        Py::new(
+            py,
+            (
+                Self { children },
+                Element::new(Arc::new(schedule::Element::new(common, variant))),
+            ),
+        )
+    }
+
+    #[getter]
+    fn columns(slf: &Bound<Self>) -> Vec<GridLength> {
+        Self::variant(slf).columns().to_vec()
+    }
+
+    #[getter]
+    fn rows(slf: &Bound<Self>) -> Vec<Vec<ChannelId>> {
+        Self::variant(slf).rows().to_vec()
+    }
+}
+
+/// Fill in the :class:`Param` placeholders of a schedule.
+///
+/// Subtrees without placeholders are shared with the template, so a large
+/// template can be built once and bound for every point of a scan.
+///
+/// .. code-block:: python
+///
+///     template = Stack(Play("xy", "hann", Param("amp"), 100e-9), measure)
+///     schedule = bind(template, {"amp": 0.42})
+///
+/// Args:
+///     schedule (Element): Root element of the template.
+///     params (Mapping[str, float]): Values of the parameters by their names.
+///         Phases are in the unit set by :func:`set_phase_unit`. Unused values
+///         are ignored.
+/// Returns:
+///     Element: Schedule without placeholders.
+/// Raises:
+///     ValueError: If a parameter has no value or a value is invalid for its
+///         field.
+#[pyfunction]
+fn bind(
+    py: Python,
+    schedule: &Bound<Element>,
+    mut params: HashMap<String, f64>,
+) -> PyResult<Py<Element>> {
+    phase_params_to_cycles(&schedule.get().0, &mut params)?;
+    let root = bind_params(&schedule.get().0, &params)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut objects = HashMap::new();
+    collect_py_elements(schedule, &mut objects)?;
+    let mut memo = objects.into_iter().map(|(k, v)| (k, v.unbind())).collect();
+    to_py_element(py, &root, &mut memo)
+}
+
+/// Converts the values of the parameters bound to phases from the unit set by
+/// :func:`set_phase_unit` to cycles, which [`bind_params`] expects.
+fn phase_params_to_cycles(root: &ElementRef, params: &mut HashMap<String, f64>) -> PyResult<()> {
+    for name in phase_param_names(root) {
+        if let Some(value) = params.get_mut(&name) {
+            *value = Phase::from_py_value(*value)?.value();
+        }
+    }
+    Ok(())
+}
+
+/// Create a copy of the schedule with parameters of labeled :class:`Play`
+/// elements replaced.
+///
+/// Only the elements on the paths from the root to the patched elements are
+/// copied. All other subtrees are shared with the original schedule, so
+/// patching a large cached template is cheap.
+///
+/// .. code-block:: python
+///
+///     schedule = patch(template, {"x90_q0": {"amplitude": 0.42}})
+///
+/// Args:
+///     schedule (Element): Root element of the schedule.
+///     patches (Mapping[str, Mapping[str, float]]): New parameters of the
+///         :class:`Play` elements by their labels. Available parameters are
+///         'amplitude', 'width', 'plateau', 'drag_coef', 'frequency',
+///         'chirp_rate' and 'phase'.
+/// Returns:
+///     Element: Patched schedule.
+/// Raises:
+///     ValueError: If a parameter is unknown or invalid, or a label is not
+///         found on any :class:`Play` element.
+#[pyfunction]
+fn patch(
+    schedule: &Bound<Element>,
+    patches: HashMap<Label, HashMap<String, f64>>,
+) -> PyResult<Py<Element>> {
+    const FIELDS: [&str; 7] = [
+        "amplitude",
+        "width",
+        "plateau",
+        "drag_coef",
+        "frequency",
+        "chirp_rate",
+        "phase",
+    ];
+    if let Some(field) = patches
+        .values()
+        .flat_map(|p| p.keys())
+        .find(|k| !FIELDS.contains(&k.as_str()))
+    {
+        return Err(PyValueError::new_err(format!(
+            "Unknown Play parameter '{}'",
+            field
+        )));
+    }
+    let mut patcher = Patcher {
+        patches: &patches,
+        found: HashSet::new(),
+        memo: HashMap::new(),
+    };
+    let result = patcher.patch(schedule)?;
+    if let Some(label) = patches.keys().find(|l| !patcher.found.contains(l)) {
+        return Err(PyValueError::new_err(format!(
+            "Label '{}' not found on any Play element",
+            label
+        )));
+    }
+    Ok(result.unwrap_or_else(|| schedule.clone().unbind()))
+}
+
+struct Patcher<'a> {
+    patches: &'a HashMap<Label, HashMap<String, f64>>,
+    found: HashSet<&'a Label>,
+    memo: HashMap<*const schedule::Element, Option<Py<Element>>>,
+}
+
+impl<'a> Patcher<'a> {
+    /// Returns `None` if nothing in the subtree is patched.
+    fn patch(&mut self, obj: &Bound<Element>) -> PyResult<Option<Py<Element>>> {
+        let py = obj.py();
+        let key = Arc::as_ptr(&obj.get().0);
+        if let Some(result) = self.memo.get(&key) {
+            return Ok(result.as_ref().map(|x| x.clone_ref(py)));
+        }
+        let result = self.patch_uncached(obj)?;
+        self.memo
+            .insert(key, result.as_ref().map(|x| x.clone_ref(py)));
+        Ok(result)
+    }
+
+    fn patch_uncached(&mut self, obj: &Bound<Element>) -> PyResult<Option<Py<Element>>> {
+        let py = obj.py();
+        let element = &obj.get().0;
+        let common = element.common.clone();
+        if let Ok(play) = obj.downcast::<Play>() {
+            let Some((label, patch)) = common.label().and_then(|l| self.patches.get_key_value(l))
+            else {
+                return Ok(None);
+            };
+            self.found.insert(label);
+            let mut variant = Play::variant(play).clone();
+            for (field, &value) in patch {
+                variant = match field.as_str() {
+                    "amplitude" => variant.with_amplitude(Amplitude::new(value)?)?,
+                    "width" => variant.with_width(Time::new(value)?)?,
+                    "plateau" => variant.with_plateau(Time::new(value)?)?,
+                    "drag_coef" => variant.with_drag_coef(value)?,
+                    "frequency" => variant.with_frequency(Frequency::new(value)?)?,
+                    "chirp_rate" => variant.with_chirp_rate(ChirpRate::new(value)?)?,
+                    "phase" => variant.with_phase(Phase::from_py_value(value)?)?,
+                    _ => unreachable!("Fields should be validated"),
+                };
+            }
+            let element = Element::new(Arc::new(schedule::Element::new(common, variant)));
+            return into_element(py, Py::new(py, (Play, element))?).map(Some);
+        }
+        if let Ok(s) = obj.downcast::<Stack>() {
+            let children = Stack::children(s)?;
+            let Some(children) = self.patch_all(py, children.iter())? else {
+                return Ok(None);
+            };
+            return into_element(py, Stack::with_children(s, children)?).map(Some);
+        }
+        if let Ok(a) = obj.downcast::<Absolute>() {
+            let entries = &a.get().children;
+            let Some(children) = self.patch_all(py, entries.iter().map(|e| &e.element))? else {
+                return Ok(None);
+            };
+            let entries = entries
+                .iter()
+                .zip(children)
+                .map(|(e, element)| {
+                    let entry = AbsoluteEntry {
+                        time: e.time,
+                        element,
+                    };
+                    Ok(Py::new(py, entry)?.into_any())
+                })
+                .collect::<PyResult<_>>()?;
+            return into_element(py, Absolute::with_children(a, entries)?).map(Some);
+        }
+        if let Ok(o) = obj.downcast::<Overlay>() {
+            let Some(children) = self.patch_all(py, o.get().children.iter())? else {
+                return Ok(None);
+            };
+            return into_element(py, Overlay::with_children(o, children)?).map(Some);
+        }
+        if let Ok(g) = obj.downcast::<Grid>() {
+            let entries = &g.get().children;
+            let Some(children) = self.patch_all(py, entries.iter().map(|e| &e.element))? else {
+                return Ok(None);
+            };
+            let entries = entries
+                .iter()
+                .zip(children)
+                .map(|(e, element)| {
+                    let entry = GridEntry {
+                        element,
+                        column: e.column,
+                        span: e.span,
+                        row: e.row,
+                        row_span: e.row_span,
+                    };
+                    Ok(Py::new(py, entry)?.into_any())
+                })
+                .collect::<PyResult<_>>()?;
+            return into_element(py, Grid::with_children(g, entries)?).map(Some);
+        }
+        if let Ok(r) = obj.downcast::<Repeat>() {
+            let Some(child) = self.patch(r.get().child.bind(py))? else {
+                return Ok(None);
+            };
+            let variant = Repeat::variant(r);
+            let variant = schedule::Repeat::new(child.get().0.clone(), variant.count())
+                .with_spacing(variant.spacing())?;
+            let element = Element::new(Arc::new(schedule::Element::new(common, variant)));
+            return into_element(py, Py::new(py, (Repeat { child }, element))?).map(Some);
+        }
+        if let Ok(s) = obj.downcast::<Scale>() {
+            let Some(child) = self.patch(s.get().child.bind(py))? else {
+                return Ok(None);
+            };
+            let variant = schedule::Scale::new(child.get().0.clone(), Scale::variant(s).factor())?;
+            let element = Element::new(Arc::new(schedule::Element::new(common, variant)));
+            return into_element(py, Py::new(py, (Scale { child }, element))?).map(Some);
+        }
+        if let Ok(r) = obj.downcast::<Reverse>() {
+            let Some(child) = self.patch(r.get().child.bind(py))? else {
+                return Ok(None);
+            };
+            let variant = schedule::Reverse::new(child.get().0.clone());
+            let element = Element::new(Arc::new(schedule::Element::new(common, variant)));
+            return into_element(py, Py::new(py, (Reverse { child }, element))?).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Returns `None` if no child is patched.
+    fn patch_all<'b>(
+        &mut self,
+        py: Python,
+        children: impl Iterator<Item = &'b Py<Element>>,
+    ) -> PyResult<Option<Vec<Py<Element>>>> {
+        let mut changed = false;
+        let mut result = vec![];
+        for child in children {
+            match self.patch(child.bind(py))? {
+                Some(patched) => {
+                    changed = true;
+                    result.push(patched);
+                }
+                None => result.push(child.clone_ref(py)),
+            }
+        }
+        Ok(changed.then_some(result))
+    }
+}
+
+fn into_element<T>(py: Python, obj: Py<T>) -> PyResult<Py<Element>> {
+    Ok(obj.into_bound(py).into_any().downcast_into()?.unbind())
+}
+
+/// Creates the Python objects of a tree built in Rust. Shared subtrees share
+/// the Python objects.
+fn to_py_element(
+    py: Python,
+    element: &ElementRef,
+    memo: &mut HashMap<*const schedule::Element, Py<Element>>,
+) -> PyResult<Py<Element>> {
+    if let Some(obj) = memo.get(&Arc::as_ptr(element)) {
+        return Ok(obj.clone_ref(py));
+    }
+    let base = Element::new(element.clone());
+    let obj = match &element.variant {
+        schedule::ElementVariant::Play(_) => into_element(py, Py::new(py, (Play, base))?)?,
+        schedule::ElementVariant::ShiftPhase(_) => {
+            into_element(py, Py::new(py, (ShiftPhase, base))?)?
+        }
+        schedule::ElementVariant::SetPhase(_) => into_element(py, Py::new(py, (SetPhase, base))?)?,
+        schedule::ElementVariant::ShiftFreq(_) => {
+            into_element(py, Py::new(py, (ShiftFreq, base))?)?
+        }
+        schedule::ElementVariant::SetFreq(_) => into_element(py, Py::new(py, (SetFreq, base))?)?,
+        schedule::ElementVariant::PhaseRamp(_) => {
+            into_element(py, Py::new(py, (PhaseRamp, base))?)?
+        }
+        schedule::ElementVariant::SwapPhase(_) => {
+            into_element(py, Py::new(py, (SwapPhase, base))?)?
+        }
+        schedule::ElementVariant::Barrier(_) => into_element(py, Py::new(py, (Barrier, base))?)?,
+        schedule::ElementVariant::Delay(_) => into_element(py, Py::new(py, (Delay, base))?)?,
+        schedule::ElementVariant::Marker(_) => into_element(py, Py::new(py, (Marker, base))?)?,
+        schedule::ElementVariant::Repeat(r) => {
+            let child = to_py_element(py, r.child(), memo)?;
+            into_element(py, Py::new(py, (Repeat { child }, base))?)?
+        }
+        schedule::ElementVariant::Scale(c) => {
+            let child = to_py_element(py, c.child(), memo)?;
+            into_element(py, Py::new(py, (Scale { child }, base))?)?
+        }
+        schedule::ElementVariant::Reverse(r) => {
+            let child = to_py_element(py, r.child(), memo)?;
+            into_element(py, Py::new(py, (Reverse { child }, base))?)?
+        }
+        schedule::ElementVariant::Stack(s) => {
+            let children = s
+                .children()
+                .iter()
+                .map(|c| to_py_element(py, c, memo))
+                .collect::<PyResult<_>>()?;
+            into_element(
+                py,
+                Py::new(py, (Stack::with_py_children(py, children), base))?,
+            )?
+        }
+        schedule::ElementVariant::Absolute(a) => {
+            let children = a
+                .children()
+                .iter()
+                .map(|e| {
+                    Ok(AbsoluteEntry {
+                        time: e.time(),
+                        element: to_py_element(py, e.element(), memo)?,
+                    })
+                })
+                .collect::<PyResult<_>>()?;
+            into_element(py, Py::new(py, (Absolute { children }, base))?)?
+        }
+        schedule::ElementVariant::Overlay(o) => {
+            let children = o
+                .children()
+                .iter()
+                .map(|c| to_py_element(py, c, memo))
+                .collect::<PyResult<_>>()?;
+            into_element(py, Py::new(py, (Overlay { children }, base))?)?
+        }
+        schedule::ElementVariant::Grid(g) => {
+            let children = g
+                .children()
+                .iter()
+                .map(|e| {
+                    Ok(GridEntry {
+                        element: to_py_element(py, e.element(), memo)?,
+                        column: e.column(),
+                        span: e.span(),
+                        row: e.row(),
+                        row_span: e.row_span(),
+                    })
+                })
+                .collect::<PyResult<_>>()?;
+            into_element(py, Py::new(py, (Grid { children }, base))?)?
+        }
+    };
+    memo.insert(Arc::as_ptr(element), obj.clone_ref(py));
+    Ok(obj)
+}
+
+/// Generate waveforms from a schedule.
+///
+/// .. caution::
+///
+///     Crosstalk matrix will not be applied to offset of the channels.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets
+///         which are composed into the same channels in the given order.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix with corresponding channel ids. An optional third
+///         matrix is applied to the DRAG component instead, e.g. an identity
+///         matrix excludes DRAG from crosstalk. A 3D array of shape
+///         ``(n, n, taps)`` gives a FIR kernel for each pair instead of a
+///         constant, which can't be combined with a DRAG matrix. A sequence
+///         of ``(start_time, matrix)`` switches the matrix during the
+///         schedule, each matrix applies to the pulses starting from its
+///         start time. Default is ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. ``True`` enables all passes. Available
+///         passes are 'merge_shift_phase', 'remove_zero_amplitude',
+///         'collapse_stack' and 'fold_repeat'. The passes do not change the
+///         generated waveforms. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel, e.g. for calibration or unit
+///         conversion. The factors multiply with `amp_scales` and are applied
+///         before the crosstalk matrix. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. The layout is unchanged and the
+///         waveforms of these channels contain no pulses. Default is ``None``.
+///     aliases (Mapping[str, str] | None): Channel aliases mapping the names
+///         used in the schedule to the names in `channels` or
+///         `digital_channels`, e.g. ``{"q0.drive": "awg1.ch3"}``. An alias may
+///         refer to another alias, but it must not have the name of a channel,
+///         and every chain of aliases must end at a channel. The aliases are
+///         resolved before the layout, so the other arguments, e.g. `scales`
+///         and `crosstalk`, use the resolved names. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes, ``"f64"``
+///         or ``"f32"``. ``"f32"`` halves the memory of the envelope cache for
+///         long shapes, with an error of each sample of at most 6e-8 times
+///         the amplitude of the pulse including DRAG. Default is ``"f64"``.
+///     clip_limit (float | None): Largest absolute value of the samples after
+///         post-processing. Waveforms exceeding it raise
+///         :class:`ClippingError` with a report of the clipped samples
+///         instead of being returned. Default is ``None``, which doesn't
+///         check the waveforms.
+///     digital_channels (Mapping[str, DigitalChannel] | None): Information
+///         of the digital channels of the :class:`Marker` elements. Their
+///         names should differ from the outputs of `channels`. Default is
+///         ``None``.
+/// Returns:
+///     Dict[str, numpy.ndarray]: Waveforms of the channels. The key is the
+///         channel name and the value is the waveform. The shape of the
+///         waveform is ``(n, length)``, where ``n`` is 2 for complex waveform
+///         and 1 for real waveform. The negative output of a differential
+///         channel is under the name given by :attr:`Channel.differential`.
+///         The waveform of a digital channel is a ``uint8`` array of shape
+///         ``(length,)``.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid, the aliases are cyclic or refer to
+///         an unknown channel, or a :class:`Marker` is on an unknown digital
+///         channel.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulses cannot be sampled to the waveforms.
+///     ClippingError: If a waveform exceeds `clip_limit`.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Barrier, Channel, Hann, Play, Stack, generate_waveforms
+///         channels = {"xy": Channel(30e6, 2e9, 1000)}
+///         shapes = {"hann": Hann()}
+///         schedule = Stack(duration=500e-9).with_children(
+///             Play(
+///                 channel_id="xy",
+///                 shape_id="hann",
+///                 amplitude=0.3,
+///                 width=100e-9,
+///                 plateau=200e-9,
+///             ),
+///             Barrier(duration=10e-9),
+///         )
+///         result = generate_waveforms(channels, shapes, schedule)
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+    envelope_precision="f64",
+    clip_limit=None,
+    digital_channels=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_waveforms(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    envelope_precision: &str,
+    clip_limit: Option<f64>,
+    digital_channels: Option<HashMap<ChannelId, DigitalChannel>>,
+) -> PyResult<HashMap<ChannelId, PyObject>> {
+    let _span = tracing::info_span!("generate_waveforms").entered();
+    let digital_channels = digital_channels.unwrap_or_default();
+    if let Some(n) = channels
+        .iter()
+        .flat_map(|(n, c)| c.outputs(n))
+        .find(|n| digital_channels.contains_key(n))
+    {
+        return Err(PyValueError::new_err(format!(
+            "Digital channel '{}' has the same name as an analog output.",
+            n
+        )));
+    }
+    let root = extract_schedule(schedule)?;
+    let root = resolve_aliases_to(py, &root, aliases, |n| {
+        channels.contains_key(n) || digital_channels.contains_key(n)
+    })?;
+    let waveforms = sample_waveforms(
+        py,
+        channels,
+        shapes,
+        &root,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        crosstalk,
+        optimize,
+        amp_scales,
+        scales,
+        mute_channels,
+        envelope_precision,
+        clip_limit,
+    )?;
+    let markers: HashMap<_, _> = digital_channels
+        .iter()
+        .map(|(n, c)| {
+            (
+                n.clone(),
+                PyArray1::zeros_bound(py, c.length, false).unbind(),
+            )
+        })
+        .collect();
+    let mut views = markers
+        .iter()
+        // SAFETY: These arrays are just created.
+        .map(|(n, w)| (n.clone(), unsafe { w.bind(py).as_array_mut() }))
+        .collect();
+    let configs = digital_channels
+        .iter()
+        .map(|(n, c)| (n.clone(), c.config()))
+        .collect();
+    generate::sample_markers(&root, &configs, &mut views)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    drop(views);
+    Ok(waveforms
+        .into_iter()
+        .map(|(n, w)| (n, w.into_any()))
+        .chain(markers.into_iter().map(|(n, w)| (n, w.into_any())))
+        .collect())
+}
+
+/// Analog waveforms of :func:`generate_waveforms`, which are also used by
+/// other functions sampling a whole schedule.
+///
+/// The `schedule` is extracted and its channel aliases are resolved.
+#[allow(clippy::too_many_arguments)]
+fn sample_waveforms(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &ElementRef,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    envelope_precision: &str,
+    clip_limit: Option<f64>,
+) -> PyResult<HashMap<ChannelId, Py<PyArray2<f64>>>> {
+    ensure_numpy(py)?;
+    if clip_limit.is_some_and(|l| l.is_nan() || l < 0.0) {
+        return Err(PyValueError::new_err("clip_limit should not be negative"));
+    }
+    let envelope_precision = extract_envelope_precision(envelope_precision)?;
+    let schedule = optimize_schedule(py, schedule, optimize, amp_tolerance)?;
+    let executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    let pulse_lists = build_pulse_lists(py, &schedule, executor)?;
+    let waveforms: HashMap<_, _> = channels
+        .iter()
+        .flat_map(|(n, c)| {
+            let n_w = if c.is_real { 1 } else { 2 };
+            c.outputs(n).map(move |o| {
+                let w = PyArray2::zeros_bound(py, (n_w, c.length), false).unbind();
+                (o, w)
+            })
+        })
+        .collect();
+    let mut views = waveforms
+        .iter()
+        // SAFETY: These arrays are just created.
+        .map(|(n, w)| (n.clone(), unsafe { w.bind(py).as_array_mut() }))
+        .collect();
+    let arrays = channel_arrays(py, &channels);
+    sample_into(
+        py,
+        &schedule,
+        pulse_lists,
+        &channel_configs(&channels, &arrays, envelope_precision),
+        &mut views,
+        crosstalk.as_ref(),
+        time_tolerance,
+    )?;
+    drop(views);
+    if let Some(limit) = clip_limit {
+        check_clipping(py, &channels, &schedule, &waveforms, limit)?;
+    }
+    Ok(waveforms)
+}
+
+/// Generate waveforms from a schedule into preallocated arrays.
+///
+/// The waveforms are the same as those of :func:`generate_waveforms`, but they
+/// are written in place into the arrays in `buffers`, e.g. pinned or
+/// memory-mapped arrays reused across repetitions for zero-copy upload. The
+/// previous contents of the arrays are overwritten.
+///
+/// Args:
+///     buffers (Mapping[str, numpy.ndarray]): Writable ``float64`` arrays of
+///         each channel output with shape ``(n, length)`` as in the result of
+///         :func:`generate_waveforms`, including the negative outputs of
+///         differential channels. The arrays must not overlap.
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule as in :func:`generate_waveforms`.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix as in :func:`generate_waveforms`. Default is
+///         ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes as in
+///         :func:`generate_waveforms`. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+///     aliases (Mapping[str, str] | None): Channel aliases as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes as in
+///         :func:`generate_waveforms`. Default is ``"f64"``.
+/// Raises:
+///     ValueError: If some input is invalid, a buffer is missing, has the
+///         wrong shape or overlaps another buffer, or a buffer doesn't belong
+///         to any channel.
+///     TypeError: If some input has an invalid type, e.g. a buffer is not a
+///         ``float64`` array.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulses cannot be sampled to the waveforms.
+/// Example:
+///     .. code-block:: python
+///
+///         buffers = {"xy": np.zeros((2, 1000))}
+///         for schedule in schedules:
+///             generate_waveforms_into(buffers, channels, shapes, schedule)
+///             device.upload(buffers["xy"])
+#[pyfunction]
+#[pyo3(signature = (
+    buffers,
+    channels,
+    shapes,
+    schedule,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+    envelope_precision="f64",
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_waveforms_into(
+    py: Python,
+    buffers: HashMap<ChannelId, Bound<PyArray2<f64>>>,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    envelope_precision: &str,
+) -> PyResult<()> {
+    let _span = tracing::info_span!("generate_waveforms_into").entered();
+    let envelope_precision = extract_envelope_precision(envelope_precision)?;
+    let output_shapes: HashMap<_, _> = channels
+        .iter()
+        .flat_map(|(n, c)| {
+            let n_w = if c.is_real { 1 } else { 2 };
+            c.outputs(n).map(move |o| (o, [n_w, c.length]))
+        })
+        .collect();
+    if let Some(n) = buffers.keys().find(|n| !output_shapes.contains_key(*n)) {
+        return Err(PyValueError::new_err(format!(
+            "Buffer '{}' doesn't belong to any channel.",
+            n
+        )));
+    }
+    for (n, shape) in &output_shapes {
+        let buffer = buffers
+            .get(n)
+            .ok_or_else(|| PyValueError::new_err(format!("Buffer of '{}' is not given.", n)))?;
+        if buffer.shape() != shape {
+            return Err(PyValueError::new_err(format!(
+                "Buffer of '{}' should have shape {:?}, got {:?}",
+                n,
+                shape,
+                buffer.shape()
+            )));
+        }
+    }
+    let mut writable = buffers
+        .iter()
+        .map(|(n, w)| {
+            let w = w.try_readwrite().map_err(|_| {
+                PyValueError::new_err(format!(
+                    "Buffer of '{}' is not writable or overlaps another buffer.",
+                    n
+                ))
+            })?;
+            Ok((n.clone(), w))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    let pulse_lists = build_pulse_lists(py, &schedule, executor)?;
+    let mut views = writable
+        .iter_mut()
+        .map(|(n, w)| (n.clone(), w.as_array_mut()))
+        .collect();
+    let arrays = channel_arrays(py, &channels);
+    sample_into(
+        py,
+        &schedule,
+        pulse_lists,
+        &channel_configs(&channels, &arrays, envelope_precision),
+        &mut views,
+        crosstalk.as_ref(),
+        time_tolerance,
+    )?;
+    drop(views);
+    Ok(())
+}
+
+/// Generate waveforms from a schedule in chunks of samples.
+///
+/// The waveforms are the same as those of :func:`generate_waveforms`, but
+/// only one chunk of each channel is in memory at a time, so long waveforms
+/// can be streamed to the hardware. Padding and filters depend on the whole
+/// waveform and are not supported.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule as in :func:`generate_waveforms`.
+///     chunk_size (int): Number of samples of each chunk.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix as in :func:`generate_waveforms`. Default is
+///         ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes as in
+///         :func:`generate_waveforms`. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel, e.g. for calibration or unit
+///         conversion. The factors multiply with `amp_scales` and are applied
+///         before the crosstalk matrix. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes as in
+///         :func:`generate_waveforms`. Default is ``"f64"``.
+/// Returns:
+///     WaveformChunks: Iterator over the chunks.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid, or a channel has padding or
+///         filters.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+/// Example:
+///     .. code-block:: python
+///
+///         for start, chunk in generate_waveform_chunks(
+///             channels, shapes, schedule, chunk_size=1 << 20
+///         ):
+///             device.write(start, chunk["xy"])
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    chunk_size,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+    envelope_precision="f64",
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_waveform_chunks(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    chunk_size: usize,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    envelope_precision: &str,
+) -> PyResult<WaveformChunks> {
+    let _span = tracing::info_span!("generate_waveform_chunks").entered();
+    ensure_numpy(py)?;
+    let envelope_precision = extract_envelope_precision(envelope_precision)?;
+    if chunk_size == 0 {
+        return Err(PyValueError::new_err("chunk_size should be positive."));
+    }
+    let arrays = channel_arrays(py, &channels);
+    generate::check_chunked(&channel_configs(&channels, &arrays, envelope_precision))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    drop(arrays);
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    let pulse_lists = build_pulse_lists(py, &schedule, executor)?;
+    Ok(WaveformChunks {
+        channels,
+        schedule,
+        pulse_lists,
+        crosstalk,
+        chunk_size,
+        time_tolerance,
+        envelope_precision,
+        start: 0,
+    })
+}
+
+/// Start indices and samples of the non-zero runs of a channel.
+type SparseRuns = Vec<(usize, Py<PyArray2<f64>>)>;
+
+/// Generate waveforms as runs of non-zero samples.
+///
+/// .. note::
+///
+///     This function is experimental and may change in future releases.
+///
+/// Instead of dense arrays, each channel is returned as a list of
+/// ``(start, samples)`` tuples, where ``start`` is the index of the first
+/// sample of the run and ``samples`` is an array of shape ``(n, length)``.
+/// A sample is non-zero if any of the ``n`` rows is non-zero, and the samples
+/// between the runs are zero. For short pulses in long windows this takes
+/// orders of magnitude less memory, and the runs can be written directly to
+/// instruments supporting waveform placement by offset.
+///
+/// The waveforms are sampled chunk by chunk as in
+/// :func:`generate_waveform_chunks`, so padding and filters are not supported.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule as in :func:`generate_waveforms`.
+///     chunk_size (int): Number of samples sampled at a time, which bounds the
+///         memory of the dense buffers. Default is 65536.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix as in :func:`generate_waveforms`. Default is
+///         ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes as in
+///         :func:`generate_waveforms`. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes as in
+///         :func:`generate_waveforms`. Default is ``"f64"``.
+/// Returns:
+///     dict[str, list[tuple[int, numpy.ndarray]]]: Non-zero runs of each
+///     channel in the order of their start.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid, or a channel has padding or
+///         filters.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    chunk_size=1 << 16,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+    envelope_precision="f64",
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_sparse_waveforms(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    chunk_size: usize,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    envelope_precision: &str,
+) -> PyResult<HashMap<ChannelId, SparseRuns>> {
+    let _span = tracing::info_span!("generate_sparse_waveforms").entered();
+    let mut chunks = generate_waveform_chunks(
+        py,
+        channels,
+        shapes,
+        schedule,
+        chunk_size,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        crosstalk,
+        optimize,
+        amp_scales,
+        scales,
+        mute_channels,
+        aliases,
+        envelope_precision,
+    )?;
+    let mut sparse: HashMap<ChannelId, generate::SparseWaveform> = HashMap::new();
+    while let Some((start, waveforms)) = chunks.next_chunk(py)? {
+        for (n, w) in waveforms {
+            let w = w.bind(py).readonly();
+            sparse.entry(n).or_default().push_chunk(start, w.as_array());
+        }
+    }
+    Ok(sparse
+        .into_iter()
+        .map(|(n, w)| {
+            let runs = w
+                .into_runs()
+                .into_iter()
+                .map(|(start, run)| (start, run.into_pyarray_bound(py).unbind()))
+                .collect();
+            (n, runs)
+        })
+        .collect())
+}
+
+/// Iterator over the waveform chunks returned by
+/// :func:`generate_waveform_chunks`.
+///
+/// Each item is a tuple ``(start, waveforms)``, where ``start`` is the index of
+/// the first sample of the chunk and ``waveforms`` maps the channel names to
+/// arrays of shape ``(n, length)``. The last chunk of a channel may be
+/// shorter than ``chunk_size``, and channels without remaining samples are
+/// left out.
+#[pyclass(module = "bosing")]
+struct WaveformChunks {
+    channels: HashMap<ChannelId, Channel>,
+    schedule: ElementRef,
+    pulse_lists: HashMap<ChannelId, PulseList>,
+    crosstalk: Option<CrosstalkArg>,
+    chunk_size: usize,
+    time_tolerance: Time,
+    envelope_precision: EnvelopePrecision,
+    start: usize,
+}
+
+/// Start index and waveforms of a chunk.
+type WaveformChunk = (usize, HashMap<ChannelId, Py<PyArray2<f64>>>);
+
+#[pymethods]
+impl WaveformChunks {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> PyResult<Option<WaveformChunk>> {
+        let py = slf.py();
+        slf.next_chunk(py)
+    }
+}
+
+impl WaveformChunks {
+    fn next_chunk(&mut self, py: Python) -> PyResult<Option<WaveformChunk>> {
+        let start = self.start;
+        let waveforms: HashMap<_, _> = self
+            .channels
+            .iter()
+            .filter(|(_, c)| c.length > start)
+            .flat_map(|(n, c)| {
+                let n_w = if c.is_real { 1 } else { 2 };
+                let length = (c.length - start).min(self.chunk_size);
+                c.outputs(n).map(move |o| {
+                    let w = PyArray2::zeros_bound(py, (n_w, length), false).unbind();
+                    (o, w)
+                })
+            })
+            .collect();
+        if waveforms.is_empty() {
+            return Ok(None);
+        }
+        let _span = tracing::info_span!("waveform_chunk", start).entered();
+        let arrays = channel_arrays(py, &self.channels);
+        let configs = channel_configs(&self.channels, &arrays, self.envelope_precision);
+        let mut sampler = pulse::ChunkSampler::new(&self.pulse_lists);
+        for (n, c) in &self.channels {
+            let config = &configs[n];
+            sampler.add_channel(
+                n.clone(),
+                c.length,
+                config.sample_rate,
+                config.delay,
+                config.align_level,
+                config.overflow,
+            );
+            sampler.set_envelope_precision(n, config.envelope_precision);
+            if let Some(differential) = &config.differential {
+                let negative = differential.negative.clone();
+                sampler.add_channel(
+                    negative.clone(),
+                    c.length,
+                    config.sample_rate,
+                    config.delay + differential.skew,
+                    config.align_level,
+                    config.overflow,
+                );
+                sampler.set_envelope_precision(&negative, config.envelope_precision);
+                sampler.set_source(negative, n.clone());
+            }
+        }
+        if let Some(crosstalk) = &self.crosstalk {
+            sampler.set_crosstalk(crosstalk.to_crosstalk());
+        }
+        let mut views = waveforms
+            .iter()
+            // SAFETY: These arrays are just created.
+            .map(|(n, w)| (n.clone(), unsafe { w.bind(py).as_array_mut() }))
+            .collect();
+        let time_tolerance = self.time_tolerance;
+        let stats = py
+            .allow_threads(|| {
+                generate::sample_chunk_into(&sampler, &configs, &mut views, start, time_tolerance)
+            })
+            .map_err(|e| match e {
+                generate::Error::Sampling(e) => {
+                    sampling_error(py, e, &self.schedule, time_tolerance)
+                }
+                e => PyValueError::new_err(e.to_string()),
+            })?;
+        warn_overflow(py, &stats)?;
+        drop(views);
+        drop(sampler);
+        drop(configs);
+        drop(arrays);
+        self.start = start + self.chunk_size;
+        Ok(Some((start, waveforms)))
+    }
+}
+
+/// numpy is only imported when waveforms are generated, so that schedules can
+/// be built without numpy installed.
+fn ensure_numpy(py: Python) -> PyResult<()> {
+    py.import_bound("numpy").map(|_| ()).map_err(|e| {
+        let err = PyImportError::new_err("numpy is required for waveform generation.");
+        err.set_cause(py, Some(e));
+        err
+    })
+}
+
+/// Extract the root element of a schedule.
+///
+/// Multiple roots with time offsets are composed with an :class:`Absolute`
+/// layout. They are executed in the given order.
+/// Collects the Python objects of the element tree by the address of the
+/// underlying element.
+fn collect_py_elements<'py>(
+    obj: &Bound<'py, Element>,
+    objects: &mut HashMap<*const schedule::Element, Bound<'py, Element>>,
+) -> PyResult<()> {
+    if objects
+        .insert(Arc::as_ptr(&obj.get().0), obj.clone())
+        .is_some()
+    {
+        return Ok(());
+    }
+    let py = obj.py();
+    let children: Vec<Py<Element>> = if let Ok(s) = obj.downcast::<Stack>() {
+        Stack::children(s)?
+    } else if let Ok(a) = obj.downcast::<Absolute>() {
+        let entries = &a.get().children;
+        entries.iter().map(|e| e.element.clone_ref(py)).collect()
+    } else if let Ok(o) = obj.downcast::<Overlay>() {
+        o.get().children.iter().map(|c| c.clone_ref(py)).collect()
+    } else if let Ok(g) = obj.downcast::<Grid>() {
+        let entries = &g.get().children;
+        entries.iter().map(|e| e.element.clone_ref(py)).collect()
+    } else if let Ok(r) = obj.downcast::<Repeat>() {
+        vec![r.get().child.clone_ref(py)]
+    } else if let Ok(s) = obj.downcast::<Scale>() {
+        vec![s.get().child.clone_ref(py)]
+    } else if let Ok(r) = obj.downcast::<Reverse>() {
+        vec![r.get().child.clone_ref(py)]
+    } else {
+        vec![]
+    };
+    for child in children {
+        collect_py_elements(child.bind(py), objects)?;
+    }
+    Ok(())
+}
+
+/// Extracts the schedule root and resolves its duration references.
+fn extract_schedule(obj: &Bound<PyAny>) -> PyResult<ElementRef> {
+    let root = extract_schedule_root(obj)?;
+    let params = param_names(&root);
+    if !params.is_empty() {
+        return Err(PyValueError::new_err(format!(
+            "Schedule has unbound parameters: {}",
+            params.join(", ")
+        )));
+    }
+    resolve_duration_refs(&root).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn extract_schedule_root(obj: &Bound<PyAny>) -> PyResult<ElementRef> {
+    if let Ok(element) = obj.downcast::<Element>() {
+        return Ok(element.get().0.clone());
+    }
+    let children = obj
+        .iter()?
+        .map(|x| {
+            let entry = extract_absolute_entry(&x?)?;
+            let element = entry.element.get().0.clone();
+            Ok(schedule::AbsoluteEntry::new(element).with_time(entry.time)?)
+        })
+        .collect::<PyResult<_>>()?;
+    let variant = schedule::Absolute::new().with_children(children);
+    let common = ElementCommonBuilder::new().build()?;
+    Ok(Arc::new(schedule::Element::new(common, variant)))
+}
+
+fn extract_passes(obj: &Bound<PyAny>) -> PyResult<Vec<Pass>> {
+    if let Ok(enabled) = obj.extract::<bool>() {
+        return Ok(if enabled { Pass::ALL.to_vec() } else { vec![] });
+    }
+    let names: Vec<String> = obj.extract()?;
+    names
+        .iter()
+        .map(|s| s.parse())
+        .collect::<anyhow::Result<_>>()
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn optimize_schedule(
+    py: Python,
+    schedule: &ElementRef,
+    optimize: Option<&Bound<PyAny>>,
+    amp_tolerance: Amplitude,
+) -> PyResult<ElementRef> {
+    let passes = match optimize {
+        Some(obj) => extract_passes(obj)?,
+        None => vec![],
+    };
+    Ok(py.allow_threads(|| Optimizer::new(passes, amp_tolerance).optimize(schedule)))
+}
+
+/// Validates the alias table against `channels` and replaces aliased channel
+/// ids in the schedule.
+fn resolve_schedule_aliases(
+    py: Python,
+    schedule: &ElementRef,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    channels: &HashMap<ChannelId, Channel>,
+) -> PyResult<ElementRef> {
+    resolve_aliases_to(py, schedule, aliases, |n| channels.contains_key(n))
+}
+
+/// Same as [`resolve_schedule_aliases`], but the channel names are given by
+/// `is_channel`, e.g. to include the digital channels of markers.
+fn resolve_aliases_to(
+    py: Python,
+    schedule: &ElementRef,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    is_channel: impl Fn(&ChannelId) -> bool,
+) -> PyResult<ElementRef> {
+    let Some(aliases) = aliases else {
+        return Ok(schedule.clone());
+    };
+    if let Some(n) = aliases.keys().find(|n| is_channel(n)) {
+        return Err(PyValueError::new_err(format!(
+            "Channel alias '{}' shadows a channel.",
+            n
+        )));
+    }
+    let aliases =
+        canonicalize_aliases(&aliases).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    if let Some((n, target)) = aliases.iter().find(|(_, t)| !is_channel(t)) {
+        return Err(PyValueError::new_err(format!(
+            "Target '{}' of channel alias '{}' not found.",
+            target, n
+        )));
+    }
+    Ok(py.allow_threads(|| resolve_aliases(schedule, &aliases)))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_executor(
+    py: Python,
+    channels: &HashMap<ChannelId, Channel>,
+    shapes: &HashMap<ShapeId, Py<Shape>>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+) -> PyResult<Executor> {
+    let amp_scales = amp_scales.unwrap_or_default();
+    if let Some((label, scale)) = amp_scales.iter().find(|(_, s)| !s.is_finite()) {
+        return Err(PyValueError::new_err(format!(
+            "Invalid amplitude scale for label '{}': {}",
+            label, scale
+        )));
+    }
+    let scales = scales.unwrap_or_default();
+    if let Some((n, scale)) = scales.iter().find(|(_, s)| !s.is_finite()) {
+        return Err(PyValueError::new_err(format!(
+            "Invalid amplitude scale for channel '{}': {}",
+            n, scale
+        )));
+    }
+    if let Some(n) = scales.keys().find(|n| !channels.contains_key(*n)) {
+        return Err(PyValueError::new_err(format!(
+            "Scaled channel '{}' not found.",
+            n
+        )));
+    }
+    let mute_channels = mute_channels.unwrap_or_default();
+    if let Some(n) = mute_channels.iter().find(|n| !channels.contains_key(*n)) {
+        return Err(PyValueError::new_err(format!(
+            "Muted channel '{}' not found.",
+            n
+        )));
+    }
+    let mut executor = Executor::new(amp_tolerance, time_tolerance, allow_oversize);
+    for (label, scale) in amp_scales {
+        executor.add_amp_scale(label, scale);
+    }
+    for (n, scale) in scales {
+        executor.add_channel_scale(n, scale);
+    }
+    for n in mute_channels {
+        executor.mute_channel(n);
+    }
+    for (n, c) in channels {
+        executor.add_channel(n.clone(), c.base_freq, c.wrap_phase);
+        if c.phase_from_first_use {
+            executor.phase_from_first_use(n.clone());
+        }
+    }
+    for (n, s) in shapes {
+        let s = s.bind(py);
+        executor.add_shape(n.clone(), Shape::get_rust_shape(s)?);
+    }
+    Ok(executor)
+}
+
+fn build_pulse_lists(
+    py: Python,
+    schedule: &ElementRef,
+    mut executor: Executor,
+) -> PyResult<HashMap<ChannelId, PulseList>> {
+    py.allow_threads(|| executor.execute(schedule))
+        .map_err(|e| schedule_error(py, e))?;
+    Ok(executor.into_result())
+}
+
+fn channel_arrays<'py>(
+    py: Python<'py>,
+    channels: &HashMap<ChannelId, Channel>,
+) -> HashMap<ChannelId, ChannelArrays<'py>> {
+    channels
+        .iter()
+        .map(|(n, c)| (n.clone(), c.readonly_arrays(py)))
+        .collect()
+}
+
+fn channel_configs<'a>(
+    channels: &HashMap<ChannelId, Channel>,
+    arrays: &'a HashMap<ChannelId, ChannelArrays>,
+    envelope_precision: EnvelopePrecision,
+) -> HashMap<ChannelId, generate::ChannelConfig<'a>> {
+    channels
+        .iter()
+        .map(|(n, c)| {
+            let config = generate::ChannelConfig {
+                envelope_precision,
+                ..c.config(&arrays[n])
+            };
+            (n.clone(), config)
+        })
+        .collect()
+}
+
+fn extract_envelope_precision(value: &str) -> PyResult<EnvelopePrecision> {
+    match value {
+        "f64" => Ok(EnvelopePrecision::F64),
+        "f32" => Ok(EnvelopePrecision::F32),
+        _ => Err(PyValueError::new_err(format!(
+            "Invalid envelope precision '{}', expected 'f64' or 'f32'.",
+            value
+        ))),
+    }
+}
+
+/// Crosstalk argument of the waveform generation functions.
+///
+/// Accepts ``(matrix, names)`` or ``(matrix, names, drag_matrix)``. The
+/// matrix may also be a 3D array of FIR kernels or a sequence of
+/// ``(start_time, matrix)``. The arrays are copied so that the argument can
+/// outlive the call.
+struct CrosstalkArg {
+    matrices: CrosstalkMatrices,
+    names: Vec<ChannelId>,
+    drag_matrix: Option<Array2<f64>>,
+}
+
+enum CrosstalkMatrices {
+    /// Matrices sorted by their start time.
+    Piecewise(Vec<(Time, Array2<f64>)>),
+    Kernels(Array3<f64>),
+}
+
+impl<'py> FromPyObject<'py> for CrosstalkArg {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let tuple = ob.downcast::<PyTuple>()?;
+        if !matches!(tuple.len(), 2 | 3) {
+            return Err(PyValueError::new_err(
+                "Crosstalk should be a tuple of (matrix, names) or (matrix, names, drag_matrix).",
+            ));
+        }
+        let matrices = extract_crosstalk_matrices(&tuple.get_item(0)?)?;
+        let names: Vec<ChannelId> = tuple.get_item(1)?.extract()?;
+        let drag_matrix = match tuple.len() {
+            3 => {
+                let m: PyArrayLike2<f64, AllowTypeChange> = tuple.get_item(2)?.extract()?;
+                Some(m.as_array().to_owned())
+            }
+            _ => None,
+        };
+        let nl = names.len();
+        let shapes: Vec<_> = match &matrices {
+            CrosstalkMatrices::Piecewise(segments) => {
+                segments.iter().map(|(_, m)| m.shape()).collect()
+            }
+            CrosstalkMatrices::Kernels(kernels) => vec![&kernels.shape()[..2]],
+        };
+        if shapes.iter().any(|&s| s != [nl, nl]) {
+            return Err(PyValueError::new_err(
+                "The size of the crosstalk matrix must be the same as the number of names.",
+            ));
+        }
+        if let CrosstalkMatrices::Kernels(kernels) = &matrices {
+            if kernels.shape()[2] == 0 {
+                return Err(PyValueError::new_err(
+                    "Crosstalk kernels should have at least one tap.",
+                ));
+            }
+            if drag_matrix.is_some() {
+                return Err(PyValueError::new_err(
+                    "A DRAG crosstalk matrix can't be used with crosstalk kernels.",
+                ));
+            }
+        }
+        if drag_matrix.as_ref().is_some_and(|m| m.shape() != [nl, nl]) {
+            return Err(PyValueError::new_err(
+                "The size of the DRAG crosstalk matrix must be the same as the number of names.",
+            ));
+        }
+        Ok(Self {
+            matrices,
+            names,
+            drag_matrix,
+        })
+    }
+}
+
+/// Extracts a matrix, a 3D array of kernels or a sequence of
+/// ``(start_time, matrix)``.
+fn extract_crosstalk_matrices(ob: &Bound<PyAny>) -> PyResult<CrosstalkMatrices> {
+    if let Ok(array) = ob.extract::<PyArrayLikeDyn<f64, AllowTypeChange>>() {
+        let array = array.as_array();
+        return match array.ndim() {
+            2 => {
+                let matrix = array.into_dimensionality::<Ix2>().expect("Checked ndim");
+                Ok(CrosstalkMatrices::Piecewise(vec![(
+                    Time::ZERO,
+                    matrix.to_owned(),
+                )]))
+            }
+            3 => {
+                let kernels = array.into_dimensionality::<Ix3>().expect("Checked ndim");
+                Ok(CrosstalkMatrices::Kernels(kernels.to_owned()))
+            }
+            _ => Err(PyValueError::new_err(
+                "The crosstalk matrix should be a 2D matrix or a 3D array of kernels.",
+            )),
+        };
+    }
+    let segments: Vec<(Time, PyArrayLike2<f64, AllowTypeChange>)> = ob.extract()?;
+    if segments.is_empty() {
+        return Err(PyValueError::new_err(
+            "At least one crosstalk matrix is needed.",
+        ));
+    }
+    if segments.windows(2).any(|w| w[0].0 >= w[1].0) {
+        return Err(PyValueError::new_err(
+            "The start times of the crosstalk matrices should be strictly increasing.",
+        ));
+    }
+    Ok(CrosstalkMatrices::Piecewise(
+        segments
+            .into_iter()
+            .map(|(t, m)| (t, m.as_array().to_owned()))
+            .collect(),
+    ))
+}
+
+impl CrosstalkArg {
+    fn to_crosstalk(&self) -> pulse::Crosstalk<'_> {
+        let names = self.names.clone();
+        match &self.matrices {
+            CrosstalkMatrices::Piecewise(segments) => {
+                let segments = segments.iter().map(|(t, m)| (*t, m.view())).collect();
+                let crosstalk = pulse::Crosstalk::piecewise(segments, names);
+                match &self.drag_matrix {
+                    Some(m) => crosstalk.with_drag_matrix(m.view()),
+                    None => crosstalk,
+                }
+            }
+            CrosstalkMatrices::Kernels(kernels) => {
+                pulse::Crosstalk::from_kernels(kernels.view(), names)
+            }
+        }
+    }
+}
+
+fn sample_into(
+    py: Python,
+    schedule: &ElementRef,
+    pulse_lists: HashMap<ChannelId, PulseList>,
+    configs: &HashMap<ChannelId, generate::ChannelConfig>,
+    waveforms: &mut HashMap<ChannelId, ArrayViewMut2<f64>>,
+    crosstalk: Option<&CrosstalkArg>,
+    time_tolerance: Time,
+) -> PyResult<()> {
+    let crosstalk = crosstalk.map(CrosstalkArg::to_crosstalk);
+    let stats = py
+        .allow_threads(|| {
+            generate::sample_into(
+                pulse_lists,
+                configs,
+                waveforms,
+                crosstalk.as_ref(),
+                time_tolerance,
+            )
+        })
+        .map_err(|e| match e {
+            generate::Error::Sampling(e) => sampling_error(py, e, schedule, time_tolerance),
+            e => PyValueError::new_err(e.to_string()),
+        })?;
+    warn_overflow(py, &stats)
+}
+
+/// Issues a :class:`RuntimeWarning` for each channel with truncated or wrapped
+/// pulses.
+fn warn_overflow(py: Python, stats: &HashMap<ChannelId, pulse::OverflowStats>) -> PyResult<()> {
+    let mut stats: Vec<_> = stats.iter().collect();
+    stats.sort_unstable_by_key(|(n, _)| n.to_string());
+    for (n, s) in stats {
+        let msg = format!(
+            "{} pulses of channel '{}' extend {} samples past the end of the waveform",
+            s.pulses, n, s.samples
+        );
+        PyErr::warn_bound(py, &py.get_type_bound::<PyRuntimeWarning>(), &msg, 1)?;
+    }
+    Ok(())
+}
+
+/// Compute amplitude scale factors so that waveform peaks reach a target.
+///
+/// A dry run of :func:`generate_waveforms` is performed to find the peak
+/// absolute value of each channel after post-processing. For each label, the
+/// scale factor is chosen such that the highest peak among the channels played
+/// on by :class:`Play` elements with the label reaches `target`. The result can
+/// be passed to :func:`generate_waveforms` as `amp_scales`.
+///
+/// .. caution::
+///
+///     The waveforms are assumed to scale linearly with the amplitudes. Channel
+///     offset and contributions from other labels on the same channel are not
+///     taken into account, so the final peaks are only approximately equal to
+///     `target` in these cases.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     labels (Sequence[str]): Labels of the :class:`Play` elements to scale.
+///     target (float): Target peak value as a fraction of full scale 1.0.
+///         Default is 0.9.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix with corresponding channel ids. An optional third
+///         matrix is applied to the DRAG component instead, e.g. an identity
+///         matrix excludes DRAG from crosstalk. A 3D array of shape
+///         ``(n, n, taps)`` gives a FIR kernel for each pair instead of a
+///         constant, which can't be combined with a DRAG matrix. A sequence
+///         of ``(start_time, matrix)`` switches the matrix during the
+///         schedule, each matrix applies to the pulses starting from its
+///         start time. Default is ``None``.
+/// Returns:
+///     Dict[str, float]: Scale factors of the labels. Labels without any
+///         non-zero pulse get a scale factor of 1.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulses cannot be sampled to the waveforms.
+/// Example:
+///     .. code-block:: python
+///
+///         scales = compute_amp_scales(channels, shapes, schedule, ["drive"])
+///         result = generate_waveforms(channels, shapes, schedule, amp_scales=scales)
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    labels,
+    *,
+    target=0.9,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn compute_amp_scales(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    labels: Vec<Label>,
+    target: f64,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+) -> PyResult<HashMap<Label, f64>> {
+    if !(target.is_finite() && target > 0.0) {
+        return Err(PyValueError::new_err(format!("Invalid target {}", target)));
+    }
+    let schedule = extract_schedule(schedule)?;
+    let label_channels = executor::label_channels(&schedule);
+    let waveforms = sample_waveforms(
+        py,
+        channels,
+        shapes,
+        &schedule,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        crosstalk,
+        None,
+        None,
+        None,
+        None,
+        "f64",
+        None,
+    )?;
+    let peaks: HashMap<_, _> = waveforms
+        .into_iter()
+        .map(|(n, w)| {
+            let w = w.bind(py).readonly();
+            let peak = w.as_array().iter().fold(0.0f64, |acc, x| acc.max(x.abs()));
+            (n, peak)
+        })
+        .collect();
+    Ok(labels
+        .into_iter()
+        .map(|label| {
+            let peak = label_channels
+                .get(&label)
+                .into_iter()
+                .flatten()
+                .filter_map(|n| peaks.get(n))
+                .fold(0.0f64, |acc, &x| acc.max(x));
+            let scale = if peak > 0.0 { target / peak } else { 1.0 };
+            (label, scale)
+        })
+        .collect())
+}
+
+/// Generate waveforms for a sweep of amplitudes and phases.
+///
+/// :class:`Play` elements are selected by their labels. For the ``i``-th sweep
+/// point, the amplitude of the selected elements is multiplied by
+/// ``amp_scales[label][i]`` and ``phase_shifts[label][i]`` is added to their
+/// phase. The schedule is arranged only once, and the sweep points are sampled
+/// in parallel with a shared envelope cache.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     amp_scales (Mapping[str, array_like] | None): Amplitude scale factors of
+///         each sweep point by labels. Default is ``None``.
+///     phase_shifts (Mapping[str, array_like] | None): Additional phases in
+///         **cycles**, or radians as set by :func:`set_phase_unit`, of each
+///         sweep point by labels. Default is ``None``.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix with corresponding channel ids. An optional third
+///         matrix is applied to the DRAG component instead, e.g. an identity
+///         matrix excludes DRAG from crosstalk. A 3D array of shape
+///         ``(n, n, taps)`` gives a FIR kernel for each pair instead of a
+///         constant, which can't be combined with a DRAG matrix. A sequence
+///         of ``(start_time, matrix)`` switches the matrix during the
+///         schedule, each matrix applies to the pulses starting from its
+///         start time. Default is ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel, applied to every sweep
+///         point. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes as in
+///         :func:`generate_waveforms`. Default is ``"f64"``.
+/// Returns:
+///     Dict[str, numpy.ndarray]: Waveforms of the channels. The shape of the
+///         waveform is ``(m, n, length)``, where ``m`` is the number of sweep
+///         points and ``n`` is 2 for complex waveform and 1 for real waveform.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid, the sweep arrays have different
+///         lengths, or a label matches no :class:`Play` element.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulses cannot be sampled to the waveforms.
+/// Example:
+///     .. code-block:: python
+///
+///         amps = np.linspace(0, 1, 51)
+///         result = generate_sweep(channels, shapes, schedule, amp_scales={"drive": amps})
+///         # result["xy"].shape == (51, 2, length)
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    amp_scales=None,
+    phase_shifts=None,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    scales=None,
+    aliases=None,
+    envelope_precision="f64",
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_sweep(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    amp_scales: Option<HashMap<Label, Vec<f64>>>,
+    phase_shifts: Option<HashMap<Label, Vec<f64>>>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    envelope_precision: &str,
+) -> PyResult<HashMap<ChannelId, Py<PyArray3<f64>>>> {
+    let _span = tracing::info_span!("generate_sweep").entered();
+    ensure_numpy(py)?;
+    let envelope_precision = extract_envelope_precision(envelope_precision)?;
+    let amp_scales = amp_scales.unwrap_or_default();
+    let phase_shifts = phase_shifts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(label, values)| {
+            let values = values
+                .into_iter()
+                .map(Phase::from_py_value)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((label, values))
+        })
+        .collect::<PyResult<HashMap<_, _>>>()?;
+    let lengths: Vec<_> = amp_scales
+        .values()
+        .map(Vec::len)
+        .chain(phase_shifts.values().map(Vec::len))
+        .collect();
+    let Some(&n_sweep) = lengths.first() else {
+        return Err(PyValueError::new_err(
+            "At least one sweep axis is required.",
+        ));
+    };
+    if lengths.iter().any(|&l| l != n_sweep) {
+        return Err(PyValueError::new_err(
+            "All sweep arrays must have the same length.",
+        ));
+    }
+    if amp_scales.values().flatten().any(|s| !s.is_finite()) {
+        return Err(PyValueError::new_err("Amplitude scales must be finite."));
+    }
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let mut executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        None,
+        scales,
+        None,
+    )?;
+    executor.record_pulses();
+    py.allow_threads(|| executor.execute(&schedule))
+        .map_err(|e| schedule_error(py, e))?;
+    let base = regenerate::Regenerator::new(
+        channels.keys().cloned(),
+        executor.into_pulse_records(),
+        amp_tolerance,
+        time_tolerance,
+    );
+    if let Some(label) = amp_scales
+        .keys()
+        .chain(phase_shifts.keys())
+        .find(|l| !base.has_label(l))
+    {
+        return Err(PyValueError::new_err(format!(
+            "Label '{}' doesn't match any Play element.",
+            label
+        )));
+    }
+    let waveforms: HashMap<_, _> = channels
+        .iter()
+        .flat_map(|(n, c)| {
+            let n_w = if c.is_real { 1 } else { 2 };
+            c.outputs(n).map(move |o| {
+                let w = PyArray3::zeros_bound(py, (n_sweep, n_w, c.length), false).unbind();
+                (o, w)
+            })
+        })
+        .collect();
+    let arrays = channel_arrays(py, &channels);
+    let configs = channel_configs(&channels, &arrays, envelope_precision);
+    let crosstalk = crosstalk.as_ref().map(CrosstalkArg::to_crosstalk);
+    let mut arrays3: Vec<_> = waveforms
+        .iter()
+        // SAFETY: These arrays are just created.
+        .map(|(n, w)| (n, unsafe { w.bind(py).as_array_mut() }))
+        .collect();
+    let mut points: Vec<HashMap<_, _>> = (0..n_sweep).map(|_| HashMap::new()).collect();
+    for (n, w) in &mut arrays3 {
+        for (views, view) in points.iter_mut().zip(w.axis_iter_mut(Axis(0))) {
+            views.insert((*n).clone(), view);
+        }
+    }
+    let results: Vec<_> = py.allow_threads(|| {
+        points
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, mut views)| {
+                // All channels of a fresh regenerator are dirty.
+                let mut regenerator = base.clone();
+                for (label, values) in &amp_scales {
+                    regenerator.set_amp_scale(label.clone(), values[i]);
+                }
+                for (label, values) in &phase_shifts {
+                    regenerator.set_phase_shift(label.clone(), values[i]);
+                }
+                generate::sample_into(
+                    regenerator.pulse_lists(),
+                    &configs,
+                    &mut views,
+                    crosstalk.as_ref(),
+                    time_tolerance,
+                )
+            })
+            .collect()
+    });
+    for result in results {
+        match result {
+            Ok(stats) => warn_overflow(py, &stats)?,
+            Err(generate::Error::Sampling(e)) => {
+                return Err(sampling_error(py, e, &schedule, time_tolerance))
+            }
+            Err(e) => return Err(PyValueError::new_err(e.to_string())),
+        }
+    }
+    Ok(waveforms)
+}
+
+/// Generate waveforms of a schedule template for a batch of parameter sets.
+///
+/// The :class:`Param` placeholders of the template are bound with each
+/// parameter set as in :func:`bind`, and the shots are executed and sampled in
+/// parallel. This avoids the per-call overhead of calling
+/// :func:`generate_waveforms` in a loop, which leaves most cores idle.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule template as in :func:`generate_waveforms`.
+///     param_sets (Sequence[Mapping[str, float]]): Values of the parameters of
+///         each shot.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix as in :func:`generate_waveforms`. Default is
+///         ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         bound schedule of each shot. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+///     aliases (Mapping[str, str] | None): Channel aliases as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes as in
+///         :func:`generate_waveforms`. Default is ``"f64"``.
+/// Returns:
+///     Dict[str, numpy.ndarray]: Waveforms of the channels. The shape of the
+///         waveform is ``(m, n, length)``, where ``m`` is the number of shots
+///         and ``n`` is 2 for complex waveform and 1 for real waveform.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid, or a parameter has no value in
+///         a parameter set.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulses cannot be sampled to the waveforms.
+/// Example:
+///     .. code-block:: python
+///
+///         template = Play("xy", "hann", Param("amp"), 100e-9)
+///         param_sets = [{"amp": a} for a in np.linspace(0, 1, 51)]
+///         result = generate_waveforms_batch(channels, shapes, template, param_sets)
+///         # result["xy"].shape == (51, 2, length)
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    param_sets,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+    envelope_precision="f64",
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_waveforms_batch(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    param_sets: Vec<HashMap<String, f64>>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+    envelope_precision: &str,
+) -> PyResult<HashMap<ChannelId, Py<PyArray3<f64>>>> {
+    let _span = tracing::info_span!("generate_waveforms_batch", shots = param_sets.len()).entered();
+    ensure_numpy(py)?;
+    let envelope_precision = extract_envelope_precision(envelope_precision)?;
+    let template = extract_schedule_root(schedule)?;
+    let template = resolve_schedule_aliases(py, &template, aliases, &channels)?;
+    let param_sets = param_sets
+        .into_iter()
+        .map(|mut params| {
+            phase_params_to_cycles(&template, &mut params)?;
+            Ok(params)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let passes = match optimize {
+        Some(obj) => extract_passes(obj)?,
+        None => vec![],
+    };
+    let base_executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    let n_shots = param_sets.len();
+    let waveforms: HashMap<_, _> = channels
+        .iter()
+        .flat_map(|(n, c)| {
+            let n_w = if c.is_real { 1 } else { 2 };
+            c.outputs(n).map(move |o| {
+                let w = PyArray3::zeros_bound(py, (n_shots, n_w, c.length), false).unbind();
+                (o, w)
+            })
+        })
+        .collect();
+    let arrays = channel_arrays(py, &channels);
+    let configs = channel_configs(&channels, &arrays, envelope_precision);
+    let crosstalk = crosstalk.as_ref().map(CrosstalkArg::to_crosstalk);
+    let mut arrays3: Vec<_> = waveforms
+        .iter()
+        // SAFETY: These arrays are just created.
+        .map(|(n, w)| (n, unsafe { w.bind(py).as_array_mut() }))
+        .collect();
+    let mut shots: Vec<HashMap<_, _>> = (0..n_shots).map(|_| HashMap::new()).collect();
+    for (n, w) in &mut arrays3 {
+        for (views, view) in shots.iter_mut().zip(w.axis_iter_mut(Axis(0))) {
+            views.insert((*n).clone(), view);
+        }
+    }
+    let results: Vec<_> = py.allow_threads(|| {
+        shots
+            .into_par_iter()
+            .zip(param_sets.par_iter())
+            .map(|(mut views, params)| {
+                let schedule = bind_params(&template, params)
+                    .and_then(|s| resolve_duration_refs(&s))
+                    .map_err(ShotError::Bind)?;
+                let schedule = Optimizer::new(passes.clone(), amp_tolerance).optimize(&schedule);
+                let mut executor = base_executor.clone();
+                executor.execute(&schedule).map_err(ShotError::Schedule)?;
+                generate::sample_into(
+                    executor.into_result(),
+                    &configs,
+                    &mut views,
+                    crosstalk.as_ref(),
+                    time_tolerance,
+                )
+                .map_err(|e| ShotError::Sampling(e, schedule))
+            })
+            .collect()
+    });
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(stats) => warn_overflow(py, &stats)?,
+            Err(ShotError::Bind(e)) => {
+                return Err(PyValueError::new_err(format!("Shot {}: {}", i, e)))
+            }
+            Err(ShotError::Schedule(e)) => return Err(schedule_error(py, e)),
+            Err(ShotError::Sampling(generate::Error::Sampling(e), schedule)) => {
+                return Err(sampling_error(py, e, &schedule, time_tolerance))
+            }
+            Err(ShotError::Sampling(e, _)) => return Err(PyValueError::new_err(e.to_string())),
+        }
+    }
+    Ok(waveforms)
+}
+
+/// Error of a shot of [`generate_waveforms_batch`].
+enum ShotError {
+    Bind(anyhow::Error),
+    Schedule(executor::Error),
+    Sampling(generate::Error, ElementRef),
+}
+
+/// Regenerate waveforms after changing the amplitudes and phases of labeled
+/// pulses.
+///
+/// The schedule is arranged once on construction. :meth:`update` changes the
+/// amplitude scale or phase shift of the :class:`Play` elements with a label,
+/// and :meth:`generate` samples again only the channels with changed pulses.
+/// This is much faster than :func:`generate_waveforms` in amplitude or phase
+/// sweeps, where the timing of the schedule doesn't change.
+///
+/// .. note::
+///
+///     The arrays of unchanged channels returned by :meth:`generate` are the
+///     same objects as in the previous call. Copy them before modifying.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule as in :func:`generate_waveforms`.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     optimize (bool | Sequence[str]): Optimization passes as in
+///         :func:`generate_waveforms`. Default is ``False``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+///     aliases (Mapping[str, str] | None): Channel aliases as in
+///         :func:`generate_waveforms`. Default is ``None``.
+///     envelope_precision (str): Precision of the cached envelopes as in
+///         :func:`generate_waveforms`. Default is ``"f64"``.
+/// Raises:
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+/// Example:
+///     .. code-block:: python
+///
+///         regenerator = Regenerator(channels, shapes, schedule)
+///         for amp in np.linspace(0, 1, 51):
+///             regenerator.update("x180", amp_scale=amp)
+///             result = regenerator.generate()
+#[pyclass(module = "bosing")]
+struct Regenerator {
+    channels: HashMap<ChannelId, Channel>,
+    schedule: ElementRef,
+    regenerator: regenerate::Regenerator,
+    waveforms: HashMap<ChannelId, Py<PyArray2<f64>>>,
+    time_tolerance: Time,
+    envelope_precision: EnvelopePrecision,
+}
+
+#[pymethods]
+impl Regenerator {
+    #[new]
+    #[pyo3(signature = (
+        channels,
+        shapes,
+        schedule,
+        *,
+        time_tolerance=Time::new(1e-12).unwrap(),
+        amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+        allow_oversize=false,
+        optimize=None,
+        scales=None,
+        mute_channels=None,
+        aliases=None,
+        envelope_precision="f64",
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python,
+        channels: HashMap<ChannelId, Channel>,
+        shapes: HashMap<ShapeId, Py<Shape>>,
+        schedule: &Bound<PyAny>,
+        time_tolerance: Time,
+        amp_tolerance: Amplitude,
+        allow_oversize: bool,
+        optimize: Option<&Bound<PyAny>>,
+        scales: Option<HashMap<ChannelId, f64>>,
+        mute_channels: Option<Vec<ChannelId>>,
+        aliases: Option<HashMap<ChannelId, ChannelId>>,
+        envelope_precision: &str,
+    ) -> PyResult<Self> {
+        let envelope_precision = extract_envelope_precision(envelope_precision)?;
+        let schedule = extract_schedule(schedule)?;
+        let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+        let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+        let mut executor = build_executor(
+            py,
+            &channels,
+            &shapes,
+            time_tolerance,
+            amp_tolerance,
+            allow_oversize,
+            None,
+            scales,
+            mute_channels,
+        )?;
+        executor.record_pulses();
+        py.allow_threads(|| executor.execute(&schedule))
+            .map_err(|e| schedule_error(py, e))?;
+        let regenerator = regenerate::Regenerator::new(
+            channels.keys().cloned(),
+            executor.into_pulse_records(),
+            amp_tolerance,
+            time_tolerance,
+        );
+        Ok(Self {
+            channels,
+            schedule,
+            regenerator,
+            waveforms: HashMap::new(),
+            time_tolerance,
+            envelope_precision,
+        })
+    }
+
+    /// Change the pulses with the label.
+    ///
+    /// The changes are relative to the schedule, not to the previous update.
+    ///
+    /// Args:
+    ///     label (str): Label of the :class:`Play` elements.
+    ///     amp_scale (float | None): Amplitude scale factor. Default is
+    ///         ``None``, which keeps the current value.
+    ///     phase_shift (float | None): Phase added to the pulses in cycles, or
+    ///         radians as set by :func:`set_phase_unit`. Default is ``None``,
+    ///         which keeps the current value.
+    /// Raises:
+    ///     ValueError: If `amp_scale` is not finite.
+    #[pyo3(signature = (label, *, amp_scale=None, phase_shift=None))]
+    fn update(
+        &mut self,
+        label: Label,
+        amp_scale: Option<f64>,
+        phase_shift: Option<Phase>,
+    ) -> PyResult<()> {
+        if let Some(scale) = amp_scale {
+            if !scale.is_finite() {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid amplitude scale for label '{}': {}",
+                    label, scale
+                )));
+            }
+            self.regenerator.set_amp_scale(label.clone(), scale);
+        }
+        if let Some(phase) = phase_shift {
+            self.regenerator.set_phase_shift(label, phase);
+        }
+        Ok(())
+    }
+
+    /// Generate the waveforms with the current updates.
+    ///
+    /// Returns:
+    ///     Dict[str, numpy.ndarray]: Waveforms of the channels as in
+    ///         :func:`generate_waveforms`.
+    /// Raises:
+    ///     ImportError: If numpy is not installed.
+    ///     SamplingError: If the pulses cannot be sampled to the waveforms.
+    fn generate(&mut self, py: Python) -> PyResult<HashMap<ChannelId, Py<PyArray2<f64>>>> {
+        let _span = tracing::info_span!("regenerate").entered();
+        ensure_numpy(py)?;
+        let pulse_lists = py.allow_threads(|| self.regenerator.pulse_lists());
+        let dirty: HashMap<_, _> = self
+            .channels
+            .iter()
+            .filter(|(n, _)| pulse_lists.contains_key(*n))
+            .map(|(n, c)| (n.clone(), c.clone()))
+            .collect();
+        let waveforms: HashMap<_, _> = dirty
+            .iter()
+            .flat_map(|(n, c)| {
+                let n_w = if c.is_real { 1 } else { 2 };
+                c.outputs(n).map(move |o| {
+                    let w = PyArray2::zeros_bound(py, (n_w, c.length), false).unbind();
+                    (o, w)
+                })
+            })
+            .collect();
+        let mut views = waveforms
+            .iter()
+            // SAFETY: These arrays are just created.
+            .map(|(n, w)| (n.clone(), unsafe { w.bind(py).as_array_mut() }))
+            .collect();
+        let arrays = channel_arrays(py, &dirty);
+        sample_into(
+            py,
+            &self.schedule,
+            pulse_lists,
+            &channel_configs(&dirty, &arrays, self.envelope_precision),
+            &mut views,
+            None,
+            self.time_tolerance,
+        )?;
+        drop(views);
+        self.regenerator.clear_dirty();
+        self.waveforms.extend(waveforms);
+        Ok(self
+            .waveforms
+            .iter()
+            .map(|(n, w)| (n.clone(), w.clone_ref(py)))
+            .collect())
+    }
+}
+
+/// Parameter grid and waveforms of an IQ imbalance sweep.
+type IqSweep<'py> = (Bound<'py, PyArray2<f64>>, Bound<'py, PyArray3<f64>>);
+
+/// Generate waveforms of a two-tone test signal for a sweep of IQ imbalance
+/// corrections.
+///
+/// This automates the sideband calibration of an IQ mixer. The test signal is
+/// a sum of constant tones covering the whole waveform, which is sampled only
+/// once. For each point of the grid spanned by `gains` and `phases`, the IQ
+/// matrix of the channel is replaced by
+///
+/// .. math::
+///
+///     \begin{pmatrix} 1 & 0 \\ g \sin 2\pi\phi & g \cos 2\pi\phi
+///     \end{pmatrix} M
+///
+/// where :math:`M` is the current IQ matrix, or the identity if it is not set.
+/// I.e. the Q component is scaled by :math:`g` and rotated by :math:`\phi`
+/// towards I. The other post-processing steps of the channel are unchanged.
+///
+/// Args:
+///     channel (Channel): The complex channel to calibrate.
+///     frequencies (Sequence[float]): Frequencies of the tones, in addition to
+///         the base frequency of the channel. Usually two tones.
+///     gains (Sequence[float]): Gain factors :math:`g` of the Q component.
+///     phases (Sequence[float]): Phase skews :math:`\phi` in **cycles**, or
+///         radians as set by :func:`set_phase_unit`.
+///     amplitude (float): Amplitude of each tone. Default is 0.4.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+/// Returns:
+///     tuple[numpy.ndarray, numpy.ndarray]: The parameter grid of shape
+///         ``(m, 2)`` with columns :math:`g` and :math:`\phi`, and the
+///         waveforms of shape ``(m, 2, length)``, where ``m`` is
+///         ``len(gains) * len(phases)``. The phases vary fastest.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid or the channel is real.
+///     SamplingError: If the tones cannot be sampled to the waveforms.
+/// Example:
+///     .. code-block:: python
+///
+///         params, waveforms = iq_imbalance_sweep(
+///             channels["xy"],
+///             [50e6, -50e6],
+///             gains=np.linspace(0.95, 1.05, 11),
+///             phases=np.linspace(-0.01, 0.01, 21),
+///         )
+///         best = min(range(len(params)), key=lambda i: measure(waveforms[i]))
+///         gain, phase = params[best]
+#[pyfunction]
+#[pyo3(signature = (
+    channel,
+    frequencies,
+    gains,
+    phases,
+    *,
+    amplitude=Amplitude::new(0.4).unwrap(),
+    time_tolerance=Time::new(1e-12).unwrap(),
+))]
+fn iq_imbalance_sweep<'py>(
+    py: Python<'py>,
+    channel: Channel,
+    frequencies: Vec<Frequency>,
+    gains: Vec<f64>,
+    phases: Vec<f64>,
+    amplitude: Amplitude,
+    time_tolerance: Time,
+) -> PyResult<IqSweep<'py>> {
+    let _span = tracing::info_span!("iq_imbalance_sweep").entered();
+    ensure_numpy(py)?;
+    if channel.is_real {
+        return Err(PyValueError::new_err(
+            "IQ imbalance sweep requires a complex channel.",
+        ));
+    }
+    if frequencies.is_empty() {
+        return Err(PyValueError::new_err("At least one tone is required."));
+    }
+    if gains.iter().chain(&phases).any(|x| !x.is_finite()) {
+        return Err(PyValueError::new_err("Gains and phases must be finite."));
+    }
+    let id = ChannelId::new("iq");
+    let length = Time::new(channel.length as f64 / channel.sample_rate.value())?;
+    let plateau = (length - channel.total_delay().max(Time::ZERO)).max(Time::ZERO);
+    let tones = frequencies
+        .into_iter()
+        .map(|f| {
+            let play = schedule::Play::new(id.clone(), None, amplitude, Time::ZERO)?
+                .with_plateau(plateau)?
+                .with_frequency(f)?;
+            let common = ElementCommonBuilder::new().build()?;
+            Ok(schedule::AbsoluteEntry::new(Arc::new(
+                schedule::Element::new(common, play),
+            )))
+        })
+        .collect::<PyResult<_>>()?;
+    let common = ElementCommonBuilder::new().build()?;
+    let root = Arc::new(schedule::Element::new(
+        common,
+        schedule::Absolute::new().with_children(tones),
+    ));
+    let channels = [(id.clone(), channel)].into_iter().collect();
+    let executor = build_executor(
+        py,
+        &channels,
+        &HashMap::new(),
+        time_tolerance,
+        Amplitude::ZERO,
+        false,
+        None,
+        None,
+        None,
+    )?;
+    let pulse_lists = build_pulse_lists(py, &root, executor)?;
+    let channel = &channels[&id];
+    let arrays = channel.readonly_arrays(py);
+    let current = arrays
+        .iq_matrix
+        .as_ref()
+        .map_or_else(|| Array2::eye(2), |m| m.as_array().to_owned());
+    let mut params = Vec::with_capacity(gains.len() * phases.len() * 2);
+    let mut matrices = Vec::with_capacity(gains.len() * phases.len());
+    for &gain in &gains {
+        for &phase in &phases {
+            params.extend([gain, phase]);
+            let phase = Phase::from_py_value(phase)?.value();
+            matrices.push(generate::iq_imbalance_matrix(gain, phase).dot(&current));
+        }
+    }
+    let waveforms = PyArray3::zeros_bound(py, (matrices.len(), 2, channel.length), false);
+    // SAFETY: The array is just created.
+    let buffer = unsafe { waveforms.as_array_mut() };
+    let config = channel.config(&arrays);
+    let stats = py
+        .allow_threads(|| {
+            generate::sample_iq_sweep(pulse_lists, &id, &config, &matrices, buffer, time_tolerance)
+        })
+        .map_err(|e| match e {
+            generate::Error::Sampling(e) => sampling_error(py, e, &root, time_tolerance),
+            e => PyValueError::new_err(e.to_string()),
+        })?;
+    warn_overflow(py, &stats)?;
+    let params = Array2::from_shape_vec((matrices.len(), 2), params)
+        .expect("Shape should match")
+        .into_pyarray_bound(py);
+    Ok((params, waveforms))
+}
+
+/// Size and complexity of a schedule tree returned by :meth:`Element.stats`.
+///
+/// Attributes:
+///     node_count (int): Number of elements in the tree.
+///     depth (int): Number of elements on the longest path from the root to a
+///         leaf.
+///     variant_counts (dict[str, int]): Number of elements of each type, e.g.
+///         ``{"Stack": 1, "Play": 2}``.
+///     pulse_count (int): Estimated number of pulses, i.e. non-phantom
+///         :class:`Play` elements counting the repetitions of :class:`Repeat`.
+///         Muted channels and merging of pulses are not taken into account.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct ElementStats {
+    node_count: u64,
+    depth: u64,
+    variant_counts: HashMap<String, u64>,
+    pulse_count: u64,
+}
+
+/// Statistics of the pulses of a channel.
+///
+/// Pulses with the same envelope, frequency and start time are merged into
+/// one before sampling, so `pulses` is usually smaller than the number of
+/// :class:`Play` elements.
+///
+/// Attributes:
+///     pulses (int): Number of pulses after merging.
+///     envelopes (list[tuple[str | None, float, float, int]]): Distinct
+///         envelopes used by the channel as ``(shape_id, width, plateau,
+///         count)``, sorted by `count` in descending order. `shape_id` is
+///         ``None`` for rectangular pulses, in which case `width` is 0 and
+///         `plateau` is the total length.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct PulseStats {
+    pulses: usize,
+    envelopes: Vec<(Option<ShapeId>, Time, Time, usize)>,
+}
+
+/// Compute pulse statistics of each channel without sampling waveforms.
+///
+/// This is useful to estimate the memory needed by a sequencer. Arguments
+/// have the same meaning as in :func:`generate_waveforms`.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+/// Returns:
+///     Dict[str, PulseStats]: Pulse statistics of the channels.
+/// Raises:
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    optimize=None,
+    amp_scales=None,
+    mute_channels=None,
+    aliases=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn pulse_stats(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+) -> PyResult<HashMap<ChannelId, PulseStats>> {
+    let _span = tracing::info_span!("pulse_stats").entered();
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        None,
+        mute_channels,
+    )?;
+    let pulse_lists = build_pulse_lists(py, &schedule, executor)?;
+    let shape_ids = shapes
+        .iter()
+        .map(|(n, s)| Ok((Shape::get_rust_shape(s.bind(py))?, n.clone())))
+        .collect::<PyResult<HashMap<_, _>>>()?;
+    Ok(pulse_lists
+        .into_iter()
+        .map(|(n, list)| {
+            let mut envelopes: Vec<_> = list
+                .envelope_counts()
+                .into_iter()
+                .map(|(e, count)| {
+                    let shape_id = e.shape().and_then(|s| shape_ids.get(s)).cloned();
+                    (shape_id, e.width(), e.plateau(), count)
+                })
+                .collect();
+            envelopes.sort_by_key(|e| std::cmp::Reverse(e.3));
+            let stats = PulseStats {
+                pulses: list.len(),
+                envelopes,
+            };
+            (n, stats)
+        })
+        .collect())
+}
+
+/// Pulse which doesn't fit in the waveform of its channel, returned by
+/// :func:`find_clipped_pulses`.
+///
+/// Attributes:
+///     path (str): Path to the :class:`Play` element in the schedule tree,
+///         like ``"Stack > 0: Play 'x'"``, where each segment is the index of
+///         the child, its type and its label.
+///     label (str | None): Label of the element.
+///     channel (str): Channel of the pulse.
+///     start (float): Start time of the pulse in the schedule.
+///     end (float): End time of the pulse in the schedule.
+///     before (float): Time by which the pulse starts before the waveform.
+///     after (float): Time by which the pulse ends after the waveform.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct ClippedPulse {
+    path: String,
+    label: Option<Label>,
+    channel: ChannelId,
+    start: Time,
+    end: Time,
+    before: Time,
+    after: Time,
+}
+
+/// Find the pulses which don't fit in the waveforms of their channels.
+///
+/// :func:`generate_waveforms` only reports the first pulse out of bounds with
+/// its time. This function reports every such :class:`Play` element with its
+/// position in the schedule tree, which makes it easier to find the element to
+/// fix in deep schedules. The waveform of a channel covers the schedule time
+/// from ``-delay`` to ``length / sample_rate - delay``, where `delay`
+/// includes the group delay compensation. Arguments have the same meaning as
+/// in :func:`generate_waveforms`.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+/// Returns:
+///     list[ClippedPulse]: Clipped pulses in the order of the schedule tree.
+/// Raises:
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    optimize=None,
+    amp_scales=None,
+    mute_channels=None,
+    aliases=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn find_clipped_pulses(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+) -> PyResult<Vec<ClippedPulse>> {
+    let _span = tracing::info_span!("find_clipped_pulses").entered();
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        None,
+        mute_channels,
+    )?;
+    let windows = channels
+        .iter()
+        .map(|(n, c)| {
+            let window = schedule::TimeRange {
+                start: -c.total_delay(),
+                span: c.sample_rate.dt() * c.length as f64,
+            };
+            (n.clone(), window)
+        })
+        .collect();
+    let report = py.allow_threads(|| executor.validate(&schedule, &windows));
+    Ok(report
+        .into_iter()
+        .map(|p| ClippedPulse {
+            path: p.path,
+            label: p.label,
+            channel: p.channel,
+            start: p.time_range.start,
+            end: p.time_range.start + p.time_range.span,
+            before: p.before,
+            after: p.after,
+        })
+        .collect())
+}
+
+/// Difference between two schedule trees returned by :func:`diff`.
+///
+/// Attributes:
+///     path (str): Path to the element in the new schedule, or in the old one
+///         if the element was removed, like ``"Stack > 0: Play 'x'"``.
+///     field (str): Name of the field in the format of
+///         :meth:`Element.to_json`, or ``"element"`` if the element was added,
+///         removed or replaced by an element of another type.
+///     old (Any): Old value in the JSON format, ``None`` if the field has its
+///         default value or the element was added.
+///     new (Any): New value in the JSON format, ``None`` if the field has its
+///         default value or the element was removed.
+#[pyclass(get_all, frozen)]
+#[derive(Debug)]
+struct Difference {
+    path: String,
+    field: String,
+    old: PyObject,
+    new: PyObject,
+}
+
+/// Compare two schedules without generating waveforms.
+///
+/// The schedules are compared field by field in the format of
+/// :meth:`Element.to_json`. Equal children at the start and the end of
+/// containers are skipped and the rest is matched by type and label, so an
+/// inserted or removed child is reported once. The order of ``channel_ids`` of
+/// :class:`Barrier` and :class:`Delay` is ignored, and shared subtrees are not
+/// compared.
+///
+/// Args:
+///     a (Element): Old schedule.
+///     b (Element): New schedule.
+/// Returns:
+///     list[Difference]: Differences in the order of the schedule tree.
+/// Raises:
+///     ValueError: If a schedule can't be serialized.
+///
+/// Example:
+///     .. code-block:: python
+///
+///         for d in bosing.diff(previous, schedule):
+///             logger.info("%s: %s %r -> %r", d.path, d.field, d.old, d.new)
+#[pyfunction]
+fn diff(py: Python, a: &Bound<Element>, b: &Bound<Element>) -> PyResult<Vec<Difference>> {
+    let _span = tracing::info_span!("diff").entered();
+    let (a, b) = (a.get().0.clone(), b.get().0.clone());
+    let differences = py
+        .allow_threads(|| schedule::diff(&a, &b))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let json = py.import_bound("json")?;
+    let to_py = |value: Option<serde_json::Value>| -> PyResult<PyObject> {
+        match value {
+            Some(value) => Ok(json.call_method1("loads", (value.to_string(),))?.unbind()),
+            None => Ok(py.None()),
+        }
+    };
+    differences
+        .into_iter()
+        .map(|d| {
+            Ok(Difference {
+                path: d.path,
+                field: d.field,
+                old: to_py(d.old)?,
+                new: to_py(d.new)?,
+            })
+        })
+        .collect()
+}
+
+/// Print the timing of a schedule as an indented narrative.
+///
+/// Each line describes an element with its type, label, start, end, duration
+/// and channels, indented by its depth in the schedule tree, e.g.
+/// ``Play 'x90' 10 ns .. 40 ns (30 ns) [xy]``. This is a textual alternative
+/// to plotting for terminal sessions and log files. Phantom elements are
+/// omitted, and elements placed multiple times, e.g. in :class:`Repeat`, are
+/// described once per placement.
+///
+/// Args:
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule as in :func:`generate_waveforms`.
+///     channels (Sequence[str] | None): Only describe elements on these
+///         channels. Elements without channels, like :class:`Barrier` on all
+///         channels, are always described. Default is ``None``, which
+///         describes all elements.
+///     file (typing.TextIO | None): File to print to, as in :func:`print`.
+///         Default is ``None``, which prints to :data:`sys.stdout`.
+/// Raises:
+///     ValueError: If the schedule is invalid.
+/// Example:
+///     .. code-block:: python
+///
+///         bosing.explain(schedule, ["xy"])
+#[pyfunction]
+#[pyo3(signature = (schedule, channels=None, *, file=None))]
+fn explain(
+    py: Python,
+    schedule: &Bound<PyAny>,
+    channels: Option<Vec<ChannelId>>,
+    file: Option<PyObject>,
+) -> PyResult<()> {
+    let schedule = extract_schedule(schedule)?;
+    let text = py.allow_threads(|| executor::explain(&schedule, channels.as_deref()));
+    let kwargs = PyDict::new_bound(py);
+    kwargs.set_item("end", "")?;
+    kwargs.set_item("file", file)?;
+    py.import_bound("builtins")?
+        .getattr("print")?
+        .call((text,), Some(&kwargs))?;
+    Ok(())
+}
+
+/// Timing of a labeled element on a channel returned by
+/// :func:`element_timings`.
+///
+/// Attributes:
+///     label (str): Label of the element.
+///     channel (str): Name of the channel.
+///     schedule_time (float): Start time of the element in the schedule.
+///     hardware_time (float): Start time of the element at the output of the
+///         instrument relative to the trigger.
+///     duration (float): Duration of the element.
+#[pyclass(get_all, frozen)]
+#[derive(Debug)]
+struct ElementTiming {
+    label: Label,
+    channel: ChannelId,
+    schedule_time: Time,
+    hardware_time: Time,
+    duration: Time,
+}
+
+/// Report the schedule time and the hardware time of the labeled elements.
+///
+/// The hardware time is the time at the output of the instrument after the
+/// trigger. It is the schedule time plus :attr:`Channel.delay`, the group delay
+/// of the filters if it's not compensated and :attr:`Channel.trigger_latency`,
+/// so it differs between channels even if the elements are aligned in the
+/// schedule. Elements without channels, like :class:`Barrier` on all
+/// channels, are reported on every channel. Phantom elements are omitted, and
+/// elements placed multiple times are reported once per placement.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Schedule as in :func:`generate_waveforms`.
+///     labels (Sequence[str] | None): Only report elements with these labels.
+///         Default is ``None``, which reports all labeled elements.
+/// Returns:
+///     list[ElementTiming]: Timings sorted by schedule time and channel.
+/// Raises:
+///     ValueError: If the schedule is invalid or an element is on a channel
+///         which is not in `channels`.
+/// Example:
+///     .. code-block:: python
+///
+///         for t in bosing.element_timings(channels, schedule, labels=["ro"]):
+///             print(t.channel, t.hardware_time)
+#[pyfunction]
+#[pyo3(signature = (channels, schedule, *, labels=None))]
+fn element_timings(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    schedule: &Bound<PyAny>,
+    labels: Option<Vec<Label>>,
+) -> PyResult<Vec<ElementTiming>> {
+    let schedule = extract_schedule(schedule)?;
+    let labels: Option<HashSet<_>> = labels.map(|l| l.into_iter().collect());
+    let spans =
+        py.allow_threads(|| labeled_channel_spans(&schedule, channels.keys(), labels.as_ref()));
+    let mut timings = vec![];
+    for (label, ids, span) in spans {
+        for id in ids {
+            let channel = channels.get(&id).ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "Element '{}' is on unknown channel '{}'",
+                    label, id
+                ))
+            })?;
+            let latency = channel.total_delay()
+                + channel.group_delay.unwrap_or(Time::ZERO)
+                + channel.trigger_latency;
+            timings.push(ElementTiming {
+                label: label.clone(),
+                channel: id,
+                schedule_time: span.start,
+                hardware_time: span.start + latency,
+                duration: span.span,
+            });
+        }
+    }
+    timings.sort_by_cached_key(|t| (t.schedule_time, t.channel.to_string()));
+    Ok(timings)
+}
+
+/// Labeled elements with their channels and time ranges in the schedule.
+/// Elements without channels are on all `channels`, sorted by name.
+fn labeled_channel_spans<'a>(
+    schedule: &ElementRef,
+    channels: impl IntoIterator<Item = &'a ChannelId>,
+    labels: Option<&HashSet<Label>>,
+) -> Vec<(Label, Vec<ChannelId>, schedule::TimeRange)> {
+    let mut all_channels: Vec<_> = channels.into_iter().cloned().collect();
+    all_channels.sort_by_cached_key(ToString::to_string);
+    executor::labeled_spans(schedule)
+        .filter_map(|(item, span)| {
+            let label = item.common.label()?;
+            if labels.is_some_and(|l| !l.contains(label)) {
+                return None;
+            }
+            let ids = match item.channels() {
+                [] => all_channels.clone(),
+                ids => ids.to_vec(),
+            };
+            Some((label.clone(), ids, span))
+        })
+        .collect()
+}
+
+/// Run of samples exceeding the clipping limit of :func:`generate_waveforms`.
+///
+/// Attributes:
+///     channel (str): Name of the output, which is the negative output for
+///         the second output of a differential pair.
+///     start (int): Index of the first sample beyond the limit.
+///     end (int): Index after the last sample beyond the limit.
+///     peak (float): Largest absolute value of the samples.
+///     labels (list[str]): Labels of the elements on the channel overlapping
+///         the samples in the schedule, sorted by name. Filters can spread
+///         pulses further, so this is only a hint of the cause.
+#[pyclass(get_all, frozen)]
+#[derive(Debug)]
+struct Clipping {
+    channel: ChannelId,
+    start: usize,
+    end: usize,
+    peak: f64,
+    labels: Vec<Label>,
+}
+
+/// Raises :class:`ClippingError` if a waveform has samples beyond `limit`.
+fn check_clipping(
+    py: Python,
+    channels: &HashMap<ChannelId, Channel>,
+    schedule: &ElementRef,
+    waveforms: &HashMap<ChannelId, Py<PyArray2<f64>>>,
+    limit: f64,
+) -> PyResult<()> {
+    let mut clippings = vec![];
+    for (n, c) in channels {
+        for output in c.outputs(n) {
+            let waveform = waveforms[&output].bind(py).readonly();
+            for r in generate::find_clipping(waveform.as_array(), limit) {
+                clippings.push((n, c, output.clone(), r));
+            }
+        }
+    }
+    if clippings.is_empty() {
+        return Ok(());
+    }
+    let spans = labeled_channel_spans(schedule, channels.keys(), None);
+    let mut clippings: Vec<_> = clippings
+        .into_iter()
+        .map(|(n, c, output, generate::ClippedRange { range, peak })| {
+            // Schedule time of the samples, see `element_timings`.
+            let latency = c.total_delay() + c.group_delay.unwrap_or(Time::ZERO);
+            let dt = c.sample_rate.dt().value();
+            let start = range.start as f64 * dt - latency.value();
+            let end = range.end as f64 * dt - latency.value();
+            let mut labels: Vec<_> = spans
+                .iter()
+                .filter(|(_, ids, span)| {
+                    ids.contains(n)
+                        && span.start.value() < end
+                        && (span.start + span.span).value() > start
+                })
+                .map(|(label, _, _)| label.clone())
+                .collect();
+            labels.sort_by_cached_key(ToString::to_string);
+            labels.dedup();
+            let clipping = Clipping {
+                channel: output,
+                start: range.start,
+                end: range.end,
+                peak,
+                labels,
+            };
+            (clipping, start)
+        })
+        .collect();
+    clippings.sort_by_cached_key(|(c, _)| (c.channel.to_string(), c.start));
+    let (first, time) = &clippings[0];
+    let message = format!(
+        "{} ranges of the waveforms exceed the limit {}, the first on channel '{}' at sample {} with peak {}",
+        clippings.len(),
+        limit,
+        first.channel,
+        first.start,
+        first.peak
+    );
+    let (channel, time) = (first.channel.clone(), *time);
+    let details = PyDict::new_bound(py);
+    details.set_item("limit", limit)?;
+    let clippings: Vec<_> = clippings.into_iter().map(|(c, _)| c).collect();
+    details.set_item("clippings", clippings.into_py(py))?;
+    Err(new_bosing_error::<ClippingError>(
+        py,
+        message,
+        "clipping",
+        Some(&channel),
+        None,
+        Some(time),
+        details,
+    ))
+}
+
+/// Fragment instance in a schedule returned by
+/// :meth:`FragmentRegistry.instances`.
+///
+/// Attributes:
+///     name (str): Name of the fragment.
+///     version (int): Version of the fragment.
+///     arguments (dict[str, Any]): Arguments of the fragment in the JSON
+///         format.
+///     paths (list[str]): Paths to the occurrences of the instance like
+///         ``"Stack > 0: Play 'x90'"``, see :class:`Difference`.
+#[pyclass(get_all, frozen)]
+#[derive(Debug)]
+struct FragmentInstance {
+    name: String,
+    version: u32,
+    arguments: PyObject,
+    paths: Vec<String>,
+}
+
+impl FragmentInstance {
+    fn from_instance(py: Python, instance: schedule::FragmentInstance) -> PyResult<Self> {
+        let arguments = py
+            .import_bound("json")?
+            .call_method1(
+                "loads",
+                (serde_json::Value::from(instance.arguments).to_string(),),
+            )?
+            .unbind();
+        Ok(Self {
+            name: instance.name,
+            version: instance.version,
+            arguments,
+            paths: instance.paths,
+        })
+    }
+}
+
+fn json_object(value: &Bound<PyAny>) -> PyResult<serde_json::Map<String, serde_json::Value>> {
+    let text: String = value
+        .py()
+        .import_bound("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&text).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Registry of named and versioned schedule fragments.
+///
+/// A fragment is a function building a schedule subtree from keyword
+/// arguments, e.g. a gate of a shared gate library. Subtrees built by
+/// :meth:`instantiate` are remembered with the fragment version and the
+/// arguments, so that :meth:`instances` and :meth:`to_json` can tell which
+/// fragments a schedule was built from. The registry doesn't keep the
+/// subtrees alive.
+///
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import FragmentRegistry, Play, Stack
+///         gates = FragmentRegistry()
+///         gates.register("x90", lambda qubit: Play(qubit, "hann", 0.5, 30e-9))
+///         gates.register("x90", lambda qubit: Play(qubit, "hann", 0.4, 30e-9), version=2)
+///         schedule = Stack(gates.instantiate("x90", {"qubit": "q0"}))
+///         document = gates.to_json(schedule)
+#[pyclass]
+struct FragmentRegistry(schedule::FragmentRegistry<Py<PyAny>>);
+
+#[pymethods]
+impl FragmentRegistry {
+    #[new]
+    fn new() -> Self {
+        Self(schedule::FragmentRegistry::new())
+    }
+
+    /// Register a version of a fragment.
+    ///
+    /// The parameters of the fragment are the named parameters of `factory`.
+    ///
+    /// Args:
+    ///     name (str): Name of the fragment.
+    ///     factory (Callable[..., Element]): Function building the subtree.
+    ///     version (int): Version of the fragment. Default is 1.
+    /// Raises:
+    ///     ValueError: If the version is already registered.
+    #[pyo3(signature = (name, factory, *, version=1))]
+    fn register(&mut self, name: String, factory: &Bound<PyAny>, version: u32) -> PyResult<()> {
+        let py = factory.py();
+        let inspect = py.import_bound("inspect")?;
+        let kind = inspect.getattr("Parameter")?;
+        let var_kinds = [
+            kind.getattr("VAR_POSITIONAL")?,
+            kind.getattr("VAR_KEYWORD")?,
+        ];
+        let mut params = vec![];
+        for param in inspect
+            .call_method1("signature", (factory,))?
+            .getattr("parameters")?
+            .call_method0("values")?
+            .iter()?
+        {
+            let param = param?;
+            let param_kind = param.getattr("kind")?;
+            if !var_kinds.iter().any(|k| k.eq(&param_kind).unwrap_or(false)) {
+                params.push(param.getattr("name")?.extract()?);
+            }
+        }
+        self.0
+            .register(name, version, params, factory.clone().unbind())
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Build a subtree from a fragment.
+    ///
+    /// Args:
+    ///     name (str): Name of the fragment.
+    ///     arguments (dict[str, Any] | None): Keyword arguments of the
+    ///         fragment. They must be JSON serializable.
+    ///     version (int | None): Version of the fragment. ``None`` uses the
+    ///         latest version.
+    /// Returns:
+    ///     Element: Root of the subtree.
+    /// Raises:
+    ///     KeyError: If the fragment or the version is not registered.
+    ///     ValueError: If the fragment has no parameter of an argument.
+    ///     TypeError: If the fragment doesn't return an element.
+    #[pyo3(signature = (name, arguments=None, *, version=None))]
+    fn instantiate<'py>(
+        slf: &Bound<'py, Self>,
+        name: String,
+        arguments: Option<&Bound<'py, PyDict>>,
+        version: Option<u32>,
+    ) -> PyResult<Bound<'py, Element>> {
+        let py = slf.py();
+        let arguments = arguments.cloned().unwrap_or_else(|| PyDict::new_bound(py));
+        let values = json_object(&arguments)?;
+        let (version, factory) = {
+            let this = slf.borrow();
+            let (version, fragment) = this.0.get(&name, version, &values).map_err(|e| match e {
+                schedule::FragmentError::UnknownParameter { .. } => {
+                    PyValueError::new_err(e.to_string())
+                }
+                _ => PyKeyError::new_err(e.to_string()),
+            })?;
+            (version, fragment.factory.clone_ref(py))
+        };
+        let element = factory.bind(py).call((), Some(&arguments))?;
+        let element = element.downcast_into::<Element>()?;
+        slf.borrow_mut()
+            .0
+            .record(&element.get().0, name, version, values);
+        Ok(element)
+    }
+
+    /// Names of the registered fragments, sorted.
+    ///
+    /// Returns:
+    ///     list[str]: Names of the fragments.
+    fn names(&self) -> Vec<String> {
+        self.0.names().map(str::to_string).collect()
+    }
+
+    /// Registered versions of a fragment, sorted.
+    ///
+    /// Args:
+    ///     name (str): Name of the fragment.
+    /// Returns:
+    ///     list[int]: Versions of the fragment.
+    /// Raises:
+    ///     KeyError: If the fragment is not registered.
+    fn versions(&self, name: &str) -> PyResult<Vec<u32>> {
+        self.0
+            .versions(name)
+            .map_err(|e| PyKeyError::new_err(e.to_string()))
+    }
+
+    /// Fragment instances built by this registry in a schedule.
+    ///
+    /// Args:
+    ///     schedule (Element): Root of the schedule.
+    /// Returns:
+    ///     list[FragmentInstance]: Instances in the order of their first
+    ///     occurrence.
+    fn instances(&self, py: Python, schedule: &Bound<Element>) -> PyResult<Vec<FragmentInstance>> {
+        self.0
+            .instances(&schedule.get().0)
+            .into_iter()
+            .map(|i| FragmentInstance::from_instance(py, i))
+            .collect()
+    }
+
+    /// Serialize a schedule together with its fragment instances.
+    ///
+    /// The document is ``{"schedule": ..., "fragments": [...]}`` with the
+    /// schedule in the format of :meth:`Element.to_json`.
+    ///
+    /// Args:
+    ///     schedule (Element): Root of the schedule.
+    /// Returns:
+    ///     str: JSON document.
+    /// Raises:
+    ///     ValueError: If the schedule can't be serialized.
+    fn to_json(&self, schedule: &Bound<Element>) -> PyResult<String> {
+        let root = &schedule.get().0;
+        schedule::to_json_with_fragments(root, &self.0.instances(root))
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Deserialize a document created by :meth:`to_json`.
+    ///
+    /// Args:
+    ///     text (str): JSON document.
+    /// Returns:
+    ///     tuple[Element, list[FragmentInstance]]: Root of the schedule and
+    ///     the fragment instances in it.
+    /// Raises:
+    ///     ValueError: If the document is invalid.
+    #[staticmethod]
+    fn from_json(py: Python, text: &str) -> PyResult<(Py<Element>, Vec<FragmentInstance>)> {
+        let (root, instances) = py
+            .allow_threads(|| schedule::from_json_with_fragments(text))
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let root = to_py_element(py, &root, &mut HashMap::new())?;
+        let instances = instances
+            .into_iter()
+            .map(|i| FragmentInstance::from_instance(py, i))
+            .collect::<PyResult<_>>()?;
+        Ok((root, instances))
+    }
+}
+
+/// Frame events of a channel returned by :func:`compile_frames`.
+///
+/// Each event is a tuple whose first item is the kind and the second the time
+/// relative to the start of the schedule, without the channel delay:
+///
+/// - ``("shift_phase", time, phase)``
+/// - ``("set_phase", time, phase)``
+/// - ``("shift_freq", time, freq)``
+/// - ``("set_freq", time, freq)``
+/// - ``("phase_ramp", time, duration, rate)``
+/// - ``("swap_phase", time, other_channel)``
+/// - ``("pulse", time, envelope, amplitude, drag_coef, freq, phase, chirp_rate)``:
+///   `freq` and `phase` are relative to the frame of the channel, and
+///   `envelope` is the index into `envelopes`. `chirp_rate` is that of
+///   :class:`Play`.
+///
+/// Phases are in cycles, or radians as set by :func:`set_phase_unit`. Phase
+/// ramp rates are always in cycles per second. The events are sorted by time,
+/// and simultaneous events are in execution order.
+///
+/// Attributes:
+///     envelopes (list[tuple[str | None, float, float]]): Distinct envelopes
+///         as ``(shape_id, width, plateau)``, like in :class:`PulseStats`.
+///     envelope_samples (list[numpy.ndarray]): Envelopes sampled at the
+///         sample rate of the channel, starting exactly at a sample point.
+///     events (list[tuple]): Frame events.
+#[pyclass(get_all, frozen)]
+#[derive(Debug)]
+struct FrameProgram {
+    envelopes: Vec<(Option<ShapeId>, Time, Time)>,
+    envelope_samples: Vec<Py<PyArray1<f64>>>,
+    events: Vec<PyObject>,
+}
+
+/// Compile a schedule to frame events for hardware which updates the frames
+/// natively.
+///
+/// Instead of baking the frequency and phase changes into the pulses like
+/// :func:`generate_waveforms`, the changes are returned as separate events and
+/// the pulses only reference their envelopes. Arguments have the same meaning
+/// as in :func:`generate_waveforms`.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel, e.g. for calibration or unit
+///         conversion. The factors multiply with `amp_scales`. Default is
+///         ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+/// Returns:
+///     Dict[str, FrameProgram]: Frame events of the channels.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn compile_frames(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+) -> PyResult<HashMap<ChannelId, FrameProgram>> {
+    let _span = tracing::info_span!("compile_frames").entered();
+    ensure_numpy(py)?;
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let mut executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    executor.record_frames();
+    py.allow_threads(|| executor.execute(&schedule))
+        .map_err(|e| schedule_error(py, e))?;
+    let shape_ids = shapes
+        .iter()
+        .map(|(n, s)| Ok((Shape::get_rust_shape(s.bind(py))?, n.clone())))
+        .collect::<PyResult<HashMap<_, _>>>()?;
+    executor
+        .into_frame_programs()
+        .into_iter()
+        .map(|(n, program)| {
+            let sample_rate = channels[&n].sample_rate;
+            let envelopes = program
+                .envelopes
+                .iter()
+                .map(|e| {
+                    let shape_id = e.shape().and_then(|s| shape_ids.get(s)).cloned();
+                    (shape_id, e.width(), e.plateau())
+                })
+                .collect();
+            let envelope_samples = program
+                .envelopes
+                .iter()
+                .map(|e| {
+                    let samples = pulse::sample_envelope_at(e, sample_rate)?;
+                    Ok(PyArray1::from_slice_bound(py, &samples).unbind())
+                })
+                .collect::<PyResult<_>>()?;
+            let events = program
+                .events
+                .iter()
+                .map(|e| frame_event_to_py(py, e))
+                .collect();
+            let program = FrameProgram {
+                envelopes,
+                envelope_samples,
+                events,
+            };
+            Ok((n, program))
+        })
+        .collect()
+}
+
+fn frame_event_to_py(py: Python, event: &FrameEvent) -> PyObject {
+    match *event {
+        FrameEvent::ShiftPhase { time, phase } => ("shift_phase", time, phase).into_py(py),
+        FrameEvent::SetPhase { time, phase } => ("set_phase", time, phase).into_py(py),
+        FrameEvent::ShiftFreq { time, freq } => ("shift_freq", time, freq).into_py(py),
+        FrameEvent::SetFreq { time, freq } => ("set_freq", time, freq).into_py(py),
+        FrameEvent::PhaseRamp {
+            time,
+            duration,
+            rate,
+        } => ("phase_ramp", time, duration, rate).into_py(py),
+        FrameEvent::SwapPhase { time, ref other } => {
+            ("swap_phase", time, other.clone()).into_py(py)
+        }
+        FrameEvent::Pulse {
+            time,
+            envelope,
+            amplitude,
+            drag_coef,
+            freq,
+            chirp_rate,
+            phase,
+        } => (
+            "pulse", time, envelope, amplitude, drag_coef, freq, phase, chirp_rate,
+        )
+            .into_py(py),
+    }
+}
+
+/// Pulses of a channel returned by :func:`compile_timeline`.
+///
+/// Each pulse is a tuple ``(time, envelope, amplitude, drag, freq,
+/// chirp_rate)``, where `time` is relative to the start of the schedule,
+/// without the channel delay, and `envelope` is the index into `envelopes`.
+/// The value of the pulse at time ``t`` is
+///
+/// .. code-block:: python
+///
+///     (amplitude * e(t - time) + drag * de/dt(t - time)) * exp(2j * pi * phi)
+///     phi = freq * (t - time) + chirp_rate * (t - center) ** 2 / 2
+///
+/// where ``e`` is the envelope and ``center`` the middle of the pulse.
+/// `amplitude` and `drag` are complex and include the phase of the carrier at
+/// `time` without the chirp, and `freq` is the total frequency of the pulse at
+/// its middle. The pulses are sorted by time.
+///
+/// Attributes:
+///     envelopes (list[tuple[str | None, float, float]]): Distinct envelopes
+///         as ``(shape_id, width, plateau)``, like in :class:`PulseStats`.
+///     envelope_samples (list[numpy.ndarray] | None): Envelopes sampled at
+///         `envelope_rate` starting at 0, or ``None`` if `envelope_rate` is not
+///         given.
+///     pulses (list[tuple[float, int, complex, complex, float, float]]): Pulses
+///         of the channel.
+#[pyclass(get_all, frozen)]
+#[derive(Debug)]
+struct PulseTimeline {
+    envelopes: Vec<(Option<ShapeId>, Time, Time)>,
+    envelope_samples: Option<Vec<Py<PyArray1<f64>>>>,
+    pulses: Vec<PyObject>,
+}
+
+/// Compile a schedule to a pulse-level timeline of each channel.
+///
+/// Unlike :func:`generate_waveforms`, the pulses are not sampled, so the
+/// result can be used by simulators with their own time steps. Crosstalk and
+/// the post-processing of the channels are not applied. Arguments have the
+/// same meaning as in :func:`generate_waveforms`.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     envelope_rate (float | None): Sample rate of
+///         :attr:`PulseTimeline.envelope_samples`. Default is ``None``.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+/// Returns:
+///     Dict[str, PulseTimeline]: Pulses of the channels.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    envelope_rate=None,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn compile_timeline(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    envelope_rate: Option<Frequency>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+) -> PyResult<HashMap<ChannelId, PulseTimeline>> {
+    let _span = tracing::info_span!("compile_timeline").entered();
+    ensure_numpy(py)?;
+    if envelope_rate.is_some_and(|r| !(r.value().is_finite() && r.value() > 0.0)) {
+        return Err(PyValueError::new_err(
+            "envelope_rate should be positive and finite.",
+        ));
+    }
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    let pulse_lists = build_pulse_lists(py, &schedule, executor)?;
+    let shape_ids = shapes
+        .iter()
+        .map(|(n, s)| Ok((Shape::get_rust_shape(s.bind(py))?, n.clone())))
+        .collect::<PyResult<HashMap<_, _>>>()?;
+    pulse_lists
+        .iter()
+        .map(|(n, list)| {
+            let mut envelope_ids = HashMap::new();
+            let mut envelopes = vec![];
+            let pulses = list
+                .timeline()
+                .into_iter()
+                .map(|p| {
+                    let envelope = *envelope_ids.entry(p.envelope).or_insert_with(|| {
+                        envelopes.push(p.envelope);
+                        envelopes.len() - 1
+                    });
+                    let amplitude =
+                        PyComplex::from_doubles_bound(py, p.amplitude.re, p.amplitude.im);
+                    let drag = PyComplex::from_doubles_bound(py, p.drag.re, p.drag.im);
+                    (p.time, envelope, amplitude, drag, p.freq, p.chirp_rate).into_py(py)
+                })
+                .collect();
+            let envelope_samples = envelope_rate
+                .map(|rate| {
+                    envelopes
+                        .iter()
+                        .map(|e| {
+                            let samples = pulse::sample_envelope_at(e, rate)?;
+                            Ok(PyArray1::from_slice_bound(py, &samples).unbind())
+                        })
+                        .collect::<PyResult<_>>()
+                })
+                .transpose()?;
+            let envelopes = envelopes
+                .iter()
+                .map(|e| {
+                    let shape_id = e.shape().and_then(|s| shape_ids.get(s)).cloned();
+                    (shape_id, e.width(), e.plateau())
+                })
+                .collect();
+            let timeline = PulseTimeline {
+                envelopes,
+                envelope_samples,
+                pulses,
+            };
+            Ok((n.clone(), timeline))
+        })
+        .collect()
+}
+
+/// Pulse returned by :func:`generate_pulse_table`.
+///
+/// The parameters are those of the :class:`Play` element after layout. The
+/// carrier of the pulse is ``exp(2j * pi * (freq * (t - time) + phase))``.
+///
+/// Attributes:
+///     channel (str): Channel of the pulse.
+///     time (float): Start of the pulse relative to the start of the schedule,
+///         without the channel delay.
+///     shape_id (str | None): Shape of the rising edge.
+///     width (float): Width of the rising edge, scaled by the enclosing
+///         :class:`Scale` elements.
+///     fall_shape_id (str | None): Shape of the falling edge of asymmetric
+///         pulses.
+///     fall_width (float | None): Width of the falling edge of asymmetric
+///         pulses.
+///     plateau (float): Plateau of the pulse, including the stretch of
+///         flexible pulses.
+///     amplitude (float): Amplitude including `amp_scales` and `scales`.
+///     drag_coef (float): DRAG coefficient.
+///     freq (float): Total frequency of the carrier at the middle of the
+///         pulse.
+///     chirp_rate (float): Rate of the linear frequency sweep, see
+///         :class:`Play`.
+///     phase (float): Phase of the carrier at `time` in cycles, or radians as
+///         set by :func:`set_phase_unit`, including the frame of the channel
+///         but not the chirp.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct ScheduledPulse {
+    channel: ChannelId,
+    time: Time,
+    shape_id: Option<ShapeId>,
+    width: Time,
+    fall_shape_id: Option<ShapeId>,
+    fall_width: Option<Time>,
+    plateau: Time,
+    amplitude: Amplitude,
+    drag_coef: f64,
+    freq: Frequency,
+    chirp_rate: ChirpRate,
+    phase: Phase,
+}
+
+/// Generate a table of the scheduled pulses instead of waveforms.
+///
+/// Each executed :class:`Play` element becomes one row, without merging the
+/// pulses at the same time. This suits hardware with native pulse queues.
+/// Arguments have the same meaning as in :func:`generate_waveforms`.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+/// Returns:
+///     list[ScheduledPulse]: Pulses of all channels sorted by time.
+/// Raises:
+///     ValueError: If some input is invalid.
+///     TypeError: If some input has an invalid type.
+///     ScheduleError: If the schedule cannot be executed.
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    *,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn generate_pulse_table(
+    py: Python,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+) -> PyResult<Vec<ScheduledPulse>> {
+    let _span = tracing::info_span!("generate_pulse_table").entered();
+    let schedule = extract_schedule(schedule)?;
+    let schedule = resolve_schedule_aliases(py, &schedule, aliases, &channels)?;
+    let schedule = optimize_schedule(py, &schedule, optimize, amp_tolerance)?;
+    let mut executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        amp_scales,
+        scales,
+        mute_channels,
+    )?;
+    executor.record_pulse_table();
+    py.allow_threads(|| executor.execute(&schedule))
+        .map_err(|e| schedule_error(py, e))?;
+    Ok(executor
+        .into_pulse_table()
+        .into_iter()
+        .map(|p| ScheduledPulse {
+            channel: p.channel,
+            time: p.time,
+            shape_id: p.shape_id,
+            width: p.width,
+            fall_shape_id: p.fall_shape_id,
+            fall_width: p.fall_width,
+            plateau: p.plateau,
+            amplitude: p.amplitude,
+            drag_coef: p.drag_coef,
+            freq: p.freq,
+            chirp_rate: p.chirp_rate,
+            phase: p.phase,
+        })
+        .collect())
+}
+
+/// Amplitude statistics of a waveform.
+///
+/// All rows of the waveform, e.g. both I and Q, are counted together.
+///
+/// Attributes:
+///     peak (float): Maximum absolute value of the samples.
+///     rms (float): Root mean square of the samples.
+///     papr (float): Peak-to-average power ratio ``(peak / rms)**2``. ``nan``
+///         if the waveform is all zeros.
+///     histogram (numpy.ndarray): Sample counts of the histogram bins.
+///     bin_edges (numpy.ndarray): Edges of the histogram bins, with length
+///         ``len(histogram) + 1``.
+#[pyclass(get_all, frozen)]
+#[derive(Debug, Clone)]
+struct WaveformStats {
+    peak: f64,
+    rms: f64,
+    papr: f64,
+    histogram: Py<PyArray1<u64>>,
+    bin_edges: Py<PyArray1<f64>>,
+}
+
+/// Compute amplitude statistics of generated waveforms.
+///
+/// The waveforms are read in place, so this is cheap even for long
+/// waveforms. It helps to choose the ranges of amplifiers and DACs.
+///
+/// Args:
+///     waveforms (Mapping[str, numpy.ndarray]): Waveforms returned by
+///         :func:`generate_waveforms` or :func:`generate_sweep`. The arrays
+///         should have dtype ``float64``.
+///     bins (int): Number of histogram bins. Default is 64.
+///     range (tuple[float, float] | None): Range of the histogram. Samples
+///         outside the range are not counted. Default is ``(-peak, peak)`` of
+///         each waveform, or ``(-1, 1)`` if the waveform is all zeros.
+/// Returns:
+///     Dict[str, WaveformStats]: Statistics of the waveforms.
+/// Raises:
+///     ValueError: If `bins` is zero or `range` is invalid.
+///     TypeError: If some array does not have dtype ``float64``.
+#[pyfunction]
+#[pyo3(signature = (waveforms, *, bins=64, range=None))]
+fn waveform_stats(
+    py: Python,
+    waveforms: HashMap<ChannelId, PyReadonlyArrayDyn<f64>>,
+    bins: usize,
+    range: Option<(f64, f64)>,
+) -> PyResult<HashMap<ChannelId, WaveformStats>> {
+    if bins == 0 {
+        return Err(PyValueError::new_err("bins should be at least 1."));
+    }
+    if let Some((lo, hi)) = range {
+        if !(lo.is_finite() && hi.is_finite() && lo < hi) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid range ({}, {})",
+                lo, hi
+            )));
+        }
+    }
+    let views: Vec<_> = waveforms.iter().map(|(n, w)| (n, w.as_array())).collect();
+    let stats: Vec<_> = py.allow_threads(|| {
+        views
+            .into_par_iter()
+            .map(|(n, w)| (n, stats::WaveformStats::new(w, bins, range)))
+            .collect()
+    });
+    Ok(stats
+        .into_iter()
+        .map(|(n, s)| {
+            let stats = WaveformStats {
+                peak: s.peak(),
+                rms: s.rms(),
+                papr: s.papr(),
+                histogram: PyArray1::from_slice_bound(py, s.histogram()).unbind(),
+                bin_edges: s.bin_edges().into_pyarray_bound(py).unbind(),
+            };
+            (n.clone(), stats)
+        })
+        .collect())
+}
+
+type TimeSpan = (Time, Time);
+
+/// Measure the schedule without generating waveforms.
+///
+/// Only the layout of the schedule is computed, so no channel or shape
+/// configuration is needed. The busy span of a channel starts at the beginning
+/// of its first pulse and ends at the end of its last pulse. Phantom elements
+/// are ignored.
+///
+/// Args:
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+/// Returns:
+///     tuple[float, dict[str, tuple[float, float]]]: Total duration of the
+///         schedule and busy span ``(start, end)`` of each channel. Channels
+///         without pulses are omitted.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Play, Stack, measure_schedule
+///         schedule = Stack(duration=500e-9).with_children(
+///             Play("xy", "hann", 0.3, 100e-9),
+///         )
+///         duration, spans = measure_schedule(schedule)
+///         # duration == 500e-9, spans == {"xy": (400e-9, 500e-9)}
+#[pyfunction]
+fn measure_schedule(
+    py: Python,
+    schedule: &Bound<PyAny>,
+) -> PyResult<(Time, HashMap<ChannelId, TimeSpan>)> {
+    let schedule = extract_schedule(schedule)?;
+    Ok(py.allow_threads(|| {
+        let duration = schedule.measure();
+        let spans = executor::channel_spans(&schedule)
+            .into_iter()
+            .map(|(n, r)| (n, (r.start, r.start + r.span)))
+            .collect();
+        (duration, spans)
+    }))
+}
+
+/// Compute the area of a pulse envelope with unit amplitude.
+///
+/// The envelope is sampled in the same way as in :func:`generate_waveforms`
+/// with the pulse starting exactly at a sample point, so the result is
+/// consistent with the generated waveforms. This is useful for estimating the
+/// amplitude of a :math:`\pi` pulse.
+///
+/// Args:
+///     shape (Shape | None): Shape of the pulse. If ``None``, the pulse is a
+///         rectangular pulse.
+///     width (float): Width of the pulse.
+///     sample_rate (float): Sample rate of the channel.
+///     plateau (float): Plateau length of the pulse. Defaults to 0.
+///     drag_coef (float): Drag coefficient of the pulse. Defaults to 0.
+/// Returns:
+///     complex: Area of the envelope. The imaginary part is the contribution
+///         of the DRAG correction.
+/// Raises:
+///     ValueError: If some input is invalid.
+#[pyfunction]
+#[pyo3(signature = (shape, width, sample_rate, *, plateau=Time::ZERO, drag_coef=0.0))]
+fn envelope_area<'py>(
+    py: Python<'py>,
+    shape: Option<&Bound<'py, Shape>>,
+    width: Time,
+    sample_rate: Frequency,
+    plateau: Time,
+    drag_coef: f64,
+) -> PyResult<Bound<'py, PyComplex>> {
+    if !drag_coef.is_finite() {
+        return Err(PyValueError::new_err(format!(
+            "Invalid drag_coef {}",
+            drag_coef
+        )));
+    }
+    let envelope = extract_envelope(shape, width, plateau, sample_rate)?;
+    let area = pulse::envelope_area(&envelope, drag_coef, sample_rate)?;
+    Ok(PyComplex::from_doubles_bound(py, area.re, area.im))
+}
+
+/// Compute the full width at half maximum of a pulse envelope.
+///
+/// The envelope is sampled in the same way as in :func:`generate_waveforms`
+/// with the pulse starting exactly at a sample point. Half maximum crossings
+/// are linearly interpolated between samples.
+///
+/// Args:
+///     shape (Shape | None): Shape of the pulse. If ``None``, the pulse is a
+///         rectangular pulse.
+///     width (float): Width of the pulse.
+///     sample_rate (float): Sample rate of the channel.
+///     plateau (float): Plateau length of the pulse. Defaults to 0.
+/// Returns:
+///     float: Full width at half maximum of the envelope.
+/// Raises:
+///     ValueError: If some input is invalid.
+#[pyfunction]
+#[pyo3(signature = (shape, width, sample_rate, *, plateau=Time::ZERO))]
+fn envelope_fwhm(
+    shape: Option<&Bound<Shape>>,
+    width: Time,
+    sample_rate: Frequency,
+    plateau: Time,
+) -> PyResult<f64> {
+    let envelope = extract_envelope(shape, width, plateau, sample_rate)?;
+    Ok(pulse::envelope_fwhm(&envelope, sample_rate)?)
+}
+
+/// Shift a generated waveform by a number of samples.
+///
+/// This is a post step for quick alignment tests, e.g. between channels,
+/// without changing the delays of the channels and generating the waveforms
+/// again. The shift is applied to the final waveform including filters, and
+/// may be fractional or negative. The fractional part is interpolated with a
+/// windowed sinc kernel, so the waveform should have no content near the
+/// Nyquist frequency. Samples shifted in from beyond the ends hold the first
+/// or the last sample, which keeps offsets and :attr:`Padding.Hold` intact.
+///
+/// Args:
+///     waveform (array_like): Waveform of shape ``(n, length)`` as returned by
+///         :func:`generate_waveforms`.
+///     shift (float): Number of samples to delay the waveform. Negative
+///         values advance the waveform.
+///     half_width (int): Number of taps on each side of the interpolation
+///         kernel. Default is 16.
+/// Returns:
+///     numpy.ndarray: Shifted waveform of the same shape.
+/// Raises:
+///     ValueError: If `shift` is not finite or `half_width` is zero.
+/// Example:
+///     .. code-block:: python
+///
+///         result = generate_waveforms(channels, shapes, schedule)
+///         shifted = shift_waveform(result["z"], 0.5)
+#[pyfunction]
+#[pyo3(signature = (waveform, shift, *, half_width=16))]
+fn shift_waveform<'py>(
+    py: Python<'py>,
+    waveform: PyArrayLike2<'py, f64, AllowTypeChange>,
+    shift: f64,
+    half_width: usize,
+) -> PyResult<Bound<'py, PyArray2<f64>>> {
+    if !shift.is_finite() {
+        return Err(PyValueError::new_err(format!("Invalid shift {}", shift)));
+    }
+    if half_width == 0 {
+        return Err(PyValueError::new_err("half_width should be positive."));
+    }
+    let waveform = waveform.as_array();
+    let shifted = py.allow_threads(|| pulse::shift_waveform(waveform, shift, half_width));
+    Ok(shifted.into_pyarray_bound(py))
+}
+
+/// DAC codes and the value of one code.
+type QuantizedWaveform<'py> = (Bound<'py, PyArray2<i16>>, f64);
+
+/// Quantize waveforms to signed 16-bit DAC codes.
+///
+/// The full scale of each channel is mapped to code 32767 and the negative
+/// full scale to -32767. Samples beyond the full scale are an error unless
+/// `allow_clip` is ``True``, in which case they are clipped with a
+/// :class:`RuntimeWarning`.
+///
+/// Args:
+///     waveforms (Mapping[str, array_like]): Waveforms as returned by
+///         :func:`generate_waveforms`.
+///     full_scale (float | Mapping[str, float]): Full scale of all channels,
+///         or of each channel in `waveforms`, in the unit of the waveforms.
+///     dither (bool): Add triangular dither of one code peak before rounding.
+///         Default is ``False``.
+///     seed (int): Seed of the dither. The result is reproducible for the
+///         same seed. Default is 0.
+///     allow_clip (bool): Clip samples beyond the full scale instead of
+///         raising an error. Default is ``False``.
+/// Returns:
+///     Dict[str, tuple[numpy.ndarray, float]]: Codes of each channel with
+///         dtype ``int16`` and the value of one code, i.e. the full scale
+///         divided by 32767.
+/// Raises:
+///     ValueError: If some input is invalid, or a waveform exceeds the full
+///         scale and `allow_clip` is ``False``.
+/// Example:
+///     .. code-block:: python
+///
+///         result = generate_waveforms(channels, shapes, schedule)
+///         codes = quantize_waveforms(result, {"xy": 0.5, "z": 1.0})
+///         device.write(codes["xy"][0])
+#[pyfunction]
+#[pyo3(signature = (waveforms, full_scale, *, dither=false, seed=0, allow_clip=false))]
+fn quantize_waveforms<'py>(
+    py: Python<'py>,
+    waveforms: HashMap<ChannelId, PyArrayLike2<'py, f64, AllowTypeChange>>,
+    full_scale: &Bound<PyAny>,
+    dither: bool,
+    seed: u64,
+    allow_clip: bool,
+) -> PyResult<HashMap<ChannelId, QuantizedWaveform<'py>>> {
+    let full_scales: HashMap<ChannelId, f64> = match full_scale.extract::<f64>() {
+        Ok(value) => waveforms.keys().map(|n| (n.clone(), value)).collect(),
+        Err(_) => full_scale.extract()?,
+    };
+    let mut names: Vec<_> = waveforms.keys().collect();
+    names.sort_unstable_by_key(|n| n.to_string());
+    let mut result = HashMap::new();
+    for (i, n) in names.into_iter().enumerate() {
+        let value = *full_scales.get(n).ok_or_else(|| {
+            PyValueError::new_err(format!("Full scale of channel '{}' is not given.", n))
+        })?;
+        if !(value.is_finite() && value > 0.0) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid full scale for channel '{}': {}",
+                n, value
+            )));
+        }
+        let dither = dither.then(|| seed.wrapping_add(i as u64));
+        let quantized = generate::quantize(waveforms[n].as_array(), value, dither);
+        if quantized.clipped > 0 {
+            let msg = format!(
+                "{} samples of channel '{}' exceed the full scale {}",
+                quantized.clipped, n, value
+            );
+            if !allow_clip {
+                return Err(PyValueError::new_err(msg));
+            }
+            PyErr::warn_bound(py, &py.get_type_bound::<PyRuntimeWarning>(), &msg, 1)?;
+        }
+        let codes = quantized.codes.into_pyarray_bound(py);
+        result.insert(n.clone(), (codes, quantized.scale));
+    }
+    Ok(result)
+}
+
+fn extract_envelope(
+    shape: Option<&Bound<Shape>>,
+    width: Time,
+    plateau: Time,
+    sample_rate: Frequency,
+) -> PyResult<Envelope> {
+    if !(width.value().is_finite() && width >= Time::ZERO) {
+        return Err(PyValueError::new_err(format!("Invalid width {:?}", width)));
+    }
+    if !(plateau.value().is_finite() && plateau >= Time::ZERO) {
+        return Err(PyValueError::new_err(format!(
+            "Invalid plateau {:?}",
+            plateau
+        )));
+    }
+    if !(sample_rate.value().is_finite() && sample_rate.value() > 0.0) {
+        return Err(PyValueError::new_err(format!(
+            "Invalid sample_rate {:?}",
+            sample_rate
+        )));
+    }
+    let shape = shape.map(Shape::get_rust_shape).transpose()?;
+    Ok(Envelope::new(shape, width, plateau))
+}
+
+/// Generate integration weights of a labeled readout pulse.
+///
+/// The weights are the matched filter of the pulse, i.e. the complex conjugate
+/// of the pulse including its envelope, carrier and frame phase, sampled at
+/// the digitizer sample rate. The first weight corresponds to the start of the
+/// pulse. The start time is also returned so that the acquisition window can
+/// be configured from the same schedule as the stimulus.
+///
+/// Args:
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     label (str): Label of the readout :class:`Play` element. Exactly one
+///         non-phantom :class:`Play` element should have the label.
+///     sample_rate (float): Sample rate of the digitizer.
+///     length (int | None): Number of weights. Default is the number of
+///         samples covering the pulse.
+///     align_level (int): Time axis alignment granularity of the digitizer.
+///         Default is -10.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+/// Returns:
+///     tuple[float, numpy.ndarray]: Start time of the pulse and the complex
+///         weights with shape ``(length,)``.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid or the label does not match
+///         exactly one pulse.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulse cannot be sampled.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Channel, Hann, Play, Stack, integration_weights
+///         channels = {"ro": Channel(50e6, 2e9, 10000)}
+///         shapes = {"hann": Hann()}
+///         schedule = Stack(duration=2e-6).with_children(
+///             Play("ro", "hann", 0.1, 100e-9, plateau=1e-6, label="readout"),
+///         )
+///         start, weights = integration_weights(
+///             channels, shapes, schedule, "readout", 500e6
+///         )
+#[pyfunction]
+#[pyo3(signature = (
+    channels,
+    shapes,
+    schedule,
+    label,
+    sample_rate,
+    *,
+    length=None,
+    align_level=-10,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn integration_weights<'py>(
+    py: Python<'py>,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    label: Label,
+    sample_rate: Frequency,
+    length: Option<usize>,
+    align_level: i32,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+) -> PyResult<(Time, Bound<'py, PyArray1<Complex64>>)> {
+    ensure_numpy(py)?;
+    if !(sample_rate.value().is_finite() && sample_rate.value() > 0.0) {
+        return Err(PyValueError::new_err(format!(
+            "Invalid sample_rate {:?}",
+            sample_rate
+        )));
+    }
+    let schedule = extract_schedule(schedule)?;
+    let spans: Vec<_> = executor::play_spans(&schedule)
+        .filter(|(item, ..)| item.common.label() == Some(&label))
+        .map(|(_, play, time_range)| (play.channel_id().clone(), time_range))
+        .collect();
+    let [(channel, time_range)] = spans.as_slice() else {
+        return Err(PyValueError::new_err(format!(
+            "Expected exactly one Play element with label '{}', found {}.",
+            label,
+            spans.len()
+        )));
+    };
+    let mut executor = build_executor(
+        py,
+        &channels,
+        &shapes,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        None,
+        None,
+        None,
+    )?;
+    executor.solo_label(label);
+    let pulse_lists = build_pulse_lists(py, &schedule, executor)?;
+    let n_pulse = ((time_range.span.value() - time_tolerance.value()) * sample_rate.value())
+        .ceil()
+        .max(0.0) as usize;
+    let length = length.unwrap_or(n_pulse);
+    // Extra samples in case the envelope is longer than the pulse due to
+    // rounding.
+    let mut waveform = Array2::zeros((2, length.max(n_pulse + 2)));
+    let mut sampler = Sampler::new(pulse_lists);
+    sampler.add_channel(
+        channel.clone(),
+        waveform.view_mut(),
+        sample_rate,
+        -time_range.start,
+        align_level,
+        Overflow::Error,
+    );
+    py.allow_threads(|| sampler.sample(time_tolerance))
+        .map_err(|e| sampling_error(py, e, &schedule, time_tolerance))?;
+    let weights = waveform
+        .columns()
+        .into_iter()
+        .take(length)
+        .map(|c| Complex64::new(c[0], -c[1]))
+        .collect();
+    Ok((time_range.start, PyArray1::from_vec_bound(py, weights)))
+}
+
+/// Load an experiment description from a YAML or TOML file.
+///
+/// The document describes the channels, shapes and schedule with the same
+/// arguments as the Python classes. See :doc:`experiment` for the schema.
+///
+/// Args:
+///     path (str | os.PathLike[str]): Path of the document.
+///     format (str | None): 'yaml' or 'toml'. Default is ``None``, which infers
+///         the format from the file extension.
+/// Returns:
+///     tuple[dict[str, Channel], dict[str, Shape], Element]: Channels, shapes
+///         and schedule which can be passed to :func:`generate_waveforms`.
+/// Raises:
+///     OSError: If the file cannot be read.
+///     ValueError: If the document does not follow the schema or some value
+///         is invalid.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import generate_waveforms, load_experiment
+///         channels, shapes, schedule = load_experiment("experiment.yaml")
+///         result = generate_waveforms(channels, shapes, schedule)
+#[pyfunction]
+#[pyo3(signature = (path, *, format=None))]
+fn load_experiment<'py>(
+    py: Python<'py>,
+    path: PathBuf,
+    format: Option<&str>,
+) -> PyResult<(Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyAny>)> {
+    let format = match format.or_else(|| path.extension().and_then(|e| e.to_str())) {
+        Some("yaml" | "yml") => experiment::Format::Yaml,
+        Some("toml") => experiment::Format::Toml,
+        _ => {
+            return Err(PyValueError::new_err(
+                "Unknown format, expected 'yaml' or 'toml'.",
+            ))
+        }
+    };
+    let text = std::fs::read_to_string(&path)?;
+    let desc = experiment::ExperimentDesc::parse(&text, format)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    desc.to_py(py)
+}
+
+/// Directory of generated waveforms shared between processes.
+///
+/// Each call to :meth:`save` creates a subdirectory with the waveforms as
+/// ``waveforms/<channel>.npy``, the schedule as ``schedule.json`` and the
+/// channel configurations, user metadata and bosing version in ``meta.json``.
+/// The result is written to a temporary directory first and renamed into
+/// place, so concurrent writers, also in other processes, never overwrite
+/// each other and incomplete results are never listed.
+///
+/// Args:
+///     path (str | os.PathLike[str]): Root directory of the store. It is
+///         created if it does not exist.
+/// Raises:
+///     OSError: If the directory cannot be created.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import ResultStore, generate_waveforms
+///         store = ResultStore("results")
+///         waveforms = generate_waveforms(channels, shapes, schedule)
+///         store.save(waveforms, schedule=schedule, channels=channels)
+#[pyclass(frozen)]
+struct ResultStore(store::ResultStore);
+
+#[pymethods]
+impl ResultStore {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        Ok(Self(store::ResultStore::new(path)?))
+    }
+
+    /// Root directory of the store.
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.0.root().to_owned()
+    }
+
+    /// Save a result to the store.
+    ///
+    /// Args:
+    ///     waveforms (Mapping[str, numpy.ndarray]): Waveforms of the channels,
+    ///         e.g. the result of :func:`generate_waveforms`.
+    ///     name (str | None): Name of the result. It may contain letters,
+    ///         digits, ``-``, ``_`` and ``.`` and must not start with ``.``.
+    ///         ``None`` picks the next free sequence number like ``000042``.
+    ///     schedule (Element | None): Schedule of the waveforms.
+    ///     channels (Mapping[str, Channel] | None): Channel configurations.
+    ///     metadata (object): JSON serializable user metadata.
+    /// Returns:
+    ///     str: Directory of the result.
+    /// Raises:
+    ///     FileExistsError: If a result named `name` already exists.
+    ///     ValueError: If `name` or a channel name is not a valid file name.
+    ///     OSError: If the result cannot be written.
+    #[pyo3(signature = (waveforms, *, name=None, schedule=None, channels=None, metadata=None))]
+    fn save(
+        &self,
+        py: Python,
+        waveforms: HashMap<ChannelId, PyReadonlyArray2<f64>>,
+        name: Option<&str>,
+        schedule: Option<&Bound<Element>>,
+        channels: Option<HashMap<ChannelId, Channel>>,
+        metadata: Option<&Bound<PyAny>>,
+    ) -> PyResult<PathBuf> {
+        let waveforms = waveforms
+            .iter()
+            .map(|(n, w)| (n.clone(), w.as_array()))
+            .collect();
+        let schedule = schedule
+            .map(|s| schedule::to_json(&s.get().0))
+            .transpose()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let channels = channels
+            .map(|channels| {
+                channels
+                    .iter()
+                    .map(|(n, c)| Ok((n.to_string(), c.to_json_value(py)?)))
+                    .collect::<PyResult<serde_json::Map<_, _>>>()
+                    .map(Into::into)
+            })
+            .transpose()?;
+        let metadata = metadata
+            .map(|m| -> PyResult<_> {
+                let text: String = py
+                    .import_bound("json")?
+                    .call_method1("dumps", (m,))?
+                    .extract()?;
+                serde_json::from_str(&text).map_err(|e| PyValueError::new_err(e.to_string()))
+            })
+            .transpose()?;
+        let record = store::Record {
+            waveforms,
+            schedule,
+            channels,
+            metadata,
+        };
+        self.0.save(name, &record).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::InvalidInput {
+                PyValueError::new_err(e.to_string())
+            } else {
+                e.into()
+            }
+        })
+    }
+
+    /// Names of the results in the store, sorted.
+    ///
+    /// Returns:
+    ///     list[str]: Names of the complete results.
+    /// Raises:
+    ///     OSError: If the directory cannot be read.
+    fn names(&self) -> PyResult<Vec<String>> {
+        Ok(self.0.names()?)
+    }
+}
+
+/// HTTP server streaming generated waveforms to non-Python clients.
+///
+/// Only available if the package is built with the ``serve`` feature.
+///
+/// ``POST /generate`` accepts an experiment document in JSON with the same
+/// schema as :func:`load_experiment`. The waveforms are streamed back as
+/// newline-delimited JSON, each line being a chunk of one channel like
+/// ``{"channel": "xy", "offset": 0, "data": [[...], [...]]}``. Channels are
+/// sent in the order of their names. Invalid documents are answered with
+/// status 400 and the error message, and bodies larger than 64 MiB with status
+/// 413.
+///
+/// Args:
+///     address (str): Address to listen on, e.g. ``"127.0.0.1:8000"``. Use port
+///         0 to pick a free port.
+///     chunk_size (int): Maximum number of samples in a chunk. Default is 4096.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+/// Raises:
+///     OSError: If the address cannot be bound.
+/// Example:
+///     .. code-block:: python
+///
+///         from bosing import Server
+///         server = Server("127.0.0.1:8000")
+///         server.serve()
+#[cfg(feature = "serve")]
+#[pyclass(frozen)]
+struct Server {
+    server: tiny_http::Server,
+    chunk_size: usize,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+}
+
+#[cfg(feature = "serve")]
+#[pymethods]
+impl Server {
+    #[new]
+    #[pyo3(signature = (
+        address,
+        *,
+        chunk_size=4096,
+        time_tolerance=Time::new(1e-12).unwrap(),
+        amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+        allow_oversize=false,
+    ))]
+    fn new(
+        address: &str,
+        chunk_size: usize,
+        time_tolerance: Time,
+        amp_tolerance: Amplitude,
+        allow_oversize: bool,
+    ) -> PyResult<Self> {
+        if chunk_size == 0 {
+            return Err(PyValueError::new_err("chunk_size should be at least 1."));
+        }
+        let server = tiny_http::Server::http(address)
+            .map_err(|e| pyo3::exceptions::PyOSError::new_err(e.to_string()))?;
+        Ok(Self {
+            server,
+            chunk_size,
+            time_tolerance,
+            amp_tolerance,
+            allow_oversize,
+        })
+    }
+
+    /// str: Address the server is listening on.
+    #[getter]
+    fn address(&self) -> String {
+        self.server.server_addr().to_string()
+    }
+
+    /// Handle requests until interrupted.
+    ///
+    /// The GIL is released while waiting for requests and sending responses.
+    ///
+    /// Args:
+    ///     max_requests (int | None): Return after handling this number of
+    ///         requests. Default is ``None``, which serves until
+    ///         ``KeyboardInterrupt``.
+    #[pyo3(signature = (max_requests=None))]
+    fn serve(&self, py: Python, max_requests: Option<usize>) -> PyResult<()> {
+        let mut handled = 0;
+        while !matches!(max_requests, Some(m) if handled >= m) {
+            let request = py.allow_threads(|| {
+                self.server
+                    .recv_timeout(std::time::Duration::from_millis(100))
+            })?;
+            py.check_signals()?;
+            if let Some(request) = request {
+                self.handle(py, request);
+                handled += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serve")]
+impl Server {
+    fn handle(&self, py: Python, mut request: tiny_http::Request) {
+        // Failing to respond means the client is gone, which should not stop
+        // the server.
+        let desc = match py.allow_threads(|| serve::parse_request(&mut request)) {
+            Ok(desc) => desc,
+            Err(e) => {
+                let _ = serve::respond_request_error(request, &e);
+                return;
+            }
+        };
+        match self.generate(py, &desc) {
+            Ok(waveforms) => {
+                let _ = py.allow_threads(|| {
+                    serve::respond_waveforms(request, waveforms, self.chunk_size)
+                });
+            }
+            Err(e) => {
+                let _ = serve::respond_error(request, tiny_http::StatusCode(400), &e.to_string());
+            }
+        }
+    }
+
+    fn generate(
+        &self,
+        py: Python,
+        desc: &experiment::ExperimentDesc,
+    ) -> PyResult<Vec<serve::Output>> {
+        let (channels, shapes, schedule) = desc.to_py(py)?;
+        let channels: HashMap<ChannelId, Channel> = channels.extract()?;
+        let mut metadata = HashMap::new();
+        for (name, channel) in &channels {
+            if let Some(m) = channel.metadata_json(py)? {
+                metadata.extend(channel.outputs(name).map(|n| (n, m.clone())));
+            }
+        }
+        let waveforms = sample_waveforms(
+            py,
+            channels,
+            shapes.extract()?,
+            &extract_schedule(&schedule)?,
+            self.time_tolerance,
+            self.amp_tolerance,
+            self.allow_oversize,
+            None,
+            None,
+            None,
+            None,
+            None,
+            "f64",
+            None,
+        )?;
+        let mut waveforms: Vec<_> = waveforms
+            .into_iter()
+            .map(|(n, w)| serve::Output {
+                name: n.to_string(),
+                waveform: w.bind(py).to_owned_array(),
+                metadata: metadata.remove(&n),
+            })
+            .collect();
+        waveforms.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(waveforms)
+    }
+}
+
+/// Export an interactive HTML preview of the schedule and its waveforms.
+///
+/// The file contains a zoomable timeline of the :class:`Play` elements and the
+/// generated waveforms as an embedded Vega-Lite chart. Channel-less
+/// :class:`Barrier` children of :class:`Grid` elements are drawn as dashed
+/// vertical lines at their start and end. The waveforms are
+/// decimated to at most `max_points` points per component, keeping the
+/// minimum and maximum of each bucket. No display server is needed to create
+/// the file.
+///
+/// .. note::
+///
+///     The Vega libraries are not embedded but loaded from
+///     ``cdn.jsdelivr.net`` when the file is opened, so viewing the preview
+///     needs network access. It renders blank offline or in viewers which
+///     block external scripts.
+///
+/// Args:
+///     path (str | os.PathLike[str]): Path of the HTML file.
+///     channels (Mapping[str, Channel]): Information of the channels.
+///     shapes (Mapping[str, Shape]): Shapes used in the schedule.
+///     schedule (Element | Iterable[AbsoluteEntry | Element | tuple[float, Element]]):
+///         Root element of the schedule, or multiple roots with time offsets.
+///     title (str | None): Title of the preview. Default is ``None``.
+///     max_points (int): Maximum number of points of each waveform component.
+///         Default is 2000.
+///     time_tolerance (float): Tolerance for time comparison. Default is 1e-12.
+///     amp_tolerance (float): Tolerance for amplitude comparison. Default is
+///         0.1 / 2^16.
+///     allow_oversize (bool): Allow oversize elements. Default is ``False``.
+///     crosstalk (tuple[array_like, Sequence[str]] | tuple[array_like, Sequence[str], array_like] | None):
+///         Crosstalk matrix with corresponding channel ids. An optional third
+///         matrix is applied to the DRAG component instead, e.g. an identity
+///         matrix excludes DRAG from crosstalk. A 3D array of shape
+///         ``(n, n, taps)`` gives a FIR kernel for each pair instead of a
+///         constant, which can't be combined with a DRAG matrix. A sequence
+///         of ``(start_time, matrix)`` switches the matrix during the
+///         schedule, each matrix applies to the pulses starting from its
+///         start time. Default is ``None``.
+///     optimize (bool | Sequence[str]): Optimization passes applied to the
+///         schedule before execution. Default is ``False``.
+///     amp_scales (Mapping[str, float] | None): Amplitude scale factors of
+///         :class:`Play` elements by their labels. Default is ``None``.
+///     scales (Mapping[str, float] | None): Amplitude scale factors of all
+///         :class:`Play` elements on each channel, e.g. for calibration or unit
+///         conversion. The factors multiply with `amp_scales` and are applied
+///         before the crosstalk matrix. Default is ``None``.
+///     mute_channels (Sequence[str] | None): Channels whose :class:`Play`
+///         elements are treated as phantom. Default is ``None``.
+/// Raises:
+///     ImportError: If numpy is not installed.
+///     ValueError: If some input is invalid.
+///     OSError: If the file cannot be written.
+///     ScheduleError: If the schedule cannot be executed.
+///     SamplingError: If the pulses cannot be sampled to the waveforms.
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    channels,
+    shapes,
+    schedule,
+    *,
+    title=None,
+    max_points=2000,
+    time_tolerance=Time::new(1e-12).unwrap(),
+    amp_tolerance=Amplitude::new(0.1 / 2f64.powi(16)).unwrap(),
+    allow_oversize=false,
+    crosstalk=None,
+    optimize=None,
+    amp_scales=None,
+    scales=None,
+    mute_channels=None,
+    aliases=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn export_html(
+    py: Python,
+    path: PathBuf,
+    channels: HashMap<ChannelId, Channel>,
+    shapes: HashMap<ShapeId, Py<Shape>>,
+    schedule: &Bound<PyAny>,
+    title: Option<String>,
+    max_points: usize,
+    time_tolerance: Time,
+    amp_tolerance: Amplitude,
+    allow_oversize: bool,
+    crosstalk: Option<CrosstalkArg>,
+    optimize: Option<&Bound<PyAny>>,
+    amp_scales: Option<HashMap<Label, f64>>,
+    scales: Option<HashMap<ChannelId, f64>>,
+    mute_channels: Option<Vec<ChannelId>>,
+    aliases: Option<HashMap<ChannelId, ChannelId>>,
+) -> PyResult<()> {
+    if max_points < 2 {
+        return Err(PyValueError::new_err("max_points should be at least 2."));
+    }
+    let root = extract_schedule(schedule)?;
+    let root = resolve_schedule_aliases(py, &root, aliases, &channels)?;
+    let waveforms = sample_waveforms(
+        py,
+        channels.clone(),
+        shapes,
+        &root,
+        time_tolerance,
+        amp_tolerance,
+        allow_oversize,
+        crosstalk,
+        optimize,
+        amp_scales,
+        scales,
+        mute_channels,
+        "f64",
+        None,
+    )?;
+    let blocks: Vec<_> = executor::play_spans(&root)
+        .map(|(item, play, time_range)| preview::Block {
+            channel: play.channel_id().to_string(),
+            label: item.common.label().map(ToString::to_string),
+            start: time_range.start.value(),
+            end: (time_range.start + time_range.span).value(),
+        })
+        .collect();
+    let sync_lines: Vec<_> = executor::grid_barriers(&root)
+        .flat_map(|(item, time_range)| {
+            let label = item.common.label().map(ToString::to_string);
+            let start = time_range.start.value();
+            let end = (time_range.start + time_range.span).value();
+            let times = if end > start {
+                vec![start, end]
+            } else {
+                vec![start]
+            };
+            times.into_iter().map(move |time| preview::SyncLine {
+                label: label.clone(),
+                time,
+            })
+        })
+        .collect();
+    let mut names: Vec<_> = channels.keys().collect();
+    names.sort_by_key(|n| n.to_string());
+    let mut traces = vec![];
+    for n in names {
+        let c = &channels[n];
+        let w = waveforms[n].bind(py).readonly();
+        let w = w.as_array();
+        let components: &[_] = if c.is_real { &[""] } else { &[".I", ".Q"] };
+        for (row, suffix) in w.outer_iter().zip(components) {
+            traces.push(preview::Trace::new(
+                format!("{}{}", n, suffix),
+                row,
+                c.sample_rate.dt().value(),
+                -c.total_delay().value(),
+                max_points,
+            ));
+        }
+    }
+    let title = title.unwrap_or_else(|| "Bosing preview".into());
+    let html = preview::render(&title, &blocks, &sync_lines, &traces);
+    std::fs::write(path, html)?;
+    Ok(())
+}
+
+/// Register the presets of an AWG model for :meth:`Channel.for_awg`.
+///
+/// An existing preset of the same model, including a built-in one, is
+/// replaced. The model name is case-insensitive.
+///
+/// Args:
+///     model (str): AWG model.
+///     preset (AwgPreset): Presets of the model.
+/// Example:
+///     .. code-block:: python
+///
+///         register_awg("MyAWG", AwgPreset(5e9, granularity=32))
+///         channel = Channel.for_awg("myawg", 10000)
+#[pyfunction]
+fn register_awg(model: &str, preset: AwgPreset) {
+    awg_presets_registry()
+        .lock()
+        .expect("Lock should not be poisoned")
+        .insert(model.to_ascii_uppercase(), preset);
+}
+
+/// Registered AWG presets.
+///
+/// Returns:
+///     dict[str, AwgPreset]: Presets by upper-case model name.
+#[pyfunction]
+fn awg_presets() -> HashMap<String, AwgPreset> {
+    awg_presets_registry()
+        .lock()
+        .expect("Lock should not be poisoned")
+        .clone()
+}
+
+/// Set the directory of the persistent envelope cache.
+///
+/// Sampled envelopes of :class:`Interp` shapes are stored in the directory so
+/// that later runs, also in other processes, can skip resampling. Entries
+/// created by other versions of bosing are ignored. When the total size
+/// exceeds `max_size`, the oldest entries are removed.
+///
+/// Args:
+///     path (str | os.PathLike[str] | None): Cache directory. It is created if
+///         it does not exist. ``None`` disables the cache.
+///     max_size (int): Maximum total size of the cache in bytes. Default is
+///         256 MiB.
+/// Raises:
+///     OSError: If the directory cannot be created or read.
+#[pyfunction]
+#[pyo3(signature = (path, *, max_size=256 * 1024 * 1024))]
+fn set_envelope_cache(path: Option<PathBuf>, max_size: u64) -> PyResult<()> {
+    let cache = path
+        .map(|p| disk_cache::DiskCache::new(p, max_size))
+        .transpose()?;
+    disk_cache::set_global(cache);
+    Ok(())
+}
+
+/// Remove all entries of the persistent envelope cache.
+///
+/// Does nothing if the cache is disabled.
+///
+/// Raises:
+///     OSError: If the entries cannot be removed.
+#[pyfunction]
+fn clear_envelope_cache() -> PyResult<()> {
+    disk_cache::clear_global()?;
+    Ok(())
+}
+
+/// Enable or disable the layout check before waveform generation.
+///
+/// When enabled, every container is checked to arrange its children across the
+/// duration it measured before pulses are generated. A violation indicates a bug in
+/// the layout and raises :class:`ScheduleError` with code ``"layout_drift"``
+/// and the path to the offending element in ``details["path"]``. The check is
+/// disabled by default in release builds.
+///
+/// Args:
+///     enabled (bool): Whether to enable the check.
+#[pyfunction]
+fn set_layout_check(enabled: bool) {
+    executor::set_check_layout(enabled);
+}
+
+/// Set the unit of phases in the Python API.
+///
+/// Phases are in cycles by default, so that :math:`0.5` means :math:`\pi`
+/// radians. With ``"radians"``, all phases given to and returned by bosing are
+/// in radians instead, e.g. the `phase` of :class:`Play`, :class:`ShiftPhase`
+/// and :class:`SetPhase`, the `phase_shifts` of :func:`generate_sweep` and the
+/// phases of :func:`compile_frames`. Frequencies and the `rate` of
+/// :class:`PhaseRamp` are always in Hz. Serialized schedules, e.g.
+/// :meth:`Element.to_json` and experiment documents, always store phases in
+/// cycles.
+///
+/// The setting applies to the whole module and takes effect when values are
+/// converted, so elements created before a change keep their phase.
+///
+/// Args:
+///     unit (str): ``"cycles"`` or ``"radians"``.
+/// Raises:
+///     ValueError: If `unit` is invalid.
+/// Example:
+///     .. code-block:: python
+///
+///         bosing.set_phase_unit("radians")
+///         bosing.ShiftPhase("xy", np.pi / 2)
+#[pyfunction]
+fn set_phase_unit(unit: &str) -> PyResult<()> {
+    let radians = match unit {
+        "cycles" => false,
+        "radians" => true,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid phase unit '{}'. Must be 'cycles' or 'radians'",
+                unit
+            )))
+        }
+    };
+    quant::set_phase_in_radians(radians);
+    Ok(())
+}
+
+/// Get the unit of phases in the Python API set by :func:`set_phase_unit`.
+///
+/// Returns:
+///     str: ``"cycles"`` or ``"radians"``.
+#[pyfunction]
+fn get_phase_unit() -> &'static str {
+    if quant::phase_in_radians() {
+        "radians"
+    } else {
+        "cycles"
+    }
+}
+
+/// Configure diagnostic logging of waveform generation.
+///
+/// The generation steps, e.g. measuring and arranging the schedule, building
+/// the pulse lists and sampling each channel, are recorded with their
+/// durations, as well as the errors. This helps to diagnose slow or failing
+/// generations after the fact. Logging is disabled by default, and calling
+/// this function without `file` and `python` disables it again.
+///
+/// Records from worker threads are forwarded to Python when the GIL is
+/// available again, at the latest when the generation function returns.
+///
+/// Args:
+///     file (str | os.PathLike[str] | None): Append the records to this file.
+///         Default is ``None``.
+///     python (bool): Forward the records to the ``"bosing"`` logger of the
+///         :mod:`logging` module. Default is ``False``.
+///     level (str): Minimum level of the records, one of 'error', 'warn',
+///         'info', 'debug' and 'trace'. Default is 'info'.
+/// Raises:
+///     OSError: If the file cannot be opened.
+///     ValueError: If `level` is invalid.
+#[pyfunction]
+#[pyo3(signature = (file=None, *, python=false, level="info"))]
+fn set_logging(file: Option<PathBuf>, python: bool, level: &str) -> PyResult<()> {
+    let level = tracing::Level::from_str(level)
+        .map_err(|_| PyValueError::new_err(format!("Invalid log level '{}'", level)))?;
+    logging::configure(file.as_deref(), python, level)?;
+    Ok(())
+}
+
+/// Information about the build and the instruction set used by the kernels.
+///
+/// The mixing, IIR filtering and short FIR filtering kernels detect the CPU
+/// features at runtime, so the same wheel uses the best available instruction
+/// set on each machine. Crosstalk kernels always use the baseline instruction
+/// set, and long FIR filters use the FFT convolution of ``rustfft``, which does
+/// its own detection.
+///
+/// Returns:
+///     dict[str, object]: Build information with keys
+///
+///     - ``"version"`` (str): Version of the package.
+///     - ``"target_arch"`` (str): CPU architecture of the build, e.g.
+///       ``"x86_64"``.
+///     - ``"target_os"`` (str): Operating system of the build.
+///     - ``"cpu_features"`` (list[str]): Detected SIMD extensions, e.g.
+///       ``["avx2", "fma"]``.
+///     - ``"kernels"`` (str): Instruction set used by the mixing and IIR
+///       filtering kernels, one of ``"avx2"``, ``"neon"`` and ``"scalar"``.
+#[pyfunction]
+fn build_info(py: Python) -> PyResult<Bound<PyDict>> {
+    let info = PyDict::new_bound(py);
+    info.set_item("version", env!("CARGO_PKG_VERSION"))?;
+    info.set_item("target_arch", std::env::consts::ARCH)?;
+    info.set_item("target_os", std::env::consts::OS)?;
+    info.set_item("cpu_features", pulse::cpu_features())?;
+    info.set_item("kernels", pulse::simd_level())?;
+    Ok(info)
+}
+
+/// Generates microwave pulses for superconducting quantum computing
+/// experiments.
+///
+/// Importing the module and building schedules do not require numpy. numpy is
+/// only needed when generating waveforms or passing arrays to :class:`Channel`.
+///
+/// .. caution::
+///
+///     The unit of phase is number of cycles, not radians. For example, a phase
+///     of :math:`0.5` means a phase shift of :math:`\pi` radians. Use
+///     :func:`set_phase_unit` to give and get phases in radians instead.
+#[pymodule]
+fn bosing(_py: Python, m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<Absolute>()?;
+    m.add_class::<AbsoluteEntry>()?;
+    m.add_class::<Alignment>()?;
+    m.add_class::<AwgPreset>()?;
+    m.add_class::<Barrier>()?;
+    m.add_class::<Delay>()?;
+    m.add_class::<Marker>()?;
+    m.add_class::<Difference>()?;
+    m.add_class::<ElementTiming>()?;
+    m.add("BosingError", m.py().get_type_bound::<BosingError>())?;
+    m.add_class::<Channel>()?;
+    m.add_class::<Clipping>()?;
+    m.add("ClippingError", m.py().get_type_bound::<ClippingError>())?;
+    m.add_class::<DigitalChannel>()?;
+    m.add_class::<Direction>()?;
+    m.add_class::<Element>()?;
+    m.add_class::<ClippedPulse>()?;
+    m.add_class::<FrameProgram>()?;
+    m.add_class::<PulseTimeline>()?;
+    m.add_class::<ScheduledPulse>()?;
+    m.add_class::<ElementStats>()?;
+    m.add_class::<FragmentInstance>()?;
+    m.add_class::<FragmentRegistry>()?;
+    m.add_class::<Grid>()?;
+    m.add_class::<GridEntry>()?;
+    m.add_class::<GridLength>()?;
+    m.add_class::<GridLengthUnit>()?;
+    m.add_class::<Gaussian>()?;
+    m.add_class::<CosineSum>()?;
+    m.add_class::<CustomShape>()?;
+    m.add_class::<ErfSquare>()?;
+    m.add_class::<ShapeProduct>()?;
+    m.add_class::<ShapeSum>()?;
+    m.add_class::<Hann>()?;
+    m.add_class::<Interp>()?;
+    m.add_class::<Padding>()?;
+    m.add_class::<Overflow>()?;
+    m.add_class::<Overlay>()?;
+    m.add_class::<Param>()?;
+    m.add_class::<PhaseRamp>()?;
+    m.add_class::<Play>()?;
+    m.add_class::<PulseStats>()?;
+    m.add_class::<Regenerator>()?;
+    m.add_class::<Repeat>()?;
+    m.add_class::<ResultStore>()?;
+    m.add("SamplingError", m.py().get_type_bound::<SamplingError>())?;
+    m.add_class::<Scale>()?;
+    m.add_class::<Reverse>()?;
+    m.add("ScheduleError", m.py().get_type_bound::<ScheduleError>())?;
+    #[cfg(feature = "serve")]
+    m.add_class::<Server>()?;
+    m.add_class::<SetFreq>()?;
+    m.add_class::<SetPhase>()?;
+    m.add_class::<ShiftFreq>()?;
+    m.add_class::<ShiftPhase>()?;
+    m.add_class::<Shape>()?;
+    m.add_class::<Spline>()?;
+    m.add_class::<SampledShape>()?;
+    m.add_class::<Stack>()?;
+    m.add_class::<SwapPhase>()?;
+    m.add_class::<WaveformChunks>()?;
+    m.add_class::<WaveformStats>()?;
+    m.add_function(wrap_pyfunction!(awg_presets, m)?)?;
+    m.add_function(wrap_pyfunction!(bind, m)?)?;
+    m.add_function(wrap_pyfunction!(build_info, m)?)?;
+    m.add_function(wrap_pyfunction!(clear_envelope_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(compile_frames, m)?)?;
+    m.add_function(wrap_pyfunction!(compile_timeline, m)?)?;
+    m.add_function(wrap_pyfunction!(diff, m)?)?;
+    m.add_function(wrap_pyfunction!(explain, m)?)?;
+    m.add_function(wrap_pyfunction!(element_timings, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_pulse_table, m)?)?;
+    m.add_function(wrap_pyfunction!(find_clipped_pulses, m)?)?;
+    m.add_function(wrap_pyfunction!(compute_amp_scales, m)?)?;
+    m.add_function(wrap_pyfunction!(envelope_area, m)?)?;
+    m.add_function(wrap_pyfunction!(envelope_fwhm, m)?)?;
+    m.add_function(wrap_pyfunction!(shift_waveform, m)?)?;
+    m.add_function(wrap_pyfunction!(quantize_waveforms, m)?)?;
+    m.add_function(wrap_pyfunction!(export_html, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_sweep, m)?)?;
+    m.add_function(wrap_pyfunction!(iq_imbalance_sweep, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_sparse_waveforms, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_waveform_chunks, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_waveforms_into, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_waveforms, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_waveforms_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(integration_weights, m)?)?;
+    m.add_function(wrap_pyfunction!(load_experiment, m)?)?;
+    m.add_function(wrap_pyfunction!(measure_schedule, m)?)?;
+    m.add_function(wrap_pyfunction!(patch, m)?)?;
+    m.add_function(wrap_pyfunction!(pulse_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(register_awg, m)?)?;
+    m.add_function(wrap_pyfunction!(set_envelope_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(set_layout_check, m)?)?;
+    m.add_function(wrap_pyfunction!(set_phase_unit, m)?)?;
+    m.add_function(wrap_pyfunction!(get_phase_unit, m)?)?;
+    m.add_function(wrap_pyfunction!(set_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(waveform_stats, m)?)?;
+    Ok(())
+}
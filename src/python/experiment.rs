@@ -0,0 +1,350 @@
+//! Experiment description documents.
+//!
+//! An experiment document contains the channels, shapes and schedule needed by
+//! [`generate_waveforms`](crate::generate_waveforms). The channel and shape
+//! descriptions mirror the arguments of the Python classes and are converted
+//! to Python objects by calling the constructors, so the same validation
+//! applies. The schedule uses the serde format of [`crate::schedule::json`].
+//!
+//! YAML and TOML documents are supported. YAML anchors, aliases and merge keys
+//! can be used for reuse, and anchored templates can be put under the
+//! top-level `definitions` key which is otherwise ignored.
+
+use std::collections::BTreeMap;
+
+use hashbrown::HashMap;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict, PyTypeInfo};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    to_py_element, Channel, CosineSum, ErfSquare, Gaussian, Hann, Interp, SampledShape,
+    ShapeProduct, ShapeSum, Spline,
+};
+use crate::schedule::ElementRef;
+
+#[derive(Debug, Error)]
+pub(crate) enum Error {
+    #[error("Invalid YAML document: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid TOML document: {0}")]
+    Toml(#[from] toml::de::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Yaml,
+    Toml,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExperimentDesc {
+    #[serde(default)]
+    pub(crate) channels: BTreeMap<String, ChannelDesc>,
+    #[serde(default)]
+    pub(crate) shapes: BTreeMap<String, ShapeDesc>,
+    pub(crate) schedule: ElementRef,
+    // Only for YAML anchors, the content is ignored.
+    #[serde(default, rename = "definitions", skip_serializing)]
+    _definitions: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ChannelDesc {
+    pub(crate) base_freq: f64,
+    pub(crate) sample_rate: f64,
+    pub(crate) length: usize,
+    pub(crate) delay: Option<f64>,
+    pub(crate) align_level: Option<i32>,
+    pub(crate) iq_matrix: Option<Vec<Vec<f64>>>,
+    pub(crate) offset: Option<Vec<f64>>,
+    pub(crate) iir: Option<Vec<Vec<f64>>>,
+    pub(crate) fir: Option<Vec<f64>>,
+    pub(crate) exp_corrections: Option<Vec<(f64, f64)>>,
+    pub(crate) compression_table: Option<Vec<(f64, f64)>>,
+    pub(crate) compression_poly: Option<Vec<f64>>,
+    pub(crate) filter_offset: Option<bool>,
+    pub(crate) is_real: Option<bool>,
+    pub(crate) padding: Option<String>,
+    pub(crate) ramp_length: Option<usize>,
+    pub(crate) wrap_phase: Option<bool>,
+    pub(crate) compensate_group_delay: Option<bool>,
+    pub(crate) phase_from_first_use: Option<bool>,
+    pub(crate) blanking: Option<Vec<(f64, f64)>>,
+    pub(crate) overflow: Option<String>,
+    pub(crate) differential: Option<String>,
+    pub(crate) skew: Option<f64>,
+    pub(crate) trigger_latency: Option<f64>,
+    pub(crate) metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum ShapeDesc {
+    Hann,
+    Gaussian {
+        truncation: Option<f64>,
+    },
+    ErfSquare {
+        truncation: Option<f64>,
+    },
+    Product {
+        a: Box<ShapeDesc>,
+        b: Box<ShapeDesc>,
+    },
+    Sum {
+        a: Box<ShapeDesc>,
+        b: Box<ShapeDesc>,
+    },
+    CosineSum {
+        a0: f64,
+        a1: f64,
+        a2: Option<f64>,
+        a3: Option<f64>,
+    },
+    Interp {
+        knots: Vec<f64>,
+        controls: Vec<f64>,
+        degree: usize,
+    },
+    Spline {
+        x: Vec<f64>,
+        y: Vec<f64>,
+    },
+    Sampled {
+        samples: Vec<f64>,
+        sample_rate: f64,
+        interpolation: Option<String>,
+    },
+}
+
+impl ExperimentDesc {
+    pub(crate) fn parse(text: &str, format: Format) -> Result<Self, Error> {
+        match format {
+            Format::Yaml => {
+                let mut value: serde_yaml::Value = serde_yaml::from_str(text)?;
+                value.apply_merge()?;
+                Ok(serde_yaml::from_value(value)?)
+            }
+            Format::Toml => Ok(toml::from_str(text)?),
+        }
+    }
+
+    /// Builds the Python objects `(channels, shapes, schedule)`.
+    pub(crate) fn to_py<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyDict>, Bound<'py, PyDict>, Bound<'py, PyAny>)> {
+        let channels = PyDict::new_bound(py);
+        for (name, c) in &self.channels {
+            channels.set_item(name, c.to_py(py)?)?;
+        }
+        let shapes = PyDict::new_bound(py);
+        for (name, s) in &self.shapes {
+            shapes.set_item(name, s.to_py(py)?)?;
+        }
+        let schedule = to_py_element(py, &self.schedule, &mut HashMap::new())?;
+        Ok((channels, shapes, schedule.into_bound(py).into_any()))
+    }
+}
+
+impl ChannelDesc {
+    fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let kwargs = PyDict::new_bound(py);
+        set_opt(&kwargs, "delay", &self.delay)?;
+        set_opt(&kwargs, "align_level", &self.align_level)?;
+        set_opt(&kwargs, "iq_matrix", &self.iq_matrix)?;
+        set_opt(&kwargs, "offset", &self.offset)?;
+        set_opt(&kwargs, "iir", &self.iir)?;
+        set_opt(&kwargs, "fir", &self.fir)?;
+        set_opt(&kwargs, "exp_corrections", &self.exp_corrections)?;
+        set_opt(&kwargs, "compression_table", &self.compression_table)?;
+        set_opt(&kwargs, "compression_poly", &self.compression_poly)?;
+        set_opt(&kwargs, "filter_offset", &self.filter_offset)?;
+        set_opt(&kwargs, "is_real", &self.is_real)?;
+        set_opt(&kwargs, "padding", &self.padding)?;
+        set_opt(&kwargs, "ramp_length", &self.ramp_length)?;
+        set_opt(&kwargs, "wrap_phase", &self.wrap_phase)?;
+        set_opt(
+            &kwargs,
+            "compensate_group_delay",
+            &self.compensate_group_delay,
+        )?;
+        set_opt(&kwargs, "phase_from_first_use", &self.phase_from_first_use)?;
+        set_opt(&kwargs, "blanking", &self.blanking)?;
+        set_opt(&kwargs, "overflow", &self.overflow)?;
+        set_opt(&kwargs, "differential", &self.differential)?;
+        set_opt(&kwargs, "skew", &self.skew)?;
+        set_opt(&kwargs, "trigger_latency", &self.trigger_latency)?;
+        if let Some(metadata) = &self.metadata {
+            let text = serde_json::to_string(metadata)
+                .map_err(|e| PyValueError::new_err(e.to_string()))?;
+            let metadata = py.import_bound("json")?.call_method1("loads", (text,))?;
+            kwargs.set_item("metadata", metadata)?;
+        }
+        Channel::type_object_bound(py).call(
+            (self.base_freq, self.sample_rate, self.length),
+            Some(&kwargs),
+        )
+    }
+}
+
+impl ShapeDesc {
+    fn to_py<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        match self {
+            ShapeDesc::Hann => Hann::type_object_bound(py).call0(),
+            ShapeDesc::Gaussian { truncation } => {
+                let kwargs = PyDict::new_bound(py);
+                set_opt(&kwargs, "truncation", truncation)?;
+                Gaussian::type_object_bound(py).call((), Some(&kwargs))
+            }
+            ShapeDesc::ErfSquare { truncation } => {
+                let kwargs = PyDict::new_bound(py);
+                set_opt(&kwargs, "truncation", truncation)?;
+                ErfSquare::type_object_bound(py).call((), Some(&kwargs))
+            }
+            ShapeDesc::Product { a, b } => {
+                ShapeProduct::type_object_bound(py).call1((a.to_py(py)?, b.to_py(py)?))
+            }
+            ShapeDesc::Sum { a, b } => {
+                ShapeSum::type_object_bound(py).call1((a.to_py(py)?, b.to_py(py)?))
+            }
+            ShapeDesc::CosineSum { a0, a1, a2, a3 } => {
+                let kwargs = PyDict::new_bound(py);
+                set_opt(&kwargs, "a2", a2)?;
+                set_opt(&kwargs, "a3", a3)?;
+                CosineSum::type_object_bound(py).call((*a0, *a1), Some(&kwargs))
+            }
+            ShapeDesc::Interp {
+                knots,
+                controls,
+                degree,
+            } => Interp::type_object_bound(py).call1((knots.clone(), controls.clone(), *degree)),
+            ShapeDesc::Spline { x, y } => {
+                Spline::type_object_bound(py).call1((x.clone(), y.clone()))
+            }
+            ShapeDesc::Sampled {
+                samples,
+                sample_rate,
+                interpolation,
+            } => {
+                let kwargs = PyDict::new_bound(py);
+                set_opt(&kwargs, "interpolation", interpolation)?;
+                SampledShape::type_object_bound(py)
+                    .call((samples.clone(), *sample_rate), Some(&kwargs))
+            }
+        }
+    }
+}
+
+fn set_opt<T: ToPyObject>(kwargs: &Bound<PyDict>, name: &str, value: &Option<T>) -> PyResult<()> {
+    match value {
+        Some(v) => kwargs.set_item(name, v),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        quant::{Label, Time},
+        schedule::{Absolute, Play, Stack},
+    };
+
+    #[test]
+    fn yaml_anchors_and_merge() {
+        let text = "
+definitions:
+  pi: &pi {type: play, channel_id: xy, shape_id: hann, amplitude: 0.5, width: 4.0e-8}
+channels:
+  xy: {base_freq: 1.0e8, sample_rate: 2.0e9, length: 1000}
+shapes:
+  hann: {type: hann}
+  windowed: {type: product, a: {type: hann}, b: {type: gaussian, truncation: 3.0}}
+schedule:
+  type: stack
+  duration: 5.0e-7
+  children:
+    - *pi
+    - <<: *pi
+      amplitude: 0.25
+      label: half
+";
+        let desc = ExperimentDesc::parse(text, Format::Yaml).unwrap();
+
+        assert_eq!(desc.channels["xy"].length, 1000);
+        assert!(matches!(desc.shapes["hann"], ShapeDesc::Hann));
+        let ShapeDesc::Product { a, b } = &desc.shapes["windowed"] else {
+            panic!("Expected product");
+        };
+        assert!(matches!(**a, ShapeDesc::Hann));
+        assert!(matches!(
+            **b,
+            ShapeDesc::Gaussian {
+                truncation: Some(3.0)
+            }
+        ));
+        let schedule = &desc.schedule;
+        assert_eq!(schedule.common.duration(), Some(Time::new(5e-7).unwrap()));
+        let stack: &Stack = (&schedule.variant).try_into().unwrap();
+        let [p1, p2] = stack.children() else {
+            panic!("Expected two children");
+        };
+        let play1: &Play = (&p1.variant).try_into().unwrap();
+        let play2: &Play = (&p2.variant).try_into().unwrap();
+        assert_eq!(play1.amplitude().value(), 0.5);
+        assert_eq!(play2.amplitude().value(), 0.25);
+        assert_eq!(play2.width().value(), 4e-8);
+        assert_eq!(p2.common.label(), Some(&Label::new("half")));
+    }
+
+    #[test]
+    fn toml() {
+        let text = r#"
+[channels.z]
+base_freq = 0.0
+sample_rate = 2e9
+length = 100
+is_real = true
+
+[channels.z.metadata]
+slot = 3
+port = "A"
+
+[schedule]
+type = "absolute"
+
+[[schedule.children]]
+time = 1e-8
+
+[schedule.children.element]
+type = "play"
+channel_id = "z"
+amplitude = 0.1
+width = 2e-8
+"#;
+        let desc = ExperimentDesc::parse(text, Format::Toml).unwrap();
+
+        assert_eq!(desc.channels["z"].is_real, Some(true));
+        let metadata = desc.channels["z"].metadata.as_ref().unwrap();
+        assert_eq!(metadata["slot"], 3);
+        assert_eq!(metadata["port"], "A");
+        let absolute: &Absolute = (&desc.schedule.variant).try_into().unwrap();
+        let entry = &absolute.children()[0];
+        assert_eq!(entry.time(), Time::new(1e-8).unwrap());
+        let play: &Play = (&entry.element().variant).try_into().unwrap();
+        assert_eq!(play.shape_id(), None);
+    }
+
+    #[test]
+    fn unknown_field() {
+        let text = "schedule: {type: barrier, duraton: 1.0e-8}";
+
+        let err = ExperimentDesc::parse(text, Format::Yaml).unwrap_err();
+
+        assert!(err.to_string().contains("duraton"));
+    }
+}
@@ -0,0 +1,247 @@
+//! Diagnostic logging of waveform generation.
+//!
+//! The generation steps are instrumented with [`tracing`] spans. Nothing is
+//! recorded until [`configure`] installs a file writer or forwards the records
+//! to the Python `logging` module.
+//!
+//! Records can't be sent to Python from threads without the GIL, because the
+//! thread holding the GIL may be waiting for them, e.g. in a rayon scope.
+//! Such records are queued and flushed the next time a record is emitted or a
+//! span is closed on a thread holding the GIL, which at the latest happens
+//! when the top-level span of the Python call is closed.
+
+use std::{
+    fmt::{self, Write as _},
+    fs::File,
+    io,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use pyo3::prelude::*;
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    fmt::format::FmtSpan,
+    layer::{Context, SubscriberExt as _},
+    registry::{LookupSpan, Registry},
+    reload, Layer,
+};
+
+type Layered = tracing_subscriber::layer::Layered<reload::Layer<LevelFilter, Registry>, Registry>;
+type BoxedLayer = Box<dyn Layer<Layered> + Send + Sync>;
+
+struct Handles {
+    level: reload::Handle<LevelFilter, Registry>,
+    layers: reload::Handle<Vec<BoxedLayer>, Layered>,
+}
+
+static HANDLES: OnceLock<Handles> = OnceLock::new();
+
+/// Replaces the log outputs. Logging is disabled if neither `file` nor
+/// `python` is set.
+pub(crate) fn configure(file: Option<&Path>, python: bool, level: Level) -> io::Result<()> {
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    if let Some(path) = file {
+        let file = File::options().create(true).append(true).open(path)?;
+        layers.push(
+            tracing_subscriber::fmt::layer()
+                .with_writer(Mutex::new(file))
+                .with_thread_ids(true)
+                .with_span_events(FmtSpan::CLOSE)
+                .boxed(),
+        );
+    }
+    if python {
+        layers.push(PythonLayer.boxed());
+    }
+    let level = if layers.is_empty() {
+        LevelFilter::OFF
+    } else {
+        LevelFilter::from_level(level)
+    };
+    let handles = HANDLES.get_or_init(|| {
+        let (level, level_handle) = reload::Layer::new(LevelFilter::OFF);
+        let (layers, layers_handle) = reload::Layer::new(Vec::new());
+        tracing::subscriber::set_global_default(Registry::default().with(level).with(layers))
+            .expect("The global subscriber should only be set here");
+        Handles {
+            level: level_handle,
+            layers: layers_handle,
+        }
+    });
+    handles
+        .layers
+        .reload(layers)
+        .expect("The subscriber should be alive");
+    handles
+        .level
+        .reload(level)
+        .expect("The subscriber should be alive");
+    Ok(())
+}
+
+struct Record {
+    level: Level,
+    message: String,
+}
+
+static QUEUE: Mutex<Vec<Record>> = Mutex::new(Vec::new());
+
+/// Forwards the records to the `bosing` logger of the Python `logging` module.
+struct PythonLayer;
+
+/// Formatted fields and creation time of a span.
+struct SpanData {
+    fields: String,
+    created: Instant,
+}
+
+impl<S> Layer<S> for PythonLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanData {
+            fields: visitor.fields,
+            created: Instant::now(),
+        });
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut message = scope_prefix(ctx.event_scope(event));
+        message.push_str(&visitor.message);
+        message.push_str(&visitor.fields);
+        push(*event.metadata().level(), message);
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let elapsed = span
+            .extensions()
+            .get::<SpanData>()
+            .map(|d| d.created.elapsed());
+        let mut message = scope_prefix(Some(span.scope()));
+        message.push_str("close");
+        if let Some(elapsed) = elapsed {
+            let _ = write!(message, " elapsed={:?}", elapsed);
+        }
+        push(*span.metadata().level(), message);
+    }
+}
+
+/// Formats the span scope like `outer{a=1}:inner: `, outermost first.
+fn scope_prefix<'a, R>(scope: Option<tracing_subscriber::registry::Scope<'a, R>>) -> String
+where
+    R: LookupSpan<'a>,
+{
+    let mut prefix = String::new();
+    for span in scope.into_iter().flat_map(|s| s.from_root()) {
+        prefix.push_str(span.name());
+        if let Some(data) = span.extensions().get::<SpanData>() {
+            if !data.fields.is_empty() {
+                let _ = write!(prefix, "{{{}}}", data.fields.trim_start());
+            }
+        }
+        prefix.push(':');
+    }
+    if !prefix.is_empty() {
+        prefix.push(' ');
+    }
+    prefix
+}
+
+fn push(level: Level, message: String) {
+    QUEUE
+        .lock()
+        .expect("Log queue should not be poisoned")
+        .push(Record { level, message });
+    // SAFETY: Only checks the thread state of the current thread.
+    if unsafe { pyo3::ffi::PyGILState_Check() } == 1 {
+        flush();
+    }
+}
+
+fn flush() {
+    let records = std::mem::take(&mut *QUEUE.lock().expect("Log queue should not be poisoned"));
+    if records.is_empty() {
+        return;
+    }
+    Python::with_gil(|py| {
+        let Ok(logger) = py
+            .import_bound("logging")
+            .and_then(|m| m.call_method1("getLogger", ("bosing",)))
+        else {
+            return;
+        };
+        for Record { level, message } in records {
+            // Errors of the Python handlers can't be reported anywhere.
+            let _ = logger.call_method1("log", (python_level(level), message));
+        }
+    });
+}
+
+fn python_level(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 40,
+        Level::WARN => 30,
+        Level::INFO => 20,
+        Level::DEBUG => 10,
+        Level::TRACE => 5,
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::info_span;
+
+    use super::*;
+
+    #[test]
+    fn log_to_file() {
+        let path = std::env::temp_dir().join(format!("bosing-log-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        configure(Some(&path), false, Level::INFO).unwrap();
+        info_span!("outer", channel = "xy").in_scope(|| tracing::info!("hello"));
+        tracing::debug!("filtered");
+        configure(None, false, Level::INFO).unwrap();
+        tracing::info!("disabled");
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(text.contains("outer{channel=\"xy\"}: bosing::python::logging::tests: hello"));
+        assert!(text.contains("close"));
+        assert!(!text.contains("filtered"));
+        assert!(!text.contains("disabled"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Waveform streaming over HTTP.
+//!
+//! The server accepts experiment documents in JSON with the same schema as
+//! [`ExperimentDesc`] and streams the generated waveforms back as
+//! newline-delimited JSON. Each line is a chunk of one channel:
+//!
+//! ```json
+//! {"channel":"xy","offset":0,"data":[[0.0,0.1],[0.0,0.0]]}
+//! ```
+//!
+//! Channels are sent in the order of their names, and the chunks of a channel
+//! are sent in order of `offset`. The first chunk of a channel with metadata
+//! carries it in a `metadata` field. The waveform generation itself lives in
+//! [`super::Server`] because it needs the Python objects.
+
+use std::io::{self, Read};
+
+use ndarray::{s, Array2};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+use tiny_http::{Header, Method, Request, Response, StatusCode};
+
+use super::experiment::ExperimentDesc;
+
+/// Largest accepted request body in bytes.
+const MAX_BODY_SIZE: u64 = 64 << 20;
+
+#[derive(Debug, Error)]
+pub(crate) enum Error {
+    #[error("Not found")]
+    NotFound,
+    #[error("Method {0} is not allowed, expected POST")]
+    MethodNotAllowed(Method),
+    #[error("Request body exceeds the limit of {0} bytes")]
+    PayloadTooLarge(u64),
+    #[error("Failed to read the request body: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid JSON document: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::NotFound => StatusCode(404),
+            Error::MethodNotAllowed(_) => StatusCode(405),
+            Error::PayloadTooLarge(_) => StatusCode(413),
+            Error::Io(_) | Error::Json(_) => StatusCode(400),
+        }
+    }
+}
+
+/// Reads the experiment document of a `POST /generate` request.
+pub(crate) fn parse_request(request: &mut Request) -> Result<ExperimentDesc, Error> {
+    if request.url() != "/generate" {
+        return Err(Error::NotFound);
+    }
+    if request.method() != &Method::Post {
+        return Err(Error::MethodNotAllowed(request.method().clone()));
+    }
+    // The length is checked again while reading because it may be missing.
+    if request
+        .body_length()
+        .is_some_and(|n| n as u64 > MAX_BODY_SIZE)
+    {
+        return Err(Error::PayloadTooLarge(MAX_BODY_SIZE));
+    }
+    let body = read_body(request.as_reader(), MAX_BODY_SIZE)?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Reads at most `limit` bytes of the body.
+fn read_body(reader: impl Read, limit: u64) -> Result<String, Error> {
+    let mut body = String::new();
+    reader.take(limit + 1).read_to_string(&mut body)?;
+    if body.len() as u64 > limit {
+        return Err(Error::PayloadTooLarge(limit));
+    }
+    Ok(body)
+}
+
+/// Responds with a plain text error message.
+pub(crate) fn respond_error(request: Request, status: StatusCode, message: &str) -> io::Result<()> {
+    request.respond(Response::from_string(message).with_status_code(status))
+}
+
+pub(crate) fn respond_request_error(request: Request, err: &Error) -> io::Result<()> {
+    respond_error(request, err.status_code(), &err.to_string())
+}
+
+/// Generated waveform of one output, with the metadata of its channel.
+pub(crate) struct Output {
+    pub(crate) name: String,
+    pub(crate) waveform: Array2<f64>,
+    pub(crate) metadata: Option<Map<String, Value>>,
+}
+
+/// Streams the waveforms with chunked transfer encoding.
+pub(crate) fn respond_waveforms(
+    request: Request,
+    waveforms: Vec<Output>,
+    chunk_size: usize,
+) -> io::Result<()> {
+    let content_type = Header::from_bytes("Content-Type", "application/x-ndjson")
+        .expect("The header should be valid");
+    let response = Response::new(
+        StatusCode(200),
+        vec![content_type],
+        ChunkStream::new(waveforms, chunk_size),
+        None,
+        None,
+    );
+    request.respond(response)
+}
+
+#[derive(Serialize)]
+struct Chunk<'a> {
+    channel: &'a str,
+    offset: usize,
+    data: Vec<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a Map<String, Value>>,
+}
+
+/// Lazily encodes the waveform chunks, one line at a time.
+struct ChunkStream {
+    waveforms: Vec<Output>,
+    chunk_size: usize,
+    channel: usize,
+    offset: usize,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl ChunkStream {
+    fn new(waveforms: Vec<Output>, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "Chunk size should be positive");
+        Self {
+            waveforms,
+            chunk_size,
+            channel: 0,
+            offset: 0,
+            line: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Encodes the next chunk into `self.line`. Returns `false` if all chunks
+    /// are sent.
+    fn next_line(&mut self) -> io::Result<bool> {
+        loop {
+            let Some(Output {
+                name,
+                waveform,
+                metadata,
+            }) = self.waveforms.get(self.channel)
+            else {
+                return Ok(false);
+            };
+            let length = waveform.ncols();
+            // An empty waveform is still sent as a single empty chunk.
+            if self.offset > 0 && self.offset >= length {
+                self.channel += 1;
+                self.offset = 0;
+                continue;
+            }
+            let end = (self.offset + self.chunk_size).min(length);
+            let chunk = Chunk {
+                channel: name,
+                offset: self.offset,
+                data: waveform
+                    .slice(s![.., self.offset..end])
+                    .rows()
+                    .into_iter()
+                    .map(|r| r.to_vec())
+                    .collect(),
+                metadata: metadata.as_ref().filter(|_| self.offset == 0),
+            };
+            self.line.clear();
+            self.pos = 0;
+            serde_json::to_writer(&mut self.line, &chunk)?;
+            self.line.push(b'\n');
+            self.offset = end.max(1);
+            return Ok(true);
+        }
+    }
+}
+
+impl Read for ChunkStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() && !self.next_line()? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn body_limit() {
+        assert_eq!(read_body("{}".as_bytes(), 2).unwrap(), "{}");
+        let err = read_body("{ }".as_bytes(), 2).unwrap_err();
+        assert!(matches!(err, Error::PayloadTooLarge(2)));
+        assert_eq!(err.status_code(), StatusCode(413));
+    }
+
+    #[test]
+    fn stream_chunks() {
+        let Value::Object(metadata) = json!({"slot": 3}) else {
+            unreachable!()
+        };
+        let waveforms = vec![
+            Output {
+                name: "a".to_string(),
+                waveform: array![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]],
+                metadata: Some(metadata),
+            },
+            Output {
+                name: "b".to_string(),
+                waveform: Array2::zeros((1, 0)),
+                metadata: None,
+            },
+        ];
+        let mut text = String::new();
+
+        ChunkStream::new(waveforms, 2)
+            .read_to_string(&mut text)
+            .unwrap();
+
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"channel":"a","offset":0,"data":[[1.0,2.0],[4.0,5.0]],"metadata":{"slot":3}}"#,
+                r#"{"channel":"a","offset":2,"data":[[3.0],[6.0]]}"#,
+                r#"{"channel":"b","offset":0,"data":[[]]}"#,
+            ]
+        );
+    }
+}
@@ -1,17 +1,22 @@
 use std::{
     iter::Sum,
     ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
+use num::complex::Complex64;
 use num::NumCast;
-use numpy::Complex64;
 use ordered_float::NotNan;
+#[cfg(feature = "python")]
 use pyo3::{exceptions::PyValueError, prelude::*, types::PyFloat, IntoPy};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
-pub(crate) enum Error {
+pub enum Error {
     #[error("NaN value is not allowed")]
     NanValue(#[from] ordered_float::FloatIsNan),
     #[error("Infinite value is not allowed")]
@@ -21,7 +26,7 @@ pub(crate) enum Error {
 macro_rules! def_quant {
     ($t:ident) => {
         #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
-        pub(crate) struct $t(NotNan<f64>);
+        pub struct $t(NotNan<f64>);
     };
 }
 
@@ -29,44 +34,84 @@ def_quant!(Time);
 def_quant!(Frequency);
 def_quant!(Phase);
 def_quant!(Amplitude);
+def_quant!(ChirpRate);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub(crate) struct AlignedIndex(NotNan<f64>);
+pub struct AlignedIndex(NotNan<f64>);
 
 macro_rules! def_id {
     ($t:ident) => {
         #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-        pub(crate) struct $t(Arc<str>);
+        pub struct $t(Arc<str>);
     };
 }
 
 def_id!(ChannelId);
 def_id!(ShapeId);
+def_id!(Label);
 
 type Result<T> = std::result::Result<T, Error>;
 
 impl Time {
-    pub(crate) const INFINITY: Self = Self(unsafe { NotNan::new_unchecked(f64::INFINITY) });
+    pub const INFINITY: Self = Self(unsafe { NotNan::new_unchecked(f64::INFINITY) });
+}
+
+static PHASE_IN_RADIANS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether phases are given and returned in radians instead of cycles in
+/// the Python API. Phases are always stored in cycles.
+pub fn set_phase_in_radians(enabled: bool) {
+    PHASE_IN_RADIANS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn phase_in_radians() -> bool {
+    PHASE_IN_RADIANS.load(Ordering::Relaxed)
 }
 
 impl Phase {
+    /// Phase from a value in the unit of the Python API, see
+    /// [`set_phase_in_radians`].
+    pub fn from_py_value(value: f64) -> Result<Self> {
+        if phase_in_radians() {
+            Self::new(value / std::f64::consts::TAU)
+        } else {
+            Self::new(value)
+        }
+    }
+
+    /// Value in the unit of the Python API.
+    pub fn py_value(&self) -> f64 {
+        if phase_in_radians() {
+            self.radians()
+        } else {
+            self.value()
+        }
+    }
+
     fn radians(&self) -> f64 {
         self.value() * std::f64::consts::TAU
     }
 
-    pub(crate) fn phaser(&self) -> Complex64 {
+    pub fn phaser(&self) -> Complex64 {
         Complex64::from_polar(1.0, self.radians())
     }
+
+    /// Equivalent phase in the range `[0, 1)`.
+    pub fn wrapped(&self) -> Self {
+        // `rem_euclid` may round up to exactly 1.0 for tiny negative values.
+        let v = self.value().rem_euclid(1.0);
+        Self::new(if v < 1.0 { v } else { 0.0 }).expect("Should be a valid phase value")
+    }
 }
 
 impl Frequency {
-    pub(crate) fn dt(&self) -> Time {
+    pub fn dt(&self) -> Time {
         Time::new(1.0 / self.value()).expect("Frequency should be non-zero")
     }
 }
 
 impl AlignedIndex {
-    pub(crate) fn new(time: Time, sample_rate: Frequency, align_level: i32) -> Result<Self> {
+    pub fn new(time: Time, sample_rate: Frequency, align_level: i32) -> Result<Self> {
         fn scaleb(x: f64, s: i32) -> f64 {
             x * (s as f64).exp2()
         }
@@ -83,15 +128,15 @@ impl AlignedIndex {
         Ok(Self(NotNan::new(value)?))
     }
 
-    pub(crate) fn value(&self) -> f64 {
+    pub fn value(&self) -> f64 {
         self.0.into_inner()
     }
 
-    pub(crate) fn ceil_to_usize(&self) -> Option<usize> {
+    pub fn ceil_to_usize(&self) -> Option<usize> {
         <usize as NumCast>::from(self.0.ceil())
     }
 
-    pub(crate) fn index_offset(&self) -> Result<Self> {
+    pub fn index_offset(&self) -> Result<Self> {
         Self::from_value(self.0.ceil() - self.0.into_inner())
     }
 }
@@ -112,6 +157,15 @@ impl Mul<Frequency> for Time {
     }
 }
 
+impl Mul<Time> for ChirpRate {
+    type Output = Frequency;
+
+    fn mul(self, rhs: Time) -> Self::Output {
+        Frequency::new(self.value() * rhs.value()).expect("Should be a valid frequency value")
+    }
+}
+
+#[cfg(feature = "python")]
 impl From<Error> for PyErr {
     fn from(err: Error) -> Self {
         PyValueError::new_err(err.to_string())
@@ -149,21 +203,32 @@ macro_rules! forward_ref_binop {
 macro_rules! impl_quant {
     ($t:ty) => {
         impl $t {
-            pub(crate) fn new(value: f64) -> Result<Self> {
+            pub fn new(value: f64) -> Result<Self> {
                 Ok(Self(NotNan::new(value)?))
             }
 
-            pub(crate) fn value(&self) -> f64 {
+            pub fn value(&self) -> f64 {
                 self.0.into_inner()
             }
 
-            pub(crate) const ZERO: Self = Self(unsafe { NotNan::new_unchecked(0.0) });
+            pub const ZERO: Self = Self(unsafe { NotNan::new_unchecked(0.0) });
         }
 
-        impl<'py> FromPyObject<'py> for $t {
-            fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
-                let value = ob.extract()?;
-                Ok(Self::new(value)?)
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                self.value().serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                let value = f64::deserialize(deserializer)?;
+                Self::new(value).map_err(de::Error::custom)
             }
         }
 
@@ -243,12 +308,6 @@ macro_rules! impl_quant {
             }
         }
 
-        impl IntoPy<PyObject> for $t {
-            fn into_py(self, py: Python) -> PyObject {
-                PyFloat::new_bound(py, self.value()).into()
-            }
-        }
-
         impl From<$t> for f64 {
             fn from(q: $t) -> Self {
                 q.value()
@@ -269,11 +328,55 @@ impl_quant!(Time);
 impl_quant!(Frequency);
 impl_quant!(Phase);
 impl_quant!(Amplitude);
+impl_quant!(ChirpRate);
+
+#[cfg(feature = "python")]
+macro_rules! impl_quant_py {
+    ($t:ty) => {
+        impl<'py> FromPyObject<'py> for $t {
+            fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+                let value = ob.extract()?;
+                Ok(Self::new(value)?)
+            }
+        }
+
+        impl IntoPy<PyObject> for $t {
+            fn into_py(self, py: Python) -> PyObject {
+                PyFloat::new_bound(py, self.value()).into()
+            }
+        }
+    };
+}
+
+#[cfg(feature = "python")]
+impl_quant_py!(Time);
+#[cfg(feature = "python")]
+impl_quant_py!(Frequency);
+#[cfg(feature = "python")]
+impl_quant_py!(Amplitude);
+#[cfg(feature = "python")]
+impl_quant_py!(ChirpRate);
+
+// Phases are converted with the unit set by `set_phase_in_radians`.
+#[cfg(feature = "python")]
+impl<'py> FromPyObject<'py> for Phase {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let value = ob.extract()?;
+        Ok(Self::from_py_value(value)?)
+    }
+}
+
+#[cfg(feature = "python")]
+impl IntoPy<PyObject> for Phase {
+    fn into_py(self, py: Python) -> PyObject {
+        PyFloat::new_bound(py, self.py_value()).into()
+    }
+}
 
 macro_rules! impl_id {
     ($t:ty) => {
         impl $t {
-            pub(crate) fn new(name: impl Into<Arc<str>>) -> Self {
+            pub fn new(name: impl Into<Arc<str>>) -> Self {
                 Self(name.into())
             }
         }
@@ -284,6 +387,28 @@ macro_rules! impl_id {
             }
         }
 
+        impl Serialize for $t {
+            fn serialize<S: Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $t {
+            fn deserialize<D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> std::result::Result<Self, D::Error> {
+                String::deserialize(deserializer).map(Self::new)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "python")]
+macro_rules! impl_id_py {
+    ($t:ty) => {
         impl<'py> FromPyObject<'py> for $t {
             fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
                 let name = ob.extract::<&str>()?;
@@ -307,3 +432,11 @@ macro_rules! impl_id {
 
 impl_id!(ChannelId);
 impl_id!(ShapeId);
+impl_id!(Label);
+
+#[cfg(feature = "python")]
+impl_id_py!(ChannelId);
+#[cfg(feature = "python")]
+impl_id_py!(ShapeId);
+#[cfg(feature = "python")]
+impl_id_py!(Label);
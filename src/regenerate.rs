@@ -0,0 +1,210 @@
+//! Re-sampling of labeled pulses with new amplitudes and phases.
+
+use hashbrown::{HashMap, HashSet};
+
+use crate::{
+    executor::PulseRecord,
+    pulse::{PulseList, PulseListBuilder},
+    quant::{Amplitude, ChannelId, Label, Phase, Time},
+};
+
+/// Pulses of an executed schedule whose pulse lists can be rebuilt with new
+/// amplitude scales and phase shifts of the labeled pulses.
+///
+/// Executing a schedule arranges the whole tree, which is wasted in amplitude
+/// or phase sweeps where the timing doesn't change. The regenerator keeps the
+/// pulses recorded by [`Executor::record_pulses`](crate::executor::Executor::record_pulses)
+/// and tracks which channels have pulses with changed parameters, so that
+/// only those channels need to be sampled again.
+#[derive(Debug, Clone)]
+pub struct Regenerator {
+    pulses: HashMap<ChannelId, Vec<PulseRecord>>,
+    label_channels: HashMap<Label, HashSet<ChannelId>>,
+    amp_scales: HashMap<Label, f64>,
+    phase_shifts: HashMap<Label, Phase>,
+    dirty: HashSet<ChannelId>,
+    amp_tolerance: Amplitude,
+    time_tolerance: Time,
+}
+
+impl Regenerator {
+    /// Creates a regenerator of the `channels` with the recorded pulses. All
+    /// channels are dirty at first.
+    pub fn new(
+        channels: impl IntoIterator<Item = ChannelId>,
+        records: Vec<PulseRecord>,
+        amp_tolerance: Amplitude,
+        time_tolerance: Time,
+    ) -> Self {
+        let mut pulses: HashMap<_, Vec<_>> =
+            channels.into_iter().map(|n| (n, Vec::new())).collect();
+        let mut label_channels: HashMap<_, HashSet<_>> = HashMap::new();
+        for record in records {
+            if let Some(label) = &record.label {
+                label_channels
+                    .entry(label.clone())
+                    .or_default()
+                    .insert(record.channel.clone());
+            }
+            pulses
+                .entry(record.channel.clone())
+                .or_default()
+                .push(record);
+        }
+        let dirty = pulses.keys().cloned().collect();
+        Self {
+            pulses,
+            label_channels,
+            amp_scales: HashMap::new(),
+            phase_shifts: HashMap::new(),
+            dirty,
+            amp_tolerance,
+            time_tolerance,
+        }
+    }
+
+    /// Scales the amplitude of the pulses with the label relative to the
+    /// recorded amplitude.
+    pub fn set_amp_scale(&mut self, label: Label, scale: f64) {
+        if self.amp_scales.get(&label) != Some(&scale) {
+            self.mark_dirty(&label);
+            self.amp_scales.insert(label, scale);
+        }
+    }
+
+    /// Adds a phase to the pulses with the label relative to the recorded
+    /// phase.
+    pub fn set_phase_shift(&mut self, label: Label, phase: Phase) {
+        if self.phase_shifts.get(&label) != Some(&phase) {
+            self.mark_dirty(&label);
+            self.phase_shifts.insert(label, phase);
+        }
+    }
+
+    /// Whether any recorded pulse has the label.
+    pub fn has_label(&self, label: &Label) -> bool {
+        self.label_channels.contains_key(label)
+    }
+
+    /// Channels with changed pulses since the last [`clear_dirty`](Self::clear_dirty).
+    pub fn dirty(&self) -> impl Iterator<Item = &ChannelId> {
+        self.dirty.iter()
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// Pulse lists of the dirty channels.
+    pub fn pulse_lists(&self) -> HashMap<ChannelId, PulseList> {
+        self.dirty
+            .iter()
+            .map(|n| (n.clone(), self.build(&self.pulses[n])))
+            .collect()
+    }
+
+    fn build(&self, records: &[PulseRecord]) -> PulseList {
+        let mut builder = PulseListBuilder::new(self.amp_tolerance, self.time_tolerance);
+        for record in records {
+            let mut args = record.args.clone();
+            if let Some(label) = &record.label {
+                if let Some(&scale) = self.amp_scales.get(label) {
+                    args.amplitude = args.amplitude * scale;
+                }
+                if let Some(&phase) = self.phase_shifts.get(label) {
+                    args.phase += phase;
+                }
+            }
+            builder.push(args);
+        }
+        builder.build()
+    }
+
+    fn mark_dirty(&mut self, label: &Label) {
+        if let Some(channels) = self.label_channels.get(label) {
+            self.dirty.extend(channels.iter().cloned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ndarray::Array2;
+
+    use super::*;
+    use crate::{
+        executor::Executor,
+        generate::{sample_into, ChannelConfig},
+        pulse::EnvelopePrecision,
+        quant::Frequency,
+        schedule::{test_util::play, Element, ElementCommonBuilder, Stack},
+        Overflow, Padding,
+    };
+
+    fn executor() -> Executor {
+        let mut executor = Executor::new(Amplitude::ZERO, Time::ZERO, false);
+        for n in ["a", "b"] {
+            executor.add_channel(ChannelId::new(n), Frequency::ZERO, false);
+        }
+        executor
+    }
+
+    fn sample(pulse_lists: HashMap<ChannelId, PulseList>) -> Array2<f64> {
+        let config = ChannelConfig {
+            sample_rate: Frequency::new(1.0).unwrap(),
+            delay: Time::ZERO,
+            align_level: 0,
+            padding: Padding::Zero,
+            ramp_length: 0,
+            overflow: Overflow::Error,
+            iq_matrix: None,
+            offset: None,
+            iir: None,
+            fir: None,
+            predistortion: None,
+            filter_offset: false,
+            blanking: vec![],
+            envelope_precision: EnvelopePrecision::F64,
+            differential: None,
+        };
+        let channels = [(ChannelId::new("a"), config)].into_iter().collect();
+        let mut waveform = Array2::zeros((2, 10));
+        let mut buffers = [(ChannelId::new("a"), waveform.view_mut())]
+            .into_iter()
+            .collect();
+        sample_into(pulse_lists, &channels, &mut buffers, None, Time::ZERO).unwrap();
+        drop(buffers);
+        waveform
+    }
+
+    #[test]
+    fn rebuild_changed_channels() {
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![play("a", 0.5, Some("x")), play("b", 0.5, Some("y"))]),
+        ));
+        let mut recording = executor();
+        recording.record_pulses();
+        recording.execute(&root).unwrap();
+        let channels = [ChannelId::new("a"), ChannelId::new("b")];
+        let records = recording.into_pulse_records();
+        let mut regenerator = Regenerator::new(channels, records, Amplitude::ZERO, Time::ZERO);
+        regenerator.clear_dirty();
+
+        regenerator.set_amp_scale(Label::new("x"), 0.5);
+        regenerator.set_phase_shift(Label::new("x"), Phase::new(0.25).unwrap());
+
+        let dirty: Vec<_> = regenerator.dirty().collect();
+        assert_eq!(dirty, [&ChannelId::new("a")]);
+        let mut expected = executor();
+        expected.add_amp_scale(Label::new("x"), 0.5);
+        expected.add_phase_shift(Label::new("x"), Phase::new(0.25).unwrap());
+        expected.execute(&root).unwrap();
+        assert_eq!(
+            sample(regenerator.pulse_lists()),
+            sample(expected.into_result())
+        );
+    }
+}
@@ -1,9 +1,19 @@
 mod absolute;
+mod diff;
+mod fragment;
 mod grid;
+mod json;
+mod overlay;
+mod parallel;
 mod play;
 mod repeat;
+mod reverse;
+mod scale;
 mod simple;
 mod stack;
+mod stats;
+#[cfg(test)]
+pub(crate) mod test_util;
 
 use std::sync::Arc;
 
@@ -13,60 +23,119 @@ use hashbrown::HashSet;
 use mockall::automock;
 
 use crate::{
-    quant::{ChannelId, Time},
+    quant::{ChannelId, Label, Time},
     Alignment,
 };
 
-pub(crate) use absolute::{Absolute, AbsoluteEntry};
-pub(crate) use grid::{Grid, GridEntry};
-pub(crate) use play::Play;
-pub(crate) use repeat::Repeat;
-pub(crate) use simple::{Barrier, SetFreq, SetPhase, ShiftFreq, ShiftPhase, SwapPhase};
-pub(crate) use stack::Stack;
+pub use absolute::{Absolute, AbsoluteEntry};
+pub use diff::{diff, Difference};
+pub use fragment::{
+    from_json_with_fragments, to_json_with_fragments, Fragment, FragmentError, FragmentInstance,
+    FragmentRegistry,
+};
+pub use grid::{Grid, GridConflict, GridEntry};
+pub use json::{from_json, to_json};
+pub use overlay::Overlay;
+pub use parallel::measure_parallel;
+pub use play::Play;
+pub use repeat::Repeat;
+pub use reverse::Reverse;
+pub use scale::Scale;
+pub use simple::{
+    Barrier, Delay, Marker, PhaseRamp, SetFreq, SetPhase, ShiftFreq, ShiftPhase, SwapPhase,
+};
+pub use stack::Stack;
+pub use stats::ScheduleStats;
 
-pub(crate) type ElementRef = Arc<Element>;
+pub type ElementRef = Arc<Element>;
 
 #[derive(Debug, Clone)]
-pub(crate) struct Element {
-    pub(crate) common: ElementCommon,
-    pub(crate) variant: ElementVariant,
+pub struct Element {
+    pub common: ElementCommon,
+    pub variant: ElementVariant,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct ElementCommon {
+pub struct ElementCommon {
     margin: (Time, Time),
     alignment: Alignment,
     phantom: bool,
+    phantom_ignored: bool,
     duration: Option<Time>,
     max_duration: Time,
     min_duration: Time,
+    label: Option<Label>,
+    duration_ref: Option<DurationRef>,
+    params: Vec<ParamRef>,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct ElementCommonBuilder(ElementCommon);
+pub struct ElementCommonBuilder(ElementCommon);
+
+/// Duration declared as a multiple of the measured duration of the first
+/// element with the label, see [`crate::optimize::DurationResolver`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationRef {
+    label: Label,
+    factor: f64,
+}
+
+/// Field of the element whose value is given later by the name of a
+/// parameter, see [`crate::optimize::bind_params`]. Until then the field
+/// holds a placeholder value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamRef {
+    field: String,
+    name: String,
+}
 
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct TimeRange {
-    pub(crate) start: Time,
-    pub(crate) span: Time,
+pub struct TimeRange {
+    pub start: Time,
+    pub span: Time,
 }
 
 #[derive(Debug, Clone, Copy)]
-pub(crate) struct Arranged<T> {
-    pub(crate) item: T,
-    pub(crate) time_range: TimeRange,
+pub struct Arranged<T> {
+    pub item: T,
+    pub time_range: TimeRange,
 }
 
 #[cfg_attr(test, automock)]
-pub(crate) trait Measure {
+pub trait Measure {
     fn measure(&self) -> Time;
     fn channels(&self) -> &[ChannelId];
+
+    /// Whether the item is left out of the layout. Ignored items neither take
+    /// time nor synchronize channels in a [`Stack`], where they are placed at
+    /// the start, and don't extend an [`Absolute`].
+    fn ignored(&self) -> bool {
+        false
+    }
+}
+
+pub trait Arrange {
+    type Child;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&Self::Child>>;
 }
 
-pub(crate) trait Arrange {
-    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&ElementRef>>;
+/// Child of the layout containers.
+///
+/// The containers are generic over their children, so that the layout engine
+/// can be reused with other leaf types, see [`crate::layout`].
+pub trait LayoutItem: Measure {
+    /// Placement in a [`Grid`] cell or, with
+    /// [`Stack::with_align_children`], in the free time of a [`Stack`] larger
+    /// than the child.
+    fn alignment(&self) -> Alignment {
+        Alignment::End
+    }
 }
 
+#[cfg(test)]
+impl LayoutItem for MockMeasure {}
+
 #[derive(Debug)]
 struct MinMax {
     min: Time,
@@ -76,7 +145,7 @@ struct MinMax {
 macro_rules! impl_variant {
     ($($variant:ident),*$(,)?) => {
         #[derive(Debug, Clone)]
-        pub(crate) enum ElementVariant {
+        pub enum ElementVariant {
             $($variant($variant),)*
         }
 
@@ -110,6 +179,15 @@ macro_rules! impl_variant {
         }
         )*
 
+        impl ElementVariant {
+            /// Name of the variant type, e.g. `"Stack"`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(ElementVariant::$variant(_) => stringify!($variant),)*
+                }
+            }
+        }
+
         impl Measure for ElementVariant {
             fn measure(&self) -> Time {
                 match self {
@@ -127,19 +205,26 @@ macro_rules! impl_variant {
 }
 
 impl_variant!(
-    Play, ShiftPhase, SetPhase, ShiftFreq, SetFreq, SwapPhase, Barrier, Repeat, Stack, Absolute,
-    Grid,
+    Play, ShiftPhase, SetPhase, ShiftFreq, SetFreq, PhaseRamp, SwapPhase, Barrier, Delay, Marker,
+    Repeat, Stack, Absolute, Overlay, Grid, Scale, Reverse,
 );
 
 impl Element {
-    pub(crate) fn new(common: ElementCommon, variant: impl Into<ElementVariant>) -> Self {
+    pub fn new(common: ElementCommon, variant: impl Into<ElementVariant>) -> Self {
         Self {
             common,
             variant: variant.into(),
         }
     }
 
-    pub(crate) fn inner_time_range(&self, time_range: TimeRange) -> TimeRange {
+    /// Duration of the element after applying duration constraints, excluding
+    /// margins.
+    pub fn inner_duration(&self) -> Time {
+        let min_max = self.common.min_max_duration();
+        min_max.clamp(self.variant.measure())
+    }
+
+    pub fn inner_time_range(&self, time_range: TimeRange) -> TimeRange {
         let min_max = self.common.min_max_duration();
         let inner_start = time_range.start + self.common.margin.0;
         let inner_span = min_max.clamp(time_range.span - self.common.total_margin());
@@ -151,30 +236,52 @@ impl Element {
 }
 
 impl ElementCommon {
-    pub(crate) fn margin(&self) -> (Time, Time) {
+    pub fn margin(&self) -> (Time, Time) {
         self.margin
     }
 
-    pub(crate) fn alignment(&self) -> Alignment {
+    pub fn alignment(&self) -> Alignment {
         self.alignment
     }
 
-    pub(crate) fn phantom(&self) -> bool {
+    pub fn phantom(&self) -> bool {
         self.phantom
     }
 
-    pub(crate) fn duration(&self) -> Option<Time> {
+    /// Whether the element is phantom and ignored by the layout, i.e. it is
+    /// measured as zero and occupies no channels.
+    pub fn phantom_ignored(&self) -> bool {
+        self.phantom && self.phantom_ignored
+    }
+
+    pub fn duration(&self) -> Option<Time> {
         self.duration
     }
 
-    pub(crate) fn max_duration(&self) -> Time {
+    pub fn max_duration(&self) -> Time {
         self.max_duration
     }
 
-    pub(crate) fn min_duration(&self) -> Time {
+    pub fn min_duration(&self) -> Time {
         self.min_duration
     }
 
+    pub fn label(&self) -> Option<&Label> {
+        self.label.as_ref()
+    }
+
+    pub fn duration_ref(&self) -> Option<&DurationRef> {
+        self.duration_ref.as_ref()
+    }
+
+    pub fn params(&self) -> &[ParamRef] {
+        &self.params
+    }
+
+    pub fn to_builder(&self) -> ElementCommonBuilder {
+        ElementCommonBuilder(self.clone())
+    }
+
     fn min_max_duration(&self) -> MinMax {
         let min_max = MinMax::new(self.min_duration, self.max_duration);
         let max = min_max.clamp(self.duration.unwrap_or(Time::INFINITY));
@@ -188,41 +295,63 @@ impl ElementCommon {
 }
 
 impl ElementCommonBuilder {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         Self::default()
     }
 
-    pub(crate) fn margin(&mut self, margin: (Time, Time)) -> &mut Self {
+    pub fn margin(&mut self, margin: (Time, Time)) -> &mut Self {
         self.0.margin = margin;
         self
     }
 
-    pub(crate) fn alignment(&mut self, alignment: Alignment) -> &mut Self {
+    pub fn alignment(&mut self, alignment: Alignment) -> &mut Self {
         self.0.alignment = alignment;
         self
     }
 
-    pub(crate) fn phantom(&mut self, phantom: bool) -> &mut Self {
+    pub fn phantom(&mut self, phantom: bool) -> &mut Self {
         self.0.phantom = phantom;
         self
     }
 
-    pub(crate) fn duration(&mut self, duration: Option<Time>) -> &mut Self {
+    /// Ignores the element in the layout if it is phantom. By default phantom
+    /// elements are measured and arranged like other elements.
+    pub fn phantom_ignored(&mut self, phantom_ignored: bool) -> &mut Self {
+        self.0.phantom_ignored = phantom_ignored;
+        self
+    }
+
+    pub fn duration(&mut self, duration: Option<Time>) -> &mut Self {
         self.0.duration = duration;
         self
     }
 
-    pub(crate) fn max_duration(&mut self, max_duration: Time) -> &mut Self {
+    pub fn max_duration(&mut self, max_duration: Time) -> &mut Self {
         self.0.max_duration = max_duration;
         self
     }
 
-    pub(crate) fn min_duration(&mut self, min_duration: Time) -> &mut Self {
+    pub fn min_duration(&mut self, min_duration: Time) -> &mut Self {
         self.0.min_duration = min_duration;
         self
     }
 
-    pub(crate) fn validate(&self) -> Result<()> {
+    pub fn label(&mut self, label: Option<Label>) -> &mut Self {
+        self.0.label = label;
+        self
+    }
+
+    pub fn duration_ref(&mut self, duration_ref: Option<DurationRef>) -> &mut Self {
+        self.0.duration_ref = duration_ref;
+        self
+    }
+
+    pub fn params(&mut self, params: Vec<ParamRef>) -> &mut Self {
+        self.0.params = params;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
         let v = &self.0;
         if !(v.margin.0.value().is_finite() && v.margin.1.value().is_finite()) {
             bail!("Invalid margin {:?}", v.margin);
@@ -238,15 +367,55 @@ impl ElementCommonBuilder {
         if v.max_duration < Time::ZERO {
             bail!("Invalid max_duration {:?}", v.max_duration);
         }
+        if let Some(r) = &v.duration_ref {
+            if !(r.factor.is_finite() && r.factor >= 0.0) {
+                bail!("Invalid duration factor {:?}", r.factor);
+            }
+            if v.duration.is_some() {
+                bail!("duration and duration reference are mutually exclusive");
+            }
+        }
+        let mut fields = HashSet::new();
+        if let Some(p) = v.params.iter().find(|p| !fields.insert(&p.field)) {
+            bail!("Field {} is bound to more than one parameter", p.field);
+        }
         Ok(())
     }
 
-    pub(crate) fn build(&self) -> Result<ElementCommon> {
+    pub fn build(&self) -> Result<ElementCommon> {
         self.validate()?;
         Ok(self.0.clone())
     }
 }
 
+impl DurationRef {
+    pub fn new(label: Label, factor: f64) -> Self {
+        Self { label, factor }
+    }
+
+    pub fn label(&self) -> &Label {
+        &self.label
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+}
+
+impl ParamRef {
+    pub fn new(field: String, name: String) -> Self {
+        Self { field, name }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl MinMax {
     fn new(min: Time, max: Time) -> Self {
         Self { min, max }
@@ -263,27 +432,48 @@ impl Default for ElementCommonBuilder {
             margin: Default::default(),
             alignment: Alignment::End,
             phantom: false,
+            phantom_ignored: false,
             duration: None,
             max_duration: Time::INFINITY,
             min_duration: Default::default(),
+            label: None,
+            duration_ref: None,
+            params: vec![],
         })
     }
 }
 
 impl Measure for Element {
     fn measure(&self) -> Time {
-        let inner_duration = self.variant.measure();
-        let min_max = self.common.min_max_duration();
-        let duration = min_max.clamp(inner_duration) + self.common.total_margin();
+        if self.common.phantom_ignored() {
+            return Time::ZERO;
+        }
+        let duration = self.inner_duration() + self.common.total_margin();
         duration.max(Time::ZERO)
     }
 
     fn channels(&self) -> &[ChannelId] {
+        if self.common.phantom_ignored() {
+            return &[];
+        }
         self.variant.channels()
     }
+
+    fn ignored(&self) -> bool {
+        self.common.phantom_ignored()
+    }
+}
+
+impl LayoutItem for Element {
+    fn alignment(&self) -> Alignment {
+        self.common.alignment
+    }
 }
 
-impl Measure for ElementRef {
+impl<T> Measure for Arc<T>
+where
+    T: Measure + ?Sized,
+{
     fn measure(&self) -> Time {
         (**self).measure()
     }
@@ -291,6 +481,19 @@ impl Measure for ElementRef {
     fn channels(&self) -> &[ChannelId] {
         (**self).channels()
     }
+
+    fn ignored(&self) -> bool {
+        (**self).ignored()
+    }
+}
+
+impl<T> LayoutItem for Arc<T>
+where
+    T: LayoutItem + ?Sized,
+{
+    fn alignment(&self) -> Alignment {
+        (**self).alignment()
+    }
 }
 
 impl<T> Measure for &T
@@ -304,6 +507,19 @@ where
     fn channels(&self) -> &[ChannelId] {
         (*self).channels()
     }
+
+    fn ignored(&self) -> bool {
+        (*self).ignored()
+    }
+}
+
+impl<T> LayoutItem for &T
+where
+    T: LayoutItem + ?Sized,
+{
+    fn alignment(&self) -> Alignment {
+        (*self).alignment()
+    }
 }
 
 fn merge_channel_ids<'a, I>(ids: I) -> Vec<ChannelId>
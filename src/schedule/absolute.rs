@@ -10,54 +10,76 @@ use crate::{
 use super::{Arrange, Arranged, TimeRange};
 
 #[derive(Debug, Clone)]
-pub(crate) struct AbsoluteEntry {
+pub struct AbsoluteEntry<C = ElementRef> {
     time: Time,
-    element: ElementRef,
+    element: C,
 }
 
-#[derive(Debug, Clone, Default)]
-pub(crate) struct Absolute {
-    children: Vec<AbsoluteEntry>,
+#[derive(Debug, Clone)]
+pub struct Absolute<C = ElementRef> {
+    children: Vec<AbsoluteEntry<C>>,
     channel_ids: Vec<ChannelId>,
     measure_result: OnceLock<Time>,
 }
 
-impl AbsoluteEntry {
-    pub(crate) fn new(element: ElementRef) -> Self {
+impl<C> AbsoluteEntry<C> {
+    pub fn new(element: C) -> Self {
         Self {
             time: Time::ZERO,
             element,
         }
     }
 
-    pub(crate) fn with_time(mut self, time: Time) -> Result<Self> {
+    pub fn with_time(mut self, time: Time) -> Result<Self> {
         if !time.value().is_finite() {
             bail!("Invalid time {:?}", time);
         }
         self.time = time;
         Ok(self)
     }
+
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    pub fn element(&self) -> &C {
+        &self.element
+    }
 }
 
-impl Absolute {
-    pub(crate) fn new() -> Self {
+impl<C: Measure> Absolute<C> {
+    pub fn new() -> Self {
         Self::default()
     }
 
-    pub(crate) fn with_children(mut self, children: Vec<AbsoluteEntry>) -> Self {
-        let channel_ids = merge_channel_ids(children.iter().map(|e| e.element.variant.channels()));
+    pub fn with_children(mut self, children: Vec<AbsoluteEntry<C>>) -> Self {
+        let channel_ids = merge_channel_ids(children.iter().map(|e| e.element.channels()));
         self.children = children;
         self.channel_ids = channel_ids;
         self
     }
 
+    pub fn children(&self) -> &[AbsoluteEntry<C>] {
+        &self.children
+    }
+
     fn measure_result(&self) -> &Time {
         self.measure_result
             .get_or_init(|| measure_absolute(self.children.iter().map(|e| (&e.element, e.time))))
     }
 }
 
-impl Measure for Absolute {
+impl<C> Default for Absolute<C> {
+    fn default() -> Self {
+        Self {
+            children: vec![],
+            channel_ids: vec![],
+            measure_result: OnceLock::new(),
+        }
+    }
+}
+
+impl<C: Measure> Measure for Absolute<C> {
     fn measure(&self) -> Time {
         *self.measure_result()
     }
@@ -67,8 +89,10 @@ impl Measure for Absolute {
     }
 }
 
-impl Arrange for Absolute {
-    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&ElementRef>> {
+impl<C: Measure> Arrange for Absolute<C> {
+    type Child = C;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&C>> {
         self.children.iter().map(
             move |AbsoluteEntry {
                       time: offset,
@@ -93,6 +117,7 @@ where
 {
     children
         .into_iter()
+        .filter(|(child, _)| !child.ignored())
         .map(|(child, offset)| offset + child.measure())
         .max()
         .unwrap_or(Time::ZERO)
@@ -0,0 +1,360 @@
+use std::{collections::BTreeMap, ops::Range, sync::Arc};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::{executor::path_segment, quant::Label};
+
+use super::{ElementRef, ElementVariant};
+
+/// Largest table of [`align`] before falling back to matching by position.
+const MAX_ALIGN_CELLS: usize = 1 << 20;
+
+/// Field or element which differs between two schedule trees, see [`diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Path to the element like `"Stack > 0: Play 'x'"`, in the new tree if
+    /// the element exists there.
+    pub path: String,
+    /// Name of the field in the format of [`to_json`](super::to_json), or
+    /// `"element"` if the element was added, removed or changed its variant.
+    pub field: String,
+    /// `None` if the field has its default value or the element was added.
+    pub old: Option<Value>,
+    /// `None` if the field has its default value or the element was removed.
+    pub new: Option<Value>,
+}
+
+/// Compares two schedule trees field by field.
+///
+/// Equal children at the start and the end of containers are skipped and the
+/// rest is matched by variant and label, so inserting or removing a child is
+/// reported once. The order of `channel_ids` of [`Barrier`](super::Barrier)
+/// and [`Delay`](super::Delay) is ignored. Shared subtrees are equal without
+/// being compared.
+pub fn diff(old: &ElementRef, new: &ElementRef) -> Result<Vec<Difference>> {
+    let old_value = serde_json::to_value(old)?;
+    let new_value = serde_json::to_value(new)?;
+    let mut path = vec![new.variant.name().to_string()];
+    let mut differences = vec![];
+    diff_element(
+        Node::root(old, &old_value),
+        Node::root(new, &new_value),
+        &mut path,
+        &mut differences,
+    );
+    Ok(differences)
+}
+
+/// Element with its serialized value and the fields of its container entry,
+/// like the `time` of an [`AbsoluteEntry`](super::AbsoluteEntry).
+#[derive(Clone, Copy)]
+struct Node<'a> {
+    element: &'a ElementRef,
+    value: &'a Value,
+    entry: &'a Value,
+}
+
+impl<'a> Node<'a> {
+    fn root(element: &'a ElementRef, value: &'a Value) -> Self {
+        Self {
+            element,
+            value,
+            entry: value,
+        }
+    }
+
+    fn fields(&self) -> BTreeMap<&'a str, &'a Value> {
+        object_fields(self.value, &["child", "children"])
+    }
+
+    fn entry_fields(&self) -> BTreeMap<&'a str, &'a Value> {
+        if std::ptr::eq(self.entry, self.value) {
+            BTreeMap::new()
+        } else {
+            object_fields(self.entry, &["element"])
+        }
+    }
+
+    fn key(&self) -> (&'static str, Option<&'a Label>) {
+        (self.element.variant.name(), self.element.common.label())
+    }
+
+    fn children(&self) -> Vec<Node<'a>> {
+        let elements: Vec<&ElementRef> = match &self.element.variant {
+            ElementVariant::Repeat(r) => vec![r.child()],
+            ElementVariant::Scale(s) => vec![s.child()],
+            ElementVariant::Reverse(v) => vec![v.child()],
+            ElementVariant::Stack(s) => s.children().iter().collect(),
+            ElementVariant::Absolute(a) => a.children().iter().map(|e| e.element()).collect(),
+            ElementVariant::Overlay(o) => o.children().iter().collect(),
+            ElementVariant::Grid(g) => g.children().iter().map(|e| e.element()).collect(),
+            _ => vec![],
+        };
+        let entries: Vec<&Value> = match (self.value.get("child"), self.value.get("children")) {
+            (Some(child), _) => vec![child],
+            (None, Some(Value::Array(children))) => children.iter().collect(),
+            _ => vec![],
+        };
+        elements
+            .into_iter()
+            .zip(entries)
+            .map(|(element, entry)| Node {
+                element,
+                value: entry.get("element").unwrap_or(entry),
+                entry,
+            })
+            .collect()
+    }
+}
+
+fn diff_element(old: Node, new: Node, path: &mut Vec<String>, out: &mut Vec<Difference>) {
+    if Arc::ptr_eq(old.element, new.element) && old.entry_fields() == new.entry_fields() {
+        return;
+    }
+    let joined = path.join(" > ");
+    diff_fields(&old.entry_fields(), &new.entry_fields(), &joined, out);
+    if old.element.variant.name() != new.element.variant.name() {
+        out.push(Difference {
+            path: joined,
+            field: "element".into(),
+            old: Some(old.value.clone()),
+            new: Some(new.value.clone()),
+        });
+        return;
+    }
+    diff_fields(&old.fields(), &new.fields(), &joined, out);
+
+    let old_children = old.children();
+    let new_children = new.children();
+    let prefix = old_children
+        .iter()
+        .zip(&new_children)
+        .take_while(|(o, n)| o.entry == n.entry)
+        .count();
+    let suffix = old_children[prefix..]
+        .iter()
+        .rev()
+        .zip(new_children[prefix..].iter().rev())
+        .take_while(|(o, n)| o.entry == n.entry)
+        .count();
+    let old_range = prefix..old_children.len() - suffix;
+    let new_range = prefix..new_children.len() - suffix;
+    for pair in align(&old_children, old_range, &new_children, new_range) {
+        match pair {
+            (Some(i), Some(j)) => {
+                path.push(path_segment(j, new_children[j].element));
+                diff_element(old_children[i], new_children[j], path, out);
+            }
+            (Some(i), None) => {
+                let o = old_children[i];
+                path.push(path_segment(i, o.element));
+                out.push(Difference {
+                    path: path.join(" > "),
+                    field: "element".into(),
+                    old: Some(o.value.clone()),
+                    new: None,
+                });
+            }
+            (None, Some(j)) => {
+                let n = new_children[j];
+                path.push(path_segment(j, n.element));
+                out.push(Difference {
+                    path: path.join(" > "),
+                    field: "element".into(),
+                    old: None,
+                    new: Some(n.value.clone()),
+                });
+            }
+            (None, None) => continue,
+        }
+        path.pop();
+    }
+}
+
+/// Pairs of indices of matched children, with `None` for removed or added
+/// children.
+///
+/// Children are matched by position if the numbers are equal. Otherwise the
+/// longest common subsequence of variants and labels is matched, unless the
+/// table would be too large.
+fn align(
+    old: &[Node],
+    old_range: Range<usize>,
+    new: &[Node],
+    new_range: Range<usize>,
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let (m, n) = (old_range.len(), new_range.len());
+    if m == n || m.saturating_mul(n) > MAX_ALIGN_CELLS {
+        return (0..m.max(n))
+            .map(|k| {
+                let i = old_range.clone().nth(k);
+                let j = new_range.clone().nth(k);
+                (i, j)
+            })
+            .collect();
+    }
+    let old_keys: Vec<_> = old[old_range.clone()].iter().map(Node::key).collect();
+    let new_keys: Vec<_> = new[new_range.clone()].iter().map(Node::key).collect();
+    // lengths[i][j] is the length of the common subsequence of the suffixes.
+    let mut lengths = vec![vec![0u32; n + 1]; m + 1];
+    for i in (0..m).rev() {
+        for j in (0..n).rev() {
+            lengths[i][j] = if old_keys[i] == new_keys[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < m || j < n {
+        if i < m && j < n && old_keys[i] == new_keys[j] {
+            pairs.push((Some(old_range.start + i), Some(new_range.start + j)));
+            i += 1;
+            j += 1;
+        } else if j == n || i < m && lengths[i + 1][j] >= lengths[i][j + 1] {
+            pairs.push((Some(old_range.start + i), None));
+            i += 1;
+        } else {
+            pairs.push((None, Some(new_range.start + j)));
+            j += 1;
+        }
+    }
+    pairs
+}
+
+fn diff_fields(
+    old: &BTreeMap<&str, &Value>,
+    new: &BTreeMap<&str, &Value>,
+    path: &str,
+    out: &mut Vec<Difference>,
+) {
+    let mut keys: Vec<&str> = old.keys().chain(new.keys()).copied().collect();
+    keys.sort_unstable();
+    keys.dedup();
+    for key in keys {
+        let o = old.get(key).copied();
+        let n = new.get(key).copied();
+        let equal = if key == "channel_ids" {
+            sorted_ids(o) == sorted_ids(n)
+        } else {
+            o == n
+        };
+        if !equal {
+            out.push(Difference {
+                path: path.to_string(),
+                field: key.to_string(),
+                old: o.cloned(),
+                new: n.cloned(),
+            });
+        }
+    }
+}
+
+fn object_fields<'a>(value: &'a Value, skip: &[&str]) -> BTreeMap<&'a str, &'a Value> {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(k, _)| !skip.contains(&k.as_str()))
+        .map(|(k, v)| (k.as_str(), v))
+        .collect()
+}
+
+fn sorted_ids(value: Option<&Value>) -> Vec<&str> {
+    let mut ids: Vec<_> = value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        quant::{ChannelId, Time},
+        schedule::{
+            test_util::play, Absolute, AbsoluteEntry, Barrier, Element, ElementCommonBuilder, Stack,
+        },
+    };
+
+    fn stack(children: Vec<ElementRef>) -> ElementRef {
+        Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(children),
+        ))
+    }
+
+    fn barrier(channels: &[&str]) -> ElementRef {
+        Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Barrier::new(channels.iter().map(|&c| ChannelId::new(c)).collect()),
+        ))
+    }
+
+    #[test]
+    fn changed_field_and_inserted_child() {
+        let shared = play("q0", 0.5, Some("x"));
+        let inserted = play("q0", 0.1, Some("z"));
+        let old = stack(vec![
+            shared.clone(),
+            barrier(&["q0", "q1"]),
+            play("q1", 0.2, Some("y")),
+        ]);
+        let new = stack(vec![
+            shared,
+            inserted.clone(),
+            barrier(&["q1", "q0"]),
+            play("q1", 0.3, Some("y")),
+        ]);
+
+        let differences = diff(&old, &new).unwrap();
+
+        assert_eq!(
+            differences,
+            [
+                Difference {
+                    path: "Stack > 1: Play 'z'".into(),
+                    field: "element".into(),
+                    old: None,
+                    new: Some(serde_json::to_value(&inserted).unwrap()),
+                },
+                Difference {
+                    path: "Stack > 3: Play 'y'".into(),
+                    field: "amplitude".into(),
+                    old: Some(json!(0.2)),
+                    new: Some(json!(0.3)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_fields_and_variant_change() {
+        let absolute = |time: f64, child: ElementRef| {
+            Arc::new(Element::new(
+                ElementCommonBuilder::new().build().unwrap(),
+                Absolute::new().with_children(vec![AbsoluteEntry::new(child)
+                    .with_time(Time::new(time).unwrap())
+                    .unwrap()]),
+            ))
+        };
+        let old = absolute(1.0, play("q0", 0.5, Some("x")));
+        let new = absolute(2.0, stack(vec![]));
+
+        let differences = diff(&old, &new).unwrap();
+
+        let fields: Vec<_> = differences.iter().map(|d| d.field.as_str()).collect();
+        assert_eq!(fields, ["time", "element"]);
+        assert_eq!(differences[1].path, "Absolute > 0: Stack");
+        assert_eq!(differences[1].old.as_ref().unwrap()["type"], "play");
+    }
+}
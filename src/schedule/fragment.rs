@@ -0,0 +1,309 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+};
+
+use anyhow::Result;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::executor::path_segment;
+
+use super::{Element, ElementRef, ElementVariant};
+
+/// Named and versioned schedule fragments, e.g. a gate library shared
+/// between experiments.
+///
+/// A fragment is a factory `F` which builds a subtree from named parameters.
+/// The subtrees built from the fragments are remembered with the arguments,
+/// so that [`instances`](Self::instances) can tell which fragments a schedule
+/// was built from. Only weak references are kept, so the registry doesn't
+/// keep the subtrees alive.
+#[derive(Debug, Clone)]
+pub struct FragmentRegistry<F> {
+    fragments: BTreeMap<String, BTreeMap<u32, Fragment<F>>>,
+    built: Vec<(Weak<Element>, Instance)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fragment<F> {
+    pub params: Vec<String>,
+    pub factory: F,
+}
+
+/// Fragment instance found in a schedule, see
+/// [`FragmentRegistry::instances`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FragmentInstance {
+    pub name: String,
+    pub version: u32,
+    pub arguments: Map<String, Value>,
+    /// Paths to the occurrences like `"Stack > 1: Stack 'x90'"`, see
+    /// [`diff`](super::diff).
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum FragmentError {
+    #[error("Fragment '{0}' is not registered")]
+    NotFound(String),
+    #[error("Fragment '{name}' has no version {version}")]
+    VersionNotFound { name: String, version: u32 },
+    #[error("Fragment '{name}' version {version} is already registered")]
+    DuplicateVersion { name: String, version: u32 },
+    #[error("Fragment '{name}' has no parameter '{param}'")]
+    UnknownParameter { name: String, param: String },
+}
+
+#[derive(Debug, Clone)]
+struct Instance {
+    name: String,
+    version: u32,
+    arguments: Map<String, Value>,
+}
+
+/// Schedule with the fragment instances it contains.
+#[derive(Serialize, Deserialize)]
+struct Document {
+    schedule: ElementRef,
+    #[serde(default)]
+    fragments: Vec<FragmentInstance>,
+}
+
+impl<F> FragmentRegistry<F> {
+    pub fn new() -> Self {
+        Self {
+            fragments: BTreeMap::new(),
+            built: Vec::new(),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        name: String,
+        version: u32,
+        params: Vec<String>,
+        factory: F,
+    ) -> Result<(), FragmentError> {
+        let versions = self.fragments.entry(name.clone()).or_default();
+        if versions.contains_key(&version) {
+            return Err(FragmentError::DuplicateVersion { name, version });
+        }
+        versions.insert(version, Fragment { params, factory });
+        Ok(())
+    }
+
+    /// Names of the registered fragments, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fragments.keys().map(String::as_str)
+    }
+
+    /// Registered versions of the fragment, sorted.
+    pub fn versions(&self, name: &str) -> Result<Vec<u32>, FragmentError> {
+        let versions = self
+            .fragments
+            .get(name)
+            .ok_or_else(|| FragmentError::NotFound(name.to_string()))?;
+        Ok(versions.keys().copied().collect())
+    }
+
+    /// Looks up a version of the fragment, or the latest one if `version` is
+    /// `None`, and checks that it has the parameters in `arguments`.
+    pub fn get(
+        &self,
+        name: &str,
+        version: Option<u32>,
+        arguments: &Map<String, Value>,
+    ) -> Result<(u32, &Fragment<F>), FragmentError> {
+        let versions = self
+            .fragments
+            .get(name)
+            .ok_or_else(|| FragmentError::NotFound(name.to_string()))?;
+        let (&version, fragment) =
+            match version {
+                Some(version) => versions.get_key_value(&version).ok_or_else(|| {
+                    FragmentError::VersionNotFound {
+                        name: name.to_string(),
+                        version,
+                    }
+                })?,
+                None => versions
+                    .last_key_value()
+                    .expect("Registered fragments should have a version"),
+            };
+        if let Some(param) = arguments.keys().find(|k| !fragment.params.contains(k)) {
+            return Err(FragmentError::UnknownParameter {
+                name: name.to_string(),
+                param: param.clone(),
+            });
+        }
+        Ok((version, fragment))
+    }
+
+    /// Remembers that `element` was built from the fragment.
+    pub fn record(
+        &mut self,
+        element: &ElementRef,
+        name: String,
+        version: u32,
+        arguments: Map<String, Value>,
+    ) {
+        self.built.retain(|(e, _)| e.strong_count() > 0);
+        let instance = Instance {
+            name,
+            version,
+            arguments,
+        };
+        self.built.push((Arc::downgrade(element), instance));
+    }
+
+    /// Fragment instances in the tree in the order of their first occurrence.
+    /// Instances in shared subtrees have a path for every occurrence.
+    pub fn instances(&self, root: &ElementRef) -> Vec<FragmentInstance> {
+        let built: HashMap<_, _> = self
+            .built
+            .iter()
+            .map(|(e, instance)| (e.as_ptr(), instance))
+            .collect();
+        let mut found: Vec<FragmentInstance> = vec![];
+        let mut indices = HashMap::new();
+        let mut path = vec![root.variant.name().to_string()];
+        walk(root, &mut path, &mut |element, path| {
+            let Some(instance) = built.get(&Arc::as_ptr(element)) else {
+                return;
+            };
+            let index = *indices.entry(Arc::as_ptr(element)).or_insert_with(|| {
+                found.push(FragmentInstance {
+                    name: instance.name.clone(),
+                    version: instance.version,
+                    arguments: instance.arguments.clone(),
+                    paths: vec![],
+                });
+                found.len() - 1
+            });
+            found[index].paths.push(path.join(" > "));
+        });
+        found
+    }
+}
+
+impl<F> Default for FragmentRegistry<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn walk(
+    element: &ElementRef,
+    path: &mut Vec<String>,
+    visit: &mut impl FnMut(&ElementRef, &[String]),
+) {
+    visit(element, path);
+    let children: Vec<&ElementRef> = match &element.variant {
+        ElementVariant::Repeat(r) => vec![r.child()],
+        ElementVariant::Scale(s) => vec![s.child()],
+        ElementVariant::Reverse(v) => vec![v.child()],
+        ElementVariant::Stack(s) => s.children().iter().collect(),
+        ElementVariant::Absolute(a) => a.children().iter().map(|e| e.element()).collect(),
+        ElementVariant::Overlay(o) => o.children().iter().collect(),
+        ElementVariant::Grid(g) => g.children().iter().map(|e| e.element()).collect(),
+        _ => vec![],
+    };
+    for (i, child) in children.into_iter().enumerate() {
+        path.push(path_segment(i, child));
+        walk(child, path, visit);
+        path.pop();
+    }
+}
+
+/// Serializes the schedule like [`to_json`](super::to_json) together with the
+/// fragment instances it was built from, as
+/// `{"schedule": ..., "fragments": [...]}`.
+pub fn to_json_with_fragments(root: &ElementRef, fragments: &[FragmentInstance]) -> Result<String> {
+    let document = Document {
+        schedule: root.clone(),
+        fragments: fragments.to_vec(),
+    };
+    Ok(serde_json::to_string(&document)?)
+}
+
+/// Reads a document written by [`to_json_with_fragments`].
+pub fn from_json_with_fragments(text: &str) -> Result<(ElementRef, Vec<FragmentInstance>)> {
+    let document: Document = serde_json::from_str(text)?;
+    Ok((document.schedule, document.fragments))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::schedule::{test_util::play, ElementCommonBuilder, Stack};
+
+    fn x90(registry: &mut FragmentRegistry<()>, qubit: &str) -> ElementRef {
+        let arguments: Map<_, _> = [("qubit".to_string(), json!(qubit))].into_iter().collect();
+        let (version, _) = registry.get("x90", None, &arguments).unwrap();
+        let element = play(qubit, 0.5, Some("x90"));
+        registry.record(&element, "x90".into(), version, arguments);
+        element
+    }
+
+    #[test]
+    fn instances_in_schedule() {
+        let mut registry = FragmentRegistry::new();
+        let params = vec!["qubit".to_string()];
+        registry
+            .register("x90".into(), 1, params.clone(), ())
+            .unwrap();
+        registry
+            .register("x90".into(), 2, params.clone(), ())
+            .unwrap();
+        let q0 = x90(&mut registry, "q0");
+        let q1 = x90(&mut registry, "q1");
+        let unused = x90(&mut registry, "q2");
+        drop(unused);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![q0.clone(), q1, q0]),
+        ));
+
+        let instances = registry.instances(&root);
+
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[0].version, 2);
+        assert_eq!(instances[0].arguments["qubit"], "q0");
+        assert_eq!(
+            instances[0].paths,
+            ["Stack > 0: Play 'x90'", "Stack > 2: Play 'x90'"]
+        );
+        let text = to_json_with_fragments(&root, &instances).unwrap();
+        let (_, read) = from_json_with_fragments(&text).unwrap();
+        assert_eq!(read, instances);
+    }
+
+    #[test]
+    fn lookup_errors() {
+        let mut registry = FragmentRegistry::new();
+        registry.register("x90".into(), 1, vec![], ()).unwrap();
+        let arguments = [("qubit".to_string(), json!("q0"))].into_iter().collect();
+
+        assert!(matches!(
+            registry.register("x90".into(), 1, vec![], ()),
+            Err(FragmentError::DuplicateVersion { version: 1, .. })
+        ));
+        assert!(matches!(
+            registry.get("x90", Some(2), &Map::new()),
+            Err(FragmentError::VersionNotFound { version: 2, .. })
+        ));
+        assert!(matches!(
+            registry.get("x90", None, &arguments),
+            Err(FragmentError::UnknownParameter { .. })
+        ));
+        assert!(matches!(
+            registry.get("y90", None, &Map::new()),
+            Err(FragmentError::NotFound(_))
+        ));
+    }
+}
@@ -1,28 +1,42 @@
 mod helper;
 
-use std::sync::OnceLock;
+use std::{ops::Range, sync::OnceLock};
 
 use anyhow::{bail, Result};
 
 use crate::{
     quant::{ChannelId, Time},
-    schedule::{grid::helper::Helper, merge_channel_ids, Alignment, Arranged, ElementRef, Measure},
+    schedule::{
+        grid::helper::{normalize_span, Helper},
+        merge_channel_ids, Alignment, Arranged, ElementRef, LayoutItem, Measure,
+    },
     GridLength,
 };
 
 use super::{Arrange, TimeRange};
 
 #[derive(Debug, Clone)]
-pub(crate) struct GridEntry {
-    element: ElementRef,
+pub struct GridEntry<C = ElementRef> {
+    element: C,
     column: usize,
     span: usize,
+    row: usize,
+    row_span: usize,
 }
 
+/// Grid of time columns and channel rows.
+///
+/// The columns divide the time of the grid and are shared by all rows, so
+/// children in different rows are aligned to the same columns. A row is a lane
+/// of the channels in its definition, e.g. the channels of a qubit. A child
+/// spanning several rows may use the channels of all of them, and the channels
+/// of the rows are occupied by the grid even if no child uses them. A row
+/// without channels doesn't restrict its children.
 #[derive(Debug, Clone)]
-pub(crate) struct Grid {
-    children: Vec<GridEntry>,
+pub struct Grid<C = ElementRef> {
+    children: Vec<GridEntry<C>>,
     columns: Vec<GridLength>,
+    rows: Vec<Vec<ChannelId>>,
     channel_ids: Vec<ChannelId>,
     measure_result: OnceLock<MeasureResult>,
 }
@@ -34,41 +48,90 @@ struct MeasureResult {
     child_durations: Vec<Time>,
 }
 
+/// Child which needs more time than the columns it occupies provide, see
+/// [`Grid::find_conflict`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridConflict {
+    /// Index of the child in [`Grid::children`].
+    pub child: usize,
+    /// Measured duration of the child, including its minimum duration and
+    /// margins.
+    pub required: Time,
+    /// Total size of the columns spanned by the child, up to the end of the
+    /// grid.
+    pub available: Time,
+}
+
 struct MeasureItem {
     column: usize,
     span: usize,
     duration: Time,
 }
 
-impl GridEntry {
-    pub(crate) fn new(element: ElementRef) -> Self {
+impl<C> GridEntry<C> {
+    pub fn new(element: C) -> Self {
         Self {
             element,
             column: 0,
             span: 1,
+            row: 0,
+            row_span: 1,
         }
     }
 
-    pub(crate) fn with_column(mut self, column: usize) -> Self {
+    pub fn with_column(mut self, column: usize) -> Self {
         self.column = column;
         self
     }
 
-    pub(crate) fn with_span(mut self, span: usize) -> Result<Self> {
+    pub fn with_span(mut self, span: usize) -> Result<Self> {
         if span == 0 {
             bail!("Span should be greater than 0");
         }
         self.span = span;
         Ok(self)
     }
+
+    pub fn with_row(mut self, row: usize) -> Self {
+        self.row = row;
+        self
+    }
+
+    pub fn with_row_span(mut self, row_span: usize) -> Result<Self> {
+        if row_span == 0 {
+            bail!("Row span should be greater than 0");
+        }
+        self.row_span = row_span;
+        Ok(self)
+    }
+
+    pub fn element(&self) -> &C {
+        &self.element
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn span(&self) -> usize {
+        self.span
+    }
+
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn row_span(&self) -> usize {
+        self.row_span
+    }
 }
 
-impl Grid {
-    pub(crate) fn new() -> Self {
+impl<C: LayoutItem> Grid<C> {
+    pub fn new() -> Self {
         Self::default()
     }
 
-    pub(crate) fn with_columns(mut self, columns: Vec<GridLength>) -> Self {
+    pub fn with_columns(mut self, columns: Vec<GridLength>) -> Self {
         if columns.is_empty() {
             self.columns = vec![GridLength::star(1.0).unwrap()];
         } else {
@@ -78,18 +141,106 @@ impl Grid {
         self
     }
 
-    pub(crate) fn with_children(mut self, children: Vec<GridEntry>) -> Self {
-        let channel_ids = merge_channel_ids(children.iter().map(|e| e.element.variant.channels()));
+    /// Sets the channels of the rows. No rows means one row without channels.
+    pub fn with_rows(mut self, rows: Vec<Vec<ChannelId>>) -> Self {
+        if rows.is_empty() {
+            self.rows = vec![vec![]];
+        } else {
+            self.rows = rows;
+        }
+        self.update_channel_ids();
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<GridEntry<C>>) -> Self {
         self.children = children;
-        self.channel_ids = channel_ids;
+        self.update_channel_ids();
         self.measure_result.take();
         self
     }
 
-    pub(crate) fn columns(&self) -> &[GridLength] {
+    pub fn columns(&self) -> &[GridLength] {
         &self.columns
     }
 
+    pub fn rows(&self) -> &[Vec<ChannelId>] {
+        &self.rows
+    }
+
+    /// Rows occupied by the child, with the row index and span clamped to the
+    /// rows of the grid like the columns.
+    pub fn row_range(&self, entry: &GridEntry<C>) -> Range<usize> {
+        let span = normalize_span(entry.row, entry.row_span, self.rows.len());
+        span.start()..span.start() + span.span()
+    }
+
+    /// Finds the first child using a channel outside the rows it spans,
+    /// returning the index of the child and the channel.
+    pub fn find_row_violation(&self) -> Option<(usize, &ChannelId)> {
+        self.children.iter().enumerate().find_map(|(i, entry)| {
+            let rows = &self.rows[self.row_range(entry)];
+            if rows.iter().any(Vec::is_empty) {
+                return None;
+            }
+            entry
+                .element
+                .channels()
+                .iter()
+                .find(|c| !rows.iter().flatten().any(|r| r == *c))
+                .map(|c| (i, c))
+        })
+    }
+
+    pub fn children(&self) -> &[GridEntry<C>] {
+        &self.children
+    }
+
+    /// Finds the first child which doesn't fit in its columns by more than
+    /// `tolerance` when the grid is arranged in `span`.
+    ///
+    /// Star and auto columns grow to fit their children, and the star columns
+    /// which are already large enough grow last, so in the measured duration
+    /// only children spanning fixed columns alone can conflict. Such a child
+    /// would overlap the neighbouring columns or be shortened below its
+    /// minimum duration. If `span` is shorter than the measured duration, the
+    /// columns can't shrink below the minimum durations of their children
+    /// either, and the children in the columns past the end of the grid
+    /// conflict.
+    pub fn find_conflict(&self, span: Time, tolerance: Time) -> Option<GridConflict> {
+        let MeasureResult {
+            column_sizes,
+            child_durations,
+            ..
+        } = self.measure_result();
+        let mut helper = Helper::new_with_column_sizes(&self.columns, column_sizes.clone());
+        helper.expand_to_fit(span);
+        let column_starts = helper.column_starts();
+        self.children
+            .iter()
+            .zip(child_durations)
+            .enumerate()
+            .find_map(|(i, (entry, &required))| {
+                let columns = helper.normalize_span(entry.column, entry.span);
+                let start = column_starts[columns.start()].min(span);
+                let end = column_starts[columns.start() + columns.span()].min(span);
+                let available = end - start;
+                (required > available + tolerance).then_some(GridConflict {
+                    child: i,
+                    required,
+                    available,
+                })
+            })
+    }
+
+    fn update_channel_ids(&mut self) {
+        self.channel_ids = merge_channel_ids(
+            self.children
+                .iter()
+                .map(|e| e.element.channels())
+                .chain(self.rows.iter().map(Vec::as_slice)),
+        );
+    }
+
     fn measure_result(&self) -> &MeasureResult {
         self.measure_result.get_or_init(|| {
             measure_grid(
@@ -104,18 +255,19 @@ impl Grid {
     }
 }
 
-impl Default for Grid {
+impl<C> Default for Grid<C> {
     fn default() -> Self {
         Self {
             children: vec![],
             columns: vec![GridLength::star(1.0).unwrap()],
+            rows: vec![vec![]],
             channel_ids: vec![],
             measure_result: OnceLock::new(),
         }
     }
 }
 
-impl Measure for Grid {
+impl<C: LayoutItem> Measure for Grid<C> {
     fn measure(&self) -> Time {
         let MeasureResult { total_duration, .. } = self.measure_result();
         *total_duration
@@ -126,8 +278,10 @@ impl Measure for Grid {
     }
 }
 
-impl Arrange for Grid {
-    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&ElementRef>> {
+impl<C: LayoutItem> Arrange for Grid<C> {
+    type Child = C;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&C>> {
         let MeasureResult {
             column_sizes,
             child_durations,
@@ -142,6 +296,7 @@ impl Arrange for Grid {
                     element,
                     column,
                     span,
+                    ..
                 },
                 &child_duration,
             )| {
@@ -149,11 +304,12 @@ impl Arrange for Grid {
                 let start = span.start();
                 let span = span.span();
                 let span_duration = column_starts[start + span] - column_starts[start];
-                let child_duration = match element.common.alignment {
+                let alignment = element.alignment();
+                let child_duration = match alignment {
                     Alignment::Stretch => span_duration,
                     _ => child_duration,
                 };
-                let child_offset = match element.common.alignment {
+                let child_offset = match alignment {
                     Alignment::End => span_duration - child_duration,
                     Alignment::Center => (span_duration - child_duration) / 2.0,
                     _ => Time::ZERO,
@@ -213,6 +369,10 @@ mod tests {
     use test_case::test_case;
 
     use super::*;
+    use crate::schedule::{
+        test_util::{play, play_with},
+        ElementCommonBuilder,
+    };
 
     fn time_vec(v: &[f64]) -> Vec<Time> {
         v.iter().map(|&d| Time::new(d).unwrap()).collect()
@@ -246,4 +406,96 @@ mod tests {
         assert_eq!(total_duration, Time::new(expected.0).unwrap());
         assert_eq!(column_sizes, time_vec(&expected.1));
     }
+
+    #[test]
+    fn find_conflict() {
+        let child = |min_duration: f64| {
+            let common = ElementCommonBuilder::new()
+                .alignment(Alignment::Stretch)
+                .min_duration(Time::new(min_duration).unwrap())
+                .build()
+                .unwrap();
+            play_with("xy", 1.0, common)
+        };
+        let columns: Vec<GridLength> = ["*", "20"].iter().map(|s| s.parse().unwrap()).collect();
+        let grid = Grid::new().with_columns(columns).with_children(vec![
+            GridEntry::new(child(30.0)),
+            GridEntry::new(child(15.0)).with_column(1),
+            GridEntry::new(child(25.0)).with_column(1),
+        ]);
+
+        let span = grid.measure();
+        assert_eq!(span, Time::new(50.0).unwrap());
+        assert_eq!(
+            grid.find_conflict(span, Time::ZERO),
+            Some(GridConflict {
+                child: 2,
+                required: Time::new(25.0).unwrap(),
+                available: Time::new(20.0).unwrap(),
+            })
+        );
+        assert_eq!(grid.find_conflict(span, Time::new(5.0).unwrap()), None);
+    }
+
+    #[test]
+    fn find_conflict_in_star_columns() {
+        let child = |min_duration: f64| {
+            let common = ElementCommonBuilder::new()
+                .alignment(Alignment::Stretch)
+                .min_duration(Time::new(min_duration).unwrap())
+                .build()
+                .unwrap();
+            play_with("xy", 1.0, common)
+        };
+        let columns: Vec<GridLength> = ["*", "2*"].iter().map(|s| s.parse().unwrap()).collect();
+        let grid = Grid::new().with_columns(columns).with_children(vec![
+            GridEntry::new(child(30.0)),
+            GridEntry::new(child(20.0)).with_column(1),
+        ]);
+
+        // The second column gives up its share to fit the first child.
+        let span = Time::new(60.0).unwrap();
+        assert_eq!(grid.find_conflict(span, Time::ZERO), None);
+        let spans: Vec<_> = grid
+            .arrange(TimeRange {
+                start: Time::ZERO,
+                span,
+            })
+            .map(|a| a.time_range.span)
+            .collect();
+        assert_eq!(spans, time_vec(&[30.0, 30.0]));
+        assert_eq!(
+            grid.find_conflict(Time::new(40.0).unwrap(), Time::ZERO),
+            Some(GridConflict {
+                child: 1,
+                required: Time::new(20.0).unwrap(),
+                available: Time::new(10.0).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn rows() {
+        let rows = vec![
+            vec![ChannelId::new("q0")],
+            vec![ChannelId::new("q1"), ChannelId::new("c01")],
+            vec![ChannelId::new("q2")],
+        ];
+        let grid = Grid::new().with_rows(rows).with_children(vec![
+            GridEntry::new(play("q0", 1.0, None)),
+            GridEntry::new(play("c01", 1.0, None))
+                .with_row_span(2)
+                .unwrap(),
+            GridEntry::new(play("q1", 1.0, None))
+                .with_row(1)
+                .with_row_span(5)
+                .unwrap(),
+        ]);
+
+        assert_eq!(grid.channels().len(), 4);
+        assert_eq!(grid.row_range(&grid.children()[2]), 1..3);
+        assert_eq!(grid.find_row_violation(), None);
+        let grid = grid.with_children(vec![GridEntry::new(play("q1", 1.0, None)).with_row(2)]);
+        assert_eq!(grid.find_row_violation(), Some((0, &ChannelId::new("q1"))));
+    }
 }
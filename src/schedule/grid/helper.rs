@@ -60,10 +60,7 @@ impl<'a> Helper<'a> {
     }
 
     pub(super) fn normalize_span(&self, col: usize, span: usize) -> NormalizedSpan {
-        let n_col = self.columns.len();
-        let col = col.min(n_col - 1);
-        let span = span.min(n_col - col);
-        NormalizedSpan { start: col, span }
+        normalize_span(col, span, self.columns.len())
     }
 
     pub(super) fn expand_to_fit(&mut self, required: Time) -> bool {
@@ -166,6 +163,13 @@ impl<'a> Helper<'a> {
     }
 }
 
+/// Clamps the span of columns or rows to the `count` ones of the grid.
+pub(super) fn normalize_span(start: usize, span: usize, count: usize) -> NormalizedSpan {
+    let start = start.min(count - 1);
+    let span = span.min(count - start);
+    NormalizedSpan { start, span }
+}
+
 fn prefix_sum<T>(arr: &[T]) -> Vec<T>
 where
     T: std::ops::Add<Output = T> + Copy + Default,
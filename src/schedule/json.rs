@@ -0,0 +1,751 @@
+//! Serde representation of schedule trees.
+//!
+//! The same format is used for the `schedule` of experiment documents, which
+//! is why the deserializer also accepts the shorthands of the Python API, e.g.
+//! a single number as margin, and rejects unknown fields. Shared subtrees are
+//! written out at every occurrence and are no longer shared after
+//! deserialization.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::{
+    quant::{Amplitude, ChannelId, ChirpRate, Frequency, Label, Phase, ShapeId, Time},
+    schedule::{
+        Absolute, AbsoluteEntry, Barrier, Delay, DurationRef, Element, ElementCommon,
+        ElementCommonBuilder, ElementRef, ElementVariant, Grid, GridEntry, Marker, Overlay,
+        ParamRef, PhaseRamp, Play, Repeat, Reverse, Scale, SetFreq, SetPhase, ShiftFreq,
+        ShiftPhase, Stack, SwapPhase,
+    },
+    Alignment, Direction, GridLength, GridLengthUnit,
+};
+
+pub fn to_json(root: &ElementRef) -> Result<String> {
+    Ok(serde_json::to_string(root)?)
+}
+
+pub fn from_json(text: &str) -> Result<ElementRef> {
+    Ok(serde_json::from_str(text)?)
+}
+
+#[derive(Serialize)]
+struct ElementRepr {
+    #[serde(flatten)]
+    variant: VariantRepr,
+    #[serde(flatten)]
+    common: CommonRepr,
+}
+
+/// Fields of [`CommonRepr`]. They are split off before the variant is
+/// deserialized, because `deny_unknown_fields` doesn't work with flattening.
+const COMMON_FIELDS: &[&str] = &[
+    "margin",
+    "alignment",
+    "phantom",
+    "phantom_ignored",
+    "duration",
+    "duration_of",
+    "max_duration",
+    "min_duration",
+    "label",
+    "params",
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CommonRepr {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    margin: Option<MarginRepr>,
+    #[serde(
+        default = "default_alignment",
+        skip_serializing_if = "is_default_alignment"
+    )]
+    alignment: Alignment,
+    #[serde(default, skip_serializing_if = "PhantomRepr::is_false")]
+    phantom: PhantomRepr,
+    #[serde(default, skip_serializing_if = "is_false")]
+    phantom_ignored: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<Time>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration_of: Option<DurationOfRepr>,
+    /// `None` if unbounded, because JSON has no infinity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_duration: Option<Time>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    min_duration: Time,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<Label>,
+    /// Parameter names by field.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, String>,
+}
+
+/// Either the same margin on both sides or `[before, after]`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum MarginRepr {
+    Both(Time),
+    Pair(Time, Time),
+}
+
+/// Either a bool or `"layout"` or `"ignore"`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PhantomRepr {
+    Flag(bool),
+    Mode(PhantomMode),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PhantomMode {
+    Layout,
+    Ignore,
+}
+
+/// Either a label or `[label, factor]`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum DurationOfRepr {
+    Label(Label),
+    Scaled(Label, f64),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum VariantRepr {
+    Play(PlayRepr),
+    ShiftPhase {
+        channel_id: ChannelId,
+        phase: Phase,
+    },
+    SetPhase {
+        channel_id: ChannelId,
+        phase: Phase,
+    },
+    ShiftFreq {
+        channel_id: ChannelId,
+        frequency: Frequency,
+    },
+    SetFreq {
+        channel_id: ChannelId,
+        frequency: Frequency,
+    },
+    PhaseRamp {
+        channel_id: ChannelId,
+        rate: Frequency,
+    },
+    SwapPhase {
+        channel_id1: ChannelId,
+        channel_id2: ChannelId,
+    },
+    Barrier {
+        #[serde(default)]
+        channel_ids: Vec<ChannelId>,
+    },
+    Delay {
+        #[serde(default)]
+        channel_ids: Vec<ChannelId>,
+        delay: Time,
+    },
+    Marker {
+        channel_id: ChannelId,
+        width: Time,
+    },
+    Repeat {
+        child: ElementRef,
+        count: usize,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        spacing: Time,
+    },
+    Scale {
+        child: ElementRef,
+        factor: f64,
+    },
+    Reverse {
+        child: ElementRef,
+    },
+    Stack {
+        #[serde(default)]
+        children: Vec<ElementRef>,
+        #[serde(default = "default_direction")]
+        direction: Direction,
+        #[serde(default, skip_serializing_if = "is_false")]
+        auto_barrier: bool,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        spacing: Time,
+        #[serde(default, skip_serializing_if = "is_false")]
+        align_children: bool,
+    },
+    Absolute {
+        #[serde(default)]
+        children: Vec<AbsoluteEntryRepr>,
+    },
+    Overlay {
+        #[serde(default)]
+        children: Vec<ElementRef>,
+    },
+    Grid {
+        #[serde(default)]
+        children: Vec<GridEntryRepr>,
+        #[serde(default)]
+        columns: Vec<GridLengthRepr>,
+        #[serde(default, skip_serializing_if = "is_single_free_row")]
+        rows: Vec<Vec<ChannelId>>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlayRepr {
+    channel_id: ChannelId,
+    shape_id: Option<ShapeId>,
+    amplitude: Amplitude,
+    width: Time,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    plateau: Time,
+    #[serde(default, skip_serializing_if = "is_zero_f64")]
+    drag_coef: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    frequency: Frequency,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    chirp_rate: ChirpRate,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    phase: Phase,
+    #[serde(default, skip_serializing_if = "is_false")]
+    flexible: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    align_level: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fall_shape_id: Option<ShapeId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fall_width: Option<Time>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    centered: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AbsoluteEntryRepr {
+    #[serde(default)]
+    time: Time,
+    element: ElementRef,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GridEntryRepr {
+    #[serde(default)]
+    column: usize,
+    #[serde(default = "default_span")]
+    span: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    row: usize,
+    #[serde(default = "default_span", skip_serializing_if = "is_one")]
+    row_span: usize,
+    element: ElementRef,
+}
+
+/// Either a fixed duration or a string like `"auto"`, `"*"` or `"2*"`.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum GridLengthRepr {
+    Fixed(f64),
+    Str(String),
+}
+
+impl Serialize for Element {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ElementRepr::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = Map::deserialize(deserializer)?;
+        let common = COMMON_FIELDS
+            .iter()
+            .filter_map(|&name| fields.remove_entry(name))
+            .collect();
+        let repr = ElementRepr {
+            variant: serde_json::from_value(Value::Object(fields)).map_err(de::Error::custom)?,
+            common: serde_json::from_value(Value::Object(common)).map_err(de::Error::custom)?,
+        };
+        repr.try_into().map_err(de::Error::custom)
+    }
+}
+
+impl From<&Element> for ElementRepr {
+    fn from(element: &Element) -> Self {
+        Self {
+            variant: (&element.variant).into(),
+            common: (&element.common).into(),
+        }
+    }
+}
+
+impl TryFrom<ElementRepr> for Element {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: ElementRepr) -> Result<Self> {
+        let common: ElementCommon = repr.common.try_into()?;
+        let variant: ElementVariant = repr.variant.try_into()?;
+        Ok(Element::new(common, variant))
+    }
+}
+
+impl From<&ElementCommon> for CommonRepr {
+    fn from(common: &ElementCommon) -> Self {
+        let max_duration = common.max_duration();
+        let (before, after) = common.margin();
+        Self {
+            margin: (!is_zero_margin(&common.margin())).then_some(MarginRepr::Pair(before, after)),
+            alignment: common.alignment(),
+            phantom: PhantomRepr::Flag(common.phantom()),
+            phantom_ignored: common.phantom_ignored(),
+            duration: common.duration(),
+            duration_of: common
+                .duration_ref()
+                .map(|r| DurationOfRepr::Scaled(r.label().clone(), r.factor())),
+            max_duration: (max_duration != Time::INFINITY).then_some(max_duration),
+            min_duration: common.min_duration(),
+            label: common.label().cloned(),
+            params: common
+                .params()
+                .iter()
+                .map(|p| (p.field().to_string(), p.name().to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<CommonRepr> for ElementCommon {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: CommonRepr) -> Result<Self> {
+        let margin = match repr.margin {
+            Some(MarginRepr::Both(t)) => (t, t),
+            Some(MarginRepr::Pair(t1, t2)) => (t1, t2),
+            None => (Time::ZERO, Time::ZERO),
+        };
+        let (phantom, phantom_ignored) = match repr.phantom {
+            PhantomRepr::Flag(phantom) => (phantom, repr.phantom_ignored),
+            PhantomRepr::Mode(PhantomMode::Layout) => (true, repr.phantom_ignored),
+            PhantomRepr::Mode(PhantomMode::Ignore) => (true, true),
+        };
+        let duration_ref = repr.duration_of.map(|d| match d {
+            DurationOfRepr::Label(label) => DurationRef::new(label, 1.0),
+            DurationOfRepr::Scaled(label, factor) => DurationRef::new(label, factor),
+        });
+        ElementCommonBuilder::new()
+            .margin(margin)
+            .alignment(repr.alignment)
+            .phantom(phantom)
+            .phantom_ignored(phantom_ignored)
+            .duration(repr.duration)
+            .duration_ref(duration_ref)
+            .max_duration(repr.max_duration.unwrap_or(Time::INFINITY))
+            .min_duration(repr.min_duration)
+            .label(repr.label)
+            .params(
+                repr.params
+                    .into_iter()
+                    .map(|(field, name)| ParamRef::new(field, name))
+                    .collect(),
+            )
+            .build()
+    }
+}
+
+impl From<&ElementVariant> for VariantRepr {
+    fn from(variant: &ElementVariant) -> Self {
+        match variant {
+            ElementVariant::Play(v) => VariantRepr::Play(PlayRepr {
+                channel_id: v.channel_id().clone(),
+                shape_id: v.shape_id().cloned(),
+                amplitude: v.amplitude(),
+                width: v.width(),
+                plateau: v.plateau(),
+                drag_coef: v.drag_coef(),
+                frequency: v.frequency(),
+                chirp_rate: v.chirp_rate(),
+                phase: v.phase(),
+                flexible: v.flexible(),
+                align_level: v.align_level(),
+                fall_shape_id: v.fall_shape_id().cloned(),
+                fall_width: v.fall_width(),
+                centered: v.centered(),
+            }),
+            ElementVariant::ShiftPhase(v) => VariantRepr::ShiftPhase {
+                channel_id: v.channel_id().clone(),
+                phase: v.phase(),
+            },
+            ElementVariant::SetPhase(v) => VariantRepr::SetPhase {
+                channel_id: v.channel_id().clone(),
+                phase: v.phase(),
+            },
+            ElementVariant::ShiftFreq(v) => VariantRepr::ShiftFreq {
+                channel_id: v.channel_id().clone(),
+                frequency: v.frequency(),
+            },
+            ElementVariant::SetFreq(v) => VariantRepr::SetFreq {
+                channel_id: v.channel_id().clone(),
+                frequency: v.frequency(),
+            },
+            ElementVariant::PhaseRamp(v) => VariantRepr::PhaseRamp {
+                channel_id: v.channel_id().clone(),
+                rate: v.rate(),
+            },
+            ElementVariant::SwapPhase(v) => VariantRepr::SwapPhase {
+                channel_id1: v.channel_id1().clone(),
+                channel_id2: v.channel_id2().clone(),
+            },
+            ElementVariant::Barrier(v) => VariantRepr::Barrier {
+                channel_ids: v.channel_ids().to_vec(),
+            },
+            ElementVariant::Delay(v) => VariantRepr::Delay {
+                channel_ids: v.channel_ids().to_vec(),
+                delay: v.delay(),
+            },
+            ElementVariant::Marker(v) => VariantRepr::Marker {
+                channel_id: v.channel_id().clone(),
+                width: v.width(),
+            },
+            ElementVariant::Repeat(v) => VariantRepr::Repeat {
+                child: v.child().clone(),
+                count: v.count(),
+                spacing: v.spacing(),
+            },
+            ElementVariant::Scale(v) => VariantRepr::Scale {
+                child: v.child().clone(),
+                factor: v.factor(),
+            },
+            ElementVariant::Reverse(v) => VariantRepr::Reverse {
+                child: v.child().clone(),
+            },
+            ElementVariant::Stack(v) => VariantRepr::Stack {
+                children: v.children().to_vec(),
+                direction: v.direction(),
+                auto_barrier: v.auto_barrier(),
+                spacing: v.spacing(),
+                align_children: v.align_children(),
+            },
+            ElementVariant::Absolute(v) => VariantRepr::Absolute {
+                children: v
+                    .children()
+                    .iter()
+                    .map(|e| AbsoluteEntryRepr {
+                        time: e.time(),
+                        element: e.element().clone(),
+                    })
+                    .collect(),
+            },
+            ElementVariant::Overlay(v) => VariantRepr::Overlay {
+                children: v.children().to_vec(),
+            },
+            ElementVariant::Grid(v) => VariantRepr::Grid {
+                children: v
+                    .children()
+                    .iter()
+                    .map(|e| GridEntryRepr {
+                        column: e.column(),
+                        span: e.span(),
+                        row: e.row(),
+                        row_span: e.row_span(),
+                        element: e.element().clone(),
+                    })
+                    .collect(),
+                columns: v.columns().iter().map(Into::into).collect(),
+                rows: v.rows().to_vec(),
+            },
+        }
+    }
+}
+
+impl TryFrom<VariantRepr> for ElementVariant {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: VariantRepr) -> Result<Self> {
+        let variant = match repr {
+            VariantRepr::Play(p) => Play::new(p.channel_id, p.shape_id, p.amplitude, p.width)?
+                .with_plateau(p.plateau)?
+                .with_drag_coef(p.drag_coef)?
+                .with_frequency(p.frequency)?
+                .with_chirp_rate(p.chirp_rate)?
+                .with_phase(p.phase)?
+                .with_flexible(p.flexible)
+                .with_align_level(p.align_level)
+                .with_fall(p.fall_shape_id, p.fall_width)?
+                .with_centered(p.centered)
+                .into(),
+            VariantRepr::ShiftPhase { channel_id, phase } => {
+                ShiftPhase::new(channel_id, phase)?.into()
+            }
+            VariantRepr::SetPhase { channel_id, phase } => SetPhase::new(channel_id, phase)?.into(),
+            VariantRepr::ShiftFreq {
+                channel_id,
+                frequency,
+            } => ShiftFreq::new(channel_id, frequency)?.into(),
+            VariantRepr::SetFreq {
+                channel_id,
+                frequency,
+            } => SetFreq::new(channel_id, frequency)?.into(),
+            VariantRepr::PhaseRamp { channel_id, rate } => PhaseRamp::new(channel_id, rate)?.into(),
+            VariantRepr::SwapPhase {
+                channel_id1,
+                channel_id2,
+            } => SwapPhase::new(channel_id1, channel_id2).into(),
+            VariantRepr::Barrier { channel_ids } => Barrier::new(channel_ids).into(),
+            VariantRepr::Delay { channel_ids, delay } => Delay::new(channel_ids, delay)?.into(),
+            VariantRepr::Marker { channel_id, width } => Marker::new(channel_id, width)?.into(),
+            VariantRepr::Repeat {
+                child,
+                count,
+                spacing,
+            } => Repeat::new(child, count).with_spacing(spacing)?.into(),
+            VariantRepr::Scale { child, factor } => Scale::new(child, factor)?.into(),
+            VariantRepr::Reverse { child } => Reverse::new(child).into(),
+            VariantRepr::Stack {
+                children,
+                direction,
+                auto_barrier,
+                spacing,
+                align_children,
+            } => Stack::new()
+                .with_direction(direction)
+                .with_auto_barrier(auto_barrier)
+                .with_spacing(spacing)
+                .with_align_children(align_children)
+                .with_children(children)
+                .into(),
+            VariantRepr::Absolute { children } => {
+                let children = children
+                    .into_iter()
+                    .map(|e| AbsoluteEntry::new(e.element).with_time(e.time))
+                    .collect::<Result<_>>()?;
+                Absolute::new().with_children(children).into()
+            }
+            VariantRepr::Overlay { children } => Overlay::new().with_children(children).into(),
+            VariantRepr::Grid {
+                children,
+                columns,
+                rows,
+            } => {
+                let children = children
+                    .into_iter()
+                    .map(|e| {
+                        GridEntry::new(e.element)
+                            .with_column(e.column)
+                            .with_span(e.span)?
+                            .with_row(e.row)
+                            .with_row_span(e.row_span)
+                    })
+                    .collect::<Result<_>>()?;
+                let columns = columns
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<_>>()?;
+                let grid = Grid::new()
+                    .with_columns(columns)
+                    .with_rows(rows)
+                    .with_children(children);
+                if let Some((child, channel)) = grid.find_row_violation() {
+                    bail!("Grid child {child} uses channel {channel:?} outside of its rows");
+                }
+                grid.into()
+            }
+        };
+        Ok(variant)
+    }
+}
+
+impl From<&GridLength> for GridLengthRepr {
+    fn from(length: &GridLength) -> Self {
+        match length.unit {
+            GridLengthUnit::Seconds => GridLengthRepr::Fixed(length.value),
+            GridLengthUnit::Auto => GridLengthRepr::Str("auto".to_string()),
+            GridLengthUnit::Star => GridLengthRepr::Str(format!("{}*", length.value)),
+        }
+    }
+}
+
+impl TryFrom<GridLengthRepr> for GridLength {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: GridLengthRepr) -> Result<Self> {
+        match repr {
+            GridLengthRepr::Fixed(v) => Ok(GridLength::fixed(v)?),
+            GridLengthRepr::Str(s) => s.parse(),
+        }
+    }
+}
+
+impl Default for PhantomRepr {
+    fn default() -> Self {
+        PhantomRepr::Flag(false)
+    }
+}
+
+impl PhantomRepr {
+    fn is_false(&self) -> bool {
+        matches!(self, PhantomRepr::Flag(false))
+    }
+}
+
+fn default_alignment() -> Alignment {
+    Alignment::End
+}
+
+fn default_direction() -> Direction {
+    Direction::Backward
+}
+
+fn default_span() -> usize {
+    1
+}
+
+fn is_one(value: &usize) -> bool {
+    *value == 1
+}
+
+fn is_single_free_row(rows: &Vec<Vec<ChannelId>>) -> bool {
+    matches!(rows.as_slice(), [row] if row.is_empty())
+}
+
+fn is_default_alignment(alignment: &Alignment) -> bool {
+    *alignment == default_alignment()
+}
+
+fn is_zero_margin(margin: &(Time, Time)) -> bool {
+    *margin == (Time::ZERO, Time::ZERO)
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+fn is_zero_f64(value: &f64) -> bool {
+    *value == 0.0
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::schedule::Measure as _;
+
+    #[test]
+    fn round_trip() {
+        let play = Play::new(
+            ChannelId::new("xy"),
+            Some(ShapeId::new("hann")),
+            Amplitude::new(0.5).unwrap(),
+            Time::new(10.0).unwrap(),
+        )
+        .unwrap()
+        .with_drag_coef(0.1)
+        .unwrap();
+        let play = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .label(Some(Label::new("x90")))
+                .build()
+                .unwrap(),
+            play,
+        ));
+        let barrier = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .margin((Time::new(1.0).unwrap(), Time::ZERO))
+                .build()
+                .unwrap(),
+            Barrier::new(vec![ChannelId::new("xy")]),
+        ));
+        let grid = Grid::new()
+            .with_columns(vec![GridLength::auto(), "2*".parse().unwrap()])
+            .with_children(vec![
+                GridEntry::new(play.clone()),
+                GridEntry::new(barrier).with_column(1),
+            ]);
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new()
+                .max_duration(Time::new(100.0).unwrap())
+                .build()
+                .unwrap(),
+            Stack::new()
+                .with_direction(Direction::Forward)
+                .with_children(vec![
+                    Arc::new(Element::new(
+                        ElementCommonBuilder::new().build().unwrap(),
+                        grid,
+                    )),
+                    Arc::new(Element::new(
+                        ElementCommonBuilder::new().build().unwrap(),
+                        Delay::new(vec![], Time::new(5.0).unwrap()).unwrap(),
+                    )),
+                ]),
+        ));
+
+        let text = to_json(&root).unwrap();
+        let result = from_json(&text).unwrap();
+
+        assert_eq!(to_json(&result).unwrap(), text);
+        assert_eq!(result.measure(), root.measure());
+        assert_eq!(result.common.max_duration(), Time::new(100.0).unwrap());
+        let stack: &Stack = (&result.variant).try_into().unwrap();
+        assert_eq!(stack.direction(), Direction::Forward);
+        let grid: &Grid = (&stack.children()[0].variant).try_into().unwrap();
+        let play: &Play = (&grid.children()[0].element().variant).try_into().unwrap();
+        assert_eq!(play.drag_coef(), 0.1);
+        assert_eq!(
+            grid.children()[0].element().common.label(),
+            Some(&Label::new("x90"))
+        );
+        let delay: &Delay = (&stack.children()[1].variant).try_into().unwrap();
+        assert_eq!(delay.delay(), Time::new(5.0).unwrap());
+    }
+
+    #[test]
+    fn shorthands() {
+        let text = r#"{
+            "type": "barrier",
+            "margin": 2.0,
+            "phantom": "ignore",
+            "duration_of": "x90"
+        }"#;
+
+        let result = from_json(text).unwrap();
+
+        let two = Time::new(2.0).unwrap();
+        assert_eq!(result.common.margin(), (two, two));
+        assert!(result.common.phantom());
+        assert!(result.common.phantom_ignored());
+        let duration_ref = result.common.duration_ref().unwrap();
+        assert_eq!(duration_ref.label(), &Label::new("x90"));
+        assert_eq!(duration_ref.factor(), 1.0);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let text = r#"{"type": "stack", "children": [{"type": "barrier", "duraton": 1.0}]}"#;
+
+        let err = from_json(text).unwrap_err();
+
+        assert!(err.to_string().contains("duraton"));
+    }
+
+    #[test]
+    fn invalid_values() {
+        let text =
+            r#"{"type": "repeat", "count": 2, "child": {"type": "barrier"}, "duration": -1.0}"#;
+
+        assert!(from_json(text).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::OnceLock;
+
+use crate::{
+    quant::{ChannelId, Time},
+    schedule::{merge_channel_ids, ElementRef, LayoutItem, Measure},
+    Alignment,
+};
+
+use super::{Arrange, Arranged, TimeRange};
+
+/// Plays the children simultaneously.
+///
+/// All children start at the start of the container, which lasts as long as
+/// the longest child. Children with [`Alignment::Stretch`] fill the whole
+/// container, so that they end together with it when the container is
+/// stretched.
+#[derive(Debug, Clone)]
+pub struct Overlay<C = ElementRef> {
+    children: Vec<C>,
+    channel_ids: Vec<ChannelId>,
+    measure_result: OnceLock<Time>,
+}
+
+impl<C: Measure> Overlay<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_children(mut self, children: Vec<C>) -> Self {
+        let channel_ids = merge_channel_ids(children.iter().map(Measure::channels));
+        self.children = children;
+        self.channel_ids = channel_ids;
+        self
+    }
+
+    pub fn children(&self) -> &[C] {
+        &self.children
+    }
+
+    fn measure_result(&self) -> &Time {
+        self.measure_result.get_or_init(|| {
+            self.children
+                .iter()
+                .filter(|child| !child.ignored())
+                .map(Measure::measure)
+                .max()
+                .unwrap_or(Time::ZERO)
+        })
+    }
+}
+
+impl<C> Default for Overlay<C> {
+    fn default() -> Self {
+        Self {
+            children: vec![],
+            channel_ids: vec![],
+            measure_result: OnceLock::new(),
+        }
+    }
+}
+
+impl<C: Measure> Measure for Overlay<C> {
+    fn measure(&self) -> Time {
+        *self.measure_result()
+    }
+
+    fn channels(&self) -> &[ChannelId] {
+        &self.channel_ids
+    }
+}
+
+impl<C: LayoutItem> Arrange for Overlay<C> {
+    type Child = C;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&C>> {
+        self.children.iter().map(move |child| {
+            let span = match child.alignment() {
+                Alignment::Stretch => time_range.span,
+                _ => child.measure(),
+            };
+            Arranged {
+                item: child,
+                time_range: TimeRange {
+                    start: time_range.start,
+                    span,
+                },
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::MockMeasure;
+
+    fn child(channel: &str, duration: f64) -> MockMeasure {
+        let mut mock = MockMeasure::new();
+        mock.expect_measure()
+            .return_const(Time::new(duration).unwrap());
+        mock.expect_channels()
+            .return_const(vec![ChannelId::new(channel)]);
+        mock.expect_ignored().return_const(false);
+        mock
+    }
+
+    #[test]
+    fn measure_and_arrange() {
+        let overlay = Overlay::new().with_children(vec![child("a", 10.0), child("b", 30.0)]);
+        let time_range = TimeRange {
+            start: Time::new(5.0).unwrap(),
+            span: Time::new(40.0).unwrap(),
+        };
+
+        assert_eq!(overlay.measure(), Time::new(30.0).unwrap());
+        assert_eq!(overlay.channels().len(), 2);
+        let arranged: Vec<_> = overlay
+            .arrange(time_range)
+            .map(|a| (a.time_range.start.value(), a.time_range.span.value()))
+            .collect();
+        assert_eq!(arranged, [(5.0, 10.0), (5.0, 30.0)]);
+    }
+}
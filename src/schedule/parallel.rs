@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use hashbrown::HashSet;
+use rayon::prelude::*;
+
+use crate::quant::Time;
+
+use super::{ElementRef, ElementVariant, Measure};
+
+/// Measures the tree on the rayon thread pool and returns the duration of the
+/// root.
+///
+/// The measurements are cached by the containers, so the later passes reuse
+/// them. The tree is split into enough independent subtrees near the root,
+/// which are measured serially on the worker threads. A shared subtree may be
+/// measured by two workers at once, in which case one of them waits for the
+/// other. This can't deadlock because measuring never waits on the thread
+/// pool.
+pub fn measure_parallel(root: &ElementRef) -> Time {
+    let target = rayon::current_num_threads() * 8;
+    if target > 8 {
+        let subtrees = split(root, target);
+        subtrees.par_iter().for_each(|e| {
+            e.measure();
+        });
+    }
+    root.measure()
+}
+
+/// Distinct descendants of `root` at the shallowest depth with at least
+/// `target` of them, or the deepest containers if the tree is too small.
+fn split(root: &ElementRef, target: usize) -> Vec<&ElementRef> {
+    let mut current = vec![root];
+    loop {
+        let mut seen = HashSet::new();
+        let next: Vec<_> = current
+            .iter()
+            .flat_map(|e| children(e))
+            .filter(|e| seen.insert(Arc::as_ptr(e)))
+            .collect();
+        if next.is_empty() {
+            return current;
+        }
+        if next.len() >= target {
+            return next;
+        }
+        current = next;
+    }
+}
+
+fn children(element: &ElementRef) -> Vec<&ElementRef> {
+    match &element.variant {
+        ElementVariant::Repeat(r) => vec![r.child()],
+        ElementVariant::Scale(s) => vec![s.child()],
+        ElementVariant::Reverse(v) => vec![v.child()],
+        ElementVariant::Stack(s) => s.children().iter().collect(),
+        ElementVariant::Absolute(a) => a.children().iter().map(|e| e.element()).collect(),
+        ElementVariant::Overlay(o) => o.children().iter().collect(),
+        ElementVariant::Grid(g) => g.children().iter().map(|e| e.element()).collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::{test_util::play, Element, ElementCommonBuilder, Repeat, Stack};
+
+    #[test]
+    fn same_as_serial() {
+        let build = || {
+            let shared = Arc::new(Element::new(
+                ElementCommonBuilder::new().build().unwrap(),
+                Stack::new().with_children((0..100).map(|_| play("a", 1.0, None)).collect()),
+            ));
+            let repeat = Arc::new(Element::new(
+                ElementCommonBuilder::new().build().unwrap(),
+                Repeat::new(shared.clone(), 3),
+            ));
+            Arc::new(Element::new(
+                ElementCommonBuilder::new().build().unwrap(),
+                Stack::new().with_children(vec![shared, play("b", 1.0, None), repeat]),
+            ))
+        };
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let measure = pool.install(|| measure_parallel(&build()));
+
+        assert_eq!(measure, build().measure());
+        assert_eq!(measure, Time::new(4000.0).unwrap());
+    }
+}
@@ -1,12 +1,12 @@
 use anyhow::{bail, Result};
 
 use crate::{
-    quant::{Amplitude, ChannelId, Frequency, Phase, ShapeId, Time},
+    quant::{Amplitude, ChannelId, ChirpRate, Frequency, Phase, ShapeId, Time},
     schedule::Measure,
 };
 
 #[derive(Debug, Clone)]
-pub(crate) struct Play {
+pub struct Play {
     channel_id: [ChannelId; 1],
     shape_id: Option<ShapeId>,
     amplitude: Amplitude,
@@ -14,12 +14,17 @@ pub(crate) struct Play {
     plateau: Time,
     drag_coef: f64,
     frequency: Frequency,
+    chirp_rate: ChirpRate,
     phase: Phase,
     flexible: bool,
+    align_level: Option<i32>,
+    fall_shape_id: Option<ShapeId>,
+    fall_width: Option<Time>,
+    centered: bool,
 }
 
 impl Play {
-    pub(crate) fn new(
+    pub fn new(
         channel_id: ChannelId,
         shape_id: Option<ShapeId>,
         amplitude: Amplitude,
@@ -39,12 +44,33 @@ impl Play {
             plateau: Time::ZERO,
             drag_coef: 0.0,
             frequency: Frequency::ZERO,
+            chirp_rate: ChirpRate::ZERO,
             phase: Phase::ZERO,
             flexible: false,
+            align_level: None,
+            fall_shape_id: None,
+            fall_width: None,
+            centered: false,
         })
     }
 
-    pub(crate) fn with_plateau(mut self, plateau: Time) -> Result<Self> {
+    pub fn with_amplitude(mut self, amplitude: Amplitude) -> Result<Self> {
+        if !amplitude.value().is_finite() {
+            bail!("Invalid amplitude {:?}", amplitude);
+        }
+        self.amplitude = amplitude;
+        Ok(self)
+    }
+
+    pub fn with_width(mut self, width: Time) -> Result<Self> {
+        if !width.value().is_finite() || width.value() < 0.0 {
+            bail!("Invalid width {:?}", width);
+        }
+        self.width = width;
+        Ok(self)
+    }
+
+    pub fn with_plateau(mut self, plateau: Time) -> Result<Self> {
         if !plateau.value().is_finite() || plateau.value() < 0.0 {
             bail!("Invalid plateau {:?}", plateau);
         }
@@ -52,7 +78,25 @@ impl Play {
         Ok(self)
     }
 
-    pub(crate) fn with_drag_coef(mut self, drag_coef: f64) -> Result<Self> {
+    /// Sets the plateau so that the pulse lasts `total` including the edges.
+    /// Call it after [`with_fall`](Self::with_fall), which changes the
+    /// [`edge_width`](Self::edge_width).
+    pub fn with_total(self, total: Time) -> Result<Self> {
+        if !total.value().is_finite() {
+            bail!("Invalid total {:?}", total);
+        }
+        let edge_width = self.edge_width();
+        if total < edge_width {
+            bail!(
+                "total {:?} is shorter than the edges of the pulse {:?}",
+                total,
+                edge_width
+            );
+        }
+        self.with_plateau(total - edge_width)
+    }
+
+    pub fn with_drag_coef(mut self, drag_coef: f64) -> Result<Self> {
         if !drag_coef.is_finite() {
             bail!("Invalid drag_coef {}", drag_coef);
         }
@@ -60,7 +104,7 @@ impl Play {
         Ok(self)
     }
 
-    pub(crate) fn with_frequency(mut self, frequency: Frequency) -> Result<Self> {
+    pub fn with_frequency(mut self, frequency: Frequency) -> Result<Self> {
         if !frequency.value().is_finite() {
             bail!("Invalid frequency {:?}", frequency);
         }
@@ -68,7 +112,18 @@ impl Play {
         Ok(self)
     }
 
-    pub(crate) fn with_phase(mut self, phase: Phase) -> Result<Self> {
+    /// Sweeps the frequency linearly across the pulse. The frequency is
+    /// `frequency + chirp_rate * (t - center)`, so the carrier phase at the
+    /// center of the pulse doesn't depend on the chirp.
+    pub fn with_chirp_rate(mut self, chirp_rate: ChirpRate) -> Result<Self> {
+        if !chirp_rate.value().is_finite() {
+            bail!("Invalid chirp_rate {:?}", chirp_rate);
+        }
+        self.chirp_rate = chirp_rate;
+        Ok(self)
+    }
+
+    pub fn with_phase(mut self, phase: Phase) -> Result<Self> {
         if !phase.value().is_finite() {
             bail!("Invalid phase {:?}", phase);
         }
@@ -76,46 +131,116 @@ impl Play {
         Ok(self)
     }
 
-    pub(crate) fn with_flexible(mut self, flexible: bool) -> Self {
+    pub fn with_flexible(mut self, flexible: bool) -> Self {
         self.flexible = flexible;
         self
     }
 
-    pub(crate) fn channel_id(&self) -> &ChannelId {
+    pub fn with_align_level(mut self, align_level: Option<i32>) -> Self {
+        self.align_level = align_level;
+        self
+    }
+
+    /// Positions the pulse at the center of the allotted span instead of the
+    /// start. Only matters if the span is longer than the pulse.
+    pub fn with_centered(mut self, centered: bool) -> Self {
+        self.centered = centered;
+        self
+    }
+
+    /// Sets a different shape or width for the falling edge. `None` means the
+    /// same as the rising edge.
+    pub fn with_fall(
+        mut self,
+        fall_shape_id: Option<ShapeId>,
+        fall_width: Option<Time>,
+    ) -> Result<Self> {
+        if self.shape_id.is_none() && (fall_shape_id.is_some() || fall_width.is_some()) {
+            bail!("Rectangular pulse can not have a different falling edge");
+        }
+        if let Some(fall_width) = fall_width {
+            if !fall_width.value().is_finite() || fall_width.value() < 0.0 {
+                bail!("Invalid fall_width {:?}", fall_width);
+            }
+        }
+        self.fall_shape_id = fall_shape_id;
+        self.fall_width = fall_width;
+        Ok(self)
+    }
+
+    pub fn with_channel_id(mut self, channel_id: ChannelId) -> Self {
+        self.channel_id = [channel_id];
+        self
+    }
+
+    pub fn channel_id(&self) -> &ChannelId {
         &self.channel_id[0]
     }
 
-    pub(crate) fn shape_id(&self) -> Option<&ShapeId> {
+    pub fn shape_id(&self) -> Option<&ShapeId> {
         self.shape_id.as_ref()
     }
 
-    pub(crate) fn amplitude(&self) -> Amplitude {
+    pub fn amplitude(&self) -> Amplitude {
         self.amplitude
     }
 
-    pub(crate) fn width(&self) -> Time {
+    pub fn width(&self) -> Time {
         self.width
     }
 
-    pub(crate) fn plateau(&self) -> Time {
+    pub fn plateau(&self) -> Time {
         self.plateau
     }
 
-    pub(crate) fn drag_coef(&self) -> f64 {
+    pub fn drag_coef(&self) -> f64 {
         self.drag_coef
     }
 
-    pub(crate) fn frequency(&self) -> Frequency {
+    pub fn frequency(&self) -> Frequency {
         self.frequency
     }
 
-    pub(crate) fn phase(&self) -> Phase {
+    pub fn chirp_rate(&self) -> ChirpRate {
+        self.chirp_rate
+    }
+
+    pub fn phase(&self) -> Phase {
         self.phase
     }
 
-    pub(crate) fn flexible(&self) -> bool {
+    pub fn flexible(&self) -> bool {
         self.flexible
     }
+
+    pub fn align_level(&self) -> Option<i32> {
+        self.align_level
+    }
+
+    pub fn fall_shape_id(&self) -> Option<&ShapeId> {
+        self.fall_shape_id.as_ref()
+    }
+
+    pub fn fall_width(&self) -> Option<Time> {
+        self.fall_width
+    }
+
+    pub fn centered(&self) -> bool {
+        self.centered
+    }
+
+    pub fn is_asymmetric(&self) -> bool {
+        self.fall_shape_id.is_some() || self.fall_width.is_some()
+    }
+
+    /// Total duration of the rising and falling edges. Each edge takes half of
+    /// its width.
+    pub fn edge_width(&self) -> Time {
+        match self.fall_width {
+            Some(fall_width) => (self.width + fall_width) / 2.0,
+            None => self.width,
+        }
+    }
 }
 
 impl Measure for Play {
@@ -125,9 +250,9 @@ impl Measure for Play {
 
     fn measure(&self) -> Time {
         if self.flexible {
-            self.width
+            self.edge_width()
         } else {
-            self.width + self.plateau
+            self.edge_width() + self.plateau
         }
     }
 }
@@ -10,7 +10,7 @@ use crate::{
 use super::{Arrange, Arranged, TimeRange};
 
 #[derive(Debug, Clone)]
-pub(crate) struct Repeat {
+pub struct Repeat {
     child: ElementRef,
     count: usize,
     spacing: Time,
@@ -18,7 +18,7 @@ pub(crate) struct Repeat {
 }
 
 impl Repeat {
-    pub(crate) fn new(child: ElementRef, count: usize) -> Self {
+    pub fn new(child: ElementRef, count: usize) -> Self {
         Self {
             child,
             count,
@@ -27,7 +27,7 @@ impl Repeat {
         }
     }
 
-    pub(crate) fn with_spacing(mut self, spacing: Time) -> Result<Self> {
+    pub fn with_spacing(mut self, spacing: Time) -> Result<Self> {
         if !spacing.value().is_finite() {
             bail!("Invalid spacing {:?}", spacing);
         }
@@ -36,11 +36,15 @@ impl Repeat {
         Ok(self)
     }
 
-    pub(crate) fn count(&self) -> usize {
+    pub fn child(&self) -> &ElementRef {
+        &self.child
+    }
+
+    pub fn count(&self) -> usize {
         self.count
     }
 
-    pub(crate) fn spacing(&self) -> Time {
+    pub fn spacing(&self) -> Time {
         self.spacing
     }
 }
@@ -63,7 +67,9 @@ impl Measure for Repeat {
 }
 
 impl Arrange for Repeat {
-    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&ElementRef>> {
+    type Child = ElementRef;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&Self::Child>> {
         let child_duration = self.child.measure();
         let offset_per_repeat = child_duration + self.spacing;
         (0..self.count).map(move |i| {
@@ -0,0 +1,83 @@
+use std::iter;
+
+use crate::{
+    quant::{ChannelId, Time},
+    schedule::{ElementRef, Measure},
+};
+
+use super::{Arrange, Arranged, TimeRange};
+
+/// Plays the child subtree back to front in time.
+///
+/// The child is arranged in its own time axis, which is mirrored to the parent
+/// one by the consumer of the arrangement, see [`Reverse::to_parent`]. Only
+/// the placement of the elements is mirrored, the pulse envelopes are played
+/// as is.
+#[derive(Debug, Clone)]
+pub struct Reverse {
+    child: ElementRef,
+}
+
+impl Reverse {
+    pub fn new(child: ElementRef) -> Self {
+        Self { child }
+    }
+
+    pub fn child(&self) -> &ElementRef {
+        &self.child
+    }
+
+    /// Maps a time in the child time axis to the parent time axis, given the
+    /// time range passed to [`Arrange::arrange`].
+    pub fn to_parent(&self, time_range: TimeRange, time: Time) -> Time {
+        time_range.start + time_range.span - (time - time_range.start)
+    }
+}
+
+impl Measure for Reverse {
+    fn measure(&self) -> Time {
+        self.child.measure()
+    }
+
+    fn channels(&self) -> &[ChannelId] {
+        self.child.channels()
+    }
+}
+
+impl Arrange for Reverse {
+    type Child = ElementRef;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&Self::Child>> {
+        iter::once(Arranged {
+            item: &self.child,
+            time_range,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::test_util::play;
+
+    #[test]
+    fn measure_and_arrange() {
+        let child = play("xy", 1.0, None);
+        let reverse = Reverse::new(child);
+        let time_range = TimeRange {
+            start: Time::new(5.0).unwrap(),
+            span: Time::new(20.0).unwrap(),
+        };
+
+        let arranged: Vec<_> = reverse.arrange(time_range).collect();
+
+        assert_eq!(reverse.measure(), Time::new(10.0).unwrap());
+        assert_eq!(arranged.len(), 1);
+        assert_eq!(arranged[0].time_range.start, Time::new(5.0).unwrap());
+        assert_eq!(arranged[0].time_range.span, Time::new(20.0).unwrap());
+        assert_eq!(
+            reverse.to_parent(time_range, Time::new(7.0).unwrap()),
+            Time::new(23.0).unwrap()
+        );
+    }
+}
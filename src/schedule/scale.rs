@@ -0,0 +1,108 @@
+use std::iter;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    quant::{ChannelId, Time},
+    schedule::{ElementRef, Measure},
+};
+
+use super::{Arrange, Arranged, TimeRange};
+
+/// Scales all durations of the child subtree by a factor.
+///
+/// The child is arranged in its own time axis, where durations are unscaled.
+/// Mapping the child time axis back to the parent one is done by the consumer
+/// of the arrangement, see [`Scale::to_parent`].
+#[derive(Debug, Clone)]
+pub struct Scale {
+    child: ElementRef,
+    factor: f64,
+}
+
+impl Scale {
+    pub fn new(child: ElementRef, factor: f64) -> Result<Self> {
+        if !(factor.is_finite() && factor > 0.0) {
+            bail!("Invalid factor {}", factor);
+        }
+        Ok(Self { child, factor })
+    }
+
+    pub fn child(&self) -> &ElementRef {
+        &self.child
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    /// Maps a time in the child time axis to the parent time axis, given the
+    /// time range passed to [`Arrange::arrange`].
+    pub fn to_parent(&self, time_range: TimeRange, time: Time) -> Time {
+        time_range.start + (time - time_range.start) * self.factor
+    }
+}
+
+impl Measure for Scale {
+    fn measure(&self) -> Time {
+        self.child.measure() * self.factor
+    }
+
+    fn channels(&self) -> &[ChannelId] {
+        self.child.channels()
+    }
+}
+
+impl Arrange for Scale {
+    type Child = ElementRef;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&Self::Child>> {
+        iter::once(Arranged {
+            item: &self.child,
+            time_range: TimeRange {
+                start: time_range.start,
+                span: time_range.span / self.factor,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::schedule::{test_util::play, Barrier, Element, ElementCommonBuilder};
+
+    #[test]
+    fn measure_and_arrange() {
+        let child = play("xy", 1.0, None);
+        let scale = Scale::new(child, 2.0).unwrap();
+        let time_range = TimeRange {
+            start: Time::new(5.0).unwrap(),
+            span: Time::new(20.0).unwrap(),
+        };
+
+        let arranged: Vec<_> = scale.arrange(time_range).collect();
+
+        assert_eq!(scale.measure(), Time::new(20.0).unwrap());
+        assert_eq!(arranged.len(), 1);
+        assert_eq!(arranged[0].time_range.start, Time::new(5.0).unwrap());
+        assert_eq!(arranged[0].time_range.span, Time::new(10.0).unwrap());
+        assert_eq!(
+            scale.to_parent(time_range, Time::new(10.0).unwrap()),
+            Time::new(15.0).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_factor() {
+        let child = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Barrier::new(vec![]),
+        ));
+
+        assert!(Scale::new(child.clone(), 0.0).is_err());
+        assert!(Scale::new(child, f64::NAN).is_err());
+    }
+}
@@ -6,41 +6,74 @@ use crate::{
 };
 
 #[derive(Debug, Clone)]
-pub(crate) struct ShiftPhase {
+pub struct ShiftPhase {
     channel_ids: [ChannelId; 1],
     phase: Phase,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct SetPhase {
+pub struct SetPhase {
     channel_ids: [ChannelId; 1],
     phase: Phase,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct ShiftFreq {
+pub struct ShiftFreq {
     channel_ids: [ChannelId; 1],
     frequency: Frequency,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct SetFreq {
+pub struct SetFreq {
     channel_ids: [ChannelId; 1],
     frequency: Frequency,
 }
 
+/// Continuous phase shift of a channel.
+///
+/// The phase offset of the channel increases linearly at `rate` cycles per
+/// unit time over the span the element is arranged in. Like the other
+/// instructions it is measured as zero, so the span is usually set by the
+/// duration of the element.
 #[derive(Debug, Clone)]
-pub(crate) struct SwapPhase {
+pub struct PhaseRamp {
+    channel_ids: [ChannelId; 1],
+    rate: Frequency,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwapPhase {
     channel_ids: [ChannelId; 2],
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Barrier {
+pub struct Barrier {
+    channel_ids: Vec<ChannelId>,
+}
+
+/// Idle time on some channels.
+///
+/// Like a [`Barrier`] it occupies all channels of the parent if no channel is
+/// given, but it is measured as `delay` instead of zero.
+#[derive(Debug, Clone)]
+pub struct Delay {
     channel_ids: Vec<ChannelId>,
+    delay: Time,
+}
+
+/// High level of a digital marker channel.
+///
+/// The marker is high over the span the element is arranged in, which is
+/// measured as `width`, so a stretched marker covers the whole span given by
+/// its parent.
+#[derive(Debug, Clone)]
+pub struct Marker {
+    channel_ids: [ChannelId; 1],
+    width: Time,
 }
 
 impl ShiftPhase {
-    pub(crate) fn new(channel_id: ChannelId, phase: Phase) -> Result<Self> {
+    pub fn new(channel_id: ChannelId, phase: Phase) -> Result<Self> {
         if !phase.value().is_finite() {
             bail!("Invalid phase {:?}", phase);
         }
@@ -50,17 +83,17 @@ impl ShiftPhase {
         })
     }
 
-    pub(crate) fn channel_id(&self) -> &ChannelId {
+    pub fn channel_id(&self) -> &ChannelId {
         &self.channel_ids[0]
     }
 
-    pub(crate) fn phase(&self) -> Phase {
+    pub fn phase(&self) -> Phase {
         self.phase
     }
 }
 
 impl SetPhase {
-    pub(crate) fn new(channel_id: ChannelId, phase: Phase) -> Result<Self> {
+    pub fn new(channel_id: ChannelId, phase: Phase) -> Result<Self> {
         if !phase.value().is_finite() {
             bail!("Invalid phase {:?}", phase);
         }
@@ -70,17 +103,17 @@ impl SetPhase {
         })
     }
 
-    pub(crate) fn channel_id(&self) -> &ChannelId {
+    pub fn channel_id(&self) -> &ChannelId {
         &self.channel_ids[0]
     }
 
-    pub(crate) fn phase(&self) -> Phase {
+    pub fn phase(&self) -> Phase {
         self.phase
     }
 }
 
 impl ShiftFreq {
-    pub(crate) fn new(channel_id: ChannelId, frequency: Frequency) -> Result<Self> {
+    pub fn new(channel_id: ChannelId, frequency: Frequency) -> Result<Self> {
         if !frequency.value().is_finite() {
             bail!("Invalid frequency {:?}", frequency);
         }
@@ -90,17 +123,17 @@ impl ShiftFreq {
         })
     }
 
-    pub(crate) fn channel_id(&self) -> &ChannelId {
+    pub fn channel_id(&self) -> &ChannelId {
         &self.channel_ids[0]
     }
 
-    pub(crate) fn frequency(&self) -> Frequency {
+    pub fn frequency(&self) -> Frequency {
         self.frequency
     }
 }
 
 impl SetFreq {
-    pub(crate) fn new(channel_id: ChannelId, frequency: Frequency) -> Result<Self> {
+    pub fn new(channel_id: ChannelId, frequency: Frequency) -> Result<Self> {
         if !frequency.value().is_finite() {
             bail!("Invalid frequency {:?}", frequency);
         }
@@ -110,41 +143,98 @@ impl SetFreq {
         })
     }
 
-    pub(crate) fn channel_id(&self) -> &ChannelId {
+    pub fn channel_id(&self) -> &ChannelId {
         &self.channel_ids[0]
     }
 
-    pub(crate) fn frequency(&self) -> Frequency {
+    pub fn frequency(&self) -> Frequency {
         self.frequency
     }
 }
 
+impl PhaseRamp {
+    pub fn new(channel_id: ChannelId, rate: Frequency) -> Result<Self> {
+        if !rate.value().is_finite() {
+            bail!("Invalid rate {:?}", rate);
+        }
+        Ok(Self {
+            channel_ids: [channel_id],
+            rate,
+        })
+    }
+
+    pub fn channel_id(&self) -> &ChannelId {
+        &self.channel_ids[0]
+    }
+
+    pub fn rate(&self) -> Frequency {
+        self.rate
+    }
+}
+
 impl SwapPhase {
-    pub(crate) fn new(channel_id1: ChannelId, channel_id2: ChannelId) -> Self {
+    pub fn new(channel_id1: ChannelId, channel_id2: ChannelId) -> Self {
         Self {
             channel_ids: [channel_id1, channel_id2],
         }
     }
 
-    pub(crate) fn channel_id1(&self) -> &ChannelId {
+    pub fn channel_id1(&self) -> &ChannelId {
         &self.channel_ids[0]
     }
 
-    pub(crate) fn channel_id2(&self) -> &ChannelId {
+    pub fn channel_id2(&self) -> &ChannelId {
         &self.channel_ids[1]
     }
 }
 
 impl Barrier {
-    pub(crate) fn new(channel_ids: Vec<ChannelId>) -> Self {
+    pub fn new(channel_ids: Vec<ChannelId>) -> Self {
         Self { channel_ids }
     }
 
-    pub(crate) fn channel_ids(&self) -> &[ChannelId] {
+    pub fn channel_ids(&self) -> &[ChannelId] {
         &self.channel_ids
     }
 }
 
+impl Delay {
+    pub fn new(channel_ids: Vec<ChannelId>, delay: Time) -> Result<Self> {
+        if !(delay.value().is_finite() && delay >= Time::ZERO) {
+            bail!("Invalid delay {:?}", delay);
+        }
+        Ok(Self { channel_ids, delay })
+    }
+
+    pub fn channel_ids(&self) -> &[ChannelId] {
+        &self.channel_ids
+    }
+
+    pub fn delay(&self) -> Time {
+        self.delay
+    }
+}
+
+impl Marker {
+    pub fn new(channel_id: ChannelId, width: Time) -> Result<Self> {
+        if !(width.value().is_finite() && width >= Time::ZERO) {
+            bail!("Invalid width {:?}", width);
+        }
+        Ok(Self {
+            channel_ids: [channel_id],
+            width,
+        })
+    }
+
+    pub fn channel_id(&self) -> &ChannelId {
+        &self.channel_ids[0]
+    }
+
+    pub fn width(&self) -> Time {
+        self.width
+    }
+}
+
 macro_rules! impl_measure {
     ($t:ty) => {
         impl Measure for $t {
@@ -163,5 +253,26 @@ impl_measure!(ShiftPhase);
 impl_measure!(SetPhase);
 impl_measure!(ShiftFreq);
 impl_measure!(SetFreq);
+impl_measure!(PhaseRamp);
 impl_measure!(SwapPhase);
 impl_measure!(Barrier);
+
+impl Measure for Delay {
+    fn measure(&self) -> Time {
+        self.delay
+    }
+
+    fn channels(&self) -> &[ChannelId] {
+        &self.channel_ids
+    }
+}
+
+impl Measure for Marker {
+    fn measure(&self) -> Time {
+        self.width
+    }
+
+    fn channels(&self) -> &[ChannelId] {
+        &self.channel_ids
+    }
+}
@@ -2,40 +2,83 @@ mod helper;
 
 use std::sync::OnceLock;
 
+use hashbrown::HashMap;
+
 use crate::{
     quant::{ChannelId, Time},
-    schedule::{merge_channel_ids, stack::helper::Helper, Arranged, ElementRef, Measure},
-    Direction,
+    schedule::{
+        merge_channel_ids, stack::helper::Helper, Arranged, ElementRef, LayoutItem, Measure,
+    },
+    Alignment, Direction,
 };
 
 use super::{Arrange, TimeRange};
 
 #[derive(Debug, Clone)]
-pub(crate) struct Stack {
-    children: Vec<ElementRef>,
+pub struct Stack<C = ElementRef> {
+    children: Vec<C>,
     direction: Direction,
+    auto_barrier: bool,
+    spacing: Time,
+    align_children: bool,
     channel_ids: Vec<ChannelId>,
     measure_result: OnceLock<MeasureResult>,
 }
 
+/// Options of [`measure_stack`].
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    direction: Direction,
+    auto_barrier: bool,
+    spacing: Time,
+    align_children: bool,
+}
+
 #[derive(Debug, Clone)]
 struct MeasureResult {
     total_duration: Time,
     child_timings: Vec<TimeRange>,
 }
 
-impl Stack {
-    pub(crate) fn new() -> Self {
+impl<C: LayoutItem> Stack<C> {
+    pub fn new() -> Self {
         Self::default()
     }
 
-    pub(crate) fn with_direction(mut self, direction: Direction) -> Self {
+    pub fn with_direction(mut self, direction: Direction) -> Self {
         self.direction = direction;
         self.measure_result.take();
         self
     }
 
-    pub(crate) fn with_children(mut self, children: Vec<ElementRef>) -> Self {
+    /// Synchronizes all channels of the stack between consecutive children,
+    /// as if a [`Barrier`](super::Barrier) without channels were inserted.
+    pub fn with_auto_barrier(mut self, auto_barrier: bool) -> Self {
+        self.auto_barrier = auto_barrier;
+        self.measure_result.take();
+        self
+    }
+
+    /// Keeps a gap of `spacing` between consecutive children with a duration
+    /// on the same channel.
+    pub fn with_spacing(mut self, spacing: Time) -> Self {
+        self.spacing = spacing;
+        self.measure_result.take();
+        self
+    }
+
+    /// Places each child in the time left free on its channels by the
+    /// neighbouring children according to its [`LayoutItem::alignment`].
+    ///
+    /// Without it the children are packed in the direction of the stack,
+    /// regardless of their alignment.
+    pub fn with_align_children(mut self, align_children: bool) -> Self {
+        self.align_children = align_children;
+        self.measure_result.take();
+        self
+    }
+
+    pub fn with_children(mut self, children: Vec<C>) -> Self {
         let channel_ids = merge_channel_ids(children.iter().map(|e| e.channels()));
         self.children = children;
         self.channel_ids = channel_ids;
@@ -43,28 +86,57 @@ impl Stack {
         self
     }
 
-    pub(crate) fn direction(&self) -> Direction {
+    pub fn direction(&self) -> Direction {
         self.direction
     }
 
+    pub fn auto_barrier(&self) -> bool {
+        self.auto_barrier
+    }
+
+    pub fn spacing(&self) -> Time {
+        self.spacing
+    }
+
+    pub fn align_children(&self) -> bool {
+        self.align_children
+    }
+
+    pub fn children(&self) -> &[C] {
+        &self.children
+    }
+
     fn measure_result(&self) -> &MeasureResult {
-        self.measure_result
-            .get_or_init(|| measure_stack(&self.children, &self.channel_ids, self.direction))
+        self.measure_result.get_or_init(|| {
+            measure_stack(
+                &self.children,
+                &self.channel_ids,
+                Options {
+                    direction: self.direction,
+                    auto_barrier: self.auto_barrier,
+                    spacing: self.spacing,
+                    align_children: self.align_children,
+                },
+            )
+        })
     }
 }
 
-impl Default for Stack {
+impl<C> Default for Stack<C> {
     fn default() -> Self {
         Self {
             children: vec![],
             direction: Direction::Backward,
+            auto_barrier: false,
+            spacing: Time::ZERO,
+            align_children: false,
             channel_ids: vec![],
             measure_result: OnceLock::new(),
         }
     }
 }
 
-impl Measure for Stack {
+impl<C: LayoutItem> Measure for Stack<C> {
     fn measure(&self) -> Time {
         let MeasureResult { total_duration, .. } = self.measure_result();
         *total_duration
@@ -75,8 +147,10 @@ impl Measure for Stack {
     }
 }
 
-impl Arrange for Stack {
-    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&ElementRef>> {
+impl<C: LayoutItem> Arrange for Stack<C> {
+    type Child = C;
+
+    fn arrange(&self, time_range: TimeRange) -> impl Iterator<Item = Arranged<&C>> {
         let MeasureResult { child_timings, .. } = self.measure_result();
         self.children.iter().zip(child_timings).map(
             move |(
@@ -105,26 +179,124 @@ impl Arrange for Stack {
     }
 }
 
-fn measure_stack<I>(children: I, channels: &[ChannelId], direction: Direction) -> MeasureResult
+fn measure_stack<I>(children: I, channels: &[ChannelId], options: Options) -> MeasureResult
 where
     I: IntoIterator,
     I::IntoIter: DoubleEndedIterator,
-    I::Item: Measure,
+    I::Item: LayoutItem,
 {
+    let Options {
+        direction,
+        auto_barrier,
+        spacing,
+        align_children,
+    } = options;
+    let children: Vec<_> = children.into_iter().collect();
     let mut helper = Helper::new(channels);
-    let child_timings = map_and_collect_by_direction(children, direction, |child| {
+    let mut child_timings = map_and_collect_by_direction(&children, direction, |child| {
+        if child.ignored() {
+            return TimeRange {
+                start: Time::ZERO,
+                span: Time::ZERO,
+            };
+        }
+        if auto_barrier {
+            let synced = helper.get_usage(&[]);
+            helper.update_usage(synced, &[], false);
+        }
         let child_channels = child.channels();
         let span = child.measure();
-        let start = helper.get_usage(child_channels);
-        helper.update_usage(start + span, child_channels);
+        let occupied = span > Time::ZERO;
+        let start = if occupied {
+            helper.get_start(child_channels, spacing)
+        } else {
+            helper.get_usage(child_channels)
+        };
+        helper.update_usage(start + span, child_channels, occupied);
         TimeRange { start, span }
     });
+    let total_duration = helper.into_max_usage();
+    if align_children {
+        align_stack_children(
+            &children,
+            &mut child_timings,
+            channels,
+            options,
+            total_duration,
+        );
+    }
     MeasureResult {
-        total_duration: helper.into_max_usage(),
+        total_duration,
         child_timings,
     }
 }
 
+/// Moves the packed children within the time left free on their channels.
+///
+/// The timings are measured from the start of the stack in the direction of
+/// the stack, so the packed side is the start for [`Direction::Forward`] and
+/// the end for [`Direction::Backward`].
+fn align_stack_children<C: LayoutItem>(
+    children: &[C],
+    child_timings: &mut [TimeRange],
+    channels: &[ChannelId],
+    options: Options,
+    total_duration: Time,
+) {
+    let order: Vec<usize> = match options.direction {
+        Direction::Forward => (0..children.len()).collect(),
+        Direction::Backward => (0..children.len()).rev().collect(),
+    };
+    // Start of the next child on each channel, less the spacing before it.
+    let mut next: HashMap<&ChannelId, Time> = HashMap::new();
+    let mut next_all = total_duration;
+    for &i in order.iter().rev() {
+        let child = &children[i];
+        if child.ignored() {
+            continue;
+        }
+        let child_channels = match child.channels() {
+            [] => channels,
+            c => c,
+        };
+        let TimeRange { start, span } = child_timings[i];
+        let end = child_channels
+            .iter()
+            .filter_map(|c| next.get(c))
+            .fold(next_all, |a, &b| a.min(b))
+            .max(start + span);
+        let slack = end - start - span;
+        child_timings[i] = match (child.alignment(), options.direction) {
+            (Alignment::Stretch, _) => TimeRange {
+                start,
+                span: end - start,
+            },
+            (Alignment::Center, _) => TimeRange {
+                start: start + slack / 2.0,
+                span,
+            },
+            (Alignment::Start, Direction::Forward) | (Alignment::End, Direction::Backward) => {
+                TimeRange { start, span }
+            }
+            _ => TimeRange {
+                start: start + slack,
+                span,
+            },
+        };
+        let gap = if span > Time::ZERO {
+            options.spacing
+        } else {
+            Time::ZERO
+        };
+        if channels.is_empty() {
+            next_all = start - gap;
+        }
+        for c in child_channels {
+            next.insert(c, start - gap);
+        }
+    }
+}
+
 /// Map by direction but collect in the original order.
 fn map_and_collect_by_direction<I, F, T>(source: I, direction: Direction, f: F) -> Vec<T>
 where
@@ -149,6 +321,15 @@ mod tests {
     use super::*;
     use crate::schedule::MockMeasure;
 
+    fn options(direction: Direction, auto_barrier: bool) -> Options {
+        Options {
+            direction,
+            auto_barrier,
+            spacing: Time::ZERO,
+            align_children: false,
+        }
+    }
+
     #[test_case(Direction::Forward; "forward")]
     #[test_case(Direction::Backward; "backward")]
 
@@ -176,13 +357,14 @@ mod tests {
             mock.expect_measure()
                 .return_const(Time::new(duration).unwrap());
             mock.expect_channels().return_const(vec![]);
+            mock.expect_ignored().return_const(false);
             mock
         });
 
         let MeasureResult {
             total_duration,
             child_timings,
-        } = measure_stack(children, &[], direction);
+        } = measure_stack(children, &[], options(direction, false));
 
         assert_eq!(total_duration, Time::new(60.0).unwrap());
         assert_eq!(
@@ -208,9 +390,16 @@ mod tests {
     /// ch[1] -----| 20 |---|    |---| 10 |-----
     ///            +----+   +----+   +----+
     /// ```
-    #[test_case(Direction::Forward, &[0.0, 0.0, 20.0, 40.0, 40.0]; "forward")]
-    #[test_case(Direction::Backward, &[40.0, 40.0, 20.0, 0.0, 0.0]; "backward")]
-    fn test_measure_with_channels(direction: Direction, offsets: &[f64]) {
+    #[test_case(Direction::Forward, false, 60.0, &[0.0, 0.0, 20.0, 40.0, 40.0]; "forward")]
+    #[test_case(Direction::Backward, false, 60.0, &[40.0, 40.0, 20.0, 0.0, 0.0]; "backward")]
+    #[test_case(Direction::Forward, true, 80.0, &[0.0, 10.0, 30.0, 50.0, 70.0]; "forward auto barrier")]
+    #[test_case(Direction::Backward, true, 80.0, &[70.0, 50.0, 30.0, 10.0, 0.0]; "backward auto barrier")]
+    fn test_measure_with_channels(
+        direction: Direction,
+        auto_barrier: bool,
+        total: f64,
+        offsets: &[f64],
+    ) {
         let children = [
             create_mock(10.0, &[0]),
             create_mock(20.0, &[1]),
@@ -223,9 +412,9 @@ mod tests {
         let MeasureResult {
             total_duration,
             child_timings,
-        } = measure_stack(children, &channels, direction);
+        } = measure_stack(children, &channels, options(direction, auto_barrier));
 
-        assert_eq!(total_duration, Time::new(60.0).unwrap());
+        assert_eq!(total_duration, Time::new(total).unwrap());
         assert_eq!(
             child_timings
                 .into_iter()
@@ -243,7 +432,35 @@ mod tests {
                 .return_const(Time::new(duration).unwrap());
             mock.expect_channels()
                 .return_const(channels.iter().copied().map(create_channel).collect());
+            mock.expect_ignored().return_const(false);
             mock
         }
     }
+
+    #[test]
+    fn test_measure_ignored() {
+        let channels = [ChannelId::new("0"), ChannelId::new("1")];
+        let create_mock = |duration: f64, channels: &[ChannelId], ignored: bool| {
+            let mut mock = MockMeasure::new();
+            mock.expect_measure()
+                .return_const(Time::new(duration).unwrap());
+            mock.expect_channels().return_const(channels.to_vec());
+            mock.expect_ignored().return_const(ignored);
+            mock
+        };
+        let children = [
+            create_mock(10.0, &channels[..1], false),
+            create_mock(20.0, &channels, true),
+            create_mock(10.0, &channels[1..], false),
+        ];
+
+        let MeasureResult {
+            total_duration,
+            child_timings,
+        } = measure_stack(children, &channels, options(Direction::Forward, true));
+
+        assert_eq!(total_duration, Time::new(20.0).unwrap());
+        assert_eq!(child_timings[1].span, Time::ZERO);
+        assert_eq!(child_timings[2].start, Time::new(10.0).unwrap());
+    }
 }
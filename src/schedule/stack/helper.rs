@@ -8,10 +8,27 @@ pub(super) struct Helper<'a> {
     usage: ChannelUsage,
 }
 
+/// Usage of a channel, and whether a child with a duration occupied it.
+#[derive(Debug, Clone, Copy, Default)]
+struct Usage {
+    time: Time,
+    occupied: bool,
+}
+
 #[derive(Debug)]
 enum ChannelUsage {
-    Single(Time),
-    Multiple(HashMap<ChannelId, Time>),
+    Single(Usage),
+    Multiple(HashMap<ChannelId, Usage>),
+}
+
+impl Usage {
+    fn with_spacing(self, spacing: Time) -> Time {
+        if self.occupied {
+            self.time + spacing
+        } else {
+            self.time
+        }
+    }
 }
 
 impl<'a> Helper<'a> {
@@ -19,7 +36,7 @@ impl<'a> Helper<'a> {
         Self {
             all_channels,
             usage: if all_channels.is_empty() {
-                ChannelUsage::Single(Time::ZERO)
+                ChannelUsage::Single(Usage::default())
             } else {
                 ChannelUsage::Multiple(HashMap::with_capacity(all_channels.len()))
             },
@@ -27,29 +44,49 @@ impl<'a> Helper<'a> {
     }
 
     pub(super) fn get_usage(&self, channels: &[ChannelId]) -> Time {
+        self.get_start(channels, Time::ZERO)
+    }
+
+    /// Earliest start on the channels keeping `spacing` after the children
+    /// which occupied them.
+    pub(super) fn get_start(&self, channels: &[ChannelId], spacing: Time) -> Time {
         match &self.usage {
-            ChannelUsage::Single(v) => *v,
+            ChannelUsage::Single(v) => v.with_spacing(spacing),
             ChannelUsage::Multiple(d) => (if channels.is_empty() {
-                d.values().max()
+                d.values().map(|u| u.with_spacing(spacing)).max()
             } else {
-                channels.iter().filter_map(|i| d.get(i)).max()
+                channels
+                    .iter()
+                    .filter_map(|i| d.get(i))
+                    .map(|u| u.with_spacing(spacing))
+                    .max()
             })
-            .copied()
             .unwrap_or_default(),
         }
     }
 
-    pub(super) fn update_usage(&mut self, new_duration: Time, channels: &[ChannelId]) {
+    /// Updates the usage of the channels. Channels stay occupied once a child
+    /// with a duration used them.
+    pub(super) fn update_usage(
+        &mut self,
+        new_duration: Time,
+        channels: &[ChannelId],
+        occupied: bool,
+    ) {
         let channels = if channels.is_empty() {
             self.all_channels
         } else {
             channels
         };
+        let update = |u: &mut Usage| {
+            u.time = new_duration;
+            u.occupied |= occupied;
+        };
         match &mut self.usage {
-            ChannelUsage::Single(v) => *v = new_duration,
+            ChannelUsage::Single(v) => update(v),
             ChannelUsage::Multiple(d) => {
                 for ch in channels {
-                    d.insert(ch.clone(), new_duration);
+                    update(d.entry(ch.clone()).or_default());
                 }
             }
         };
@@ -57,8 +94,8 @@ impl<'a> Helper<'a> {
 
     pub(super) fn into_max_usage(self) -> Time {
         match self.usage {
-            ChannelUsage::Single(v) => v,
-            ChannelUsage::Multiple(d) => d.into_values().max().unwrap_or_default(),
+            ChannelUsage::Single(v) => v.time,
+            ChannelUsage::Multiple(d) => d.into_values().map(|u| u.time).max().unwrap_or_default(),
         }
     }
 }
@@ -72,7 +109,7 @@ mod tests {
         let mut helper = Helper::new(&[]);
         assert_eq!(helper.get_usage(&[]), Time::ZERO);
         let time = Time::new(10.0).unwrap();
-        helper.update_usage(time, &[]);
+        helper.update_usage(time, &[], true);
         assert_eq!(helper.get_usage(&[]), time);
         assert_eq!(helper.into_max_usage(), time);
     }
@@ -87,12 +124,12 @@ mod tests {
         assert_eq!(helper.get_usage(&[channels[0].clone()]), Time::ZERO);
 
         let t1 = Time::new(10.0).unwrap();
-        helper.update_usage(t1, &[]);
+        helper.update_usage(t1, &[], true);
         assert_eq!(helper.get_usage(&[]), t1);
         assert_eq!(helper.get_usage(&[channels[0].clone()]), t1);
 
         let t2 = Time::new(20.0).unwrap();
-        helper.update_usage(t2, &[channels[0].clone()]);
+        helper.update_usage(t2, &[channels[0].clone()], true);
         assert_eq!(helper.get_usage(&[]), t2);
         assert_eq!(helper.get_usage(&[channels[0].clone()]), t2);
         assert_eq!(helper.get_usage(&[channels[1].clone()]), t1);
@@ -102,4 +139,19 @@ mod tests {
         );
         assert_eq!(helper.into_max_usage(), t2);
     }
+
+    #[test]
+    fn test_helper_spacing() {
+        let channels = (0..2)
+            .map(|i| ChannelId::new(i.to_string()))
+            .collect::<Vec<_>>();
+        let mut helper = Helper::new(&channels);
+        let spacing = Time::new(5.0).unwrap();
+        let t1 = Time::new(10.0).unwrap();
+        helper.update_usage(t1, &channels[..1], true);
+        helper.update_usage(t1, &channels[1..], false);
+        assert_eq!(helper.get_start(&channels[..1], spacing), t1 + spacing);
+        assert_eq!(helper.get_start(&channels[1..], spacing), t1);
+        assert_eq!(helper.get_start(&[], spacing), t1 + spacing);
+    }
 }
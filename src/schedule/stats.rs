@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+
+use super::{Element, ElementRef, ElementVariant};
+
+/// Size and complexity of a schedule tree.
+///
+/// Shared subtrees are counted every time they appear in the tree, but are
+/// only visited once, so the computation is linear in the number of distinct
+/// elements. All counts saturate at [`u64::MAX`].
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleStats {
+    /// Number of elements in the tree.
+    pub node_count: u64,
+    /// Number of elements on the longest path from the root to a leaf.
+    pub depth: u64,
+    /// Number of elements of each variant, by [`ElementVariant::name`].
+    pub variant_counts: HashMap<&'static str, u64>,
+    /// Number of non-phantom [`Play`](super::Play) elements executed,
+    /// counting the repetitions of [`Repeat`](super::Repeat).
+    pub pulse_count: u64,
+}
+
+impl ScheduleStats {
+    pub fn new(root: &ElementRef) -> Self {
+        collect(root, &mut HashMap::new())
+    }
+
+    fn add_child(&mut self, child: &Self, repetitions: u64) {
+        self.node_count = self.node_count.saturating_add(child.node_count);
+        self.depth = self.depth.max(child.depth);
+        for (&name, &count) in &child.variant_counts {
+            let c = self.variant_counts.entry(name).or_default();
+            *c = c.saturating_add(count);
+        }
+        self.pulse_count = self
+            .pulse_count
+            .saturating_add(child.pulse_count.saturating_mul(repetitions));
+    }
+}
+
+fn collect(
+    element: &ElementRef,
+    memo: &mut HashMap<*const Element, ScheduleStats>,
+) -> ScheduleStats {
+    if let Some(stats) = memo.get(&Arc::as_ptr(element)) {
+        return stats.clone();
+    }
+    let mut stats = ScheduleStats::default();
+    let children: Vec<(&ElementRef, u64)> = match &element.variant {
+        ElementVariant::Repeat(r) => vec![(r.child(), r.count() as u64)],
+        ElementVariant::Scale(s) => vec![(s.child(), 1)],
+        ElementVariant::Reverse(v) => vec![(v.child(), 1)],
+        ElementVariant::Stack(s) => s.children().iter().map(|c| (c, 1)).collect(),
+        ElementVariant::Absolute(a) => a.children().iter().map(|e| (e.element(), 1)).collect(),
+        ElementVariant::Overlay(o) => o.children().iter().map(|c| (c, 1)).collect(),
+        ElementVariant::Grid(g) => g.children().iter().map(|e| (e.element(), 1)).collect(),
+        _ => vec![],
+    };
+    for (child, repetitions) in children {
+        let child = collect(child, memo);
+        stats.add_child(&child, repetitions);
+    }
+    stats.node_count = stats.node_count.saturating_add(1);
+    stats.depth = stats.depth.saturating_add(1);
+    let count = stats
+        .variant_counts
+        .entry(element.variant.name())
+        .or_default();
+    *count = count.saturating_add(1);
+    if element.common.phantom() {
+        stats.pulse_count = 0;
+    } else if matches!(element.variant, ElementVariant::Play(_)) {
+        stats.pulse_count = 1;
+    }
+    memo.insert(Arc::as_ptr(element), stats.clone());
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::{
+        test_util::{play, play_with},
+        ElementCommonBuilder, Repeat, Stack,
+    };
+
+    #[test]
+    fn count_repeated_and_shared() {
+        let shared = play("a", 1.0, None);
+        let phantom = play_with(
+            "a",
+            1.0,
+            ElementCommonBuilder::new().phantom(true).build().unwrap(),
+        );
+        let repeat = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Repeat::new(shared.clone(), 3),
+        ));
+        let root = Arc::new(Element::new(
+            ElementCommonBuilder::new().build().unwrap(),
+            Stack::new().with_children(vec![repeat, shared, phantom]),
+        ));
+
+        let stats = ScheduleStats::new(&root);
+
+        assert_eq!(stats.node_count, 5);
+        assert_eq!(stats.depth, 3);
+        assert_eq!(stats.variant_counts["Play"], 3);
+        assert_eq!(stats.variant_counts["Repeat"], 1);
+        assert_eq!(stats.variant_counts["Stack"], 1);
+        assert_eq!(stats.pulse_count, 4);
+    }
+}
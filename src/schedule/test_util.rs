@@ -0,0 +1,29 @@
+//! Element factories shared by the unit tests.
+
+use std::sync::Arc;
+
+use crate::{
+    quant::{Amplitude, ChannelId, Label, Time},
+    schedule::{Element, ElementCommon, ElementCommonBuilder, ElementRef, Play},
+};
+
+/// A play of width 10 without shape.
+pub(crate) fn play(channel: &str, amplitude: f64, label: Option<&str>) -> ElementRef {
+    let common = ElementCommonBuilder::new()
+        .label(label.map(Label::new))
+        .build()
+        .unwrap();
+    play_with(channel, amplitude, common)
+}
+
+/// Same as [`play`] with the given common properties.
+pub(crate) fn play_with(channel: &str, amplitude: f64, common: ElementCommon) -> ElementRef {
+    let play = Play::new(
+        ChannelId::new(channel),
+        None,
+        Amplitude::new(amplitude).unwrap(),
+        Time::new(10.0).unwrap(),
+    )
+    .unwrap();
+    Arc::new(Element::new(common, play))
+}
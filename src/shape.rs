@@ -1,4 +1,12 @@
-use std::{hash::Hash, sync::Arc};
+use std::{
+    cell::RefCell,
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher as _},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use anyhow::{bail, Result};
 use bspline::BSpline;
@@ -12,14 +20,72 @@ use ordered_float::NotNan;
 /// Internally, shape instances are cached such that we can compare and hash
 /// by instance address.
 #[derive(Debug, Clone)]
-pub(crate) struct Shape(Arc<ShapeVariant>);
+pub struct Shape(Arc<Instance>);
+
+#[derive(Debug)]
+struct Instance {
+    variant: ShapeVariant,
+    /// Hash of the [`ShapeKey`] which is stable across processes if `stable`.
+    content_hash: u64,
+    stable: bool,
+    cacheable: bool,
+    fingerprint: Option<u64>,
+}
+
+/// Function sampling a custom shape with the arguments of
+/// [`Shape::sample_array`].
+pub type SampleFn = dyn Fn(f64, f64, &mut [f64]) -> Result<()> + Send + Sync;
+
+static CUSTOM_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// First error of a custom shape in the current [`Shape::try_sample_array`].
+    static SAMPLE_ERROR: RefCell<Option<anyhow::Error>> = const { RefCell::new(None) };
+}
 
 impl Shape {
-    pub(crate) fn new_hann() -> Self {
+    pub fn new_hann() -> Self {
         Self(get_shape_instance(ShapeKey::Hann))
     }
 
-    pub(crate) fn new_interp(knots: Vec<f64>, controls: Vec<f64>, degree: usize) -> Result<Self> {
+    /// Gaussian truncated at `truncation` standard deviations from the
+    /// center on both sides. The pedestal at the truncation point is
+    /// subtracted and the result rescaled, so that the shape is zero at the
+    /// edges and one at the center.
+    pub fn new_gaussian(truncation: f64) -> Result<Self> {
+        if !(truncation.is_finite() && truncation > 0.0) {
+            bail!("Invalid truncation {}", truncation);
+        }
+        let key = ShapeKey::Gaussian(NotNan::new(truncation)?);
+        Ok(Self(get_shape_instance(key)))
+    }
+
+    /// Flat-top shape with error function edges, i.e. a rectangle smoothed by
+    /// a Gaussian filter. The rising edge spans `[-0.5, 0]` and is centered at
+    /// `-0.25`, where it is truncated at `truncation` standard deviations of
+    /// the filter and rescaled. The falling edge is the mirror image. The flat
+    /// part is given by the plateau of the pulse.
+    pub fn new_erf_square(truncation: f64) -> Result<Self> {
+        if !(truncation.is_finite() && truncation > 0.0) {
+            bail!("Invalid truncation {}", truncation);
+        }
+        let key = ShapeKey::ErfSquare(NotNan::new(truncation)?);
+        Ok(Self(get_shape_instance(key)))
+    }
+
+    /// Sum of cosines `a0 + a1 cos(2 pi x) + a2 cos(4 pi x) + a3 cos(6 pi x)`
+    /// as in the generalized cosine windows. The Hann shape is
+    /// `[0.5, 0.5, 0.0, 0.0]`.
+    pub fn new_cosine_sum(coefficients: [f64; 4]) -> Result<Self> {
+        if coefficients.iter().any(|a| !a.is_finite()) {
+            bail!("Invalid coefficients {:?}", coefficients);
+        }
+        let key =
+            ShapeKey::CosineSum(coefficients.map(|a| NotNan::new(a).expect("Should be finite")));
+        Ok(Self(get_shape_instance(key)))
+    }
+
+    pub fn new_interp(knots: Vec<f64>, controls: Vec<f64>, degree: usize) -> Result<Self> {
         let knots = knots
             .into_iter()
             .map(NotNan::new)
@@ -32,8 +98,122 @@ impl Shape {
         Ok(Self(get_shape_instance(key)))
     }
 
-    pub(crate) fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
-        self.0.sample_array(x0, dx, array);
+    /// Cubic Hermite spline through the points `(x, y)` with Catmull-Rom
+    /// tangents. The shape is zero outside `[x[0], x[n - 1]]`.
+    pub fn new_spline(x: Vec<f64>, y: Vec<f64>) -> Result<Self> {
+        if x.len() != y.len() {
+            bail!(
+                "Length mismatch: {} x values, {} y values",
+                x.len(),
+                y.len()
+            );
+        }
+        if x.len() < 2 {
+            bail!("At least 2 points are required");
+        }
+        if x.iter().chain(&y).any(|v| !v.is_finite()) {
+            bail!("Spline points must be finite");
+        }
+        if x.windows(2).any(|w| w[0] >= w[1]) {
+            bail!("Spline x values must be strictly increasing");
+        }
+        let x = x.into_iter().map(NotNan::new).collect::<Result<_, _>>()?;
+        let y = y.into_iter().map(NotNan::new).collect::<Result<_, _>>()?;
+        Ok(Self(get_shape_instance(ShapeKey::Spline(x, y))))
+    }
+
+    /// Shape from pre-sampled values, e.g. a measured envelope. Sample `k` of
+    /// `n` is at `-0.5 + k / n`, so the envelope is reproduced when the width
+    /// of the pulse is `n` sample intervals. The shape returns to zero at
+    /// `0.5`.
+    pub fn new_sampled(samples: Vec<f64>, interpolation: Interpolation) -> Result<Self> {
+        if samples.is_empty() {
+            bail!("At least 1 sample is required");
+        }
+        let samples = samples
+            .into_iter()
+            .map(NotNan::new)
+            .collect::<Result<_, _>>()?;
+        let key = ShapeKey::Sampled(samples, interpolation);
+        Ok(Self(get_shape_instance(key)))
+    }
+
+    /// Composite shape with the rising half of `rise` and the falling half of
+    /// `fall`. The rising edge takes `rise_fraction` of the total width.
+    pub fn new_asymmetric(rise: Shape, fall: Shape, rise_fraction: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&rise_fraction) {
+            bail!("Invalid rise fraction {}", rise_fraction);
+        }
+        let key = ShapeKey::Asymmetric(rise, fall, NotNan::new(rise_fraction)?);
+        Ok(Self(get_shape_instance(key)))
+    }
+
+    /// Elementwise product of two shapes, e.g. a window times a slow
+    /// modulation. The plateau is inserted at the peak of `a`.
+    pub fn new_product(a: Shape, b: Shape) -> Self {
+        Self(get_shape_instance(ShapeKey::Product(a, b)))
+    }
+
+    /// Elementwise sum of two shapes. The plateau is inserted at the peak of
+    /// `a`. The sum is not rescaled, so it is normalized only if the values of
+    /// the two shapes at the peak add up to one.
+    pub fn new_sum(a: Shape, b: Shape) -> Self {
+        Self(get_shape_instance(ShapeKey::Sum(a, b)))
+    }
+
+    /// Shape sampled by `function`, e.g. a Python callable.
+    ///
+    /// The shape is only equal to itself and has no fingerprint. If
+    /// `cacheable` is false, the sampled envelopes are not cached, which is
+    /// needed if the function is not pure.
+    pub fn new_custom(function: Arc<SampleFn>, cacheable: bool) -> Self {
+        let id = CUSTOM_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let mut hasher = DefaultHasher::new();
+        ("custom", id).hash(&mut hasher);
+        Self(Arc::new(Instance {
+            variant: Custom(function).into(),
+            content_hash: hasher.finish(),
+            stable: false,
+            cacheable,
+            fingerprint: None,
+        }))
+    }
+
+    /// Samples of custom shapes whose function failed are NaN, use
+    /// [`Shape::try_sample_array`] to get the error.
+    pub fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
+        self.0.variant.sample_array(x0, dx, array);
+    }
+
+    /// Same as [`Shape::sample_array`], but returns the first error of custom
+    /// shapes.
+    pub fn try_sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) -> Result<()> {
+        // The function of a custom shape may sample other shapes.
+        let outer = SAMPLE_ERROR.take();
+        self.sample_array(x0, dx, array);
+        match SAMPLE_ERROR.replace(outer) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Position of the peak in the range \[-0.5, 0.5\], where the plateau is
+    /// inserted.
+    pub fn peak(&self) -> f64 {
+        self.0.variant.peak()
+    }
+
+    /// Content hash of the shape which is stable across processes.
+    ///
+    /// Returns `None` for shapes that are cheap to sample and not worth
+    /// persisting.
+    pub fn fingerprint(&self) -> Option<u64> {
+        self.0.fingerprint
+    }
+
+    /// Whether the sampled envelopes of the shape may be cached.
+    pub fn cacheable(&self) -> bool {
+        self.0.cacheable
     }
 }
 
@@ -53,28 +233,109 @@ impl Eq for Shape {}
 
 type HashableArray = Vec<NotNan<f64>>;
 
+/// Interpolation between the values of [`Shape::new_sampled`].
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Cubic Hermite with Catmull-Rom tangents.
+    Cubic,
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 enum ShapeKey {
     Hann,
+    Gaussian(NotNan<f64>),
+    CosineSum([NotNan<f64>; 4]),
+    ErfSquare(NotNan<f64>),
     Interp(HashableArray, HashableArray, usize),
+    Spline(HashableArray, HashableArray),
+    Sampled(HashableArray, Interpolation),
+    Asymmetric(Shape, Shape, NotNan<f64>),
+    Product(Shape, Shape),
+    Sum(Shape, Shape),
 }
 
 #[cached(size = 128)]
-fn get_shape_instance(a: ShapeKey) -> Arc<ShapeVariant> {
+fn get_shape_instance(a: ShapeKey) -> Arc<Instance> {
+    // `Shape` hashes by address, so composite shapes combine the content
+    // hashes of their parts instead.
+    let content_hash = {
+        let mut hasher = DefaultHasher::new();
+        match &a {
+            ShapeKey::Asymmetric(rise, fall, rise_fraction) => (
+                "asymmetric",
+                rise.0.content_hash,
+                fall.0.content_hash,
+                rise_fraction,
+            )
+                .hash(&mut hasher),
+            ShapeKey::Product(a, b) => {
+                ("product", a.0.content_hash, b.0.content_hash).hash(&mut hasher)
+            }
+            ShapeKey::Sum(a, b) => ("sum", a.0.content_hash, b.0.content_hash).hash(&mut hasher),
+            key => key.hash(&mut hasher),
+        }
+        hasher.finish()
+    };
+    let parts = match &a {
+        ShapeKey::Asymmetric(a, b, _) | ShapeKey::Product(a, b) | ShapeKey::Sum(a, b) => {
+            vec![a, b]
+        }
+        _ => vec![],
+    };
+    let stable = parts.iter().all(|s| s.0.stable);
+    let cacheable = parts.iter().all(|s| s.cacheable());
+    let fingerprint = match &a {
+        ShapeKey::Hann
+        | ShapeKey::Gaussian(_)
+        | ShapeKey::CosineSum(_)
+        | ShapeKey::ErfSquare(_) => None,
+        ShapeKey::Interp(..) | ShapeKey::Spline(..) | ShapeKey::Sampled(..) => Some(content_hash),
+        ShapeKey::Asymmetric(..) | ShapeKey::Product(..) | ShapeKey::Sum(..) => {
+            (stable && parts.iter().any(|s| s.fingerprint().is_some())).then_some(content_hash)
+        }
+    };
     let variant = match a {
         ShapeKey::Hann => Hann.into(),
+        ShapeKey::Gaussian(truncation) => Gaussian::new(truncation.into()).into(),
+        ShapeKey::CosineSum(coefficients) => CosineSum(coefficients.map(f64::from)).into(),
+        ShapeKey::ErfSquare(truncation) => ErfSquare::new(truncation.into()).into(),
         ShapeKey::Interp(t, c, k) => {
             let t = t.into_iter().map(|v| v.into()).collect();
             let c = c.into_iter().map(|v| v.into()).collect();
             Interp::new(t, c, k).into()
         }
+        ShapeKey::Spline(x, y) => {
+            let x = x.into_iter().map(|v| v.into()).collect();
+            let y = y.into_iter().map(|v| v.into()).collect();
+            Spline::new(x, y).into()
+        }
+        ShapeKey::Sampled(samples, interpolation) => {
+            let samples = samples.into_iter().map(|v| v.into()).collect();
+            Sampled::new(samples, interpolation).into()
+        }
+        ShapeKey::Asymmetric(rise, fall, rise_fraction) => {
+            Asymmetric::new(rise, fall, rise_fraction.into()).into()
+        }
+        ShapeKey::Product(a, b) => Product(a, b).into(),
+        ShapeKey::Sum(a, b) => Sum(a, b).into(),
     };
-    Arc::new(variant)
+    Arc::new(Instance {
+        variant,
+        content_hash,
+        stable,
+        cacheable,
+        fingerprint,
+    })
 }
 
 trait ShapeTrait {
     /// Sample the shape at a given position x in the range \[-0.5, 0.5\].
     fn sample(&self, x: f64) -> f64;
+    /// Position of the peak where the plateau of a flat-top pulse is inserted.
+    fn peak(&self) -> f64 {
+        0.0
+    }
     fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
         for (i, y) in array.iter_mut().enumerate() {
             *y = self.sample(x0 + i as f64 * dx);
@@ -91,6 +352,122 @@ impl ShapeTrait for Hann {
     }
 }
 
+#[derive(Debug, Clone)]
+struct CosineSum([f64; 4]);
+
+impl ShapeTrait for CosineSum {
+    fn sample(&self, x: f64) -> f64 {
+        if !(-0.5..=0.5).contains(&x) {
+            return 0.0;
+        }
+        let phase = 2.0 * std::f64::consts::PI * x;
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(k, a)| a * (k as f64 * phase).cos())
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Gaussian {
+    /// Standard deviation in units of the width.
+    sigma: f64,
+    pedestal: f64,
+}
+
+impl Gaussian {
+    fn new(truncation: f64) -> Self {
+        let sigma = 0.5 / truncation;
+        let pedestal = (-0.5 * truncation * truncation).exp();
+        Self { sigma, pedestal }
+    }
+
+    fn normalize(&self, g: f64) -> f64 {
+        (g - self.pedestal) / (1.0 - self.pedestal)
+    }
+}
+
+impl ShapeTrait for Gaussian {
+    fn sample(&self, x: f64) -> f64 {
+        if !(-0.5..=0.5).contains(&x) {
+            return 0.0;
+        }
+        let u = x / self.sigma;
+        self.normalize((-0.5 * u * u).exp())
+    }
+
+    /// Fills the samples outside of the support with zeros without
+    /// evaluating the exponential.
+    fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
+        let scale = -0.5 / (self.sigma * self.sigma);
+        for (i, y) in array.iter_mut().enumerate() {
+            let x = x0 + i as f64 * dx;
+            *y = if (-0.5..=0.5).contains(&x) {
+                self.normalize((scale * x * x).exp())
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ErfSquare {
+    /// Inverse of `sqrt(2)` times the standard deviation in units of the
+    /// width.
+    scale: f64,
+    /// Value of the unnormalized edge at the truncation point.
+    pedestal: f64,
+}
+
+impl ErfSquare {
+    fn new(truncation: f64) -> Self {
+        let scale = 4.0 * truncation / std::f64::consts::SQRT_2;
+        let pedestal = erf(truncation / std::f64::consts::SQRT_2);
+        Self { scale, pedestal }
+    }
+}
+
+impl ShapeTrait for ErfSquare {
+    fn sample(&self, x: f64) -> f64 {
+        if !(-0.5..=0.5).contains(&x) {
+            return 0.0;
+        }
+        let u = self.scale * (0.25 - x.abs());
+        (erf(u) + self.pedestal) / (2.0 * self.pedestal)
+    }
+}
+
+/// Error function with an absolute error of about 1e-15.
+///
+/// The power series is used for small arguments and the continued fraction of
+/// `erfc` for large ones, where the series loses precision.
+fn erf(x: f64) -> f64 {
+    let a = x.abs();
+    let y = if a < 2.0 {
+        let x2 = a * a;
+        let mut term = a;
+        let mut sum = a;
+        let mut n = 0.0;
+        while term.abs() > 1e-17 * sum {
+            n += 1.0;
+            term *= -x2 / n;
+            sum += term / (2.0 * n + 1.0);
+        }
+        sum * std::f64::consts::FRAC_2_SQRT_PI
+    } else if a < 6.0 {
+        let mut t = a;
+        for k in (1..=60).rev() {
+            t = a + 0.5 * k as f64 / t;
+        }
+        1.0 - (-a * a).exp() / (t * std::f64::consts::PI.sqrt())
+    } else {
+        1.0
+    };
+    y.copysign(x)
+}
+
 #[derive(Debug, Clone)]
 struct Interp(BSpline<f64, f64>);
 
@@ -110,6 +487,254 @@ impl ShapeTrait for Interp {
     }
 }
 
+/// Cubic Hermite spline with Catmull-Rom tangents, which are one-sided at the
+/// end points.
+#[derive(Debug, Clone)]
+struct Spline {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    tangents: Vec<f64>,
+}
+
+impl Spline {
+    fn new(x: Vec<f64>, y: Vec<f64>) -> Self {
+        let n = x.len();
+        let tangents = (0..n)
+            .map(|i| {
+                let (l, r) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                (y[r] - y[l]) / (x[r] - x[l])
+            })
+            .collect();
+        Self { x, y, tangents }
+    }
+
+    /// Evaluates segment `i`, which contains `x`.
+    fn eval(&self, i: usize, x: f64) -> f64 {
+        let h = self.x[i + 1] - self.x[i];
+        let t = (x - self.x[i]) / h;
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+        h00 * self.y[i]
+            + h10 * h * self.tangents[i]
+            + h01 * self.y[i + 1]
+            + h11 * h * self.tangents[i + 1]
+    }
+
+    fn contains(&self, x: f64) -> bool {
+        self.x[0] <= x && x <= self.x[self.x.len() - 1]
+    }
+}
+
+impl ShapeTrait for Spline {
+    fn sample(&self, x: f64) -> f64 {
+        if !self.contains(x) {
+            return 0.0;
+        }
+        let i = self
+            .x
+            .partition_point(|&k| k <= x)
+            .clamp(1, self.x.len() - 1);
+        self.eval(i - 1, x)
+    }
+
+    /// Walks the segments instead of searching for each sample.
+    fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
+        if dx <= 0.0 {
+            for (i, y) in array.iter_mut().enumerate() {
+                *y = self.sample(x0 + i as f64 * dx);
+            }
+            return;
+        }
+        let last = self.x.len() - 2;
+        let mut segment = 0;
+        for (i, y) in array.iter_mut().enumerate() {
+            let x = x0 + i as f64 * dx;
+            if !self.contains(x) {
+                *y = 0.0;
+                continue;
+            }
+            while segment < last && self.x[segment + 1] <= x {
+                segment += 1;
+            }
+            *y = self.eval(segment, x);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Sampled {
+    /// Samples with a trailing zero at `0.5`.
+    y: Vec<f64>,
+    /// Tangents in units of the sample interval if cubic.
+    tangents: Option<Vec<f64>>,
+}
+
+impl Sampled {
+    fn new(mut y: Vec<f64>, interpolation: Interpolation) -> Self {
+        y.push(0.0);
+        let n = y.len();
+        let tangents = match interpolation {
+            Interpolation::Linear => None,
+            Interpolation::Cubic => Some(
+                (0..n)
+                    .map(|i| {
+                        let (l, r) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                        (y[r] - y[l]) / (r - l) as f64
+                    })
+                    .collect(),
+            ),
+        };
+        Self { y, tangents }
+    }
+}
+
+impl ShapeTrait for Sampled {
+    fn sample(&self, x: f64) -> f64 {
+        if !(-0.5..=0.5).contains(&x) {
+            return 0.0;
+        }
+        let last = self.y.len() - 1;
+        let u = (x + 0.5) * last as f64;
+        let i = (u.floor() as usize).min(last - 1);
+        let t = u - i as f64;
+        let (y0, y1) = (self.y[i], self.y[i + 1]);
+        match &self.tangents {
+            None => y0 + t * (y1 - y0),
+            Some(m) => {
+                let t2 = t * t;
+                let t3 = t2 * t;
+                (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+                    + (t3 - 2.0 * t2 + t) * m[i]
+                    + (-2.0 * t3 + 3.0 * t2) * y1
+                    + (t3 - t2) * m[i + 1]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Asymmetric {
+    rise: Shape,
+    fall: Shape,
+    rise_fraction: f64,
+}
+
+impl Asymmetric {
+    fn new(rise: Shape, fall: Shape, rise_fraction: f64) -> Self {
+        Self {
+            rise,
+            fall,
+            rise_fraction,
+        }
+    }
+}
+
+impl ShapeTrait for Asymmetric {
+    fn sample(&self, x: f64) -> f64 {
+        let peak = self.peak();
+        if x < peak {
+            if self.rise_fraction == 0.0 {
+                return 0.0;
+            }
+            self.rise
+                .0
+                .variant
+                .sample((x - peak) * 0.5 / self.rise_fraction)
+        } else {
+            if self.rise_fraction == 1.0 {
+                return 0.0;
+            }
+            self.fall
+                .0
+                .variant
+                .sample((x - peak) * 0.5 / (1.0 - self.rise_fraction))
+        }
+    }
+
+    fn peak(&self) -> f64 {
+        self.rise_fraction - 0.5
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Product(Shape, Shape);
+
+impl ShapeTrait for Product {
+    fn sample(&self, x: f64) -> f64 {
+        self.0 .0.variant.sample(x) * self.1 .0.variant.sample(x)
+    }
+
+    fn peak(&self) -> f64 {
+        self.0.peak()
+    }
+
+    fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
+        let mut other = vec![0.0; array.len()];
+        self.0.sample_array(x0, dx, array);
+        self.1.sample_array(x0, dx, &mut other);
+        for (y, z) in array.iter_mut().zip(other) {
+            *y *= z;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Sum(Shape, Shape);
+
+impl ShapeTrait for Sum {
+    fn sample(&self, x: f64) -> f64 {
+        self.0 .0.variant.sample(x) + self.1 .0.variant.sample(x)
+    }
+
+    fn peak(&self) -> f64 {
+        self.0.peak()
+    }
+
+    fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
+        let mut other = vec![0.0; array.len()];
+        self.0.sample_array(x0, dx, array);
+        self.1.sample_array(x0, dx, &mut other);
+        for (y, z) in array.iter_mut().zip(other) {
+            *y += z;
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Custom(Arc<SampleFn>);
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Custom")
+    }
+}
+
+impl ShapeTrait for Custom {
+    fn sample(&self, x: f64) -> f64 {
+        let mut y = [0.0];
+        self.sample_array(x, 0.0, &mut y);
+        y[0]
+    }
+
+    fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
+        // Don't call the function again after an error.
+        let mut failed = SAMPLE_ERROR.with_borrow(Option::is_some);
+        if !failed {
+            if let Err(e) = (self.0)(x0, dx, array) {
+                SAMPLE_ERROR.set(Some(e));
+                failed = true;
+            }
+        }
+        if failed {
+            array.fill(f64::NAN);
+        }
+    }
+}
+
 macro_rules! impl_variant {
     ($($variant:ident),*$(,)?) => {
 #[derive(Debug, Clone)]
@@ -154,6 +779,12 @@ impl ShapeTrait for ShapeVariant {
         }
     }
 
+    fn peak(&self) -> f64 {
+        match self {
+            $(ShapeVariant::$variant(v) => v.peak(),)*
+        }
+    }
+
     fn sample_array(&self, x0: f64, dx: f64, array: &mut [f64]) {
         match self {
             $(ShapeVariant::$variant(v) => v.sample_array(x0, dx, array),)*
@@ -163,7 +794,10 @@ impl ShapeTrait for ShapeVariant {
     };
 }
 
-impl_variant!(Hann, Interp);
+impl_variant!(
+    Hann, CosineSum, Gaussian, ErfSquare, Interp, Spline, Sampled, Asymmetric, Product, Sum,
+    Custom,
+);
 
 #[cfg(test)]
 mod tests {
@@ -181,6 +815,167 @@ mod tests {
         assert_approx_eq!(f64, hann.sample(0.5), 0.0);
     }
 
+    #[test]
+    fn test_cosine_sum() {
+        let hann = CosineSum([0.5, 0.5, 0.0, 0.0]);
+        for x in [-0.5, -0.3, 0.0, 0.1, 0.5] {
+            assert_approx_eq!(f64, hann.sample(x), Hann.sample(x), epsilon = 1e-15);
+        }
+        let blackman = CosineSum([0.42, 0.5, 0.08, 0.0]);
+        assert_approx_eq!(f64, blackman.sample(0.0), 1.0);
+        assert_approx_eq!(f64, blackman.sample(-0.5), 0.0, epsilon = 1e-15);
+        assert_approx_eq!(f64, blackman.sample(0.25), 0.34);
+        assert_approx_eq!(f64, blackman.sample(0.6), 0.0);
+        assert!(Shape::new_cosine_sum([f64::NAN, 0.0, 0.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn test_gaussian() {
+        let gaussian = Gaussian::new(2.0);
+        assert_approx_eq!(f64, gaussian.sample(-0.5), 0.0);
+        assert_approx_eq!(f64, gaussian.sample(0.0), 1.0);
+        assert_approx_eq!(f64, gaussian.sample(0.5), 0.0, epsilon = 1e-15);
+        assert_approx_eq!(f64, gaussian.sample(0.6), 0.0);
+        let expected = ((-0.5f64).exp() - (-2.0f64).exp()) / (1.0 - (-2.0f64).exp());
+        assert_approx_eq!(f64, gaussian.sample(0.25), expected);
+        let mut array = [0.0; 1200];
+        gaussian.sample_array(-0.6, 0.001, &mut array);
+        for (i, &y) in array.iter().enumerate() {
+            let x = -0.6 + i as f64 * 0.001;
+            assert_approx_eq!(f64, y, gaussian.sample(x), epsilon = 1e-12);
+        }
+        assert!(Shape::new_gaussian(0.0).is_err());
+        assert_eq!(
+            Shape::new_gaussian(2.0).unwrap(),
+            Shape::new_gaussian(2.0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_erf() {
+        let expected = [
+            (0.0, 0.0),
+            (0.5, 0.5204998778130465),
+            (1.0, 0.8427007929497149),
+            (1.9, 0.9927904292352575),
+            (2.0, 0.9953222650189527),
+            (3.0, 0.9999779095030014),
+            (5.0, 0.9999999999984626),
+        ];
+        for (x, y) in expected {
+            assert_approx_eq!(f64, erf(x), y, epsilon = 1e-15);
+            assert_approx_eq!(f64, erf(-x), -y, epsilon = 1e-15);
+        }
+    }
+
+    #[test]
+    fn test_erf_square() {
+        let shape = ErfSquare::new(2.0);
+        assert_approx_eq!(f64, shape.sample(-0.5), 0.0, epsilon = 1e-15);
+        assert_approx_eq!(f64, shape.sample(-0.25), 0.5);
+        assert_approx_eq!(f64, shape.sample(0.0), 1.0);
+        assert_approx_eq!(f64, shape.sample(0.5), 0.0, epsilon = 1e-15);
+        assert_approx_eq!(f64, shape.sample(0.6), 0.0);
+        assert_approx_eq!(f64, shape.sample(-0.1), shape.sample(0.1));
+        let expected = (erf(2f64.sqrt()) + erf(2f64.sqrt() / 2.0)) / (2.0 * erf(2f64.sqrt()));
+        assert_approx_eq!(f64, shape.sample(0.125), expected);
+        assert!(Shape::new_erf_square(-1.0).is_err());
+    }
+
+    #[test]
+    fn test_product_and_sum() {
+        let hann = Shape::new_hann();
+        let gaussian = Shape::new_gaussian(2.0).unwrap();
+        let product = Shape::new_product(hann.clone(), gaussian.clone());
+        let sum = Shape::new_sum(hann.clone(), gaussian.clone());
+        let mut array = [0.0; 11];
+        let mut expected = [0.0; 11];
+        product.sample_array(-0.5, 0.1, &mut array);
+        for (i, y) in expected.iter_mut().enumerate() {
+            let x = -0.5 + i as f64 * 0.1;
+            *y = Hann.sample(x) * Gaussian::new(2.0).sample(x);
+        }
+        for (y, z) in array.iter().zip(expected) {
+            assert_approx_eq!(f64, *y, z, epsilon = 1e-15);
+        }
+        sum.sample_array(-0.5, 0.1, &mut array);
+        assert_approx_eq!(f64, array[5], 2.0);
+        assert_approx_eq!(f64, array[0], 0.0, epsilon = 1e-15);
+        assert_eq!(product, Shape::new_product(hann.clone(), gaussian.clone()));
+        assert_ne!(product, Shape::new_product(gaussian.clone(), hann.clone()));
+        assert_eq!(product.fingerprint(), None);
+    }
+
+    #[test]
+    fn composite_fingerprint() {
+        let spline = Shape::new_spline(vec![-0.5, 0.0, 0.5], vec![0.0, 1.0, 0.0]).unwrap();
+        let hann = Shape::new_hann();
+        let gaussian = Shape::new_gaussian(2.0).unwrap();
+        let fingerprints = [
+            Shape::new_product(spline.clone(), hann.clone()),
+            Shape::new_product(spline.clone(), gaussian.clone()),
+            Shape::new_sum(spline.clone(), hann.clone()),
+            Shape::new_asymmetric(spline.clone(), hann, 0.5).unwrap(),
+            Shape::new_asymmetric(spline, gaussian, 0.5).unwrap(),
+        ]
+        .map(|s| s.fingerprint().unwrap());
+        for (i, a) in fingerprints.iter().enumerate() {
+            for b in &fingerprints[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom() {
+        let linear = Shape::new_custom(
+            Arc::new(|x0, dx, array: &mut [f64]| {
+                for (i, y) in array.iter_mut().enumerate() {
+                    *y = x0 + i as f64 * dx;
+                }
+                Ok(())
+            }),
+            true,
+        );
+        let failing = Shape::new_custom(
+            Arc::new(|x0, _, _: &mut [f64]| {
+                if x0 > 0.0 {
+                    bail!("x0 = {}", x0);
+                }
+                Ok(())
+            }),
+            false,
+        );
+        let spline = Shape::new_spline(vec![-0.5, 0.5], vec![1.0, 1.0]).unwrap();
+
+        let mut array = [0.0; 3];
+        linear.try_sample_array(-0.5, 0.5, &mut array).unwrap();
+        assert_eq!(array, [-0.5, 0.0, 0.5]);
+        let asymmetric = Shape::new_asymmetric(linear.clone(), failing.clone(), 0.5).unwrap();
+        let err = asymmetric.try_sample_array(-0.5, 0.25, &mut [0.0; 5]);
+        assert_eq!(err.unwrap_err().to_string(), "x0 = 0.25");
+        asymmetric.try_sample_array(-0.5, 0.25, &mut array).unwrap();
+        assert_ne!(linear, Shape::new_custom(Arc::new(|_, _, _| Ok(())), true));
+        let product = Shape::new_product(spline.clone(), linear);
+        assert!(product.cacheable());
+        assert_eq!(product.fingerprint(), None);
+        assert!(!Shape::new_sum(spline, failing).cacheable());
+    }
+
+    #[test]
+    fn test_asymmetric() {
+        let hann = Shape::new_hann();
+        let shape = Shape::new_asymmetric(hann.clone(), hann, 0.25).unwrap();
+        assert_approx_eq!(f64, shape.peak(), -0.25);
+        let variant = &shape.0.variant;
+        assert_approx_eq!(f64, variant.sample(-0.5), 0.0);
+        assert_approx_eq!(f64, variant.sample(-0.375), 0.5);
+        assert_approx_eq!(f64, variant.sample(-0.25), 1.0);
+        assert_approx_eq!(f64, variant.sample(0.125), 0.5);
+        assert_approx_eq!(f64, variant.sample(0.5), 0.0);
+        assert!(Shape::new_asymmetric(Shape::new_hann(), Shape::new_hann(), 1.5).is_err());
+    }
+
     #[test]
     fn test_interp() {
         // Generated with the following Python code:
@@ -251,6 +1046,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_spline() {
+        let spline = Spline::new(vec![-0.5, -0.1, 0.0, 0.5], vec![0.0, 0.8, 1.0, 0.0]);
+        assert_approx_eq!(f64, spline.sample(-0.5), 0.0);
+        assert_approx_eq!(f64, spline.sample(-0.1), 0.8);
+        assert_approx_eq!(f64, spline.sample(0.0), 1.0);
+        assert_approx_eq!(f64, spline.sample(0.5), 0.0);
+        assert_approx_eq!(f64, spline.sample(0.6), 0.0);
+        // Linear data is reproduced exactly.
+        let line = Spline::new(vec![-0.5, 0.0, 0.2, 0.5], vec![0.0, 1.0, 1.4, 2.0]);
+        assert_approx_eq!(f64, line.sample(0.1), 1.2, epsilon = 1e-12);
+        let mut array = [0.0; 12];
+        spline.sample_array(-0.55, 0.1, &mut array);
+        for (i, &y) in array.iter().enumerate() {
+            let x = -0.55 + i as f64 * 0.1;
+            assert_approx_eq!(f64, y, spline.sample(x), epsilon = 1e-12);
+        }
+        assert!(Shape::new_spline(vec![0.0, 0.0], vec![1.0, 1.0]).is_err());
+        assert!(Shape::new_spline(vec![0.0], vec![1.0]).is_err());
+    }
+
+    #[test]
+    fn test_sampled() {
+        let samples = vec![0.0, 0.5, 1.0, 0.5];
+        let linear = Shape::new_sampled(samples.clone(), Interpolation::Linear).unwrap();
+        let cubic = Shape::new_sampled(samples, Interpolation::Cubic).unwrap();
+        let mut array = [0.0; 4];
+        for shape in [&linear, &cubic] {
+            shape.sample_array(-0.5, 0.25, &mut array);
+            assert_eq!(array, [0.0, 0.5, 1.0, 0.5]);
+            assert_eq!(shape.0.variant.sample(0.5), 0.0);
+            assert_eq!(shape.0.variant.sample(0.6), 0.0);
+            assert!(shape.fingerprint().is_some());
+        }
+        assert_approx_eq!(f64, linear.0.variant.sample(0.375), 0.25);
+        assert_approx_eq!(f64, linear.0.variant.sample(-0.125), 0.75);
+        // Catmull-Rom through the symmetric points around the peak.
+        assert_approx_eq!(f64, cubic.0.variant.sample(-0.125), 0.8125);
+        assert!(Shape::new_sampled(vec![], Interpolation::Linear).is_err());
+        assert!(Shape::new_sampled(vec![f64::NAN], Interpolation::Cubic).is_err());
+    }
+
     #[test]
     fn test_shape_eq() {
         let h1 = Shape::new_hann();
@@ -0,0 +1,115 @@
+//! Amplitude statistics of sampled waveforms.
+
+use ndarray::{Array1, ArrayViewD};
+
+/// Peak, RMS and amplitude histogram of a waveform.
+///
+/// All components of the waveform are treated as samples of the same
+/// distribution, e.g. both rows of an IQ waveform.
+#[derive(Debug, Clone)]
+pub(crate) struct WaveformStats {
+    peak: f64,
+    rms: f64,
+    histogram: Vec<u64>,
+    range: (f64, f64),
+}
+
+impl WaveformStats {
+    /// Computes the statistics with `bins` equal-width histogram bins.
+    ///
+    /// If `range` is `None`, the histogram covers `[-peak, peak]`, or
+    /// `[-1, 1]` if the waveform is all zeros. Samples outside the range are
+    /// not counted, and the last bin includes its right edge.
+    pub(crate) fn new(waveform: ArrayViewD<f64>, bins: usize, range: Option<(f64, f64)>) -> Self {
+        assert!(bins > 0, "There should be at least one bin");
+        let peak = waveform.iter().fold(0.0f64, |acc, x| acc.max(x.abs()));
+        let rms = if waveform.is_empty() {
+            0.0
+        } else {
+            (waveform.iter().map(|x| x * x).sum::<f64>() / waveform.len() as f64).sqrt()
+        };
+        let range = range.unwrap_or(if peak > 0.0 {
+            (-peak, peak)
+        } else {
+            (-1.0, 1.0)
+        });
+        let (lo, hi) = range;
+        let mut histogram = vec![0; bins];
+        for &x in &waveform {
+            if !(lo..=hi).contains(&x) {
+                continue;
+            }
+            let i = ((x - lo) / (hi - lo) * bins as f64) as usize;
+            histogram[i.min(bins - 1)] += 1;
+        }
+        Self {
+            peak,
+            rms,
+            histogram,
+            range,
+        }
+    }
+
+    /// Maximum absolute value of the samples.
+    pub(crate) fn peak(&self) -> f64 {
+        self.peak
+    }
+
+    pub(crate) fn rms(&self) -> f64 {
+        self.rms
+    }
+
+    /// Peak-to-average power ratio, i.e. `(peak / rms)^2`. `NaN` if the
+    /// waveform is all zeros.
+    pub(crate) fn papr(&self) -> f64 {
+        (self.peak / self.rms).powi(2)
+    }
+
+    pub(crate) fn histogram(&self) -> &[u64] {
+        &self.histogram
+    }
+
+    pub(crate) fn bin_edges(&self) -> Array1<f64> {
+        Array1::linspace(self.range.0, self.range.1, self.histogram.len() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    #[test]
+    fn square_wave() {
+        let w = array![[1.0, -1.0, 1.0, -1.0], [0.5, 0.5, -0.5, -0.5]];
+
+        let stats = WaveformStats::new(w.view().into_dyn(), 4, None);
+
+        assert_eq!(stats.peak(), 1.0);
+        float_cmp::assert_approx_eq!(f64, stats.rms(), 0.625f64.sqrt());
+        float_cmp::assert_approx_eq!(f64, stats.papr(), 1.6);
+        assert_eq!(stats.histogram(), &[2, 2, 0, 4]);
+        assert_eq!(stats.bin_edges(), array![-1.0, -0.5, 0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn explicit_range() {
+        let w = array![[0.0, 0.0, 0.2, 2.0]];
+
+        let stats = WaveformStats::new(w.view().into_dyn(), 2, Some((0.0, 1.0)));
+
+        assert_eq!(stats.histogram(), &[3, 0]);
+    }
+
+    #[test]
+    fn all_zeros() {
+        let w = array![[0.0, 0.0]];
+
+        let stats = WaveformStats::new(w.view().into_dyn(), 2, None);
+
+        assert_eq!(stats.rms(), 0.0);
+        assert!(stats.papr().is_nan());
+        assert_eq!(stats.histogram(), &[0, 2]);
+    }
+}
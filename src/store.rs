@@ -0,0 +1,290 @@
+//! On-disk store of generated waveforms shared between processes.
+//!
+//! Each result is a directory below the root of the store:
+//!
+//! ```text
+//! <root>/<name>/meta.json
+//! <root>/<name>/schedule.json
+//! <root>/<name>/waveforms/<channel>.npy
+//! ```
+//!
+//! A result is written to a staging directory first and then renamed into
+//! place. Renaming onto an existing result fails, so concurrent writers never
+//! overwrite each other and readers never see a partially written result.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ndarray::ArrayView2;
+use serde_json::{json, Value};
+
+use crate::quant::ChannelId;
+
+const STAGING_PREFIX: &str = ".staging-";
+const META_FILE: &str = "meta.json";
+const FORMAT_VERSION: u32 = 1;
+
+/// Number of attempts to find a free automatic name before giving up.
+const MAX_ATTEMPTS: usize = 1000;
+
+static STAGING_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+pub struct ResultStore {
+    root: PathBuf,
+}
+
+/// Contents of a result, see [`ResultStore::save`].
+#[derive(Debug, Clone, Default)]
+pub struct Record<'a> {
+    pub waveforms: Vec<(ChannelId, ArrayView2<'a, f64>)>,
+    /// Schedule as returned by [`to_json`](crate::schedule::to_json).
+    pub schedule: Option<String>,
+    /// Channel configurations, stored in `meta.json`.
+    pub channels: Option<Value>,
+    /// User metadata, stored in `meta.json`.
+    pub metadata: Option<Value>,
+}
+
+impl ResultStore {
+    /// Opens the store at `root`, which is created if it does not exist.
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Writes the record and returns the directory of the result.
+    ///
+    /// Without `name` the result gets the next free sequence number, e.g.
+    /// `000042`. An explicit `name` which is already used is an
+    /// [`io::ErrorKind::AlreadyExists`] error.
+    pub fn save(&self, name: Option<&str>, record: &Record) -> io::Result<PathBuf> {
+        if let Some(name) = name {
+            check_name(name)?;
+        }
+        let staging = self.root.join(format!(
+            "{}{}-{}",
+            STAGING_PREFIX,
+            std::process::id(),
+            STAGING_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = write_record(&staging, record).and_then(|()| self.publish(&staging, name));
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&staging);
+        }
+        result
+    }
+
+    /// Names of the complete results in the store, sorted.
+    pub fn names(&self) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !name.starts_with(STAGING_PREFIX) && entry.path().join(META_FILE).is_file() {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn publish(&self, staging: &Path, name: Option<&str>) -> io::Result<PathBuf> {
+        if let Some(name) = name {
+            let target = self.root.join(name);
+            return rename_new(staging, &target).map(|()| target);
+        }
+        let mut next = self.next_number()?;
+        for _ in 0..MAX_ATTEMPTS {
+            let target = self.root.join(format!("{:06}", next));
+            match rename_new(staging, &target) {
+                Ok(()) => return Ok(target),
+                // Taken by another writer in the meantime.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => next += 1,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "No free result name found",
+        ))
+    }
+
+    fn next_number(&self) -> io::Result<u64> {
+        let last = self
+            .names()?
+            .iter()
+            .filter_map(|n| n.parse::<u64>().ok())
+            .max();
+        Ok(last.map_or(0, |n| n + 1))
+    }
+}
+
+fn check_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid result name '{}'", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Renames `from` to `to`, failing with [`io::ErrorKind::AlreadyExists`]
+/// if `to` exists.
+fn rename_new(from: &Path, to: &Path) -> io::Result<()> {
+    // Renaming a directory replaces an existing empty directory, so results
+    // are never empty.
+    if to.exists() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    fs::rename(from, to).map_err(|e| {
+        if to.exists() {
+            io::ErrorKind::AlreadyExists.into()
+        } else {
+            e
+        }
+    })
+}
+
+fn write_record(dir: &Path, record: &Record) -> io::Result<()> {
+    let waveform_dir = dir.join("waveforms");
+    fs::create_dir_all(&waveform_dir)?;
+    for (n, w) in &record.waveforms {
+        let file_name = format!("{}.npy", n);
+        check_name(&file_name)?;
+        fs::write(waveform_dir.join(file_name), npy_bytes(*w))?;
+    }
+    if let Some(schedule) = &record.schedule {
+        fs::write(dir.join("schedule.json"), schedule)?;
+    }
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64());
+    let meta = json!({
+        "format": FORMAT_VERSION,
+        "version": env!("CARGO_PKG_VERSION"),
+        "created": created,
+        "channels": record.channels,
+        "metadata": record.metadata,
+    });
+    let text = serde_json::to_string_pretty(&meta).map_err(io::Error::from)?;
+    // Written last, so that a result is only listed when it is complete.
+    fs::write(dir.join(META_FILE), text)
+}
+
+/// Serializes the array in the `.npy` format version 1.0.
+fn npy_bytes(array: ArrayView2<f64>) -> Vec<u8> {
+    let (rows, cols) = array.dim();
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        rows, cols
+    );
+    // Magic, version and header length take 10 bytes, and the header ends
+    // with a newline. The data should start at a multiple of 64 bytes.
+    let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    let mut bytes = Vec::with_capacity(10 + header.len() + array.len() * 8);
+    bytes.extend_from_slice(b"\x93NUMPY\x01\x00");
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for v in array.iter() {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::array;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bosing-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn save_and_list() {
+        let dir = temp_dir("save");
+        let store = ResultStore::new(&dir).unwrap();
+        let waveform = array![[1.0, 2.0], [3.0, 4.0]];
+        let record = Record {
+            waveforms: vec![(ChannelId::new("xy"), waveform.view())],
+            schedule: Some("{}".into()),
+            ..Default::default()
+        };
+
+        let first = store.save(None, &record).unwrap();
+        let second = store.save(None, &record).unwrap();
+        store.save(Some("calib"), &record).unwrap();
+        let duplicate = store.save(Some("calib"), &record);
+
+        assert_eq!(first, dir.join("000000"));
+        assert_eq!(second, dir.join("000001"));
+        assert_eq!(duplicate.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(store.names().unwrap(), ["000000", "000001", "calib"]);
+        let npy = fs::read(first.join("waveforms/xy.npy")).unwrap();
+        assert_eq!((npy.len() - 32) % 64, 0);
+        assert_eq!(&npy[npy.len() - 8..], 4.0f64.to_le_bytes());
+        let meta: Value =
+            serde_json::from_str(&fs::read_to_string(first.join(META_FILE)).unwrap()).unwrap();
+        assert_eq!(meta["version"], env!("CARGO_PKG_VERSION"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writers() {
+        let dir = temp_dir("concurrent");
+        let store = ResultStore::new(&dir).unwrap();
+        let waveform = array![[0.0; 16]];
+
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    let record = Record {
+                        waveforms: vec![(ChannelId::new("z"), waveform.view())],
+                        ..Default::default()
+                    };
+                    for _ in 0..4 {
+                        store.save(None, &record).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(store.names().unwrap().len(), 32);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_name() {
+        let dir = temp_dir("invalid");
+        let store = ResultStore::new(&dir).unwrap();
+
+        let result = store.save(Some("../escape"), &Record::default());
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,15 @@
+import io
+import json
+import math
+import os
+import subprocess
+import sys
+import threading
+import urllib.error
+import urllib.request
+
 import numpy as np
+import pytest
 
 import bosing
 
@@ -17,6 +28,23 @@ def test_basic():
     assert np.any(w != 0)
 
 
+
+@pytest.mark.parametrize(
+    "cls",
+    [bosing.Alignment, bosing.Direction, bosing.GridLengthUnit, bosing.Padding, bosing.Overflow],
+)
+def test_enum_semantics(cls):
+    import pickle
+
+    for name in dir(cls):
+        if not name[0].isupper():
+            continue
+        value = getattr(cls, name)
+        assert str(value) == name.lower()
+        assert cls.convert(str(value)) == value
+        assert pickle.loads(pickle.dumps(value)) == value
+        assert {value: 1}[cls.convert(name.lower())] == 1
+
 def test_mixing():
     shapes = {"hann": bosing.Hann()}
     schedule = bosing.Stack(duration=500e-9).with_children(
@@ -43,3 +71,1588 @@ def test_mixing():
     w2 = w2 * np.exp(1j * (2 * np.pi * freq * np.arange(1000) / 2e9))
 
     assert np.allclose(w1, w2)
+
+
+def test_optimize():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    play = bosing.Play("xy", "hann", 0.3, 50e-9)
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.ShiftPhase("xy", 0.1),
+        bosing.ShiftPhase("xy", 0.2),
+        bosing.Stack(play, play, play, direction="forward"),
+        bosing.Play("xy", "hann", 0.0, 50e-9),
+        bosing.Stack(play),
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, schedule, optimize=True)["xy"]
+    assert np.allclose(w1, w2)
+
+
+def test_error_codes():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+
+    schedule = bosing.Play("missing", "hann", 0.1, 10e-9)
+    with pytest.raises(bosing.ScheduleError) as info:
+        bosing.generate_waveforms(channels, shapes, schedule)
+    assert info.value.code == "channel_not_found"
+    assert info.value.channel == "missing"
+
+    play = bosing.Play("xy", "hann", 0.1, 10e-9, label="x")
+    schedule = bosing.Stack(duration=505e-9).with_children(play)
+    with pytest.raises(bosing.SamplingError) as info:
+        bosing.generate_waveforms(channels, shapes, schedule)
+    assert isinstance(info.value, bosing.BosingError)
+    assert info.value.code == "end_time_out_of_bounds"
+    assert info.value.channel == "xy"
+    assert info.value.label == "x"
+    assert info.value.details["path"] == "Stack > 0: Play 'x'"
+    assert info.value.details["pulse_time"] == pytest.approx(495e-9)
+    assert info.value.details["length"] == 1000
+    assert info.value.details["index"] > 1000
+
+    play = bosing.Play("xy", "hann", 0.1, 10e-9, alignment="stretch", min_duration=30e-9)
+    schedule = bosing.Grid((play, 1), columns=["*", 20e-9])
+    with pytest.raises(bosing.ScheduleError) as info:
+        bosing.generate_waveforms(channels, shapes, schedule)
+    assert info.value.code == "overconstrained_grid"
+    assert info.value.details["child"] == 0
+    schedule = bosing.Grid(play, columns=["*"], max_duration=20e-9)
+    with pytest.raises(bosing.ScheduleError) as info:
+        bosing.generate_waveforms(channels, shapes, schedule)
+    assert info.value.code == "overconstrained_grid"
+    assert info.value.details["available"] == pytest.approx(20e-9)
+
+
+def test_measure_schedule():
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+        bosing.Play("z", None, 0.1, 50e-9),
+    )
+    duration, spans = bosing.measure_schedule(schedule)
+    assert np.isclose(duration, 500e-9)
+    assert set(spans) == {"xy", "z"}
+    assert np.allclose(spans["xy"], (400e-9, 500e-9))
+    assert np.allclose(spans["z"], (450e-9, 500e-9))
+
+
+def test_amp_scales():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000), "z": bosing.Channel(0, 2e9, 1000, is_real=True)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9, label="drive"),
+        bosing.Play("z", None, 0.1, 50e-9),
+    )
+    scales = bosing.compute_amp_scales(channels, shapes, schedule, ["drive"], target=0.5)
+    result = bosing.generate_waveforms(channels, shapes, schedule, amp_scales=scales)
+    assert np.isclose(np.max(np.abs(result["xy"])), 0.5)
+    assert np.isclose(np.max(np.abs(result["z"])), 0.1)
+
+
+def test_import_without_numpy():
+    code = """
+import sys
+sys.modules["numpy"] = None
+import bosing
+schedule = bosing.Stack(duration=500e-9).with_children(bosing.Play("xy", "hann", 0.3, 100e-9))
+start, end = bosing.measure_schedule(schedule)[1]["xy"]
+assert abs(start - 400e-9) < 1e-15
+assert abs(end - 500e-9) < 1e-15
+try:
+    bosing.generate_waveforms({"xy": bosing.Channel(0, 2e9, 1000)}, {"hann": bosing.Hann()}, schedule)
+except ImportError:
+    pass
+else:
+    raise AssertionError("ImportError not raised")
+"""
+    subprocess.run([sys.executable, "-c", code], check=True)
+
+
+def test_mute_channels():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000), "z": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+        bosing.Play("z", "hann", 0.1, 50e-9),
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, schedule)
+    w2 = bosing.generate_waveforms(channels, shapes, schedule, mute_channels=["xy"])
+    assert np.all(w2["xy"] == 0)
+    assert np.allclose(w1["z"], w2["z"])
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, mute_channels=["missing"])
+
+
+def test_channel_scales():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000), "z": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9, label="drive"),
+        bosing.Play("z", "hann", 0.1, 50e-9),
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, schedule)
+    w2 = bosing.generate_waveforms(
+        channels, shapes, schedule, amp_scales={"drive": 2.0}, scales={"xy": 1.02}
+    )
+    assert np.allclose(w2["xy"], w1["xy"] * 2.04)
+    assert np.allclose(w1["z"], w2["z"])
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, scales={"missing": 1.0})
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, scales={"xy": float("nan")})
+
+
+def test_channel_aliases():
+    channels = {"awg1.ch1": bosing.Channel(0, 2e9, 1000), "awg1.ch2": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("q0.drive", "hann", 0.3, 100e-9),
+        bosing.Play("awg1.ch2", "hann", 0.1, 50e-9),
+    )
+    w = bosing.generate_waveforms(channels, shapes, schedule, aliases={"q0": "awg1.ch1", "q0.drive": "q0"})
+    expected = bosing.generate_waveforms(
+        channels,
+        shapes,
+        bosing.Stack(duration=500e-9).with_children(
+            bosing.Play("awg1.ch1", "hann", 0.3, 100e-9),
+            bosing.Play("awg1.ch2", "hann", 0.1, 50e-9),
+        ),
+    )
+    assert np.allclose(w["awg1.ch1"], expected["awg1.ch1"])
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, aliases={"q0.drive": "missing"})
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, aliases={"awg1.ch2": "awg1.ch1"})
+
+
+def test_waveform_stats():
+    waveforms = {"xy": np.array([[1.0, -1.0, 1.0, -1.0], [0.5, 0.5, -0.5, -0.5]])}
+    stats = bosing.waveform_stats(waveforms, bins=4)["xy"]
+    assert stats.peak == 1.0
+    assert stats.rms == pytest.approx(0.625**0.5)
+    assert stats.papr == pytest.approx(1.6)
+    assert np.array_equal(stats.histogram, [2, 2, 0, 4])
+    assert np.allclose(stats.bin_edges, [-1.0, -0.5, 0.0, 0.5, 1.0])
+    with pytest.raises(ValueError):
+        bosing.waveform_stats(waveforms, bins=0)
+
+
+def test_phase_from_first_use():
+    shapes = {"hann": bosing.Hann()}
+    play = bosing.Play("xy", "hann", 0.3, 100e-9)
+    early = bosing.Stack(duration=500e-9).with_children(play)
+    late = bosing.Stack(duration=500e-9).with_children(bosing.Barrier(duration=123e-9), play)
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000, phase_from_first_use=True)}
+    assert channels["xy"].phase_from_first_use
+    w_early = bosing.generate_waveforms(channels, shapes, early)["xy"]
+    w_late = bosing.generate_waveforms(channels, shapes, late)["xy"]
+    assert np.allclose(w_early[:, :200], w_late[:, 246:446])
+
+
+
+def test_blanking():
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Play("xy", "hann", 0.3, 100e-9, plateau=200e-9)
+    channels = {
+        "xy": bosing.Channel(
+            0, 1e9, 400, delay=10e-9, offset=[0.1, 0.1], blanking=[(50e-9, 100.5e-9)]
+        )
+    }
+    assert channels["xy"].blanking == [(50e-9, 100.5e-9)]
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+    assert np.all(w[:, 60:111] == 0)
+    assert np.all(w[:, 111:300] != 0)
+    assert np.all(w[:, 350:] == 0.1)
+    with pytest.raises(ValueError):
+        bosing.Channel(0, 1e9, 400, blanking=[(2e-9, 1e-9)])
+
+
+def test_overflow():
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Play("ro", "hann", 0.3, 100e-9, plateau=200e-9)
+    with pytest.raises(bosing.SamplingError):
+        bosing.generate_waveforms({"ro": bosing.Channel(0, 1e9, 250)}, shapes, schedule)
+    channels = {"ro": bosing.Channel(0, 1e9, 250, overflow="truncate")}
+    assert channels["ro"].overflow == bosing.Overflow.Truncate
+    with pytest.warns(RuntimeWarning, match="50 samples"):
+        truncated = bosing.generate_waveforms(channels, shapes, schedule)["ro"]
+    channels = {"ro": bosing.Channel(0, 1e9, 250, overflow=bosing.Overflow.Wrap)}
+    with pytest.warns(RuntimeWarning):
+        wrapped = bosing.generate_waveforms(channels, shapes, schedule)["ro"]
+    assert np.all(truncated[:, :50] == 0)
+    assert np.all(wrapped[:, :50] != 0)
+    assert np.array_equal(truncated[:, 50:], wrapped[:, 50:])
+    with pytest.raises(ValueError):
+        bosing.Channel(0, 1e9, 250, overflow="clip")
+
+
+def test_differential():
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Play("z", "hann", 0.3, 100e-9)
+    channels = {
+        "z": bosing.Channel(
+            0, 1e9, 200, is_real=True, offset=[0.1], differential="z_n", skew=4e-9
+        )
+    }
+    assert channels["z"].differential == "z_n"
+    w = bosing.generate_waveforms(channels, shapes, schedule)
+    assert np.allclose(w["z_n"][:, 4:] - 0.1, -(w["z"][:, :-4] - 0.1))
+    chunks = dict(bosing.generate_waveform_chunks(channels, shapes, schedule, 200))
+    assert np.array_equal(chunks[0]["z_n"], w["z_n"])
+    channels["x"] = bosing.Channel(0, 1e9, 200, is_real=True, differential="z_n")
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule)
+
+
+def test_waveform_chunks():
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(
+        bosing.Play("xy", "hann", 0.3, 100e-9, plateau=200e-9),
+        bosing.Play("z", "hann", 0.2, 50e-9),
+    )
+    channels = {
+        "xy": bosing.Channel(30e6, 1e9, 400, offset=[0.1, 0.1]),
+        "z": bosing.Channel(0, 1e9, 250, is_real=True),
+    }
+    full = bosing.generate_waveforms(channels, shapes, schedule)
+    chunks = list(
+        bosing.generate_waveform_chunks(channels, shapes, schedule, chunk_size=128)
+    )
+    assert [start for start, _ in chunks] == [0, 128, 256, 384]
+    assert set(chunks[-1][1]) == {"xy"}
+    for name, w in full.items():
+        joined = np.concatenate([c[name] for _, c in chunks if name in c], axis=1)
+        assert np.allclose(joined, w)
+    channels["xy"] = bosing.Channel(0, 1e9, 400, padding="hold")
+    with pytest.raises(ValueError):
+        bosing.generate_waveform_chunks(channels, shapes, schedule, chunk_size=128)
+
+
+
+def test_sparse_waveforms():
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Absolute(
+        bosing.Play("z", "hann", 0.3, 50e-9),
+        (500e-9, bosing.Play("z", "hann", 0.2, 50e-9)),
+    )
+    channels = {"z": bosing.Channel(0, 1e9, 1000, is_real=True)}
+    full = bosing.generate_waveforms(channels, shapes, schedule)["z"]
+
+    runs = bosing.generate_sparse_waveforms(channels, shapes, schedule, chunk_size=64)["z"]
+
+    assert len(runs) == 2
+    dense = np.zeros_like(full)
+    for start, samples in runs:
+        assert np.all(np.any(samples != 0, axis=0))
+        dense[:, start : start + samples.shape[1]] = samples
+    assert np.array_equal(dense, full)
+
+def test_absolute_from_arrays():
+    pulse = bosing.Play("xy", "hann", 0.3, 100e-9)
+    times = np.arange(1000) * 200e-9
+    absolute = bosing.Absolute.from_arrays(times, [pulse] * 1000, label="a")
+    assert len(absolute.children) == 1000
+    assert absolute.children[3].time == pytest.approx(600e-9)
+    assert absolute.children[3].element is pulse
+    assert absolute.label == "a"
+    with pytest.raises(ValueError):
+        bosing.Absolute.from_arrays(times[::-1], [pulse] * 1000)
+    with pytest.raises(ValueError):
+        bosing.Absolute.from_arrays(times, [pulse])
+    with pytest.raises(ValueError):
+        bosing.Absolute.from_arrays([np.nan], [pulse])
+
+
+def test_duration_of():
+    gates = bosing.Stack(
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+        label="gates",
+    )
+    idle = bosing.Barrier("z", duration_of=("gates", 1.5))
+    assert idle.duration_of == ("gates", 1.5)
+    assert idle.duration is None
+    schedule = bosing.Stack(gates, idle, direction="forward")
+    schedule.arrange()
+    assert schedule.child_time_ranges[1] == pytest.approx((0, 300e-9))
+    assert bosing.Barrier(duration_of="gates").duration_of == ("gates", 1.0)
+    with pytest.raises(ValueError):
+        bosing.Barrier(duration=1e-9, duration_of="gates")
+    cyclic = bosing.Stack(bosing.Barrier(duration_of="outer"), label="outer")
+    with pytest.raises(ValueError):
+        cyclic.arrange()
+
+
+def test_json_round_trip():
+    x90 = bosing.Play("xy", "hann", 0.3, 100e-9, label="x90", drag_coef=0.1)
+    grid = bosing.Grid(
+        bosing.GridEntry(x90, 0),
+        bosing.GridEntry(bosing.Barrier("xy", duration=20e-9), 1),
+        columns=["auto", "2*"],
+    )
+    schedule = bosing.Stack(
+        bosing.Repeat(grid, 3, spacing=10e-9),
+        bosing.Absolute(bosing.AbsoluteEntry(5e-9, bosing.ShiftPhase("xy", 0.25))),
+        direction="forward",
+        margin=(1e-9, 2e-9),
+    )
+
+    text = schedule.to_json()
+    loaded = bosing.Element.from_json(text)
+
+    assert isinstance(loaded, bosing.Stack)
+    assert loaded.to_json() == text
+    assert loaded.direction == bosing.Direction.Forward
+    assert loaded.margin == schedule.margin
+    repeat = loaded.children[0]
+    assert repeat.count == 3
+    play = repeat.child.children[0].element
+    assert play.label == "x90"
+    assert play.drag_coef == 0.1
+    with pytest.raises(ValueError):
+        bosing.Element.from_json('{"type": "play"}')
+
+def test_patch():
+    x90 = bosing.Play("xy", "hann", 0.3, 100e-9, label="x90")
+    other = bosing.Play("z", "hann", 0.1, 50e-9)
+    inner = bosing.Stack(x90, bosing.Barrier(duration=10e-9))
+    schedule = bosing.Stack(bosing.Repeat(inner, 2), other)
+
+    patched = bosing.patch(schedule, {"x90": {"amplitude": 0.42}})
+
+    assert isinstance(patched, bosing.Stack)
+    repeat, other2 = patched.children
+    assert other2 is other
+    assert repeat.child.children[0].amplitude == 0.42
+    assert repeat.child.children[1] is inner.children[1]
+    assert x90.amplitude == 0.3
+    with pytest.raises(ValueError):
+        bosing.patch(schedule, {"missing": {"amplitude": 0.1}})
+    with pytest.raises(ValueError):
+        bosing.patch(schedule, {"x90": {"channel_id": 1.0}})
+
+
+
+def test_bind():
+    x90 = bosing.Play("xy", "hann", bosing.Param("amp"), 100e-9, phase=bosing.Param("phi"))
+    other = bosing.Play("z", "hann", 0.1, 50e-9)
+    template = bosing.Stack(x90, other, bosing.Delay(bosing.Param("wait"), "xy"))
+
+    schedule = bosing.bind(template, {"amp": 0.42, "phi": 0.25, "wait": 10e-9})
+
+    assert x90.params == {"amplitude": "amp", "phase": "phi"}
+    assert template.param_names() == ["amp", "phi", "wait"]
+    assert schedule.param_names() == []
+    play, other2, delay = schedule.children
+    assert play.amplitude == 0.42
+    assert play.phase == 0.25
+    assert delay.delay == 10e-9
+    assert other2 is other
+    with pytest.raises(ValueError):
+        bosing.bind(template, {"amp": 0.42})
+    with pytest.raises(ValueError):
+        bosing.measure_schedule(template)
+
+def test_drag_crosstalk():
+    channels = {"xy0": bosing.Channel(0, 2e9, 1000), "xy1": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    names = ["xy0", "xy1"]
+    matrix = np.array([[1.0, 0.0], [0.2, 1.0]])
+
+    def xy1(drag_coef, crosstalk):
+        schedule = bosing.Play("xy0", "hann", 0.3, 100e-9, drag_coef=drag_coef)
+        return bosing.generate_waveforms(channels, shapes, schedule, crosstalk=crosstalk)["xy1"]
+
+    excluded = xy1(1e-9, (matrix, names, np.eye(2)))
+    assert np.allclose(excluded, xy1(0.0, (matrix, names)))
+    assert not np.allclose(excluded, xy1(1e-9, (matrix, names)))
+    with pytest.raises(ValueError):
+        xy1(0.0, (matrix, names, np.eye(3)))
+
+
+def test_kernel_crosstalk():
+    channels = {"xy0": bosing.Channel(0, 2e9, 1000), "xy1": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Play("xy0", "hann", 0.3, 100e-9)
+    names = ["xy0", "xy1"]
+    kernels = np.zeros((2, 2, 3))
+    kernels[0, 0, 0] = kernels[1, 1, 0] = 1.0
+    kernels[1, 0] = [0.0, 0.2, 0.1]
+
+    waveforms = bosing.generate_waveforms(channels, shapes, schedule, crosstalk=(kernels, names))
+    expected = np.apply_along_axis(lambda x: np.convolve(x, kernels[1, 0])[:1000], 1, waveforms["xy0"])
+    assert np.allclose(waveforms["xy1"], expected)
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, crosstalk=(kernels, names, np.eye(2)))
+
+
+def test_piecewise_crosstalk():
+    channels = {"xy0": bosing.Channel(0, 2e9, 1000), "xy1": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    play = bosing.Play("xy0", "hann", 0.3, 100e-9)
+    schedule = bosing.Absolute().with_children(play, (300e-9, play))
+    names = ["xy0", "xy1"]
+    before = np.array([[1.0, 0.0], [0.5, 1.0]])
+    after = np.array([[1.0, 0.0], [0.2, 1.0]])
+
+    crosstalk = ([(0.0, before), (200e-9, after)], names)
+    waveforms = bosing.generate_waveforms(channels, shapes, schedule, crosstalk=crosstalk)
+    xy0 = waveforms["xy0"]
+    assert np.allclose(waveforms["xy1"][:, :400], 0.5 * xy0[:, :400])
+    assert np.allclose(waveforms["xy1"][:, 400:], 0.2 * xy0[:, 400:])
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, crosstalk=([(1e-7, before), (0.0, after)], names))
+
+
+@pytest.mark.skipif(not hasattr(bosing, "Server"), reason="built without the serve feature")
+def test_server():
+    server = bosing.Server("127.0.0.1:0", chunk_size=300)
+    thread = threading.Thread(target=server.serve, args=(2,))
+    thread.start()
+    experiment = {
+        "channels": {"xy": {"base_freq": 30e6, "sample_rate": 2e9, "length": 1000}},
+        "shapes": {"hann": {"type": "hann"}},
+        "schedule": {"type": "play", "channel_id": "xy", "shape_id": "hann", "amplitude": 0.3, "width": 100e-9},
+    }
+    url = f"http://{server.address}/generate"
+    with urllib.request.urlopen(url, data=json.dumps(experiment).encode()) as response:
+        chunks = [json.loads(line) for line in response]
+    with pytest.raises(urllib.error.HTTPError):
+        urllib.request.urlopen(url, data=b"{}")
+    thread.join()
+
+    assert [c["offset"] for c in chunks] == [0, 300, 600, 900]
+    w = np.concatenate([c["data"] for c in chunks], axis=1)
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    expected = bosing.generate_waveforms(channels, {"hann": bosing.Hann()}, bosing.Play("xy", "hann", 0.3, 100e-9))
+    assert np.allclose(w, expected["xy"])
+
+
+def test_logging(caplog):
+    channels = {"xy": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Play("xy", "hann", 0.3, 100e-9)
+    try:
+        bosing.set_logging(python=True, level="info")
+        with caplog.at_level("INFO", logger="bosing"):
+            bosing.generate_waveforms(channels, shapes, schedule)
+    finally:
+        bosing.set_logging()
+    messages = [r.getMessage() for r in caplog.records if r.name == "bosing"]
+    assert any(m.startswith("generate_waveforms:sample{channel=xy}: close") for m in messages)
+    with pytest.raises(ValueError):
+        bosing.set_logging(python=True, level="verbose")
+
+
+def test_element_stats():
+    play = bosing.Play("xy", "hann", 0.3, 100e-9)
+    schedule = bosing.Stack(bosing.Repeat(play, 10), play, bosing.Barrier(phantom=True))
+    stats = schedule.stats()
+    assert stats.node_count == 5
+    assert stats.depth == 3
+    assert stats.variant_counts == {"Stack": 1, "Repeat": 1, "Play": 2, "Barrier": 1}
+    assert stats.pulse_count == 11
+
+
+def test_build_info():
+    info = bosing.build_info()
+    assert info["kernels"] in ("avx2", "neon", "scalar")
+    assert isinstance(info["cpu_features"], list)
+
+
+def test_envelope_cache(tmp_path):
+    channels = {"xy": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"interp": bosing.Interp([-0.5, -0.5, 0.5, 0.5], [0.0, 1.0], 1)}
+    # Use an unusual width to avoid hitting the in-memory cache
+    schedule = bosing.Stack(duration=500e-9).with_children(bosing.Play("xy", "interp", 0.3, 123.4e-9))
+    try:
+        bosing.set_envelope_cache(tmp_path)
+        bosing.generate_waveforms(channels, shapes, schedule)
+        assert any(tmp_path.glob("*.env"))
+        bosing.clear_envelope_cache()
+        assert not any(tmp_path.glob("*.env"))
+    finally:
+        bosing.set_envelope_cache(None)
+
+
+def test_spline():
+    spline = bosing.Spline([-0.5, -0.2, 0.0, 0.2, 0.5], [0.0, 0.6, 1.0, 0.6, 0.0])
+    assert spline.x == [-0.5, -0.2, 0.0, 0.2, 0.5]
+    channels = {"xy": bosing.Channel(0, 1e9, 200)}
+    shapes = {"spline": spline}
+    schedule = bosing.Play("xy", "spline", 0.5, 100e-9)
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+    assert w[0, 50] == pytest.approx(0.5)
+    assert w[0, 30] == pytest.approx(0.3)
+    assert np.all(w[:, 101:] == 0)
+    with pytest.raises(ValueError):
+        bosing.Spline([0.0, 0.0], [1.0, 1.0])
+
+
+def test_sampled_shape():
+    measured = np.sin(np.pi * np.arange(50) / 50) ** 2
+    channels = {"xy": bosing.Channel(0, 2e9, 200, align_level=0, is_real=True)}
+    shapes = {
+        "native": bosing.SampledShape(measured, 1e9),
+        "cubic": bosing.SampledShape(measured, 1e9, interpolation="cubic"),
+    }
+    assert shapes["native"].duration == pytest.approx(50e-9)
+    schedule = bosing.Stack(
+        bosing.Play("xy", "native", 1.0, 50e-9),
+        bosing.Play("xy", "cubic", 1.0, 50e-9),
+        direction="forward",
+    )
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"][0]
+    np.testing.assert_allclose(w[0:100:2], measured, atol=1e-12)
+    np.testing.assert_allclose(w[1:99:2], (measured[:-1] + measured[1:]) / 2, atol=1e-12)
+    np.testing.assert_allclose(w[100:200:2], measured, atol=1e-12)
+    with pytest.raises(ValueError):
+        bosing.SampledShape([], 1e9)
+    with pytest.raises(ValueError):
+        bosing.SampledShape([1.0], 1e9, interpolation="nearest")
+
+def test_pulse_stats():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.1, 50e-9),
+        bosing.Play("xy", "hann", 0.1, 50e-9),
+        bosing.Play("xy", "hann", 0.1, 50e-9, phantom=True),
+        bosing.Play("xy", None, 0.1, 50e-9),
+    )
+    stats = bosing.pulse_stats(channels, shapes, schedule)["xy"]
+    assert stats.pulses == 3
+    assert stats.envelopes[0][0] == "hann"
+    assert stats.envelopes[0][3] == 2
+    assert stats.envelopes[1][0] is None
+
+
+def test_diff():
+    shared = bosing.Play("xy", "hann", 0.5, 50e-9, label="x")
+    old = bosing.Stack(
+        shared,
+        bosing.Barrier("xy", "ro"),
+        bosing.Play("ro", None, 0.1, 400e-9, label="readout"),
+    )
+    new = bosing.Stack(
+        shared,
+        bosing.Play("xy", "hann", 0.2, 50e-9, label="y"),
+        bosing.Barrier("ro", "xy"),
+        bosing.Play("ro", None, 0.2, 400e-9, label="readout"),
+    )
+    assert bosing.diff(old, old) == []
+    added, changed = bosing.diff(old, new)
+    assert added.path == "Stack > 1: Play 'y'"
+    assert added.field == "element"
+    assert added.old is None
+    assert added.new["amplitude"] == 0.2
+    assert changed.path == "Stack > 3: Play 'readout'"
+    assert changed.field == "amplitude"
+    assert (changed.old, changed.new) == (0.1, 0.2)
+
+
+def test_explain():
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.5, 50e-9, label="x"),
+        bosing.Barrier(),
+        bosing.Play("ro", None, 0.1, 400e-9),
+    )
+    out = io.StringIO()
+    bosing.explain(schedule, file=out)
+    lines = out.getvalue().splitlines()
+    assert lines[0] == "Stack 0 s .. 500 ns (500 ns) [ro, xy]"
+    assert lines[1].startswith("  Play 'x' ")
+    assert lines[2].endswith("[all]")
+    out = io.StringIO()
+    bosing.explain(schedule, ["xy"], file=out)
+    assert len(out.getvalue().splitlines()) == 3
+
+
+def test_phase_unit():
+    assert bosing.get_phase_unit() == "cycles"
+    bosing.set_phase_unit("radians")
+    try:
+        assert bosing.get_phase_unit() == "radians"
+        shift = bosing.ShiftPhase("xy", math.pi / 2)
+        assert shift.phase == pytest.approx(math.pi / 2)
+        assert json.loads(shift.to_json())["phase"] == pytest.approx(0.25)
+        template = bosing.Play("xy", None, 0.1, 10e-9, phase=bosing.Param("p"))
+        bound = bosing.bind(template, {"p": math.pi})
+        assert json.loads(bound.to_json())["phase"] == pytest.approx(0.5)
+    finally:
+        bosing.set_phase_unit("cycles")
+    assert shift.phase == pytest.approx(0.25)
+    with pytest.raises(ValueError, match="radians"):
+        bosing.set_phase_unit("degrees")
+
+
+def test_element_timings():
+    channels = {
+        "xy": bosing.Channel(0, 2e9, 1000, delay=10e-9, trigger_latency=100e-9),
+        "ro": bosing.Channel(0, 2e9, 1000, trigger_latency=50e-9),
+    }
+    schedule = bosing.Stack().with_children(
+        bosing.Play("xy", "hann", 0.5, 50e-9, label="x"),
+        bosing.Barrier(label="sync"),
+        bosing.Play("ro", None, 0.1, 400e-9, label="ro"),
+    )
+    timings = bosing.element_timings(channels, schedule)
+    assert [(t.label, t.channel) for t in timings] == [
+        ("x", "xy"),
+        ("sync", "ro"),
+        ("ro", "ro"),
+        ("sync", "xy"),
+    ]
+    ro = timings[2]
+    assert ro.schedule_time == pytest.approx(50e-9)
+    assert ro.hardware_time == pytest.approx(100e-9)
+    assert ro.duration == pytest.approx(400e-9)
+    assert timings[0].hardware_time == pytest.approx(110e-9)
+    timings = bosing.element_timings(channels, schedule, labels=["x"])
+    assert len(timings) == 1
+    with pytest.raises(ValueError, match="unknown channel"):
+        bosing.element_timings({"xy": channels["xy"]}, schedule)
+
+
+def test_fragment_registry():
+    gates = bosing.FragmentRegistry()
+    gates.register("x90", lambda qubit: bosing.Play(qubit, "hann", 0.5, 30e-9))
+    gates.register(
+        "x90", lambda qubit, amp=0.4: bosing.Play(qubit, "hann", amp, 30e-9), version=2
+    )
+    assert gates.names() == ["x90"]
+    assert gates.versions("x90") == [1, 2]
+    old = gates.instantiate("x90", {"qubit": "q0"}, version=1)
+    new = gates.instantiate("x90", {"qubit": "q1", "amp": 0.3})
+    assert new.amplitude == 0.3
+    schedule = bosing.Stack(old, new, old)
+    (first, second) = gates.instances(schedule)
+    assert (first.name, first.version, first.arguments) == ("x90", 1, {"qubit": "q0"})
+    assert first.paths == ["Stack > 0: Play", "Stack > 2: Play"]
+    assert second.version == 2
+    _, instances = bosing.FragmentRegistry.from_json(gates.to_json(schedule))
+    assert [i.arguments for i in instances] == [{"qubit": "q0"}, {"qubit": "q1", "amp": 0.3}]
+    with pytest.raises(ValueError):
+        gates.instantiate("x90", {"qubit": "q0", "amp": 0.3}, version=1)
+    with pytest.raises(KeyError):
+        gates.instantiate("y90", {"qubit": "q0"})
+    with pytest.raises(ValueError):
+        gates.register("x90", lambda qubit: None)
+
+def test_find_clipped_pulses():
+    channels = {
+        "xy": bosing.Channel(0, 2e9, 1000),
+        "ro": bosing.Channel(0, 2e9, 1000, delay=-20e-9),
+    }
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(direction="forward").with_children(
+        bosing.Play("xy", "hann", 0.1, 50e-9),
+        bosing.Stack(
+            bosing.Play("ro", None, 0.1, 400e-9, label="readout"),
+            bosing.Play("xy", "hann", 0.1, 50e-9),
+        ),
+    )
+    assert bosing.find_clipped_pulses(channels, shapes, schedule) == []
+    channels["ro"] = bosing.Channel(0, 2e9, 800, delay=-20e-9)
+    (clipped,) = bosing.find_clipped_pulses(channels, shapes, schedule)
+    assert clipped.path == "Stack > 1: Stack > 0: Play 'readout'"
+    assert clipped.label == "readout"
+    assert clipped.channel == "ro"
+    assert clipped.start == pytest.approx(50e-9)
+    assert clipped.after == pytest.approx(30e-9)
+    assert clipped.before == 0
+
+
+def test_chirp():
+    channels = {"xy": bosing.Channel(0, 1e9, 100)}
+    chirp_rate = 1e15
+    play = bosing.Play("xy", None, 0.5, 40e-9, chirp_rate=chirp_rate)
+    schedule = bosing.Absolute().with_children((10e-9, play))
+    waveform = bosing.generate_waveforms(channels, {}, schedule)["xy"]
+
+    t = np.arange(100) * 1e-9
+    expected = 0.5 * np.exp(1j * np.pi * chirp_rate * (t - 30e-9) ** 2)
+    expected[(t < 10e-9 - 1e-12) | (t > 50e-9 - 1e-12)] = 0
+    assert play.chirp_rate == chirp_rate
+    assert np.allclose(waveform[0] + 1j * waveform[1], expected)
+    table = bosing.generate_pulse_table(channels, {}, schedule)
+    assert table[0].chirp_rate == chirp_rate
+
+def test_compile_frames():
+    channels = {"xy": bosing.Channel(100e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(direction="forward").with_children(
+        bosing.Play("xy", "hann", 0.1, 50e-9),
+        bosing.ShiftPhase("xy", 0.25),
+        bosing.Play("xy", "hann", 0.2, 50e-9, phase=0.1),
+    )
+    program = bosing.compile_frames(channels, shapes, schedule)["xy"]
+    assert program.envelopes == [("hann", pytest.approx(50e-9), 0.0)]
+    assert len(program.envelope_samples[0]) == 100
+    kinds = [e[0] for e in program.events]
+    assert kinds == ["pulse", "shift_phase", "pulse"]
+    assert program.events[1][1:] == pytest.approx((50e-9, 0.25))
+    assert program.events[2][2] == 0
+    assert program.events[2][3] == pytest.approx(0.2)
+    assert program.events[2][6] == pytest.approx(0.1)
+
+
+def test_compile_timeline():
+    channels = {"xy": bosing.Channel(100e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(direction="forward").with_children(
+        bosing.Play("xy", "hann", 0.1, 50e-9),
+        bosing.ShiftPhase("xy", 0.25),
+        bosing.Play("xy", "hann", 0.2, 50e-9),
+    )
+    timeline = bosing.compile_timeline(channels, shapes, schedule, envelope_rate=1e9)["xy"]
+    assert timeline.envelopes == [("hann", pytest.approx(50e-9), 0.0)]
+    assert len(timeline.envelope_samples[0]) == 50
+    assert [p[0] for p in timeline.pulses] == pytest.approx([0.0, 50e-9])
+    # 100 MHz carrier advances 5 cycles in 50 ns, plus the phase shift.
+    assert timeline.pulses[1][2] == pytest.approx(0.2j, abs=1e-12)
+    assert timeline.pulses[1][4] == pytest.approx(100e6)
+    assert bosing.compile_timeline(channels, shapes, schedule)["xy"].envelope_samples is None
+
+
+def test_generate_pulse_table():
+    channels = {"xy": bosing.Channel(100e6, 2e9, 1000), "z": bosing.Channel(0, 2e9, 1000, is_real=True)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(direction="forward").with_children(
+        bosing.Play("xy", "hann", 0.1, 50e-9, label="x"),
+        bosing.ShiftPhase("xy", 0.25),
+        bosing.Play("xy", "hann", 0.2, 40e-9, plateau=10e-9),
+        bosing.Play("z", None, 0.3, 20e-9),
+    )
+    table = bosing.generate_pulse_table(channels, shapes, schedule, amp_scales={"x": 0.5})
+    assert [(p.channel, p.shape_id) for p in table] == [("xy", "hann"), ("z", None), ("xy", "hann")]
+    assert table[0].amplitude == pytest.approx(0.05)
+    assert table[2].time == pytest.approx(50e-9)
+    assert table[2].plateau == pytest.approx(10e-9)
+    assert table[2].freq == pytest.approx(100e6)
+    assert table[2].phase == pytest.approx(0.25)
+
+
+def test_multiple_roots():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000), "z": bosing.Channel(0, 2e9, 1000, is_real=True)}
+    shapes = {"hann": bosing.Hann()}
+    background = bosing.Play("z", None, 0.1, 400e-9)
+    experiment = bosing.Play("xy", "hann", 0.3, 100e-9)
+    result = bosing.generate_waveforms(channels, shapes, [background, (100e-9, experiment)])
+    z = bosing.generate_waveforms(channels, shapes, background)["z"]
+    xy = bosing.generate_waveforms(channels, shapes, bosing.Absolute((100e-9, experiment)))["xy"]
+    assert np.allclose(result["z"], z)
+    assert np.allclose(result["xy"], xy)
+
+
+def test_generate_sweep():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9, label="drive"),
+    )
+    amps = np.linspace(0, 1, 5)
+    phases = np.linspace(0, 0.5, 5)
+    result = bosing.generate_sweep(
+        channels, shapes, schedule, amp_scales={"drive": amps}, phase_shifts={"drive": phases}
+    )["xy"]
+    assert result.shape == (5, 2, 1000)
+    for i, (a, p) in enumerate(zip(amps, phases)):
+        play = bosing.Play("xy", "hann", 0.3 * a, 100e-9, phase=p)
+        expected = bosing.generate_waveforms(channels, shapes, bosing.Stack(play, duration=500e-9))["xy"]
+        assert np.allclose(result[i], expected)
+    with pytest.raises(ValueError):
+        bosing.generate_sweep(channels, shapes, schedule, amp_scales={"readout": amps})
+
+
+def test_generate_waveforms_into():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000), "z": bosing.Channel(0, 2e9, 500, is_real=True)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+        bosing.Play("z", "hann", 0.2, 100e-9),
+    )
+    expected = bosing.generate_waveforms(channels, shapes, schedule)
+    buffers = {"xy": np.full((2, 1000), np.nan), "z": np.full((1, 500), np.nan)}
+    bosing.generate_waveforms_into(buffers, channels, shapes, schedule)
+    for name, w in expected.items():
+        assert np.array_equal(buffers[name], w)
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms_into({"xy": buffers["xy"]}, channels, shapes, schedule)
+    with pytest.raises(ValueError):
+        wrong = {"xy": np.zeros((2, 999)), "z": buffers["z"]}
+        bosing.generate_waveforms_into(wrong, channels, shapes, schedule)
+
+
+def test_generate_waveforms_batch():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    template = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", bosing.Param("amp"), 100e-9, phase=bosing.Param("phase")),
+    )
+    param_sets = [{"amp": a, "phase": p} for a, p in zip([0.1, 0.2, 0.3], [0.0, 0.25, 0.5])]
+    result = bosing.generate_waveforms_batch(channels, shapes, template, param_sets)["xy"]
+    assert result.shape == (3, 2, 1000)
+    for i, params in enumerate(param_sets):
+        schedule = bosing.bind(template, params)
+        expected = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+        assert np.allclose(result[i], expected)
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms_batch(channels, shapes, template, [{"amp": 0.1}])
+
+def test_regenerator():
+    channels = {
+        "xy0": bosing.Channel(30e6, 2e9, 1000),
+        "xy1": bosing.Channel(40e6, 2e9, 1000),
+    }
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy0", "hann", 0.3, 100e-9, label="x"),
+        bosing.Play("xy1", "hann", 0.3, 100e-9, label="y"),
+    )
+    regenerator = bosing.Regenerator(channels, shapes, schedule)
+    first = regenerator.generate()
+    regenerator.update("x", amp_scale=0.5, phase_shift=0.25)
+    second = regenerator.generate()
+    shifted = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy0", "hann", 0.15, 100e-9, phase=0.25),
+        bosing.Play("xy1", "hann", 0.3, 100e-9),
+    )
+    expected = bosing.generate_waveforms(channels, shapes, shifted)
+    assert second["xy1"] is first["xy1"]
+    assert np.allclose(second["xy0"], expected["xy0"])
+    with pytest.raises(ValueError):
+        regenerator.update("x", amp_scale=float("nan"))
+
+def test_envelope_utils():
+    area = bosing.envelope_area(None, 100e-9, 1e9)
+    assert area == pytest.approx(100e-9)
+    assert bosing.envelope_fwhm(None, 100e-9, 1e9) == pytest.approx(100e-9)
+    area = bosing.envelope_area(bosing.Hann(), 100e-9, 1e9, plateau=20e-9)
+    assert area.real == pytest.approx(70e-9)
+    assert bosing.envelope_fwhm(bosing.Hann(), 100e-9, 1e9) == pytest.approx(50e-9, rel=1e-2)
+
+
+def test_shift_waveform():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Play("xy", "hann", 0.3, 100e-9)
+    waveform = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+    shifted = bosing.shift_waveform(waveform, 10)
+    assert np.array_equal(shifted[:, 10:], waveform[:, :-10])
+    half = bosing.shift_waveform(bosing.shift_waveform(waveform, 0.5), 0.5)
+    assert np.allclose(half[:, 1:], waveform[:, :-1], atol=1e-3)
+    with pytest.raises(ValueError):
+        bosing.shift_waveform(waveform, float("inf"))
+
+
+def test_quantize_waveforms():
+    waveforms = {"xy": np.array([[0.0, 0.25, -0.5]]), "z": np.array([[1.0, -2.0]])}
+    with pytest.warns(RuntimeWarning, match="1 samples of channel 'z'"):
+        result = bosing.quantize_waveforms(waveforms, {"xy": 0.5, "z": 1.0}, allow_clip=True)
+    codes, scale = result["xy"]
+    assert codes.dtype == np.int16
+    assert np.array_equal(codes, [[0, 16384, -32767]])
+    assert scale == pytest.approx(0.5 / 32767)
+    assert np.array_equal(result["z"][0], [[32767, -32767]])
+    with pytest.raises(ValueError):
+        bosing.quantize_waveforms(waveforms, 1.0)
+    dithered = bosing.quantize_waveforms(waveforms, 2.0, dither=True, seed=1)
+    again = bosing.quantize_waveforms(waveforms, 2.0, dither=True, seed=1)
+    assert np.array_equal(dithered["xy"][0], again["xy"][0])
+
+
+@pytest.mark.parametrize("wrap_phase", [True, False])
+def test_shift_phase(wrap_phase):
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000, wrap_phase=wrap_phase)}
+    shapes = {"hann": bosing.Hann()}
+    shifted = bosing.Stack(duration=500e-9).with_children(
+        bosing.ShiftPhase("xy", 1.25),
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+    )
+    direct = bosing.Stack(duration=500e-9).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9, phase=0.25),
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, shifted)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, direct)["xy"]
+    assert np.allclose(w1, w2)
+
+
+def test_export_html(tmp_path):
+    channels = {
+        "xy": bosing.Channel(30e6, 2e9, 10000),
+        "z": bosing.Channel(0, 2e9, 10000, is_real=True),
+    }
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=5e-6).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9, label="drive"),
+        bosing.Play("z", "hann", 0.1, 200e-9),
+    )
+    path = tmp_path / "preview.html"
+    bosing.export_html(path, channels, shapes, schedule, title="test", max_points=100)
+    html = path.read_text()
+    assert "<title>test</title>" in html
+    assert '"label":"drive"' in html
+    assert '"trace":"xy.Q"' in html
+    assert '"trace":"z"' in html
+
+
+def test_grid_barrier_sync_line(tmp_path):
+    channels = {"q0": bosing.Channel(0, 2e9, 1000), "q1": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    grid = bosing.Grid(columns=["auto", "auto", "auto"]).with_children(
+        (bosing.Play("q0", "hann", 0.3, 100e-9), 0),
+        (bosing.Barrier(duration=20e-9, label="sync"), 1),
+        (bosing.Play("q1", "hann", 0.3, 100e-9), 2),
+    )
+    grid.arrange()
+    assert grid.child_time_ranges[1] == pytest.approx((100e-9, 120e-9))
+    path = tmp_path / "preview.html"
+    bosing.export_html(path, channels, shapes, grid)
+    html = path.read_text()
+    assert '{"label":"sync","t":1e-7}' in html
+    assert html.count('"label":"sync"') == 2
+
+
+def test_phantom_ignored():
+    note = bosing.Play("q1", "hann", 0.3, 100e-9, phantom="ignore")
+    assert note.phantom and note.phantom_ignored
+    schedule = bosing.Stack(
+        bosing.Play("q0", "hann", 0.3, 40e-9),
+        note,
+        bosing.Play("q1", "hann", 0.3, 40e-9),
+        direction="forward",
+    )
+    schedule.arrange()
+    assert schedule.child_time_ranges[2][0] == pytest.approx(0.0)
+    assert bosing.Element.from_json(note.to_json()).phantom_ignored
+    kept = bosing.Play("q1", "hann", 0.3, 100e-9, phantom="layout")
+    assert kept.phantom and not kept.phantom_ignored
+    with pytest.raises(ValueError):
+        bosing.Play("q1", "hann", 0.3, 100e-9, phantom="hidden")
+
+
+def test_grid_rows():
+    x = lambda ch: bosing.Play(ch, "hann", 0.3, 40e-9)
+    grid = bosing.Grid(
+        bosing.GridEntry(x("q0"), 0, row=0),
+        bosing.GridEntry(x("q1"), 0, row=1),
+        bosing.GridEntry(bosing.Play("c01", None, 0.1, 60e-9), 1, row=0, row_span=2),
+        columns=["auto", "auto"],
+        rows=[["q0"], ["q1", "c01"], ["q2"]],
+    )
+    assert grid.rows == [["q0"], ["q1", "c01"], ["q2"]]
+    assert grid.children[2].row_span == 2
+    schedule = bosing.Stack(grid, x("q2"))
+    schedule.arrange()
+    assert schedule.child_time_ranges[1] == pytest.approx((100e-9, 140e-9))
+    assert bosing.Element.from_json(grid.to_json()).rows == grid.rows
+    with pytest.raises(ValueError):
+        bosing.Grid(bosing.GridEntry(x("q1"), row=0), rows=[["q0"], ["q1"]])
+    with pytest.raises(ValueError):
+        bosing.GridEntry(x("q0"), row_span=0)
+
+
+def test_integration_weights():
+    channels = {"ro": bosing.Channel(50e6, 1e9, 2000), "xy": bosing.Channel(30e6, 1e9, 2000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=2e-6).with_children(
+        bosing.Play("xy", "hann", 0.3, 100e-9),
+        bosing.ShiftPhase("ro", 0.25),
+        bosing.Play("ro", "hann", 0.1, 100e-9, plateau=400e-9, label="readout"),
+        bosing.Barrier(duration=100e-9),
+    )
+    start, weights = bosing.integration_weights(channels, shapes, schedule, "readout", 1e9)
+    assert start == pytest.approx(1.4e-6)
+    assert weights.shape == (500,)
+    ro = bosing.generate_waveforms(channels, shapes, schedule)["ro"]
+    i0 = round(start * 1e9)
+    pulse = ro[0, i0 : i0 + 500] + 1j * ro[1, i0 : i0 + 500]
+    assert np.allclose(weights, np.conj(pulse))
+
+    with pytest.raises(ValueError):
+        bosing.integration_weights(channels, shapes, schedule, "missing", 1e9)
+
+
+def test_load_experiment(tmp_path):
+    path = tmp_path / "experiment.yaml"
+    path.write_text(
+        """
+definitions:
+  pi: &pi {type: play, channel_id: xy, shape_id: hann, amplitude: 0.3, width: 100.0e-9}
+channels:
+  xy: {base_freq: 30.0e+6, sample_rate: 2.0e+9, length: 1000}
+shapes:
+  hann: {type: hann}
+schedule:
+  type: stack
+  duration: 500.0e-9
+  children:
+    - *pi
+    - <<: *pi
+      amplitude: 0.1
+"""
+    )
+    channels, shapes, schedule = bosing.load_experiment(path)
+    play = bosing.Play("xy", "hann", 0.3, 100e-9)
+    expected = bosing.Stack(duration=500e-9).with_children(play, bosing.Play("xy", "hann", 0.1, 100e-9))
+    w1 = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, expected)["xy"]
+    assert np.allclose(w1, w2)
+
+    path.write_text("schedule: {type: barrier, duraton: 1.0e-8}")
+    with pytest.raises(ValueError, match="duraton"):
+        bosing.load_experiment(path)
+
+
+def test_scale():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    block = bosing.Stack(
+        bosing.Play("xy", "hann", 0.3, 50e-9, plateau=20e-9),
+        bosing.Play("xy", "hann", 0.2, 30e-9, margin=10e-9),
+    )
+    scaled = bosing.Stack(bosing.Scale(block, 2.0), duration=500e-9)
+    expected = bosing.Stack(
+        bosing.Stack(
+            bosing.Play("xy", "hann", 0.3, 100e-9, plateau=40e-9),
+            bosing.Play("xy", "hann", 0.2, 60e-9, margin=20e-9),
+        ),
+        duration=500e-9,
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, scaled)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, expected)["xy"]
+    assert np.allclose(w1, w2)
+    assert bosing.Scale(block, 2.0).factor == 2.0
+
+
+def test_layout_check():
+    channels = {"xy": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Grid(
+        bosing.Scale(bosing.Play("xy", "hann", 0.3, 100e-9), 2.0),
+        bosing.Play("xy", "hann", 0.3, 100e-9, flexible=True),
+        columns=["auto", "*"],
+        duration=500e-9,
+    )
+    try:
+        bosing.set_layout_check(True)
+        bosing.generate_waveforms(channels, shapes, schedule)
+    finally:
+        bosing.set_layout_check(False)
+
+
+def test_stack_from_plays():
+    channels = {"xy0": bosing.Channel(0, 2e9, 1000), "xy1": bosing.Channel(0, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    n = 10
+    channel_ids = ["xy0", "xy1"] * (n // 2)
+    amplitudes = np.linspace(0.1, 0.5, n)
+    widths = np.full(n, 50e-9)
+    phases = np.linspace(0, 1, n)
+    bulk = bosing.Stack.from_plays(channel_ids, ["hann"] * n, amplitudes, widths, phases=phases)
+    plays = [
+        bosing.Play(c, "hann", a, w, phase=p)
+        for c, a, w, p in zip(channel_ids, amplitudes, widths, phases)
+    ]
+    expected = bosing.Stack(*plays)
+
+    assert len(bulk.children) == n
+    assert isinstance(bulk.children[0], bosing.Play)
+    assert bulk.children[1].channel_id == "xy1"
+    w1 = bosing.generate_waveforms(channels, shapes, bulk)
+    w2 = bosing.generate_waveforms(channels, shapes, expected)
+    for c in channels:
+        assert np.allclose(w1[c], w2[c])
+    with pytest.raises(ValueError):
+        bosing.Stack.from_plays(channel_ids, ["hann"], amplitudes, widths)
+
+
+def test_stack_auto_barrier():
+    x0 = bosing.Play("xy0", "hann", 0.5, 10e-9)
+    x1 = bosing.Play("xy1", "hann", 0.5, 20e-9)
+    stack = bosing.Stack(x0, x1, x0, auto_barrier=True)
+    plain = bosing.Stack(x0, x1, x0)
+    explicit = bosing.Stack(x0, bosing.Barrier(), x1, bosing.Barrier(), x0)
+
+    assert stack.auto_barrier
+    assert not plain.auto_barrier
+    assert stack.with_children(x0, x1).auto_barrier
+    assert bosing.measure_schedule(stack)[0] == pytest.approx(40e-9)
+    assert bosing.measure_schedule(plain)[0] == pytest.approx(20e-9)
+    assert bosing.measure_schedule(explicit)[0] == pytest.approx(40e-9)
+
+
+def test_stack_alignment_and_spacing():
+    x0 = bosing.Play("xy0", "hann", 0.5, 10e-9, alignment="start")
+    x1 = bosing.Play("xy1", "hann", 0.5, 30e-9, alignment="start")
+    centered = bosing.Play("xy0", "hann", 0.5, 10e-9, alignment="center")
+    stack = bosing.Stack(
+        x0, x1, centered, direction="forward", spacing=5e-9, align_children=True
+    )
+
+    assert stack.spacing == pytest.approx(5e-9)
+    assert stack.align_children
+    assert not bosing.Stack(x0).align_children
+    assert stack.with_children(x0).spacing == pytest.approx(5e-9)
+    stack.arrange()
+    assert stack.child_time_ranges[2] == pytest.approx((17.5e-9, 10e-9))
+
+def test_group_delay_compensation():
+    fir = np.zeros(21)
+    fir[10] = 1.0
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(duration=200e-9).with_children(
+        bosing.Play("xy", "hann", 0.5, 50e-9),
+    )
+    plain = bosing.Channel(0, 1e9, 1000)
+    compensated = bosing.Channel(0, 1e9, 1000, fir=fir, compensate_group_delay=True)
+
+    assert plain.group_delay == 0.0
+    assert compensated.group_delay == pytest.approx(10e-9)
+    w1 = bosing.generate_waveforms({"xy": plain}, shapes, schedule)
+    w2 = bosing.generate_waveforms({"xy": compensated}, shapes, schedule)
+    assert np.allclose(w1["xy"], w2["xy"])
+    with pytest.raises(ValueError):
+        bosing.Channel(0, 1e9, 1000, fir=[1.0, -1.0], compensate_group_delay=True)
+
+
+def test_exp_corrections():
+    amplitude, tau = -0.05, 30e-9
+    r = math.exp(-1 / (tau * 1e9))
+    gain = 1 + amplitude
+    sos = [[1 / gain, -r / gain, 0, 1, -(r + amplitude) / gain, 0]]
+    schedule = bosing.Play("z", None, 0.5, 0, plateau=100e-9)
+    corrected = bosing.Channel(0, 1e9, 300, is_real=True, exp_corrections=[(amplitude, tau)])
+    manual = bosing.Channel(0, 1e9, 300, is_real=True, iir=sos)
+
+    assert corrected.exp_corrections == [(amplitude, tau)]
+    assert corrected.group_delay == pytest.approx(manual.group_delay)
+    w1 = bosing.generate_waveforms({"z": corrected}, {}, schedule)
+    w2 = bosing.generate_waveforms({"z": manual}, {}, schedule)
+    assert np.allclose(w1["z"], w2["z"])
+    with pytest.raises(ValueError, match="unstable"):
+        bosing.Channel(0, 1e9, 300, exp_corrections=[(-1.2, tau)])
+
+
+def test_compression_predistortion():
+    schedule = bosing.Play("xy", "hann", 0.8, 100e-9)
+    shapes = {"hann": bosing.Hann()}
+    plain = bosing.Channel(0, 1e9, 200)
+    compressed = bosing.Channel(0, 1e9, 200, compression_poly=[1.0, 0.0, -0.2])
+
+    assert compressed.compression_poly == [1.0, 0.0, -0.2]
+    w1 = bosing.generate_waveforms({"xy": plain}, shapes, schedule)["xy"]
+    w2 = bosing.generate_waveforms({"xy": compressed}, shapes, schedule)["xy"]
+    amplitude = np.hypot(*w2)
+    assert np.allclose(amplitude - 0.2 * amplitude**3, np.hypot(*w1), atol=1e-6)
+    with pytest.raises(ValueError):
+        bosing.Channel(0, 1e9, 200, compression_table=[(0.5, 0.5), (1.0, 0.4)])
+    with pytest.raises(ValueError):
+        bosing.Channel(0, 1e9, 200, compression_table=[(1.0, 0.8)], compression_poly=[1.0])
+
+
+def test_clip_limit():
+    channels = {"xy": bosing.Channel(0, 1e9, 200), "z": bosing.Channel(0, 1e9, 200, is_real=True)}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack().with_children(
+        bosing.Play("xy", "hann", 0.8, 100e-9, label="x180"),
+        bosing.Play("z", "hann", 0.5, 100e-9, label="flux"),
+    )
+
+    waveforms = bosing.generate_waveforms(channels, shapes, schedule, clip_limit=0.9)
+    assert set(waveforms) == {"xy", "z"}
+    with pytest.raises(bosing.ClippingError) as info:
+        bosing.generate_waveforms(channels, shapes, schedule, clip_limit=0.6)
+    err = info.value
+    assert err.code == "clipping"
+    assert err.channel == "xy"
+    [clipping] = err.details["clippings"]
+    assert clipping.labels == ["x180"]
+    assert 0 < clipping.start < clipping.end < 200
+    assert clipping.peak == pytest.approx(np.abs(waveforms["xy"][0]).max())
+
+
+def test_marker():
+    channels = {"ro": bosing.Channel(0, 1e9, 200)}
+    digital_channels = {"gate": bosing.DigitalChannel(1e9, 200, delay=10e-9)}
+    schedule = bosing.Stack(direction="forward").with_children(
+        bosing.Barrier(duration=20e-9),
+        bosing.Overlay(
+            bosing.Play("ro", None, 0.2, 0, plateau=100e-9),
+            bosing.Marker("gate", alignment="stretch"),
+        ),
+    )
+
+    waveforms = bosing.generate_waveforms(channels, {}, schedule, digital_channels=digital_channels)
+    gate = waveforms["gate"]
+    assert gate.dtype == np.uint8
+    assert gate.shape == (200,)
+    assert np.array_equal(np.flatnonzero(gate), np.arange(30, 130))
+    assert np.array_equal(np.flatnonzero(waveforms["ro"][0]), np.arange(20, 120))
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, {}, schedule)
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, {}, schedule, digital_channels={"ro": bosing.DigitalChannel(1e9, 200)})
+
+
+def test_marker_alias():
+    channels = {"ro": bosing.Channel(0, 1e9, 200)}
+    digital_channels = {"gate": bosing.DigitalChannel(1e9, 200)}
+    schedule = bosing.Stack(direction="forward").with_children(
+        bosing.Barrier(duration=20e-9),
+        bosing.Overlay(
+            bosing.Play("q0.ro", None, 0.2, 0, plateau=100e-9),
+            bosing.Marker("q0.gate", alignment="stretch"),
+        ),
+    )
+
+    waveforms = bosing.generate_waveforms(
+        channels,
+        {},
+        schedule,
+        aliases={"q0.ro": "ro", "q0.gate": "gate"},
+        digital_channels=digital_channels,
+    )
+    assert np.array_equal(np.flatnonzero(waveforms["gate"]), np.arange(20, 120))
+    assert np.array_equal(np.flatnonzero(waveforms["ro"][0]), np.arange(20, 120))
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(
+            channels,
+            {},
+            schedule,
+            aliases={"q0.ro": "ro", "gate": "ro"},
+            digital_channels=digital_channels,
+        )
+
+
+def test_asymmetric_play():
+    channels = {"xy": bosing.Channel(0, 1e9, 1000)}
+    shapes = {"hann": bosing.Hann(), "interp": bosing.Interp([-0.5, -0.5, 0.5, 0.5], [0.0, 1.0], 1)}
+    symmetric = bosing.Play("xy", "hann", 0.5, 40e-9, plateau=20e-9)
+    same = bosing.Play("xy", "hann", 0.5, 40e-9, plateau=20e-9, fall_width=40e-9)
+    asymmetric = bosing.Play("xy", "hann", 0.5, 40e-9, plateau=20e-9, fall_width=20e-9)
+
+    w1 = bosing.generate_waveforms(channels, shapes, bosing.Stack(symmetric, duration=100e-9))
+    w2 = bosing.generate_waveforms(channels, shapes, bosing.Stack(same, duration=100e-9))
+    assert np.allclose(w1["xy"], w2["xy"])
+    assert asymmetric.fall_width == pytest.approx(20e-9)
+    _, busy = bosing.measure_schedule(bosing.Stack(asymmetric))
+    assert busy["xy"][1] == pytest.approx(50e-9)
+    w3 = bosing.generate_waveforms(channels, shapes, bosing.Stack(asymmetric, duration=100e-9))
+    i = w3["xy"][0]
+    nonzero = np.nonzero(i)[0]
+    # 20 ns rise, 20 ns plateau, 10 ns fall
+    assert nonzero[-1] - nonzero[0] == pytest.approx(50, abs=1)
+    bosing.Play("xy", "hann", 0.5, 40e-9, fall_shape_id="interp")
+    with pytest.raises(ValueError):
+        bosing.Play("xy", None, 0.5, 40e-9, fall_width=20e-9)
+
+
+def test_play_total():
+    play = bosing.Play("xy", "hann", 0.5, 40e-9, total=100e-9)
+    assert play.plateau == pytest.approx(60e-9)
+    assert bosing.measure_schedule(play)[0] == pytest.approx(100e-9)
+    asymmetric = bosing.Play("xy", "hann", 0.5, 40e-9, fall_width=20e-9, total=100e-9)
+    assert asymmetric.plateau == pytest.approx(70e-9)
+    with pytest.raises(RuntimeError, match="shorter"):
+        bosing.Play("xy", "hann", 0.5, 40e-9, total=30e-9)
+    with pytest.raises(ValueError):
+        bosing.Play("xy", "hann", 0.5, 40e-9, plateau=10e-9, total=100e-9)
+    with pytest.raises(ValueError):
+        bosing.Play("xy", "hann", 0.5, bosing.Param("w"), total=100e-9)
+
+
+def test_arrange():
+    p0 = bosing.Play("xy0", "hann", 0.5, 50e-9)
+    p1 = bosing.Play("xy1", "hann", 0.5, 100e-9)
+    inner = bosing.Stack(p1)
+    grid = bosing.Grid(p0, (inner, 1), columns=["auto", "auto"])
+    schedule = bosing.Stack(bosing.Repeat(grid, 2), direction="forward")
+
+    assert grid.child_time_ranges is None
+    schedule.arrange(time=10e-9)
+
+    assert schedule.child_time_ranges == pytest.approx([(10e-9, 310e-9)])
+    assert grid.child_time_ranges == pytest.approx([(10e-9, 60e-9), (60e-9, 160e-9)])
+    assert inner.child_time_ranges == pytest.approx([(60e-9, 160e-9)])
+    assert p0.child_time_ranges is None
+
+
+def test_centered_play():
+    play = bosing.Play("xy", "hann", 0.5, 40e-9, centered=True, duration=100e-9, margin=(10e-9, 0))
+    _, spans = bosing.measure_schedule(play)
+    assert play.centered
+    assert spans["xy"] == pytest.approx((40e-9, 80e-9))
+
+
+def test_overlay():
+    x0 = bosing.Play("xy0", "hann", 0.5, 10e-9)
+    x1 = bosing.Play("xy1", "hann", 0.5, 30e-9)
+    flux = bosing.Play("z0", None, 0.1, 5e-9, alignment="stretch")
+    overlay = bosing.Overlay(x0, x1, flux)
+
+    assert overlay.children == [x0, x1, flux]
+    assert bosing.measure_schedule(overlay)[0] == pytest.approx(30e-9)
+    assert bosing.Overlay.from_json(overlay.to_json()).children[1].width == pytest.approx(
+        30e-9
+    )
+    overlay.arrange(duration=50e-9)
+    assert overlay.child_time_ranges == pytest.approx(
+        [(0, 10e-9), (0, 30e-9), (0, 50e-9)]
+    )
+
+
+def test_reverse():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    block = bosing.Stack(
+        bosing.Play("xy", "hann", 0.3, 50e-9, plateau=20e-9),
+        bosing.Play("xy", "hann", 0.2, 30e-9, margin=(10e-9, 0)),
+        direction="forward",
+    )
+    reversed_ = bosing.Stack(bosing.Reverse(block), duration=500e-9)
+    expected = bosing.Stack(
+        bosing.Stack(
+            bosing.Play("xy", "hann", 0.2, 30e-9, margin=(0, 10e-9)),
+            bosing.Play("xy", "hann", 0.3, 50e-9, plateau=20e-9),
+            direction="forward",
+        ),
+        duration=500e-9,
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, reversed_)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, expected)["xy"]
+    assert np.allclose(w1, w2)
+    assert bosing.Reverse(block).child is block
+
+
+def test_result_store(tmp_path):
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000, metadata={"slot": 3})}
+    shapes = {"hann": bosing.Hann()}
+    schedule = bosing.Stack(bosing.Play("xy", "hann", 0.3, 50e-9), duration=500e-9)
+    waveforms = bosing.generate_waveforms(channels, shapes, schedule)
+    store = bosing.ResultStore(tmp_path)
+
+    first = store.save(waveforms, schedule=schedule, channels=channels, metadata={"qubit": 1})
+    store.save(waveforms, name="rabi")
+    with pytest.raises(FileExistsError):
+        store.save(waveforms, name="rabi")
+
+    assert store.names() == ["000000", "rabi"]
+    assert np.array_equal(np.load(os.path.join(first, "waveforms", "xy.npy")), waveforms["xy"])
+    with open(os.path.join(first, "meta.json")) as f:
+        meta = json.load(f)
+    assert meta["metadata"] == {"qubit": 1}
+    assert meta["channels"]["xy"]["length"] == 1000
+    assert meta["channels"]["xy"]["metadata"] == {"slot": 3}
+    assert channels["xy"].metadata == {"slot": 3}
+    assert bosing.Channel(0, 1e9, 10).metadata is None
+    with pytest.raises(ValueError):
+        bosing.Channel(0, 1e9, 10, metadata={"slot": object()})
+    with open(os.path.join(first, "schedule.json")) as f:
+        duration, _ = bosing.measure_schedule(bosing.Element.from_json(f.read()))
+    assert duration == pytest.approx(500e-9)
+
+
+def test_delay():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    shapes = {"hann": bosing.Hann()}
+    x90 = bosing.Play("xy", "hann", 0.3, 50e-9)
+    delayed = bosing.Stack(x90, bosing.Delay(100e-9, "xy"), x90, direction="forward")
+    expected = bosing.Stack(
+        x90, bosing.Play("xy", "hann", 0.0, 100e-9), x90, direction="forward"
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, delayed)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, expected)["xy"]
+    assert np.allclose(w1, w2)
+    assert bosing.measure_schedule(delayed)[0] == pytest.approx(200e-9)
+    assert bosing.Delay(10e-9).channel_ids == []
+
+
+def test_awg_presets():
+    channel = bosing.Channel.for_awg("hdawg", 1000, base_freq=100e6, is_real=True)
+    assert channel.sample_rate == 2.4e9
+    assert channel.length == 1008
+    assert channel.base_freq == 100e6
+    assert channel.is_real
+
+    bosing.register_awg("TestAWG", bosing.AwgPreset(5e9, align_level=0, granularity=32))
+    channel = bosing.Channel.for_awg("testawg", 100)
+    assert channel.length == 128
+    assert channel.align_level == 0
+    assert "TESTAWG" in bosing.awg_presets()
+    with pytest.raises(ValueError):
+        bosing.Channel.for_awg("unknown", 100)
+
+
+def test_gaussian():
+    channels = {"xy": bosing.Channel(0, 1e9, 200, align_level=0)}
+    shapes = {"gauss": bosing.Gaussian(truncation=3.0)}
+    schedule = bosing.Play("xy", "gauss", 1.0, 100e-9)
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"][0]
+    t = np.arange(200) * 1e-9 + 0.5e-9
+    sigma = 100e-9 / 6
+    g = np.exp(-0.5 * ((t - 150e-9) / sigma) ** 2)
+    pedestal = np.exp(-4.5)
+    expected = np.where(np.abs(t - 150e-9) <= 50e-9, (g - pedestal) / (1 - pedestal), 0.0)
+    assert np.max(np.abs(w - expected)) < 0.05
+    assert shapes["gauss"].truncation == 3.0
+    with pytest.raises(ValueError):
+        bosing.Gaussian(truncation=0.0)
+
+
+def test_cosine_sum():
+    channels = {"xy": bosing.Channel(0, 1e9, 100, align_level=0)}
+    shapes = {
+        "blackman": bosing.CosineSum.blackman(),
+        "custom": bosing.CosineSum(0.42, 0.5, a2=0.08),
+    }
+    schedule = bosing.Stack(
+        bosing.Play("xy", "blackman", 1.0, 50e-9),
+        bosing.Play("xy", "custom", 1.0, 50e-9),
+    )
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"][0]
+    x = (np.arange(50) + 0.5) / 50 - 0.5
+    expected = 0.42 + 0.5 * np.cos(2 * np.pi * x) + 0.08 * np.cos(4 * np.pi * x)
+    assert np.max(np.abs(w[:50] - expected)) < 0.05
+    assert np.max(np.abs(w[50:] - expected)) < 0.05
+    assert shapes["custom"].coefficients == (0.42, 0.5, 0.08, 0.0)
+    assert bosing.CosineSum.hamming().coefficients[0] == pytest.approx(0.54, abs=1e-3)
+    assert sum(bosing.CosineSum.nuttall().coefficients) == pytest.approx(1.0)
+    with pytest.raises(ValueError):
+        bosing.CosineSum(float("nan"), 0.5)
+
+
+def test_envelope_precision():
+    channels = {"xy": bosing.Channel(30e6, 2e9, 1000)}
+    x = np.linspace(-0.5, 0.5, 201)
+    shapes = {"spline": bosing.Spline(x, np.cos(np.pi * x) ** 2)}
+    schedule = bosing.Play("xy", "spline", 0.8, 400e-9, drag_coef=1e-10)
+    reference = bosing.generate_waveforms(channels, shapes, schedule)["xy"]
+    single = bosing.generate_waveforms(
+        channels, shapes, schedule, envelope_precision="f32"
+    )["xy"]
+    error = np.max(np.abs(single - reference))
+    assert 0 < error < 2**-24 * (0.8 + 1e-10 * 2e9)
+    with pytest.raises(ValueError):
+        bosing.generate_waveforms(channels, shapes, schedule, envelope_precision="f16")
+
+
+def test_erf_square():
+    channels = {"z": bosing.Channel(0, 1e9, 200, align_level=0, is_real=True)}
+    shapes = {"erf": bosing.ErfSquare(truncation=2.0)}
+    schedule = bosing.Play("z", "erf", 1.0, 40e-9, plateau=100e-9)
+    w = bosing.generate_waveforms(channels, shapes, schedule)["z"][0]
+    assert np.all(w[25:115] > 0.99)
+    assert w[10] == pytest.approx(0.5)
+    assert w[130] == pytest.approx(0.5)
+    assert w[0] == pytest.approx(0.0)
+    assert np.all(w[141:] == 0.0)
+    assert shapes["erf"].truncation == 2.0
+    with pytest.raises(ValueError):
+        bosing.ErfSquare(truncation=-1.0)
+
+
+def test_shape_combinators():
+    channels = {"xy": bosing.Channel(0, 1e9, 100, align_level=0, is_real=True)}
+    x = np.linspace(-0.5, 0.5, 101)
+    ramp = bosing.Spline(x, 0.5 + x)
+    shapes = {
+        "product": bosing.Hann() * ramp,
+        "sum": bosing.ShapeSum(bosing.Hann(), ramp),
+        "hann": bosing.Hann(),
+    }
+    schedule = bosing.Stack(
+        bosing.Play("xy", "product", 1.0, 40e-9),
+        bosing.Play("xy", "sum", 0.5, 40e-9),
+        bosing.Play("xy", "hann", 1.0, 20e-9),
+        direction="forward",
+    )
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"][0]
+    t = np.arange(40) / 40 - 0.5
+    hann = 0.5 * (1 + np.cos(2 * np.pi * t))
+    np.testing.assert_allclose(w[:40], hann * (0.5 + t), atol=1e-3)
+    np.testing.assert_allclose(w[40:80], 0.5 * (hann + 0.5 + t), atol=1e-3)
+    assert isinstance(shapes["product"], bosing.ShapeProduct)
+    assert shapes["product"].b is ramp
+    with pytest.raises(TypeError):
+        bosing.Hann() * 2.0
+
+
+def test_custom_shape():
+    channels = {"xy": bosing.Channel(0, 1e9, 100, align_level=0, is_real=True)}
+    calls = []
+
+    def triangle(x):
+        calls.append(x)
+        return 1 - 2 * abs(x)
+
+    shapes = {
+        "scalar": bosing.CustomShape(triangle, cache=False),
+        "vector": bosing.CustomShape(lambda x: np.cos(np.pi * x), vectorized=True),
+    }
+    schedule = bosing.Stack(
+        bosing.Play("xy", "scalar", 1.0, 40e-9),
+        bosing.Play("xy", "vector", 1.0, 40e-9),
+        direction="forward",
+    )
+    w = bosing.generate_waveforms(channels, shapes, schedule)["xy"][0]
+    t = np.arange(40) / 40 - 0.5
+    np.testing.assert_allclose(w[:40], 1 - 2 * np.abs(t), atol=1e-12)
+    np.testing.assert_allclose(w[40:80], np.cos(np.pi * t), atol=1e-12)
+    assert len(calls) == 40
+    bosing.generate_waveforms(channels, shapes, schedule)
+    assert len(calls) == 80
+    assert shapes["vector"].vectorized
+    assert not shapes["scalar"].cache
+
+    def fail(x):
+        raise RuntimeError("boom")
+
+    shapes["scalar"] = bosing.CustomShape(fail)
+    with pytest.raises(bosing.SamplingError) as info:
+        bosing.generate_waveforms(channels, shapes, schedule)
+    assert info.value.code == "shape_failed"
+    assert isinstance(info.value.__cause__, RuntimeError)
+    with pytest.raises(TypeError):
+        bosing.CustomShape(1.0)
+
+def test_iq_imbalance_sweep():
+    channel = bosing.Channel(0, 1e9, 100, iq_matrix=[[1.0, 0.0], [0.0, 2.0]])
+    params, waveforms = bosing.iq_imbalance_sweep(
+        channel, [10e6, -20e6], gains=[1.0, 0.5], phases=[0.0, 0.25, 0.5]
+    )
+    assert params.shape == (6, 2)
+    assert waveforms.shape == (6, 2, 100)
+    np.testing.assert_allclose(params[4], [0.5, 0.25])
+    t = np.arange(100) * 1e-9
+    z = 0.4 * (np.exp(2j * np.pi * 10e6 * t) + np.exp(-2j * np.pi * 20e6 * t))
+    np.testing.assert_allclose(waveforms[0, 0], z.real, atol=1e-9)
+    np.testing.assert_allclose(waveforms[0, 1], 2 * z.imag, atol=1e-9)
+    np.testing.assert_allclose(waveforms[4, 1], 0.5 * z.real, atol=1e-9)
+    with pytest.raises(ValueError):
+        bosing.iq_imbalance_sweep(bosing.Channel(0, 1e9, 100, is_real=True), [1e6], [1.0], [0.0])
+
+
+def test_phase_ramp():
+    channels = {"xy": bosing.Channel(0, 1e9, 400)}
+    shapes = {"hann": bosing.Hann()}
+    x90 = bosing.Play("xy", "hann", 0.3, 50e-9)
+    ramp = bosing.PhaseRamp("xy", 2.5e6, duration=100e-9)
+    assert ramp.rate == 2.5e6
+    ramped = bosing.Stack(x90, ramp, x90, direction="forward")
+    shifted = bosing.Stack(
+        x90,
+        bosing.Delay(100e-9, "xy"),
+        bosing.ShiftPhase("xy", 0.25),
+        x90,
+        direction="forward",
+    )
+    w1 = bosing.generate_waveforms(channels, shapes, ramped)["xy"]
+    w2 = bosing.generate_waveforms(channels, shapes, shifted)["xy"]
+    assert np.allclose(w1, w2)
+    events = bosing.compile_frames(channels, shapes, ramped)["xy"].events
+    assert events[1][0] == "phase_ramp"
+    assert events[1][1:] == pytest.approx((50e-9, 100e-9, 2.5e6))
+    assert bosing.Element.from_json(ramped.to_json()).children[1].rate == 2.5e6